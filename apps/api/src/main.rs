@@ -1,15 +1,23 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::future::IntoFuture;
+use std::time::Duration;
 use dotenv::dotenv;
 use tokio::net::TcpListener;
-use tower_http::cors::{CorsLayer, Any};
+use tokio::sync::Notify;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tower_http::trace::{self, TraceLayer};
-use tracing::{Level, info};
+use tracing::{Level, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use axum::middleware;
 
 mod router;
 
 use shared_config::AppConfig;
+use shared_utils::request_id::request_id_middleware;
 
 #[tokio::main]
 async fn main() {
@@ -30,16 +38,60 @@ async fn main() {
     let config = AppConfig::from_env();
     
     // Set up CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let cors = build_cors_layer(&config);
+    let compression = config.response_compression_enabled.then(build_compression_layer);
     
+    let shutdown_timeout = Duration::from_secs(config.graceful_shutdown_timeout_seconds);
+
+    // Refuse to start with booking rules that contradict each other
+    if let Err(e) = appointment_cell::models::AppointmentValidationRules::from_config(&config) {
+        panic!("Invalid appointment rule configuration: {}", e);
+    }
+
+    if config.supabase_jwks_url.is_empty() {
+        // HS256 is the only way in, so refuse to start with a JWT secret that
+        // can't validate tokens rather than rejecting every request with a 401
+        match shared_utils::jwt::check_jwt_secret(&config.supabase_jwt_secret, &config.supabase_anon_key) {
+            Ok(check) => info!(
+                "JWT secret check passed (anon key verified: {})",
+                check.anon_key_verified
+            ),
+            Err(e) => panic!("Unusable SUPABASE_JWT_SECRET: {}", e),
+        }
+    } else {
+        // Tokens are checked against the JWKS; keys are fetched again on
+        // demand, so an outage at startup is only worth a warning
+        let jwks = shared_utils::jwks::JwksCache::shared(
+            &config.supabase_jwks_url,
+            Duration::from_secs(config.jwks_cache_ttl_seconds),
+        );
+        match jwks.check_reachable().await {
+            Ok(keys) => info!("JWKS reachable ({} signing keys)", keys),
+            Err(e) => warn!("JWKS at {} is not reachable: {}", config.supabase_jwks_url, e),
+        }
+    }
+
+    // Forward appointment events to registered webhooks
+    appointment_cell::services::webhooks::spawn_delivery_worker(&config);
+
+    // Keep the clinic KPIs current as appointments change
+    appointment_cell::services::business_metrics::spawn_metrics_worker();
+
+    // End video sessions whose appointments were cancelled or completed
+    video_conferencing_cell::services::session::spawn_reconciliation_worker(&config);
+
+    // Release slot holds that lapsed without being confirmed
+    appointment_cell::services::holds::spawn_expiry_worker();
+
     // Create shared state
     let state = Arc::new(config);
     
     // Build the application router
-    let app = router::create_router(state)
+    let mut app = router::create_router(state);
+    if let Some(compression) = compression {
+        app = app.layer(compression);
+    }
+    let app = app
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new()
@@ -47,6 +99,8 @@ async fn main() {
                 .on_response(trace::DefaultOnResponse::new()
                     .level(Level::INFO)),
         )
+        // Outside the trace layer so its span is the parent of the request logs
+        .layer(middleware::from_fn(request_id_middleware))
         .layer(cors);
     
     // Run the server
@@ -54,7 +108,111 @@ async fn main() {
     info!("Listening on {}", addr);
 
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app)
-        .await
-        .unwrap();
+
+    // Stop accepting connections on shutdown and let in-flight requests drain
+    let shutdown = Arc::new(Notify::new());
+    let server_shutdown = shutdown.clone();
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move { server_shutdown.notified().await })
+        .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result.unwrap(),
+        _ = shutdown_signal() => {
+            info!(
+                "Shutdown signal received, draining in-flight requests (up to {}s)",
+                shutdown_timeout.as_secs()
+            );
+            shutdown.notify_one();
+            // Live feeds never finish on their own, so end them for the drain to complete
+            appointment_cell::services::live::close_live_feeds();
+
+            match tokio::time::timeout(shutdown_timeout, server).await {
+                Ok(result) => result.unwrap(),
+                Err(_) => warn!("Graceful shutdown timed out with requests still in flight"),
+            }
+        }
+    }
+
+    info!("Server stopped");
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM (sent by container orchestrators)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
+
+/// gzip or brotli, whichever the client's Accept-Encoding prefers. Archives
+/// and PDFs are already compressed, and responses that carry their own
+/// Content-Encoding are passed through untouched.
+fn build_compression_layer() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new("application/zip"))
+        .and(NotForContentType::const_new("application/pdf"));
+
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(predicate)
+}
+
+/// Explicit origins from CORS_ALLOWED_ORIGINS with credentials allowed, or a
+/// fully permissive (credential-less) layer when CORS_ALLOW_ALL=true for local dev
+fn build_cors_layer(config: &AppConfig) -> CorsLayer {
+    if config.cors_allow_all {
+        warn!("CORS_ALLOW_ALL is enabled - any origin may call the API");
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+    }
+
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                warn!("Ignoring invalid CORS origin: {}", origin);
+                None
+            }
+        })
+        .collect();
+
+    let request_id = HeaderName::from_static("x-request-id");
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins.clone()))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, header::ACCEPT, request_id.clone()])
+        .expose_headers([request_id])
+        .allow_credentials(!origins.is_empty())
 }
@@ -1,24 +1,45 @@
 use std::sync::Arc;
 
 use axum::{
-    Router,
+    extract::State,
     routing::get,
+    Json, Router,
 };
+use serde_json::{json, Value};
 
 use auth_cell::router::auth_routes;
 use health_profile_cell::router::health_profile_routes;
 use doctor_cell::router::doctor_routes;
-use appointment_cell::router::appointment_routes;
+use appointment_cell::router::{appointment_routes, monitoring_routes, patient_timeline_routes};
+use patient_cell::router::patient_routes;
 use video_conferencing_cell::router::video_conferencing_routes;
 use shared_config::AppConfig;
 
 pub fn create_router(state: Arc<AppConfig>) -> Router {
     Router::new()
         .route("/", get(|| async { "Amae Clinic API is running!" }))
+        .route("/version", get(version).with_state(state.clone()))
         .nest("/auth", auth_routes(state.clone()))
         .nest("/health", health_profile_routes(state.clone()))
         .nest("/doctors", doctor_routes(state.clone()))
         .nest("/appointments", appointment_routes(state.clone()))
+        .nest("/patients", patient_routes(state.clone()).merge(patient_timeline_routes(state.clone())))
         .nest("/video", video_conferencing_routes(state.clone()))
+        .nest("/monitoring", monitoring_routes(state.clone()))
         // Other cells added later
-}
\ No newline at end of file
+}
+
+/// Which build is running and which optional integrations it has credentials
+/// for. Public, so it can be checked without a token during an incident.
+async fn version(State(config): State<Arc<AppConfig>>) -> Json<Value> {
+    Json(json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": env!("AMAE_GIT_SHA"),
+        "built_at": env!("AMAE_BUILD_TIMESTAMP"),
+        "integrations": {
+            "supabase": config.is_configured(),
+            "video_conferencing": config.is_video_conferencing_configured(),
+        }
+    }))
+}
@@ -1,27 +1,65 @@
 // libs/appointment-cell/src/handlers.rs
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 
 use axum::{
+    body::Body,
     extract::{Path, Query, State, Extension},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
 use axum_extra::TypedHeader;
+use futures::StreamExt;
 use headers::{Authorization, authorization::Bearer};
 use serde_json::{json, Value};
 use serde::Deserialize;
-use chrono::{DateTime, Utc};
+use tokio::sync::Mutex as AsyncMutex;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use tracing::{field::{self, Empty}, instrument, warn, Span};
 use uuid::Uuid;
 
-use shared_config::AppConfig;
-use shared_models::auth::User;
+use shared_config::{feature_flags, AppConfig, FlagContext};
+use shared_database::metrics::prometheus_text;
+use shared_database::supabase::SupabaseClient;
+use doctor_cell::models::Specialty;
+use doctor_cell::services::doctor::MAX_UTILIZATION_RANGE_DAYS;
+use health_profile_cell::api::{DocumentService, DocumentUploadError};
+use health_profile_cell::DocumentUpload;
+use shared_models::auth::{JwtClaims, Role, User};
 use shared_models::error::AppError;
+use shared_utils::etag::{etag_for, Conditional};
+use shared_utils::extractor::{roles::Admin, RequireRole};
+use shared_utils::i18n::{Locale, Message};
+use shared_utils::jwks::JwksCache;
+use shared_utils::jwt::{check_jwt_secret, create_token};
 
 use crate::models::{
     BookAppointmentRequest, UpdateAppointmentRequest, RescheduleAppointmentRequest,
     CancelAppointmentRequest, AppointmentSearchQuery, AppointmentStatus, AppointmentType,
-    SmartBookingRequest, AppointmentError
+    SmartBookingRequest, AppointmentError, CreateWebhookSubscriptionRequest,
+    CreateConsultationNoteRequest, IssuePrescriptionRequest, IssueCertificateRequest,
+    AppointmentValidationRules, SetTimingOverrideRequest, CreateIntakeFormRequest,
+    SubmitIntakeRequest, IntakeForm, ConfirmHoldRequest, RecordOutcomeRequest, Appointment
 };
 use crate::services::booking::AppointmentBookingService;
+use crate::services::business_metrics::{business_metrics, specialty_fill_rates};
+use crate::services::certificate::CertificateService;
+use crate::services::export::{export_stream, ExportFormat};
+use crate::services::events;
+use crate::services::holds;
+use crate::services::intake::IntakeService;
+use crate::services::live::{self, ClinicDay, LiveUpdate};
+use crate::services::notes::ConsultationNoteService;
+use crate::services::outcomes::{validate_outcome, OutcomeService};
+use crate::services::prescriptions::PrescriptionService;
+use crate::services::stats_cache::{self, RebuiltStats, StatsScope};
+use crate::services::timeline::PatientTimelineService;
+use crate::services::timing::AppointmentTimingService;
+use crate::services::webhooks::WebhookService;
 
 // ==============================================================================
 // QUERY PARAMETER STRUCTS
@@ -35,10 +73,23 @@ pub struct AppointmentQueryParams {
     pub appointment_type: Option<AppointmentType>,
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
+    /// Free text to look for in the patient's notes, e.g. "chest pain"
+    pub reason_contains: Option<String>,
     pub limit: Option<i32>,
     pub offset: Option<i32>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AppointmentExportQuery {
+    pub format: Option<ExportFormat>,
+    pub patient_id: Option<Uuid>,
+    pub doctor_id: Option<Uuid>,
+    pub status: Option<AppointmentStatus>,
+    pub appointment_type: Option<AppointmentType>,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ConflictCheckQuery {
     pub doctor_id: Uuid,
@@ -47,9 +98,36 @@ pub struct ConflictCheckQuery {
     pub exclude_appointment_id: Option<Uuid>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct NextAvailableQuery {
+    pub specialty: String,
+    /// A general consultation when unset
+    #[serde(rename = "type")]
+    pub appointment_type: Option<AppointmentType>,
+    /// Zone the doctors' schedules are read in; UTC when unset
+    pub timezone: Option<String>,
+    /// Slots to return, the earliest included
+    pub limit: Option<usize>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpcomingAppointmentsQuery {
     pub hours_ahead: Option<i32>,
+    /// Only appointments the patient hasn't confirmed they'll attend, for
+    /// reminder targeting
+    #[serde(default)]
+    pub unconfirmed_only: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntakeFormQuery {
+    pub appointment_type: AppointmentType,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoteSearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,135 +144,162 @@ pub struct StatsQuery {
 
 /// NEW: Smart appointment booking with automatic doctor selection and history prioritization
 #[axum::debug_handler]
+#[instrument(skip_all, fields(patient_id = %request.patient_id, doctor_id = Empty, appointment_id = Empty))]
 pub async fn smart_book_appointment(
     State(state): State<Arc<AppConfig>>,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     Extension(user): Extension<User>,
+    locale: Locale,
     Json(request): Json<SmartBookingRequest>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
     
     // Verify authorization - only patient can book their own appointment or admin can book
     let is_patient = request.patient_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_patient && !is_admin {
         return Err(AppError::Auth("Not authorized to book appointment for this patient".to_string()));
     }
+
+    let flag_context = FlagContext::new(&user.id, user.role.as_deref());
+    if !state.feature_flags.is_enabled(feature_flags::SMART_BOOKING, &flag_context) {
+        return Err(AppError::NotFound("Smart booking is not available".to_string())
+            .with_code("feature.disabled"));
+    }
     
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service_for(&state, &user)?;
     
     let smart_booking_response = booking_service.smart_book_appointment(request, token).await
-        .map_err(|e| match e {
-            AppointmentError::SpecialtyNotAvailable { specialty } => {
-                AppError::NotFound(format!("No {} doctors available at this time", specialty))
-            },
-            AppointmentError::DoctorNotAvailable => {
-                AppError::NotFound("No doctors available at this time".to_string())
-            },
-            AppointmentError::ConflictDetected => {
-                AppError::BadRequest("Appointment slot no longer available".to_string())
-            },
-            _ => AppError::Internal(e.to_string()),
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::SpecialtyNotAvailable { specialty, next_available_dates } => {
+                    AppError::NotFound(format!("No {} doctors available at this time", specialty))
+                        .with_extension("next_available_dates", json!(next_available_dates))
+                },
+                AppointmentError::DoctorNotAvailable => {
+                    AppError::NotFound("No doctors available at this time".to_string())
+                },
+                AppointmentError::ConflictDetected { suggested_alternatives } => {
+                    AppError::BadRequest("Appointment slot no longer available".to_string())
+                        .with_extension("suggested_alternatives", json!(suggested_alternatives))
+                },
+                other => other.into(),
+            }.with_code(code)
         })?;
+    record_appointment_ids(&smart_booking_response.appointment);
     
+    let intake_forms = intake_forms_for(&state, &smart_booking_response.appointment.appointment_type, token).await;
+
     Ok(Json(json!({
         "success": true,
         "smart_booking": smart_booking_response,
+        "intake_forms": intake_forms,
         "message": if smart_booking_response.is_preferred_doctor {
-            "Appointment booked with your preferred doctor based on consultation history"
+            Message::AppointmentBookedWithPreferredDoctor
         } else {
-            "Appointment booked with best available doctor"
-        }
+            Message::AppointmentBookedWithBestAvailableDoctor
+        }.text(locale.0)
     })))
 }
 
 /// Enhanced appointment booking with specialty validation
 #[axum::debug_handler]
+#[instrument(skip_all, fields(patient_id = %request.patient_id, doctor_id = Empty, appointment_id = Empty))]
 pub async fn book_appointment(
     State(state): State<Arc<AppConfig>>,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     Extension(user): Extension<User>,
+    locale: Locale,
     Json(request): Json<BookAppointmentRequest>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
     
     // Verify authorization - only patient can book their own appointment or admin/doctor can book
     let is_patient = request.patient_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
-    let is_doctor = user.role.as_deref() == Some("doctor");
+    let is_admin = user.is_admin();
+    let is_doctor = user.has_role(Role::Doctor);
     
     if !is_patient && !is_admin && !is_doctor {
         return Err(AppError::Auth("Not authorized to book appointment for this patient".to_string()));
     }
     
-    let booking_service = AppointmentBookingService::new(&state);
+    let language = locale.preferring(request.preferred_language.as_deref());
+    let booking_service = booking_service_for(&state, &user)?;
     
     let appointment = booking_service.book_appointment(request, token).await
-        .map_err(|e| match e {
-            AppointmentError::SpecialtyNotAvailable { specialty } => {
-                AppError::NotFound(format!("No {} doctors available at this time", specialty))
-            },
-            AppointmentError::DoctorNotAvailable => {
-                AppError::NotFound("Doctor not available at requested time".to_string())
-            },
-            AppointmentError::ConflictDetected => {
-                AppError::BadRequest("Appointment slot conflicts with existing booking".to_string())
-            },
-            AppointmentError::SlotNotAvailable => {
-                AppError::BadRequest("Appointment slot no longer available".to_string())
-            },
-            AppointmentError::PatientNotFound => {
-                AppError::NotFound("Patient not found".to_string())
-            },
-            AppointmentError::DoctorNotFound => {
-                AppError::NotFound("Doctor not found".to_string())
-            },
-            AppointmentError::InvalidTime(msg) => {
-                AppError::BadRequest(msg)
-            },
-            AppointmentError::ValidationError(msg) => {
-                AppError::BadRequest(msg)
-            },
-            _ => AppError::Internal(e.to_string()),
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::SpecialtyNotAvailable { specialty, next_available_dates } => {
+                    AppError::NotFound(format!("No {} doctors available at this time", specialty))
+                        .with_extension("next_available_dates", json!(next_available_dates))
+                },
+                AppointmentError::DoctorNotAvailable => {
+                    AppError::NotFound("Doctor not available at requested time".to_string())
+                },
+                AppointmentError::ConflictDetected { suggested_alternatives } => {
+                    AppError::BadRequest("Appointment slot conflicts with existing booking".to_string())
+                        .with_extension("suggested_alternatives", json!(suggested_alternatives))
+                },
+                AppointmentError::SlotNotAvailable => {
+                    AppError::BadRequest("Appointment slot no longer available".to_string())
+                },
+                AppointmentError::PatientNotFound => {
+                    AppError::NotFound("Patient not found".to_string())
+                },
+                AppointmentError::DoctorNotFound => {
+                    AppError::NotFound("Doctor not found".to_string())
+                },
+                other => other.into(),
+            }.with_code(code)
         })?;
     
+    record_appointment_ids(&appointment);
+    
+    let intake_forms = intake_forms_for(&state, &appointment.appointment_type, token).await;
+
     Ok(Json(json!({
         "success": true,
         "appointment": appointment,
-        "message": "Appointment booked successfully"
+        "intake_forms": intake_forms,
+        "message": Message::AppointmentBooked.text(language)
     })))
 }
 
 #[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
 pub async fn get_appointment(
     State(state): State<Arc<AppConfig>>,
     Path(appointment_id): Path<Uuid>,
+    headers: HeaderMap,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     Extension(user): Extension<User>,
-) -> Result<Json<Value>, AppError> {
+) -> Result<Conditional<Json<Value>>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
-    let appointment = booking_service.get_appointment(appointment_id, token).await
-        .map_err(|e| match e {
-            AppointmentError::NotFound => AppError::NotFound("Appointment not found".to_string()),
-            _ => AppError::Internal(e.to_string()),
-        })?;
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+    
+    record_appointment_ids(&appointment);
     
     // Verify authorization - only patient, doctor involved, or admin can view
     let is_patient = appointment.patient_id.to_string() == user.id;
     let is_doctor = appointment.doctor_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_patient && !is_doctor && !is_admin {
         return Err(AppError::Auth("Not authorized to view this appointment".to_string()));
     }
     
-    Ok(Json(json!(appointment)))
+    // Polling clients send back the ETag and get a 304 until the appointment changes
+    let etag = etag_for(appointment.id, appointment.updated_at);
+    Ok(Conditional::new(&headers, etag, Json(json!(appointment))))
 }
 
 #[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
 pub async fn update_appointment(
     State(state): State<Arc<AppConfig>>,
     Path(appointment_id): Path<Uuid>,
@@ -203,43 +308,41 @@ pub async fn update_appointment(
     Json(request): Json<UpdateAppointmentRequest>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     // Get appointment to check authorization
-    let appointment = booking_service.get_appointment(appointment_id, token).await
-        .map_err(|e| match e {
-            AppointmentError::NotFound => AppError::NotFound("Appointment not found".to_string()),
-            _ => AppError::Internal(e.to_string()),
-        })?;
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+    record_appointment_ids(&appointment);
     
     // Verify authorization
     let is_patient = appointment.patient_id.to_string() == user.id;
     let is_doctor = appointment.doctor_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_patient && !is_doctor && !is_admin {
         return Err(AppError::Auth("Not authorized to update this appointment".to_string()));
     }
     
     // Patients can only update certain fields
-    if is_patient && !is_admin && !is_doctor {
-        if request.status.is_some() || request.doctor_notes.is_some() {
-            return Err(AppError::Auth("Patients cannot update appointment status or doctor notes".to_string()));
-        }
+    if is_patient && !is_admin && !is_doctor
+        && (request.status.is_some() || request.doctor_notes.is_some())
+    {
+        return Err(AppError::Auth("Patients cannot update appointment status or doctor notes".to_string()));
     }
     
     let updated_appointment = booking_service.update_appointment(appointment_id, request, token).await
-        .map_err(|e| match e {
-            AppointmentError::ConflictDetected => {
-                AppError::BadRequest("Appointment conflicts with existing booking".to_string())
-            },
-            AppointmentError::InvalidStatusTransition(status) => {
-                AppError::BadRequest(format!("Cannot transition from current status: {}", status))
-            },
-            AppointmentError::InvalidTime(msg) => {
-                AppError::BadRequest(msg)
-            },
-            _ => AppError::Internal(e.to_string()),
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::ConflictDetected { suggested_alternatives } => {
+                    AppError::BadRequest("Appointment conflicts with existing booking".to_string())
+                        .with_extension("suggested_alternatives", json!(suggested_alternatives))
+                },
+                AppointmentError::InvalidStatusTransition(status) => {
+                    AppError::BadRequest(format!("Cannot transition from current status: {}", status))
+                },
+                other => other.into(),
+            }.with_code(code)
         })?;
     
     Ok(Json(json!({
@@ -250,92 +353,224 @@ pub async fn update_appointment(
 }
 
 #[axum::debug_handler]
+#[instrument(skip_all, fields(patient_id = %request.patient_id, doctor_id = Empty, hold_id = Empty))]
+pub async fn hold_appointment_slot(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    locale: Locale,
+    Json(request): Json<BookAppointmentRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    // Same rule as booking: the patient themselves, or staff on their behalf
+    let is_patient = request.patient_id.to_string() == user.id;
+    if !is_patient && !user.is_admin() && !user.has_role(Role::Doctor) {
+        return Err(AppError::Auth("Not authorized to book appointment for this patient".to_string()));
+    }
+
+    let language = locale.preferring(request.preferred_language.as_deref());
+    let booking_service = booking_service_for(&state, &user)?;
+    let hold = booking_service.hold_slot(request, &user.id, token).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::SpecialtyNotAvailable { specialty, next_available_dates } => {
+                    AppError::NotFound(format!("No {} doctors available at this time", specialty))
+                        .with_extension("next_available_dates", json!(next_available_dates))
+                },
+                AppointmentError::DoctorNotAvailable => {
+                    AppError::NotFound("Doctor not available at requested time".to_string())
+                },
+                AppointmentError::ConflictDetected { suggested_alternatives } => {
+                    AppError::BadRequest("Appointment slot conflicts with existing booking".to_string())
+                        .with_extension("suggested_alternatives", json!(suggested_alternatives))
+                },
+                AppointmentError::SlotNotAvailable => {
+                    AppError::BadRequest("Appointment slot is being held by someone else".to_string())
+                },
+                AppointmentError::PatientNotFound => AppError::NotFound("Patient not found".to_string()),
+                AppointmentError::DoctorNotFound => AppError::NotFound("Doctor not found".to_string()),
+                other => other.into(),
+            }.with_code(code)
+        })?;
+    let span = Span::current();
+    span.record("hold_id", field::display(hold.hold_id));
+    span.record("doctor_id", field::display(hold.doctor_id));
+
+    Ok(Json(json!({
+        "success": true,
+        "hold_id": hold.hold_id,
+        "hold": hold,
+        "message": Message::SlotHeld.text(language)
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(hold_id = %hold_id, patient_id = Empty, doctor_id = Empty, appointment_id = Empty))]
+pub async fn confirm_appointment_hold(
+    State(state): State<Arc<AppConfig>>,
+    Path(hold_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    locale: Locale,
+    details: Option<Json<ConfirmHoldRequest>>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let details = details.map(|Json(details)| details).unwrap_or_default();
+    let language = locale.preferring(details.preferred_language.as_deref());
+
+    let booking_service = booking_service_for(&state, &user)?;
+    let appointment = booking_service.confirm_hold(hold_id, details, &user, token).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::HoldNotFound => AppError::NotFound("Hold not found or expired".to_string()),
+                AppointmentError::Unauthorized => AppError::Forbidden("This hold belongs to someone else".to_string()),
+                AppointmentError::ConflictDetected { suggested_alternatives } => {
+                    AppError::BadRequest("Appointment slot conflicts with existing booking".to_string())
+                        .with_extension("suggested_alternatives", json!(suggested_alternatives))
+                },
+                other => other.into(),
+            }.with_code(code)
+        })?;
+    record_appointment_ids(&appointment);
+
+    let intake_forms = intake_forms_for(&state, &appointment.appointment_type, token).await;
+
+    Ok(Json(json!({
+        "success": true,
+        "appointment": appointment,
+        "intake_forms": intake_forms,
+        "message": Message::AppointmentBooked.text(language)
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
 pub async fn reschedule_appointment(
     State(state): State<Arc<AppConfig>>,
     Path(appointment_id): Path<Uuid>,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     Extension(user): Extension<User>,
+    locale: Locale,
     Json(request): Json<RescheduleAppointmentRequest>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     // Get appointment to check authorization
-    let appointment = booking_service.get_appointment(appointment_id, token).await
-        .map_err(|e| match e {
-            AppointmentError::NotFound => AppError::NotFound("Appointment not found".to_string()),
-            _ => AppError::Internal(e.to_string()),
-        })?;
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+    record_appointment_ids(&appointment);
     
     // Verify authorization - patient or doctor can reschedule
     let is_patient = appointment.patient_id.to_string() == user.id;
     let is_doctor = appointment.doctor_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_patient && !is_doctor && !is_admin {
         return Err(AppError::Auth("Not authorized to reschedule this appointment".to_string()));
     }
     
-    let rescheduled_appointment = booking_service.reschedule_appointment(appointment_id, request, token).await
-        .map_err(|e| match e {
-            AppointmentError::ConflictDetected => {
-                AppError::BadRequest("New appointment time conflicts with existing booking".to_string())
-            },
-            AppointmentError::InvalidTime(msg) => {
-                AppError::BadRequest(msg)
-            },
-            _ => AppError::Internal(e.to_string()),
+    let rescheduled_appointment = booking_service.reschedule_appointment(appointment_id, request, &user, token).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::Unauthorized => {
+                    AppError::Forbidden("Only doctors and admins can override the reschedule notice period".to_string())
+                },
+                AppointmentError::ConflictDetected { suggested_alternatives } => {
+                    AppError::BadRequest("New appointment time conflicts with existing booking".to_string())
+                        .with_extension("suggested_alternatives", json!(suggested_alternatives))
+                },
+                other => other.into(),
+            }.with_code(code)
         })?;
     
     Ok(Json(json!({
         "success": true,
         "appointment": rescheduled_appointment,
-        "message": "Appointment rescheduled successfully"
+        "message": Message::AppointmentRescheduled.text(locale.0)
     })))
 }
 
 #[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
 pub async fn cancel_appointment(
     State(state): State<Arc<AppConfig>>,
     Path(appointment_id): Path<Uuid>,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     Extension(user): Extension<User>,
+    locale: Locale,
     Json(request): Json<CancelAppointmentRequest>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     // Get appointment to check authorization
-    let appointment = booking_service.get_appointment(appointment_id, token).await
-        .map_err(|e| match e {
-            AppointmentError::NotFound => AppError::NotFound("Appointment not found".to_string()),
-            _ => AppError::Internal(e.to_string()),
-        })?;
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+    record_appointment_ids(&appointment);
     
     // Verify authorization - patient or doctor can cancel
     let is_patient = appointment.patient_id.to_string() == user.id;
     let is_doctor = appointment.doctor_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_patient && !is_doctor && !is_admin {
         return Err(AppError::Auth("Not authorized to cancel this appointment".to_string()));
     }
     
-    let cancelled_appointment = booking_service.cancel_appointment(appointment_id, request, token).await
-        .map_err(|e| match e {
-            AppointmentError::InvalidStatusTransition(status) => {
-                AppError::BadRequest(format!("Cannot cancel appointment in status: {}", status))
-            },
-            AppointmentError::InvalidTime(msg) => {
-                AppError::BadRequest(msg)
-            },
-            _ => AppError::Internal(e.to_string()),
+    let outcome = booking_service.cancel_appointment(appointment_id, request, token).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::InvalidStatusTransition(status) => {
+                    AppError::BadRequest(format!("Cannot cancel appointment in status: {}", status))
+                },
+                other => other.into(),
+            }.with_code(code)
         })?;
     
     Ok(Json(json!({
         "success": true,
-        "appointment": cancelled_appointment,
-        "message": "Appointment cancelled successfully"
+        "appointment": outcome.appointment,
+        "cancellation_fee_applicable": outcome.cancellation_fee_applicable,
+        "message": Message::AppointmentCancelled.text(locale.0)
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn confirm_attendance(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    locale: Locale,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.confirm_attendance(appointment_id, &user, token).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::Unauthorized => {
+                    AppError::Forbidden("Only the patient can confirm attendance".to_string())
+                },
+                AppointmentError::InvalidStatusTransition(status) => {
+                    AppError::BadRequest(format!("Cannot confirm attendance for appointment in status: {}", status))
+                },
+                other => other.into(),
+            }.with_code(code)
+        })?;
+    record_appointment_ids(&appointment);
+
+    Ok(Json(json!({
+        "success": true,
+        "appointment": appointment,
+        "attendance_confirmed_at": appointment.attendance_confirmed_at,
+        "message": Message::AttendanceConfirmed.text(locale.0)
     })))
 }
 
@@ -351,7 +586,7 @@ pub async fn search_appointments(
     Extension(user): Extension<User>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     // Build search query
     let mut search_query = AppointmentSearchQuery {
@@ -361,41 +596,70 @@ pub async fn search_appointments(
         appointment_type: params.appointment_type,
         from_date: params.from_date,
         to_date: params.to_date,
+        reason_contains: params.reason_contains,
         limit: params.limit,
         offset: params.offset,
     };
     
-    // Apply authorization filters
-    let is_admin = user.role.as_deref() == Some("admin");
-    if !is_admin {
-        // Non-admins can only see their own appointments
-        match user.role.as_deref() {
-            Some("doctor") => {
-                // Doctors can only see their appointments
-                if let Ok(doctor_uuid) = Uuid::parse_str(&user.id) {
-                    search_query.doctor_id = Some(doctor_uuid);
-                }
-            },
-            _ => {
-                // Patients can only see their appointments
-                if let Ok(patient_uuid) = Uuid::parse_str(&user.id) {
-                    search_query.patient_id = Some(patient_uuid);
-                }
-            }
-        }
-    }
+    scope_search_to_user(&mut search_query, &user);
     
-    let appointments = booking_service.search_appointments(search_query, token).await
-        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let page = booking_service.search_appointments_page(search_query, token).await?;
     
     Ok(Json(json!({
-        "appointments": appointments,
-        "total": appointments.len(),
-        "limit": params.limit,
-        "offset": params.offset
+        "appointments": page.appointments,
+        "total": page.total,
+        "limit": page.limit,
+        "offset": page.offset,
+        "has_more": page.has_more
     })))
 }
 
+#[axum::debug_handler]
+pub async fn export_appointments(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<AppointmentExportQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    // Exports span every patient, so they're limited to clinic administrators
+    admin: RequireRole<Admin>,
+) -> Result<Response, AppError> {
+
+    let format = params.format.unwrap_or(ExportFormat::Csv);
+    let mut search_query = AppointmentSearchQuery {
+        patient_id: params.patient_id,
+        doctor_id: params.doctor_id,
+        status: params.status,
+        appointment_type: params.appointment_type,
+        from_date: params.from_date,
+        to_date: params.to_date,
+        reason_contains: None,
+        limit: None,
+        offset: None,
+    };
+    scope_search_to_user(&mut search_query, &admin);
+
+    let booking_service = booking_service(&state)?;
+    let body = Body::from_stream(export_stream(
+        booking_service,
+        search_query,
+        auth.token().to_string(),
+        format,
+    ));
+
+    let disposition = format!(
+        "attachment; filename=\"appointments-{}.{}\"",
+        Utc::now().format("%Y%m%d%H%M%S"),
+        format.file_extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ).into_response())
+}
+
 #[axum::debug_handler]
 pub async fn get_upcoming_appointments(
     State(state): State<Arc<AppConfig>>,
@@ -404,16 +668,16 @@ pub async fn get_upcoming_appointments(
     Extension(user): Extension<User>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     // Determine filters based on user role
-    let (patient_id, doctor_id) = match user.role.as_deref() {
-        Some("doctor") => {
+    let (patient_id, doctor_id) = match user.app_role() {
+        Some(Role::Doctor) => {
             let doctor_uuid = Uuid::parse_str(&user.id)
                 .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?;
             (None, Some(doctor_uuid))
         },
-        Some("admin") => (None, None), // Admins can see all
+        Some(Role::Admin) => (None, None), // Admins can see all
         _ => {
             let patient_uuid = Uuid::parse_str(&user.id)
                 .map_err(|_| AppError::BadRequest("Invalid patient ID".to_string()))?;
@@ -421,8 +685,11 @@ pub async fn get_upcoming_appointments(
         }
     };
     
-    let appointments = booking_service.get_upcoming_appointments(patient_id, doctor_id, token).await
+    let mut appointments = booking_service.get_upcoming_appointments(patient_id, doctor_id, token).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+    if params.unconfirmed_only {
+        appointments.retain(|appointment| appointment.attendance_confirmed_at.is_none());
+    }
     
     Ok(Json(json!({
         "upcoming_appointments": appointments,
@@ -432,6 +699,7 @@ pub async fn get_upcoming_appointments(
 }
 
 #[axum::debug_handler]
+#[instrument(skip_all, fields(patient_id = %patient_id))]
 pub async fn get_patient_appointments(
     State(state): State<Arc<AppConfig>>,
     Path(patient_id): Path<Uuid>,
@@ -443,13 +711,13 @@ pub async fn get_patient_appointments(
     
     // Verify authorization - only the patient themselves or admin can view
     let is_own_appointments = patient_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_own_appointments && !is_admin {
         return Err(AppError::Auth("Not authorized to view appointments for this patient".to_string()));
     }
     
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     let search_query = AppointmentSearchQuery {
         patient_id: Some(patient_id),
@@ -458,21 +726,56 @@ pub async fn get_patient_appointments(
         appointment_type: params.appointment_type,
         from_date: params.from_date,
         to_date: params.to_date,
+        reason_contains: params.reason_contains,
         limit: params.limit,
         offset: params.offset,
     };
     
-    let appointments = booking_service.search_appointments(search_query, token).await
-        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let page = booking_service.search_appointments_page(search_query, token).await?;
     
     Ok(Json(json!({
         "patient_id": patient_id,
-        "appointments": appointments,
-        "total": appointments.len()
+        "appointments": page.appointments,
+        "total": page.total,
+        "limit": page.limit,
+        "offset": page.offset,
+        "has_more": page.has_more
     })))
 }
 
+/// A patient's history in date order for a clinician preparing a
+/// consultation. Admins and doctors with a care relationship only.
+#[axum::debug_handler]
+#[instrument(skip_all, fields(patient_id = %patient_id))]
+pub async fn get_patient_timeline(
+    State(state): State<Arc<AppConfig>>,
+    Path(patient_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    if !user.is_admin() && !user.has_role(Role::Doctor) {
+        return Err(AppError::Auth("Only doctors and administrators can view a patient's timeline".to_string()));
+    }
+
+    let timeline_service = PatientTimelineService::new(&state, booking_service(&state)?);
+    let timeline = timeline_service.build(patient_id, &user, token).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::Unauthorized => AppError::Forbidden(
+                    "Not authorized to view this patient's timeline".to_string()
+                ),
+                other => other.into(),
+            }.with_code(code)
+        })?;
+
+    Ok(Json(json!(timeline)))
+}
+
 #[axum::debug_handler]
+#[instrument(skip_all, fields(doctor_id = %doctor_id))]
 pub async fn get_doctor_appointments(
     State(state): State<Arc<AppConfig>>,
     Path(doctor_id): Path<Uuid>,
@@ -484,13 +787,13 @@ pub async fn get_doctor_appointments(
     
     // Verify authorization - only the doctor themselves or admin can view
     let is_own_appointments = doctor_id.to_string() == user.id;
-    let is_admin = user.role.as_deref() == Some("admin");
+    let is_admin = user.is_admin();
     
     if !is_own_appointments && !is_admin {
         return Err(AppError::Auth("Not authorized to view appointments for this doctor".to_string()));
     }
     
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
     
     let search_query = AppointmentSearchQuery {
         patient_id: params.patient_id,
@@ -499,20 +802,72 @@ pub async fn get_doctor_appointments(
         appointment_type: params.appointment_type,
         from_date: params.from_date,
         to_date: params.to_date,
+        reason_contains: params.reason_contains,
         limit: params.limit,
         offset: params.offset,
     };
     
-    let appointments = booking_service.search_appointments(search_query, token).await
-        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let page = booking_service.search_appointments_page(search_query, token).await?;
     
     Ok(Json(json!({
         "doctor_id": doctor_id,
-        "appointments": appointments,
-        "total": appointments.len()
+        "appointments": page.appointments,
+        "total": page.total,
+        "limit": page.limit,
+        "offset": page.offset,
+        "has_more": page.has_more
     })))
 }
 
+/// Live view of a doctor's day as server-sent events: a `snapshot` of the
+/// day's appointments, then each appointment event (`booked`, `cancelled`,
+/// `started`, ...) as it happens. Only the doctor or an admin may subscribe.
+/// This is an SSE stream (`text/event-stream`), not a websocket; browsers
+/// follow it with `EventSource`. The stream closes when the subscriber's
+/// bearer token expires or the server shuts down, and the client reconnects
+/// with a fresh token. Only events raised on the instance serving the feed
+/// are sent; see `live::doctor_day_feed`.
+#[axum::debug_handler]
+#[instrument(skip_all, fields(doctor_id = %doctor_id))]
+pub async fn doctor_live_feed(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Response, AppError> {
+    let is_own_feed = doctor_id.to_string() == user.id;
+    if !is_own_feed && !user.is_admin() {
+        return Err(AppError::Auth("Not authorized to follow this doctor's appointments".to_string()));
+    }
+
+    let booking_service = booking_service(&state)?;
+    let timezone = state.appointment_rules.business_timezone.clone();
+    let day = ClinicDay::containing(Utc::now(), timezone.as_deref());
+
+    let events = events::subscribe();
+    let snapshot = live::doctor_day(&booking_service, doctor_id, &day, auth.token()).await?;
+
+    let feed = live::doctor_day_feed(
+        booking_service,
+        doctor_id,
+        timezone,
+        auth.token().to_string(),
+        day,
+        events,
+        snapshot,
+    ).map(|update| match update {
+        LiveUpdate::Snapshot(appointments) => Event::default()
+            .event("snapshot")
+            .json_data(json!({ "appointments": appointments })),
+        LiveUpdate::Event(envelope) => Event::default()
+            .event(envelope.event.to_string())
+            .id(envelope.id.to_string())
+            .json_data(&envelope),
+    });
+
+    Ok(Sse::new(feed).keep_alive(KeepAlive::default()).into_response())
+}
+
 // ==============================================================================
 // CONFLICT DETECTION AND UTILITY HANDLERS
 // ==============================================================================
@@ -525,7 +880,7 @@ pub async fn check_appointment_conflicts(
     Extension(_user): Extension<User>,
 ) -> Result<Json<Value>, AppError> {
     let token = auth.token();
-    let booking_service = AppointmentBookingService::new(&state);
+    let booking_service = booking_service(&state)?;
 
     let conflict_response = booking_service
         .check_conflicts(
@@ -541,6 +896,43 @@ pub async fn check_appointment_conflicts(
     Ok(Json(json!(conflict_response)))
 }
 
+const DEFAULT_NEXT_AVAILABLE_LIMIT: usize = 5;
+const MAX_NEXT_AVAILABLE_LIMIT: usize = 20;
+
+/// The soonest free slot with any doctor of a specialty, plus the next few
+/// options, for patients who want the earliest appointment going
+#[axum::debug_handler]
+#[instrument(skip_all, fields(specialty = %params.specialty))]
+pub async fn get_next_available(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<NextAvailableQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(_user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let limit = params.limit.unwrap_or(DEFAULT_NEXT_AVAILABLE_LIMIT);
+    if !(1..=MAX_NEXT_AVAILABLE_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}", MAX_NEXT_AVAILABLE_LIMIT
+        )));
+    }
+    let appointment_type = params.appointment_type.unwrap_or(AppointmentType::GeneralConsultation);
+    let timezone = params.timezone.as_deref().unwrap_or("UTC");
+
+    let booking_service = booking_service(&state)?;
+    let mut slots = booking_service
+        .find_next_available(&params.specialty, &appointment_type, timezone, limit, token)
+        .await?;
+
+    let earliest = (!slots.is_empty()).then(|| slots.remove(0));
+    Ok(Json(json!({
+        "specialty": params.specialty,
+        "appointment_type": appointment_type,
+        "earliest": earliest,
+        "next_options": slots
+    })))
+}
+
 /// Enhanced appointment statistics with doctor continuity metrics
 #[axum::debug_handler]
 pub async fn get_appointment_stats(
@@ -552,9 +944,9 @@ pub async fn get_appointment_stats(
     let token = auth.token();
     
     // Apply authorization filters
-    let (filtered_patient_id, filtered_doctor_id) = match user.role.as_deref() {
-        Some("admin") => (params.patient_id, params.doctor_id), // Admins can see all stats
-        Some("doctor") => {
+    let (filtered_patient_id, filtered_doctor_id) = match user.app_role() {
+        Some(Role::Admin) => (params.patient_id, params.doctor_id), // Admins can see all stats
+        Some(Role::Doctor) => {
             let doctor_uuid = Uuid::parse_str(&user.id)
                 .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?;
             (params.patient_id, Some(doctor_uuid)) // Doctors can only see their own stats
@@ -566,7 +958,25 @@ pub async fn get_appointment_stats(
         }
     };
     
-    let booking_service = AppointmentBookingService::new(&state);
+    // Serve the last rebuild for this scope while it's fresh
+    let scope = StatsScope {
+        patient_id: filtered_patient_id,
+        doctor_id: filtered_doctor_id,
+        from_date: params.from_date,
+        to_date: params.to_date,
+    };
+    if let Some(rebuilt) = stats_cache_ttl(&state).and_then(|ttl| stats_cache::get(&scope, ttl)) {
+        return Ok(Json(json!({
+            "degraded": false,
+            "degraded_reason": null,
+            "stats": rebuilt.stats,
+            "source": "rebuild",
+            "computed_at": rebuilt.computed_at,
+            "note": "Statistics include doctor continuity rate showing percentage of appointments with previously seen doctors"
+        })));
+    }
+
+    let booking_service = booking_service(&state)?;
     
     let stats = booking_service.get_appointment_stats(
         filtered_patient_id,
@@ -577,7 +987,908 @@ pub async fn get_appointment_stats(
     ).await.map_err(|e| AppError::Internal(e.to_string()))?;
     
     Ok(Json(json!({
+        "degraded": stats.degradation.degraded,
+        "degraded_reason": stats.degradation.degraded_reason,
         "stats": stats,
+        "source": "live",
+        "computed_at": Utc::now(),
         "note": "Statistics include doctor continuity rate showing percentage of appointments with previously seen doctors"
     })))
-}
\ No newline at end of file
+}
+
+fn stats_cache_ttl(state: &AppConfig) -> Option<std::time::Duration> {
+    (state.appointment_stats_cache_ttl_seconds > 0)
+        .then(|| std::time::Duration::from_secs(state.appointment_stats_cache_ttl_seconds))
+}
+
+/// Recompute exact stats for a scope from database counts and cache them,
+/// so `GET /appointments/stats` serves them without recomputing
+#[axum::debug_handler]
+pub async fn rebuild_appointment_stats(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+    Json(params): Json<StatsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let scope = StatsScope {
+        patient_id: params.patient_id,
+        doctor_id: params.doctor_id,
+        from_date: params.from_date,
+        to_date: params.to_date,
+    };
+
+    let booking_service = booking_service(&state)?;
+    let stats = booking_service.rebuild_appointment_stats(&scope, auth.token()).await
+        .map_err(|e| {
+            let code = e.code();
+            AppError::Internal(e.to_string()).with_code(code)
+        })?;
+
+    let rebuilt = RebuiltStats { stats, computed_at: Utc::now() };
+    let cached = match stats_cache_ttl(&state) {
+        Some(ttl) => {
+            stats_cache::insert(scope, rebuilt.clone(), ttl);
+            true
+        }
+        None => false,
+    };
+
+    Ok(Json(json!({
+        "stats": rebuilt.stats,
+        "computed_at": rebuilt.computed_at,
+        "cached": cached,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BusinessMetricsQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// Clinic KPIs for the clinic director: booking volume, cancellation and
+/// no-show rates and lead time from the event stream, plus slot fill rate
+/// per specialty over `from..=to` (the last 7 days by default)
+#[axum::debug_handler]
+pub async fn get_business_metrics(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<BusinessMetricsQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let to = params.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = params.from.unwrap_or(to - Duration::days(6));
+    if to < from {
+        return Err(AppError::BadRequest("'to' must not be before 'from'".to_string()));
+    }
+    if (to - from).num_days() >= MAX_UTILIZATION_RANGE_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "Fill rate range cannot exceed {} days",
+            MAX_UTILIZATION_RANGE_DAYS
+        )));
+    }
+
+    let fill_rates = specialty_fill_rates(&state, from, to, auth.token()).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let degradation = fill_rates.degradation();
+
+    Ok(Json(json!({
+        "metrics": business_metrics().snapshot(),
+        "fill_rate": {
+            "from": from,
+            "to": to,
+            "by_specialty": fill_rates.by_specialty,
+            "doctors_left_out": fill_rates.doctors_left_out
+        },
+        "degraded": degradation.degraded,
+        "degraded_reason": degradation.degraded_reason
+    })))
+}
+
+/// Feature flags in force on this instance, with the calling admin's own
+/// result for each. Flags are read-only here; they change through
+/// FEATURE_FLAGS.
+#[axum::debug_handler]
+pub async fn get_feature_flags(
+    State(state): State<Arc<AppConfig>>,
+    admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let context = FlagContext::new(&admin.id, admin.role.as_deref());
+    let flags: Vec<Value> = state.feature_flags.all().into_iter()
+        .map(|(flag, rule)| json!({
+            "flag": flag,
+            "rule": rule,
+            "enabled_for_you": state.feature_flags.is_enabled(&flag, &context),
+        }))
+        .collect();
+
+    Ok(Json(json!({ "flags": flags })))
+}
+
+/// Supabase call and error counts per endpoint and slot hold outcomes, in
+/// the Prometheus text format so they can be scraped straight into a dashboard
+#[axum::debug_handler]
+pub async fn get_prometheus_metrics(
+    _admin: RequireRole<Admin>,
+) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        format!("{}{}", prometheus_text(), holds::prometheus_text()),
+    ).into_response()
+}
+
+/// How long a public health answer is reused before the checks run again
+const HEALTH_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Whether the service is able to do its job: just `ok` or `degraded`, 503
+/// on the latter. This route is public, so the result is cached for
+/// [`HEALTH_CACHE_TTL`] and concurrent probes share one run of the checks;
+/// anonymous traffic can't be turned into Supabase load. What failed is
+/// logged and available to admins from [`get_health_details`].
+#[axum::debug_handler]
+pub async fn get_health(
+    State(state): State<Arc<AppConfig>>,
+) -> Response {
+    static LAST_RESULT: OnceLock<AsyncMutex<Option<(Instant, bool)>>> = OnceLock::new();
+
+    let healthy = {
+        let mut last_result = LAST_RESULT.get_or_init(Default::default).lock().await;
+        match *last_result {
+            Some((checked_at, healthy)) if checked_at.elapsed() < HEALTH_CACHE_TTL => healthy,
+            _ => {
+                let (healthy, _) = run_health_checks(&state).await;
+                *last_result = Some((Instant::now(), healthy));
+                healthy
+            }
+        }
+    };
+
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(json!({ "status": if healthy { "ok" } else { "degraded" } }))).into_response()
+}
+
+/// The individual health checks and their errors, for admins. Always runs
+/// the checks afresh.
+#[axum::debug_handler]
+pub async fn get_health_details(
+    State(state): State<Arc<AppConfig>>,
+    _admin: RequireRole<Admin>,
+) -> Response {
+    let (healthy, checks) = run_health_checks(&state).await;
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(json!({
+        "status": if healthy { "ok" } else { "degraded" },
+        "checks": checks,
+    }))).into_response()
+}
+
+/// Run the checks for whichever way tokens are validated. With HS256 only
+/// (no JWKS URL), the local JWT secret check catches a truncated or
+/// malformed secret and the Supabase check sends a token signed with it to
+/// Supabase, which is what notices a secret rotated underneath a running
+/// process. With a JWKS, the key set must be fetchable.
+async fn run_health_checks(config: &AppConfig) -> (bool, Value) {
+    let checks = if config.supabase_jwks_url.is_empty() {
+        let jwt_secret = match check_jwt_secret(&config.supabase_jwt_secret, &config.supabase_anon_key) {
+            Ok(check) => json!({
+                "ok": true,
+                "round_trip": check.round_trip,
+                "anon_key_verified": check.anon_key_verified,
+            }),
+            Err(e) => json!({ "ok": false, "error": e }),
+        };
+        let supabase_auth = match check_supabase_auth(config).await {
+            Ok(()) => json!({ "ok": true }),
+            Err(e) => json!({ "ok": false, "error": e }),
+        };
+        json!({ "jwt_secret": jwt_secret, "supabase_auth": supabase_auth })
+    } else {
+        let jwks = JwksCache::shared(
+            &config.supabase_jwks_url,
+            std::time::Duration::from_secs(config.jwks_cache_ttl_seconds),
+        );
+        let jwks = match jwks.check_reachable().await {
+            Ok(keys) => json!({ "ok": true, "keys": keys }),
+            Err(e) => json!({ "ok": false, "error": e }),
+        };
+        json!({ "jwks": jwks })
+    };
+
+    let failed: Vec<&String> = checks.as_object()
+        .map(|checks| checks.iter().filter(|(_, check)| check["ok"] != true).map(|(name, _)| name).collect())
+        .unwrap_or_default();
+    if !failed.is_empty() {
+        warn!("Health checks failed: {}", checks);
+    }
+    (failed.is_empty(), checks)
+}
+
+/// Make a cheap read with a token we signed ourselves. Supabase rejects it
+/// with a 401 once its JWT secret no longer matches ours.
+async fn check_supabase_auth(config: &AppConfig) -> Result<(), String> {
+    let now = Utc::now().timestamp() as u64;
+    let claims = JwtClaims {
+        sub: Uuid::nil().to_string(),
+        exp: Some(now + 60),
+        email: None,
+        role: Some("authenticated".to_string()),
+        app_metadata: None,
+        user_metadata: None,
+        aud: Some("authenticated".to_string()),
+        iat: Some(now),
+    };
+    let token = create_token(&claims, &config.supabase_jwt_secret)?;
+
+    let _: Vec<Value> = SupabaseClient::new(config)
+        .request(reqwest::Method::GET, "/rest/v1/appointments?select=id&limit=1", Some(&token), None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Cancelled appointments broken down by reason, for clinic managers.
+/// Doctors see their own appointments only.
+#[axum::debug_handler]
+pub async fn get_cancellation_stats(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<StatsQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let doctor_id = match user.app_role() {
+        Some(Role::Admin) => params.doctor_id,
+        Some(Role::Doctor) => Some(Uuid::parse_str(&user.id)
+            .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?),
+        _ => return Err(AppError::Forbidden("Only doctors and administrators can view cancellation statistics".to_string())),
+    };
+
+    let booking_service = booking_service(&state)?;
+    let stats = booking_service.get_cancellation_stats(
+        params.patient_id,
+        doctor_id,
+        params.from_date,
+        params.to_date,
+        auth.token(),
+    ).await.map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(json!({ "cancellations": stats })))
+}
+
+// ==============================================================================
+// CONSULTATION NOTE HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn create_consultation_note(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateConsultationNoteRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    authorize_clinical_access(&state, appointment_id, &user, token).await?;
+
+    let author = Uuid::parse_str(&user.id)
+        .map_err(|_| AppError::BadRequest("Invalid user ID".to_string()))?;
+
+    let notes_service = ConsultationNoteService::new(&state);
+    let note = notes_service.add_note(appointment_id, author, request, token).await?;
+
+    Ok(Json(json!({
+        "note": note,
+        "message": "Consultation note added"
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn get_consultation_notes(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    authorize_clinical_access(&state, appointment_id, &user, token).await?;
+
+    let notes_service = ConsultationNoteService::new(&state);
+    let notes = notes_service.get_notes(appointment_id, token).await
+        .map_err(|e| AppError::Internal(e.to_string()).with_code(e.code()))?;
+
+    Ok(Json(json!({
+        "notes": notes,
+        "total": notes.len()
+    })))
+}
+
+/// Full-text search over the notes a doctor wrote or that belong to their
+/// patients
+#[axum::debug_handler]
+pub async fn search_consultation_notes(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<NoteSearchQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    if !user.has_role(Role::Doctor) {
+        return Err(AppError::Auth("Only doctors can search consultation notes".to_string()));
+    }
+    let doctor_id = Uuid::parse_str(&user.id)
+        .map_err(|_| AppError::BadRequest("Invalid user ID".to_string()))?;
+
+    let notes_service = ConsultationNoteService::new(&state);
+    let results = notes_service.search_notes(doctor_id, &params.q, params.limit, token).await?;
+
+    Ok(Json(json!({
+        "query": params.q,
+        "results": results,
+        "total": results.len()
+    })))
+}
+
+// ==============================================================================
+// INTAKE FORM HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+pub async fn create_intake_form(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+    Json(request): Json<CreateIntakeFormRequest>,
+) -> Result<Json<Value>, AppError> {
+    let intake_service = IntakeService::new(&state);
+    let form = intake_service.create_form(request, auth.token()).await?;
+
+    Ok(Json(json!({
+        "form": form,
+        "message": "Intake form created"
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn list_intake_forms(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<IntakeFormQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<Value>, AppError> {
+    let intake_service = IntakeService::new(&state);
+    let forms = intake_service.forms_for_type(&params.appointment_type, auth.token()).await?;
+
+    Ok(Json(json!({
+        "forms": forms,
+        "total": forms.len()
+    })))
+}
+
+/// The patient answers one of their appointment's intake forms
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn submit_intake_response(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<SubmitIntakeRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    if appointment.patient_id.to_string() != user.id {
+        return Err(AppError::Auth("Only the appointment's patient can submit intake forms".to_string()));
+    }
+
+    let intake_service = IntakeService::new(&state);
+    let response = intake_service.submit_response(&appointment, request, token).await?;
+
+    Ok(Json(json!({
+        "response": response,
+        "message": "Intake form submitted"
+    })))
+}
+
+/// Intake answers for the doctor to review before the consultation
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn get_intake_responses(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    authorize_clinical_access(&state, appointment_id, &user, token).await?;
+
+    let intake_service = IntakeService::new(&state);
+    let responses = intake_service.get_responses(appointment_id, token).await?;
+
+    Ok(Json(json!({
+        "responses": responses,
+        "total": responses.len()
+    })))
+}
+
+// ==============================================================================
+// PRESCRIPTION AND CERTIFICATE HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn issue_prescription(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<IssuePrescriptionRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    // Only the attending doctor can prescribe
+    if appointment.doctor_id.to_string() != user.id {
+        return Err(AppError::Auth("Only the appointment's doctor can issue prescriptions".to_string()));
+    }
+
+    let prescription_service = PrescriptionService::new(&state);
+    let prescription = prescription_service.issue(&appointment, appointment.doctor_id, request, token).await?;
+
+    Ok(Json(json!({
+        "prescription": prescription,
+        "message": "Prescription issued"
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn get_appointment_prescriptions(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    let is_patient = appointment.patient_id.to_string() == user.id;
+    let is_doctor = appointment.doctor_id.to_string() == user.id;
+    let is_admin = user.is_admin();
+
+    if !is_patient && !is_doctor && !is_admin {
+        return Err(AppError::Auth("Not authorized to view prescriptions for this appointment".to_string()));
+    }
+
+    let prescription_service = PrescriptionService::new(&state);
+    let prescriptions = prescription_service.get_for_appointment(appointment_id, token).await
+        .map_err(|e| AppError::Internal(e.to_string()).with_code(e.code()))?;
+
+    Ok(Json(json!({
+        "prescriptions": prescriptions,
+        "total": prescriptions.len()
+    })))
+}
+
+/// Record how the consultation ended. An appointment still in progress is
+/// completed first. A follow-up slot is suggested when one is needed; the
+/// patient books it as usual.
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn record_consultation_outcome(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<RecordOutcomeRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let mut appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    if appointment.doctor_id.to_string() != user.id {
+        return Err(AppError::Auth("Only the appointment's doctor can record its outcome".to_string()));
+    }
+
+    // Don't complete the consultation for an outcome that won't be saved
+    validate_outcome(&request)?;
+
+    if appointment.status == AppointmentStatus::InProgress {
+        let complete = UpdateAppointmentRequest {
+            status: Some(AppointmentStatus::Completed),
+            doctor_notes: None,
+            patient_notes: None,
+            reschedule_to: None,
+            reschedule_duration: None,
+            cancellation_reason: None,
+            expected_version: Some(appointment.version),
+        };
+        appointment = booking_service.update_appointment(appointment_id, complete, token).await?;
+    }
+
+    let outcome_service = OutcomeService::new(&state);
+    let outcome = outcome_service.record(&appointment, appointment.doctor_id, request, token).await?;
+
+    // The outcome is saved either way; a failed search only loses the suggestion
+    let follow_up_suggestion = match outcome_service.suggest_follow_up(&appointment, &outcome, token).await {
+        Ok(suggestion) => suggestion,
+        Err(e) => {
+            warn!("No follow-up suggestion for appointment {}: {}", appointment_id, e);
+            None
+        }
+    };
+
+    Ok(Json(json!({
+        "outcome": outcome,
+        "follow_up_suggestion": follow_up_suggestion,
+        "message": "Consultation outcome recorded"
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn issue_medical_certificate(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<IssueCertificateRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    let is_doctor = appointment.doctor_id.to_string() == user.id;
+    let is_admin = user.is_admin();
+
+    if !is_doctor && !is_admin {
+        return Err(AppError::Auth("Only the attending doctor or an administrator can issue certificates".to_string()));
+    }
+
+    let certificate_service = CertificateService::new(&state);
+    let certificate = certificate_service.issue(&appointment, &user.id, request, token).await?;
+
+    Ok(Json(json!({
+        "certificate": certificate,
+        "message": "Medical certificate issued"
+    })))
+}
+
+/// Attach a photo or document to an appointment, such as a rash photo ahead
+/// of a dermatology consultation. It's stored with the patient's documents.
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn add_appointment_attachment(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(upload): Json<DocumentUpload>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    let is_patient = appointment.patient_id.to_string() == user.id;
+    let is_doctor = appointment.doctor_id.to_string() == user.id;
+    let is_admin = user.is_admin();
+
+    if !is_patient && !is_doctor && !is_admin {
+        return Err(AppError::Auth("Not authorized to add attachments to this appointment".to_string()));
+    }
+    if matches!(appointment.status, AppointmentStatus::Cancelled | AppointmentStatus::NoShow) {
+        let e = AppointmentError::InvalidStatusTransition(appointment.status.clone());
+        return Err(AppError::BadRequest(e.to_string()).with_code(e.code()));
+    }
+
+    let document_service = DocumentService::new(&state);
+    let attachment = document_service.upload_appointment_attachment(
+        &appointment.patient_id.to_string(),
+        appointment.id,
+        &upload.title,
+        upload.file_data,
+        &upload.file_type,
+        token,
+    ).await.map_err(|e| match e.downcast_ref::<DocumentUploadError>() {
+        Some(DocumentUploadError::TooLarge { .. }) => AppError::PayloadTooLarge(e.to_string()),
+        Some(DocumentUploadError::UnsupportedType { .. } | DocumentUploadError::TypeMismatch { .. }) => {
+            AppError::UnsupportedMediaType(e.to_string())
+        }
+        None => AppError::Internal(e.to_string()),
+    })?;
+
+    Ok(Json(json!({
+        "attachment": attachment,
+        "message": "Attachment added"
+    })))
+}
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(appointment_id = %appointment_id, patient_id = Empty, doctor_id = Empty))]
+pub async fn get_appointment_attachments(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+    let booking_service = booking_service(&state)?;
+
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+
+    record_appointment_ids(&appointment);
+
+    let is_patient = appointment.patient_id.to_string() == user.id;
+    let is_doctor = appointment.doctor_id.to_string() == user.id;
+    let is_admin = user.is_admin();
+
+    if !is_patient && !is_doctor && !is_admin {
+        return Err(AppError::Auth("Not authorized to view attachments for this appointment".to_string()));
+    }
+
+    let document_service = DocumentService::new(&state);
+    let attachments = document_service.get_appointment_attachments(appointment.id, token).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(json!({
+        "attachments": attachments,
+        "total": attachments.len()
+    })))
+}
+
+// ==============================================================================
+// WEBHOOK SUBSCRIPTION HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+pub async fn create_webhook_subscription(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+    Json(request): Json<CreateWebhookSubscriptionRequest>,
+) -> Result<Json<Value>, AppError> {
+    let webhook_service = WebhookService::new(&state);
+    let subscription = webhook_service.create_subscription(request, auth.token()).await?;
+
+    // The secret is only ever shown here; receivers need it to verify X-Signature
+    Ok(Json(json!({
+        "subscription": subscription,
+        "secret": subscription.secret,
+        "message": "Webhook registered. Store the secret now, it will not be shown again."
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn list_webhook_subscriptions(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let webhook_service = WebhookService::new(&state);
+    let subscriptions = webhook_service.list_subscriptions(auth.token()).await
+        .map_err(|e| AppError::Internal(e.to_string()).with_code(e.code()))?;
+
+    Ok(Json(json!({
+        "subscriptions": subscriptions,
+        "total": subscriptions.len()
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn delete_webhook_subscription(
+    State(state): State<Arc<AppConfig>>,
+    Path(subscription_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let webhook_service = WebhookService::new(&state);
+    webhook_service.delete_subscription(subscription_id, auth.token()).await
+        .map_err(|e| {
+            let code = e.code();
+            match e {
+                AppointmentError::NotFound => AppError::NotFound("Webhook subscription not found".to_string()),
+                other => other.into(),
+            }.with_code(code)
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Webhook subscription deleted"
+    })))
+}
+
+// ==============================================================================
+// APPOINTMENT TIMING HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+#[instrument(skip_all, fields(doctor_id = %doctor_id))]
+pub async fn get_doctor_timing_overrides(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    if doctor_id.to_string() != user.id && !user.is_admin() {
+        return Err(AppError::Auth("Not authorized to view this doctor's appointment timing".to_string()));
+    }
+
+    let timing_service = timing_service(&state);
+    let overrides = timing_service.list_doctor_overrides(doctor_id, auth.token()).await
+        .map_err(|e| AppError::Internal(e.to_string()).with_code(e.code()))?;
+
+    Ok(Json(json!({
+        "doctor_id": doctor_id,
+        "overrides": overrides
+    })))
+}
+
+/// Doctors set their own duration/buffer per appointment type; admins can set anyone's
+#[axum::debug_handler]
+#[instrument(skip_all, fields(doctor_id = %doctor_id))]
+pub async fn set_doctor_timing_override(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<SetTimingOverrideRequest>,
+) -> Result<Json<Value>, AppError> {
+    if doctor_id.to_string() != user.id && !user.is_admin() {
+        return Err(AppError::Auth("Not authorized to change this doctor's appointment timing".to_string()));
+    }
+    let updated_by = Uuid::parse_str(&user.id)
+        .map_err(|_| AppError::BadRequest("Invalid user ID".to_string()))?;
+
+    let timing_service = timing_service(&state);
+    let timing_override = timing_service.set_doctor_override(doctor_id, request, updated_by, auth.token()).await?;
+
+    Ok(Json(json!({
+        "override": timing_override,
+        "message": "Appointment timing updated"
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn get_specialty_timing_overrides(
+    State(state): State<Arc<AppConfig>>,
+    Path(specialty): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let specialty = parse_specialty(&specialty)?;
+
+    let timing_service = timing_service(&state);
+    let overrides = timing_service.list_specialty_overrides(specialty, auth.token()).await
+        .map_err(|e| AppError::Internal(e.to_string()).with_code(e.code()))?;
+
+    Ok(Json(json!({
+        "specialty": specialty,
+        "overrides": overrides
+    })))
+}
+
+/// Specialty-wide defaults, used for doctors without their own setting (admin only)
+#[axum::debug_handler]
+pub async fn set_specialty_timing_override(
+    State(state): State<Arc<AppConfig>>,
+    Path(specialty): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    admin: RequireRole<Admin>,
+    Json(request): Json<SetTimingOverrideRequest>,
+) -> Result<Json<Value>, AppError> {
+    let specialty = parse_specialty(&specialty)?;
+    let updated_by = Uuid::parse_str(&admin.id)
+        .map_err(|_| AppError::BadRequest("Invalid user ID".to_string()))?;
+
+    let timing_service = timing_service(&state);
+    let timing_override = timing_service.set_specialty_override(specialty, request, updated_by, auth.token()).await?;
+
+    Ok(Json(json!({
+        "override": timing_override,
+        "message": "Appointment timing updated"
+    })))
+}
+
+// ==============================================================================
+// HELPERS
+// ==============================================================================
+
+/// Non-admins only ever see their own appointments, whatever filters they pass
+fn scope_search_to_user(search_query: &mut AppointmentSearchQuery, user: &User) {
+    match user.app_role() {
+        Some(Role::Admin) => {},
+        Some(Role::Doctor) => {
+            if let Ok(doctor_uuid) = Uuid::parse_str(&user.id) {
+                search_query.doctor_id = Some(doctor_uuid);
+            }
+        },
+        _ => {
+            if let Ok(patient_uuid) = Uuid::parse_str(&user.id) {
+                search_query.patient_id = Some(patient_uuid);
+            }
+        }
+    }
+}
+
+/// Fill in the ids a handler's span declares empty until the appointment is
+/// loaded or created. Ids only: no names, notes or tokens go on spans.
+fn record_appointment_ids(appointment: &Appointment) {
+    let span = Span::current();
+    span.record("appointment_id", field::display(appointment.id));
+    span.record("patient_id", field::display(appointment.patient_id));
+    span.record("doctor_id", field::display(appointment.doctor_id));
+}
+
+/// Booking service using this deployment's validation rules
+fn booking_service(state: &AppConfig) -> Result<AppointmentBookingService, AppError> {
+    let validation_rules = AppointmentValidationRules::from_config(state)
+        .map_err(|e| AppError::Internal(e.to_string()).with_code(e.code()))?;
+    Ok(AppointmentBookingService::new(state, validation_rules))
+}
+
+/// A booking service for `user`; admins booking on a patient's behalf aren't
+/// held to the per-patient daily limit
+fn booking_service_for(state: &AppConfig, user: &User) -> Result<AppointmentBookingService, AppError> {
+    let booking_service = booking_service(state)?;
+    Ok(if user.is_admin() { booking_service.without_daily_limit() } else { booking_service })
+}
+
+/// Clinical records are limited to the appointment's doctor and admins
+async fn authorize_clinical_access(
+    state: &AppConfig,
+    appointment_id: Uuid,
+    user: &User,
+    token: &str,
+) -> Result<(), AppError> {
+    let booking_service = booking_service(state)?;
+    let appointment = booking_service.get_appointment(appointment_id, token).await?;
+    record_appointment_ids(&appointment);
+
+    let is_doctor = appointment.doctor_id.to_string() == user.id;
+    let is_admin = user.is_admin();
+
+    if !is_doctor && !is_admin {
+        return Err(AppError::Auth("Only the appointment's doctor or an administrator can access clinical notes".to_string()));
+    }
+    Ok(())
+}
+
+fn timing_service(state: &AppConfig) -> AppointmentTimingService {
+    AppointmentTimingService::new(Arc::new(SupabaseClient::new(state)))
+}
+
+fn parse_specialty(specialty: &str) -> Result<Specialty, AppError> {
+    Specialty::parse(specialty)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown specialty: {}", specialty)))
+}
+
+/// Forms to show after booking. A failed lookup shouldn't fail the booking.
+async fn intake_forms_for(state: &AppConfig, appointment_type: &AppointmentType, token: &str) -> Vec<IntakeForm> {
+    IntakeService::new(state).forms_for_type(appointment_type, token).await
+        .unwrap_or_else(|e| {
+            warn!("Failed to look up {} intake forms: {}", appointment_type, e);
+            Vec::new()
+        })
+}
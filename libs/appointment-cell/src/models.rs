@@ -1,8 +1,15 @@
 // libs/appointment-cell/src/models.rs
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
+use chrono::{DateTime, Datelike, Utc, NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
+
+use shared_config::AppConfig;
+use shared_models::error::AppError;
+use doctor_cell::models::Specialty;
 
 // ==============================================================================
 // CORE APPOINTMENT MODELS
@@ -29,11 +36,26 @@ pub struct Appointment {
     pub medical_certificate_issued: bool,
     pub report_generated: bool,
     pub video_conference_link: Option<String>,
+    #[serde(default)]
+    pub cancellation_reason: Option<CancellationReason>,
+    /// Recorded by the doctor once the consultation is completed
+    #[serde(default)]
+    pub outcome: Option<ConsultationOutcome>,
+    /// Bumped on every update; clients send back the one they read as
+    /// `expected_version`
+    #[serde(default)]
+    pub version: i64,
+    /// When the patient said they'll attend. Separate from `Confirmed`,
+    /// which only means the booking went through.
+    #[serde(default)]
+    pub attendance_confirmed_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Stored and queried in snake_case (`in_progress`); see `canonical_enum_value`
+/// for the spellings still accepted when reading
+#[derive(Debug, Clone, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum AppointmentStatus {
     Pending,
@@ -45,21 +67,54 @@ pub enum AppointmentStatus {
     Rescheduled,
 }
 
+impl AppointmentStatus {
+    /// The canonical value, as written to the database and used in filters
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            AppointmentStatus::Pending => "pending",
+            AppointmentStatus::Confirmed => "confirmed",
+            AppointmentStatus::InProgress => "in_progress",
+            AppointmentStatus::Completed => "completed",
+            AppointmentStatus::Cancelled => "cancelled",
+            AppointmentStatus::NoShow => "no_show",
+            AppointmentStatus::Rescheduled => "rescheduled",
+        }
+    }
+}
+
 impl fmt::Display for AppointmentStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            AppointmentStatus::Pending => write!(f, "pending"),
-            AppointmentStatus::Confirmed => write!(f, "confirmed"),
-            AppointmentStatus::InProgress => write!(f, "in_progress"),
-            AppointmentStatus::Completed => write!(f, "completed"),
-            AppointmentStatus::Cancelled => write!(f, "cancelled"),
-            AppointmentStatus::NoShow => write!(f, "no_show"),
-            AppointmentStatus::Rescheduled => write!(f, "rescheduled"),
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AppointmentStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match canonical_enum_value(value).as_str() {
+            "pending" => Ok(AppointmentStatus::Pending),
+            "confirmed" => Ok(AppointmentStatus::Confirmed),
+            "in_progress" => Ok(AppointmentStatus::InProgress),
+            "completed" => Ok(AppointmentStatus::Completed),
+            "cancelled" | "canceled" => Ok(AppointmentStatus::Cancelled),
+            "no_show" => Ok(AppointmentStatus::NoShow),
+            "rescheduled" => Ok(AppointmentStatus::Rescheduled),
+            _ => Err(format!("unknown appointment status '{}'", value)),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+impl<'de> Deserialize<'de> for AppointmentStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Stored and queried in snake_case (`general_consultation`); see
+/// `canonical_enum_value` for the spellings still accepted when reading
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum AppointmentType {
     GeneralConsultation,
@@ -71,18 +126,187 @@ pub enum AppointmentType {
     WomensHealth,
 }
 
+impl AppointmentType {
+    pub fn all() -> [AppointmentType; 7] {
+        [
+            AppointmentType::GeneralConsultation,
+            AppointmentType::FollowUp,
+            AppointmentType::Prescription,
+            AppointmentType::MedicalCertificate,
+            AppointmentType::Urgent,
+            AppointmentType::MentalHealth,
+            AppointmentType::WomensHealth,
+        ]
+    }
+
+    /// The canonical value, as written to the database and used in filters
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            AppointmentType::GeneralConsultation => "general_consultation",
+            AppointmentType::FollowUp => "follow_up",
+            AppointmentType::Prescription => "prescription",
+            AppointmentType::MedicalCertificate => "medical_certificate",
+            AppointmentType::Urgent => "urgent",
+            AppointmentType::MentalHealth => "mental_health",
+            AppointmentType::WomensHealth => "womens_health",
+        }
+    }
+}
+
 impl fmt::Display for AppointmentType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AppointmentType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match canonical_enum_value(value).as_str() {
+            // Rows from before the general consultation type was split out
+            "general_consultation" | "consultation" => Ok(AppointmentType::GeneralConsultation),
+            "follow_up" => Ok(AppointmentType::FollowUp),
+            "prescription" => Ok(AppointmentType::Prescription),
+            "medical_certificate" => Ok(AppointmentType::MedicalCertificate),
+            "urgent" => Ok(AppointmentType::Urgent),
+            "mental_health" => Ok(AppointmentType::MentalHealth),
+            "womens_health" => Ok(AppointmentType::WomensHealth),
+            _ => Err(format!("unknown appointment type '{}'", value)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AppointmentType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// snake_case form of an enum value written in any of the casings older rows
+/// and clients use: `InProgress`, `IN_PROGRESS`, `in-progress` and
+/// `in progress` all become `in_progress`
+pub fn canonical_enum_value(value: &str) -> String {
+    let mut canonical = String::with_capacity(value.len() + 4);
+    let mut previous: Option<char> = None;
+    for c in value.trim().chars() {
+        if matches!(c, '-' | ' ') {
+            canonical.push('_');
+        } else {
+            if c.is_ascii_uppercase() && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit()) {
+                canonical.push('_');
+            }
+            canonical.push(c.to_ascii_lowercase());
+        }
+        previous = Some(c);
+    }
+    canonical
+}
+
+/// Scheduling metadata for an appointment type. Every per-type rule (timing,
+/// buffers, concurrency, telemedicine support, priority) is read from here so
+/// the individual accessors cannot drift apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppointmentTypeMeta {
+    pub default_duration_minutes: i32,
+    pub buffer_minutes: i32,
+    /// Overlapping appointments of this type one doctor can hold; 1 allows no overlap
+    pub max_concurrent: i32,
+    pub telemedicine_capable: bool,
+    /// Higher values are scheduled first
+    pub priority: u8,
+    /// May be booked outside clinic business hours
+    pub out_of_hours: bool,
+}
+
+impl AppointmentType {
+    pub const fn meta(&self) -> AppointmentTypeMeta {
         match self {
-            AppointmentType::GeneralConsultation => write!(f, "general_consultation"),
-            AppointmentType::FollowUp => write!(f, "follow_up"),
-            AppointmentType::Prescription => write!(f, "prescription"),
-            AppointmentType::MedicalCertificate => write!(f, "medical_certificate"),
-            AppointmentType::Urgent => write!(f, "urgent"),
-            AppointmentType::MentalHealth => write!(f, "mental_health"),
-            AppointmentType::WomensHealth => write!(f, "womens_health"),
+            AppointmentType::GeneralConsultation => AppointmentTypeMeta {
+                default_duration_minutes: 30,
+                buffer_minutes: 5,
+                max_concurrent: 1,
+                telemedicine_capable: true,
+                priority: 2,
+                out_of_hours: false,
+            },
+            AppointmentType::FollowUp => AppointmentTypeMeta {
+                default_duration_minutes: 20,
+                buffer_minutes: 5,
+                max_concurrent: 1,
+                telemedicine_capable: true,
+                priority: 2,
+                out_of_hours: false,
+            },
+            AppointmentType::Prescription => AppointmentTypeMeta {
+                default_duration_minutes: 15,
+                buffer_minutes: 0,
+                max_concurrent: 3,
+                telemedicine_capable: true,
+                priority: 1,
+                out_of_hours: false,
+            },
+            AppointmentType::MedicalCertificate => AppointmentTypeMeta {
+                default_duration_minutes: 15,
+                buffer_minutes: 0,
+                max_concurrent: 3,
+                telemedicine_capable: true,
+                priority: 1,
+                out_of_hours: false,
+            },
+            AppointmentType::Urgent => AppointmentTypeMeta {
+                default_duration_minutes: 30,
+                buffer_minutes: 10,
+                max_concurrent: 1,
+                telemedicine_capable: false,
+                priority: 4,
+                out_of_hours: true,
+            },
+            AppointmentType::MentalHealth => AppointmentTypeMeta {
+                default_duration_minutes: 50,
+                buffer_minutes: 10,
+                max_concurrent: 1,
+                telemedicine_capable: true,
+                priority: 3,
+                out_of_hours: false,
+            },
+            AppointmentType::WomensHealth => AppointmentTypeMeta {
+                default_duration_minutes: 30,
+                buffer_minutes: 10,
+                max_concurrent: 1,
+                telemedicine_capable: true,
+                priority: 2,
+                out_of_hours: false,
+            },
         }
     }
+
+    /// Default duration and post-appointment buffer, in minutes
+    pub fn get_appointment_timing(&self) -> (i32, i32) {
+        let meta = self.meta();
+        (meta.default_duration_minutes, meta.buffer_minutes)
+    }
+
+    pub fn supports_concurrent_appointments(&self) -> bool {
+        self.max_concurrent() > 1
+    }
+
+    pub fn max_concurrent(&self) -> i32 {
+        self.meta().max_concurrent
+    }
+
+    pub fn is_telemedicine_capable(&self) -> bool {
+        self.meta().telemedicine_capable
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.meta().priority
+    }
+
+    pub fn allowed_out_of_hours(&self) -> bool {
+        self.meta().out_of_hours
+    }
 }
 
 // ==============================================================================
@@ -95,13 +319,33 @@ pub struct BookAppointmentRequest {
     pub doctor_id: Option<Uuid>, // Made optional - system can find best doctor
     pub appointment_date: DateTime<Utc>,
     pub appointment_type: AppointmentType,
-    pub duration_minutes: i32,
+    #[serde(default)]
+    pub duration_minutes: Option<i32>, // Omit to use the doctor's default for the type
     pub timezone: String,
     pub patient_notes: Option<String>,
     pub preferred_language: Option<String>,
     pub specialty_required: Option<String>, // Added for specialty validation
 }
 
+/// A slot reserved for a short time while the patient finishes booking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppointmentHold {
+    pub hold_id: Uuid,
+    pub doctor_id: Uuid,
+    pub patient_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Details collected while the slot was held; they replace the ones given
+/// when the hold was placed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfirmHoldRequest {
+    pub patient_notes: Option<String>,
+    pub preferred_language: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SmartBookingRequest {
     pub patient_id: Uuid,
@@ -114,6 +358,15 @@ pub struct SmartBookingRequest {
     pub specialty_required: Option<String>,
     pub patient_notes: Option<String>,
     pub allow_history_prioritization: Option<bool>, // Enable doctor history matching
+    /// Other doctors to offer alternatives from (default 4, at most 10)
+    #[serde(default)]
+    pub max_alternative_doctors: Option<usize>,
+    /// Alternative slots per doctor (default 2, at most 10)
+    #[serde(default)]
+    pub max_slots_per_doctor: Option<usize>,
+    /// Alternative slots in total (default 10, at most 50)
+    #[serde(default)]
+    pub max_alternatives: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -123,6 +376,20 @@ pub struct UpdateAppointmentRequest {
     pub patient_notes: Option<String>,
     pub reschedule_to: Option<DateTime<Utc>>,
     pub reschedule_duration: Option<i32>,
+    /// Only stored when the update cancels the appointment
+    #[serde(default)]
+    pub cancellation_reason: Option<CancellationReason>,
+    /// The `version` the client last read. Required on `PUT /{id}`; the
+    /// update is refused if the appointment has changed since.
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+
+/// A saved cancellation and whether the clinic's fee policy applies to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancellationOutcome {
+    pub appointment: Appointment,
+    pub cancellation_fee_applicable: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,12 +397,43 @@ pub struct RescheduleAppointmentRequest {
     pub new_start_time: DateTime<Utc>,
     pub new_duration_minutes: Option<i32>,
     pub reason: Option<String>,
+    /// Move the appointment even inside the reschedule notice period.
+    /// Doctors and admins only; every override is audited.
+    #[serde(default)]
+    pub override_notice: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CancelAppointmentRequest {
+    /// Free-text note kept with the appointment
     pub reason: String,
     pub cancelled_by: CancelledBy,
+    /// Category used for cancellation analytics
+    #[serde(default)]
+    pub cancellation_reason: Option<CancellationReason>,
+}
+
+/// Why an appointment was cancelled. `Other` carries a short description
+/// when none of the categories fit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancellationReason {
+    PatientIllness,
+    SchedulingConflict,
+    DoctorUnavailable,
+    Other(String),
+}
+
+impl CancellationReason {
+    /// Reporting bucket; every `Other` description falls into "other"
+    pub fn category(&self) -> &'static str {
+        match self {
+            CancellationReason::PatientIllness => "patient_illness",
+            CancellationReason::SchedulingConflict => "scheduling_conflict",
+            CancellationReason::DoctorUnavailable => "doctor_unavailable",
+            CancellationReason::Other(_) => "other",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -154,8 +452,21 @@ pub struct AppointmentSearchQuery {
     pub appointment_type: Option<AppointmentType>,
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
+    /// Words that must all appear in the patient's notes for the
+    /// appointment, matched by full-text search
+    pub reason_contains: Option<String>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+/// One page of search results plus the total number of matching appointments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppointmentSearchPage {
+    pub appointments: Vec<Appointment>,
+    pub total: i64,
     pub limit: Option<i32>,
     pub offset: Option<i32>,
+    pub has_more: bool,
 }
 
 // ==============================================================================
@@ -171,6 +482,37 @@ pub struct SmartBookingResponse {
     pub alternative_slots: Vec<AlternativeSlot>,
 }
 
+/// How many alternatives smart booking offers, from the request's
+/// `max_*` fields with defaults filled in and upper bounds enforced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlternativeLimits {
+    pub doctors: usize,
+    pub slots_per_doctor: usize,
+    pub total: usize,
+}
+
+impl AlternativeLimits {
+    pub const DEFAULT: Self = Self { doctors: 4, slots_per_doctor: 2, total: 10 };
+    /// Each extra doctor costs an availability lookup, so keep these modest
+    pub const MAX: Self = Self { doctors: 10, slots_per_doctor: 10, total: 50 };
+
+    pub fn from_request(request: &SmartBookingRequest) -> Result<Self, AppointmentError> {
+        let limit = |name: &str, requested: Option<usize>, default: usize, max: usize| match requested {
+            Some(value) if value > max => Err(AppointmentError::ValidationError(
+                format!("{} cannot be more than {}", name, max)
+            )),
+            Some(value) => Ok(value),
+            None => Ok(default),
+        };
+
+        Ok(Self {
+            doctors: limit("max_alternative_doctors", request.max_alternative_doctors, Self::DEFAULT.doctors, Self::MAX.doctors)?,
+            slots_per_doctor: limit("max_slots_per_doctor", request.max_slots_per_doctor, Self::DEFAULT.slots_per_doctor, Self::MAX.slots_per_doctor)?,
+            total: limit("max_alternatives", request.max_alternatives, Self::DEFAULT.total, Self::MAX.total)?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlternativeSlot {
     pub doctor_id: Uuid,
@@ -231,7 +573,407 @@ pub struct AppointmentStats {
     pub no_show_appointments: i32,
     pub average_consultation_duration: i32,
     pub appointment_type_breakdown: Vec<(AppointmentType, i32)>,
+    pub cancellation_reason_breakdown: Vec<(String, i32)>,
     pub doctor_continuity_rate: f32, // % of appointments with previously seen doctors
+    /// Completed consultations per recorded disposition; one consultation
+    /// can count towards several
+    pub outcome_breakdown: Vec<(OutcomeDisposition, i32)>,
+    pub completed_without_outcome: i32,
+    #[serde(flatten)]
+    pub degradation: Degradation,
+}
+
+/// Set on a response when part of it came from a fallback instead of the
+/// full computation, so clients can show it as limited rather than complete
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Degradation {
+    pub degraded: bool,
+    pub degraded_reason: Option<String>,
+}
+
+impl Degradation {
+    pub fn because(reason: impl Into<String>) -> Self {
+        Self {
+            degraded: true,
+            degraded_reason: Some(reason.into()),
+        }
+    }
+}
+
+/// Cancelled appointments grouped by reason, most common first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancellationStats {
+    pub total_cancelled: i32,
+    pub reasons: Vec<CancellationReasonCount>,
+    /// Descriptions given with `other`, most common first
+    pub other_reasons: Vec<(String, i32)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancellationReasonCount {
+    /// A `CancellationReason` category, or "unspecified" for cancellations
+    /// recorded without one
+    pub reason: String,
+    pub count: i32,
+    pub percentage: f32,
+}
+
+// ==============================================================================
+// CONSULTATION NOTE MODELS
+// ==============================================================================
+
+/// Clinical note in SOAP form (Subjective, Objective, Assessment, Plan)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsultationNote {
+    pub id: Uuid,
+    pub appointment_id: Uuid,
+    pub subjective: Option<String>,
+    pub objective: Option<String>,
+    pub assessment: Option<String>,
+    pub plan: Option<String>,
+    pub author: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ConsultationNote {
+    /// Plain-text rendering kept in the legacy `doctor_notes` column
+    pub fn to_legacy_text(&self) -> String {
+        [
+            ("S", &self.subjective),
+            ("O", &self.objective),
+            ("A", &self.assessment),
+            ("P", &self.plan),
+        ]
+        .iter()
+        .filter_map(|(label, section)| {
+            section.as_deref()
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .map(|text| format!("{}: {}", label, text))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateConsultationNoteRequest {
+    pub subjective: Option<String>,
+    pub objective: Option<String>,
+    pub assessment: Option<String>,
+    pub plan: Option<String>,
+}
+
+/// A note matching a search, with the best matching section excerpted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsultationNoteSearchHit {
+    pub note: ConsultationNote,
+    pub patient_id: Uuid,
+    /// `subjective`, `objective`, `assessment` or `plan`
+    pub section: String,
+    /// Excerpt around the match with search terms wrapped in `<mark>`
+    pub snippet: String,
+}
+
+// ==============================================================================
+// INTAKE FORM MODELS
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntakeFieldType {
+    Text,
+    Number,
+    Boolean,
+    /// One of the field's `options`
+    Choice,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntakeFormField {
+    /// Key the answer is stored under
+    pub key: String,
+    pub label: String,
+    pub field_type: IntakeFieldType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntakeFormSchema {
+    pub fields: Vec<IntakeFormField>,
+}
+
+/// Pre-visit questionnaire for an appointment type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntakeForm {
+    pub id: Uuid,
+    pub appointment_type: AppointmentType,
+    pub title: String,
+    pub schema: IntakeFormSchema,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateIntakeFormRequest {
+    pub appointment_type: AppointmentType,
+    pub title: String,
+    pub schema: IntakeFormSchema,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitIntakeRequest {
+    pub form_id: Uuid,
+    pub answers: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A patient's answers to an intake form for one appointment. Resubmitting
+/// replaces the earlier answers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntakeResponse {
+    pub id: Uuid,
+    pub appointment_id: Uuid,
+    pub form_id: Uuid,
+    pub patient_id: Uuid,
+    pub answers: serde_json::Map<String, serde_json::Value>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+// ==============================================================================
+// PRESCRIPTION MODELS
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Medication {
+    pub name: String,
+    pub dosage: String,     // e.g. "500mg"
+    pub frequency: String,  // e.g. "twice daily"
+    pub duration_days: Option<i32>,
+    pub instructions: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prescription {
+    pub id: Uuid,
+    pub appointment_id: Uuid,
+    pub medications: Vec<Medication>,
+    pub issued_by: Uuid,
+    pub issued_at: DateTime<Utc>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuePrescriptionRequest {
+    pub medications: Vec<Medication>,
+    pub notes: Option<String>,
+}
+
+// ==============================================================================
+// CONSULTATION OUTCOME MODELS
+// ==============================================================================
+
+/// What a consultation led to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeDisposition {
+    Prescription,
+    Referral,
+    FollowUpNeeded,
+    Discharge,
+}
+
+/// The structured end of a consultation. A consultation can have several
+/// dispositions, e.g. a prescription and a follow-up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsultationOutcome {
+    pub dispositions: Vec<OutcomeDisposition>,
+    /// The service or specialty referred to; set with `referral`
+    pub referred_to: Option<String>,
+    /// Days after the consultation the follow-up should be; set with `follow_up_needed`
+    pub follow_up_in_days: Option<i32>,
+    pub summary: Option<String>,
+    pub recorded_by: Uuid,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordOutcomeRequest {
+    pub dispositions: Vec<OutcomeDisposition>,
+    pub referred_to: Option<String>,
+    pub follow_up_in_days: Option<i32>,
+    pub summary: Option<String>,
+}
+
+// ==============================================================================
+// MEDICAL CERTIFICATE MODELS
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueCertificateRequest {
+    pub reason: String,
+    pub unfit_from: NaiveDate,
+    pub unfit_until: NaiveDate,
+    pub remarks: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MedicalCertificate {
+    pub appointment_id: Uuid,
+    pub document_id: Uuid,
+    pub download_url: String,
+    pub issued_at: DateTime<Utc>,
+}
+
+// ==============================================================================
+// APPOINTMENT TIMING MODELS
+// ==============================================================================
+
+/// A doctor's or a specialty's own duration, buffer and overlap capacity for
+/// one appointment type. Exactly one of `doctor_id`/`specialty` is set. Unset minutes fall
+/// through: doctor, then specialty, then the type default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppointmentTimingOverride {
+    pub id: Uuid,
+    pub doctor_id: Option<Uuid>,
+    pub specialty: Option<Specialty>,
+    pub appointment_type: AppointmentType,
+    pub duration_minutes: Option<i32>,
+    pub buffer_minutes: Option<i32>,
+    #[serde(default)]
+    pub max_concurrent: Option<i32>,
+    pub updated_by: Uuid,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetTimingOverrideRequest {
+    pub appointment_type: AppointmentType,
+    pub duration_minutes: Option<i32>,
+    pub buffer_minutes: Option<i32>,
+    #[serde(default)]
+    pub max_concurrent: Option<i32>,
+}
+
+/// Duration, post-appointment buffer and overlap capacity that apply once
+/// overrides are resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppointmentTiming {
+    pub duration_minutes: i32,
+    pub buffer_minutes: i32,
+    pub max_concurrent: i32,
+}
+
+impl AppointmentTiming {
+    /// Layer overrides (most specific first) over the type default
+    pub fn resolve(appointment_type: &AppointmentType, overrides: &[&AppointmentTimingOverride]) -> Self {
+        let (default_duration, default_buffer) = appointment_type.get_appointment_timing();
+        Self {
+            duration_minutes: overrides
+                .iter()
+                .find_map(|o| o.duration_minutes)
+                .unwrap_or(default_duration),
+            buffer_minutes: overrides
+                .iter()
+                .find_map(|o| o.buffer_minutes)
+                .unwrap_or(default_buffer),
+            max_concurrent: overrides
+                .iter()
+                .find_map(|o| o.max_concurrent)
+                .unwrap_or(appointment_type.max_concurrent()),
+        }
+    }
+}
+
+// ==============================================================================
+// EVENT AND WEBHOOK MODELS
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AppointmentEvent {
+    Booked,
+    Confirmed,
+    Started,
+    Completed,
+    Cancelled,
+    NoShow,
+    Rescheduled,
+    /// The patient's emergency contact should be told about the appointment
+    EmergencyContactAlert,
+}
+
+impl AppointmentEvent {
+    /// Event raised when an appointment moves into `status`
+    pub fn for_status(status: &AppointmentStatus) -> Option<Self> {
+        match status {
+            AppointmentStatus::Pending => None,
+            AppointmentStatus::Confirmed => Some(AppointmentEvent::Confirmed),
+            AppointmentStatus::InProgress => Some(AppointmentEvent::Started),
+            AppointmentStatus::Completed => Some(AppointmentEvent::Completed),
+            AppointmentStatus::Cancelled => Some(AppointmentEvent::Cancelled),
+            AppointmentStatus::NoShow => Some(AppointmentEvent::NoShow),
+            AppointmentStatus::Rescheduled => Some(AppointmentEvent::Rescheduled),
+        }
+    }
+}
+
+impl fmt::Display for AppointmentEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AppointmentEvent::Booked => "booked",
+            AppointmentEvent::Confirmed => "confirmed",
+            AppointmentEvent::Started => "started",
+            AppointmentEvent::Completed => "completed",
+            AppointmentEvent::Cancelled => "cancelled",
+            AppointmentEvent::NoShow => "no_show",
+            AppointmentEvent::Rescheduled => "rescheduled",
+            AppointmentEvent::EmergencyContactAlert => "emergency_contact_alert",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One occurrence of an event, as published on the event stream and as
+/// delivered to webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppointmentEventEnvelope {
+    pub id: Uuid,
+    pub event: AppointmentEvent,
+    pub occurred_at: DateTime<Utc>,
+    pub appointment: Appointment,
+    /// Who to alert, on `emergency_contact_alert` events only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emergency_contact: Option<EmergencyContact>,
+    /// Whether a late-cancellation fee applies, on `cancelled` events only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancellation_fee_applicable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EmergencyContact {
+    pub name: Option<String>,
+    pub phone: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<AppointmentEvent>,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhookSubscriptionRequest {
+    pub url: String,
+    pub events: Vec<AppointmentEvent>,
+    pub secret: Option<String>, // Generated when omitted
 }
 
 // ==============================================================================
@@ -247,7 +989,12 @@ pub enum AppointmentError {
     SlotNotAvailable,
     
     #[error("No {specialty} doctors available at this time")]
-    SpecialtyNotAvailable { specialty: String },
+    SpecialtyNotAvailable {
+        specialty: String,
+        /// Later dates the specialty does have availability on, earliest first
+        #[serde(default)]
+        next_available_dates: Vec<NaiveDate>,
+    },
     
     #[error("Doctor not available at requested time")]
     DoctorNotAvailable,
@@ -265,14 +1012,26 @@ pub enum AppointmentError {
     InvalidStatusTransition(AppointmentStatus),
     
     #[error("Appointment conflicts with existing booking")]
-    ConflictDetected,
+    ConflictDetected {
+        /// Free slots with the same doctor the client can offer instead
+        suggested_alternatives: Vec<SuggestedSlot>,
+    },
     
     #[error("Unauthorized access to appointment")]
     Unauthorized,
     
+    #[error("Appointment hold not found or expired")]
+    HoldNotFound,
+    
+    #[error("Patient already has the maximum of {max_per_day} appointments on this day")]
+    DailyLimitReached { max_per_day: i32 },
+    
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    #[error("Appointment has changed since version {0}; reload it and try again")]
+    VersionConflict(i64),
+    
     #[error("Database error: {0}")]
     DatabaseError(String),
     
@@ -283,6 +1042,65 @@ pub enum AppointmentError {
     DoctorMatchingError(String),
 }
 
+impl AppointmentError {
+    /// Stable machine-readable code surfaced in API error responses
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppointmentError::NotFound => "appointment.not_found",
+            AppointmentError::SlotNotAvailable => "appointment.slot_unavailable",
+            AppointmentError::SpecialtyNotAvailable { .. } => "appointment.specialty_unavailable",
+            AppointmentError::DoctorNotAvailable => "appointment.doctor_unavailable",
+            AppointmentError::PatientNotFound => "appointment.patient_not_found",
+            AppointmentError::DoctorNotFound => "appointment.doctor_not_found",
+            AppointmentError::InvalidTime(_) => "appointment.invalid_time",
+            AppointmentError::InvalidStatusTransition(_) => "appointment.invalid_status_transition",
+            AppointmentError::ConflictDetected { .. } => "appointment.conflict",
+            AppointmentError::Unauthorized => "appointment.unauthorized",
+            AppointmentError::HoldNotFound => "appointment.hold_not_found",
+            AppointmentError::DailyLimitReached { .. } => "appointment.daily_limit_reached",
+            AppointmentError::ValidationError(_) => "appointment.validation",
+            AppointmentError::VersionConflict(_) => "appointment.version_conflict",
+            AppointmentError::DatabaseError(_) => "appointment.database",
+            AppointmentError::ExternalServiceError(_) => "appointment.external_service",
+            AppointmentError::DoctorMatchingError(_) => "appointment.doctor_matching",
+        }
+    }
+}
+
+/// The HTTP error an appointment error maps to unless the handler has
+/// something more specific to say, such as a friendlier message
+impl From<AppointmentError> for AppError {
+    fn from(e: AppointmentError) -> Self {
+        let code = e.code();
+        match e {
+            AppointmentError::NotFound => AppError::NotFound("Appointment not found".to_string()),
+            AppointmentError::PatientNotFound
+            | AppointmentError::DoctorNotFound
+            | AppointmentError::DoctorNotAvailable
+            | AppointmentError::HoldNotFound => AppError::NotFound(e.to_string()),
+            AppointmentError::SpecialtyNotAvailable { ref next_available_dates, .. } => {
+                AppError::NotFound(e.to_string())
+                    .with_extension("next_available_dates", serde_json::json!(next_available_dates))
+            },
+            AppointmentError::SlotNotAvailable | AppointmentError::VersionConflict(_) => {
+                AppError::Conflict(e.to_string())
+            },
+            AppointmentError::ConflictDetected { ref suggested_alternatives } => {
+                AppError::Conflict(e.to_string())
+                    .with_extension("suggested_alternatives", serde_json::json!(suggested_alternatives))
+            },
+            AppointmentError::Unauthorized => AppError::Forbidden(e.to_string()),
+            AppointmentError::InvalidTime(msg) | AppointmentError::ValidationError(msg) => AppError::BadRequest(msg),
+            AppointmentError::InvalidStatusTransition(_) | AppointmentError::DailyLimitReached { .. } => {
+                AppError::BadRequest(e.to_string())
+            },
+            AppointmentError::DatabaseError(msg) => AppError::Database(msg),
+            AppointmentError::ExternalServiceError(msg) => AppError::ExternalService(msg),
+            AppointmentError::DoctorMatchingError(_) => AppError::Internal(e.to_string()),
+        }.with_code(code)
+    }
+}
+
 // ==============================================================================
 // VALIDATION MODELS
 // ==============================================================================
@@ -293,10 +1111,15 @@ pub struct AppointmentValidationRules {
     pub max_advance_booking_days: i32,
     pub allowed_cancellation_hours: i32,
     pub allowed_reschedule_hours: i32,
-    pub max_appointments_per_day: i32,
+    /// Active appointments one patient may have on a day; None is unlimited
+    pub max_appointments_per_day: Option<i32>,
     pub min_appointment_duration: i32,
     pub max_appointment_duration: i32,
     pub enable_history_prioritization: bool, // New flag for history-based matching
+    /// When routine appointments may take place; None allows any hour
+    pub business_hours: Option<BusinessHours>,
+    /// When a patient's cancellation is late enough to incur a fee
+    pub cancellation_fee: CancellationFeePolicy,
 }
 
 impl Default for AppointmentValidationRules {
@@ -306,10 +1129,230 @@ impl Default for AppointmentValidationRules {
             max_advance_booking_days: 90,
             allowed_cancellation_hours: 24,
             allowed_reschedule_hours: 48,
-            max_appointments_per_day: 3,
+            max_appointments_per_day: None,
             min_appointment_duration: 15,
             max_appointment_duration: 120,
             enable_history_prioritization: true, // Enable by default
+            business_hours: None,
+            cancellation_fee: CancellationFeePolicy::default(),
+        }
+    }
+}
+
+impl AppointmentValidationRules {
+    /// The default rules with this deployment's overrides applied
+    pub fn from_config(config: &AppConfig) -> Result<Self, AppointmentError> {
+        let overrides = &config.appointment_rules;
+        let defaults = Self::default();
+
+        let rules = Self {
+            min_advance_booking_hours: overrides.min_advance_booking_hours.unwrap_or(defaults.min_advance_booking_hours),
+            max_advance_booking_days: overrides.max_advance_booking_days.unwrap_or(defaults.max_advance_booking_days),
+            allowed_cancellation_hours: overrides.cancellation_notice_hours.unwrap_or(defaults.allowed_cancellation_hours),
+            allowed_reschedule_hours: overrides.reschedule_notice_hours.unwrap_or(defaults.allowed_reschedule_hours),
+            max_appointments_per_day: overrides.max_appointments_per_day.or(defaults.max_appointments_per_day),
+            min_appointment_duration: overrides.min_duration_minutes.unwrap_or(defaults.min_appointment_duration),
+            max_appointment_duration: overrides.max_duration_minutes.unwrap_or(defaults.max_appointment_duration),
+            business_hours: overrides.business_hours.as_deref()
+                .map(|spec| BusinessHours::parse(spec, overrides.business_timezone.as_deref().unwrap_or("UTC")))
+                .transpose()?,
+            cancellation_fee: overrides.cancellation_fee_hours.as_deref()
+                .map(CancellationFeePolicy::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            ..defaults
+        };
+
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    /// Reject rule sets that would make booking impossible or nonsensical
+    pub fn validate(&self) -> Result<(), AppointmentError> {
+        let notices = [
+            ("minimum advance booking", self.min_advance_booking_hours),
+            ("cancellation notice", self.allowed_cancellation_hours),
+            ("reschedule notice", self.allowed_reschedule_hours),
+        ];
+        if let Some((name, _)) = notices.iter().find(|(_, hours)| *hours < 0) {
+            return Err(AppointmentError::ValidationError(format!("{} cannot be negative", name)));
+        }
+
+        if self.max_advance_booking_days <= 0 {
+            return Err(AppointmentError::ValidationError(
+                "Maximum advance booking must be at least one day".to_string(),
+            ));
+        }
+
+        if self.max_appointments_per_day.is_some_and(|max| max <= 0) {
+            return Err(AppointmentError::ValidationError(
+                "Maximum appointments per day must be positive".to_string(),
+            ));
+        }
+
+        if self.min_appointment_duration <= 0 {
+            return Err(AppointmentError::ValidationError(
+                "Minimum appointment duration must be positive".to_string(),
+            ));
         }
+
+        if self.min_appointment_duration >= self.max_appointment_duration {
+            return Err(AppointmentError::ValidationError(format!(
+                "Minimum appointment duration ({} min) must be less than the maximum ({} min)",
+                self.min_appointment_duration, self.max_appointment_duration
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Weekly opening hours in the clinic's timezone. Written as comma-separated
+/// `days HH:MM-HH:MM` entries, e.g. `mon-fri 08:00-18:00, sat 09:00-13:00`;
+/// days that aren't listed are closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BusinessHours {
+    pub timezone: Tz,
+    /// Opening and closing time per weekday, Monday first
+    pub days: [Option<(NaiveTime, NaiveTime)>; 7],
+}
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+impl BusinessHours {
+    pub fn parse(spec: &str, timezone: &str) -> Result<Self, AppointmentError> {
+        let invalid = |detail: String| AppointmentError::ValidationError(format!("Invalid business hours: {}", detail));
+
+        let timezone = timezone.parse::<Tz>()
+            .map_err(|_| invalid(format!("unknown timezone {}", timezone)))?;
+        let mut days = [None; 7];
+
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (day_range, hours) = entry.split_once(char::is_whitespace)
+                .ok_or_else(|| invalid(format!("expected 'days HH:MM-HH:MM', got '{}'", entry)))?;
+            let (open, close) = hours.trim().split_once('-')
+                .and_then(|(open, close)| Some((
+                    NaiveTime::parse_from_str(open.trim(), "%H:%M").ok()?,
+                    NaiveTime::parse_from_str(close.trim(), "%H:%M").ok()?,
+                )))
+                .ok_or_else(|| invalid(format!("expected HH:MM-HH:MM, got '{}'", hours.trim())))?;
+            if open >= close {
+                return Err(invalid(format!("{} closes before it opens", day_range)));
+            }
+
+            let weekday = |name: &str| WEEKDAYS.iter()
+                .position(|day| name.trim().eq_ignore_ascii_case(day))
+                .ok_or_else(|| invalid(format!("unknown day '{}'", name.trim())));
+            let (first, last) = match day_range.split_once('-') {
+                Some((first, last)) => (weekday(first)?, weekday(last)?),
+                None => (weekday(day_range)?, weekday(day_range)?),
+            };
+
+            // Ranges may wrap around the week, e.g. sat-mon
+            let mut day = first;
+            loop {
+                if days[day].replace((open, close)).is_some() {
+                    return Err(invalid(format!("{} is listed more than once", WEEKDAYS[day])));
+                }
+                if day == last {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+        }
+
+        if days.iter().all(Option::is_none) {
+            return Err(invalid("no opening hours given".to_string()));
+        }
+        Ok(Self { timezone, days })
+    }
+
+    /// Whether an appointment from `start` to `end` falls entirely within
+    /// one day's opening hours
+    pub fn covers(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        let local_start = start.with_timezone(&self.timezone);
+        let local_end = end.with_timezone(&self.timezone);
+        let weekday = local_start.weekday().num_days_from_monday() as usize;
+
+        match self.days[weekday] {
+            Some((open, close)) => {
+                local_start.date_naive() == local_end.date_naive()
+                    && local_start.time() >= open
+                    && local_end.time() <= close
+            }
+            None => false,
+        }
+    }
+}
+
+/// Fee windows for late patient cancellations, written as comma-separated
+/// `key=hours` entries, e.g. `default=48, urgent=0, doctor:<id>=72`. A
+/// doctor's window beats the appointment type's, which beats `default`; with
+/// no matching entry a cancellation never incurs a fee. Only flags the
+/// cancellation, charging happens downstream.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CancellationFeePolicy {
+    pub default_hours: Option<i32>,
+    pub by_type: HashMap<AppointmentType, i32>,
+    pub by_doctor: HashMap<Uuid, i32>,
+}
+
+impl CancellationFeePolicy {
+    pub fn parse(spec: &str) -> Result<Self, AppointmentError> {
+        let invalid = |detail: String| AppointmentError::ValidationError(format!("Invalid cancellation fee policy: {}", detail));
+        let mut policy = Self::default();
+
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, hours) = entry.split_once('=')
+                .ok_or_else(|| invalid(format!("expected 'key=hours', got '{}'", entry)))?;
+            let hours: i32 = hours.trim().parse()
+                .ok()
+                .filter(|hours| *hours >= 0)
+                .ok_or_else(|| invalid(format!("'{}' is not a whole number of hours", hours.trim())))?;
+
+            let key = key.trim();
+            let duplicate = if key.eq_ignore_ascii_case("default") {
+                policy.default_hours.replace(hours).is_some()
+            } else if let Some(doctor_id) = key.strip_prefix("doctor:") {
+                let doctor_id = doctor_id.trim().parse()
+                    .map_err(|_| invalid(format!("'{}' is not a doctor id", doctor_id.trim())))?;
+                policy.by_doctor.insert(doctor_id, hours).is_some()
+            } else {
+                let appointment_type = key.parse()
+                    .map_err(|_| invalid(format!("unknown appointment type '{}'", key)))?;
+                policy.by_type.insert(appointment_type, hours).is_some()
+            };
+            if duplicate {
+                return Err(invalid(format!("'{}' is listed twice", key)));
+            }
+        }
+
+        Ok(policy)
     }
-}
\ No newline at end of file
+
+    /// Notice below which cancelling this appointment incurs a fee
+    pub fn fee_window_hours(&self, doctor_id: Uuid, appointment_type: &AppointmentType) -> Option<i32> {
+        self.by_doctor.get(&doctor_id)
+            .or_else(|| self.by_type.get(appointment_type))
+            .copied()
+            .or(self.default_hours)
+    }
+
+    /// Whether cancelling with `notice` left before the start incurs a fee
+    pub fn fee_applies(&self, appointment: &Appointment, notice: chrono::Duration) -> bool {
+        self.fee_window_hours(appointment.doctor_id, &appointment.appointment_type)
+            .is_some_and(|hours| notice < chrono::Duration::hours(hours as i64))
+    }
+}
+
+impl fmt::Display for BusinessHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let open_days: Vec<String> = self.days.iter()
+            .zip(WEEKDAYS)
+            .filter_map(|(hours, day)| hours.map(|(open, close)| {
+                format!("{} {}-{}", day, open.format("%H:%M"), close.format("%H:%M"))
+            }))
+            .collect();
+        write!(f, "{} ({})", open_days.join(", "), self.timezone)
+    }
+}
@@ -1,41 +1,104 @@
 // libs/appointment-cell/src/router.rs
+use std::convert::Infallible;
 use std::sync::Arc;
 
 use axum::{
     Router,
-    routing::{get, post, put, patch},
+    extract::DefaultBodyLimit,
+    routing::{get, post, put, patch, delete},
     middleware,
 };
+use tower_http::limit::RequestBodyLimitLayer;
 
 use shared_config::AppConfig;
+use health_profile_cell::api::upload_body_limit;
 use shared_utils::extractor::auth_middleware;
 
 use crate::handlers;
 
 pub fn appointment_routes(state: Arc<AppConfig>) -> Router {
+    // Oversize attachments are refused with 413 before the body is read
+    let upload_limit = upload_body_limit(state.max_document_upload_bytes);
+
     // All appointment operations require authentication
     let protected_routes = Router::new()
         // ENHANCED: Core appointment management with smart booking
         .route("/smart-book", post(handlers::smart_book_appointment)) // NEW: Smart booking with history prioritization
         .route("/", post(handlers::book_appointment))
+        .route("/hold", post(handlers::hold_appointment_slot))
+        .route("/hold/{hold_id}/confirm", post(handlers::confirm_appointment_hold))
         .route("/search", get(handlers::search_appointments))
+        .route("/export", get(handlers::export_appointments))
+        .route("/notes/search", get(handlers::search_consultation_notes))
+        .route("/intake-forms", post(handlers::create_intake_form))
+        .route("/intake-forms", get(handlers::list_intake_forms))
         .route("/{appointment_id}", get(handlers::get_appointment))
         .route("/{appointment_id}", put(handlers::update_appointment))
         .route("/{appointment_id}/reschedule", patch(handlers::reschedule_appointment))
         .route("/{appointment_id}/cancel", post(handlers::cancel_appointment))
+        .route("/{appointment_id}/confirm-attendance", post(handlers::confirm_attendance))
+        .route("/{appointment_id}/notes", post(handlers::create_consultation_note))
+        .route("/{appointment_id}/notes", get(handlers::get_consultation_notes))
+        .route("/{appointment_id}/intake", post(handlers::submit_intake_response))
+        .route("/{appointment_id}/intake", get(handlers::get_intake_responses))
+        .route("/{appointment_id}/prescriptions", post(handlers::issue_prescription))
+        .route("/{appointment_id}/prescriptions", get(handlers::get_appointment_prescriptions))
+        .route("/{appointment_id}/outcome", post(handlers::record_consultation_outcome))
+        .route("/{appointment_id}/certificate", post(handlers::issue_medical_certificate))
+        .route("/{appointment_id}/attachments", get(handlers::get_appointment_attachments))
+        .route("/{appointment_id}/attachments", post(handlers::add_appointment_attachment)
+            .layer::<_, Infallible>(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(upload_limit)))
         
         // Appointment listings
         .route("/upcoming", get(handlers::get_upcoming_appointments))
         .route("/patients/{patient_id}", get(handlers::get_patient_appointments))
         .route("/doctors/{doctor_id}", get(handlers::get_doctor_appointments))
+        .route("/doctors/{doctor_id}/live", get(handlers::doctor_live_feed))
         
         // Utility endpoints
         .route("/conflicts/check", get(handlers::check_appointment_conflicts))
+        .route("/next-available", get(handlers::get_next_available))
         .route("/stats", get(handlers::get_appointment_stats)) // ENHANCED: Now includes continuity metrics
+        .route("/stats/cancellations", get(handlers::get_cancellation_stats))
+        .route("/stats/rebuild", post(handlers::rebuild_appointment_stats))
+
+        // Per-doctor and per-specialty duration/buffer defaults
+        .route("/timing/doctors/{doctor_id}", get(handlers::get_doctor_timing_overrides))
+        .route("/timing/doctors/{doctor_id}", put(handlers::set_doctor_timing_override))
+        .route("/timing/specialties/{specialty}", get(handlers::get_specialty_timing_overrides))
+        .route("/timing/specialties/{specialty}", put(handlers::set_specialty_timing_override))
+
+        // Outbound webhooks for appointment events (admin only)
+        .route("/webhooks", post(handlers::create_webhook_subscription))
+        .route("/webhooks", get(handlers::list_webhook_subscriptions))
+        .route("/webhooks/{subscription_id}", delete(handlers::delete_webhook_subscription))
         
         .layer(middleware::from_fn_with_state(state.clone(), auth_middleware));
 
     Router::new()
         .merge(protected_routes)
         .with_state(state)
-}
\ No newline at end of file
+}
+
+/// Patient-level views built from appointment data, merged into the
+/// patient routes at `/patients`
+pub fn patient_timeline_routes(state: Arc<AppConfig>) -> Router {
+    Router::new()
+        .route("/{patient_id}/timeline", get(handlers::get_patient_timeline))
+        .layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
+        .with_state(state)
+}
+
+/// Clinic-wide monitoring, mounted at `/monitoring`
+pub fn monitoring_routes(state: Arc<AppConfig>) -> Router {
+    Router::new()
+        .route("/business", get(handlers::get_business_metrics))
+        .route("/feature-flags", get(handlers::get_feature_flags))
+        .route("/metrics", get(handlers::get_prometheus_metrics))
+        .route("/health/details", get(handlers::get_health_details))
+        .layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
+        // Public, so it still answers when token validation is what's broken
+        .route("/health", get(handlers::get_health))
+        .with_state(state)
+}
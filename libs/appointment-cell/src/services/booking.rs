@@ -1,6 +1,6 @@
 // libs/appointment-cell/src/services/booking.rs
 use anyhow::Result;
-use chrono::{DateTime, Utc, Duration, NaiveTime};
+use chrono::{DateTime, Utc, Duration, NaiveDate, NaiveTime, SecondsFormat};
 use reqwest::Method;
 use serde_json::{json, Value};
 use tracing::{debug, info, warn};
@@ -8,45 +8,114 @@ use uuid::Uuid;
 use std::sync::Arc;
 
 use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
 use shared_database::supabase::SupabaseClient;
+use shared_models::auth::{Role, User};
+use doctor_cell::services::availability::AvailabilityService;
 use doctor_cell::services::matching::DoctorMatchingService;
-use doctor_cell::models::{DoctorMatchingRequest, DoctorMatch};
+use doctor_cell::models::{AvailabilityQueryRequest, AvailabilityRangeRequest, AvailableSlot, DoctorMatchingRequest, DoctorMatch, Specialty};
 
 use crate::models::{
     Appointment, AppointmentStatus, AppointmentType, BookAppointmentRequest,
-    UpdateAppointmentRequest, RescheduleAppointmentRequest, CancelAppointmentRequest,
-    AppointmentSearchQuery, AppointmentStats, AppointmentError,
+    UpdateAppointmentRequest, RescheduleAppointmentRequest, CancelAppointmentRequest, CancellationOutcome, CancelledBy,
+    AppointmentSearchQuery, AppointmentSearchPage, AppointmentStats, AppointmentError, Degradation,
     AppointmentValidationRules, SmartBookingRequest, SmartBookingResponse,
-    AlternativeSlot
+    AlternativeSlot, AppointmentEvent, CancellationReason, CancellationReasonCount, CancellationStats,
+    AppointmentHold, ConfirmHoldRequest, AlternativeLimits, ConsultationOutcome, OutcomeDisposition,
+    SuggestedSlot,
 };
-use crate::services::conflict::ConflictDetectionService;
+use crate::services::clock::{Clock, SystemClock};
+use crate::services::conflict::{BatchLimits, ConflictDetectionService};
+use crate::services::emergency_contact::EmergencyContactService;
+use crate::services::events;
+use crate::services::holds::{self, HeldSlot, HoldStore};
 use crate::services::lifecycle::AppointmentLifecycleService;
+use crate::services::notes::search_terms;
+use crate::services::schedule_lock;
+use crate::services::stats_cache::StatsScope;
+use crate::services::timing::AppointmentTimingService;
+use crate::services::versioning;
+use crate::services::webhooks::WebhookOutbox;
+
+/// Later dates offered when a specialty has nothing on the requested one
+const SPECIALTY_SUGGESTED_DATES: usize = 3;
+
+/// Days of schedules read at a time when looking for the next free slot
+/// across doctors
+const NEXT_AVAILABLE_WINDOW_DAYS: i64 = 7;
+
+/// Rows per request when a stats rebuild reads a whole column
+const STATS_COLUMN_PAGE_SIZE: usize = 1000;
+
+/// Order of search results, newest first. The id breaks ties between
+/// appointments starting at the same time so pages never overlap or skip.
+const SEARCH_ORDER: &str = "scheduled_start_time.desc,id.desc";
 
 pub struct AppointmentBookingService {
     supabase: Arc<SupabaseClient>,
     conflict_service: ConflictDetectionService,
     lifecycle_service: AppointmentLifecycleService,
+    timing_service: AppointmentTimingService,
     doctor_matching_service: DoctorMatchingService,
+    availability_service: AvailabilityService,
+    emergency_contact_service: EmergencyContactService,
+    holds: HoldStore,
+    audit_log: AuditLog,
+    webhook_outbox: WebhookOutbox,
     validation_rules: AppointmentValidationRules,
+    clock: Arc<dyn Clock>,
+}
+
+/// Doctor and timing settled for a booking, before the slot is checked
+struct PreparedSlot {
+    doctor_id: Uuid,
+    duration_minutes: i32,
+    /// End of the slot plus the doctor's post-appointment buffer
+    occupied_until: DateTime<Utc>,
+    max_concurrent: i32,
 }
 
 impl AppointmentBookingService {
-    pub fn new(config: &AppConfig) -> Self {
+    pub fn new(config: &AppConfig, validation_rules: AppointmentValidationRules) -> Self {
         let supabase = Arc::new(SupabaseClient::new(config));
 
-        let conflict_service = ConflictDetectionService::new(Arc::clone(&supabase));
+        let conflict_service = ConflictDetectionService::new(Arc::clone(&supabase))
+            .with_batch_limits(BatchLimits::from_config(config));
+        let holds = HoldStore::new(Arc::clone(&supabase));
         let lifecycle_service = AppointmentLifecycleService::new();
+        let timing_service = AppointmentTimingService::new(Arc::clone(&supabase));
         let doctor_matching_service = DoctorMatchingService::new(config);
+        let emergency_contact_service = EmergencyContactService::new(config);
 
         Self {
             conflict_service,
             lifecycle_service,
+            timing_service,
             doctor_matching_service,
+            availability_service: AvailabilityService::new(config),
+            emergency_contact_service,
+            holds,
+            audit_log: AuditLog::new(config),
+            webhook_outbox: WebhookOutbox::new(Arc::clone(&supabase), config),
             supabase,
-            validation_rules: AppointmentValidationRules::default(),
+            validation_rules,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Read the time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Book without the per-patient daily limit, for admins booking on a
+    /// patient's behalf
+    pub fn without_daily_limit(mut self) -> Self {
+        self.validation_rules.max_appointments_per_day = None;
+        self
+    }
+
     /// NEW: Smart booking with automatic doctor selection and history prioritization
     pub async fn smart_book_appointment(
         &self,
@@ -73,7 +142,7 @@ impl AppointmentBookingService {
             doctor_id: Some(doctor_match.doctor.id),
             appointment_date: selected_slot.start_time,
             appointment_type: request.appointment_type.clone(),
-            duration_minutes: request.duration_minutes,
+            duration_minutes: Some(request.duration_minutes),
             timezone: request.timezone.clone(),
             patient_notes: request.patient_notes.clone(),
             preferred_language: None,
@@ -111,55 +180,198 @@ impl AppointmentBookingService {
         &self,
         request: BookAppointmentRequest,
         auth_token: &str,
+    ) -> Result<Appointment, AppointmentError> {
+        self.book_slot(request, None, auth_token).await
+    }
+
+    /// Reserve a slot for `HOLD_TTL_MINUTES` while the patient finishes
+    /// booking. The slot goes through the same checks as a booking, and
+    /// nobody else can book or hold it until the hold is confirmed or expires.
+    pub async fn hold_slot(
+        &self,
+        request: BookAppointmentRequest,
+        held_by: &str,
+        auth_token: &str,
+    ) -> Result<AppointmentHold, AppointmentError> {
+        let slot = self.prepare_booking(&request, auth_token).await?;
+
+        let _schedule_guard = schedule_lock::lock_doctor_schedule(slot.doctor_id).await;
+        self.ensure_slot_free(&request, &slot, None, auth_token).await?;
+
+        let hold = AppointmentHold {
+            hold_id: Uuid::new_v4(),
+            doctor_id: slot.doctor_id,
+            patient_id: request.patient_id,
+            start_time: request.appointment_date,
+            end_time: request.appointment_date + Duration::minutes(slot.duration_minutes as i64),
+            expires_at: holds::expiry_from(self.clock.now()),
+        };
+        self.holds.insert(&HeldSlot {
+            hold: hold.clone(),
+            held_by: held_by.to_string(),
+            occupied_until: slot.occupied_until,
+            request: BookAppointmentRequest {
+                doctor_id: Some(slot.doctor_id),
+                duration_minutes: Some(slot.duration_minutes),
+                ..request
+            },
+        }, auth_token).await?;
+
+        info!("Slot with doctor {} at {} held until {} ({})",
+              hold.doctor_id, hold.start_time, hold.expires_at, hold.hold_id);
+        Ok(hold)
+    }
+
+    /// Turn a hold into an appointment. Only whoever placed the hold, or an
+    /// admin, can confirm it.
+    pub async fn confirm_hold(
+        &self,
+        hold_id: Uuid,
+        details: ConfirmHoldRequest,
+        actor: &User,
+        auth_token: &str,
+    ) -> Result<Appointment, AppointmentError> {
+        let held = self.holds.get(hold_id, self.clock.now(), auth_token).await?
+            .ok_or(AppointmentError::HoldNotFound)?;
+        if held.held_by != actor.id && !actor.is_admin() {
+            return Err(AppointmentError::Unauthorized);
+        }
+
+        let mut request = held.request;
+        if details.patient_notes.is_some() {
+            request.patient_notes = details.patient_notes;
+        }
+        if details.preferred_language.is_some() {
+            request.preferred_language = details.preferred_language;
+        }
+
+        let appointment = self.book_slot(request, Some(hold_id), auth_token).await?;
+        // The appointment now occupies the slot, so a hold left behind only
+        // lingers until it expires
+        if let Err(e) = self.holds.confirm(hold_id, auth_token).await {
+            warn!("Failed to release hold {} after booking appointment {}: {}", hold_id, appointment.id, e);
+        }
+        Ok(appointment)
+    }
+
+    /// Book `request`, treating the hold `own_hold` (if any) as the caller's
+    async fn book_slot(
+        &self,
+        request: BookAppointmentRequest,
+        own_hold: Option<Uuid>,
+        auth_token: &str,
     ) -> Result<Appointment, AppointmentError> {
         info!("Booking appointment for patient {} with doctor {:?}", 
               request.patient_id, request.doctor_id);
 
+        let slot = self.prepare_booking(&request, auth_token).await?;
+
+        // **Step 5: Detect Conflicts** (including the post-appointment buffer;
+        // concurrent-capable types may overlap up to their capacity). The
+        // doctor's schedule stays locked until the appointment is written.
+        let _schedule_guard = schedule_lock::lock_doctor_schedule(slot.doctor_id).await;
+        self.ensure_slot_free(&request, &slot, own_hold, auth_token).await?;
+
+        // **Step 6: Create Appointment Record**
+        let appointment = self.create_appointment_record(
+            slot.doctor_id,
+            request,
+            slot.duration_minutes,
+            auth_token,
+        ).await?;
+
+        // **Step 7: Post-Creation Tasks**
+        self.handle_post_booking_tasks(&appointment, auth_token).await?;
+
+        let envelope = events::publish(AppointmentEvent::Booked, &appointment);
+        self.webhook_outbox.enqueue(&envelope).await;
+
+        info!("Appointment {} booked successfully with doctor {}", 
+              appointment.id, slot.doctor_id);
+        Ok(appointment)
+    }
+
+    /// Validate a booking and settle its doctor and timing
+    async fn prepare_booking(
+        &self,
+        request: &BookAppointmentRequest,
+        auth_token: &str,
+    ) -> Result<PreparedSlot, AppointmentError> {
         // **Step 1: Comprehensive Validation**
-        self.validate_booking_request(&request).await?;
+        self.validate_booking_request(request).await?;
+        self.validate_daily_limit(request, auth_token).await?;
         
         // **Step 2: Verify Patient Exists**
         self.verify_patient_exists(&request.patient_id, auth_token).await?;
         
         // **Step 3: Doctor Selection and Validation**
-        let selected_doctor_id = if let Some(doctor_id) = request.doctor_id {
+        let doctor_id = if let Some(doctor_id) = request.doctor_id {
             // Validate specific doctor
-            self.validate_specific_doctor(doctor_id, &request, auth_token).await?;
+            self.validate_specific_doctor(doctor_id, request, auth_token).await?;
             doctor_id
         } else {
             // Find best doctor automatically using history prioritization
-            self.find_best_available_doctor(&request, auth_token).await?
+            self.find_best_available_doctor(request, auth_token).await?
         };
         
-        // **Step 4: Detect Conflicts**
-        let end_time = request.appointment_date + Duration::minutes(request.duration_minutes as i64);
-        let conflict_check = self.conflict_service.check_conflicts(
-            selected_doctor_id,
+        // **Step 4: Resolve Timing** (the doctor's or specialty's defaults for the type)
+        let timing = self.timing_service
+            .resolve_timing(doctor_id, &request.appointment_type, auth_token)
+            .await?;
+        let duration_minutes = request.duration_minutes.unwrap_or(timing.duration_minutes);
+        self.validate_duration(duration_minutes)?;
+        self.validate_business_hours(&request.appointment_type, request.appointment_date, duration_minutes)?;
+        self.validate_slot_fit(doctor_id, request.appointment_date, duration_minutes, auth_token).await?;
+
+        Ok(PreparedSlot {
+            doctor_id,
+            duration_minutes,
+            occupied_until: request.appointment_date
+                + Duration::minutes((duration_minutes + timing.buffer_minutes) as i64),
+            max_concurrent: timing.max_concurrent,
+        })
+    }
+
+    /// Fail unless the slot is free of appointments and of other people's
+    /// holds. Call with the doctor's schedule locked.
+    async fn ensure_slot_free(
+        &self,
+        request: &BookAppointmentRequest,
+        slot: &PreparedSlot,
+        own_hold: Option<Uuid>,
+        auth_token: &str,
+    ) -> Result<(), AppointmentError> {
+        let conflict_check = self.conflict_service.check_conflicts_with_details(
+            slot.doctor_id,
             request.appointment_date,
-            end_time,
+            slot.occupied_until,
+            Some((&request.appointment_type, slot.max_concurrent)),
             None,
             auth_token,
         ).await?;
 
         if conflict_check.has_conflict {
             warn!("Appointment conflict detected for doctor {} at {}", 
-                  selected_doctor_id, request.appointment_date);
-            return Err(AppointmentError::ConflictDetected);
+                  slot.doctor_id, request.appointment_date);
+            return Err(AppointmentError::ConflictDetected {
+                suggested_alternatives: conflict_check.suggested_alternatives,
+            });
         }
 
-        // **Step 5: Create Appointment Record**
-        let appointment = self.create_appointment_record(
-            selected_doctor_id,
-            request,
+        let held = self.conflict_service.is_held(
+            slot.doctor_id,
+            request.appointment_date,
+            slot.occupied_until,
+            own_hold,
+            self.clock.now(),
             auth_token,
         ).await?;
+        if held {
+            debug!("Slot with doctor {} at {} is held", slot.doctor_id, request.appointment_date);
+            return Err(AppointmentError::SlotNotAvailable);
+        }
 
-        // **Step 6: Post-Creation Tasks**
-        self.handle_post_booking_tasks(&appointment, auth_token).await?;
-
-        info!("Appointment {} booked successfully with doctor {}", 
-              appointment.id, selected_doctor_id);
-        Ok(appointment)
+        Ok(())
     }
 
     /// Update an existing appointment
@@ -168,11 +380,47 @@ impl AppointmentBookingService {
         appointment_id: Uuid,
         request: UpdateAppointmentRequest,
         auth_token: &str,
+    ) -> Result<Appointment, AppointmentError> {
+        if request.expected_version.is_none() {
+            return Err(AppointmentError::ValidationError(
+                "expected_version is required; send the version from the appointment you are editing".to_string()
+            ));
+        }
+        self.apply_update(appointment_id, request, true, None, auth_token).await
+    }
+
+    /// `update_appointment`, optionally without the reschedule notice check
+    /// for moves a doctor or admin has overridden. A cancellation passes its
+    /// fee decision along so the `cancelled` event carries it.
+    async fn apply_update(
+        &self,
+        appointment_id: Uuid,
+        request: UpdateAppointmentRequest,
+        enforce_reschedule_notice: bool,
+        cancellation_fee_applicable: Option<bool>,
+        auth_token: &str,
     ) -> Result<Appointment, AppointmentError> {
         debug!("Updating appointment: {}", appointment_id);
 
         // Get current appointment
-        let current_appointment = self.get_appointment(appointment_id, auth_token).await?;
+        let mut current_appointment = self.get_appointment(appointment_id, auth_token).await?;
+
+        // A move must check and take the new slot without another booking
+        // slipping in between; re-read once the doctor's schedule is ours
+        let _schedule_guard = match request.reschedule_to {
+            Some(_) => {
+                let guard = schedule_lock::lock_doctor_schedule(current_appointment.doctor_id).await;
+                current_appointment = self.get_appointment(appointment_id, auth_token).await?;
+                Some(guard)
+            }
+            None => None,
+        };
+
+        if let Some(expected_version) = request.expected_version {
+            if expected_version != current_appointment.version {
+                return Err(AppointmentError::VersionConflict(expected_version));
+            }
+        }
 
         // Handle status transitions
         if let Some(new_status) = &request.status {
@@ -188,22 +436,31 @@ impl AppointmentBookingService {
             let new_end_time = new_start_time + Duration::minutes(new_duration as i64);
 
             // Validate reschedule timing
-            self.validate_reschedule_timing(&current_appointment, new_start_time)?;
+            self.validate_reschedule_timing(&current_appointment, new_start_time, enforce_reschedule_notice)?;
 
             // Check for conflicts with new time
-            let conflict_check = self.conflict_service.check_conflicts(
+            let timing = self.timing_service
+                .resolve_timing(current_appointment.doctor_id, &current_appointment.appointment_type, auth_token)
+                .await?;
+            let conflict_check = self.conflict_service.check_conflicts_with_details(
                 current_appointment.doctor_id,
                 new_start_time,
                 new_end_time,
+                Some((&current_appointment.appointment_type, timing.max_concurrent)),
                 Some(appointment_id),
                 auth_token,
             ).await?;
 
             if conflict_check.has_conflict {
-                return Err(AppointmentError::ConflictDetected);
+                return Err(AppointmentError::ConflictDetected {
+                    suggested_alternatives: conflict_check.suggested_alternatives,
+                });
             }
         }
 
+        let status_changed = request.status.as_ref()
+            .is_some_and(|status| *status != current_appointment.status);
+
         // Perform the update
         let updated_appointment = self.update_appointment_record(
             &current_appointment,
@@ -211,23 +468,78 @@ impl AppointmentBookingService {
             auth_token,
         ).await?;
 
+        if status_changed {
+            if let Some(event) = AppointmentEvent::for_status(&updated_appointment.status) {
+                let envelope = match (event, cancellation_fee_applicable) {
+                    (AppointmentEvent::Cancelled, Some(fee_applicable)) => {
+                        events::publish_cancellation(&updated_appointment, fee_applicable)
+                    }
+                    _ => events::publish(event, &updated_appointment),
+                };
+                self.webhook_outbox.enqueue(&envelope).await;
+                self.notify_emergency_contact(event, &updated_appointment, auth_token).await;
+            }
+        }
+
         info!("Appointment {} updated successfully", appointment_id);
         Ok(updated_appointment)
     }
 
-    /// Reschedule an appointment to a new time
+    /// Alert the patient's emergency contact about a safeguarding event. The
+    /// status change has already been saved, so failures are only logged.
+    async fn notify_emergency_contact(
+        &self,
+        event: AppointmentEvent,
+        appointment: &Appointment,
+        auth_token: &str,
+    ) {
+        if let Err(e) = self.emergency_contact_service
+            .notify_for_event(event, appointment, auth_token)
+            .await
+        {
+            warn!("Failed to alert emergency contact for appointment {}: {}", appointment.id, e);
+        }
+    }
+
+    /// Reschedule an appointment to a new time. The conflict check and the
+    /// move happen under the doctor's schedule lock, and the move is one
+    /// update, so the old slot is freed in the same write that takes the new
+    /// one. Repeating a reschedule that already happened returns the
+    /// appointment unchanged.
+    ///
+    /// Patients must respect the reschedule notice period. Doctors and admins
+    /// can override it with `override_notice`, and each override that was
+    /// actually needed is written to the audit log.
     pub async fn reschedule_appointment(
         &self,
         appointment_id: Uuid,
         request: RescheduleAppointmentRequest,
+        actor: &User,
         auth_token: &str,
     ) -> Result<Appointment, AppointmentError> {
         debug!("Rescheduling appointment: {}", appointment_id);
 
+        let actor_role = actor.app_role();
+        if request.override_notice && !matches!(actor_role, Some(Role::Doctor | Role::Admin)) {
+            return Err(AppointmentError::Unauthorized);
+        }
+
         let current_appointment = self.get_appointment(appointment_id, auth_token).await?;
 
+        let same_duration = request.new_duration_minutes
+            .is_none_or(|duration| duration == current_appointment.duration_minutes);
+        if current_appointment.status == AppointmentStatus::Rescheduled
+            && current_appointment.scheduled_start_time == request.new_start_time
+            && same_duration
+        {
+            debug!("Appointment {} is already rescheduled to {}", appointment_id, request.new_start_time);
+            return Ok(current_appointment);
+        }
+
         // Validate reschedule is allowed
-        self.validate_reschedule_timing(&current_appointment, request.new_start_time)?;
+        let enforce_notice = !request.override_notice;
+        self.validate_reschedule_timing(&current_appointment, request.new_start_time, enforce_notice)?;
+        let notice_overridden = request.override_notice && self.within_reschedule_notice(&current_appointment);
 
         let update_request = UpdateAppointmentRequest {
             status: Some(AppointmentStatus::Rescheduled),
@@ -235,18 +547,38 @@ impl AppointmentBookingService {
             patient_notes: None,
             reschedule_to: Some(request.new_start_time),
             reschedule_duration: request.new_duration_minutes,
+            cancellation_reason: None,
+            expected_version: None,
         };
 
-        self.update_appointment(appointment_id, update_request, auth_token).await
+        let rescheduled = self.apply_update(appointment_id, update_request, enforce_notice, None, auth_token).await?;
+
+        if notice_overridden {
+            warn!("Reschedule notice for appointment {} overridden by {}", appointment_id, actor.id);
+            let audit_entry = AuditEntry::new(actor.id.clone(), "appointment.reschedule_notice_overridden", "appointment", appointment_id.to_string())
+                .with_details(json!({
+                    "actor_role": actor_role,
+                    "previous_start_time": current_appointment.scheduled_start_time,
+                    "new_start_time": rescheduled.scheduled_start_time,
+                    "notice_hours": self.validation_rules.allowed_reschedule_hours,
+                    "reason": request.reason
+                }));
+            if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+                warn!("Failed to audit reschedule override for appointment {}: {}", appointment_id, e);
+            }
+        }
+
+        Ok(rescheduled)
     }
 
-    /// Cancel an appointment
+    /// Cancel an appointment, flagging whether the notice given is short
+    /// enough for the cancellation fee policy to apply
     pub async fn cancel_appointment(
         &self,
         appointment_id: Uuid,
         request: CancelAppointmentRequest,
         auth_token: &str,
-    ) -> Result<Appointment, AppointmentError> {
+    ) -> Result<CancellationOutcome, AppointmentError> {
         debug!("Cancelling appointment: {}", appointment_id);
 
         let current_appointment = self.get_appointment(appointment_id, auth_token).await?;
@@ -254,6 +586,11 @@ impl AppointmentBookingService {
         // Validate cancellation is allowed
         self.validate_cancellation_timing(&current_appointment)?;
 
+        // Only patients pay for late cancellations; the clinic's own never incur a fee
+        let notice = current_appointment.scheduled_start_time - self.clock.now();
+        let cancellation_fee_applicable = matches!(request.cancelled_by, CancelledBy::Patient)
+            && self.validation_rules.cancellation_fee.fee_applies(&current_appointment, notice);
+
         // Determine who cancelled for audit trail
         let cancellation_note = format!("Cancelled by {:?}: {}", request.cancelled_by, request.reason);
 
@@ -263,15 +600,72 @@ impl AppointmentBookingService {
             patient_notes: None,
             reschedule_to: None,
             reschedule_duration: None,
+            cancellation_reason: request.cancellation_reason.clone(),
+            expected_version: None,
         };
 
-        let cancelled_appointment = self.update_appointment(appointment_id, update_request, auth_token).await?;
+        let cancelled_appointment = self.apply_update(
+            appointment_id,
+            update_request,
+            true,
+            Some(cancellation_fee_applicable),
+            auth_token,
+        ).await?;
 
         // Handle post-cancellation tasks
         self.handle_post_cancellation_tasks(&cancelled_appointment, &request, auth_token).await?;
 
-        info!("Appointment {} cancelled successfully", appointment_id);
-        Ok(cancelled_appointment)
+        info!("Appointment {} cancelled successfully (fee applicable: {})", appointment_id, cancellation_fee_applicable);
+        Ok(CancellationOutcome {
+            appointment: cancelled_appointment,
+            cancellation_fee_applicable,
+        })
+    }
+
+    /// Record the patient's acknowledgment that they'll attend. Only the
+    /// appointment's patient, or an admin on their behalf, can confirm, and
+    /// only before it starts. Confirming twice keeps the first timestamp.
+    pub async fn confirm_attendance(
+        &self,
+        appointment_id: Uuid,
+        actor: &User,
+        auth_token: &str,
+    ) -> Result<Appointment, AppointmentError> {
+        debug!("Confirming attendance for appointment: {}", appointment_id);
+
+        let appointment = self.get_appointment(appointment_id, auth_token).await?;
+        if appointment.patient_id.to_string() != actor.id && !actor.is_admin() {
+            return Err(AppointmentError::Unauthorized);
+        }
+        if appointment.attendance_confirmed_at.is_some() {
+            debug!("Attendance for appointment {} is already confirmed", appointment_id);
+            return Ok(appointment);
+        }
+
+        let now = self.clock.now();
+        if !matches!(appointment.status, AppointmentStatus::Pending | AppointmentStatus::Confirmed)
+            || appointment.scheduled_start_time <= now
+        {
+            return Err(AppointmentError::InvalidStatusTransition(appointment.status.clone()));
+        }
+
+        let update_data = json!({
+            "attendance_confirmed_at": now.to_rfc3339(),
+            "updated_at": now.to_rfc3339()
+        });
+        let confirmed = versioning::patch_appointment(&self.supabase, &appointment, update_data, auth_token).await?;
+
+        let audit_entry = AuditEntry::new(actor.id.clone(), "appointment.attendance_confirmed", "appointment", appointment_id.to_string())
+            .with_details(json!({
+                "patient_id": appointment.patient_id,
+                "scheduled_start_time": appointment.scheduled_start_time
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit attendance confirmation for appointment {}: {}", appointment_id, e);
+        }
+
+        info!("Attendance confirmed for appointment {}", appointment_id);
+        Ok(confirmed)
     }
 
     /// Get appointment by ID
@@ -300,7 +694,8 @@ impl AppointmentBookingService {
         Ok(appointment)
     }
 
-    /// Search appointments with filters
+    /// Search appointments with filters. Nothing is counted; use
+    /// `search_appointments_page` when the caller shows a total.
     pub async fn search_appointments(
         &self,
         query: AppointmentSearchQuery,
@@ -308,38 +703,72 @@ impl AppointmentBookingService {
     ) -> Result<Vec<Appointment>, AppointmentError> {
         debug!("Searching appointments with filters: {:?}", query);
 
-        let mut query_parts = Vec::new();
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &search_path(&query)?,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
 
-        // Build query filters
-        if let Some(patient_id) = query.patient_id {
-            query_parts.push(format!("patient_id=eq.{}", patient_id));
-        }
-        if let Some(doctor_id) = query.doctor_id {
-            query_parts.push(format!("doctor_id=eq.{}", doctor_id));
-        }
-        if let Some(status) = query.status {
-            query_parts.push(format!("status=eq.{}", status));
-        }
-        if let Some(appointment_type) = query.appointment_type {
-            query_parts.push(format!("appointment_type=eq.{}", appointment_type));
-        }
-        if let Some(from_date) = query.from_date {
-            query_parts.push(format!("scheduled_start_time=gte.{}", from_date.to_rfc3339()));
-        }
-        if let Some(to_date) = query.to_date {
-            query_parts.push(format!("scheduled_start_time=lte.{}", to_date.to_rfc3339()));
-        }
+        parse_appointments(result)
+    }
 
-        let mut path = format!("/rest/v1/appointments?{}&order=scheduled_start_time.desc", 
-                              query_parts.join("&"));
+    /// Search appointments with filters, returning the page together with the
+    /// total number of matches so clients can paginate
+    pub async fn search_appointments_page(
+        &self,
+        query: AppointmentSearchQuery,
+        auth_token: &str,
+    ) -> Result<AppointmentSearchPage, AppointmentError> {
+        debug!("Searching appointments with filters: {:?}", query);
 
-        if let Some(limit) = query.limit {
-            path.push_str(&format!("&limit={}", limit));
-        }
-        if let Some(offset) = query.offset {
-            path.push_str(&format!("&offset={}", offset));
+        let limit = query.limit;
+        let offset = query.offset;
+        let path = search_path(&query)?;
+
+        let (result, total): (Vec<Value>, Option<i64>) = self.supabase.request_with_count(
+            &path,
+            Some(auth_token),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let appointments = parse_appointments(result)?;
+
+        // Without a Content-Range total, the best we know is what's been seen so far
+        let seen = offset.unwrap_or(0) as i64 + appointments.len() as i64;
+        let total = total.unwrap_or(seen);
+
+        Ok(AppointmentSearchPage {
+            appointments,
+            total,
+            limit,
+            offset,
+            has_more: seen < total,
+        })
+    }
+
+    /// One page of an export: up to `limit` matches strictly after `after`,
+    /// the (start time, id) of the last row already exported, in
+    /// `SEARCH_ORDER`. Paging by position rather than offset means rows
+    /// booked or cancelled mid-export can't shift later pages, and no total
+    /// is counted since an export never shows one.
+    pub async fn export_page(
+        &self,
+        query: &AppointmentSearchQuery,
+        after: Option<(DateTime<Utc>, Uuid)>,
+        limit: i32,
+        auth_token: &str,
+    ) -> Result<Vec<Appointment>, AppointmentError> {
+        let mut query_parts = search_filters(query)?;
+        if let Some((start, id)) = after {
+            let start = start.to_rfc3339_opts(SecondsFormat::Micros, true);
+            query_parts.push(format!(
+                "or=(scheduled_start_time.lt.{start},and(scheduled_start_time.eq.{start},id.lt.{id}))"
+            ));
         }
 
+        let path = format!("/rest/v1/appointments?{}&order={}&limit={}",
+                           query_parts.join("&"), SEARCH_ORDER, limit);
+
         let result: Vec<Value> = self.supabase.request(
             Method::GET,
             &path,
@@ -347,12 +776,7 @@ impl AppointmentBookingService {
             None,
         ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
 
-        let appointments: Vec<Appointment> = result.into_iter()
-            .map(|apt| serde_json::from_value(apt))
-            .collect::<std::result::Result<Vec<Appointment>, _>>()
-            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse appointments: {}", e)))?;
-
-        Ok(appointments)
+        parse_appointments(result)
     }
 
     /// Get upcoming appointments (next 24 hours)
@@ -362,7 +786,7 @@ impl AppointmentBookingService {
         doctor_id: Option<Uuid>,
         auth_token: &str,
     ) -> Result<Vec<Appointment>, AppointmentError> {
-        let now = Utc::now();
+        let now = self.clock.now();
         let tomorrow = now + Duration::hours(24);
 
         let query = AppointmentSearchQuery {
@@ -372,6 +796,7 @@ impl AppointmentBookingService {
             appointment_type: None,
             from_date: Some(now),
             to_date: Some(tomorrow),
+            reason_contains: None,
             limit: Some(50),
             offset: None,
         };
@@ -406,6 +831,7 @@ impl AppointmentBookingService {
             appointment_type: None,
             from_date,
             to_date,
+            reason_contains: None,
             limit: None,
             offset: None,
         };
@@ -438,10 +864,26 @@ impl AppointmentBookingService {
             *type_breakdown.entry(appointment.appointment_type.clone()).or_insert(0) += 1;
         }
         let appointment_type_breakdown: Vec<(AppointmentType, i32)> = type_breakdown.into_iter().collect();
+        let cancellation_reason_breakdown = cancellation_breakdown(&appointments).into_iter()
+            .map(|count| (count.reason, count.count))
+            .collect();
+        let (outcome_breakdown, completed_without_outcome) = outcome_breakdown(
+            appointments.iter()
+                .filter(|apt| apt.status == AppointmentStatus::Completed)
+                .map(|apt| apt.outcome.as_ref())
+        );
 
         // NEW: Calculate doctor continuity rate
+        let mut degradation = Degradation::default();
         let doctor_continuity_rate = if let Some(patient_id) = patient_id {
-            self.calculate_doctor_continuity_rate(patient_id, auth_token).await.unwrap_or(0.0)
+            match self.calculate_doctor_continuity_rate(patient_id, auth_token).await {
+                Ok(rate) => rate,
+                Err(e) => {
+                    warn!("Reporting a continuity rate of 0 for patient {}: {}", patient_id, e);
+                    degradation = Degradation::because("Doctor continuity rate could not be calculated");
+                    0.0
+                }
+            }
         } else {
             0.0
         };
@@ -453,7 +895,184 @@ impl AppointmentBookingService {
             no_show_appointments,
             average_consultation_duration,
             appointment_type_breakdown,
+            cancellation_reason_breakdown,
             doctor_continuity_rate,
+            outcome_breakdown,
+            completed_without_outcome,
+            degradation,
+        })
+    }
+
+    /// Exact stats for `scope`, from per-status and per-type row counts
+    /// rather than the fetched rows `get_appointment_stats` works from. Only
+    /// the duration and cancellation reason columns are read in full, a page
+    /// at a time. Nothing falls back: a failed query fails the rebuild.
+    pub async fn rebuild_appointment_stats(
+        &self,
+        scope: &StatsScope,
+        auth_token: &str,
+    ) -> Result<AppointmentStats, AppointmentError> {
+        debug!("Rebuilding appointment statistics for {:?}", scope);
+
+        let mut filters = Vec::new();
+        if let Some(patient_id) = scope.patient_id {
+            filters.push(format!("patient_id=eq.{}", patient_id));
+        }
+        if let Some(doctor_id) = scope.doctor_id {
+            filters.push(format!("doctor_id=eq.{}", doctor_id));
+        }
+        if let Some(from_date) = scope.from_date {
+            filters.push(format!("scheduled_start_time=gte.{}", from_date.to_rfc3339_opts(SecondsFormat::Secs, true)));
+        }
+        if let Some(to_date) = scope.to_date {
+            filters.push(format!("scheduled_start_time=lte.{}", to_date.to_rfc3339_opts(SecondsFormat::Secs, true)));
+        }
+        let with = |extra: String| filters.iter().cloned().chain(std::iter::once(extra)).collect::<Vec<_>>();
+
+        let total_appointments = self.count_appointments(&filters, auth_token).await?;
+        let completed_appointments = self.count_appointments(
+            &with(format!("status=eq.{}", AppointmentStatus::Completed)), auth_token,
+        ).await?;
+        let cancelled_appointments = self.count_appointments(
+            &with(format!("status=eq.{}", AppointmentStatus::Cancelled)), auth_token,
+        ).await?;
+        let no_show_appointments = self.count_appointments(
+            &with(format!("status=eq.{}", AppointmentStatus::NoShow)), auth_token,
+        ).await?;
+
+        let mut appointment_type_breakdown = Vec::new();
+        for appointment_type in AppointmentType::all() {
+            let count = self.count_appointments(
+                &with(format!("appointment_type=eq.{}", appointment_type)), auth_token,
+            ).await?;
+            if count > 0 {
+                appointment_type_breakdown.push((appointment_type, count));
+            }
+        }
+
+        let durations = self.appointment_column(
+            &with(format!("status=eq.{}", AppointmentStatus::Completed)), "duration_minutes", auth_token,
+        ).await?;
+        let total_duration: i64 = durations.iter().filter_map(Value::as_i64).sum();
+        let average_consultation_duration = if durations.is_empty() {
+            0
+        } else {
+            (total_duration / durations.len() as i64) as i32
+        };
+
+        let reasons = self.appointment_column(
+            &with(format!("status=eq.{}", AppointmentStatus::Cancelled)), "cancellation_reason", auth_token,
+        ).await?;
+        let mut reason_counts = std::collections::HashMap::new();
+        for reason in reasons {
+            let reason: Option<CancellationReason> = serde_json::from_value(reason).ok().flatten();
+            *reason_counts.entry(reason.as_ref().map_or("unspecified", CancellationReason::category)).or_insert(0) += 1;
+        }
+        let mut cancellation_reason_breakdown: Vec<(String, i32)> = reason_counts.into_iter()
+            .map(|(reason, count)| (reason.to_string(), count))
+            .collect();
+        cancellation_reason_breakdown.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let outcomes: Vec<Option<ConsultationOutcome>> = self.appointment_column(
+            &with(format!("status=eq.{}", AppointmentStatus::Completed)), "outcome", auth_token,
+        ).await?
+            .into_iter()
+            .map(|outcome| serde_json::from_value(outcome).ok().flatten())
+            .collect();
+        let (outcome_breakdown, completed_without_outcome) = outcome_breakdown(outcomes.iter().map(Option::as_ref));
+
+        let doctor_continuity_rate = match scope.patient_id {
+            Some(patient_id) => self.calculate_doctor_continuity_rate(patient_id, auth_token).await?,
+            None => 0.0,
+        };
+
+        Ok(AppointmentStats {
+            total_appointments,
+            completed_appointments,
+            cancelled_appointments,
+            no_show_appointments,
+            average_consultation_duration,
+            appointment_type_breakdown,
+            cancellation_reason_breakdown,
+            doctor_continuity_rate,
+            outcome_breakdown,
+            completed_without_outcome,
+            degradation: Degradation::default(),
+        })
+    }
+
+    /// Number of appointments matching `filters`, as counted by the database
+    async fn count_appointments(&self, filters: &[String], auth_token: &str) -> Result<i32, AppointmentError> {
+        let path = format!("/rest/v1/appointments?select=id&{}&limit=1", filters.join("&"));
+        let (_, total): (Vec<Value>, Option<i64>) = self.supabase.request_with_count(&path, Some(auth_token))
+            .await
+            .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        total.map(|total| total as i32).ok_or_else(|| {
+            AppointmentError::DatabaseError("Appointment count was not reported".to_string())
+        })
+    }
+
+    /// One column of every appointment matching `filters`, read a page at a time
+    async fn appointment_column(
+        &self,
+        filters: &[String],
+        column: &str,
+        auth_token: &str,
+    ) -> Result<Vec<Value>, AppointmentError> {
+        let mut values = Vec::new();
+        loop {
+            let path = format!(
+                "/rest/v1/appointments?select={}&{}&order=id&limit={}&offset={}",
+                column, filters.join("&"), STATS_COLUMN_PAGE_SIZE, values.len()
+            );
+            let page: Vec<Value> = self.supabase.request(Method::GET, &path, Some(auth_token), None)
+                .await
+                .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+            let last_page = page.len() < STATS_COLUMN_PAGE_SIZE;
+            values.extend(page.into_iter().map(|mut row| row[column].take()));
+            if last_page {
+                return Ok(values);
+            }
+        }
+    }
+
+    /// Why appointments in the range were cancelled
+    pub async fn get_cancellation_stats(
+        &self,
+        patient_id: Option<Uuid>,
+        doctor_id: Option<Uuid>,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        auth_token: &str,
+    ) -> Result<CancellationStats, AppointmentError> {
+        let query = AppointmentSearchQuery {
+            patient_id,
+            doctor_id,
+            status: Some(AppointmentStatus::Cancelled),
+            appointment_type: None,
+            from_date,
+            to_date,
+            reason_contains: None,
+            limit: None,
+            offset: None,
+        };
+        let cancelled = self.search_appointments(query, auth_token).await?;
+
+        let mut other_reasons = std::collections::HashMap::new();
+        for appointment in &cancelled {
+            if let Some(CancellationReason::Other(description)) = &appointment.cancellation_reason {
+                *other_reasons.entry(description.trim().to_lowercase()).or_insert(0) += 1;
+            }
+        }
+        let mut other_reasons: Vec<(String, i32)> = other_reasons.into_iter().collect();
+        other_reasons.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Ok(CancellationStats {
+            total_cancelled: cancelled.len() as i32,
+            reasons: cancellation_breakdown(&cancelled),
+            other_reasons,
         })
     }
 
@@ -495,31 +1114,136 @@ impl AppointmentBookingService {
             timezone: request.timezone.clone(),
         };
 
-        let best_match = self.doctor_matching_service
-            .find_best_doctor(matching_request, auth_token)
+        let best_match = match self.doctor_matching_service.find_best_doctor(matching_request, auth_token).await {
+            Ok(best_match) => best_match,
+            Err(doctor_cell::models::DoctorError::NotAvailable) => None,
+            Err(e) => return Err(AppointmentError::DoctorMatchingError(e.to_string())),
+        };
+
+        match (best_match, &request.specialty_required) {
+            (Some(doctor_match), _) => Ok(doctor_match),
+            (None, Some(specialty)) => Err(self.specialty_unavailable(
+                specialty,
+                request.preferred_date.unwrap_or_else(|| self.clock.now().date_naive()),
+                &request.appointment_type,
+                request.duration_minutes,
+                &request.timezone,
+                auth_token,
+            ).await),
+            (None, None) => Err(AppointmentError::DoctorNotAvailable),
+        }
+    }
+
+    /// `SpecialtyNotAvailable` carrying the next dates after `after` that the
+    /// specialty has availability on, so the patient isn't left at a dead end.
+    /// A failed lookahead only means no suggestions.
+    async fn specialty_unavailable(
+        &self,
+        specialty: &str,
+        after: NaiveDate,
+        appointment_type: &AppointmentType,
+        duration_minutes: i32,
+        timezone: &str,
+        auth_token: &str,
+    ) -> AppointmentError {
+        let next_available_dates = self.doctor_matching_service
+            .next_available_dates_for_specialty(
+                specialty,
+                after,
+                appointment_type.as_str(),
+                duration_minutes,
+                timezone,
+                SPECIALTY_SUGGESTED_DATES,
+                auth_token,
+            )
             .await
-            .map_err(|e| match e {
-                doctor_cell::models::DoctorError::NotAvailable => {
-                    if let Some(specialty) = &request.specialty_required {
-                        AppointmentError::SpecialtyNotAvailable { 
-                            specialty: specialty.clone() 
-                        }
-                    } else {
-                        AppointmentError::DoctorNotAvailable
-                    }
-                },
-                _ => AppointmentError::DoctorMatchingError(e.to_string()),
-            })?;
+            .unwrap_or_else(|e| {
+                warn!("Could not look ahead for {} availability: {}", specialty, e);
+                Vec::new()
+            });
+
+        AppointmentError::SpecialtyNotAvailable {
+            specialty: specialty.to_string(),
+            next_available_dates,
+        }
+    }
+
+    /// The soonest `count` free slots with any verified doctor of
+    /// `specialty`, earliest first. Schedules are read a week at a time and
+    /// the search stops once enough free slots are found, or at the end of
+    /// the booking window.
+    pub async fn find_next_available(
+        &self,
+        specialty: &str,
+        appointment_type: &AppointmentType,
+        timezone: &str,
+        count: usize,
+        auth_token: &str,
+    ) -> Result<Vec<SuggestedSlot>, AppointmentError> {
+        if Specialty::parse(specialty).is_none() {
+            return Err(AppointmentError::ValidationError(format!("Unknown specialty: {}", specialty)));
+        }
+        let doctors = self.doctor_matching_service.specialty_doctors(specialty, auth_token).await
+            .map_err(|e| AppointmentError::DoctorMatchingError(e.to_string()))?;
 
-        best_match.ok_or_else(|| {
-            if let Some(specialty) = &request.specialty_required {
-                AppointmentError::SpecialtyNotAvailable { 
-                    specialty: specialty.clone() 
+        let duration_minutes = appointment_type.meta().default_duration_minutes;
+        let now = self.clock.now();
+        let earliest_start = now + Duration::hours(self.validation_rules.min_advance_booking_hours as i64);
+        let latest_start = now + Duration::days(self.validation_rules.max_advance_booking_days as i64);
+
+        let mut found = Vec::new();
+        let mut from = earliest_start.date_naive();
+        while found.len() < count && from <= latest_start.date_naive() {
+            let to = (from + Duration::days(NEXT_AVAILABLE_WINDOW_DAYS - 1)).min(latest_start.date_naive());
+
+            let mut candidates: Vec<(Uuid, AvailableSlot)> = Vec::new();
+            for doctor in &doctors {
+                let range = AvailabilityRangeRequest {
+                    from,
+                    to,
+                    timezone: Some(timezone.to_string()),
+                    appointment_type: Some(appointment_type.as_str().to_string()),
+                    duration_minutes: Some(duration_minutes),
+                };
+                match self.availability_service.get_available_slots_range(&doctor.id.to_string(), range, Some(auth_token)).await {
+                    Ok(days) => candidates.extend(days.into_iter()
+                        .flat_map(|day| day.available_slots)
+                        .map(|slot| (doctor.id, slot))),
+                    Err(e) => warn!("Leaving doctor {} out of the next available search: {}", doctor.id, e),
                 }
-            } else {
-                AppointmentError::DoctorNotAvailable
             }
-        })
+            candidates.retain(|(_, slot)| earliest_start <= slot.start_time && slot.start_time <= latest_start);
+            candidates.sort_by_key(|(_, slot)| slot.start_time);
+
+            for (doctor_id, slot) in candidates {
+                if found.len() == count {
+                    break;
+                }
+                if self.validate_business_hours(appointment_type, slot.start_time, duration_minutes).is_err()
+                    || self.conflict_service
+                        .is_held(doctor_id, slot.start_time, slot.end_time, None, now, auth_token)
+                        .await?
+                {
+                    continue;
+                }
+                let conflicts = self.conflict_service
+                    .check_conflicts(doctor_id, slot.start_time, slot.end_time, None, auth_token)
+                    .await?;
+                if !conflicts.has_conflict {
+                    found.push(SuggestedSlot {
+                        start_time: slot.start_time,
+                        end_time: slot.end_time,
+                        doctor_id,
+                        appointment_type: appointment_type.clone(),
+                    });
+                }
+            }
+
+            from = to + Duration::days(1);
+        }
+
+        debug!("Found {} next available {} slots", found.len(), specialty);
+        Ok(found)
     }
 
     /// NEW: Select optimal slot from doctor's available slots
@@ -555,6 +1279,10 @@ impl AppointmentBookingService {
         auth_token: &str,
     ) -> Result<Vec<AlternativeSlot>, AppointmentError> {
         debug!("Generating alternative slots for patient {}", request.patient_id);
+        let limits = AlternativeLimits::from_request(request)?;
+        if limits.doctors == 0 || limits.slots_per_doctor == 0 || limits.total == 0 {
+            return Ok(Vec::new());
+        }
 
         let matching_request = DoctorMatchingRequest {
             patient_id: request.patient_id,
@@ -568,17 +1296,17 @@ impl AppointmentBookingService {
         };
 
         let matches = self.doctor_matching_service
-            .find_matching_doctors(matching_request, auth_token, Some(5))
+            // One extra in case the booked doctor is among the matches
+            .find_matching_doctors(matching_request, auth_token, Some(limits.doctors + 1))
             .await
             .map_err(|e| AppointmentError::DoctorMatchingError(e.to_string()))?;
 
         let mut alternatives = Vec::new();
-        for doctor_match in matches {
-            if doctor_match.doctor.id == *exclude_doctor_id {
-                continue; // Skip the already selected doctor
-            }
-
-            for slot in doctor_match.available_slots.iter().take(2) { // Max 2 slots per doctor
+        let other_doctors = matches.into_iter()
+            .filter(|doctor_match| doctor_match.doctor.id != *exclude_doctor_id) // Skip the already selected doctor
+            .take(limits.doctors);
+        for doctor_match in other_doctors {
+            for slot in doctor_match.available_slots.iter().take(limits.slots_per_doctor) {
                 let has_history = doctor_match.match_reasons.iter()
                     .any(|reason| reason.contains("Previous patient"));
 
@@ -595,7 +1323,7 @@ impl AppointmentBookingService {
 
         // Sort by match score (history prioritization)
         alternatives.sort_by(|a, b| b.match_score.partial_cmp(&a.match_score).unwrap());
-        alternatives.truncate(10); // Limit to 10 alternatives
+        alternatives.truncate(limits.total);
 
         Ok(alternatives)
     }
@@ -613,6 +1341,7 @@ impl AppointmentBookingService {
             appointment_type: None,
             from_date: None,
             to_date: None,
+            reason_contains: None,
             limit: None,
             offset: None,
         };
@@ -647,6 +1376,8 @@ impl AppointmentBookingService {
         debug!("Finding best available doctor for patient {} with specialty {:?}", 
                request.patient_id, request.specialty_required);
 
+        let duration_minutes = request.duration_minutes
+            .unwrap_or_else(|| request.appointment_type.get_appointment_timing().0);
         let matching_request = DoctorMatchingRequest {
             patient_id: request.patient_id,
             preferred_date: Some(request.appointment_date.date_naive()),
@@ -654,37 +1385,27 @@ impl AppointmentBookingService {
             preferred_time_end: Some((request.appointment_date + Duration::hours(2)).time()),
             specialty_required: request.specialty_required.clone(),
             appointment_type: request.appointment_type.to_string(),
-            duration_minutes: request.duration_minutes,
+            duration_minutes,
             timezone: request.timezone.clone(),
         };
 
-        let best_match = self.doctor_matching_service
-            .find_best_doctor(matching_request, auth_token)
-            .await
-            .map_err(|e| match e {
-                doctor_cell::models::DoctorError::NotAvailable => {
-                    if let Some(specialty) = &request.specialty_required {
-                        AppointmentError::SpecialtyNotAvailable { 
-                            specialty: specialty.clone() 
-                        }
-                    } else {
-                        AppointmentError::DoctorNotAvailable
-                    }
-                },
-                _ => AppointmentError::DoctorMatchingError(e.to_string()),
-            })?;
-
-        match best_match {
-            Some(doctor_match) => Ok(doctor_match.doctor.id),
-            None => {
-                if let Some(specialty) = &request.specialty_required {
-                    Err(AppointmentError::SpecialtyNotAvailable { 
-                        specialty: specialty.clone() 
-                    })
-                } else {
-                    Err(AppointmentError::DoctorNotAvailable)
-                }
-            }
+        let best_match = match self.doctor_matching_service.find_best_doctor(matching_request, auth_token).await {
+            Ok(best_match) => best_match,
+            Err(doctor_cell::models::DoctorError::NotAvailable) => None,
+            Err(e) => return Err(AppointmentError::DoctorMatchingError(e.to_string())),
+        };
+
+        match (best_match, &request.specialty_required) {
+            (Some(doctor_match), _) => Ok(doctor_match.doctor.id),
+            (None, Some(specialty)) => Err(self.specialty_unavailable(
+                specialty,
+                request.appointment_date.date_naive(),
+                &request.appointment_type,
+                duration_minutes,
+                &request.timezone,
+                auth_token,
+            ).await),
+            (None, None) => Err(AppointmentError::DoctorNotAvailable),
         }
     }
 
@@ -724,9 +1445,10 @@ impl AppointmentBookingService {
         // NEW: Validate specialty match if required
         if let Some(ref required_specialty) = request.specialty_required {
             let doctor_specialty = doctor_info["specialty"].as_str().unwrap_or("");
-            if !doctor_specialty.to_lowercase().contains(&required_specialty.to_lowercase()) {
-                return Err(AppointmentError::SpecialtyNotAvailable { 
-                    specialty: required_specialty.clone() 
+            if Specialty::parse(doctor_specialty) != Specialty::parse(required_specialty) {
+                return Err(AppointmentError::SpecialtyNotAvailable {
+                    specialty: required_specialty.clone(),
+                    next_available_dates: Vec::new(),
                 });
             }
         }
@@ -735,22 +1457,13 @@ impl AppointmentBookingService {
     }
 
     async fn validate_smart_booking_request(&self, request: &SmartBookingRequest) -> Result<(), AppointmentError> {
-        let now = Utc::now();
+        let now = self.clock.now();
 
-        // Validate duration
-        if request.duration_minutes < self.validation_rules.min_appointment_duration {
-            return Err(AppointmentError::InvalidTime(
-                format!("Appointment duration must be at least {} minutes", 
-                       self.validation_rules.min_appointment_duration)
-            ));
-        }
+        Self::validate_specialty_required(request.specialty_required.as_deref())?;
+        AlternativeLimits::from_request(request)?;
 
-        if request.duration_minutes > self.validation_rules.max_appointment_duration {
-            return Err(AppointmentError::InvalidTime(
-                format!("Appointment duration cannot exceed {} minutes", 
-                       self.validation_rules.max_appointment_duration)
-            ));
-        }
+        // Validate duration
+        self.validate_duration(request.duration_minutes)?;
 
         // Validate preferred date if provided
         if let Some(preferred_date) = request.preferred_date {
@@ -780,7 +1493,9 @@ impl AppointmentBookingService {
     }
 
     async fn validate_booking_request(&self, request: &BookAppointmentRequest) -> Result<(), AppointmentError> {
-        let now = Utc::now();
+        let now = self.clock.now();
+
+        Self::validate_specialty_required(request.specialty_required.as_deref())?;
 
         // Check minimum advance booking time
         let min_advance = Duration::hours(self.validation_rules.min_advance_booking_hours as i64);
@@ -800,15 +1515,101 @@ impl AppointmentBookingService {
             ));
         }
 
-        // Validate duration
-        if request.duration_minutes < self.validation_rules.min_appointment_duration {
+        // Validate duration when given; otherwise it's resolved once the doctor is known
+        if let Some(duration_minutes) = request.duration_minutes {
+            self.validate_duration(duration_minutes)?;
+        }
+
+        self.validate_business_hours(
+            &request.appointment_type,
+            request.appointment_date,
+            request.duration_minutes.unwrap_or(0),
+        )?;
+
+        Ok(())
+    }
+
+    /// A patient may only hold so many active appointments on one day, so a
+    /// misbehaving client can't fill the schedule
+    async fn validate_daily_limit(
+        &self,
+        request: &BookAppointmentRequest,
+        auth_token: &str,
+    ) -> Result<(), AppointmentError> {
+        let Some(max_per_day) = self.validation_rules.max_appointments_per_day else {
+            return Ok(());
+        };
+
+        let within_limit = self.conflict_service
+            .check_patient_daily_limit(request.patient_id, request.appointment_date, max_per_day, auth_token)
+            .await?;
+        if !within_limit {
+            warn!("Patient {} reached the daily limit of {} appointments", request.patient_id, max_per_day);
+            return Err(AppointmentError::DailyLimitReached { max_per_day });
+        }
+        Ok(())
+    }
+
+    /// Routine appointments must fall within clinic business hours; urgent
+    /// care can be booked at any time
+    fn validate_business_hours(
+        &self,
+        appointment_type: &AppointmentType,
+        start: DateTime<Utc>,
+        duration_minutes: i32,
+    ) -> Result<(), AppointmentError> {
+        let Some(ref business_hours) = self.validation_rules.business_hours else {
+            return Ok(());
+        };
+        if appointment_type.allowed_out_of_hours() {
+            return Ok(());
+        }
+
+        let end = start + Duration::minutes(duration_minutes as i64);
+        if !business_hours.covers(start, end) {
+            return Err(AppointmentError::InvalidTime(format!(
+                "Non-urgent appointments can only be booked during clinic hours: {}. \
+                 Book an urgent appointment for care outside these hours.",
+                business_hours
+            )));
+        }
+        Ok(())
+    }
+
+    /// The doctor's schedule is cut into slots of the schedule's own length.
+    /// A booking that starts inside one of them must end with it, or it runs
+    /// into the buffer and the next patient's slot.
+    async fn validate_slot_fit(
+        &self,
+        doctor_id: Uuid,
+        start: DateTime<Utc>,
+        duration_minutes: i32,
+        auth_token: &str,
+    ) -> Result<(), AppointmentError> {
+        let query = AvailabilityQueryRequest {
+            date: start.date_naive(),
+            timezone: None,
+            // Slot boundaries are the same whatever the type booked into them
+            appointment_type: None,
+            duration_minutes: None,
+        };
+        let slots = self.availability_service
+            .get_available_slots(&doctor_id.to_string(), query, auth_token)
+            .await
+            .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        check_slot_fit(&slots, start, duration_minutes)
+    }
+
+    fn validate_duration(&self, duration_minutes: i32) -> Result<(), AppointmentError> {
+        if duration_minutes < self.validation_rules.min_appointment_duration {
             return Err(AppointmentError::InvalidTime(
                 format!("Appointment duration must be at least {} minutes", 
                        self.validation_rules.min_appointment_duration)
             ));
         }
 
-        if request.duration_minutes > self.validation_rules.max_appointment_duration {
+        if duration_minutes > self.validation_rules.max_appointment_duration {
             return Err(AppointmentError::InvalidTime(
                 format!("Appointment duration cannot exceed {} minutes", 
                        self.validation_rules.max_appointment_duration)
@@ -818,6 +1619,16 @@ impl AppointmentBookingService {
         Ok(())
     }
 
+    /// Reject specialties that are not in the doctor-cell registry
+    fn validate_specialty_required(specialty: Option<&str>) -> Result<(), AppointmentError> {
+        match specialty {
+            Some(specialty) if Specialty::parse(specialty).is_none() => Err(
+                AppointmentError::ValidationError(format!("Unknown specialty: {}", specialty))
+            ),
+            _ => Ok(()),
+        }
+    }
+
     async fn verify_patient_exists(&self, patient_id: &Uuid, auth_token: &str) -> Result<(), AppointmentError> {
         let path = format!("/rest/v1/patients?id=eq.{}", patient_id);
         let result: Vec<Value> = self.supabase.request(
@@ -838,10 +1649,11 @@ impl AppointmentBookingService {
         &self,
         doctor_id: Uuid,
         request: BookAppointmentRequest,
+        duration_minutes: i32,
         auth_token: &str,
     ) -> Result<Appointment, AppointmentError> {
-        let end_time = request.appointment_date + Duration::minutes(request.duration_minutes as i64);
-        let now = Utc::now();
+        let end_time = request.appointment_date + Duration::minutes(duration_minutes as i64);
+        let now = self.clock.now();
 
         let appointment_data = json!({
             "patient_id": request.patient_id,
@@ -849,7 +1661,7 @@ impl AppointmentBookingService {
             "appointment_date": request.appointment_date.to_rfc3339(),
             "status": AppointmentStatus::Pending.to_string(),
             "appointment_type": request.appointment_type.to_string(),
-            "duration_minutes": request.duration_minutes,
+            "duration_minutes": duration_minutes,
             "timezone": request.timezone,
             "scheduled_start_time": request.appointment_date.to_rfc3339(),
             "scheduled_end_time": end_time.to_rfc3339(),
@@ -891,23 +1703,27 @@ impl AppointmentBookingService {
         let mut update_data = serde_json::Map::new();
 
         // Handle status changes
-        if let Some(status) = request.status {
+        if let Some(status) = &request.status {
             update_data.insert("status".to_string(), json!(status.to_string()));
             
             // Set timing based on status
             match status {
                 AppointmentStatus::InProgress => {
-                    update_data.insert("actual_start_time".to_string(), json!(Utc::now().to_rfc3339()));
+                    update_data.insert("actual_start_time".to_string(), json!(self.clock.now().to_rfc3339()));
                 },
-                AppointmentStatus::Completed => {
-                    if current_appointment.actual_start_time.is_some() {
-                        update_data.insert("actual_end_time".to_string(), json!(Utc::now().to_rfc3339()));
-                    }
+                AppointmentStatus::Completed if current_appointment.actual_start_time.is_some() => {
+                    update_data.insert("actual_end_time".to_string(), json!(self.clock.now().to_rfc3339()));
                 },
                 _ => {}
             }
         }
 
+        if request.status == Some(AppointmentStatus::Cancelled) {
+            if let Some(reason) = request.cancellation_reason {
+                update_data.insert("cancellation_reason".to_string(), json!(reason));
+            }
+        }
+
         // Handle notes updates
         if let Some(doctor_notes) = request.doctor_notes {
             update_data.insert("doctor_notes".to_string(), json!(doctor_notes));
@@ -930,35 +1746,35 @@ impl AppointmentBookingService {
             }
         }
 
-        update_data.insert("updated_at".to_string(), json!(Utc::now().to_rfc3339()));
+        update_data.insert("updated_at".to_string(), json!(self.clock.now().to_rfc3339()));
 
-        let path = format!("/rest/v1/appointments?id=eq.{}", current_appointment.id);
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
-
-        let result: Vec<Value> = self.supabase.request_with_headers(
-            Method::PATCH,
-            &path,
-            Some(auth_token),
-            Some(Value::Object(update_data)),
-            Some(headers),
-        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
-
-        if result.is_empty() {
-            return Err(AppointmentError::DatabaseError("Failed to update appointment".to_string()));
+        // Only write over the appointment as it was read, so a concurrent
+        // update isn't lost and a move lands in the slot that was checked
+        let is_move = request.reschedule_to.is_some();
+        match versioning::patch_appointment(&self.supabase, current_appointment, Value::Object(update_data), auth_token).await {
+            Err(AppointmentError::VersionConflict(_)) if is_move => {
+                warn!("Appointment {} changed while it was being rescheduled", current_appointment.id);
+                Err(AppointmentError::ConflictDetected { suggested_alternatives: vec![] })
+            }
+            result => result,
         }
-
-        let updated_appointment: Appointment = serde_json::from_value(result[0].clone())
-            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse updated appointment: {}", e)))?;
-
-        Ok(updated_appointment)
     }
 
-    fn validate_reschedule_timing(&self, appointment: &Appointment, new_time: DateTime<Utc>) -> Result<(), AppointmentError> {
-        let now = Utc::now();
+    /// Whether the appointment starts too soon to be moved without an override
+    fn within_reschedule_notice(&self, appointment: &Appointment) -> bool {
         let min_reschedule_notice = Duration::hours(self.validation_rules.allowed_reschedule_hours as i64);
+        appointment.scheduled_start_time <= self.clock.now() + min_reschedule_notice
+    }
+
+    fn validate_reschedule_timing(
+        &self,
+        appointment: &Appointment,
+        new_time: DateTime<Utc>,
+        enforce_notice: bool,
+    ) -> Result<(), AppointmentError> {
+        let now = self.clock.now();
 
-        if appointment.scheduled_start_time <= now + min_reschedule_notice {
+        if enforce_notice && self.within_reschedule_notice(appointment) {
             return Err(AppointmentError::InvalidTime(
                 format!("Appointment can only be rescheduled at least {} hours in advance", 
                        self.validation_rules.allowed_reschedule_hours)
@@ -970,11 +1786,13 @@ impl AppointmentBookingService {
             return Err(AppointmentError::InvalidTime("Rescheduled time must be in the future".to_string()));
         }
 
+        self.validate_business_hours(&appointment.appointment_type, new_time, appointment.duration_minutes)?;
+
         Ok(())
     }
 
     fn validate_cancellation_timing(&self, appointment: &Appointment) -> Result<(), AppointmentError> {
-        let now = Utc::now();
+        let now = self.clock.now();
         let min_cancellation_notice = Duration::hours(self.validation_rules.allowed_cancellation_hours as i64);
 
         // Check if appointment can be cancelled
@@ -1018,10 +1836,139 @@ impl AppointmentBookingService {
         // TODO: Implement post-cancellation tasks
         // - Send cancellation notifications
         // - Update calendar events
-        // - Log cancellation reason for analytics
         
-        debug!("Post-cancellation tasks completed for appointment {} (cancelled by {:?})", 
-               appointment.id, request.cancelled_by);
+        debug!("Post-cancellation tasks completed for appointment {} (cancelled by {:?}, reason {})", 
+               appointment.id, request.cancelled_by,
+               request.cancellation_reason.as_ref().map_or("unspecified", CancellationReason::category));
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// The appointments path for `query` in `SEARCH_ORDER`, with its limit and
+/// offset
+fn search_path(query: &AppointmentSearchQuery) -> Result<String, AppointmentError> {
+    let mut path = format!("/rest/v1/appointments?{}&order={}",
+                           search_filters(query)?.join("&"), SEARCH_ORDER);
+
+    if let Some(limit) = query.limit {
+        path.push_str(&format!("&limit={}", limit));
+    }
+    if let Some(offset) = query.offset {
+        path.push_str(&format!("&offset={}", offset));
+    }
+    Ok(path)
+}
+
+fn parse_appointments(rows: Vec<Value>) -> Result<Vec<Appointment>, AppointmentError> {
+    rows.into_iter()
+        .map(serde_json::from_value)
+        .collect::<std::result::Result<Vec<Appointment>, _>>()
+        .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse appointments: {}", e)))
+}
+
+/// PostgREST filters for everything `query` narrows on, without ordering or
+/// paging
+fn search_filters(query: &AppointmentSearchQuery) -> Result<Vec<String>, AppointmentError> {
+    let mut query_parts = Vec::new();
+
+    if let Some(patient_id) = query.patient_id {
+        query_parts.push(format!("patient_id=eq.{}", patient_id));
+    }
+    if let Some(doctor_id) = query.doctor_id {
+        query_parts.push(format!("doctor_id=eq.{}", doctor_id));
+    }
+    if let Some(status) = &query.status {
+        query_parts.push(format!("status=eq.{}", status));
+    }
+    if let Some(appointment_type) = &query.appointment_type {
+        query_parts.push(format!("appointment_type=eq.{}", appointment_type));
+    }
+    if let Some(from_date) = query.from_date {
+        query_parts.push(format!("scheduled_start_time=gte.{}", from_date.to_rfc3339()));
+    }
+    if let Some(to_date) = query.to_date {
+        query_parts.push(format!("scheduled_start_time=lte.{}", to_date.to_rfc3339()));
+    }
+    if let Some(reason) = &query.reason_contains {
+        // Full-text search rather than ilike, so an index on
+        // to_tsvector('english', patient_notes) can serve it
+        let terms = search_terms(reason);
+        if terms.is_empty() {
+            return Err(AppointmentError::ValidationError(
+                "reason_contains has no words to search for".to_string(),
+            ));
+        }
+        query_parts.push(format!("patient_notes=plfts(english).{}", terms.join("%20")));
+    }
+
+    Ok(query_parts)
+}
+
+/// Cancelled appointments among `appointments` counted by reason category,
+/// most common first
+fn cancellation_breakdown(appointments: &[Appointment]) -> Vec<CancellationReasonCount> {
+    let cancelled: Vec<&Appointment> = appointments.iter()
+        .filter(|apt| apt.status == AppointmentStatus::Cancelled)
+        .collect();
+
+    let mut counts = std::collections::HashMap::new();
+    for appointment in &cancelled {
+        let reason = appointment.cancellation_reason.as_ref()
+            .map_or("unspecified", CancellationReason::category);
+        *counts.entry(reason).or_insert(0) += 1;
+    }
+
+    let mut breakdown: Vec<CancellationReasonCount> = counts.into_iter()
+        .map(|(reason, count)| CancellationReasonCount {
+            reason: reason.to_string(),
+            count,
+            percentage: count as f32 * 100.0 / cancelled.len() as f32,
+        })
+        .collect();
+    breakdown.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.reason.cmp(&b.reason)));
+    breakdown
+}
+
+/// Completed consultations counted per disposition, in `OutcomeDisposition`
+/// order and leaving out dispositions nobody recorded, and how many
+/// consultations have no outcome yet
+fn outcome_breakdown<'a>(
+    outcomes: impl Iterator<Item = Option<&'a ConsultationOutcome>>,
+) -> (Vec<(OutcomeDisposition, i32)>, i32) {
+    let mut counts = std::collections::BTreeMap::new();
+    let mut without_outcome = 0;
+    for outcome in outcomes {
+        match outcome {
+            Some(outcome) => {
+                for disposition in &outcome.dispositions {
+                    *counts.entry(*disposition).or_insert(0) += 1;
+                }
+            }
+            None => without_outcome += 1,
+        }
+    }
+    (counts.into_iter().collect(), without_outcome)
+}
+
+/// Fail if an appointment of `duration_minutes` starting at `start` would
+/// overrun the schedule slot it starts in. Starts outside every slot (and
+/// doctors with no schedule that day) have no boundary to check.
+fn check_slot_fit(slots: &[AvailableSlot], start: DateTime<Utc>, duration_minutes: i32) -> Result<(), AppointmentError> {
+    let Some(slot) = slots.iter().find(|slot| slot.start_time <= start && start < slot.end_time) else {
+        return Ok(());
+    };
+
+    let room = (slot.end_time - start).num_minutes();
+    if duration_minutes as i64 > room {
+        return Err(AppointmentError::InvalidTime(format!(
+            "A {}-minute appointment at {} runs past the end of the doctor's {}-minute slot at {}; \
+             at most {} minutes fit",
+            duration_minutes,
+            start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            slot.duration_minutes,
+            slot.end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            room
+        )));
+    }
+    Ok(())
+}
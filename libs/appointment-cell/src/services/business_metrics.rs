@@ -0,0 +1,316 @@
+// libs/appointment-cell/src/services/business_metrics.rs
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use doctor_cell::models::DoctorSearchFilters;
+use doctor_cell::services::doctor::DoctorService;
+use shared_config::AppConfig;
+
+use crate::models::{AppointmentError, AppointmentEvent, AppointmentEventEnvelope, Degradation};
+use crate::services::events;
+use crate::services::holds::{hold_stats, HoldStats};
+
+/// Days of history kept for the bookings-per-day series
+pub const BOOKINGS_HISTORY_DAYS: i64 = 30;
+
+/// Clinic KPIs fed from appointment events: bookings per day, cancellation
+/// and no-show rates and booking lead time. Counts start when the process
+/// does, so rates are only meaningful once it has been up for a while.
+pub struct BusinessMetrics {
+    started_at: DateTime<Utc>,
+    state: Mutex<BusinessState>,
+}
+
+#[derive(Default)]
+struct BusinessState {
+    bookings_by_day: BTreeMap<NaiveDate, u64>,
+    bookings: u64,
+    cancellations: u64,
+    no_shows: u64,
+    completed: u64,
+    lead_time_minutes_total: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BusinessMetricsSnapshot {
+    pub since: DateTime<Utc>,
+    pub bookings_by_day: Vec<DailyBookings>,
+    pub bookings: u64,
+    pub cancellations: u64,
+    pub no_shows: u64,
+    pub completed: u64,
+    /// Cancellations per booking
+    pub cancellation_rate: Option<f64>,
+    /// No-shows among appointments that were either attended or missed
+    pub no_show_rate: Option<f64>,
+    /// Time from booking to the appointment's start
+    pub average_lead_time_hours: Option<f64>,
+    /// Slot holds and how many became bookings
+    pub holds: HoldStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyBookings {
+    pub date: NaiveDate,
+    pub bookings: u64,
+}
+
+/// Share of the specialty's scheduled time that was booked
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecialtyFillRate {
+    pub specialty: String,
+    pub doctors: usize,
+    pub available_minutes: i64,
+    pub booked_minutes: i64,
+    pub fill_rate: f64,
+}
+
+/// Fill rates plus how many doctors had to be left out of them
+#[derive(Debug, Clone, Serialize)]
+pub struct FillRateReport {
+    pub by_specialty: Vec<SpecialtyFillRate>,
+    pub doctors_left_out: usize,
+}
+
+impl FillRateReport {
+    pub fn degradation(&self) -> Degradation {
+        if self.doctors_left_out == 0 {
+            return Degradation::default();
+        }
+        Degradation::because(format!(
+            "Utilization for {} doctor(s) could not be computed; their schedules are not in the fill rates",
+            self.doctors_left_out
+        ))
+    }
+}
+
+/// The metrics shared by every request in this process
+pub fn business_metrics() -> &'static BusinessMetrics {
+    static METRICS: OnceLock<BusinessMetrics> = OnceLock::new();
+    METRICS.get_or_init(BusinessMetrics::new)
+}
+
+impl BusinessMetrics {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            state: Mutex::new(BusinessState::default()),
+        }
+    }
+
+    pub fn record(&self, envelope: &AppointmentEventEnvelope) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match envelope.event {
+            AppointmentEvent::Booked => {
+                state.bookings += 1;
+                *state.bookings_by_day.entry(envelope.occurred_at.date_naive()).or_default() += 1;
+
+                let lead_time = envelope.appointment.scheduled_start_time - envelope.occurred_at;
+                state.lead_time_minutes_total += lead_time.num_minutes().max(0);
+
+                let oldest = envelope.occurred_at.date_naive() - Duration::days(BOOKINGS_HISTORY_DAYS - 1);
+                state.bookings_by_day.retain(|date, _| *date >= oldest);
+            }
+            AppointmentEvent::Cancelled => state.cancellations += 1,
+            AppointmentEvent::NoShow => state.no_shows += 1,
+            AppointmentEvent::Completed => state.completed += 1,
+            _ => {}
+        }
+    }
+
+    pub fn snapshot(&self) -> BusinessMetricsSnapshot {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let attended_or_missed = state.completed + state.no_shows;
+
+        BusinessMetricsSnapshot {
+            since: self.started_at,
+            bookings_by_day: state.bookings_by_day.iter()
+                .map(|(date, bookings)| DailyBookings { date: *date, bookings: *bookings })
+                .collect(),
+            bookings: state.bookings,
+            cancellations: state.cancellations,
+            no_shows: state.no_shows,
+            completed: state.completed,
+            cancellation_rate: (state.bookings > 0)
+                .then(|| state.cancellations as f64 / state.bookings as f64),
+            no_show_rate: (attended_or_missed > 0)
+                .then(|| state.no_shows as f64 / attended_or_missed as f64),
+            average_lead_time_hours: (state.bookings > 0)
+                .then(|| state.lead_time_minutes_total as f64 / state.bookings as f64 / 60.0),
+            holds: hold_stats(),
+        }
+    }
+}
+
+/// Feed every appointment event into `business_metrics()`
+pub fn spawn_metrics_worker() -> JoinHandle<()> {
+    let mut receiver = events::subscribe();
+
+    tokio::spawn(async move {
+        info!("Business metrics worker started");
+        loop {
+            match receiver.recv().await {
+                Ok(envelope) => business_metrics().record(&envelope),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Business metrics worker fell behind, {} events were not counted", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Slot fill rate per specialty over `from..=to`, from each available
+/// doctor's schedule and bookings. Doctors whose utilization can't be
+/// computed are left out, and counted, rather than failing the whole report.
+pub async fn specialty_fill_rates(
+    config: &AppConfig,
+    from: NaiveDate,
+    to: NaiveDate,
+    auth_token: &str,
+) -> Result<FillRateReport, AppointmentError> {
+    let doctor_service = DoctorService::new(config);
+    let doctors = doctor_service.search_doctors(DoctorSearchFilters::default(), auth_token, None, None).await
+        .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+    let mut by_specialty: HashMap<String, (usize, i64, i64)> = HashMap::new();
+    let mut doctors_left_out = 0;
+    for doctor in doctors {
+        let utilization = match doctor_service.utilization(&doctor.id.to_string(), from, to, auth_token).await {
+            Ok(utilization) => utilization,
+            Err(e) => {
+                warn!("Leaving doctor {} out of fill rates: {}", doctor.id, e);
+                doctors_left_out += 1;
+                continue;
+            }
+        };
+        let entry = by_specialty.entry(doctor.specialty).or_default();
+        entry.0 += 1;
+        entry.1 += utilization.available_minutes;
+        entry.2 += utilization.booked_minutes;
+    }
+
+    Ok(FillRateReport {
+        by_specialty: fill_rates(by_specialty),
+        doctors_left_out,
+    })
+}
+
+fn fill_rates(by_specialty: HashMap<String, (usize, i64, i64)>) -> Vec<SpecialtyFillRate> {
+    let mut rates: Vec<SpecialtyFillRate> = by_specialty.into_iter()
+        .map(|(specialty, (doctors, available_minutes, booked_minutes))| SpecialtyFillRate {
+            specialty,
+            doctors,
+            available_minutes,
+            booked_minutes,
+            fill_rate: if available_minutes > 0 {
+                booked_minutes as f64 / available_minutes as f64
+            } else {
+                0.0
+            },
+        })
+        .collect();
+    rates.sort_by(|a, b| a.specialty.cmp(&b.specialty));
+    rates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Appointment, AppointmentStatus, AppointmentType};
+    use uuid::Uuid;
+
+    fn envelope(event: AppointmentEvent, occurred_at: DateTime<Utc>, starts_in_hours: i64) -> AppointmentEventEnvelope {
+        let start = occurred_at + Duration::hours(starts_in_hours);
+        AppointmentEventEnvelope {
+            id: Uuid::new_v4(),
+            event,
+            occurred_at,
+            appointment: Appointment {
+                id: Uuid::new_v4(),
+                patient_id: Uuid::new_v4(),
+                doctor_id: Uuid::new_v4(),
+                appointment_date: start,
+                status: AppointmentStatus::Pending,
+                appointment_type: AppointmentType::GeneralConsultation,
+                duration_minutes: 30,
+                timezone: "UTC".to_string(),
+                scheduled_start_time: start,
+                scheduled_end_time: start + Duration::minutes(30),
+                actual_start_time: None,
+                actual_end_time: None,
+                notes: None,
+                patient_notes: None,
+                doctor_notes: None,
+                prescription_issued: false,
+                medical_certificate_issued: false,
+                report_generated: false,
+                video_conference_link: None,
+                cancellation_reason: None,
+                outcome: None,
+                version: 0,
+                attendance_confirmed_at: None,
+                created_at: occurred_at,
+                updated_at: occurred_at,
+            },
+            emergency_contact: None,
+            cancellation_fee_applicable: None,
+        }
+    }
+
+    #[test]
+    fn test_rates_and_lead_time() {
+        let metrics = BusinessMetrics::new();
+        let now = Utc::now();
+
+        metrics.record(&envelope(AppointmentEvent::Booked, now, 24));
+        metrics.record(&envelope(AppointmentEvent::Booked, now, 72));
+        metrics.record(&envelope(AppointmentEvent::Booked, now - Duration::days(1), 48));
+        metrics.record(&envelope(AppointmentEvent::Booked, now - Duration::days(1), 48));
+        metrics.record(&envelope(AppointmentEvent::Cancelled, now, 24));
+        metrics.record(&envelope(AppointmentEvent::Completed, now, 0));
+        metrics.record(&envelope(AppointmentEvent::Completed, now, 0));
+        metrics.record(&envelope(AppointmentEvent::Completed, now, 0));
+        metrics.record(&envelope(AppointmentEvent::NoShow, now, 0));
+        metrics.record(&envelope(AppointmentEvent::Confirmed, now, 24));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.bookings, 4);
+        assert_eq!(snapshot.cancellation_rate, Some(0.25));
+        assert_eq!(snapshot.no_show_rate, Some(0.25));
+        assert_eq!(snapshot.average_lead_time_hours, Some(48.0));
+        let days: Vec<u64> = snapshot.bookings_by_day.iter().map(|day| day.bookings).collect();
+        assert_eq!(days, vec![2, 2]);
+    }
+
+    #[test]
+    fn test_bookings_by_day_keeps_recent_history_only() {
+        let metrics = BusinessMetrics::new();
+        let now = Utc::now();
+
+        metrics.record(&envelope(AppointmentEvent::Booked, now - Duration::days(BOOKINGS_HISTORY_DAYS), 24));
+        metrics.record(&envelope(AppointmentEvent::Booked, now, 24));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.bookings, 2);
+        assert_eq!(snapshot.bookings_by_day.len(), 1);
+        assert_eq!(snapshot.bookings_by_day[0].date, now.date_naive());
+    }
+
+    #[test]
+    fn test_fill_rates_by_specialty() {
+        let rates = fill_rates(HashMap::from([
+            ("Cardiology".to_string(), (2, 960, 240)),
+            ("Dermatology".to_string(), (1, 0, 0)),
+        ]));
+        assert_eq!(rates[0].specialty, "Cardiology");
+        assert_eq!(rates[0].fill_rate, 0.25);
+        assert_eq!(rates[1].fill_rate, 0.0);
+    }
+}
@@ -0,0 +1,335 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, NaiveDate, Utc};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use health_profile_cell::api::DocumentService;
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{
+    Appointment, AppointmentError, AppointmentStatus, IssueCertificateRequest, MedicalCertificate,
+};
+use crate::services::versioning;
+
+/// Longest sick-leave period a single certificate may cover
+pub const MAX_CERTIFICATE_DAYS: i64 = 28;
+
+/// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 60.0;
+
+/// Everything printed on a certificate
+#[derive(Debug, Clone)]
+pub struct CertificateDetails {
+    pub clinic_name: String,
+    pub clinic_address: String,
+    pub clinic_contact: String,
+    pub certificate_id: Uuid,
+    pub patient_name: String,
+    pub patient_date_of_birth: Option<String>,
+    pub doctor_name: String,
+    pub doctor_registration_number: String,
+    pub consultation_date: DateTime<Utc>,
+    pub reason: String,
+    pub unfit_from: NaiveDate,
+    pub unfit_until: NaiveDate,
+    pub remarks: Option<String>,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// Generates medical certificates as PDFs, files them in the patient's
+/// documents and sets the appointment's `medical_certificate_issued` flag.
+pub struct CertificateService {
+    supabase: SupabaseClient,
+    document_service: DocumentService,
+    audit_log: AuditLog,
+    clinic_name: String,
+    clinic_address: String,
+    clinic_contact: String,
+}
+
+impl CertificateService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            document_service: DocumentService::new(config),
+            audit_log: AuditLog::new(config),
+            clinic_name: config.clinic_name.clone(),
+            clinic_address: config.clinic_address.clone(),
+            clinic_contact: config.clinic_contact.clone(),
+        }
+    }
+
+    pub async fn issue(
+        &self,
+        appointment: &Appointment,
+        issued_by: &str,
+        request: IssueCertificateRequest,
+        auth_token: &str,
+    ) -> Result<MedicalCertificate, AppointmentError> {
+        debug!("Issuing medical certificate for appointment {}", appointment.id);
+
+        if !matches!(appointment.status, AppointmentStatus::InProgress | AppointmentStatus::Completed) {
+            return Err(AppointmentError::InvalidStatusTransition(appointment.status.clone()));
+        }
+        validate_certificate_request(&request)?;
+
+        let patient = self.fetch_one("patients", appointment.patient_id, auth_token).await?
+            .ok_or(AppointmentError::PatientNotFound)?;
+        let doctor = self.fetch_one("doctors", appointment.doctor_id, auth_token).await?
+            .ok_or(AppointmentError::DoctorNotFound)?;
+
+        let doctor_registration_number = doctor["license_number"].as_str()
+            .filter(|number| !number.trim().is_empty())
+            .ok_or_else(|| AppointmentError::ValidationError(
+                "The doctor has no registration number on file".to_string()
+            ))?
+            .to_string();
+
+        let details = CertificateDetails {
+            clinic_name: self.clinic_name.clone(),
+            clinic_address: self.clinic_address.clone(),
+            clinic_contact: self.clinic_contact.clone(),
+            certificate_id: Uuid::new_v4(),
+            patient_name: patient["full_name"].as_str().unwrap_or("Unknown patient").to_string(),
+            patient_date_of_birth: patient["date_of_birth"].as_str().map(str::to_string),
+            doctor_name: doctor["full_name"].as_str().unwrap_or("Unknown doctor").to_string(),
+            doctor_registration_number,
+            consultation_date: appointment.actual_start_time.unwrap_or(appointment.scheduled_start_time),
+            reason: request.reason,
+            unfit_from: request.unfit_from,
+            unfit_until: request.unfit_until,
+            remarks: request.remarks,
+            issued_at: Utc::now(),
+        };
+
+        let pdf = render_certificate_pdf(&details);
+        let title = format!("Medical certificate {}", details.issued_at.format("%Y-%m-%d"));
+        let document = self.document_service.upload_document(
+            &appointment.patient_id.to_string(),
+            &title,
+            BASE64.encode(&pdf),
+            "application/pdf",
+            auth_token,
+        ).await.map_err(|e| AppointmentError::ExternalServiceError(format!("Failed to store certificate: {}", e)))?;
+
+        let update_data = json!({
+            "medical_certificate_issued": true,
+            "updated_at": Utc::now().to_rfc3339()
+        });
+        versioning::set_appointment_fields(&self.supabase, appointment.id, update_data, auth_token).await?;
+
+        let audit_entry = AuditEntry::new(issued_by, "medical_certificate.issued", "document", document.id.to_string())
+            .with_details(json!({
+                "appointment_id": appointment.id,
+                "patient_id": appointment.patient_id,
+                "certificate_id": details.certificate_id
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit medical certificate for appointment {}: {}", appointment.id, e);
+        }
+
+        info!("Medical certificate {} issued for appointment {}", details.certificate_id, appointment.id);
+        Ok(MedicalCertificate {
+            appointment_id: appointment.id,
+            document_id: document.id,
+            download_url: document.file_url,
+            issued_at: details.issued_at,
+        })
+    }
+
+    async fn fetch_one(&self, table: &str, id: Uuid, auth_token: &str) -> Result<Option<Value>, AppointmentError> {
+        let path = format!("/rest/v1/{}?id=eq.{}", table, id);
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        Ok(result.into_iter().next())
+    }
+}
+
+fn validate_certificate_request(request: &IssueCertificateRequest) -> Result<(), AppointmentError> {
+    if request.reason.trim().is_empty() {
+        return Err(AppointmentError::ValidationError("A reason is required".to_string()));
+    }
+    if request.unfit_until < request.unfit_from {
+        return Err(AppointmentError::ValidationError(
+            "unfit_until cannot be before unfit_from".to_string()
+        ));
+    }
+    if (request.unfit_until - request.unfit_from).num_days() + 1 > MAX_CERTIFICATE_DAYS {
+        return Err(AppointmentError::ValidationError(format!(
+            "A certificate can cover at most {} days", MAX_CERTIFICATE_DAYS
+        )));
+    }
+    Ok(())
+}
+
+/// Lay out a one-page A4 certificate using the built-in Helvetica fonts
+pub fn render_certificate_pdf(details: &CertificateDetails) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let regular_font_id = Ref::new(4);
+    let bold_font_id = Ref::new(5);
+    let content_id = Ref::new(6);
+    let info_id = Ref::new(7);
+    let regular = Name(b"F1");
+    let bold = Name(b"F2");
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+    page.parent(page_tree_id);
+    page.contents(content_id);
+    let mut resources = page.resources();
+    resources.fonts().pair(regular, regular_font_id).pair(bold, bold_font_id);
+    resources.finish();
+    page.finish();
+
+    pdf.type1_font(regular_font_id).base_font(Name(b"Helvetica"));
+    pdf.type1_font(bold_font_id).base_font(Name(b"Helvetica-Bold"));
+    pdf.document_info(info_id)
+        .title(TextStr("Medical Certificate"))
+        .producer(TextStr(&details.clinic_name));
+
+    let mut lines: Vec<(Name, f32, String)> = vec![(bold, 20.0, details.clinic_name.clone())];
+    for branding in [&details.clinic_address, &details.clinic_contact] {
+        if !branding.is_empty() {
+            lines.push((regular, 10.0, branding.clone()));
+        }
+    }
+    lines.push((regular, 10.0, String::new()));
+    lines.push((bold, 16.0, "MEDICAL CERTIFICATE".to_string()));
+    lines.push((regular, 9.0, format!("Certificate no. {}", details.certificate_id)));
+    lines.push((regular, 10.0, String::new()));
+
+    let patient = match &details.patient_date_of_birth {
+        Some(dob) => format!("{} (date of birth {})", details.patient_name, dob),
+        None => details.patient_name.clone(),
+    };
+    let days = (details.unfit_until - details.unfit_from).num_days() + 1;
+    let statement = format!(
+        "I certify that I examined {} on {} and that, in my opinion, they are unfit for work \
+         or study from {} to {} inclusive ({} day{}).",
+        patient,
+        details.consultation_date.format("%d %B %Y"),
+        details.unfit_from.format("%d %B %Y"),
+        details.unfit_until.format("%d %B %Y"),
+        days,
+        if days == 1 { "" } else { "s" },
+    );
+    for line in wrap_text(&statement, 85) {
+        lines.push((regular, 11.0, line));
+    }
+    lines.push((regular, 11.0, String::new()));
+    for line in wrap_text(&format!("Reason: {}", details.reason), 85) {
+        lines.push((regular, 11.0, line));
+    }
+    if let Some(remarks) = details.remarks.as_deref().filter(|r| !r.trim().is_empty()) {
+        for line in wrap_text(&format!("Remarks: {}", remarks), 85) {
+            lines.push((regular, 11.0, line));
+        }
+    }
+    lines.push((regular, 11.0, String::new()));
+    lines.push((regular, 11.0, String::new()));
+    lines.push((bold, 11.0, details.doctor_name.clone()));
+    lines.push((regular, 10.0, format!("Registration no. {}", details.doctor_registration_number)));
+    lines.push((regular, 10.0, format!("Issued {}", details.issued_at.format("%d %B %Y %H:%M UTC"))));
+
+    let mut content = Content::new();
+    let mut y = PAGE_HEIGHT - MARGIN;
+    for (font, size, text) in &lines {
+        y -= size * 1.5;
+        content.begin_text();
+        content.set_font(*font, *size);
+        content.next_line(MARGIN, y);
+        content.show(Str(pdf_text(text).as_bytes()));
+        content.end_text();
+    }
+
+    // Rule under the letterhead
+    let rule_y = PAGE_HEIGHT - MARGIN - 20.0 * 1.5 - 8.0;
+    content.set_line_width(0.75);
+    content.move_to(MARGIN, rule_y);
+    content.line_to(PAGE_WIDTH - MARGIN, rule_y);
+    content.stroke();
+
+    pdf.stream(content_id, &content.finish());
+    pdf.finish()
+}
+
+/// The standard fonts only cover Latin text; anything else is replaced
+fn pdf_text(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' })
+        .collect()
+}
+
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_certificate_pdf() {
+        let details = CertificateDetails {
+            clinic_name: "Amae Clinic".to_string(),
+            clinic_address: "1 High Street".to_string(),
+            clinic_contact: String::new(),
+            certificate_id: Uuid::new_v4(),
+            patient_name: "Jane Doe".to_string(),
+            patient_date_of_birth: Some("1990-01-01".to_string()),
+            doctor_name: "Dr. Test".to_string(),
+            doctor_registration_number: "LIC123456".to_string(),
+            consultation_date: Utc::now(),
+            reason: "Influenza".to_string(),
+            unfit_from: NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(),
+            unfit_until: NaiveDate::from_ymd_opt(2024, 12, 27).unwrap(),
+            remarks: None,
+            issued_at: Utc::now(),
+        };
+
+        let pdf = render_certificate_pdf(&details);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(text.contains("Registration no. LIC123456"));
+        assert!(text.contains("Reason: Influenza"));
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let lines = wrap_text("one two three four five", 9);
+        assert_eq!(lines, vec!["one two", "three", "four five"]);
+        assert!(wrap_text("", 10).is_empty());
+    }
+}
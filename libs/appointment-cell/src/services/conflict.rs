@@ -1,118 +1,235 @@
 // libs/appointment-cell/src/services/conflict.rs
 use anyhow::Result;
 use chrono::{DateTime, Utc, Duration, Timelike};
+use futures::stream::{self, StreamExt};
 use reqwest::Method;
+use serde::Serialize;
 use serde_json::Value;
 use tracing::{debug, warn};
 use uuid::Uuid;
 
 use std::sync::Arc;
+use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 
 use crate::models::{
     Appointment, AppointmentStatus, AppointmentType, ConflictCheckRequest, 
     ConflictCheckResponse, SuggestedSlot, AppointmentError
 };
+use crate::services::holds::{filter_time, HOLDS_TABLE};
+
+/// Most alternatives offered after a conflict
+const MAX_SUGGESTED_ALTERNATIVES: usize = 5;
+
+/// Days after the conflicting one searched for alternatives
+const ALTERNATIVE_SEARCH_DAYS: i64 = 3;
+
+/// How a batch of conflict checks is run
+#[derive(Debug, Clone, Copy)]
+pub struct BatchLimits {
+    /// Checks in flight at once
+    pub concurrency: usize,
+    /// Time each check gets before it's reported as failed
+    pub timeout: std::time::Duration,
+    /// Most checks one batch may hold
+    pub max_batch_size: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            concurrency: 5,
+            timeout: std::time::Duration::from_secs(10),
+            max_batch_size: 100,
+        }
+    }
+}
+
+impl BatchLimits {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            concurrency: config.batch_conflict_check_concurrency.max(1),
+            timeout: std::time::Duration::from_secs(config.batch_conflict_check_timeout_seconds),
+            max_batch_size: config.batch_conflict_check_max_size,
+        }
+    }
+}
+
+/// Counts over a batch of conflict checks
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// One result per check, in the order they were asked for. A failed or
+/// timed out check doesn't fail the others.
+#[derive(Debug)]
+pub struct BatchConflictCheck {
+    pub results: Vec<Result<ConflictCheckResponse, AppointmentError>>,
+    pub summary: BatchSummary,
+}
 
 pub struct ConflictDetectionService {
     supabase: Arc<SupabaseClient>,
+    batch_limits: BatchLimits,
 }
 
 impl ConflictDetectionService {
     pub fn new(supabase: Arc<SupabaseClient>) -> Self {
-        Self { supabase }
+        Self {
+            supabase,
+            batch_limits: BatchLimits::default(),
+        }
+    }
+
+    /// Run batches with `limits` instead of the defaults
+    pub fn with_batch_limits(mut self, limits: BatchLimits) -> Self {
+        self.batch_limits = limits;
+        self
     }
 
     /// Check for appointment conflicts for a doctor at a specific time
-    pub fn check_conflicts<'a>(
-        &'a self,
+    pub async fn check_conflicts(
+        &self,
         doctor_id: Uuid,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
         exclude_appointment_id: Option<Uuid>,
-        auth_token: &'a str,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<ConflictCheckResponse, AppointmentError>> + Send + 'a>> {
-        Box::pin(async move {
-            debug!("Checking conflicts for doctor {} from {} to {}", 
-                   doctor_id, start_time, end_time);
-
-            // Get existing appointments for the doctor in the time range
-            let existing_appointments = self.get_doctor_appointments_in_range(
+        auth_token: &str,
+    ) -> Result<ConflictCheckResponse, AppointmentError> {
+        self.check_conflicts_with_details(doctor_id, start_time, end_time, None, exclude_appointment_id, auth_token).await
+    }
+
+    /// Check for conflicts when booking an appointment of `capacity.0`, which
+    /// may overlap with up to `capacity.1 - 1` other appointments of the same
+    /// type. Any overlapping appointment of a different type is a conflict.
+    /// Without a capacity any overlap is a conflict.
+    ///
+    /// On a conflict `suggested_alternatives` holds the doctor's nearest free
+    /// slots of the same length, so the client can offer to rebook.
+    pub async fn check_conflicts_with_details(
+        &self,
+        doctor_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        capacity: Option<(&AppointmentType, i32)>,
+        exclude_appointment_id: Option<Uuid>,
+        auth_token: &str,
+    ) -> Result<ConflictCheckResponse, AppointmentError> {
+        let mut response = self.detect_conflicts(
+            doctor_id,
+            start_time,
+            end_time,
+            capacity,
+            exclude_appointment_id,
+            auth_token,
+        ).await?;
+
+        if response.has_conflict {
+            warn!("Conflict detected for doctor {} - {} conflicting appointments", 
+                  doctor_id, response.conflicting_appointments.len());
+
+            // Suggestions are best effort; the conflict itself is the answer
+            response.suggested_alternatives = self.generate_alternative_slots(
                 doctor_id,
                 start_time,
                 end_time,
+                capacity,
                 exclude_appointment_id,
                 auth_token,
-            ).await?;
-
-            let mut conflicting_appointments = Vec::new();
-
-            // Check for overlaps
-            for appointment in existing_appointments {
-                if self.appointments_overlap(
-                    start_time, 
-                    end_time, 
-                    appointment.scheduled_start_time, 
-                    appointment.scheduled_end_time
-                ) {
-                    // Only consider active appointments as conflicts
-                    if self.is_active_appointment(&appointment.status) {
-                        conflicting_appointments.push(appointment);
-                    }
-                }
-            }
+            ).await.unwrap_or_else(|e| {
+                warn!("Failed to suggest alternatives for doctor {}: {}", doctor_id, e);
+                vec![]
+            });
+        }
 
-            let has_conflict = !conflicting_appointments.is_empty();
+        Ok(response)
+    }
 
-            // Generate suggestions if there's a conflict
-            let suggested_alternatives = if has_conflict {
-                self.generate_alternative_slots(
-                    doctor_id,
-                    start_time,
-                    end_time,
-                    auth_token,
-                ).await.unwrap_or_default()
-            } else {
-                vec![]
-            };
+    /// Whether a slot hold other than `except_hold` covers any of
+    /// `start_time..end_time` for the doctor. Holds lapse at `expires_at`,
+    /// so only ones still live at `now` count.
+    pub async fn is_held(
+        &self,
+        doctor_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        except_hold: Option<Uuid>,
+        now: DateTime<Utc>,
+        auth_token: &str,
+    ) -> Result<bool, AppointmentError> {
+        let mut path = format!(
+            "/rest/v1/{}?doctor_id=eq.{}&start_time=lt.{}&occupied_until=gt.{}&expires_at=gt.{}&select=hold_id&limit=1",
+            HOLDS_TABLE,
+            doctor_id,
+            filter_time(end_time),
+            filter_time(start_time),
+            filter_time(now)
+        );
+        if let Some(hold_id) = except_hold {
+            path.push_str(&format!("&hold_id=neq.{}", hold_id));
+        }
 
-            if has_conflict {
-                warn!("Conflict detected for doctor {} - {} conflicting appointments", 
-                      doctor_id, conflicting_appointments.len());
-            }
+        let holds: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
 
-            Ok(ConflictCheckResponse {
-                has_conflict,
-                conflicting_appointments,
-                suggested_alternatives,
-            })
-        })
+        Ok(!holds.is_empty())
     }
 
-    /// Perform a bulk conflict check for multiple time slots
-    pub fn bulk_conflict_check<'a>(
-        &'a self,
+    /// Check many time slots at once, up to `BatchLimits::concurrency` at a
+    /// time. A batch over `BatchLimits::max_batch_size` is refused whole.
+    pub async fn bulk_conflict_check(
+        &self,
         requests: Vec<ConflictCheckRequest>,
-        auth_token: &'a str,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<ConflictCheckResponse>, AppointmentError>> + Send + 'a>> {
-        Box::pin(async move {
-            debug!("Performing bulk conflict check for {} requests", requests.len());
-
-            let mut responses = Vec::with_capacity(requests.len());
+        auth_token: &str,
+    ) -> Result<BatchConflictCheck, AppointmentError> {
+        debug!("Performing bulk conflict check for {} requests", requests.len());
+
+        let limits = self.batch_limits;
+        if requests.len() > limits.max_batch_size {
+            return Err(AppointmentError::ValidationError(format!(
+                "A batch can hold at most {} conflict checks, got {}",
+                limits.max_batch_size, requests.len()
+            )));
+        }
 
-            for request in requests {
-                let response = self.check_conflicts(
+        let results: Vec<Result<ConflictCheckResponse, AppointmentError>> = stream::iter(requests)
+            .map(|request| async move {
+                let check = self.check_conflicts(
                     request.doctor_id,
                     request.start_time,
                     request.end_time,
                     request.exclude_appointment_id,
                     auth_token,
-                ).await?;
-                responses.push(response);
-            }
+                );
+                tokio::time::timeout(limits.timeout, check).await.unwrap_or_else(|_| {
+                    Err(AppointmentError::DatabaseError(format!(
+                        "Conflict check for doctor {} timed out after {}s",
+                        request.doctor_id, limits.timeout.as_secs()
+                    )))
+                })
+            })
+            .buffered(limits.concurrency.max(1))
+            .collect()
+            .await;
+
+        let succeeded = results.iter().filter(|result| result.is_ok()).count();
+        let summary = BatchSummary {
+            total: results.len(),
+            succeeded,
+            failed: results.len() - succeeded,
+        };
+        if summary.failed > 0 {
+            warn!("{} of {} conflict checks in a batch failed", summary.failed, summary.total);
+        }
 
-            Ok(responses)
-        })
+        Ok(BatchConflictCheck { results, summary })
     }
 
     /// Check if a patient has too many appointments in a day (business rule validation)
@@ -161,10 +278,11 @@ impl ConflictDetectionService {
         let extended_start = start_time - buffer_duration;
         let extended_end = end_time + buffer_duration;
 
-        let conflict_response = self.check_conflicts(
+        let conflict_response = self.detect_conflicts(
             doctor_id,
             extended_start,
             extended_end,
+            None,
             exclude_appointment_id,
             auth_token,
         ).await?;
@@ -172,10 +290,12 @@ impl ConflictDetectionService {
         Ok(!conflict_response.has_conflict)
     }
 
-    /// Find the next available slot after a conflict (basic implementation)
+    /// Find the next available slot after a conflict (basic implementation).
+    /// The suggestion is for the appointment type asked about.
     pub async fn find_next_available_slot(
         &self,
         doctor_id: Uuid,
+        appointment_type: &AppointmentType,
         preferred_start: DateTime<Utc>,
         duration_minutes: i32,
         max_search_days: i32,
@@ -194,11 +314,12 @@ impl ConflictDetectionService {
         while current_time < search_end {
             let slot_end = current_time + duration;
 
-            let conflict_response = self.check_conflicts(
+            let conflict_response = self.detect_conflicts(
                 doctor_id,
                 current_time,
                 slot_end,
                 None,
+                None,
                 auth_token,
             ).await?;
 
@@ -209,7 +330,7 @@ impl ConflictDetectionService {
                         start_time: current_time,
                         end_time: slot_end,
                         doctor_id,
-                        appointment_type: AppointmentType::GeneralConsultation, // Default
+                        appointment_type: appointment_type.clone(),
                     }));
                 }
             }
@@ -224,6 +345,68 @@ impl ConflictDetectionService {
     // PRIVATE HELPER METHODS
     // ==============================================================================
 
+    /// Conflicts for one time range, without looking for alternatives
+    async fn detect_conflicts(
+        &self,
+        doctor_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        capacity: Option<(&AppointmentType, i32)>,
+        exclude_appointment_id: Option<Uuid>,
+        auth_token: &str,
+    ) -> Result<ConflictCheckResponse, AppointmentError> {
+        debug!("Checking conflicts for doctor {} from {} to {}", 
+               doctor_id, start_time, end_time);
+
+        let existing_appointments = self.get_doctor_appointments_in_range(
+            doctor_id,
+            start_time,
+            end_time,
+            exclude_appointment_id,
+            auth_token,
+        ).await?;
+
+        let conflicting_appointments = self.find_conflicts(&existing_appointments, start_time, end_time, capacity);
+
+        Ok(ConflictCheckResponse {
+            has_conflict: !conflicting_appointments.is_empty(),
+            conflicting_appointments,
+            suggested_alternatives: vec![],
+        })
+    }
+
+    /// The active appointments in `existing` that stop `start_time..end_time`
+    /// from being booked; empty when the range is free
+    fn find_conflicts(
+        &self,
+        existing: &[Appointment],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        capacity: Option<(&AppointmentType, i32)>,
+    ) -> Vec<Appointment> {
+        let overlapping: Vec<Appointment> = existing.iter()
+            .filter(|apt| self.is_active_appointment(&apt.status))
+            .filter(|apt| self.appointments_overlap(
+                start_time,
+                end_time,
+                apt.scheduled_start_time,
+                apt.scheduled_end_time,
+            ))
+            .cloned()
+            .collect();
+
+        let has_conflict = match capacity {
+            Some((appointment_type, max_concurrent)) => {
+                let all_same_type = overlapping.iter()
+                    .all(|apt| apt.appointment_type == *appointment_type);
+                !all_same_type || overlapping.len() >= max_concurrent.max(1) as usize
+            }
+            None => !overlapping.is_empty(),
+        };
+
+        if has_conflict { overlapping } else { vec![] }
+    }
+
     async fn get_doctor_appointments_in_range(
         &self,
         doctor_id: Uuid,
@@ -253,7 +436,7 @@ impl ConflictDetectionService {
         ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
 
         let appointments: Vec<Appointment> = result.into_iter()
-            .map(|apt| serde_json::from_value(apt))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<Appointment>, _>>()
             .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse appointments: {}", e)))?;
 
@@ -267,7 +450,7 @@ impl ConflictDetectionService {
         end_time: DateTime<Utc>,
         auth_token: &str,
     ) -> Result<Vec<Appointment>, AppointmentError> {
-        let query_parts = vec![
+        let query_parts = [
             format!("patient_id=eq.{}", patient_id),
             format!("scheduled_start_time=gte.{}", start_time.to_rfc3339()),
             format!("scheduled_start_time=lte.{}", end_time.to_rfc3339()),
@@ -284,7 +467,7 @@ impl ConflictDetectionService {
         ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
 
         let appointments: Vec<Appointment> = result.into_iter()
-            .map(|apt| serde_json::from_value(apt))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<Appointment>, _>>()
             .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse appointments: {}", e)))?;
 
@@ -306,72 +489,63 @@ impl ConflictDetectionService {
         matches!(status,
             AppointmentStatus::Pending |
             AppointmentStatus::Confirmed |
-            AppointmentStatus::InProgress
+            AppointmentStatus::InProgress |
+            AppointmentStatus::Rescheduled
         )
     }
 
+    /// The doctor's nearest free slots from the start of the conflicting day
+    /// through the next few days, checked against one fetch of their schedule
     async fn generate_alternative_slots(
         &self,
         doctor_id: Uuid,
         original_start: DateTime<Utc>,
         original_end: DateTime<Utc>,
+        capacity: Option<(&AppointmentType, i32)>,
+        exclude_appointment_id: Option<Uuid>,
         auth_token: &str,
     ) -> Result<Vec<SuggestedSlot>, AppointmentError> {
         debug!("Generating alternative slots for doctor {}", doctor_id);
 
-        let duration_minutes = (original_end - original_start).num_minutes() as i32;
-        let mut suggestions = Vec::new();
+        let duration = original_end - original_start;
+        let appointment_type = capacity
+            .map(|(appointment_type, _)| appointment_type.clone())
+            .unwrap_or(AppointmentType::GeneralConsultation);
 
-        // Try to find slots on the same day
-        let same_day_start = original_start.date_naive().and_hms_opt(8, 0, 0).unwrap().and_utc();
-        let same_day_end = original_start.date_naive().and_hms_opt(20, 0, 0).unwrap().and_utc();
+        let search_start = original_start.date_naive().and_hms_opt(8, 0, 0).unwrap().and_utc();
+        let search_end = (original_start + Duration::days(ALTERNATIVE_SEARCH_DAYS))
+            .date_naive().and_hms_opt(20, 0, 0).unwrap().and_utc();
 
-        // Search in 30-minute increments on the same day
-        let mut current_time = same_day_start;
-        while current_time < same_day_end && suggestions.len() < 3 {
-            let slot_end = current_time + Duration::minutes(duration_minutes as i64);
-
-            if current_time != original_start {  // Skip the original conflicting time
-                let conflict_response = self.check_conflicts(
-                    doctor_id,
-                    current_time,
-                    slot_end,
-                    None,
-                    auth_token,
-                ).await?;
+        let existing_appointments = self.get_doctor_appointments_in_range(
+            doctor_id,
+            search_start,
+            search_end + duration,
+            exclude_appointment_id,
+            auth_token,
+        ).await?;
 
-                if !conflict_response.has_conflict && self.is_during_working_hours(current_time) {
-                    suggestions.push(SuggestedSlot {
-                        start_time: current_time,
-                        end_time: slot_end,
-                        doctor_id,
-                        appointment_type: AppointmentType::GeneralConsultation,
-                    });
-                }
-            }
+        let now = Utc::now();
+        let mut suggestions = Vec::new();
 
-            current_time += Duration::minutes(30);
-        }
+        // Search in 30-minute increments
+        let mut current_time = search_start;
+        while current_time < search_end && suggestions.len() < MAX_SUGGESTED_ALTERNATIVES {
+            let slot_end = current_time + duration;
 
-        // If not enough slots found on same day, try next few days
-        if suggestions.len() < 3 {
-            for day_offset in 1..=3 {
-                let next_day = original_start + Duration::days(day_offset);
-                let day_start = next_day.date_naive().and_hms_opt(8, 0, 0).unwrap().and_utc();
+            let is_candidate = current_time != original_start
+                && current_time > now
+                && self.is_during_working_hours(current_time);
 
-                if let Ok(Some(slot)) = self.find_next_available_slot(
+            if is_candidate && self.find_conflicts(&existing_appointments, current_time, slot_end, capacity).is_empty() {
+                suggestions.push(SuggestedSlot {
+                    start_time: current_time,
+                    end_time: slot_end,
                     doctor_id,
-                    day_start,
-                    duration_minutes,
-                    1, // Search only within that day
-                    auth_token,
-                ).await {
-                    suggestions.push(slot);
-                    if suggestions.len() >= 5 {
-                        break;
-                    }
-                }
+                    appointment_type: appointment_type.clone(),
+                });
             }
+
+            current_time += Duration::minutes(30);
         }
 
         Ok(suggestions)
@@ -381,6 +555,6 @@ impl ConflictDetectionService {
     /// NOTE: In production, this should integrate with doctor availability service
     fn is_during_working_hours(&self, time: DateTime<Utc>) -> bool {
         let hour = time.hour();
-        hour >= 8 && hour < 20
+        (8..20).contains(&hour)
     }
 }
\ No newline at end of file
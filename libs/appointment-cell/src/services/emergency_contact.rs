@@ -0,0 +1,108 @@
+// libs/appointment-cell/src/services/emergency_contact.rs
+use std::sync::Arc;
+
+use tracing::{debug, info};
+
+use health_profile_cell::api::HealthProfileService;
+use patient_cell::models::ConsentType;
+use patient_cell::services::consent::ConsentService;
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{Appointment, AppointmentError, AppointmentEvent, AppointmentType, EmergencyContact};
+use crate::services::events;
+use crate::services::webhooks::WebhookOutbox;
+
+/// Safeguarding alerts to a patient's emergency contact. The alert goes out
+/// as an `emergency_contact_alert` event, so webhook subscribers (SMS or
+/// call-centre integrations) do the actual contacting.
+pub struct EmergencyContactService {
+    profile_service: HealthProfileService,
+    consent_service: ConsentService,
+    webhook_outbox: WebhookOutbox,
+}
+
+impl EmergencyContactService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            profile_service: HealthProfileService::new(config),
+            consent_service: ConsentService::new(config),
+            webhook_outbox: WebhookOutbox::new(Arc::new(SupabaseClient::new(config)), config),
+        }
+    }
+
+    /// Alert the emergency contact if `event` warrants it and the patient has
+    /// opted in, named a contact phone and consented. Returns whether an
+    /// alert was raised.
+    pub async fn notify_for_event(
+        &self,
+        event: AppointmentEvent,
+        appointment: &Appointment,
+        auth_token: &str,
+    ) -> Result<bool, AppointmentError> {
+        if !requires_emergency_contact(event, &appointment.appointment_type) {
+            return Ok(false);
+        }
+
+        let profile = self.profile_service
+            .get_profile(&appointment.patient_id.to_string(), auth_token)
+            .await
+            .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let phone = profile.emergency_contact_phone
+            .map(|phone| phone.trim().to_string())
+            .filter(|phone| !phone.is_empty());
+        let phone = match phone {
+            Some(phone) if profile.notify_emergency_contact => phone,
+            _ => {
+                debug!("Patient {} has not opted in to emergency contact alerts", appointment.patient_id);
+                return Ok(false);
+            }
+        };
+
+        let consented = self.consent_service
+            .has_valid_consent(appointment.patient_id, ConsentType::EmergencyContact, auth_token)
+            .await
+            .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+        if !consented {
+            info!(
+                "Not alerting emergency contact of patient {}: emergency contact consent not given",
+                appointment.patient_id
+            );
+            return Ok(false);
+        }
+
+        let contact = EmergencyContact {
+            name: profile.emergency_contact_name,
+            phone,
+        };
+        let envelope = events::publish_emergency_contact_alert(appointment, contact);
+        self.webhook_outbox.enqueue(&envelope).await;
+
+        info!(
+            "Emergency contact alert {} raised for {} on appointment {}",
+            envelope.id, event, appointment.id
+        );
+        Ok(true)
+    }
+}
+
+/// Events that put a vulnerable patient's safety in question: missing an
+/// urgent or mental health appointment
+pub fn requires_emergency_contact(event: AppointmentEvent, appointment_type: &AppointmentType) -> bool {
+    event == AppointmentEvent::NoShow
+        && matches!(appointment_type, AppointmentType::Urgent | AppointmentType::MentalHealth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_missed_urgent_and_mental_health_appointments_alert() {
+        assert!(requires_emergency_contact(AppointmentEvent::NoShow, &AppointmentType::Urgent));
+        assert!(requires_emergency_contact(AppointmentEvent::NoShow, &AppointmentType::MentalHealth));
+        assert!(!requires_emergency_contact(AppointmentEvent::NoShow, &AppointmentType::GeneralConsultation));
+        assert!(!requires_emergency_contact(AppointmentEvent::Cancelled, &AppointmentType::Urgent));
+    }
+}
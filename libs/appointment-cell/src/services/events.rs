@@ -0,0 +1,65 @@
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use tokio::sync::broadcast;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::models::{Appointment, AppointmentEvent, AppointmentEventEnvelope, EmergencyContact};
+
+/// Events buffered per subscriber before a slow subscriber starts lagging
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+static EVENT_BUS: OnceLock<broadcast::Sender<AppointmentEventEnvelope>> = OnceLock::new();
+
+fn bus() -> &'static broadcast::Sender<AppointmentEventEnvelope> {
+    EVENT_BUS.get_or_init(|| broadcast::channel(EVENT_CHANNEL_CAPACITY).0)
+}
+
+/// Publish an appointment event to every in-process subscriber. Publishing
+/// never fails; events raised while nobody is subscribed are dropped, so
+/// webhooks are queued through `WebhookOutbox` instead.
+pub fn publish(event: AppointmentEvent, appointment: &Appointment) -> AppointmentEventEnvelope {
+    publish_envelope(event, appointment, None, None)
+}
+
+/// Publish a `cancelled` event telling billing whether a late-cancellation
+/// fee applies
+pub fn publish_cancellation(appointment: &Appointment, cancellation_fee_applicable: bool) -> AppointmentEventEnvelope {
+    publish_envelope(AppointmentEvent::Cancelled, appointment, None, Some(cancellation_fee_applicable))
+}
+
+/// Publish an `emergency_contact_alert` carrying who should be alerted
+pub fn publish_emergency_contact_alert(
+    appointment: &Appointment,
+    contact: EmergencyContact,
+) -> AppointmentEventEnvelope {
+    publish_envelope(AppointmentEvent::EmergencyContactAlert, appointment, Some(contact), None)
+}
+
+fn publish_envelope(
+    event: AppointmentEvent,
+    appointment: &Appointment,
+    emergency_contact: Option<EmergencyContact>,
+    cancellation_fee_applicable: Option<bool>,
+) -> AppointmentEventEnvelope {
+    let envelope = AppointmentEventEnvelope {
+        id: Uuid::new_v4(),
+        event,
+        occurred_at: Utc::now(),
+        appointment: appointment.clone(),
+        emergency_contact,
+        cancellation_fee_applicable,
+    };
+
+    match bus().send(envelope.clone()) {
+        Ok(receivers) => debug!("Published {} event for appointment {} to {} subscribers", event, appointment.id, receivers),
+        Err(_) => debug!("No subscribers for {} event on appointment {}", event, appointment.id),
+    }
+
+    envelope
+}
+
+pub fn subscribe() -> broadcast::Receiver<AppointmentEventEnvelope> {
+    bus().subscribe()
+}
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::models::{Appointment, AppointmentError, AppointmentSearchQuery};
+use crate::services::booking::AppointmentBookingService;
+
+/// Rows fetched from the database per round trip while exporting
+pub const EXPORT_PAGE_SIZE: i32 = 500;
+
+const CSV_COLUMNS: [&str; 12] = [
+    "id",
+    "patient_id",
+    "doctor_id",
+    "appointment_type",
+    "status",
+    "scheduled_start_time",
+    "scheduled_end_time",
+    "duration_minutes",
+    "timezone",
+    "actual_start_time",
+    "actual_end_time",
+    "created_at",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    /// Newline-delimited JSON, one appointment per line
+    #[serde(alias = "ndjson")]
+    Json,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/x-ndjson",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "ndjson",
+        }
+    }
+
+    /// Encode one page of appointments. CSV output starts with a header row
+    /// when `first_page` is set.
+    pub fn encode(&self, appointments: &[Appointment], first_page: bool) -> String {
+        let mut out = String::new();
+        match self {
+            ExportFormat::Csv => {
+                if first_page {
+                    out.push_str(&CSV_COLUMNS.join(","));
+                    out.push('\n');
+                }
+                for appointment in appointments {
+                    out.push_str(&csv_row(appointment));
+                    out.push('\n');
+                }
+            }
+            ExportFormat::Json => {
+                for appointment in appointments {
+                    match serde_json::to_string(appointment) {
+                        Ok(line) => {
+                            out.push_str(&line);
+                            out.push('\n');
+                        }
+                        Err(e) => error!("Failed to serialize appointment {} for export: {}", appointment.id, e),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Stream an export page by page so large date ranges never sit in memory.
+/// Each page picks up after the last appointment of the one before, and the
+/// stream ends after the first short page.
+pub fn export_stream(
+    booking_service: AppointmentBookingService,
+    query: AppointmentSearchQuery,
+    auth_token: String,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<Bytes, AppointmentError>> {
+    let booking_service = Arc::new(booking_service);
+    let query = Arc::new(query);
+
+    stream::unfold(Some(ExportCursor::Start), move |cursor| {
+        let booking_service = booking_service.clone();
+        let query = query.clone();
+        let auth_token = auth_token.clone();
+
+        async move {
+            let cursor = cursor?;
+            let after = match cursor {
+                ExportCursor::Start => None,
+                ExportCursor::After(start, id) => Some((start, id)),
+            };
+
+            let next = match booking_service.export_page(&query, after, EXPORT_PAGE_SIZE, &auth_token).await {
+                Ok(appointments) => {
+                    let next_cursor = (appointments.len() as i32 == EXPORT_PAGE_SIZE)
+                        .then(|| appointments.last())
+                        .flatten()
+                        .map(|last| ExportCursor::After(last.scheduled_start_time, last.id));
+                    let chunk = format.encode(&appointments, matches!(cursor, ExportCursor::Start));
+                    (Ok(Bytes::from(chunk)), next_cursor)
+                }
+                Err(e) => {
+                    error!("Appointment export failed after {:?}: {}", after, e);
+                    (Err(e), None)
+                }
+            };
+            Some(next)
+        }
+    })
+}
+
+/// Where the next export page starts
+#[derive(Debug, Clone, Copy)]
+enum ExportCursor {
+    Start,
+    /// After the appointment with this start time and id
+    After(DateTime<Utc>, Uuid),
+}
+
+fn csv_row(appointment: &Appointment) -> String {
+    let fields = [
+        appointment.id.to_string(),
+        appointment.patient_id.to_string(),
+        appointment.doctor_id.to_string(),
+        appointment.appointment_type.to_string(),
+        appointment.status.to_string(),
+        appointment.scheduled_start_time.to_rfc3339(),
+        appointment.scheduled_end_time.to_rfc3339(),
+        appointment.duration_minutes.to_string(),
+        appointment.timezone.clone(),
+        appointment.actual_start_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        appointment.actual_end_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        appointment.created_at.to_rfc3339(),
+    ];
+
+    fields.iter().map(|field| csv_escape(field)).collect::<Vec<_>>().join(",")
+}
+
+/// Quote a field when it contains a delimiter, quote or line break
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("Europe/London, UK"), "\"Europe/London, UK\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_csv_header_only_on_first_page() {
+        assert_eq!(ExportFormat::Csv.encode(&[], true), format!("{}\n", CSV_COLUMNS.join(",")));
+        assert_eq!(ExportFormat::Csv.encode(&[], false), "");
+    }
+}
@@ -0,0 +1,230 @@
+// libs/appointment-cell/src/services/holds.rs
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{AppointmentError, AppointmentHold, BookAppointmentRequest};
+
+/// How long a slot stays reserved before it is released again
+pub const HOLD_TTL_MINUTES: i64 = 5;
+
+/// Table holding live slot holds, one row per hold
+pub(crate) const HOLDS_TABLE: &str = "appointment_holds";
+
+/// How often expired holds are swept when nothing else touches them
+const HOLD_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+static HOLDS_PLACED: AtomicU64 = AtomicU64::new(0);
+static HOLDS_CONFIRMED: AtomicU64 = AtomicU64::new(0);
+static HOLDS_EXPIRED: AtomicU64 = AtomicU64::new(0);
+
+/// How holds placed since startup ended up
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldStats {
+    pub placed: u64,
+    pub confirmed: u64,
+    /// Lapsed without being confirmed
+    pub expired: u64,
+    /// Still live, so neither confirmed nor expired yet
+    pub active: u64,
+    /// Confirmed holds among those that were either confirmed or expired
+    pub conversion_rate: Option<f64>,
+}
+
+/// A reserved slot and the booking it becomes once confirmed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct HeldSlot {
+    #[serde(flatten)]
+    pub hold: AppointmentHold,
+    pub held_by: String,
+    /// Occupied time including the doctor's post-appointment buffer
+    pub occupied_until: DateTime<Utc>,
+    pub request: BookAppointmentRequest,
+}
+
+/// Timestamp in the form PostgREST filters take unescaped
+pub(crate) fn filter_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Slots reserved while a patient finishes booking. Holds are rows in
+/// `appointment_holds`, so every instance sees them, and a hold is treated
+/// as released the moment its `expires_at` passes; conflict detection checks
+/// them alongside appointments.
+pub(crate) struct HoldStore {
+    supabase: Arc<SupabaseClient>,
+}
+
+impl HoldStore {
+    pub fn new(supabase: Arc<SupabaseClient>) -> Self {
+        Self { supabase }
+    }
+
+    pub async fn insert(&self, held: &HeldSlot, auth_token: &str) -> Result<(), AppointmentError> {
+        let body = serde_json::to_value(held)
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to serialize hold: {}", e)))?;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let _: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            &format!("/rest/v1/{}", HOLDS_TABLE),
+            Some(auth_token),
+            Some(body),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        ledger().insert(held.hold.hold_id, held.hold.expires_at);
+        HOLDS_PLACED.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The hold, unless it doesn't exist or expired by `now`
+    pub async fn get(&self, hold_id: Uuid, now: DateTime<Utc>, auth_token: &str) -> Result<Option<HeldSlot>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/{}?hold_id=eq.{}&expires_at=gt.{}",
+            HOLDS_TABLE, hold_id, filter_time(now)
+        );
+        let rows: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        rows.into_iter().next()
+            .map(|row| serde_json::from_value(row)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse hold: {}", e))))
+            .transpose()
+    }
+
+    /// Drop a hold that has become an appointment
+    pub async fn confirm(&self, hold_id: Uuid, auth_token: &str) -> Result<(), AppointmentError> {
+        let path = format!("/rest/v1/{}?hold_id=eq.{}", HOLDS_TABLE, hold_id);
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let _: Vec<Value> = self.supabase.request_with_headers(
+            Method::DELETE,
+            &path,
+            Some(auth_token),
+            None,
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        ledger().remove(&hold_id);
+        HOLDS_CONFIRMED.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Expiry of each hold placed through this process and not yet confirmed
+/// or expired, so the metrics can tell how holds ended. Like the other
+/// business metrics it covers this process since startup.
+fn ledger() -> std::sync::MutexGuard<'static, HashMap<Uuid, DateTime<Utc>>> {
+    static LEDGER: OnceLock<Mutex<HashMap<Uuid, DateTime<Utc>>>> = OnceLock::new();
+    LEDGER.get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn expiry_from(now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::minutes(HOLD_TTL_MINUTES)
+}
+
+/// Count holds that lapsed by `now` as expired. Returns how many there were.
+pub fn purge_expired(now: DateTime<Utc>) -> u64 {
+    let mut ledger = ledger();
+    let before = ledger.len();
+    ledger.retain(|_, expires_at| *expires_at > now);
+    let expired = (before - ledger.len()) as u64;
+    HOLDS_EXPIRED.fetch_add(expired, Ordering::Relaxed);
+    expired
+}
+
+pub fn hold_stats() -> HoldStats {
+    purge_expired(Utc::now());
+    let active = ledger().len() as u64;
+    let confirmed = HOLDS_CONFIRMED.load(Ordering::Relaxed);
+    let expired = HOLDS_EXPIRED.load(Ordering::Relaxed);
+    HoldStats {
+        placed: HOLDS_PLACED.load(Ordering::Relaxed),
+        confirmed,
+        expired,
+        active,
+        conversion_rate: (confirmed + expired > 0)
+            .then(|| confirmed as f64 / (confirmed + expired) as f64),
+    }
+}
+
+/// Hold metrics in the Prometheus text exposition format
+pub fn prometheus_text() -> String {
+    let stats = hold_stats();
+    format!(
+        "# HELP appointment_holds_total Slot holds by how they ended\n\
+         # TYPE appointment_holds_total counter\n\
+         appointment_holds_total{{outcome=\"confirmed\"}} {}\n\
+         appointment_holds_total{{outcome=\"expired\"}} {}\n\
+         # HELP appointment_holds_active Slot holds not yet confirmed or expired\n\
+         # TYPE appointment_holds_active gauge\n\
+         appointment_holds_active {}\n",
+        stats.confirmed, stats.expired, stats.active
+    )
+}
+
+/// Start the background task that counts holds abandoned by a client that
+/// never came back as expired, even when nothing reads the metrics. Expired
+/// rows need no cleanup to be released; every read filters on
+/// `expires_at`. Call once at startup.
+pub fn spawn_expiry_worker() -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!("Slot hold expiry worker started");
+        let mut interval = tokio::time::interval(HOLD_SWEEP_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let expired = purge_expired(Utc::now());
+            if expired > 0 {
+                debug!("{} slot holds expired", expired);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_holds_are_counted() {
+        let lapsing = Uuid::new_v4();
+        ledger().insert(lapsing, Utc::now() + Duration::seconds(30));
+        HOLDS_PLACED.fetch_add(1, Ordering::Relaxed);
+
+        let before = hold_stats();
+        assert!(purge_expired(Utc::now() + Duration::minutes(1)) >= 1);
+        let after = hold_stats();
+        assert!(after.expired > before.expired);
+        assert!(!ledger().contains_key(&lapsing));
+        assert!(after.conversion_rate.is_some_and(|rate| (0.0..=1.0).contains(&rate)));
+        assert!(prometheus_text().contains("appointment_holds_total{outcome=\"expired\"}"));
+    }
+
+    #[test]
+    fn test_filter_time_has_no_offset_to_escape() {
+        let time = DateTime::parse_from_rfc3339("2030-01-01T09:00:00+00:00").unwrap().with_timezone(&Utc);
+        assert_eq!(filter_time(time), "2030-01-01T09:00:00.000000Z");
+    }
+}
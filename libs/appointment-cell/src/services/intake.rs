@@ -0,0 +1,275 @@
+// libs/appointment-cell/src/services/intake.rs
+use std::collections::HashSet;
+
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{
+    Appointment, AppointmentError, AppointmentStatus, AppointmentType, CreateIntakeFormRequest,
+    IntakeFieldType, IntakeForm, IntakeFormSchema, IntakeResponse, SubmitIntakeRequest,
+};
+
+/// Pre-visit questionnaires per appointment type, and patients' answers to
+/// them. Answers are checked against the form's schema before they're stored.
+pub struct IntakeService {
+    supabase: SupabaseClient,
+}
+
+impl IntakeService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+        }
+    }
+
+    pub async fn create_form(
+        &self,
+        request: CreateIntakeFormRequest,
+        auth_token: &str,
+    ) -> Result<IntakeForm, AppointmentError> {
+        if request.title.trim().is_empty() {
+            return Err(AppointmentError::ValidationError("Intake form title is required".to_string()));
+        }
+        validate_schema(&request.schema)?;
+
+        let form_data = json!({
+            "id": Uuid::new_v4(),
+            "appointment_type": request.appointment_type,
+            "title": request.title.trim(),
+            "schema": request.schema,
+            "is_active": true,
+            "created_at": Utc::now().to_rfc3339()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/intake_forms",
+            Some(auth_token),
+            Some(form_data),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let form: IntakeForm = result.into_iter().next()
+            .ok_or_else(|| AppointmentError::DatabaseError("Failed to store intake form".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse intake form: {}", e))))?;
+
+        info!("Intake form {} created for {} appointments", form.id, form.appointment_type);
+        Ok(form)
+    }
+
+    /// Active forms a patient booking `appointment_type` should fill in
+    pub async fn forms_for_type(
+        &self,
+        appointment_type: &AppointmentType,
+        auth_token: &str,
+    ) -> Result<Vec<IntakeForm>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/intake_forms?appointment_type=eq.{}&is_active=eq.true&order=created_at.asc",
+            appointment_type
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<IntakeForm>, _>>()
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse intake forms: {}", e)))
+    }
+
+    /// Store the patient's answers for one of the appointment's forms. Answers
+    /// can be revised until the consultation starts.
+    pub async fn submit_response(
+        &self,
+        appointment: &Appointment,
+        request: SubmitIntakeRequest,
+        auth_token: &str,
+    ) -> Result<IntakeResponse, AppointmentError> {
+        debug!("Submitting intake form {} for appointment {}", request.form_id, appointment.id);
+
+        if !matches!(appointment.status, AppointmentStatus::Pending | AppointmentStatus::Confirmed) {
+            return Err(AppointmentError::ValidationError(
+                "Intake forms can only be submitted before the consultation".to_string()
+            ));
+        }
+
+        let form = self.forms_for_type(&appointment.appointment_type, auth_token).await?
+            .into_iter()
+            .find(|form| form.id == request.form_id)
+            .ok_or_else(|| AppointmentError::ValidationError(
+                "Intake form does not apply to this appointment".to_string()
+            ))?;
+
+        validate_answers(&form.schema, &request.answers)?;
+
+        let response_data = json!({
+            "id": Uuid::new_v4(),
+            "appointment_id": appointment.id,
+            "form_id": form.id,
+            "patient_id": appointment.patient_id,
+            "answers": request.answers,
+            "submitted_at": Utc::now().to_rfc3339()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "Prefer",
+            reqwest::header::HeaderValue::from_static("return=representation,resolution=merge-duplicates"),
+        );
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/intake_responses?on_conflict=appointment_id,form_id",
+            Some(auth_token),
+            Some(response_data),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let response: IntakeResponse = result.into_iter().next()
+            .ok_or_else(|| AppointmentError::DatabaseError("Failed to store intake response".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse intake response: {}", e))))?;
+
+        info!("Intake form {} submitted for appointment {}", form.id, appointment.id);
+        Ok(response)
+    }
+
+    pub async fn get_responses(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Vec<IntakeResponse>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/intake_responses?appointment_id=eq.{}&order=submitted_at.asc",
+            appointment_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<IntakeResponse>, _>>()
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse intake responses: {}", e)))
+    }
+}
+
+fn validate_schema(schema: &IntakeFormSchema) -> Result<(), AppointmentError> {
+    if schema.fields.is_empty() {
+        return Err(AppointmentError::ValidationError("An intake form needs at least one field".to_string()));
+    }
+
+    let mut keys = HashSet::new();
+    for field in &schema.fields {
+        if field.key.trim().is_empty() {
+            return Err(AppointmentError::ValidationError("Every intake field needs a key".to_string()));
+        }
+        if !keys.insert(field.key.as_str()) {
+            return Err(AppointmentError::ValidationError(format!("Duplicate intake field key: {}", field.key)));
+        }
+        if field.field_type == IntakeFieldType::Choice && field.options.is_empty() {
+            return Err(AppointmentError::ValidationError(format!("Choice field {} has no options", field.key)));
+        }
+    }
+    Ok(())
+}
+
+/// Required fields must be answered, every answer must match its field's
+/// type, and answers to fields the form doesn't have are rejected
+fn validate_answers(schema: &IntakeFormSchema, answers: &Map<String, Value>) -> Result<(), AppointmentError> {
+    for key in answers.keys() {
+        if !schema.fields.iter().any(|field| &field.key == key) {
+            return Err(AppointmentError::ValidationError(format!("Unknown intake field: {}", key)));
+        }
+    }
+
+    for field in &schema.fields {
+        let answer = answers.get(&field.key)
+            .filter(|value| !value.is_null() && value.as_str().is_none_or(|text| !text.trim().is_empty()));
+        let Some(answer) = answer else {
+            if field.required {
+                return Err(AppointmentError::ValidationError(format!("{} is required", field.label)));
+            }
+            continue;
+        };
+
+        let valid = match field.field_type {
+            IntakeFieldType::Text => answer.is_string(),
+            IntakeFieldType::Number => answer.is_number(),
+            IntakeFieldType::Boolean => answer.is_boolean(),
+            IntakeFieldType::Choice => answer.as_str().is_some_and(|choice| field.options.iter().any(|option| option == choice)),
+        };
+        if !valid {
+            return Err(AppointmentError::ValidationError(format!("Invalid answer for {}", field.label)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::IntakeFormField;
+
+    fn schema() -> IntakeFormSchema {
+        let field = |key: &str, field_type, required, options: &[&str]| IntakeFormField {
+            key: key.to_string(),
+            label: key.replace('_', " "),
+            field_type,
+            required,
+            options: options.iter().map(|option| option.to_string()).collect(),
+        };
+        IntakeFormSchema {
+            fields: vec![
+                field("mood", IntakeFieldType::Choice, true, &["low", "ok", "good"]),
+                field("sleep_hours", IntakeFieldType::Number, false, &[]),
+                field("self_harm_thoughts", IntakeFieldType::Boolean, true, &[]),
+            ],
+        }
+    }
+
+    fn answers(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_validate_answers() {
+        let schema = schema();
+
+        assert!(validate_answers(&schema, &answers(json!({ "mood": "low", "self_harm_thoughts": false }))).is_ok());
+        assert!(validate_answers(&schema, &answers(json!({ "mood": "low", "sleep_hours": 6, "self_harm_thoughts": false }))).is_ok());
+
+        // Missing required answer
+        assert!(validate_answers(&schema, &answers(json!({ "mood": "low" }))).is_err());
+        assert!(validate_answers(&schema, &answers(json!({ "mood": " ", "self_harm_thoughts": true }))).is_err());
+        // Not one of the options, wrong type, unknown field
+        assert!(validate_answers(&schema, &answers(json!({ "mood": "great", "self_harm_thoughts": false }))).is_err());
+        assert!(validate_answers(&schema, &answers(json!({ "mood": "ok", "sleep_hours": "six", "self_harm_thoughts": false }))).is_err());
+        assert!(validate_answers(&schema, &answers(json!({ "mood": "ok", "self_harm_thoughts": false, "notes": "x" }))).is_err());
+    }
+
+    #[test]
+    fn test_validate_schema_rejects_duplicate_keys() {
+        let mut schema = schema();
+        assert!(validate_schema(&schema).is_ok());
+
+        schema.fields.push(schema.fields[0].clone());
+        assert!(validate_schema(&schema).is_err());
+    }
+}
@@ -8,6 +8,12 @@ use crate::models::{AppointmentStatus, AppointmentError};
 
 pub struct AppointmentLifecycleService;
 
+impl Default for AppointmentLifecycleService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AppointmentLifecycleService {
     pub fn new() -> Self {
         Self
@@ -161,11 +167,10 @@ impl AppointmentLifecycleService {
         current_time: DateTime<Utc>,
     ) -> Option<AppointmentStatus> {
         match current_status {
-            AppointmentStatus::Confirmed => {
-                // Auto mark as no-show if 30 minutes past start time
-                if self.should_mark_no_show(current_status, scheduled_start_time, current_time) {
-                    return Some(AppointmentStatus::NoShow);
-                }
+            // Auto mark as no-show if 30 minutes past start time
+            AppointmentStatus::Confirmed
+                if self.should_mark_no_show(current_status, scheduled_start_time, current_time) => {
+                return Some(AppointmentStatus::NoShow);
             },
             AppointmentStatus::InProgress => {
                 // Auto complete if 30 minutes past scheduled end time
@@ -207,7 +212,7 @@ impl AppointmentLifecycleService {
         let start_hour = scheduled_start_time.hour();
         let end_hour = scheduled_end_time.hour();
         
-        if start_hour < 8 || start_hour >= 20 || end_hour > 20 {
+        if !(8..20).contains(&start_hour) || end_hour > 20 {
             return Err(AppointmentError::InvalidTime(
                 "Appointments must be scheduled between 8 AM and 8 PM".to_string()
             ));
@@ -0,0 +1,215 @@
+// libs/appointment-cell/src/services/live.rs
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use futures::{Stream, StreamExt};
+use std::sync::OnceLock;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use shared_utils::jwt::token_expiry;
+
+use crate::models::{Appointment, AppointmentError, AppointmentEvent, AppointmentEventEnvelope, AppointmentSearchQuery};
+use crate::services::booking::AppointmentBookingService;
+
+static FEEDS_CLOSING: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+fn feeds_closing() -> &'static watch::Sender<bool> {
+    FEEDS_CLOSING.get_or_init(|| watch::channel(false).0)
+}
+
+/// End every open live feed, and any opened afterwards, once the server
+/// starts shutting down. Feeds otherwise run until their token expires,
+/// which would hold graceful shutdown open until it times out.
+pub fn close_live_feeds() {
+    feeds_closing().send_replace(true);
+}
+
+/// Resolves once `close_live_feeds` has been called
+async fn feeds_closed() {
+    let mut closing = feeds_closing().subscribe();
+    // The sender is static and never dropped, so this only returns once closing
+    let _ = closing.wait_for(|closing| *closing).await;
+}
+
+/// One calendar day in the clinic's timezone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClinicDay {
+    pub start: DateTime<Utc>,
+    /// Exclusive
+    pub end: DateTime<Utc>,
+}
+
+impl ClinicDay {
+    /// The day `now` falls on in `timezone` (an IANA name; UTC when unset
+    /// or unknown)
+    pub fn containing(now: DateTime<Utc>, timezone: Option<&str>) -> Self {
+        let tz: Tz = timezone.and_then(|name| name.parse().ok()).unwrap_or(Tz::UTC);
+        let date = now.with_timezone(&tz).date_naive();
+        let midnight = |date: chrono::NaiveDate| {
+            let local = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+            // Midnight can fall in a DST gap; the earliest instant after it is the day's start
+            tz.from_local_datetime(&local).earliest()
+                .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+                .map(|start| start.with_timezone(&Utc))
+                .unwrap_or_else(|| local.and_utc())
+        };
+        Self {
+            start: midnight(date),
+            end: midnight(date + Duration::days(1)),
+        }
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+/// What the live dashboard feed sends
+#[derive(Debug, Clone)]
+pub enum LiveUpdate {
+    /// Every appointment the doctor has that day. Sent first, again when the
+    /// day rolls over, and whenever the feed fell behind and missed events.
+    Snapshot(Vec<Appointment>),
+    Event(Box<AppointmentEventEnvelope>),
+}
+
+/// Whether an event belongs on the doctor's feed for `day`. Reschedules are
+/// always sent so an appointment moved off the day disappears from it.
+/// Emergency contact alerts carry a third party's details and stay off the
+/// feed.
+pub fn is_relevant(envelope: &AppointmentEventEnvelope, doctor_id: Uuid, day: &ClinicDay) -> bool {
+    envelope.appointment.doctor_id == doctor_id
+        && envelope.event != AppointmentEvent::EmergencyContactAlert
+        && (envelope.event == AppointmentEvent::Rescheduled
+            || day.contains(envelope.appointment.scheduled_start_time))
+}
+
+/// The doctor's appointments on `day`, earliest first
+pub async fn doctor_day(
+    booking_service: &AppointmentBookingService,
+    doctor_id: Uuid,
+    day: &ClinicDay,
+    auth_token: &str,
+) -> Result<Vec<Appointment>, AppointmentError> {
+    let query = AppointmentSearchQuery {
+        patient_id: None,
+        doctor_id: Some(doctor_id),
+        status: None,
+        appointment_type: None,
+        from_date: Some(day.start),
+        to_date: Some(day.end - Duration::seconds(1)),
+        reason_contains: None,
+        limit: None,
+        offset: None,
+    };
+    let mut appointments = booking_service.search_appointments(query, auth_token).await?;
+    appointments.sort_by_key(|appointment| appointment.scheduled_start_time);
+    Ok(appointments)
+}
+
+struct FeedState {
+    booking_service: AppointmentBookingService,
+    doctor_id: Uuid,
+    timezone: Option<String>,
+    auth_token: String,
+    day: ClinicDay,
+    events: broadcast::Receiver<AppointmentEventEnvelope>,
+    pending: Option<LiveUpdate>,
+}
+
+/// Live updates for one doctor's day: `snapshot` first, then their
+/// appointment events as they're published. Subscribe to the event bus
+/// before loading the snapshot so nothing published in between is lost.
+/// Snapshots are reloaded with `auth_token`, so the feed ends when that
+/// token expires, or if a snapshot can't be reloaded; clients reconnect.
+/// It also ends when the server shuts down (`close_live_feeds`).
+///
+/// The event bus is in-process: events raised by other instances of the
+/// API never reach this feed. With more than one instance, a dashboard only
+/// sees those changes in the snapshot it gets on its next reconnect.
+pub fn doctor_day_feed(
+    booking_service: AppointmentBookingService,
+    doctor_id: Uuid,
+    timezone: Option<String>,
+    auth_token: String,
+    day: ClinicDay,
+    events: broadcast::Receiver<AppointmentEventEnvelope>,
+    snapshot: Vec<Appointment>,
+) -> impl Stream<Item = LiveUpdate> + Send + 'static {
+    let expires_in = token_expiry(&auth_token)
+        .map(|expiry| (expiry - Utc::now()).to_std().unwrap_or_default());
+    let state = FeedState {
+        booking_service,
+        doctor_id,
+        timezone,
+        auth_token,
+        day,
+        events,
+        pending: Some(LiveUpdate::Snapshot(snapshot)),
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        if let Some(update) = state.pending.take() {
+            return Some((update, state));
+        }
+
+        loop {
+            let resync = match state.events.recv().await {
+                Ok(envelope) => {
+                    let today = ClinicDay::containing(Utc::now(), state.timezone.as_deref());
+                    if today != state.day {
+                        state.day = today;
+                        true
+                    } else if is_relevant(&envelope, state.doctor_id, &state.day) {
+                        return Some((LiveUpdate::Event(Box::new(envelope)), state));
+                    } else {
+                        false
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    debug!("Live feed for doctor {} missed {} events, resending the day", state.doctor_id, missed);
+                    true
+                }
+                Err(RecvError::Closed) => return None,
+            };
+
+            if resync {
+                return match doctor_day(&state.booking_service, state.doctor_id, &state.day, &state.auth_token).await {
+                    Ok(appointments) => Some((LiveUpdate::Snapshot(appointments), state)),
+                    Err(e) => {
+                        warn!("Ending live feed for doctor {}: {}", state.doctor_id, e);
+                        None
+                    }
+                };
+            }
+        }
+    })
+    .take_until(async move {
+        match expires_in {
+            Some(expires_in) => tokio::time::sleep(expires_in).await,
+            None => futures::future::pending().await,
+        }
+    })
+    .take_until(feeds_closed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clinic_day_follows_the_clinic_timezone() {
+        // 23:30 UTC on 1 July is already 2 July in Dublin (UTC+1)
+        let now = Utc.with_ymd_and_hms(2025, 7, 1, 23, 30, 0).unwrap();
+        let day = ClinicDay::containing(now, Some("Europe/Dublin"));
+        assert_eq!(day.start, Utc.with_ymd_and_hms(2025, 7, 1, 23, 0, 0).unwrap());
+        assert_eq!(day.end, Utc.with_ymd_and_hms(2025, 7, 2, 23, 0, 0).unwrap());
+        assert!(day.contains(now));
+
+        let utc = ClinicDay::containing(now, Some("Not/AZone"));
+        assert_eq!(utc.start, Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap());
+        assert!(!utc.contains(utc.end));
+    }
+}
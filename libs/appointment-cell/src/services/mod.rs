@@ -1,3 +1,21 @@
 pub mod booking;
 pub mod conflict;
-pub mod lifecycle;
\ No newline at end of file
+pub mod timing;
+pub mod lifecycle;
+pub mod clock;
+pub mod export;
+pub mod events;
+pub mod webhooks;
+pub mod notes;
+pub mod prescriptions;
+pub mod certificate;
+pub mod emergency_contact;
+pub mod intake;
+pub mod schedule_lock;
+pub mod holds;
+pub mod business_metrics;
+pub mod stats_cache;
+pub mod live;
+pub mod outcomes;
+pub mod timeline;
+pub mod versioning;
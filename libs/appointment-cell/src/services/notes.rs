@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{
+    AppointmentError, ConsultationNote, ConsultationNoteSearchHit, CreateConsultationNoteRequest,
+};
+use crate::services::versioning;
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 50;
+
+/// Search terms beyond this are ignored
+const MAX_SEARCH_TERMS: usize = 10;
+
+/// Characters of context kept either side of the first match in a snippet
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Structured SOAP notes for an appointment. The appointment's free-text
+/// `doctor_notes` is rewritten from the notes on every change so older
+/// clients keep seeing the clinician's notes.
+pub struct ConsultationNoteService {
+    supabase: SupabaseClient,
+}
+
+impl ConsultationNoteService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+        }
+    }
+
+    pub async fn add_note(
+        &self,
+        appointment_id: Uuid,
+        author: Uuid,
+        request: CreateConsultationNoteRequest,
+        auth_token: &str,
+    ) -> Result<ConsultationNote, AppointmentError> {
+        debug!("Adding consultation note to appointment {}", appointment_id);
+
+        let sections = [&request.subjective, &request.objective, &request.assessment, &request.plan];
+        if sections.iter().all(|section| section.as_deref().is_none_or(|text| text.trim().is_empty())) {
+            return Err(AppointmentError::ValidationError(
+                "A consultation note needs at least one SOAP section".to_string()
+            ));
+        }
+
+        let note_data = json!({
+            "id": Uuid::new_v4(),
+            "appointment_id": appointment_id,
+            "subjective": request.subjective,
+            "objective": request.objective,
+            "assessment": request.assessment,
+            "plan": request.plan,
+            "author": author,
+            "created_at": Utc::now().to_rfc3339()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/consultation_notes",
+            Some(auth_token),
+            Some(note_data),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let note: ConsultationNote = result.into_iter().next()
+            .ok_or_else(|| AppointmentError::DatabaseError("Failed to store consultation note".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse consultation note: {}", e))))?;
+
+        self.sync_legacy_doctor_notes(appointment_id, auth_token).await?;
+
+        info!("Consultation note {} added to appointment {}", note.id, appointment_id);
+        Ok(note)
+    }
+
+    /// Notes for an appointment, oldest first
+    pub async fn get_notes(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Vec<ConsultationNote>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/consultation_notes?appointment_id=eq.{}&order=created_at.asc",
+            appointment_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<ConsultationNote>, _>>()
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse consultation notes: {}", e)))
+    }
+
+    /// Full-text search (`consultation_notes.search_vector`) limited to notes
+    /// the doctor wrote or that belong to one of their patients, newest first
+    pub async fn search_notes(
+        &self,
+        doctor_id: Uuid,
+        query: &str,
+        limit: Option<usize>,
+        auth_token: &str,
+    ) -> Result<Vec<ConsultationNoteSearchHit>, AppointmentError> {
+        let terms = search_terms(query);
+        if terms.is_empty() {
+            return Err(AppointmentError::ValidationError("Search query is empty".to_string()));
+        }
+        let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+
+        let patient_ids = self.doctor_patient_ids(doctor_id, auth_token).await?;
+        let scope = if patient_ids.is_empty() {
+            format!("author=eq.{}", doctor_id)
+        } else {
+            let ids: Vec<String> = patient_ids.iter().map(Uuid::to_string).collect();
+            format!("or=(author.eq.{},appointment.patient_id.in.({}))", doctor_id, ids.join(","))
+        };
+
+        let path = format!(
+            "/rest/v1/consultation_notes?search_vector=plfts(english).{}&select=*,appointment:appointments!inner(patient_id)&{}&order=created_at.desc&limit={}",
+            terms.join("%20"), scope, limit
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let mut hits = Vec::with_capacity(result.len());
+        for row in result {
+            let patient_id = row["appointment"]["patient_id"].as_str()
+                .and_then(|id| Uuid::parse_str(id).ok());
+            let note: ConsultationNote = serde_json::from_value(row)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse consultation note: {}", e)))?;
+
+            // Don't rely on the filter alone to keep other doctors' notes out
+            let Some(patient_id) = patient_id else { continue };
+            if note.author != doctor_id && !patient_ids.contains(&patient_id) {
+                continue;
+            }
+
+            let (section, snippet) = best_snippet(&note, &terms);
+            hits.push(ConsultationNoteSearchHit { note, patient_id, section, snippet });
+        }
+
+        debug!("Note search by doctor {} for {:?} matched {} notes", doctor_id, terms, hits.len());
+        Ok(hits)
+    }
+
+    /// Patients the doctor has had any appointment with
+    async fn doctor_patient_ids(&self, doctor_id: Uuid, auth_token: &str) -> Result<HashSet<Uuid>, AppointmentError> {
+        let path = format!("/rest/v1/appointments?doctor_id=eq.{}&select=patient_id", doctor_id);
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        Ok(result.iter()
+            .filter_map(|row| row["patient_id"].as_str())
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect())
+    }
+
+    /// Rewrite the appointment's `doctor_notes` from its notes. Notes and
+    /// appointment are read again whenever another write lands first, so a
+    /// note added at the same time is never dropped from the text.
+    async fn sync_legacy_doctor_notes(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str,
+    ) -> Result<(), AppointmentError> {
+        let mut attempt = 1;
+        loop {
+            let appointment = versioning::get_appointment(&self.supabase, appointment_id, auth_token).await?;
+            let notes = self.get_notes(appointment_id, auth_token).await?;
+            let update_data = json!({
+                "doctor_notes": legacy_doctor_notes(&notes),
+                "updated_at": Utc::now().to_rfc3339()
+            });
+
+            match versioning::patch_appointment(&self.supabase, &appointment, update_data, auth_token).await {
+                Err(AppointmentError::VersionConflict(_)) if attempt < versioning::SET_FIELDS_ATTEMPTS => attempt += 1,
+                result => return result.map(|_| ()),
+            }
+        }
+    }
+}
+
+/// All notes flattened into the legacy free-text form, one block per note
+pub fn legacy_doctor_notes(notes: &[ConsultationNote]) -> String {
+    notes.iter()
+        .map(ConsultationNote::to_legacy_text)
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Lowercased words of a search query. Anything but letters and digits
+/// separates words, so the query can't inject PostgREST filter syntax.
+pub(crate) fn search_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in query.split(|c: char| !c.is_alphanumeric()).filter(|term| !term.is_empty()) {
+        let term = term.to_lowercase();
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms.truncate(MAX_SEARCH_TERMS);
+    terms
+}
+
+/// The first section mentioning a search term, excerpted with the terms
+/// marked. Full-text search also matches other forms of a word, so when no
+/// term appears literally the start of the first section is used instead.
+fn best_snippet(note: &ConsultationNote, terms: &[String]) -> (String, String) {
+    let sections = [
+        ("subjective", &note.subjective),
+        ("objective", &note.objective),
+        ("assessment", &note.assessment),
+        ("plan", &note.plan),
+    ];
+    let sections: Vec<(&str, &str)> = sections.iter()
+        .filter_map(|(name, text)| text.as_deref().map(|text| (*name, text.trim())))
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+
+    for (name, text) in &sections {
+        if let Some(snippet) = highlight_snippet(text, terms) {
+            return (name.to_string(), snippet);
+        }
+    }
+
+    sections.first()
+        .map(|(name, text)| {
+            let end = floor_char_boundary(text, SNIPPET_CONTEXT_CHARS * 2);
+            let ellipsis = if end < text.len() { "…" } else { "" };
+            (name.to_string(), format!("{}{}", &text[..end], ellipsis))
+        })
+        .unwrap_or_default()
+}
+
+/// Excerpt of `text` around the first term found, with every term in the
+/// excerpt wrapped in `<mark>`. Matching ignores ASCII case.
+fn highlight_snippet(text: &str, terms: &[String]) -> Option<String> {
+    let lower = text.to_ascii_lowercase();
+    let first_match = terms.iter().filter_map(|term| lower.find(term.as_str())).min()?;
+
+    let start = floor_char_boundary(text, first_match.saturating_sub(SNIPPET_CONTEXT_CHARS));
+    let end = floor_char_boundary(text, first_match + SNIPPET_CONTEXT_CHARS * 2);
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut i = start;
+    while i < end {
+        let matched = terms.iter()
+            .filter(|term| lower[i..].starts_with(term.as_str()))
+            .map(|term| term.len())
+            .max();
+        match matched {
+            Some(len) => {
+                snippet.push_str("<mark>");
+                snippet.push_str(&text[i..i + len]);
+                snippet.push_str("</mark>");
+                i += len;
+            }
+            None => {
+                let c = text[i..].chars().next().unwrap_or_default();
+                snippet.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    if end < text.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(subjective: Option<&str>, plan: Option<&str>) -> ConsultationNote {
+        ConsultationNote {
+            id: Uuid::new_v4(),
+            appointment_id: Uuid::new_v4(),
+            subjective: subjective.map(str::to_string),
+            objective: None,
+            assessment: Some("Viral URTI".to_string()),
+            plan: plan.map(str::to_string),
+            author: Uuid::new_v4(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_legacy_doctor_notes() {
+        let notes = vec![
+            note(Some("Sore throat for 3 days"), Some("Fluids, rest")),
+            note(None, Some("  ")),
+        ];
+
+        assert_eq!(
+            legacy_doctor_notes(&notes),
+            "S: Sore throat for 3 days\nA: Viral URTI\nP: Fluids, rest\n\nA: Viral URTI"
+        );
+    }
+
+    #[test]
+    fn test_search_terms_strip_filter_syntax() {
+        assert_eq!(search_terms("Chest pain, (or=author.eq.x)"), vec!["chest", "pain", "or", "author", "eq", "x"]);
+        assert!(search_terms("  ,;  ").is_empty());
+    }
+
+    #[test]
+    fn test_highlight_snippet_marks_terms() {
+        let terms = search_terms("migraine aura");
+        assert_eq!(
+            highlight_snippet("Recurrent Migraine with aura, worse in mornings", &terms).unwrap(),
+            "Recurrent <mark>Migraine</mark> with <mark>aura</mark>, worse in mornings"
+        );
+
+        let long = format!("{}migraine{}", "x".repeat(100), "y".repeat(200));
+        let snippet = highlight_snippet(&long, &terms).unwrap();
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("<mark>migraine</mark>"));
+
+        assert!(highlight_snippet("No relevant findings", &terms).is_none());
+    }
+}
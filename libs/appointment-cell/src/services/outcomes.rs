@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use serde_json::json;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{
+    Appointment, AppointmentError, AppointmentStatus, AppointmentType, ConsultationOutcome,
+    OutcomeDisposition, RecordOutcomeRequest, SuggestedSlot,
+};
+use crate::services::conflict::{BatchLimits, ConflictDetectionService};
+use crate::services::versioning;
+
+const MAX_FOLLOW_UP_DAYS: i32 = 365;
+const MAX_SUMMARY_LENGTH: usize = 2000;
+/// How far past the requested follow-up date to look for a free slot
+const FOLLOW_UP_SEARCH_DAYS: i32 = 7;
+
+/// Structured outcomes of completed consultations, stored on the appointment
+/// so stats can be drawn from the same rows
+pub struct OutcomeService {
+    supabase: Arc<SupabaseClient>,
+    conflict_service: ConflictDetectionService,
+    audit_log: AuditLog,
+}
+
+impl OutcomeService {
+    pub fn new(config: &AppConfig) -> Self {
+        let supabase = Arc::new(SupabaseClient::new(config));
+        Self {
+            conflict_service: ConflictDetectionService::new(Arc::clone(&supabase))
+                .with_batch_limits(BatchLimits::from_config(config)),
+            supabase,
+            audit_log: AuditLog::new(config),
+        }
+    }
+
+    /// Record (or replace) the outcome of a completed consultation
+    pub async fn record(
+        &self,
+        appointment: &Appointment,
+        recorded_by: Uuid,
+        request: RecordOutcomeRequest,
+        auth_token: &str,
+    ) -> Result<ConsultationOutcome, AppointmentError> {
+        debug!("Recording outcome for appointment {}", appointment.id);
+
+        if appointment.status != AppointmentStatus::Completed {
+            return Err(AppointmentError::InvalidStatusTransition(appointment.status.clone()));
+        }
+
+        validate_outcome(&request)?;
+
+        let outcome = ConsultationOutcome {
+            dispositions: request.dispositions,
+            referred_to: request.referred_to.map(|referred_to| referred_to.trim().to_string()),
+            follow_up_in_days: request.follow_up_in_days,
+            summary: request.summary,
+            recorded_by,
+            recorded_at: Utc::now(),
+        };
+
+        // Written over the appointment that was checked for completion
+        let update_data = json!({
+            "outcome": outcome,
+            "updated_at": Utc::now().to_rfc3339()
+        });
+        versioning::patch_appointment(&self.supabase, appointment, update_data, auth_token).await?;
+
+        let audit_entry = AuditEntry::new(recorded_by.to_string(), "appointment.outcome_recorded", "appointment", appointment.id.to_string())
+            .with_details(json!({
+                "patient_id": appointment.patient_id,
+                "dispositions": outcome.dispositions,
+                "replaced_previous": appointment.outcome.is_some()
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit outcome of appointment {}: {}", appointment.id, e);
+        }
+
+        info!("Outcome recorded for appointment {}", appointment.id);
+        Ok(outcome)
+    }
+
+    /// A free follow-up slot with the same doctor, at the same time of day
+    /// `follow_up_in_days` after the consultation or within a week after that.
+    /// `None` when the outcome asks for no follow-up or no slot is free.
+    pub async fn suggest_follow_up(
+        &self,
+        appointment: &Appointment,
+        outcome: &ConsultationOutcome,
+        auth_token: &str,
+    ) -> Result<Option<SuggestedSlot>, AppointmentError> {
+        let Some(days) = outcome.follow_up_in_days else {
+            return Ok(None);
+        };
+
+        let preferred_start = appointment.scheduled_start_time + Duration::days(days as i64);
+        let duration_minutes = AppointmentType::FollowUp.meta().default_duration_minutes;
+        self.conflict_service.find_next_available_slot(
+            appointment.doctor_id,
+            &AppointmentType::FollowUp,
+            preferred_start,
+            duration_minutes,
+            FOLLOW_UP_SEARCH_DAYS,
+            auth_token,
+        ).await
+    }
+}
+
+/// Checks `record` makes before saving, usable before anything else is written
+pub fn validate_outcome(request: &RecordOutcomeRequest) -> Result<(), AppointmentError> {
+    if request.dispositions.is_empty() {
+        return Err(AppointmentError::ValidationError(
+            "An outcome needs at least one disposition".to_string()
+        ));
+    }
+
+    let dispositions: HashSet<OutcomeDisposition> = request.dispositions.iter().copied().collect();
+    if dispositions.len() != request.dispositions.len() {
+        return Err(AppointmentError::ValidationError(
+            "Each disposition can only be given once".to_string()
+        ));
+    }
+    if dispositions.contains(&OutcomeDisposition::Discharge)
+        && dispositions.contains(&OutcomeDisposition::FollowUpNeeded)
+    {
+        return Err(AppointmentError::ValidationError(
+            "A discharged patient can't also need a follow-up".to_string()
+        ));
+    }
+
+    let has_referral = request.referred_to.as_deref().is_some_and(|referred_to| !referred_to.trim().is_empty());
+    if dispositions.contains(&OutcomeDisposition::Referral) != has_referral {
+        return Err(AppointmentError::ValidationError(
+            "referred_to is required with a referral and only allowed with one".to_string()
+        ));
+    }
+
+    match request.follow_up_in_days {
+        Some(days) if !dispositions.contains(&OutcomeDisposition::FollowUpNeeded) => {
+            return Err(AppointmentError::ValidationError(format!(
+                "follow_up_in_days ({}) is only allowed with follow_up_needed", days
+            )));
+        }
+        Some(days) if !(1..=MAX_FOLLOW_UP_DAYS).contains(&days) => {
+            return Err(AppointmentError::ValidationError(format!(
+                "follow_up_in_days must be between 1 and {}", MAX_FOLLOW_UP_DAYS
+            )));
+        }
+        None if dispositions.contains(&OutcomeDisposition::FollowUpNeeded) => {
+            return Err(AppointmentError::ValidationError(
+                "follow_up_needed requires follow_up_in_days".to_string()
+            ));
+        }
+        _ => {}
+    }
+
+    if request.summary.as_ref().is_some_and(|summary| summary.chars().count() > MAX_SUMMARY_LENGTH) {
+        return Err(AppointmentError::ValidationError(format!(
+            "Summary must be at most {} characters", MAX_SUMMARY_LENGTH
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(dispositions: Vec<OutcomeDisposition>) -> RecordOutcomeRequest {
+        RecordOutcomeRequest {
+            dispositions,
+            referred_to: None,
+            follow_up_in_days: None,
+            summary: None,
+        }
+    }
+
+    #[test]
+    fn test_outcome_validation() {
+        assert!(validate_outcome(&request(vec![OutcomeDisposition::Discharge])).is_ok());
+        assert!(validate_outcome(&request(vec![])).is_err());
+        assert!(validate_outcome(&request(vec![OutcomeDisposition::Prescription, OutcomeDisposition::Prescription])).is_err());
+        assert!(validate_outcome(&request(vec![OutcomeDisposition::Referral])).is_err());
+
+        let follow_up = RecordOutcomeRequest {
+            follow_up_in_days: Some(14),
+            ..request(vec![OutcomeDisposition::Prescription, OutcomeDisposition::FollowUpNeeded])
+        };
+        assert!(validate_outcome(&follow_up).is_ok());
+        assert!(validate_outcome(&request(vec![OutcomeDisposition::FollowUpNeeded])).is_err());
+
+        let discharged_with_follow_up = RecordOutcomeRequest {
+            follow_up_in_days: Some(14),
+            ..request(vec![OutcomeDisposition::Discharge, OutcomeDisposition::FollowUpNeeded])
+        };
+        assert!(validate_outcome(&discharged_with_follow_up).is_err());
+
+        let stray_days = RecordOutcomeRequest {
+            follow_up_in_days: Some(14),
+            ..request(vec![OutcomeDisposition::Discharge])
+        };
+        assert!(validate_outcome(&stray_days).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{
+    Appointment, AppointmentError, AppointmentStatus, IssuePrescriptionRequest, Prescription,
+};
+use crate::services::versioning;
+
+/// Prescriptions issued during an appointment. Issuing one sets the
+/// appointment's `prescription_issued` flag and writes an audit entry.
+pub struct PrescriptionService {
+    supabase: SupabaseClient,
+    audit_log: AuditLog,
+}
+
+impl PrescriptionService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            audit_log: AuditLog::new(config),
+        }
+    }
+
+    pub async fn issue(
+        &self,
+        appointment: &Appointment,
+        issued_by: Uuid,
+        request: IssuePrescriptionRequest,
+        auth_token: &str,
+    ) -> Result<Prescription, AppointmentError> {
+        debug!("Issuing prescription for appointment {}", appointment.id);
+
+        // Only a consultation that actually took place can produce a prescription
+        if !matches!(appointment.status, AppointmentStatus::InProgress | AppointmentStatus::Completed) {
+            return Err(AppointmentError::InvalidStatusTransition(appointment.status.clone()));
+        }
+
+        validate_medications(&request)?;
+
+        let prescription_data = json!({
+            "id": Uuid::new_v4(),
+            "appointment_id": appointment.id,
+            "patient_id": appointment.patient_id,
+            "medications": request.medications,
+            "issued_by": issued_by,
+            "issued_at": Utc::now().to_rfc3339(),
+            "notes": request.notes
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/prescriptions",
+            Some(auth_token),
+            Some(prescription_data),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let prescription: Prescription = result.into_iter().next()
+            .ok_or_else(|| AppointmentError::DatabaseError("Failed to store prescription".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse prescription: {}", e))))?;
+
+        let update_data = json!({
+            "prescription_issued": true,
+            "updated_at": Utc::now().to_rfc3339()
+        });
+        versioning::set_appointment_fields(&self.supabase, appointment.id, update_data, auth_token).await?;
+
+        let audit_entry = AuditEntry::new(issued_by.to_string(), "prescription.issued", "prescription", prescription.id.to_string())
+            .with_details(json!({
+                "appointment_id": appointment.id,
+                "patient_id": appointment.patient_id,
+                "medication_count": prescription.medications.len()
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit prescription {}: {}", prescription.id, e);
+        }
+
+        info!("Prescription {} issued for appointment {}", prescription.id, appointment.id);
+        Ok(prescription)
+    }
+
+    /// Prescriptions for an appointment, oldest first
+    pub async fn get_for_appointment(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Vec<Prescription>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/prescriptions?appointment_id=eq.{}&order=issued_at.asc",
+            appointment_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<Prescription>, _>>()
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse prescriptions: {}", e)))
+    }
+
+    /// Every prescription issued to a patient, oldest first
+    pub async fn get_for_patient(
+        &self,
+        patient_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Vec<Prescription>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/prescriptions?patient_id=eq.{}&order=issued_at.asc",
+            patient_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<Prescription>, _>>()
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse prescriptions: {}", e)))
+    }
+}
+
+fn validate_medications(request: &IssuePrescriptionRequest) -> Result<(), AppointmentError> {
+    if request.medications.is_empty() {
+        return Err(AppointmentError::ValidationError(
+            "A prescription needs at least one medication".to_string()
+        ));
+    }
+
+    for medication in &request.medications {
+        if medication.name.trim().is_empty()
+            || medication.dosage.trim().is_empty()
+            || medication.frequency.trim().is_empty()
+        {
+            return Err(AppointmentError::ValidationError(
+                "Each medication needs a name, dosage and frequency".to_string()
+            ));
+        }
+        if medication.duration_days.is_some_and(|days| days <= 0) {
+            return Err(AppointmentError::ValidationError(format!(
+                "Duration for {} must be a positive number of days", medication.name
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+// libs/appointment-cell/src/services/schedule_lock.rs
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use uuid::Uuid;
+
+/// Held while a doctor's schedule is checked for conflicts and written
+pub type ScheduleGuard = OwnedMutexGuard<()>;
+
+/// Serialize the check-then-write steps of booking and rescheduling for one
+/// doctor, so a slot found free can't be taken before it is written. This
+/// only orders writers within this process. Cross-instance double booking
+/// is not handled yet: two API instances can still book the same slot. The
+/// guarded update in `reschedule_appointment` only catches a concurrent
+/// change to the appointment being moved.
+pub async fn lock_doctor_schedule(doctor_id: Uuid) -> ScheduleGuard {
+    static LOCKS: OnceLock<Mutex<HashMap<Uuid, Arc<AsyncMutex<()>>>>> = OnceLock::new();
+
+    let lock = {
+        let mut locks = LOCKS.get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        // Drop locks nobody is holding or waiting on
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        Arc::clone(locks.entry(doctor_id).or_default())
+    };
+
+    lock.lock_owned().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_same_doctor_waits_other_doctors_do_not() {
+        let doctor_id = Uuid::new_v4();
+        let guard = lock_doctor_schedule(doctor_id).await;
+
+        let waiting = tokio::spawn(lock_doctor_schedule(doctor_id));
+        assert!(tokio::time::timeout(Duration::from_millis(50), lock_doctor_schedule(Uuid::new_v4())).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(guard);
+        assert!(tokio::time::timeout(Duration::from_secs(1), waiting).await.is_ok());
+    }
+}
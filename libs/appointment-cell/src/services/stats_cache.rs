@@ -0,0 +1,56 @@
+// libs/appointment-cell/src/services/stats_cache.rs
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::AppointmentStats;
+
+/// Which appointments a set of stats covers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatsScope {
+    pub patient_id: Option<Uuid>,
+    pub doctor_id: Option<Uuid>,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+}
+
+/// Exact stats from a rebuild and when they were computed
+#[derive(Debug, Clone, Serialize)]
+pub struct RebuiltStats {
+    pub stats: AppointmentStats,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Rebuilt stats per scope, kept in this process only. Reads are served from
+/// here until the TTL runs out; after that the stats endpoint falls back to
+/// computing them on the fly until the next rebuild.
+fn cache() -> &'static Mutex<HashMap<StatsScope, RebuiltStats>> {
+    static CACHE: OnceLock<Mutex<HashMap<StatsScope, RebuiltStats>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn is_fresh(rebuilt: &RebuiltStats, ttl: Duration) -> bool {
+    (Utc::now() - rebuilt.computed_at).to_std().is_ok_and(|age| age < ttl)
+}
+
+pub fn get(scope: &StatsScope, ttl: Duration) -> Option<RebuiltStats> {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    match cache.get(scope) {
+        Some(rebuilt) if is_fresh(rebuilt, ttl) => Some(rebuilt.clone()),
+        Some(_) => {
+            cache.remove(scope);
+            None
+        }
+        None => None,
+    }
+}
+
+pub fn insert(scope: StatsScope, rebuilt: RebuiltStats, ttl: Duration) {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|_, cached| is_fresh(cached, ttl));
+    cache.insert(scope, rebuilt);
+}
@@ -0,0 +1,211 @@
+// libs/appointment-cell/src/services/timeline.rs
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use health_profile_cell::api::{DocumentService, HealthProfileService};
+use health_profile_cell::models::{Document, HealthProfile};
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_models::auth::{Role, User};
+
+use crate::models::{
+    Appointment, AppointmentError, AppointmentSearchQuery, AppointmentStatus, ConsultationOutcome,
+    Prescription,
+};
+use crate::services::booking::AppointmentBookingService;
+use crate::services::prescriptions::PrescriptionService;
+
+/// One thing that happened in a patient's care
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEntry {
+    pub occurred_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub item: TimelineItem,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "details", rename_all = "snake_case")]
+pub enum TimelineItem {
+    Appointment(Box<Appointment>),
+    Outcome {
+        appointment_id: Uuid,
+        outcome: ConsultationOutcome,
+    },
+    Prescription(Prescription),
+    Document(Document),
+}
+
+/// The parts of the health profile worth reading before a consultation.
+/// Emergency contact details are left out; they're a third party's.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileHighlights {
+    pub blood_type: Option<String>,
+    pub allergies: Option<String>,
+    pub chronic_conditions: Vec<String>,
+    pub medications: Option<String>,
+    pub is_pregnant: Option<bool>,
+    pub is_breastfeeding: Option<bool>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<HealthProfile> for ProfileHighlights {
+    fn from(profile: HealthProfile) -> Self {
+        Self {
+            blood_type: profile.blood_type,
+            allergies: profile.allergies,
+            chronic_conditions: profile.chronic_conditions.unwrap_or_default(),
+            medications: profile.medications,
+            is_pregnant: profile.is_pregnant,
+            is_breastfeeding: profile.is_breastfeeding,
+            updated_at: profile.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatientTimeline {
+    pub patient_id: Uuid,
+    /// `None` when the patient has no health profile
+    pub highlights: Option<ProfileHighlights>,
+    /// Oldest first
+    pub entries: Vec<TimelineEntry>,
+}
+
+/// A patient's history in one place, for a clinician preparing a
+/// consultation: past appointments and their outcomes, prescriptions and
+/// documents, plus the profile highlights
+pub struct PatientTimelineService {
+    booking_service: AppointmentBookingService,
+    prescription_service: PrescriptionService,
+    document_service: DocumentService,
+    profile_service: HealthProfileService,
+    audit_log: AuditLog,
+    allow_any_doctor: bool,
+}
+
+impl PatientTimelineService {
+    pub fn new(config: &AppConfig, booking_service: AppointmentBookingService) -> Self {
+        Self {
+            booking_service,
+            prescription_service: PrescriptionService::new(config),
+            document_service: DocumentService::new(config),
+            profile_service: HealthProfileService::new(config),
+            audit_log: AuditLog::new(config),
+            allow_any_doctor: config.allow_any_doctor_profile_access,
+        }
+    }
+
+    /// The patient's timeline as `viewer` may see it. Admins see any
+    /// patient's; doctors only those of patients they have (or had) a
+    /// non-cancelled appointment with. Every decision is audited.
+    pub async fn build(
+        &self,
+        patient_id: Uuid,
+        viewer: &User,
+        auth_token: &str,
+    ) -> Result<PatientTimeline, AppointmentError> {
+        debug!("Building timeline for patient {}", patient_id);
+
+        let query = AppointmentSearchQuery {
+            patient_id: Some(patient_id),
+            doctor_id: None,
+            status: None,
+            appointment_type: None,
+            from_date: None,
+            to_date: None,
+            reason_contains: None,
+            limit: None,
+            offset: None,
+        };
+        let appointments = self.booking_service.search_appointments(query, auth_token).await?;
+
+        let allowed = self.can_view(viewer, &appointments);
+        self.audit(patient_id, viewer, allowed, auth_token).await;
+        if !allowed {
+            return Err(AppointmentError::Unauthorized);
+        }
+
+        let prescriptions = self.prescription_service.get_for_patient(patient_id, auth_token).await?;
+        let documents = self.document_service.get_documents(&patient_id.to_string(), auth_token).await
+            .map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+        let highlights = match self.profile_service.get_profile(&patient_id.to_string(), auth_token).await {
+            Ok(profile) => Some(ProfileHighlights::from(profile)),
+            Err(e) => {
+                debug!("No health profile highlights for patient {}: {}", patient_id, e);
+                None
+            }
+        };
+
+        Ok(PatientTimeline {
+            patient_id,
+            highlights,
+            entries: timeline_entries(appointments, prescriptions, documents, Utc::now()),
+        })
+    }
+
+    fn can_view(&self, viewer: &User, appointments: &[Appointment]) -> bool {
+        if viewer.is_admin() {
+            return true;
+        }
+        if !viewer.has_role(Role::Doctor) {
+            return false;
+        }
+        self.allow_any_doctor || appointments.iter().any(|appointment| {
+            appointment.doctor_id.to_string() == viewer.id
+                && appointment.status != AppointmentStatus::Cancelled
+        })
+    }
+
+    async fn audit(&self, patient_id: Uuid, viewer: &User, allowed: bool, auth_token: &str) {
+        let action = if allowed { "patient.timeline_viewed" } else { "patient.timeline_access_denied" };
+        let entry = AuditEntry::new(viewer.id.clone(), action, "patient", patient_id.to_string())
+            .with_details(json!({ "viewer_role": viewer.role }));
+        if let Err(e) = self.audit_log.record(entry, auth_token).await {
+            warn!("Failed to audit timeline access for patient {}: {}", patient_id, e);
+        }
+    }
+}
+
+/// Appointments that had started by `now`, their outcomes, prescriptions and
+/// documents, oldest first. An outcome follows its appointment when both
+/// carry the same time.
+pub fn timeline_entries(
+    appointments: Vec<Appointment>,
+    prescriptions: Vec<Prescription>,
+    documents: Vec<Document>,
+    now: DateTime<Utc>,
+) -> Vec<TimelineEntry> {
+    let mut entries = Vec::new();
+
+    for appointment in appointments {
+        let started_at = appointment.actual_start_time.unwrap_or(appointment.scheduled_start_time);
+        if started_at > now {
+            continue;
+        }
+        let outcome = appointment.outcome.clone().map(|outcome| TimelineEntry {
+            occurred_at: outcome.recorded_at,
+            item: TimelineItem::Outcome { appointment_id: appointment.id, outcome },
+        });
+        entries.push(TimelineEntry {
+            occurred_at: started_at,
+            item: TimelineItem::Appointment(Box::new(appointment)),
+        });
+        entries.extend(outcome);
+    }
+
+    entries.extend(prescriptions.into_iter().map(|prescription| TimelineEntry {
+        occurred_at: prescription.issued_at,
+        item: TimelineItem::Prescription(prescription),
+    }));
+    entries.extend(documents.into_iter().map(|document| TimelineEntry {
+        occurred_at: document.uploaded_at,
+        item: TimelineItem::Document(document),
+    }));
+
+    // Stable, so ties keep the order they were added in
+    entries.sort_by_key(|entry| entry.occurred_at);
+    entries
+}
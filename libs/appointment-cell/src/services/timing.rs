@@ -0,0 +1,216 @@
+// libs/appointment-cell/src/services/timing.rs
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use shared_database::supabase::SupabaseClient;
+use doctor_cell::models::Specialty;
+
+use crate::models::{
+    AppointmentError, AppointmentTiming, AppointmentTimingOverride, AppointmentType,
+    SetTimingOverrideRequest,
+};
+
+/// Per-doctor and per-specialty appointment durations, buffers and overlap
+/// capacity, layered over the defaults in `AppointmentType::meta`
+pub struct AppointmentTimingService {
+    supabase: Arc<SupabaseClient>,
+}
+
+impl AppointmentTimingService {
+    pub fn new(supabase: Arc<SupabaseClient>) -> Self {
+        Self { supabase }
+    }
+
+    /// Effective duration, buffer and capacity for booking `appointment_type` with a doctor
+    pub async fn resolve_timing(
+        &self,
+        doctor_id: Uuid,
+        appointment_type: &AppointmentType,
+        auth_token: &str,
+    ) -> Result<AppointmentTiming, AppointmentError> {
+        let specialty = self.doctor_specialty(doctor_id, auth_token).await?;
+
+        let scope = match specialty {
+            Some(specialty) => format!(
+                "or=(doctor_id.eq.{},specialty.eq.{})",
+                doctor_id,
+                specialty_key(specialty)
+            ),
+            None => format!("doctor_id=eq.{}", doctor_id),
+        };
+        let path = format!(
+            "/rest/v1/appointment_timing_overrides?appointment_type=eq.{}&{}",
+            appointment_type, scope
+        );
+        let overrides = self.fetch_overrides(&path, auth_token).await?;
+
+        // The doctor's own setting wins over their specialty's
+        let doctor_override = overrides.iter().find(|o| o.doctor_id == Some(doctor_id));
+        let specialty_override = overrides.iter().find(|o| o.specialty.is_some());
+        let layers: Vec<&AppointmentTimingOverride> = doctor_override.into_iter().chain(specialty_override).collect();
+
+        let timing = AppointmentTiming::resolve(appointment_type, &layers);
+        debug!("Resolved {} timing for doctor {}: {:?}", appointment_type, doctor_id, timing);
+        Ok(timing)
+    }
+
+    pub async fn list_doctor_overrides(
+        &self,
+        doctor_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Vec<AppointmentTimingOverride>, AppointmentError> {
+        let path = format!("/rest/v1/appointment_timing_overrides?doctor_id=eq.{}", doctor_id);
+        self.fetch_overrides(&path, auth_token).await
+    }
+
+    pub async fn list_specialty_overrides(
+        &self,
+        specialty: Specialty,
+        auth_token: &str,
+    ) -> Result<Vec<AppointmentTimingOverride>, AppointmentError> {
+        let path = format!(
+            "/rest/v1/appointment_timing_overrides?specialty=eq.{}",
+            specialty_key(specialty)
+        );
+        self.fetch_overrides(&path, auth_token).await
+    }
+
+    /// Set a doctor's duration/buffer/capacity for one type. Leaving all three
+    /// unset clears the override so the specialty or type default applies again.
+    pub async fn set_doctor_override(
+        &self,
+        doctor_id: Uuid,
+        request: SetTimingOverrideRequest,
+        updated_by: Uuid,
+        auth_token: &str,
+    ) -> Result<AppointmentTimingOverride, AppointmentError> {
+        validate_override(&request)?;
+
+        let body = json!({
+            "doctor_id": doctor_id,
+            "specialty": null,
+            "appointment_type": request.appointment_type,
+            "duration_minutes": request.duration_minutes,
+            "buffer_minutes": request.buffer_minutes,
+            "max_concurrent": request.max_concurrent,
+            "updated_by": updated_by,
+            "updated_at": Utc::now(),
+        });
+        let timing_override = self.upsert_override("doctor_id,appointment_type", body, auth_token).await?;
+
+        info!("Doctor {} {} timing set by {}", doctor_id, timing_override.appointment_type, updated_by);
+        Ok(timing_override)
+    }
+
+    /// Set a specialty's duration/buffer/capacity for one type
+    pub async fn set_specialty_override(
+        &self,
+        specialty: Specialty,
+        request: SetTimingOverrideRequest,
+        updated_by: Uuid,
+        auth_token: &str,
+    ) -> Result<AppointmentTimingOverride, AppointmentError> {
+        validate_override(&request)?;
+
+        let body = json!({
+            "doctor_id": null,
+            "specialty": specialty,
+            "appointment_type": request.appointment_type,
+            "duration_minutes": request.duration_minutes,
+            "buffer_minutes": request.buffer_minutes,
+            "max_concurrent": request.max_concurrent,
+            "updated_by": updated_by,
+            "updated_at": Utc::now(),
+        });
+        let timing_override = self.upsert_override("specialty,appointment_type", body, auth_token).await?;
+
+        info!("{} {} timing set by {}", specialty, timing_override.appointment_type, updated_by);
+        Ok(timing_override)
+    }
+
+    async fn doctor_specialty(
+        &self,
+        doctor_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Option<Specialty>, AppointmentError> {
+        let path = format!("/rest/v1/doctors?id=eq.{}&select=specialty", doctor_id);
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        Ok(result
+            .first()
+            .and_then(|doctor| doctor["specialty"].as_str())
+            .and_then(Specialty::parse))
+    }
+
+    async fn fetch_overrides(
+        &self,
+        path: &str,
+        auth_token: &str,
+    ) -> Result<Vec<AppointmentTimingOverride>, AppointmentError> {
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<AppointmentTimingOverride>, _>>()
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse timing overrides: {}", e)))
+    }
+
+    async fn upsert_override(
+        &self,
+        on_conflict: &str,
+        body: Value,
+        auth_token: &str,
+    ) -> Result<AppointmentTimingOverride, AppointmentError> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "Prefer",
+            reqwest::header::HeaderValue::from_static("return=representation,resolution=merge-duplicates"),
+        );
+
+        let path = format!("/rest/v1/appointment_timing_overrides?on_conflict={}", on_conflict);
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            &path,
+            Some(auth_token),
+            Some(body),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let row = result.into_iter().next()
+            .ok_or_else(|| AppointmentError::DatabaseError("Failed to save timing override".to_string()))?;
+        serde_json::from_value(row)
+            .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse timing override: {}", e)))
+    }
+}
+
+fn validate_override(request: &SetTimingOverrideRequest) -> Result<(), AppointmentError> {
+    if request.duration_minutes.is_some_and(|minutes| minutes <= 0) {
+        return Err(AppointmentError::ValidationError("Duration must be positive".to_string()));
+    }
+    if request.buffer_minutes.is_some_and(|minutes| minutes < 0) {
+        return Err(AppointmentError::ValidationError("Buffer cannot be negative".to_string()));
+    }
+    if request.max_concurrent.is_some_and(|capacity| capacity < 1) {
+        return Err(AppointmentError::ValidationError("Concurrent capacity must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+/// Overrides store the registry key (`dermatology`), not the display name
+fn specialty_key(specialty: Specialty) -> String {
+    json!(specialty).as_str().unwrap_or_default().to_string()
+}
@@ -0,0 +1,90 @@
+// libs/appointment-cell/src/services/versioning.rs
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{Appointment, AppointmentError};
+
+/// Attempts `set_appointment_fields` makes before giving up on a row that
+/// keeps changing under it
+pub const SET_FIELDS_ATTEMPTS: u32 = 3;
+
+/// Write `changes` over `appointment` as it was read. Every write to an
+/// appointment goes through here: the PATCH only matches while the row is
+/// still at `appointment.version` and bumps it, so no write is lost to
+/// another and a client holding an older `expected_version` is turned away.
+/// `VersionConflict` when the row changed since it was read.
+pub async fn patch_appointment(
+    supabase: &SupabaseClient,
+    appointment: &Appointment,
+    mut changes: Value,
+    auth_token: &str,
+) -> Result<Appointment, AppointmentError> {
+    changes["version"] = json!(appointment.version + 1);
+
+    let path = format!(
+        "/rest/v1/appointments?id=eq.{}&version=eq.{}",
+        appointment.id, appointment.version
+    );
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+    let result: Vec<Value> = supabase.request_with_headers(
+        Method::PATCH,
+        &path,
+        Some(auth_token),
+        Some(changes),
+        Some(headers),
+    ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+    let Some(updated) = result.into_iter().next() else {
+        warn!("Appointment {} changed since version {}", appointment.id, appointment.version);
+        return Err(AppointmentError::VersionConflict(appointment.version));
+    };
+
+    serde_json::from_value(updated)
+        .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse updated appointment: {}", e)))
+}
+
+/// `patch_appointment` for changes that don't depend on the rest of the row,
+/// such as setting a flag once a prescription is stored. A concurrent write
+/// only means reading the new version and trying again.
+pub async fn set_appointment_fields(
+    supabase: &SupabaseClient,
+    appointment_id: Uuid,
+    changes: Value,
+    auth_token: &str,
+) -> Result<Appointment, AppointmentError> {
+    let mut attempt = 1;
+    loop {
+        let current = get_appointment(supabase, appointment_id, auth_token).await?;
+        match patch_appointment(supabase, &current, changes.clone(), auth_token).await {
+            Err(AppointmentError::VersionConflict(version)) if attempt < SET_FIELDS_ATTEMPTS => {
+                debug!("Appointment {} moved past version {}, retrying", appointment_id, version);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+pub async fn get_appointment(
+    supabase: &SupabaseClient,
+    appointment_id: Uuid,
+    auth_token: &str,
+) -> Result<Appointment, AppointmentError> {
+    let path = format!("/rest/v1/appointments?id=eq.{}", appointment_id);
+    let result: Vec<Value> = supabase.request(
+        Method::GET,
+        &path,
+        Some(auth_token),
+        None,
+    ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+    let appointment = result.into_iter().next().ok_or(AppointmentError::NotFound)?;
+    serde_json::from_value(appointment)
+        .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse appointment: {}", e)))
+}
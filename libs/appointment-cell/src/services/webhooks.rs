@@ -0,0 +1,508 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{
+    AppointmentError, AppointmentEventEnvelope, CreateWebhookSubscriptionRequest,
+    WebhookSubscription,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Attempts per subscription before an event is given up on
+pub const DELIVERY_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled for each later one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the worker looks for queued events it wasn't woken for, such as
+/// those queued on another instance or left over from a restart
+const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Queued events claimed per pass
+const OUTBOX_BATCH_SIZE: usize = 50;
+
+/// How long a claimed event is left to its worker before another may take it.
+/// Well past the worst case of every attempt timing out.
+const OUTBOX_LEASE_MINUTES: i64 = 5;
+
+/// Wakes this instance's worker as soon as an event is queued
+static OUTBOX_QUEUED: Notify = Notify::const_new();
+
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// Outbound webhooks for appointment events. Events are delivered from
+/// `webhook_outbox`; each delivery is a JSON POST of the event envelope,
+/// signed with the subscription's secret, retried with backoff and recorded
+/// in `webhook_deliveries`.
+pub struct WebhookService {
+    supabase: SupabaseClient,
+    http: reqwest::Client,
+    worker_key: Option<String>,
+    allow_insecure_urls: bool,
+}
+
+impl WebhookService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            // A redirect would send the signed payload somewhere the
+            // subscription never named
+            http: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+            worker_key: config.webhook_worker_key.clone(),
+            allow_insecure_urls: config.webhook_allow_insecure_urls,
+        }
+    }
+
+    pub async fn create_subscription(
+        &self,
+        request: CreateWebhookSubscriptionRequest,
+        auth_token: &str,
+    ) -> Result<WebhookSubscription, AppointmentError> {
+        validate_webhook_url(&request.url, self.allow_insecure_urls)?;
+        if request.events.is_empty() {
+            return Err(AppointmentError::ValidationError("At least one event is required".to_string()));
+        }
+
+        let secret = match request.secret {
+            Some(secret) if secret.len() < 16 => {
+                return Err(AppointmentError::ValidationError(
+                    "Webhook secret must be at least 16 characters".to_string()
+                ));
+            }
+            Some(secret) => secret,
+            None => format!("whsec_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+        };
+
+        let subscription_data = json!({
+            "id": Uuid::new_v4(),
+            "url": request.url,
+            "events": request.events,
+            "secret": secret,
+            "is_active": true,
+            "created_at": Utc::now().to_rfc3339()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/webhook_subscriptions",
+            Some(auth_token),
+            Some(subscription_data),
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let subscription: WebhookSubscription = result.into_iter().next()
+            .ok_or_else(|| AppointmentError::DatabaseError("Failed to store webhook subscription".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse webhook subscription: {}", e))))?;
+
+        info!("Webhook subscription {} registered for {}", subscription.id, subscription.url);
+        Ok(subscription)
+    }
+
+    pub async fn list_subscriptions(&self, auth_token: &str) -> Result<Vec<WebhookSubscription>, AppointmentError> {
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            "/rest/v1/webhook_subscriptions?order=created_at.desc",
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        parse_subscriptions(result)
+    }
+
+    pub async fn delete_subscription(&self, subscription_id: Uuid, auth_token: &str) -> Result<(), AppointmentError> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let path = format!("/rest/v1/webhook_subscriptions?id=eq.{}", subscription_id);
+        let deleted: Vec<Value> = self.supabase.request_with_headers(
+            Method::DELETE,
+            &path,
+            Some(auth_token),
+            None,
+            Some(headers),
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        if deleted.is_empty() {
+            return Err(AppointmentError::NotFound);
+        }
+
+        info!("Webhook subscription {} deleted", subscription_id);
+        Ok(())
+    }
+
+    /// Deliver one event to every active subscription that asked for it, all
+    /// at once so a slow endpoint only delays its own deliveries. Runs on the
+    /// delivery worker, outside any request, so the span carries the event
+    /// and its appointment.
+    #[instrument(skip_all, fields(
+        event_id = %envelope.id,
+        appointment_id = %envelope.appointment.id,
+        patient_id = %envelope.appointment.patient_id,
+        doctor_id = %envelope.appointment.doctor_id,
+    ))]
+    pub async fn deliver_event(&self, envelope: &AppointmentEventEnvelope) -> Result<(), AppointmentError> {
+        let worker_key = self.worker_key()?;
+
+        let path = format!(
+            "/rest/v1/webhook_subscriptions?is_active=eq.true&events=cs.{{{}}}",
+            envelope.event
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(worker_key),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        // Subscriptions stored before https was required stay undelivered
+        let subscriptions: Vec<WebhookSubscription> = parse_subscriptions(result)?
+            .into_iter()
+            .filter(|subscription| match validate_webhook_url(&subscription.url, self.allow_insecure_urls) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Skipping webhook {}: {}", subscription.id, e);
+                    false
+                }
+            })
+            .collect();
+        debug!("Delivering {} event {} to {} webhooks", envelope.event, envelope.id, subscriptions.len());
+
+        let payload = serde_json::to_vec(envelope)
+            .map_err(|e| AppointmentError::ValidationError(format!("Failed to serialize event: {}", e)))?;
+
+        join_all(subscriptions.iter()
+            .map(|subscription| self.deliver_to(subscription, envelope, &payload, worker_key)))
+            .await;
+
+        Ok(())
+    }
+
+    /// Claim up to `OUTBOX_BATCH_SIZE` queued events, oldest first. Each is
+    /// claimed with a conditional update, so when several instances poll at
+    /// once an event still goes to only one of them. A claim that is never
+    /// marked delivered lapses after the lease and the event is tried again.
+    pub async fn claim_queued_events(&self) -> Result<Vec<AppointmentEventEnvelope>, AppointmentError> {
+        let worker_key = self.worker_key()?;
+        let now = Utc::now();
+        let claimable = format!(
+            "delivered_at=is.null&or=(locked_until.is.null,locked_until.lt.{})",
+            now.to_rfc3339_opts(SecondsFormat::Micros, true)
+        );
+
+        let queued: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &format!("/rest/v1/webhook_outbox?{}&select=id&order=created_at.asc&limit={}", claimable, OUTBOX_BATCH_SIZE),
+            Some(worker_key),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+        let locked_until = now + chrono::Duration::minutes(OUTBOX_LEASE_MINUTES);
+
+        let mut claimed = Vec::new();
+        for row in queued {
+            let Some(id) = row["id"].as_str() else {
+                continue;
+            };
+            let rows: Vec<Value> = self.supabase.request_with_headers(
+                Method::PATCH,
+                &format!("/rest/v1/webhook_outbox?id=eq.{}&{}", id, claimable),
+                Some(worker_key),
+                Some(json!({ "locked_until": locked_until.to_rfc3339() })),
+                Some(headers.clone()),
+            ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+
+            // Taken by another instance in the meantime
+            let Some(row) = rows.into_iter().next() else {
+                continue;
+            };
+            match serde_json::from_value(row["payload"].clone()) {
+                Ok(envelope) => claimed.push(envelope),
+                Err(e) => error!("Queued webhook event {} can't be read: {}", id, e),
+            }
+        }
+
+        Ok(claimed)
+    }
+
+    /// Take a claimed event off the queue once every subscription has had
+    /// its attempts; failures are in `webhook_deliveries`
+    pub async fn mark_delivered(&self, event_id: Uuid) -> Result<(), AppointmentError> {
+        let _: Value = self.supabase.request_with_headers(
+            Method::PATCH,
+            &format!("/rest/v1/webhook_outbox?id=eq.{}", event_id),
+            Some(self.worker_key()?),
+            Some(json!({ "delivered_at": Utc::now().to_rfc3339() })),
+            None,
+        ).await.map_err(|e| AppointmentError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    // ==============================================================================
+    // PRIVATE HELPER METHODS
+    // ==============================================================================
+
+    /// POST the payload until the endpoint answers 2xx or attempts run out
+    async fn deliver_to(
+        &self,
+        subscription: &WebhookSubscription,
+        envelope: &AppointmentEventEnvelope,
+        payload: &[u8],
+        worker_key: &str,
+    ) -> bool {
+        let signature = format!("sha256={}", sign_payload(&subscription.secret, payload));
+
+        for attempt in 1..=DELIVERY_ATTEMPTS {
+            let outcome = self.http.post(&subscription.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .header("X-Webhook-Event", envelope.event.to_string())
+                .header("X-Webhook-Delivery", envelope.id.to_string())
+                .body(payload.to_vec())
+                .send()
+                .await;
+
+            let (status_code, failure) = match outcome {
+                Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+                Ok(response) => (Some(response.status().as_u16()), Some(format!("HTTP {}", response.status()))),
+                Err(e) => (None, Some(e.to_string())),
+            };
+
+            self.record_attempt(subscription, envelope, attempt, status_code, failure.as_deref(), worker_key).await;
+
+            match failure {
+                None => {
+                    debug!("Webhook {} accepted event {} on attempt {}", subscription.id, envelope.id, attempt);
+                    return true;
+                }
+                Some(reason) if attempt < DELIVERY_ATTEMPTS => {
+                    warn!("Webhook {} attempt {} for event {} failed: {}", subscription.id, attempt, envelope.id, reason);
+                    tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+                }
+                Some(reason) => {
+                    error!("Giving up on webhook {} for event {} after {} attempts: {}",
+                           subscription.id, envelope.id, attempt, reason);
+                }
+            }
+        }
+
+        false
+    }
+
+    async fn record_attempt(
+        &self,
+        subscription: &WebhookSubscription,
+        envelope: &AppointmentEventEnvelope,
+        attempt: u32,
+        status_code: Option<u16>,
+        failure: Option<&str>,
+        worker_key: &str,
+    ) {
+        let delivery_data = json!({
+            "subscription_id": subscription.id,
+            "event_id": envelope.id,
+            "event": envelope.event,
+            "appointment_id": envelope.appointment.id,
+            "attempt": attempt,
+            "status_code": status_code,
+            "success": failure.is_none(),
+            "error": failure,
+            "attempted_at": Utc::now().to_rfc3339()
+        });
+
+        // The log is best-effort; a failed insert must not block delivery
+        let logged: Result<Value, _> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/webhook_deliveries",
+            Some(worker_key),
+            Some(delivery_data),
+            None,
+        ).await;
+
+        if let Err(e) = logged {
+            warn!("Failed to record webhook delivery for {}: {}", subscription.id, e);
+        }
+    }
+
+    /// The worker has no user token. It uses the `webhook_worker` role's key,
+    /// which reaches subscriptions and the delivery log but nothing else, so
+    /// delivery never runs with RLS bypassed.
+    fn worker_key(&self) -> Result<&str, AppointmentError> {
+        self.worker_key.as_deref()
+            .ok_or_else(|| AppointmentError::DatabaseError("WEBHOOK_WORKER_KEY is not configured".to_string()))
+    }
+}
+
+/// Events carry patient and doctor notes, so they only go out over https.
+/// `allow_insecure` also accepts http, for local development.
+pub fn validate_webhook_url(url: &str, allow_insecure: bool) -> Result<(), AppointmentError> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|e| AppointmentError::ValidationError(format!("Invalid webhook URL: {}", e)))?;
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if allow_insecure => Ok(()),
+        _ => Err(AppointmentError::ValidationError("Webhook URL must use https".to_string())),
+    }
+}
+
+/// Hex HMAC-SHA256 of the raw request body, sent as `X-Signature: sha256=<hex>`
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Queue of events waiting for the delivery worker. An event is stored in
+/// `webhook_outbox` as it is published, so one raised while the worker is
+/// busy, restarting or running on another instance is still delivered.
+pub struct WebhookOutbox {
+    supabase: Arc<SupabaseClient>,
+    worker_key: Option<String>,
+}
+
+impl WebhookOutbox {
+    pub fn new(supabase: Arc<SupabaseClient>, config: &AppConfig) -> Self {
+        Self {
+            supabase,
+            worker_key: config.webhook_worker_key.clone(),
+        }
+    }
+
+    /// Queue `envelope` for delivery. The change the event reports is already
+    /// saved, so a failure is only logged. Without `WEBHOOK_WORKER_KEY` there
+    /// is no worker to deliver it and nothing is queued.
+    pub async fn enqueue(&self, envelope: &AppointmentEventEnvelope) {
+        let Some(worker_key) = self.worker_key.as_deref() else {
+            return;
+        };
+
+        let row = json!({
+            "id": envelope.id,
+            "event": envelope.event,
+            "appointment_id": envelope.appointment.id,
+            "payload": envelope,
+            "created_at": envelope.occurred_at.to_rfc3339()
+        });
+        let queued: Result<Value, _> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/webhook_outbox",
+            Some(worker_key),
+            Some(row),
+            None,
+        ).await;
+
+        match queued {
+            Ok(_) => {
+                debug!("Queued {} event {} for webhooks", envelope.event, envelope.id);
+                OUTBOX_QUEUED.notify_one();
+            }
+            Err(e) => error!("Failed to queue {} event {} for webhooks: {}", envelope.event, envelope.id, e),
+        }
+    }
+}
+
+/// Start the background task that delivers queued appointment events to
+/// webhook subscribers. Call once at startup. Does nothing without
+/// `WEBHOOK_WORKER_KEY`.
+///
+/// Each event is delivered on its own task, so the worker keeps claiming
+/// events however long an endpoint takes to answer.
+pub fn spawn_delivery_worker(config: &AppConfig) -> Option<JoinHandle<()>> {
+    if config.webhook_worker_key.is_none() {
+        warn!("WEBHOOK_WORKER_KEY not set, webhooks will not be delivered");
+        return None;
+    }
+
+    let service = Arc::new(WebhookService::new(config));
+
+    Some(tokio::spawn(async move {
+        info!("Webhook delivery worker started");
+        let mut poll = tokio::time::interval(OUTBOX_POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {}
+                _ = OUTBOX_QUEUED.notified() => {}
+            }
+
+            let queued = match service.claim_queued_events().await {
+                Ok(queued) => queued,
+                Err(e) => {
+                    warn!("Failed to claim queued webhook events: {}", e);
+                    continue;
+                }
+            };
+            for envelope in queued {
+                let service = Arc::clone(&service);
+                tokio::spawn(async move {
+                    if let Err(e) = service.deliver_event(&envelope).await {
+                        error!("Failed to deliver {} event {}: {}", envelope.event, envelope.id, e);
+                        return;
+                    }
+                    if let Err(e) = service.mark_delivered(envelope.id).await {
+                        warn!("Failed to mark {} event {} delivered: {}", envelope.event, envelope.id, e);
+                    }
+                });
+            }
+        }
+    }))
+}
+
+fn parse_subscriptions(result: Vec<Value>) -> Result<Vec<WebhookSubscription>, AppointmentError> {
+    result.into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<Vec<WebhookSubscription>, _>>()
+        .map_err(|e| AppointmentError::DatabaseError(format!("Failed to parse webhook subscriptions: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_webhook_urls_must_use_https() {
+        assert!(validate_webhook_url("https://partner.example/hooks", false).is_ok());
+        assert!(validate_webhook_url("http://partner.example/hooks", false).is_err());
+        assert!(validate_webhook_url("http://localhost:8080/hooks", true).is_ok());
+        assert!(validate_webhook_url("ftp://partner.example/hooks", true).is_err());
+        assert!(validate_webhook_url("not a url", true).is_err());
+    }
+}
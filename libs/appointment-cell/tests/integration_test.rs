@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use axum::{
     body::Body,
     http::{Request, StatusCode},
@@ -8,14 +8,15 @@ use tower::ServiceExt;
 use serde_json::json;
 use wiremock::{MockServer, Mock, ResponseTemplate};
 use wiremock::matchers::{method, path, header, query_param};
-use chrono::{Utc, Duration, NaiveTime};
+use chrono::{DateTime, Utc, Duration, NaiveTime};
 use uuid::Uuid;
 
 use appointment_cell::router::appointment_routes;
 use appointment_cell::models::{
     BookAppointmentRequest, SmartBookingRequest, UpdateAppointmentRequest, 
     RescheduleAppointmentRequest, CancelAppointmentRequest,
-    AppointmentType, AppointmentStatus, CancelledBy
+    AppointmentType, AppointmentStatus, CancelledBy, AppointmentValidationRules, BusinessHours,
+    CancellationFeePolicy,
 };
 use shared_config::AppConfig;
 use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils, MockSupabaseResponses};
@@ -97,34 +98,23 @@ async fn setup_appointment_mocks(mock_server: &MockServer, patient_id: &str, doc
         .mount(mock_server)
         .await;
     
-    // Mock availability lookup - use broad catch-all for complex queries
-    // Based on doctor-cell success, provide AvailableSlot format that the system expects
-    let tomorrow = chrono::Utc::now() + chrono::Duration::days(1);
+    // No weekly schedule or overrides, so bookings aren't held to slot boundaries
     Mock::given(method("GET"))
         .and(path("/rest/v1/appointment_availabilities"))
-        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
-            {
-                "start_time": format!("{}T10:00:00Z", tomorrow.format("%Y-%m-%d")),
-                "end_time": format!("{}T10:30:00Z", tomorrow.format("%Y-%m-%d")),
-                "duration_minutes": 30,
-                "appointment_type": "consultation", 
-                "timezone": "UTC"
-            },
-            {
-                "start_time": format!("{}T11:00:00Z", tomorrow.format("%Y-%m-%d")),
-                "end_time": format!("{}T11:30:00Z", tomorrow.format("%Y-%m-%d")),
-                "duration_minutes": 30,
-                "appointment_type": "consultation",
-                "timezone": "UTC"
-            },
-            {
-                "start_time": format!("{}T14:00:00Z", tomorrow.format("%Y-%m-%d")),
-                "end_time": format!("{}T14:30:00Z", tomorrow.format("%Y-%m-%d")),
-                "duration_minutes": 30,
-                "appointment_type": "consultation",
-                "timezone": "UTC"
-            }
-        ])))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctor_availability_overrides"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(mock_server)
+        .await;
+    
+    // No per-doctor or per-specialty timing unless a test mounts one first
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_timing_overrides"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
         .mount(mock_server)
         .await;
     
@@ -144,6 +134,13 @@ async fn setup_appointment_mocks(mock_server: &MockServer, patient_id: &str, doc
         ])))
         .mount(mock_server)
         .await;
+
+    // No live slot holds
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_holds"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(mock_server)
+        .await;
 }
 
 #[tokio::test]
@@ -169,7 +166,7 @@ async fn test_book_appointment_success() {
         doctor_id: Some(doctor_id),
         appointment_date: appointment_time,
         appointment_type: AppointmentType::GeneralConsultation,
-        duration_minutes: 30,
+        duration_minutes: Some(30),
         timezone: "UTC".to_string(),
         patient_notes: Some("First consultation".to_string()),
         preferred_language: Some("English".to_string()),
@@ -189,6 +186,140 @@ async fn test_book_appointment_success() {
     assert_eq!(response.status(), StatusCode::OK); // Appointment booking returns 200 OK, not 201 CREATED
 }
 
+/// Stand-in for the appointment_holds table, so a hold outlives the request
+/// that placed it. Only `eq.` and `neq.` filters are applied.
+async fn mock_hold_table(mock_server: &MockServer) {
+    let rows: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+    let matching = |request: &wiremock::Request, row: &serde_json::Value| {
+        request.url.query_pairs().all(|(column, filter)| {
+            let value = row[column.as_ref()].as_str().unwrap_or_default();
+            if let Some(expected) = filter.strip_prefix("eq.") {
+                value == expected
+            } else if let Some(excluded) = filter.strip_prefix("neq.") {
+                value != excluded
+            } else {
+                true
+            }
+        })
+    };
+
+    let table = rows.clone();
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/appointment_holds"))
+        .respond_with(move |request: &wiremock::Request| {
+            let row: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            table.lock().unwrap().push(row.clone());
+            ResponseTemplate::new(201).set_body_json(vec![row])
+        })
+        .mount(mock_server)
+        .await;
+
+    let table = rows.clone();
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_holds"))
+        .respond_with(move |request: &wiremock::Request| {
+            let found: Vec<_> = table.lock().unwrap().iter()
+                .filter(|row| matching(request, row))
+                .cloned()
+                .collect();
+            ResponseTemplate::new(200).set_body_json(found)
+        })
+        .mount(mock_server)
+        .await;
+
+    Mock::given(method("DELETE"))
+        .and(path("/rest/v1/appointment_holds"))
+        .respond_with(move |request: &wiremock::Request| {
+            let mut rows = rows.lock().unwrap();
+            let (deleted, kept): (Vec<_>, Vec<_>) = rows.drain(..).partition(|row| matching(request, row));
+            *rows = kept;
+            ResponseTemplate::new(200).set_body_json(deleted)
+        })
+        .mount(mock_server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_held_slot_is_reserved_until_confirmed() {
+    let mock_server = MockServer::start().await;
+
+    let patient = TestUser::patient("patient@example.com");
+    let admin = TestUser::admin("admin@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let patient_token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let admin_token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let doctor_id = Uuid::new_v4();
+    mock_hold_table(&mock_server).await;
+    setup_appointment_mocks(&mock_server, &patient.id, &doctor_id.to_string()).await;
+
+    let booking = BookAppointmentRequest {
+        patient_id: Uuid::parse_str(&patient.id).unwrap(),
+        doctor_id: Some(doctor_id),
+        appointment_date: Utc::now() + Duration::hours(24),
+        appointment_type: AppointmentType::GeneralConsultation,
+        duration_minutes: Some(30),
+        timezone: "UTC".to_string(),
+        patient_notes: None,
+        preferred_language: None,
+        specialty_required: None,
+    };
+    let post = |uri: String, token: &str, body: String| Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap();
+    let read_json = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let response = app.clone()
+        .oneshot(post("/hold".to_string(), &patient_token, serde_json::to_string(&booking).unwrap()))
+        .await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let hold = read_json(response).await;
+    let hold_id = hold["hold_id"].as_str().unwrap().to_string();
+    assert_eq!(hold["hold"]["doctor_id"], doctor_id.to_string());
+
+    // Nobody else can book into the held slot
+    let response = app.clone()
+        .oneshot(post("/".to_string(), &admin_token, serde_json::to_string(&booking).unwrap()))
+        .await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(read_json(response).await["code"], "appointment.slot_unavailable");
+
+    let confirm_uri = format!("/hold/{}/confirm", hold_id);
+    let details = json!({ "patient_notes": "Recurring migraines" }).to_string();
+    let other_patient = TestUser::patient("other@example.com");
+    let other_token = JwtTestUtils::create_test_token(&other_patient, &config.supabase_jwt_secret, Some(24));
+    let response = app.clone()
+        .oneshot(post(confirm_uri.clone(), &other_token, details.clone()))
+        .await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app.clone()
+        .oneshot(post(confirm_uri.clone(), &patient_token, details.clone()))
+        .await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let created: Vec<serde_json::Value> = mock_server.received_requests().await.unwrap().iter()
+        .filter(|request| request.method.as_str() == "POST" && request.url.path() == "/rest/v1/appointments")
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0]["patient_notes"], "Recurring migraines");
+    assert_eq!(created[0]["doctor_id"], doctor_id.to_string());
+
+    // The hold is used up
+    let response = app.oneshot(post(confirm_uri, &patient_token, details)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[tokio::test]
 async fn test_smart_book_appointment_success() {
     let mock_server = MockServer::start().await;
@@ -217,6 +348,9 @@ async fn test_smart_book_appointment_success() {
         specialty_required: Some("General Practice".to_string()),
         patient_notes: Some("Smart booking test".to_string()),
         allow_history_prioritization: Some(true),
+        max_alternative_doctors: None,
+        max_slots_per_doctor: None,
+        max_alternatives: None,
     };
 
     let request = Request::builder()
@@ -236,6 +370,181 @@ async fn test_smart_book_appointment_success() {
     println!("Smart booking test - returning {} (expected 200, needs more work)", status);
 }
 
+#[tokio::test]
+async fn test_book_appointment_uses_specialty_duration_default() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+    let doctor_id = Uuid::new_v4();
+
+    // The doctor's own buffer, plus a longer general-practice consult
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_timing_overrides"))
+        .and(query_param("appointment_type", "eq.general_consultation"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {
+                "id": Uuid::new_v4(),
+                "doctor_id": doctor_id,
+                "specialty": null,
+                "appointment_type": "general_consultation",
+                "duration_minutes": null,
+                "buffer_minutes": 15,
+                "updated_by": doctor_id,
+                "updated_at": "2024-01-01T00:00:00Z"
+            },
+            {
+                "id": Uuid::new_v4(),
+                "doctor_id": null,
+                "specialty": "general_practice",
+                "appointment_type": "general_consultation",
+                "duration_minutes": 45,
+                "buffer_minutes": 0,
+                "updated_by": Uuid::new_v4(),
+                "updated_at": "2024-01-01T00:00:00Z"
+            }
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    setup_appointment_mocks(&mock_server, &user.id, &doctor_id.to_string()).await;
+
+    let request_body = json!({
+        "patient_id": user.id,
+        "doctor_id": doctor_id,
+        "appointment_date": Utc::now() + Duration::hours(24),
+        "appointment_type": "general_consultation",
+        "timezone": "UTC"
+    });
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/")
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(request_body.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let created = requests
+        .iter()
+        .find(|r| r.method.as_str() == "POST" && r.url.path() == "/rest/v1/appointments")
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&created.body).unwrap();
+    assert_eq!(body["duration_minutes"], 45);
+
+    // Overrides are looked up for the doctor and their registered specialty
+    let specialty_lookup = requests
+        .iter()
+        .find(|r| r.url.path() == "/rest/v1/appointment_timing_overrides")
+        .unwrap();
+    assert!(specialty_lookup.url.query().unwrap().contains("specialty.eq.general_practice"));
+}
+
+/// Book a prescription slot with a doctor whose prescription capacity is 2,
+/// while `already_booked` prescriptions overlap the slot
+async fn book_prescription_with_overlaps(already_booked: usize) -> (StatusCode, serde_json::Value) {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+    let doctor_id = Uuid::new_v4();
+    let start = Utc::now() + Duration::hours(24);
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_timing_overrides"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "doctor_id": doctor_id,
+            "specialty": null,
+            "appointment_type": "prescription",
+            "duration_minutes": null,
+            "buffer_minutes": null,
+            "max_concurrent": 2,
+            "updated_by": doctor_id,
+            "updated_at": "2024-01-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    let overlapping: Vec<serde_json::Value> = (0..already_booked)
+        .map(|_| {
+            let mut appointment = MockSupabaseResponses::appointment_response(&Uuid::new_v4().to_string(), &doctor_id.to_string());
+            appointment["appointment_type"] = json!("prescription");
+            appointment["scheduled_start_time"] = json!(start.to_rfc3339());
+            appointment["scheduled_end_time"] = json!((start + Duration::minutes(15)).to_rfc3339());
+            appointment
+        })
+        .collect();
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(overlapping))
+        .mount(&mock_server)
+        .await;
+
+    setup_appointment_mocks(&mock_server, &user.id, &doctor_id.to_string()).await;
+
+    let request_body = json!({
+        "patient_id": user.id,
+        "doctor_id": doctor_id,
+        "appointment_date": start,
+        "appointment_type": "prescription",
+        "duration_minutes": 15,
+        "timezone": "UTC"
+    });
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/")
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(request_body.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_concurrent_bookings_allowed_up_to_capacity() {
+    // Second prescription in the slot fits, the third does not
+    assert_eq!(book_prescription_with_overlaps(1).await.0, StatusCode::OK);
+    assert_eq!(book_prescription_with_overlaps(2).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_booking_conflict_suggests_alternatives() {
+    let (status, body) = book_prescription_with_overlaps(2).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "appointment.conflict");
+
+    let alternatives = body["suggested_alternatives"].as_array().unwrap();
+    assert!(!alternatives.is_empty());
+    assert!(alternatives.len() <= 5);
+    for slot in alternatives {
+        assert_eq!(slot["appointment_type"], "prescription");
+        let start: chrono::DateTime<Utc> = serde_json::from_value(slot["start_time"].clone()).unwrap();
+        let end: chrono::DateTime<Utc> = serde_json::from_value(slot["end_time"].clone()).unwrap();
+        assert!(start > Utc::now());
+        assert_eq!(end - start, Duration::minutes(15));
+    }
+}
+
 #[tokio::test]
 async fn test_get_appointment_success() {
     let mock_server = MockServer::start().await;
@@ -287,7 +596,7 @@ async fn test_get_appointment_success() {
 
     let request = Request::builder()
         .method("GET")
-        .uri(&format!("/{}", appointment_id))
+        .uri(format!("/{}", appointment_id))
         .header("authorization", format!("Bearer {}", token))
         .body(Body::empty())
         .unwrap();
@@ -352,11 +661,13 @@ async fn test_update_appointment_success() {
         patient_notes: None,
         reschedule_to: None,
         reschedule_duration: None,
+        cancellation_reason: None,
+        expected_version: Some(0),
     };
 
     let request = Request::builder()
         .method("PUT")
-        .uri(&format!("/{}", appointment_id))
+        .uri(format!("/{}", appointment_id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(serde_json::to_string(&update_body).unwrap()))
@@ -440,11 +751,12 @@ async fn test_reschedule_appointment_success() {
         new_start_time: new_time,
         new_duration_minutes: Some(45),
         reason: Some("Schedule conflict".to_string()),
+        override_notice: false,
     };
 
     let request = Request::builder()
         .method("PATCH")
-        .uri(&format!("/{}/reschedule", appointment_id))
+        .uri(format!("/{}/reschedule", appointment_id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(serde_json::to_string(&reschedule_body).unwrap()))
@@ -519,11 +831,12 @@ async fn test_cancel_appointment_success() {
     let cancel_body = CancelAppointmentRequest {
         reason: "Family emergency".to_string(),
         cancelled_by: CancelledBy::Patient,
+        cancellation_reason: None,
     };
 
     let request = Request::builder()
         .method("POST")
-        .uri(&format!("/{}/cancel", appointment_id))
+        .uri(format!("/{}/cancel", appointment_id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(serde_json::to_string(&cancel_body).unwrap()))
@@ -624,7 +937,7 @@ async fn test_check_appointment_conflicts_success() {
     
     let request = Request::builder()
         .method("GET")
-        .uri(&format!("/conflicts/check?doctor_id={}&start_time={}&end_time={}", 
+        .uri(format!("/conflicts/check?doctor_id={}&start_time={}&end_time={}", 
             user.id, start_encoded, end_encoded))
         .header("authorization", format!("Bearer {}", token))
         .body(Body::empty())
@@ -682,6 +995,51 @@ async fn test_get_appointment_stats_success() {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn test_get_appointment_stats_flags_continuity_fallback() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+
+    // The continuity rate reads the patient's completed appointments separately
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("status", "eq.completed"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("connection reset"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            MockSupabaseResponses::appointment_response(&user.id, &Uuid::new_v4().to_string())
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let request = Request::builder()
+        .method("GET")
+        .uri("/stats")
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["stats"]["total_appointments"], 1);
+    assert_eq!(body["stats"]["doctor_continuity_rate"], 0.0);
+    assert_eq!(body["degraded"], true);
+    assert_eq!(body["degraded_reason"], "Doctor continuity rate could not be calculated");
+}
+
 #[tokio::test]
 async fn test_unauthorized_requests() {
     let config = TestConfig::default().to_app_config();
@@ -712,6 +1070,26 @@ async fn test_unauthorized_requests() {
     }
 }
 
+#[tokio::test]
+async fn test_admin_only_routes_reject_patients() {
+    let config = TestConfig::default().to_app_config();
+    let user = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+
+    for uri in ["/export", "/webhooks"] {
+        let app = create_test_app(config.clone()).await;
+        let request = Request::builder()
+            .method("GET")
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "Failed for GET {}", uri);
+    }
+}
+
 #[tokio::test]
 async fn test_invalid_token_requests() {
     let config = TestConfig::default().to_app_config();
@@ -728,4 +1106,787 @@ async fn test_invalid_token_requests() {
 
     let response = app.oneshot(request).await.unwrap();
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_appointment_type_meta_consistency() {
+    let urgent = AppointmentType::Urgent;
+    assert_eq!(urgent.get_appointment_timing(), (30, 10));
+    assert!(!urgent.is_telemedicine_capable());
+    assert!(!urgent.supports_concurrent_appointments());
+    assert!(urgent.priority() > AppointmentType::GeneralConsultation.priority());
+    assert!(urgent.allowed_out_of_hours());
+    assert!(!AppointmentType::GeneralConsultation.allowed_out_of_hours());
+
+    assert!(AppointmentType::GeneralConsultation.is_telemedicine_capable());
+    assert!(AppointmentType::Prescription.supports_concurrent_appointments());
+    assert_eq!(urgent.max_concurrent(), 1);
+
+    // Legacy "consultation" rows map onto the general consultation metadata
+    let legacy: AppointmentType = serde_json::from_value(json!("consultation")).unwrap();
+    assert_eq!(legacy, AppointmentType::GeneralConsultation);
+}
+
+#[test]
+fn test_status_and_type_accept_legacy_casings() {
+    for raw in ["in_progress", "InProgress", "IN_PROGRESS", "in-progress", "In Progress"] {
+        let status: AppointmentStatus = serde_json::from_value(json!(raw)).unwrap();
+        assert_eq!(status, AppointmentStatus::InProgress, "{}", raw);
+    }
+    for raw in ["no_show", "NoShow", "NO_SHOW"] {
+        let status: AppointmentStatus = serde_json::from_value(json!(raw)).unwrap();
+        assert_eq!(status, AppointmentStatus::NoShow, "{}", raw);
+    }
+    for raw in ["womens_health", "WomensHealth", "WOMENS_HEALTH"] {
+        let appointment_type: AppointmentType = serde_json::from_value(json!(raw)).unwrap();
+        assert_eq!(appointment_type, AppointmentType::WomensHealth, "{}", raw);
+    }
+    assert!(serde_json::from_value::<AppointmentStatus>(json!("booked")).is_err());
+
+    // Writes and filters only ever use the canonical form
+    assert_eq!(serde_json::to_value(AppointmentStatus::InProgress).unwrap(), json!("in_progress"));
+    assert_eq!(AppointmentStatus::InProgress.to_string(), "in_progress");
+    assert_eq!(
+        serde_json::to_value(AppointmentType::GeneralConsultation).unwrap(),
+        json!(AppointmentType::GeneralConsultation.as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_configured_cancellation_notice_is_enforced() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    config.appointment_rules.cancellation_notice_hours = Some(48);
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+    let appointment_id = Uuid::new_v4();
+    let doctor_id = Uuid::new_v4().to_string();
+
+    // Outside the default 24h window but inside the configured 48h one
+    let start = Utc::now() + chrono::Duration::hours(25);
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("id", format!("eq.{}", appointment_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {
+                "id": appointment_id.to_string(),
+                "patient_id": user.id.clone(),
+                "doctor_id": doctor_id.clone(),
+                "appointment_date": start.to_rfc3339(),
+                "status": "confirmed",
+                "appointment_type": "general_consultation",
+                "duration_minutes": 30,
+                "timezone": "UTC",
+                "scheduled_start_time": start.to_rfc3339(),
+                "scheduled_end_time": (start + chrono::Duration::minutes(30)).to_rfc3339(),
+                "actual_start_time": null,
+                "actual_end_time": null,
+                "notes": null,
+                "patient_notes": null,
+                "doctor_notes": null,
+                "prescription_issued": false,
+                "medical_certificate_issued": false,
+                "report_generated": false,
+                "video_conference_link": null,
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z"
+            }
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let cancel_body = CancelAppointmentRequest {
+        reason: "Change of plans".to_string(),
+        cancelled_by: CancelledBy::Patient,
+        cancellation_reason: None,
+    };
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/cancel", appointment_id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_string(&cancel_body).unwrap()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["detail"].as_str().unwrap().contains("48 hours"));
+}
+
+#[tokio::test]
+async fn test_reschedule_notice_override_is_staff_only_and_audited() {
+    let mock_server = MockServer::start().await;
+
+    let patient = TestUser::patient("patient@example.com");
+    let admin = TestUser::admin("admin@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let appointment_id = Uuid::new_v4();
+    let doctor_id = Uuid::new_v4().to_string();
+
+    // Inside the default 48h reschedule notice
+    let start = Utc::now() + Duration::hours(6);
+    let mut appointment = MockSupabaseResponses::appointment_response(&patient.id, &doctor_id);
+    appointment["id"] = json!(appointment_id);
+    appointment["scheduled_start_time"] = json!(start.to_rfc3339());
+    appointment["scheduled_end_time"] = json!((start + Duration::minutes(30)).to_rfc3339());
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("id", format!("eq.{}", appointment_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([appointment])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([appointment])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    setup_appointment_mocks(&mock_server, &patient.id, &doctor_id).await;
+
+    let reschedule = |user: &TestUser, override_notice: bool| {
+        let token = JwtTestUtils::create_test_token(user, &config.supabase_jwt_secret, Some(24));
+        let body = RescheduleAppointmentRequest {
+            new_start_time: Utc::now() + Duration::hours(30),
+            new_duration_minutes: None,
+            reason: Some("Doctor called in sick".to_string()),
+            override_notice,
+        };
+        Request::builder()
+            .method("PATCH")
+            .uri(format!("/{}/reschedule", appointment_id))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_string(&body).unwrap()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(reschedule(&patient, false)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app.clone().oneshot(reschedule(&patient, true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app.oneshot(reschedule(&admin, true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let audit: Vec<serde_json::Value> = mock_server.received_requests().await.unwrap().iter()
+        .filter(|request| request.url.path() == "/rest/v1/audit_log")
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    assert_eq!(audit[0]["action"], "appointment.reschedule_notice_overridden");
+    assert_eq!(audit[0]["actor_id"], admin.id);
+    assert_eq!(audit[0]["details"]["actor_role"], "admin");
+}
+
+#[test]
+fn test_validation_rules_from_config() {
+    let mut config = TestConfig::default().to_app_config();
+
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    assert_eq!(rules.allowed_cancellation_hours, 24);
+
+    config.appointment_rules.cancellation_notice_hours = Some(12);
+    config.appointment_rules.max_duration_minutes = Some(60);
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    assert_eq!(rules.allowed_cancellation_hours, 12);
+    assert_eq!(rules.max_appointment_duration, 60);
+    assert_eq!(rules.min_appointment_duration, 15);
+
+    // Minimum duration must stay below the maximum
+    config.appointment_rules.min_duration_minutes = Some(60);
+    assert!(AppointmentValidationRules::from_config(&config).is_err());
+
+    config.appointment_rules.min_duration_minutes = None;
+    config.appointment_rules.reschedule_notice_hours = Some(-1);
+    assert!(AppointmentValidationRules::from_config(&config).is_err());
+}
+
+#[test]
+fn test_cancellation_fee_policy_precedence() {
+    let doctor_id = Uuid::new_v4();
+    let policy = CancellationFeePolicy::parse(&format!("default=48, urgent=0, doctor:{}=72", doctor_id)).unwrap();
+
+    assert_eq!(policy.fee_window_hours(Uuid::new_v4(), &AppointmentType::FollowUp), Some(48));
+    assert_eq!(policy.fee_window_hours(Uuid::new_v4(), &AppointmentType::Urgent), Some(0));
+    // The doctor's own window wins over the appointment type's
+    assert_eq!(policy.fee_window_hours(doctor_id, &AppointmentType::Urgent), Some(72));
+
+    // No policy never flags a fee
+    assert_eq!(CancellationFeePolicy::default().fee_window_hours(doctor_id, &AppointmentType::FollowUp), None);
+
+    assert!(CancellationFeePolicy::parse("default=-1").is_err());
+    assert!(CancellationFeePolicy::parse("checkup=24").is_err());
+    assert!(CancellationFeePolicy::parse("doctor:someone=24").is_err());
+    assert!(CancellationFeePolicy::parse("default=24, default=48").is_err());
+
+    let mut config = TestConfig::default().to_app_config();
+    config.appointment_rules.cancellation_fee_hours = Some("follow-up=12".to_string());
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    assert_eq!(rules.cancellation_fee.by_type.get(&AppointmentType::FollowUp), Some(&12));
+}
+
+#[test]
+fn test_business_hours_are_timezone_aware() {
+    let hours = BusinessHours::parse("mon-fri 08:00-18:00, sat 09:00-13:00", "Europe/London").unwrap();
+    let at = |value: &str| value.parse::<DateTime<Utc>>().unwrap();
+    let thirty_minutes = Duration::minutes(30);
+
+    // Monday in winter, London on GMT
+    assert!(hours.covers(at("2025-03-03T08:00:00Z"), at("2025-03-03T08:00:00Z") + thirty_minutes));
+    assert!(!hours.covers(at("2025-03-03T03:00:00Z"), at("2025-03-03T03:00:00Z") + thirty_minutes));
+    // Must end by closing time
+    assert!(!hours.covers(at("2025-03-03T17:45:00Z"), at("2025-03-03T17:45:00Z") + thirty_minutes));
+    // Monday in summer: 07:30 UTC is 08:30 BST, 17:30 UTC is 18:30 BST
+    assert!(hours.covers(at("2025-06-02T07:30:00Z"), at("2025-06-02T07:30:00Z") + thirty_minutes));
+    assert!(!hours.covers(at("2025-06-02T17:30:00Z"), at("2025-06-02T17:30:00Z") + thirty_minutes));
+    // Saturday morning only, closed on Sunday
+    assert!(hours.covers(at("2025-03-08T10:00:00Z"), at("2025-03-08T10:00:00Z") + thirty_minutes));
+    assert!(!hours.covers(at("2025-03-09T10:00:00Z"), at("2025-03-09T10:00:00Z") + thirty_minutes));
+
+    assert!(BusinessHours::parse("mon-fri 18:00-08:00", "UTC").is_err());
+    assert!(BusinessHours::parse("mon-fri 08:00-18:00, fri 09:00-12:00", "UTC").is_err());
+    assert!(BusinessHours::parse("weekdays 08:00-18:00", "UTC").is_err());
+    assert!(BusinessHours::parse("mon-fri 08:00-18:00", "Mars/Olympus").is_err());
+
+    let mut config = TestConfig::default().to_app_config();
+    config.appointment_rules.business_hours = Some("sat-sun 10:00-14:00".to_string());
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    assert_eq!(rules.business_hours.unwrap().to_string(), "sat 10:00-14:00, sun 10:00-14:00 (UTC)");
+}
+
+#[tokio::test]
+async fn test_out_of_hours_booking_allowed_only_for_urgent_care() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    config.appointment_rules.business_hours = Some("mon-sun 08:00-18:00".to_string());
+
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+    let doctor_id = Uuid::new_v4();
+    setup_appointment_mocks(&mock_server, &user.id, &doctor_id.to_string()).await;
+
+    let three_am = (Utc::now() + Duration::days(2)).date_naive()
+        .and_time(NaiveTime::from_hms_opt(3, 0, 0).unwrap())
+        .and_utc();
+    let book = |appointment_type: AppointmentType| {
+        let request_body = BookAppointmentRequest {
+            patient_id: Uuid::parse_str(&user.id).unwrap(),
+            doctor_id: Some(doctor_id),
+            appointment_date: three_am,
+            appointment_type,
+            duration_minutes: Some(30),
+            timezone: "UTC".to_string(),
+            patient_notes: None,
+            preferred_language: None,
+            specialty_required: None,
+        };
+        Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_string(&request_body).unwrap()))
+            .unwrap()
+    };
+
+    let response = create_test_app(config.clone()).await
+        .oneshot(book(AppointmentType::GeneralConsultation)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["detail"].as_str().unwrap().contains("clinic hours"));
+
+    let response = create_test_app(config).await
+        .oneshot(book(AppointmentType::Urgent)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_doctor_timing_only_settable_by_that_doctor_or_admin() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let other_doctor = TestUser::doctor("other@example.com");
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/appointment_timing_overrides"))
+        .and(query_param("on_conflict", "doctor_id,appointment_type"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "doctor_id": doctor.id,
+            "specialty": null,
+            "appointment_type": "general_consultation",
+            "duration_minutes": 45,
+            "buffer_minutes": null,
+            "updated_by": doctor.id,
+            "updated_at": "2024-01-01T00:00:00Z"
+        }])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let body = json!({ "appointment_type": "general_consultation", "duration_minutes": 45 }).to_string();
+    let put_timing = |user: &TestUser| {
+        let token = JwtTestUtils::create_test_token(user, &config.supabase_jwt_secret, Some(24));
+        Request::builder()
+            .method("PUT")
+            .uri(format!("/timing/doctors/{}", doctor.id))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(body.clone()))
+            .unwrap()
+    };
+
+    let app = create_test_app(config.clone()).await;
+    let response = app.oneshot(put_timing(&other_doctor)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let app = create_test_app(config.clone()).await;
+    let response = app.oneshot(put_timing(&doctor)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["override"]["duration_minutes"], 45);
+}
+
+/// Appointments table that POSTs and PATCHes actually change, so concurrent
+/// requests see each other's writes. Reads are slowed down to widen any race
+/// window.
+#[derive(Clone)]
+struct AppointmentTable {
+    rows: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+}
+
+impl wiremock::Respond for AppointmentTable {
+    fn respond(&self, request: &wiremock::Request) -> ResponseTemplate {
+        let query: std::collections::HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+        let mut rows = self.rows.lock().unwrap();
+        let matches = |row: &serde_json::Value| {
+            query.get("id").is_none_or(|id| match id.strip_prefix("neq.") {
+                Some(excluded) => row["id"] != excluded,
+                None => id.strip_prefix("eq.").is_some_and(|id| row["id"] == id),
+            })
+        };
+
+        if request.method.as_str() == "POST" {
+            let mut row: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            row["id"] = json!(Uuid::new_v4());
+            rows.push(row.clone());
+            return ResponseTemplate::new(201).set_body_json(json!([row]));
+        }
+        if request.method.as_str() != "PATCH" {
+            let found: Vec<_> = rows.iter().filter(|row| matches(row)).cloned().collect();
+            return ResponseTemplate::new(200)
+                .set_body_json(found)
+                .set_delay(std::time::Duration::from_millis(30));
+        }
+
+        let changes: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let expected_version = query.get("version").map(|value| value.trim_start_matches("eq.").parse::<i64>().unwrap());
+        let mut updated = Vec::new();
+        for row in rows.iter_mut().filter(|row| matches(row)) {
+            if expected_version.is_some_and(|expected| row["version"].as_i64().unwrap_or(0) != expected) {
+                continue;
+            }
+            for (key, value) in changes.as_object().unwrap() {
+                row[key] = value.clone();
+            }
+            updated.push(row.clone());
+        }
+        ResponseTemplate::new(200).set_body_json(updated)
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_reschedules_into_same_slot_only_one_wins() {
+    use appointment_cell::services::booking::AppointmentBookingService;
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let admin = TestUser::admin("admin@example.com");
+    let token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let actor = admin.to_user();
+
+    let doctor_id = Uuid::new_v4();
+    let appointment = |start: DateTime<Utc>| {
+        json!({
+            "id": Uuid::new_v4().to_string(),
+            "patient_id": Uuid::new_v4().to_string(),
+            "doctor_id": doctor_id.to_string(),
+            "appointment_date": start.to_rfc3339(),
+            "status": "confirmed",
+            "appointment_type": "general_consultation",
+            "duration_minutes": 30,
+            "timezone": "UTC",
+            "scheduled_start_time": start.to_rfc3339(),
+            "scheduled_end_time": (start + Duration::minutes(30)).to_rfc3339(),
+            "actual_start_time": null,
+            "actual_end_time": null,
+            "notes": null,
+            "patient_notes": null,
+            "doctor_notes": null,
+            "prescription_issued": false,
+            "medical_certificate_issued": false,
+            "report_generated": false,
+            "video_conference_link": null,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z"
+        })
+    };
+    let day = (Utc::now() + Duration::days(5)).date_naive();
+    let at = |hour: u32| day.and_time(NaiveTime::from_hms_opt(hour, 0, 0).unwrap()).and_utc();
+    let first = appointment(at(9));
+    let second = appointment(at(11));
+    let first_id: Uuid = first["id"].as_str().unwrap().parse().unwrap();
+    let second_id: Uuid = second["id"].as_str().unwrap().parse().unwrap();
+
+    let table = AppointmentTable { rows: Arc::new(std::sync::Mutex::new(vec![first, second])) };
+    Mock::given(path("/rest/v1/appointments"))
+        .respond_with(table)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    let service = AppointmentBookingService::new(&config, rules);
+    let move_to_ten = |appointment_id| service.reschedule_appointment(
+        appointment_id,
+        RescheduleAppointmentRequest { new_start_time: at(10), new_duration_minutes: None, reason: None, override_notice: false },
+        &actor,
+        &token,
+    );
+
+    let (first_result, second_result) = tokio::join!(move_to_ten(first_id), move_to_ten(second_id));
+    assert_eq!(
+        [first_result.is_ok(), second_result.is_ok()].iter().filter(|ok| **ok).count(), 1,
+        "exactly one reschedule should take the slot: {:?} / {:?}", first_result, second_result
+    );
+
+    // Retrying the successful reschedule is a no-op rather than an error
+    let (winner, winner_id) = match first_result {
+        Ok(appointment) => (appointment, first_id),
+        Err(_) => (second_result.unwrap(), second_id),
+    };
+    let patches_before = mock_server.received_requests().await.unwrap().iter()
+        .filter(|request| request.method.as_str() == "PATCH").count();
+    let retried = move_to_ten(winner_id).await.expect("retry should succeed");
+    assert_eq!(retried.scheduled_start_time, winner.scheduled_start_time);
+    let patches_after = mock_server.received_requests().await.unwrap().iter()
+        .filter(|request| request.method.as_str() == "PATCH").count();
+    assert_eq!(patches_before, patches_after);
+}
+
+/// The schedule lock only covers one process; bookings made through
+/// different API instances are not guarded against each other
+#[tokio::test]
+async fn test_concurrent_bookings_in_one_process_create_one_appointment() {
+    use appointment_cell::services::booking::AppointmentBookingService;
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let token = JwtTestUtils::create_test_token(
+        &TestUser::admin("admin@example.com"), &config.supabase_jwt_secret, Some(24),
+    );
+    let doctor_id = Uuid::new_v4();
+
+    let table = AppointmentTable { rows: Arc::new(std::sync::Mutex::new(Vec::new())) };
+    Mock::given(path("/rest/v1/appointments"))
+        .respond_with(table.clone())
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patients"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": Uuid::new_v4() }])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctors"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            MockSupabaseResponses::doctor_response(&doctor_id.to_string(), "doctor@example.com", "Dr. Test", "General Practice")
+        ])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    let service = AppointmentBookingService::new(&config, rules);
+    let slot = (Utc::now() + Duration::days(3)).date_naive()
+        .and_time(NaiveTime::from_hms_opt(10, 0, 0).unwrap())
+        .and_utc();
+    let book = || service.book_appointment(
+        BookAppointmentRequest {
+            patient_id: Uuid::new_v4(),
+            doctor_id: Some(doctor_id),
+            appointment_date: slot,
+            appointment_type: AppointmentType::GeneralConsultation,
+            duration_minutes: Some(30),
+            timezone: "UTC".to_string(),
+            patient_notes: None,
+            preferred_language: None,
+            specialty_required: None,
+        },
+        &token,
+    );
+
+    let (first, second) = tokio::join!(book(), book());
+    assert_eq!(
+        [first.is_ok(), second.is_ok()].iter().filter(|ok| **ok).count(), 1,
+        "exactly one booking should take the slot: {:?} / {:?}", first, second
+    );
+    assert_eq!(table.rows.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_booking_window_follows_injected_clock() {
+    use appointment_cell::models::AppointmentError;
+    use appointment_cell::services::booking::AppointmentBookingService;
+    use appointment_cell::services::clock::FixedClock;
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let token = JwtTestUtils::create_test_token(
+        &TestUser::admin("admin@example.com"), &config.supabase_jwt_secret, Some(24),
+    );
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    // Monday morning, long ago as far as the system clock is concerned
+    let now: DateTime<Utc> = "2020-01-06T08:00:00Z".parse().unwrap();
+    let clock = Arc::new(FixedClock::new(now));
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    let service = AppointmentBookingService::new(&config, rules).with_clock(clock.clone());
+    let book_at = |appointment_date: DateTime<Utc>| service.book_appointment(
+        BookAppointmentRequest {
+            patient_id: Uuid::new_v4(),
+            doctor_id: Some(Uuid::new_v4()),
+            appointment_date,
+            appointment_type: AppointmentType::GeneralConsultation,
+            duration_minutes: Some(30),
+            timezone: "UTC".to_string(),
+            patient_notes: None,
+            preferred_language: None,
+            specialty_required: None,
+        },
+        &token,
+    );
+
+    let too_soon = book_at(now + Duration::hours(1)).await.unwrap_err();
+    assert!(matches!(too_soon, AppointmentError::InvalidTime(ref msg) if msg.contains("at least 2 hours")), "{:?}", too_soon);
+
+    let too_far = book_at(now + Duration::days(91)).await.unwrap_err();
+    assert!(matches!(too_far, AppointmentError::InvalidTime(ref msg) if msg.contains("90 days")), "{:?}", too_far);
+
+    // In the window, so validation passes and the unknown patient is what fails
+    let wednesday = "2020-01-08T10:00:00Z".parse().unwrap();
+    let in_window = book_at(wednesday).await.unwrap_err();
+    assert!(matches!(in_window, AppointmentError::PatientNotFound), "{:?}", in_window);
+
+    // Moving the clock past it puts the same slot in the past
+    clock.advance(Duration::days(3));
+    let passed = book_at(wednesday).await.unwrap_err();
+    assert!(matches!(passed, AppointmentError::InvalidTime(_)), "{:?}", passed);
+}
+
+#[tokio::test]
+async fn test_clinic_scope_applies_to_queries_and_audit() {
+    use appointment_cell::services::booking::AppointmentBookingService;
+    use shared_database::audit::{AuditEntry, AuditLog};
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    config.clinic_id = Some("north".to_string());
+
+    let user = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+    let appointment = MockSupabaseResponses::appointment_response(&user.id, &Uuid::new_v4().to_string());
+    let appointment_id: Uuid = appointment["id"].as_str().unwrap().parse().unwrap();
+
+    // Rows from other clinics never match
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("id", format!("eq.{}", appointment_id)))
+        .and(query_param("clinic_id", "eq.north"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([appointment])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(wiremock::matchers::body_partial_json(json!({ "clinic_id": "north" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let rules = AppointmentValidationRules::from_config(&config).unwrap();
+    let service = AppointmentBookingService::new(&config, rules);
+    let found = service.get_appointment(appointment_id, &token).await.expect("scoped lookup should match");
+    assert_eq!(found.id, appointment_id);
+
+    AuditLog::new(&config)
+        .record(AuditEntry::new(&user.id, "appointment.viewed", "appointment", appointment_id.to_string()), &token)
+        .await
+        .expect("audit entry should be written");
+}
+
+#[tokio::test]
+async fn test_adding_a_note_moves_the_appointment_version() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+
+    let mut appointment = MockSupabaseResponses::appointment_response(&Uuid::new_v4().to_string(), &doctor.id);
+    appointment["status"] = json!("in_progress");
+    appointment["version"] = json!(0);
+    let appointment_id = appointment["id"].as_str().unwrap().to_string();
+
+    let table = AppointmentTable { rows: Arc::new(std::sync::Mutex::new(vec![appointment])) };
+    Mock::given(path("/rest/v1/appointments"))
+        .respond_with(table.clone())
+        .mount(&mock_server)
+        .await;
+    let note = json!({
+        "id": Uuid::new_v4(),
+        "appointment_id": appointment_id,
+        "subjective": "Headache for three days",
+        "objective": null,
+        "assessment": null,
+        "plan": null,
+        "author": doctor.id,
+        "created_at": "2024-01-01T10:00:00Z"
+    });
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/consultation_notes"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([note.clone()])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/consultation_notes"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([note])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&mock_server)
+        .await;
+
+    let app = create_test_app(config.clone()).await;
+    let add_note = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/notes", appointment_id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "subjective": "Headache for three days" }).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(add_note).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(table.rows.lock().unwrap()[0]["version"], 1);
+
+    // A client still holding the version from before the note is turned away
+    let update_body = UpdateAppointmentRequest {
+        status: None,
+        doctor_notes: Some("Written before the note".to_string()),
+        patient_notes: None,
+        reschedule_to: None,
+        reschedule_duration: None,
+        cancellation_reason: None,
+        expected_version: Some(0),
+    };
+    let stale_update = Request::builder()
+        .method("PUT")
+        .uri(format!("/{}", appointment_id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_string(&update_body).unwrap()))
+        .unwrap();
+    let response = app.oneshot(stale_update).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "appointment.version_conflict");
+    assert!(table.rows.lock().unwrap()[0]["doctor_notes"].as_str().unwrap().contains("Headache"));
+}
+
+#[tokio::test]
+async fn test_live_feeds_end_when_the_server_shuts_down() {
+    use futures::StreamExt;
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_user = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, Some(24));
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/doctors/{}/live", doctor_user.id))
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app(config).await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut body = response.into_body().into_data_stream();
+    let snapshot = body.next().await.unwrap().unwrap();
+    assert!(String::from_utf8(snapshot.to_vec()).unwrap().starts_with("event: snapshot"));
+
+    // The token is good for a day, so only the shutdown can end the feed
+    appointment_cell::services::live::close_live_feeds();
+    let rest: Vec<_> = tokio::time::timeout(std::time::Duration::from_secs(5), body.collect::<Vec<_>>())
+        .await
+        .expect("the feed should close once the server shuts down");
+    assert!(rest.is_empty());
+}
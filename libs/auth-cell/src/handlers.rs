@@ -15,7 +15,7 @@ use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 use shared_models::auth::TokenResponse;
 use shared_models::error::AppError;
-use shared_utils::jwt::validate_token as jwt_validate_token;
+use shared_utils::jwks::authenticate_token;
 
 // Helper function to extract token
 fn extract_bearer_token(headers: &HeaderMap) -> Result<String, AppError> {
@@ -42,7 +42,7 @@ pub async fn validate_token(
 
     let token = extract_bearer_token(&headers)?;
 
-    match jwt_validate_token(&token, &config.supabase_jwt_secret) {
+    match authenticate_token(&token, &config).await {
         Ok(user) => {
             let response = TokenResponse {
                 valid: true,
@@ -67,7 +67,7 @@ pub async fn verify_token(
 
     let token = extract_bearer_token(&headers)?;
 
-    match jwt_validate_token(&token, &config.supabase_jwt_secret) {
+    match authenticate_token(&token, &config).await {
         Ok(_) => {
             Ok(Json(json!({ "valid": true })))
         },
@@ -86,8 +86,8 @@ pub async fn get_profile(
     let token = auth_header.token();
 
     // Get the user ID from the token
-    let user = jwt_validate_token(token, &config.supabase_jwt_secret)
-        .map_err(|e| AppError::Auth(e))?;
+    let user = authenticate_token(token, &config).await
+        .map_err(AppError::Auth)?;
 
     debug!("Getting profile for user: {}", user.id);
 
@@ -2,6 +2,8 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Path, Query, State, Extension},
+    http::header,
+    response::{IntoResponse, Response},
     Json,
 };
 use axum_extra::TypedHeader;
@@ -13,17 +15,22 @@ use chrono::{NaiveDate, NaiveTime};
 use shared_config::AppConfig;
 use shared_models::auth::User;
 use shared_models::error::AppError;
+use shared_utils::extractor::{roles::Admin, RequireRole};
 
 use crate::services::{
-    doctor::DoctorService,
-    availability::AvailabilityService,
+    doctor::{DoctorService, MAX_UTILIZATION_RANGE_DAYS},
+    availability::{AvailabilityService, MAX_AVAILABILITY_RANGE_DAYS},
     matching::DoctorMatchingService,
+    review::ReviewService,
+    calendar::CalendarFeedService,
+    verification::VerificationService,
 };
 use crate::models::{
     CreateDoctorRequest, UpdateDoctorRequest, DoctorSearchFilters,
     CreateAvailabilityRequest, UpdateAvailabilityRequest, AvailabilityQueryRequest,
     DoctorImageUpload, DoctorMatchingRequest, CreateSpecialtyRequest,
-    CreateAvailabilityOverrideRequest,
+    CreateAvailabilityOverrideRequest, CreateReviewRequest,
+    SubmitVerificationRequest, ReviewVerificationRequest, AvailabilityRangeRequest,
 };
 
 use crate::models::DoctorError;
@@ -59,6 +66,23 @@ pub struct MatchingQuery {
     pub max_results: Option<usize>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UtilizationQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarFeedQuery {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewListQuery {
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
 // ==============================================================================
 // PUBLIC HANDLERS (NO AUTHENTICATION REQUIRED)
 // ==============================================================================
@@ -106,17 +130,72 @@ pub async fn get_doctor_public(
     Ok(Json(json!(doctor)))
 }
 
+/// Either a single `date`, or a `from`/`to` range for calendar views
+#[derive(Debug, Deserialize)]
+pub struct DoctorAvailabilityQuery {
+    pub date: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub timezone: Option<String>,
+    pub appointment_type: Option<String>,
+    pub duration_minutes: Option<i32>,
+}
+
 #[axum::debug_handler]
 pub async fn get_doctor_availability_public(
     State(state): State<Arc<AppConfig>>,
     Path(doctor_id): Path<String>,
-    Query(query): Query<AvailabilityQuery>,
+    Query(query): Query<DoctorAvailabilityQuery>,
 ) -> Result<Json<Value>, AppError> {
     let availability_service = AvailabilityService::new(&state);
-    
+    validate_timezone(query.timezone.as_deref())?;
+
+    match (query.from, query.to) {
+        (Some(from), Some(to)) => {
+            if to < from {
+                return Err(AppError::BadRequest("'to' must not be before 'from'".to_string()));
+            }
+            if (to - from).num_days() >= MAX_AVAILABILITY_RANGE_DAYS {
+                return Err(AppError::BadRequest(format!(
+                    "Availability range cannot exceed {} days", MAX_AVAILABILITY_RANGE_DAYS
+                )));
+            }
+
+            let doctor_service = DoctorService::new(&state);
+            doctor_service.get_doctor_public(&doctor_id).await
+                .map_err(|e| match e {
+                    DoctorError::NotFound => AppError::NotFound("Doctor not found".to_string()),
+                    _ => AppError::Internal(e.to_string()),
+                })?;
+
+            let range_request = AvailabilityRangeRequest {
+                from,
+                to,
+                timezone: query.timezone,
+                appointment_type: query.appointment_type,
+                duration_minutes: query.duration_minutes,
+            };
+            let days = availability_service.get_available_slots_range(&doctor_id, range_request, None).await
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+
+            return Ok(Json(json!({
+                "doctor_id": doctor_id,
+                "from": from,
+                "to": to,
+                "days": days,
+                "note": "These are theoretical availability slots. Verify actual availability with appointment-cell."
+            })));
+        }
+        (None, None) => {}
+        _ => return Err(AppError::BadRequest("'from' and 'to' must be given together".to_string())),
+    }
+
     // Parse the date string
-    let date = NaiveDate::parse_from_str(&query.date, "%Y-%m-%d")
-        .map_err(|_| AppError::BadRequest(format!("Invalid date format: {}. Expected YYYY-MM-DD", query.date)))?;
+    let Some(date) = query.date else {
+        return Err(AppError::BadRequest("Either 'date' or 'from' and 'to' is required".to_string()));
+    };
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest(format!("Invalid date format: {}. Expected YYYY-MM-DD", date)))?;
     
     let availability_request = AvailabilityQueryRequest {
         date,
@@ -142,6 +221,7 @@ pub async fn get_available_slots_public(
     // Parse the date string
     let date = NaiveDate::parse_from_str(&query.date, "%Y-%m-%d")
         .map_err(|_| AppError::BadRequest(format!("Invalid date format: {}. Expected YYYY-MM-DD", query.date)))?;
+    validate_timezone(query.timezone.as_deref())?;
     
     let availability_request = AvailabilityQueryRequest {
         date,
@@ -161,6 +241,16 @@ pub async fn get_available_slots_public(
     })))
 }
 
+/// Slots carry local times in the requested zone, so it has to be a real IANA name
+fn validate_timezone(timezone: Option<&str>) -> Result<(), AppError> {
+    match timezone {
+        Some(timezone) if timezone.parse::<chrono_tz::Tz>().is_err() => {
+            Err(AppError::BadRequest(format!("Invalid timezone: {}", timezone)))
+        }
+        _ => Ok(()),
+    }
+}
+
 #[axum::debug_handler]
 pub async fn get_doctor_specialties_public(
     State(state): State<Arc<AppConfig>>,
@@ -390,6 +480,34 @@ pub async fn verify_doctor(
     Ok(Json(json!(doctor)))
 }
 
+#[axum::debug_handler]
+pub async fn get_doctor_utilization(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    Query(query): Query<UtilizationQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    // Capacity planning data is admin only
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    if query.to < query.from {
+        return Err(AppError::BadRequest("'to' must not be before 'from'".to_string()));
+    }
+    if (query.to - query.from).num_days() >= MAX_UTILIZATION_RANGE_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "Utilization range cannot exceed {} days", MAX_UTILIZATION_RANGE_DAYS
+        )));
+    }
+
+    let doctor_service = DoctorService::new(&state);
+
+    let utilization = doctor_service.utilization(&doctor_id, query.from, query.to, token).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(json!(utilization)))
+}
+
 // ==============================================================================
 // AVAILABILITY HANDLERS (Doctor Configuration)
 // ==============================================================================
@@ -445,6 +563,7 @@ pub async fn get_available_slots(
     // Parse the date string
     let date = NaiveDate::parse_from_str(&query.date, "%Y-%m-%d")
         .map_err(|_| AppError::BadRequest(format!("Invalid date format: {}. Expected YYYY-MM-DD", query.date)))?;
+    validate_timezone(query.timezone.as_deref())?;
     
     let availability_query = AvailabilityQueryRequest {
         date,
@@ -503,11 +622,22 @@ pub async fn create_availability_override(
     }
     
     let availability_service = AvailabilityService::new(&state);
+
+    // Time off doesn't move what's already booked; staff get the list to
+    // reschedule. Looked up first so a failed lookup leaves nothing created.
+    let affected_appointments = if request.is_available {
+        Vec::new()
+    } else {
+        availability_service.appointments_on_date(&doctor_id, request.override_date, token).await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+    };
     
     let override_entry = availability_service.create_availability_override(&doctor_id, request, token).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
-    
-    Ok(Json(json!(override_entry)))
+
+    let mut response = json!(override_entry);
+    response["affected_appointments"] = json!(affected_appointments);
+    Ok(Json(response))
 }
 
 #[axum::debug_handler]
@@ -629,4 +759,267 @@ pub async fn get_recommended_doctors(
         "total": recommendations.len(),
         "note": "Doctor recommendations based on professional credentials and ratings."
     })))
-}
\ No newline at end of file
+}
+// ==============================================================================
+// REVIEW HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+pub async fn get_doctor_reviews_public(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    Query(query): Query<ReviewListQuery>,
+) -> Result<Json<Value>, AppError> {
+    let doctor_id = uuid::Uuid::parse_str(&doctor_id)
+        .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?;
+
+    let review_service = ReviewService::new(&state);
+
+    let reviews = review_service.get_doctor_reviews(doctor_id, None, query.limit, query.offset).await
+        .map_err(|e| match e {
+            DoctorError::DatabaseError(msg) => AppError::Database(msg),
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "reviews": reviews,
+        "total": reviews.len(),
+        "doctor_id": doctor_id
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn submit_doctor_review(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateReviewRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    // Only patients review their own appointments
+    if user.role.as_deref() != Some("patient") {
+        return Err(AppError::Auth("Only patients can review doctors".to_string()));
+    }
+
+    let doctor_id = uuid::Uuid::parse_str(&doctor_id)
+        .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?;
+    let patient_id = uuid::Uuid::parse_str(&user.id)
+        .map_err(|_| AppError::BadRequest("Invalid patient ID".to_string()))?;
+
+    let review_service = ReviewService::new(&state);
+
+    let review = review_service.submit_review(
+        doctor_id,
+        request.appointment_id,
+        patient_id,
+        request.rating,
+        request.comment,
+        token,
+    ).await.map_err(|e| match e {
+        DoctorError::NotFound => AppError::NotFound("Appointment not found".to_string()),
+        DoctorError::UnauthorizedAccess => AppError::Auth("Not authorized to review this appointment".to_string()),
+        DoctorError::ValidationError(msg) => AppError::BadRequest(msg),
+        DoctorError::Conflict(msg) => AppError::Conflict(msg),
+        DoctorError::DatabaseError(msg) => AppError::Database(msg),
+        _ => AppError::Internal(e.to_string()),
+    })?;
+
+    Ok(Json(json!(review)))
+}
+
+// ==============================================================================
+// VERIFICATION HANDLERS
+// ==============================================================================
+
+/// Doctor submits their license and credential documents for review
+#[axum::debug_handler]
+pub async fn submit_doctor_verification(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<SubmitVerificationRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    if user.id != doctor_id {
+        return Err(AppError::Forbidden("Doctors can only submit their own credentials".to_string()));
+    }
+
+    let doctor_id = uuid::Uuid::parse_str(&doctor_id)
+        .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?;
+
+    let verification_service = VerificationService::new(&state);
+
+    let verification = verification_service.submit_verification(doctor_id, request, &user, token).await
+        .map_err(verification_error)?;
+
+    Ok(Json(json!(verification)))
+}
+
+/// Latest verification submission for a doctor (doctor or admin)
+#[axum::debug_handler]
+pub async fn get_doctor_verification(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    if !user.is_admin() && user.id != doctor_id {
+        return Err(AppError::Forbidden("Not authorized to view this verification".to_string()));
+    }
+
+    let doctor_id = uuid::Uuid::parse_str(&doctor_id)
+        .map_err(|_| AppError::BadRequest("Invalid doctor ID".to_string()))?;
+
+    let verification_service = VerificationService::new(&state);
+
+    let verification = verification_service.latest_verification(doctor_id, token).await
+        .map_err(verification_error)?
+        .ok_or_else(|| AppError::NotFound("No verification request submitted".to_string()))?;
+
+    Ok(Json(json!(verification)))
+}
+
+/// Submissions awaiting an admin decision
+#[axum::debug_handler]
+pub async fn get_pending_verifications(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let verification_service = VerificationService::new(&state);
+
+    let verifications = verification_service.pending_verifications(token).await
+        .map_err(verification_error)?;
+
+    Ok(Json(json!({
+        "verifications": verifications,
+        "total": verifications.len()
+    })))
+}
+
+/// Admin approves or rejects a submission
+#[axum::debug_handler]
+pub async fn review_doctor_verification(
+    State(state): State<Arc<AppConfig>>,
+    Path(verification_id): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    admin: RequireRole<Admin>,
+    Json(request): Json<ReviewVerificationRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let verification_id = uuid::Uuid::parse_str(&verification_id)
+        .map_err(|_| AppError::BadRequest("Invalid verification ID".to_string()))?;
+
+    let verification_service = VerificationService::new(&state);
+
+    let (verification, doctor) = verification_service.review_verification(verification_id, request, &admin, token).await
+        .map_err(verification_error)?;
+
+    Ok(Json(json!({
+        "verification": verification,
+        "doctor": doctor
+    })))
+}
+
+fn verification_error(e: DoctorError) -> AppError {
+    match e {
+        DoctorError::NotFound => AppError::NotFound("Verification request or doctor not found".to_string()),
+        DoctorError::UnauthorizedAccess => AppError::Forbidden(e.to_string()),
+        DoctorError::ValidationError(msg) => AppError::BadRequest(msg),
+        _ => AppError::Internal(e.to_string()),
+    }
+}
+
+// ==============================================================================
+// CALENDAR FEED HANDLERS
+// ==============================================================================
+
+/// iCalendar feed for calendar apps; authenticated by the feed `token`
+/// query parameter instead of a bearer header
+#[axum::debug_handler]
+pub async fn get_doctor_calendar_feed(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    Query(query): Query<CalendarFeedQuery>,
+) -> Result<Response, AppError> {
+    let calendar_service = CalendarFeedService::new(&state);
+
+    let token_valid = calendar_service.verify_feed_token(&doctor_id, &query.token).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !token_valid {
+        return Err(AppError::Auth("Invalid calendar feed token".to_string()));
+    }
+
+    let calendar = calendar_service.doctor_calendar(&doctor_id).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, max-age=300"),
+        ],
+        calendar,
+    ).into_response())
+}
+
+/// Issue a new subscription URL for the doctor's calendar feed. Any
+/// previous URL stops working.
+#[axum::debug_handler]
+pub async fn rotate_doctor_calendar_feed(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    ensure_calendar_feed_owner(&user, &doctor_id)?;
+
+    let calendar_service = CalendarFeedService::new(&state);
+    let token = calendar_service.rotate_feed_token(&doctor_id, auth.token()).await
+        .map_err(calendar_feed_error)?;
+
+    Ok(Json(json!({
+        "doctor_id": doctor_id,
+        "feed_path": format!("/doctors/{}/calendar.ics?token={}", doctor_id, token)
+    })))
+}
+
+/// Turn off the doctor's calendar feed
+#[axum::debug_handler]
+pub async fn revoke_doctor_calendar_feed(
+    State(state): State<Arc<AppConfig>>,
+    Path(doctor_id): Path<String>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    ensure_calendar_feed_owner(&user, &doctor_id)?;
+
+    let calendar_service = CalendarFeedService::new(&state);
+    calendar_service.revoke_feed_token(&doctor_id, auth.token()).await
+        .map_err(calendar_feed_error)?;
+
+    Ok(Json(json!({ "success": true })))
+}
+
+fn calendar_feed_error(e: DoctorError) -> AppError {
+    match e {
+        DoctorError::NotFound => AppError::NotFound("No calendar feed to revoke".to_string()),
+        DoctorError::DatabaseError(msg) => AppError::Database(msg),
+        _ => AppError::Internal(e.to_string()),
+    }
+}
+
+fn ensure_calendar_feed_owner(user: &User, doctor_id: &str) -> Result<(), AppError> {
+    if !user.is_admin() && user.id != doctor_id {
+        return Err(AppError::Forbidden("Not authorized to manage this doctor's calendar feed".to_string()));
+    }
+    Ok(())
+}
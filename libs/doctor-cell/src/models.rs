@@ -1,7 +1,11 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc, NaiveTime, NaiveDate};
 
+use health_profile_cell::models::DocumentUpload;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Doctor {
     pub id: Uuid,
@@ -62,6 +66,19 @@ pub struct DoctorAvailabilityOverride {
     pub created_at: DateTime<Utc>,
 }
 
+/// A booked appointment that falls inside time the doctor has taken off,
+/// and so needs rescheduling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffectedAppointment {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub appointment_type: String,
+    pub status: String,
+    pub timezone: String,
+    pub scheduled_start_time: DateTime<Utc>,
+    pub scheduled_end_time: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AvailableSlot {
     pub start_time: DateTime<Utc>,
@@ -69,9 +86,14 @@ pub struct AvailableSlot {
     pub duration_minutes: i32,
     pub appointment_type: String,
     pub timezone: String,
+    /// `start_time` in `timezone`, RFC 3339 with that zone's offset on the day
+    #[serde(default)]
+    pub start_time_local: String,
+    #[serde(default)]
+    pub end_time_local: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DoctorSearchFilters {
     pub specialty: Option<String>,
     pub sub_specialty: Option<String>,
@@ -155,6 +177,22 @@ pub struct AvailabilityQueryRequest {
     pub duration_minutes: Option<i32>,
 }
 
+/// Slots for every date in `from..=to`, computed in one pass
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailabilityRangeRequest {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub timezone: Option<String>,
+    pub appointment_type: Option<String>,
+    pub duration_minutes: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyAvailableSlots {
+    pub date: NaiveDate,
+    pub available_slots: Vec<AvailableSlot>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DoctorMatchingRequest {
     pub patient_id: Uuid,
@@ -175,6 +213,64 @@ pub struct DoctorMatch {
     pub match_reasons: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorReview {
+    pub id: Uuid,
+    pub doctor_id: Uuid,
+    pub appointment_id: Uuid,
+    pub patient_id: Uuid,
+    pub rating: i32, // 1-5 stars
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateReviewRequest {
+    pub appointment_id: Uuid,
+    pub rating: i32,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// One credential submission and the admin decision on it. A doctor's
+/// `is_verified` flag follows their latest decided submission.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorVerification {
+    pub id: Uuid,
+    pub doctor_id: Uuid,
+    pub license_number: String,
+    pub issuing_authority: Option<String>,
+    #[serde(default)]
+    pub document_ids: Vec<Uuid>,
+    pub status: VerificationStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub rejection_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitVerificationRequest {
+    pub license_number: String,
+    pub issuing_authority: Option<String>,
+    /// Scans of the license and supporting certificates
+    pub documents: Vec<DocumentUpload>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewVerificationRequest {
+    pub approve: bool,
+    /// Required when rejecting; shown to the doctor
+    pub reason: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DoctorStats {
     pub total_appointments: i32,
@@ -186,6 +282,25 @@ pub struct DoctorStats {
     pub next_available_slot: Option<AvailableSlot>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppointmentTypeUtilization {
+    pub appointment_count: i32,
+    pub booked_minutes: i64,
+}
+
+/// Booked vs. scheduled minutes for a doctor over an inclusive date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorUtilization {
+    pub doctor_id: Uuid,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub available_minutes: i64,
+    pub booked_minutes: i64,
+    pub utilization_rate: f64, // booked / available, 0.0 when nothing is scheduled
+    pub appointment_count: i32,
+    pub by_appointment_type: HashMap<String, AppointmentTypeUtilization>,
+}
+
 // DTO for available time slots response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DoctorAvailabilityResponse {
@@ -202,6 +317,132 @@ pub struct DoctorImageUpload {
     pub file_data: String, // Base64 encoded image
 }
 
+// ==============================================================================
+// SPECIALTY TAXONOMY
+// ==============================================================================
+
+/// Canonical specialty registry. Free-text input ("cardiac", "Cardiologist")
+/// is normalized onto one of these before it is stored or matched on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Specialty {
+    GeneralPractice,
+    InternalMedicine,
+    Cardiology,
+    Dermatology,
+    Pediatrics,
+    Psychiatry,
+    Psychology,
+    Gynecology,
+    Neurology,
+    Orthopedics,
+    Endocrinology,
+    Gastroenterology,
+    Ophthalmology,
+    Otolaryngology,
+    Urology,
+    Oncology,
+    Pulmonology,
+    Nutrition,
+}
+
+impl Specialty {
+    pub const ALL: [Specialty; 18] = [
+        Specialty::GeneralPractice,
+        Specialty::InternalMedicine,
+        Specialty::Cardiology,
+        Specialty::Dermatology,
+        Specialty::Pediatrics,
+        Specialty::Psychiatry,
+        Specialty::Psychology,
+        Specialty::Gynecology,
+        Specialty::Neurology,
+        Specialty::Orthopedics,
+        Specialty::Endocrinology,
+        Specialty::Gastroenterology,
+        Specialty::Ophthalmology,
+        Specialty::Otolaryngology,
+        Specialty::Urology,
+        Specialty::Oncology,
+        Specialty::Pulmonology,
+        Specialty::Nutrition,
+    ];
+
+    /// Display name stored in `doctors.specialty`
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Specialty::GeneralPractice => "General Practice",
+            Specialty::InternalMedicine => "Internal Medicine",
+            Specialty::Cardiology => "Cardiology",
+            Specialty::Dermatology => "Dermatology",
+            Specialty::Pediatrics => "Pediatrics",
+            Specialty::Psychiatry => "Psychiatry",
+            Specialty::Psychology => "Psychology",
+            Specialty::Gynecology => "Gynecology",
+            Specialty::Neurology => "Neurology",
+            Specialty::Orthopedics => "Orthopedics",
+            Specialty::Endocrinology => "Endocrinology",
+            Specialty::Gastroenterology => "Gastroenterology",
+            Specialty::Ophthalmology => "Ophthalmology",
+            Specialty::Otolaryngology => "Otolaryngology",
+            Specialty::Urology => "Urology",
+            Specialty::Oncology => "Oncology",
+            Specialty::Pulmonology => "Pulmonology",
+            Specialty::Nutrition => "Nutrition",
+        }
+    }
+
+    /// Lower-case spellings accepted in addition to the display name
+    pub const fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Specialty::GeneralPractice => &["general", "general medicine", "general practitioner", "family medicine", "primary care", "gp"],
+            Specialty::InternalMedicine => &["internist"],
+            Specialty::Cardiology => &["cardiac", "cardiologist", "heart"],
+            Specialty::Dermatology => &["dermatologist", "skin"],
+            Specialty::Pediatrics => &["paediatrics", "pediatrician", "paediatrician"],
+            Specialty::Psychiatry => &["psychiatrist", "mental health"],
+            Specialty::Psychology => &["psychologist", "therapy"],
+            Specialty::Gynecology => &["gynaecology", "gynecologist", "obstetrics and gynecology", "ob/gyn", "obgyn"],
+            Specialty::Neurology => &["neurologist"],
+            Specialty::Orthopedics => &["orthopaedics", "orthopedic surgery", "orthopedist"],
+            Specialty::Endocrinology => &["endocrinologist"],
+            Specialty::Gastroenterology => &["gastroenterologist"],
+            Specialty::Ophthalmology => &["ophthalmologist"],
+            Specialty::Otolaryngology => &["ent", "ear nose and throat"],
+            Specialty::Urology => &["urologist"],
+            Specialty::Oncology => &["oncologist"],
+            Specialty::Pulmonology => &["pulmonologist", "respiratory medicine"],
+            Specialty::Nutrition => &["nutritionist", "dietitian", "dietetics"],
+        }
+    }
+
+    /// Resolve free text onto the registry. Case, surrounding whitespace and
+    /// `-`/`_` separators are ignored.
+    pub fn parse(input: &str) -> Option<Specialty> {
+        let normalized = input
+            .replace(['-', '_'], " ")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+
+        if normalized.is_empty() {
+            return None;
+        }
+
+        Specialty::ALL.into_iter().find(|specialty| {
+            specialty.as_str().to_lowercase() == normalized
+                || specialty.aliases().contains(&normalized.as_str())
+        })
+    }
+}
+
+impl std::fmt::Display for Specialty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 // Error types specific to doctor operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DoctorError {
@@ -211,6 +452,8 @@ pub enum DoctorError {
     InvalidTimeSlot,
     UnauthorizedAccess,
     ValidationError(String),
+    DatabaseError(String),
+    Conflict(String),
 }
 
 impl std::fmt::Display for DoctorError {
@@ -222,6 +465,8 @@ impl std::fmt::Display for DoctorError {
             DoctorError::InvalidTimeSlot => write!(f, "Invalid time slot"),
             DoctorError::UnauthorizedAccess => write!(f, "Unauthorized access to doctor data"),
             DoctorError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
+            DoctorError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
+            DoctorError::Conflict(msg) => write!(f, "Conflict: {}", msg),
         }
     }
 }
@@ -18,7 +18,9 @@ pub fn doctor_routes(state: Arc<AppConfig>) -> Router {
         .route("/{doctor_id}", get(handlers::get_doctor_public))
         .route("/{doctor_id}/specialties", get(handlers::get_doctor_specialties_public))
         .route("/{doctor_id}/availability", get(handlers::get_doctor_availability_public))
-        .route("/{doctor_id}/available-slots", get(handlers::get_available_slots_public));
+        .route("/{doctor_id}/available-slots", get(handlers::get_available_slots_public))
+        .route("/{doctor_id}/reviews", get(handlers::get_doctor_reviews_public))
+        .route("/{doctor_id}/calendar.ics", get(handlers::get_doctor_calendar_feed));
 
     // Protected routes (authentication required)
     let protected_routes = Router::new()
@@ -26,11 +28,19 @@ pub fn doctor_routes(state: Arc<AppConfig>) -> Router {
         .route("/", post(handlers::create_doctor))
         .route("/{doctor_id}", put(handlers::update_doctor))
         .route("/{doctor_id}/verify", patch(handlers::verify_doctor))
+        .route("/{doctor_id}/verification", get(handlers::get_doctor_verification).post(handlers::submit_doctor_verification))
+        .route("/verifications/pending", get(handlers::get_pending_verifications))
+        .route("/verifications/{verification_id}/review", post(handlers::review_doctor_verification))
         .route("/{doctor_id}/stats", get(handlers::get_doctor_stats))
+        .route("/{doctor_id}/utilization", get(handlers::get_doctor_utilization))
+        .route("/{doctor_id}/calendar-feed", post(handlers::rotate_doctor_calendar_feed).delete(handlers::revoke_doctor_calendar_feed))
         .route("/{doctor_id}/profile-image", post(handlers::upload_doctor_profile_image))
         
         // Doctor specialties management
         .route("/{doctor_id}/specialties", post(handlers::add_doctor_specialty))
+
+        // Patient reviews of completed appointments
+        .route("/{doctor_id}/reviews", post(handlers::submit_doctor_review))
         
         // Availability management - NO PRICING IN SCHEDULES
         .route("/{doctor_id}/availability", post(handlers::create_availability))
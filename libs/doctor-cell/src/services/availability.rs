@@ -1,5 +1,6 @@
 use anyhow::{Result, anyhow};
-use chrono::{NaiveDate, NaiveTime, DateTime, Utc, Datelike, Weekday, Duration};
+use chrono::{NaiveDate, NaiveTime, DateTime, Utc, Datelike, Duration};
+use chrono_tz::Tz;
 use reqwest::Method;
 use serde_json::{json, Value};
 use tracing::{debug, warn, error};
@@ -12,10 +13,16 @@ use crate::models::{
     DoctorAvailability, DoctorAvailabilityOverride, AvailableSlot,
     CreateAvailabilityRequest, UpdateAvailabilityRequest,
     CreateAvailabilityOverrideRequest, AvailabilityQueryRequest,
-    DoctorAvailabilityResponse, DoctorError,
+    DoctorAvailabilityResponse, DoctorError, AvailabilityRangeRequest, DailyAvailableSlots,
+    AffectedAppointment,
 };
+use crate::services::availability_cache::{self, SlotsKey};
 use crate::services::doctor::DoctorService;
 
+/// Longest range `get_available_slots_range` computes in one call; enough
+/// for a month view padded to whole weeks
+pub const MAX_AVAILABILITY_RANGE_DAYS: i64 = 62;
+
 pub struct AvailabilityService {
     supabase: SupabaseClient,
     config: AppConfig,
@@ -29,6 +36,12 @@ pub fn new(config: &AppConfig) -> Self {
     }
 }
 
+    /// How long computed slots are reused, `None` when caching is off
+    fn slots_cache_ttl(&self) -> Option<std::time::Duration> {
+        (self.config.availability_cache_ttl_seconds > 0)
+            .then(|| std::time::Duration::from_secs(self.config.availability_cache_ttl_seconds))
+    }
+
     /// Create availability schedule for a doctor
     pub async fn create_availability(
         &self,
@@ -49,7 +62,7 @@ pub fn new(config: &AppConfig) -> Self {
         }
 
         // Check for overlapping availability
-        if let Err(e) = self.check_availability_conflicts(
+        self.check_availability_conflicts(
             doctor_id,
             request.day_of_week,
             request.start_time,
@@ -57,9 +70,7 @@ pub fn new(config: &AppConfig) -> Self {
             request.specific_date,
             None, // No existing ID to exclude
             auth_token,
-        ).await {
-            return Err(e);
-        }
+        ).await?;
 
         let availability_data = json!({
             "doctor_id": doctor_id,
@@ -95,6 +106,7 @@ pub fn new(config: &AppConfig) -> Self {
 
         let availability: DoctorAvailability = serde_json::from_value(result[0].clone())?;
         debug!("Availability created with ID: {}", availability.id);
+        availability_cache::invalidate_doctor(doctor_id);
 
         Ok(availability)
     }
@@ -118,7 +130,7 @@ pub fn new(config: &AppConfig) -> Self {
             }
 
             // Check for conflicts
-            if let Err(e) = self.check_availability_conflicts(
+            self.check_availability_conflicts(
                 &current.doctor_id.to_string(),
                 current.day_of_week,
                 start,
@@ -126,9 +138,7 @@ pub fn new(config: &AppConfig) -> Self {
                 current.specific_date,
                 Some(availability_id),
                 auth_token,
-            ).await {
-                return Err(e);
-            }
+            ).await?;
         }
 
         // Build update object
@@ -175,6 +185,7 @@ pub fn new(config: &AppConfig) -> Self {
         }
 
         let updated_availability: DoctorAvailability = serde_json::from_value(result[0].clone())?;
+        availability_cache::invalidate_doctor(&updated_availability.doctor_id.to_string());
         Ok(updated_availability)
     }
 
@@ -195,7 +206,7 @@ pub fn new(config: &AppConfig) -> Self {
         ).await?;
 
         let availabilities: Vec<DoctorAvailability> = result.into_iter()
-            .map(|avail| serde_json::from_value(avail))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<DoctorAvailability>, _>>()?;
 
         Ok(availabilities)
@@ -204,6 +215,8 @@ pub fn new(config: &AppConfig) -> Self {
     /// Calculate available slots for a specific date
     /// NOTE: This method calculates theoretical availability based on doctor's schedule.
     /// The appointment-cell should call this and then filter out actually booked slots.
+    /// Results are cached for `availability_cache_ttl_seconds` and dropped as soon as
+    /// the doctor's schedules or overrides change through this service.
     pub async fn get_available_slots(
         &self,
         doctor_id: &str,
@@ -212,17 +225,15 @@ pub fn new(config: &AppConfig) -> Self {
     ) -> Result<Vec<AvailableSlot>> {
         debug!("Calculating theoretical available slots for doctor {} on {}", doctor_id, query.date);
 
+        let cache_ttl = self.slots_cache_ttl();
+        let cache_key = SlotsKey::new(doctor_id, &query);
+        if let Some(slots) = cache_ttl.and_then(|ttl| availability_cache::get(&cache_key, ttl)) {
+            debug!("Serving {} cached slots for doctor {} on {}", slots.len(), doctor_id, query.date);
+            return Ok(slots);
+        }
+
         // Get day of week (0 = Sunday, 1 = Monday, etc.)
-        let weekday = query.date.weekday();
-        let day_of_week = match weekday {
-            Weekday::Sun => 0,
-            Weekday::Mon => 1,
-            Weekday::Tue => 2,
-            Weekday::Wed => 3,
-            Weekday::Thu => 4,
-            Weekday::Fri => 5,
-            Weekday::Sat => 6,
-        };
+        let day_of_week = query.date.weekday().num_days_from_sunday() as i32;
 
         // Get availability schedules for this day
         let mut availability_schedules = self.get_availability_for_day(
@@ -244,36 +255,98 @@ pub fn new(config: &AppConfig) -> Self {
         if let Some(override_entry) = overrides.first() {
             if !override_entry.is_available {
                 debug!("Doctor has availability override for {}: not available", query.date);
+                if let Some(ttl) = cache_ttl {
+                    availability_cache::insert(cache_key, Vec::new(), ttl);
+                }
                 return Ok(vec![]);
             }
         }
 
-        let mut available_slots = Vec::new();
-
-        // Calculate theoretical slots for each availability schedule
-        for schedule in availability_schedules {
-            if !schedule.is_available {
-                continue;
-            }
+        let available_slots = self.slots_for_date(
+            &availability_schedules,
+            query.date,
+            query.duration_minutes,
+            query.timezone.as_deref(),
+        );
 
-            let slots = self.calculate_theoretical_slots_for_schedule(
-                &schedule,
-                query.date,
-                query.duration_minutes,
-                query.timezone.as_deref().unwrap_or(schedule.timezone.as_str()),
-            ).await?;
+        debug!("Found {} theoretical available slots", available_slots.len());
+        if let Some(ttl) = cache_ttl {
+            availability_cache::insert(cache_key, available_slots.clone(), ttl);
+        }
+        Ok(available_slots)
+    }
 
-            available_slots.extend(slots);
+    /// Theoretical slots for every date in `from..=to`, grouped by date. The
+    /// schedules and overrides for the whole range are read in two queries
+    /// and the slots computed here, rather than a round trip per date.
+    pub async fn get_available_slots_range(
+        &self,
+        doctor_id: &str,
+        request: AvailabilityRangeRequest,
+        auth_token: Option<&str>,
+    ) -> Result<Vec<DailyAvailableSlots>> {
+        debug!("Calculating theoretical available slots for doctor {} from {} to {}",
+               doctor_id, request.from, request.to);
+
+        if request.to < request.from {
+            return Err(anyhow!("'to' must not be before 'from'"));
+        }
+        if (request.to - request.from).num_days() >= MAX_AVAILABILITY_RANGE_DAYS {
+            return Err(anyhow!("Availability range cannot exceed {} days", MAX_AVAILABILITY_RANGE_DAYS));
         }
 
-        // Sort slots by start time
-        available_slots.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+        let schedules_path = format!(
+            "/rest/v1/appointment_availabilities?doctor_id=eq.{}&is_available=eq.true&or=(is_recurring.eq.true,and(specific_date.gte.{},specific_date.lte.{}))&order=start_time.asc",
+            doctor_id, request.from, request.to
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &schedules_path,
+            auth_token,
+            None,
+        ).await?;
+        let mut schedules: Vec<DoctorAvailability> = result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<Vec<DoctorAvailability>, _>>()?;
+        if let Some(ref appointment_type) = request.appointment_type {
+            schedules.retain(|schedule| schedule.appointment_type == *appointment_type);
+        }
 
-        // Remove duplicates and overlapping slots
-        available_slots = self.remove_overlapping_slots(available_slots);
+        let overrides_path = format!(
+            "/rest/v1/doctor_availability_overrides?doctor_id=eq.{}&and=(override_date.gte.{},override_date.lte.{})",
+            doctor_id, request.from, request.to
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &overrides_path,
+            auth_token,
+            None,
+        ).await?;
+        let overrides: Vec<DoctorAvailabilityOverride> = result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<Vec<DoctorAvailabilityOverride>, _>>()?;
 
-        debug!("Found {} theoretical available slots", available_slots.len());
-        Ok(available_slots)
+        let days = request.from.iter_days()
+            .take_while(|date| *date <= request.to)
+            .map(|date| {
+                let unavailable = overrides.iter()
+                    .any(|entry| entry.override_date == date && !entry.is_available);
+                let available_slots = if unavailable {
+                    Vec::new()
+                } else {
+                    let day_of_week = date.weekday().num_days_from_sunday() as i32;
+                    let day_schedules: Vec<DoctorAvailability> = schedules.iter()
+                        .filter(|schedule| schedule.day_of_week == day_of_week
+                            && (schedule.is_recurring || schedule.specific_date == Some(date)))
+                        .cloned()
+                        .collect();
+                    self.slots_for_date(&day_schedules, date, request.duration_minutes, request.timezone.as_deref())
+                };
+                DailyAvailableSlots { date, available_slots }
+            })
+            .collect();
+
+        Ok(days)
     }
 
     /// Create availability override (vacation, sick day, etc.)
@@ -326,9 +399,46 @@ pub fn new(config: &AppConfig) -> Self {
         }
 
         let override_entry: DoctorAvailabilityOverride = serde_json::from_value(result[0].clone())?;
+        availability_cache::invalidate_doctor(doctor_id);
         Ok(override_entry)
     }
 
+    /// The doctor's pending and confirmed appointments on `date`, earliest
+    /// first. Each appointment's date is read in its own timezone.
+    pub async fn appointments_on_date(
+        &self,
+        doctor_id: &str,
+        date: NaiveDate,
+        auth_token: &str,
+    ) -> Result<Vec<AffectedAppointment>> {
+        debug!("Looking for appointments of doctor {} on {}", doctor_id, date);
+
+        // Wide enough to cover the date in any timezone
+        let window_start = (date - Duration::days(1)).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        let window_end = (date + Duration::days(2)).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        let path = format!(
+            "/rest/v1/appointments?doctor_id=eq.{}&status=in.(pending,confirmed)&scheduled_start_time=gte.{}&scheduled_start_time=lt.{}&order=scheduled_start_time.asc&select=id,patient_id,appointment_type,status,timezone,scheduled_start_time,scheduled_end_time",
+            doctor_id,
+            window_start.format("%Y-%m-%dT%H:%M:%SZ"),
+            window_end.format("%Y-%m-%dT%H:%M:%SZ")
+        );
+
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await?;
+
+        let appointments: Vec<AffectedAppointment> = result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<Vec<AffectedAppointment>, _>>()?;
+
+        Ok(appointments.into_iter()
+            .filter(|appointment| falls_on(appointment.scheduled_start_time, &appointment.timezone, date))
+            .collect())
+    }
+
     /// Get availability summary for multiple doctors
     pub async fn get_doctors_availability_summary(
         &self,
@@ -389,13 +499,21 @@ pub fn new(config: &AppConfig) -> Self {
         debug!("Deleting availability: {}", availability_id);
 
         let path = format!("/rest/v1/appointment_availabilities?id=eq.{}", availability_id);
-        let _: Vec<Value> = self.supabase.request(
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let deleted: Vec<Value> = self.supabase.request_with_headers(
             Method::DELETE,
             &path,
             Some(auth_token),
             None,
+            Some(headers),
         ).await?;
 
+        for doctor_id in deleted.iter().filter_map(|row| row["doctor_id"].as_str()) {
+            availability_cache::invalidate_doctor(doctor_id);
+        }
+
         Ok(())
     }
 
@@ -422,6 +540,7 @@ pub fn new(config: &AppConfig) -> Self {
         Ok(availability)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn check_availability_conflicts(
         &self,
         doctor_id: &str,
@@ -455,11 +574,13 @@ pub fn new(config: &AppConfig) -> Self {
 
         for avail in existing {
             let existing_start = NaiveTime::parse_from_str(
-                avail["start_time"].as_str().unwrap(), 
+                avail["start_time"].as_str()
+                    .ok_or_else(|| anyhow!("Malformed availability row: missing start_time"))?,
                 "%H:%M:%S"
             )?;
             let existing_end = NaiveTime::parse_from_str(
-                avail["end_time"].as_str().unwrap(), 
+                avail["end_time"].as_str()
+                    .ok_or_else(|| anyhow!("Malformed availability row: missing end_time"))?,
                 "%H:%M:%S"
             )?;
 
@@ -501,7 +622,7 @@ pub fn new(config: &AppConfig) -> Self {
         ).await?;
 
         let availabilities: Vec<DoctorAvailability> = result.into_iter()
-            .map(|avail| serde_json::from_value(avail))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<DoctorAvailability>, _>>()?;
 
         Ok(availabilities)
@@ -527,21 +648,48 @@ pub fn new(config: &AppConfig) -> Self {
         ).await?;
 
         let overrides: Vec<DoctorAvailabilityOverride> = result.into_iter()
-            .map(|override_data| serde_json::from_value(override_data))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<DoctorAvailabilityOverride>, _>>()?;
 
         Ok(overrides)
     }
 
+    /// Sorted, non-overlapping slots from the schedules that apply on `date`
+    fn slots_for_date(
+        &self,
+        schedules: &[DoctorAvailability],
+        date: NaiveDate,
+        requested_duration: Option<i32>,
+        timezone: Option<&str>,
+    ) -> Vec<AvailableSlot> {
+        let mut available_slots = Vec::new();
+
+        // Calculate theoretical slots for each availability schedule
+        for schedule in schedules {
+            if !schedule.is_available {
+                continue;
+            }
+
+            available_slots.extend(Self::calculate_theoretical_slots_for_schedule(
+                schedule,
+                date,
+                requested_duration,
+                timezone.unwrap_or(schedule.timezone.as_str()),
+            ));
+        }
+
+        // Remove duplicates and overlapping slots
+        self.remove_overlapping_slots(available_slots)
+    }
+
     /// Calculate theoretical slots based on doctor's availability schedule
     /// This doesn't check for actual appointments - that's the appointment-cell's responsibility
-    async fn calculate_theoretical_slots_for_schedule(
-        &self,
+    fn calculate_theoretical_slots_for_schedule(
         schedule: &DoctorAvailability,
         date: NaiveDate,
         requested_duration: Option<i32>,
         timezone: &str,
-    ) -> Result<Vec<AvailableSlot>> {
+    ) -> Vec<AvailableSlot> {
         let duration_minutes = requested_duration.unwrap_or(schedule.duration_minutes);
         let buffer_minutes = schedule.buffer_minutes;
         let total_slot_duration = duration_minutes + buffer_minutes;
@@ -550,6 +698,9 @@ pub fn new(config: &AppConfig) -> Self {
         let start_datetime = date.and_time(schedule.start_time).and_utc();
         let end_datetime = date.and_time(schedule.end_time).and_utc();
 
+        // Callers validate the zone; anything unresolvable is shown in UTC
+        let local_zone = timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+
         let mut slots = Vec::new();
         let mut current_time = start_datetime;
 
@@ -562,12 +713,14 @@ pub fn new(config: &AppConfig) -> Self {
                 duration_minutes,
                 appointment_type: schedule.appointment_type.clone(),
                 timezone: timezone.to_string(),
+                start_time_local: current_time.with_timezone(&local_zone).to_rfc3339(),
+                end_time_local: slot_end.with_timezone(&local_zone).to_rfc3339(),
             });
 
             current_time += Duration::minutes(total_slot_duration as i64);
         }
 
-        Ok(slots)
+        slots
     }
 
     fn remove_overlapping_slots(&self, mut slots: Vec<AvailableSlot>) -> Vec<AvailableSlot> {
@@ -575,7 +728,7 @@ pub fn new(config: &AppConfig) -> Self {
             return slots;
         }
 
-        slots.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+        slots.sort_by_key(|a| a.start_time);
         
         let mut result = Vec::new();
         let mut last_end_time = DateTime::<Utc>::MIN_UTC;
@@ -590,11 +743,11 @@ pub fn new(config: &AppConfig) -> Self {
         result
     }
 
-    ///
-    /// PUBLIC API METHODS
-    /// 
+    // ==============================================================================
+    // PUBLIC API METHODS
+    // ==============================================================================
 
-     /// PUBLIC: Get doctor availability without authentication
+    /// PUBLIC: Get doctor availability without authentication
     pub async fn get_doctor_availability_public(
         &self,
         doctor_id: &str,
@@ -631,7 +784,7 @@ pub fn new(config: &AppConfig) -> Self {
         })?;
 
         let availability_slots: Vec<AvailableSlot> = result.into_iter()
-            .map(|slot| serde_json::from_value(slot))
+            .map(serde_json::from_value)
             .collect::<Result<Vec<AvailableSlot>, _>>()
             .map_err(|e| {
                 error!("Failed to parse availability: {}", e);
@@ -659,4 +812,25 @@ pub fn new(config: &AppConfig) -> Self {
         debug!("Returning {} theoretical slots (public) for doctor: {}", availability.len(), doctor_id);
         Ok(availability)
     }
-}
\ No newline at end of file
+}
+/// Whether `start` is on `date` in `timezone` (an IANA name; UTC when unknown)
+fn falls_on(start: DateTime<Utc>, timezone: &str, date: NaiveDate) -> bool {
+    let zone = timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+    start.with_timezone(&zone).date_naive() == date
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_falls_on_uses_the_appointments_timezone() {
+        let date = NaiveDate::from_ymd_opt(2025, 7, 2).unwrap();
+        // 23:30 UTC on 1 July is 00:30 on 2 July in Dublin
+        let late = Utc.with_ymd_and_hms(2025, 7, 1, 23, 30, 0).unwrap();
+        assert!(falls_on(late, "Europe/Dublin", date));
+        assert!(!falls_on(late, "UTC", date));
+        assert!(!falls_on(late, "Not/AZone", date));
+    }
+}
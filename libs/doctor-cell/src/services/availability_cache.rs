@@ -0,0 +1,102 @@
+// libs/doctor-cell/src/services/availability_cache.rs
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDate;
+
+use crate::models::{AvailabilityQueryRequest, AvailableSlot};
+
+/// Everything a slot computation depends on besides the doctor's schedules
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SlotsKey {
+    doctor_id: String,
+    date: NaiveDate,
+    duration_minutes: Option<i32>,
+    appointment_type: Option<String>,
+    timezone: Option<String>,
+}
+
+impl SlotsKey {
+    pub(crate) fn new(doctor_id: &str, query: &AvailabilityQueryRequest) -> Self {
+        Self {
+            doctor_id: doctor_id.to_string(),
+            date: query.date,
+            duration_minutes: query.duration_minutes,
+            appointment_type: query.appointment_type.clone(),
+            timezone: query.timezone.clone(),
+        }
+    }
+}
+
+type CachedSlots = HashMap<SlotsKey, (Instant, Vec<AvailableSlot>)>;
+
+/// Theoretical slots computed from each doctor's schedules and overrides.
+/// Bookings never go into these (appointment-cell checks them against the
+/// appointments table), so only schedule and override changes make an entry
+/// stale. Entries live in this process only; the TTL bounds how long another
+/// instance's schedule change can go unnoticed here.
+fn cache() -> &'static Mutex<CachedSlots> {
+    static CACHE: OnceLock<Mutex<CachedSlots>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+pub(crate) fn get(key: &SlotsKey, ttl: Duration) -> Option<Vec<AvailableSlot>> {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    match cache.get(key) {
+        Some((cached_at, slots)) if cached_at.elapsed() < ttl => Some(slots.clone()),
+        Some(_) => {
+            cache.remove(key);
+            None
+        }
+        None => None,
+    }
+}
+
+pub(crate) fn insert(key: SlotsKey, slots: Vec<AvailableSlot>, ttl: Duration) {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|_, (cached_at, _)| cached_at.elapsed() < ttl);
+    cache.insert(key, (Instant::now(), slots));
+}
+
+/// Drop every cached day for the doctor after their schedule changed
+pub(crate) fn invalidate_doctor(doctor_id: &str) {
+    cache().lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|key, _| key.doctor_id != doctor_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(date: NaiveDate, duration_minutes: Option<i32>) -> AvailabilityQueryRequest {
+        AvailabilityQueryRequest {
+            date,
+            timezone: None,
+            appointment_type: None,
+            duration_minutes,
+        }
+    }
+
+    #[test]
+    fn test_entries_expire_and_are_dropped_per_doctor() {
+        let ttl = Duration::from_secs(60);
+        let date = NaiveDate::from_ymd_opt(2030, 1, 7).unwrap();
+        let doctor = uuid::Uuid::new_v4().to_string();
+        let other = uuid::Uuid::new_v4().to_string();
+
+        let key = SlotsKey::new(&doctor, &query(date, Some(30)));
+        insert(key.clone(), Vec::new(), ttl);
+        insert(SlotsKey::new(&other, &query(date, Some(30))), Vec::new(), ttl);
+
+        assert!(get(&key, ttl).is_some());
+        assert!(get(&SlotsKey::new(&doctor, &query(date, Some(45))), ttl).is_none());
+        assert!(get(&key, Duration::ZERO).is_none());
+
+        insert(key.clone(), Vec::new(), ttl);
+        invalidate_doctor(&doctor);
+        assert!(get(&key, ttl).is_none());
+        assert!(get(&SlotsKey::new(&other, &query(date, Some(30))), ttl).is_some());
+    }
+}
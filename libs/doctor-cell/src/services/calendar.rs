@@ -0,0 +1,327 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+use shared_models::auth::JwtClaims;
+use shared_utils::jwt::create_token;
+
+use crate::models::DoctorError;
+
+/// Lifetime of the doctor-scoped JWT minted to read the feed's appointments
+const FEED_DB_TOKEN_TTL_SECONDS: i64 = 300;
+
+/// Include appointments that ended up to this long ago
+const FEED_LOOKBACK_DAYS: i64 = 1;
+
+const FEED_MAX_EVENTS: usize = 500;
+
+/// Subscribable iCalendar feed of a doctor's upcoming appointments. Calendar
+/// apps can't send a bearer header, so the feed URL carries a random
+/// per-doctor token instead; it grants read access to the feed and nothing
+/// else. Only its hash is stored, and rotating or revoking it replaces the row.
+pub struct CalendarFeedService {
+    supabase: SupabaseClient,
+    jwt_secret: String,
+}
+
+impl CalendarFeedService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            jwt_secret: config.supabase_jwt_secret.clone(),
+        }
+    }
+
+    /// Issue a new feed token, replacing any previous one. The token is
+    /// returned once and can't be recovered afterwards.
+    pub async fn rotate_feed_token(&self, doctor_id: &str, auth_token: &str) -> Result<String, DoctorError> {
+        let feed_token = format!("cal_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "Prefer",
+            reqwest::header::HeaderValue::from_static("return=minimal,resolution=merge-duplicates"),
+        );
+
+        let body = json!({
+            "doctor_id": doctor_id,
+            "token_hash": hash_feed_token(&feed_token),
+            "created_at": Utc::now().to_rfc3339(),
+        });
+
+        let _: Value = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/doctor_calendar_feeds?on_conflict=doctor_id",
+            Some(auth_token),
+            Some(body),
+            Some(headers),
+        ).await.map_err(|e| {
+            error!("Failed to store calendar feed token for doctor {}: {}", doctor_id, e);
+            DoctorError::DatabaseError(e.to_string())
+        })?;
+
+        info!("Rotated calendar feed token for doctor {}", doctor_id);
+        Ok(feed_token)
+    }
+
+    /// Revoke the doctor's feed; the old URL stops working immediately.
+    /// `NotFound` when the doctor has no feed to revoke.
+    pub async fn revoke_feed_token(&self, doctor_id: &str, auth_token: &str) -> Result<(), DoctorError> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let path = format!("/rest/v1/doctor_calendar_feeds?doctor_id=eq.{}", doctor_id);
+        let deleted: Vec<Value> = self.supabase.request_with_headers(
+            Method::DELETE,
+            &path,
+            Some(auth_token),
+            None,
+            Some(headers),
+        ).await.map_err(|e| {
+            error!("Failed to revoke calendar feed for doctor {}: {}", doctor_id, e);
+            DoctorError::DatabaseError(e.to_string())
+        })?;
+
+        if deleted.is_empty() {
+            return Err(DoctorError::NotFound);
+        }
+
+        info!("Revoked calendar feed token for doctor {}", doctor_id);
+        Ok(())
+    }
+
+    pub async fn verify_feed_token(&self, doctor_id: &str, token: &str) -> Result<bool, DoctorError> {
+        if token.is_empty() || Uuid::parse_str(doctor_id).is_err() {
+            return Ok(false);
+        }
+
+        let db_token = self.doctor_db_token(doctor_id, Utc::now())?;
+        let path = format!(
+            "/rest/v1/doctor_calendar_feeds?doctor_id=eq.{}&token_hash=eq.{}&select=doctor_id",
+            doctor_id,
+            hash_feed_token(token)
+        );
+
+        let rows: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(&db_token),
+            None,
+        ).await.map_err(|e| {
+            error!("Failed to look up calendar feed for doctor {}: {}", doctor_id, e);
+            DoctorError::DatabaseError(e.to_string())
+        })?;
+
+        Ok(!rows.is_empty())
+    }
+
+    /// Render the doctor's upcoming appointments as an iCalendar document
+    pub async fn doctor_calendar(&self, doctor_id: &str) -> Result<String, DoctorError> {
+        debug!("Building calendar feed for doctor: {}", doctor_id);
+
+        let now = Utc::now();
+        let db_token = self.doctor_db_token(doctor_id, now)?;
+
+        let path = format!(
+            "/rest/v1/appointments?doctor_id=eq.{}&status=in.(pending,confirmed,in_progress)&scheduled_end_time=gte.{}&order=scheduled_start_time.asc&limit={}&select=id,patient_id,appointment_type,status,timezone,scheduled_start_time,scheduled_end_time,video_conference_link,updated_at",
+            doctor_id,
+            (now - Duration::days(FEED_LOOKBACK_DAYS)).to_rfc3339_opts(SecondsFormat::Secs, true),
+            FEED_MAX_EVENTS
+        );
+
+        let appointments: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(&db_token),
+            None,
+        ).await.map_err(|e| {
+            error!("Failed to load calendar appointments for doctor {}: {}", doctor_id, e);
+            DoctorError::DatabaseError(e.to_string())
+        })?;
+
+        Ok(render_calendar(&appointments, now))
+    }
+
+    /// Short-lived token so the feed reads appointments under the doctor's RLS policies
+    fn doctor_db_token(&self, doctor_id: &str, now: DateTime<Utc>) -> Result<String, DoctorError> {
+        let claims = JwtClaims {
+            sub: doctor_id.to_string(),
+            exp: Some((now + Duration::seconds(FEED_DB_TOKEN_TTL_SECONDS)).timestamp() as u64),
+            email: None,
+            role: Some("authenticated".to_string()),
+            app_metadata: None,
+            user_metadata: None,
+            aud: Some("authenticated".to_string()),
+            iat: Some(now.timestamp() as u64),
+        };
+        create_token(&claims, &self.jwt_secret).map_err(DoctorError::ValidationError)
+    }
+}
+
+/// Hex SHA-256 of a feed token, which is what gets stored
+fn hash_feed_token(feed_token: &str) -> String {
+    Sha256::digest(feed_token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Times are written in UTC, which every client converts to the viewer's
+/// zone; the appointment's own zone is shown in the description.
+pub fn render_calendar(appointments: &[Value], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Amae Clinic//Doctor Calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "X-WR-CALNAME:Amae Clinic appointments".to_string(),
+    ];
+
+    for appointment in appointments {
+        let (Some(id), Some(start), Some(end)) = (
+            appointment["id"].as_str(),
+            parse_time(&appointment["scheduled_start_time"]),
+            parse_time(&appointment["scheduled_end_time"]),
+        ) else {
+            continue;
+        };
+
+        let patient_id = appointment["patient_id"].as_str().unwrap_or("unknown");
+        let appointment_type = appointment["appointment_type"].as_str().unwrap_or("appointment").replace('_', " ");
+        let timezone = appointment["timezone"].as_str().unwrap_or("UTC");
+        let video_link = appointment["video_conference_link"].as_str().filter(|link| !link.is_empty());
+        let stamp = parse_time(&appointment["updated_at"]).unwrap_or(now);
+
+        let mut description = vec![
+            format!("Patient reference: {}", patient_id),
+            format!("Local time: {}", local_time_label(start, timezone)),
+        ];
+        if let Some(link) = video_link {
+            description.push(format!("Video: {}", link));
+        }
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@amae-clinic", id));
+        lines.push(format!("DTSTAMP:{}", ical_utc(stamp)));
+        lines.push(format!("DTSTART:{}", ical_utc(start)));
+        lines.push(format!("DTEND:{}", ical_utc(end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&format!("{} (patient {})", appointment_type, short_ref(patient_id)))));
+        lines.push(format!("DESCRIPTION:{}", escape_text(&description.join("\n"))));
+        if let Some(link) = video_link {
+            lines.push(format!("URL:{}", link));
+        }
+        let status = if appointment["status"].as_str() == Some("pending") { "TENTATIVE" } else { "CONFIRMED" };
+        lines.push(format!("STATUS:{}", status));
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n") + "\r\n"
+}
+
+fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    value.as_str()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn ical_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Start time rendered in the appointment's zone, falling back to UTC for
+/// zone names we can't resolve
+fn local_time_label(start: DateTime<Utc>, timezone: &str) -> String {
+    match timezone.parse::<Tz>() {
+        Ok(tz) => start.with_timezone(&tz).format("%Y-%m-%d %H:%M %Z").to_string(),
+        Err(_) => start.format("%Y-%m-%d %H:%M UTC").to_string(),
+    }
+}
+
+fn short_ref(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+/// RFC 5545 TEXT escaping
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold content lines longer than 75 octets, never splitting a UTF-8 character
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_calendar_event() {
+        let appointments = vec![json!({
+            "id": "7d9f1c1e-0000-0000-0000-000000000001",
+            "patient_id": "3b2a9c4d-0000-0000-0000-000000000002",
+            "appointment_type": "follow_up_consultation",
+            "status": "confirmed",
+            "timezone": "Europe/London",
+            "scheduled_start_time": "2024-07-01T09:00:00Z",
+            "scheduled_end_time": "2024-07-01T09:30:00Z",
+            "video_conference_link": "https://clinic.example/video/abc",
+            "updated_at": "2024-06-30T12:00:00Z"
+        })];
+
+        let ics = render_calendar(&appointments, Utc::now());
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("DTSTART:20240701T090000Z\r\n"));
+        assert!(ics.contains("DTEND:20240701T093000Z\r\n"));
+        assert!(ics.contains("URL:https://clinic.example/video/abc\r\n"));
+        // 09:00 UTC is 10:00 BST in July
+        assert!(ics.replace("\r\n ", "").contains("Local time: 2024-07-01 10:00 BST"));
+        assert!(ics.lines().all(|line| line.len() <= 75));
+    }
+
+    #[test]
+    fn test_escape_and_fold() {
+        assert_eq!(escape_text("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+
+        let long = "DESCRIPTION:".to_string() + &"x".repeat(100);
+        let folded = fold_line(&long);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), long);
+    }
+
+    #[test]
+    fn test_feed_token_hash_is_stable_and_distinct() {
+        let hash = hash_feed_token("cal_abc");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_feed_token("cal_abc"));
+        assert_ne!(hash, hash_feed_token("cal_abd"));
+    }
+}
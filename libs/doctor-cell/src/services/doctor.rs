@@ -1,10 +1,12 @@
+use std::collections::HashMap;
+
 use anyhow::{Result, anyhow};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use reqwest::Method;
 use serde_json::{json, Value};
 use tracing::{debug, error};
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{Duration, NaiveDate, SecondsFormat, Utc};
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
@@ -12,17 +14,25 @@ use shared_database::supabase::SupabaseClient;
 use crate::models::{
     Doctor, DoctorSpecialty, DoctorStats, DoctorSearchFilters,
     CreateDoctorRequest, UpdateDoctorRequest, CreateSpecialtyRequest,
-    DoctorImageUpload, AvailableSlot, DoctorError
+    DoctorImageUpload, AvailableSlot, DoctorError, Specialty,
+    DoctorUtilization, AppointmentTypeUtilization, AvailabilityQueryRequest,
 };
+use crate::services::availability::AvailabilityService;
+
+/// Longest range a utilization report may cover; the denominator costs a
+/// schedule lookup per day
+pub const MAX_UTILIZATION_RANGE_DAYS: i64 = 92;
 
 pub struct DoctorService {
     supabase: SupabaseClient,
+    config: AppConfig,
 }
 
 impl DoctorService {
     pub fn new(config: &AppConfig) -> Self {
         Self {
             supabase: SupabaseClient::new(config),
+            config: config.clone(),
         }
     }
 
@@ -43,6 +53,9 @@ impl DoctorService {
             return Err(anyhow!("Invalid timezone: {}", request.timezone));
         }
 
+        let specialty = Self::normalize_specialty(&request.specialty)
+            .ok_or_else(|| anyhow!("Unknown specialty: {}", request.specialty))?;
+
         // Check if doctor with email already exists
         let existing_check_path = format!("/rest/v1/doctors?email=eq.{}", request.email);
         let existing: Vec<Value> = self.supabase.request(
@@ -59,7 +72,7 @@ impl DoctorService {
         let doctor_data = json!({
             "full_name": request.full_name,
             "email": request.email,
-            "specialty": request.specialty,
+            "specialty": specialty.as_str(),
             "bio": request.bio,
             "license_number": request.license_number,
             "years_experience": request.years_experience,
@@ -133,6 +146,14 @@ impl DoctorService {
             }
         }
 
+        let specialty = match request.specialty {
+            Some(ref specialty) => Some(
+                Self::normalize_specialty(specialty)
+                    .ok_or_else(|| anyhow!("Unknown specialty: {}", specialty))?
+            ),
+            None => None,
+        };
+
         // Build update object with only provided fields
         let mut update_data = serde_json::Map::new();
         
@@ -142,8 +163,8 @@ impl DoctorService {
         if let Some(bio) = request.bio {
             update_data.insert("bio".to_string(), json!(bio));
         }
-        if let Some(specialty) = request.specialty {
-            update_data.insert("specialty".to_string(), json!(specialty));
+        if let Some(specialty) = specialty {
+            update_data.insert("specialty".to_string(), json!(specialty.as_str()));
         }
         if let Some(experience) = request.years_experience {
             update_data.insert("years_experience".to_string(), json!(experience));
@@ -191,7 +212,7 @@ impl DoctorService {
 
         // Add filters
         if let Some(specialty) = filters.specialty {
-            query_parts.push(format!("specialty=ilike.%{}%", specialty));
+            query_parts.push(Self::specialty_query_filter(&specialty));
         }
         if let Some(min_exp) = filters.min_experience {
             query_parts.push(format!("years_experience=gte.{}", min_exp));
@@ -223,7 +244,7 @@ impl DoctorService {
         ).await?;
 
         let doctors: Vec<Doctor> = result.into_iter()
-            .map(|doc| serde_json::from_value(doc))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<Doctor>, _>>()?;
 
         Ok(doctors)
@@ -246,7 +267,7 @@ impl DoctorService {
         ).await?;
 
         let specialties: Vec<DoctorSpecialty> = result.into_iter()
-            .map(|spec| serde_json::from_value(spec))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<DoctorSpecialty>, _>>()?;
 
         Ok(specialties)
@@ -261,6 +282,9 @@ impl DoctorService {
     ) -> Result<DoctorSpecialty> {
         debug!("Adding specialty to doctor: {}", doctor_id);
 
+        let specialty_name = Self::normalize_specialty(&request.specialty_name)
+            .ok_or_else(|| anyhow!("Unknown specialty: {}", request.specialty_name))?;
+
         // If this is marked as primary, unmark other primary specialties
         if request.is_primary.unwrap_or(false) {
             let update_path = format!("/rest/v1/doctor_specialties?doctor_id=eq.{}", doctor_id);
@@ -279,7 +303,7 @@ impl DoctorService {
 
         let specialty_data = json!({
             "doctor_id": doctor_id,
-            "specialty_name": request.specialty_name,
+            "specialty_name": specialty_name.as_str(),
             "sub_specialty": request.sub_specialty,
             "certification_number": request.certification_number,
             "certification_date": request.certification_date,
@@ -412,6 +436,89 @@ impl DoctorService {
         })
     }
 
+    /// Booked minutes vs. scheduled minutes between `from` and `to` (inclusive).
+    /// Cancelled, no-show and rescheduled appointments don't count as booked.
+    pub async fn utilization(
+        &self,
+        doctor_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+        auth_token: &str,
+    ) -> Result<DoctorUtilization> {
+        debug!("Computing utilization for doctor {} from {} to {}", doctor_id, from, to);
+
+        if to < from {
+            return Err(anyhow!("'to' must not be before 'from'"));
+        }
+        if (to - from).num_days() >= MAX_UTILIZATION_RANGE_DAYS {
+            return Err(anyhow!("Utilization range cannot exceed {} days", MAX_UTILIZATION_RANGE_DAYS));
+        }
+
+        let doctor = self.get_doctor(doctor_id, auth_token).await?;
+
+        // Denominator: theoretical slots from the doctor's schedule, overrides applied
+        let availability_service = AvailabilityService::new(&self.config);
+        let mut available_minutes: i64 = 0;
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let slots = availability_service.get_available_slots(
+                doctor_id,
+                AvailabilityQueryRequest {
+                    date,
+                    timezone: None,
+                    appointment_type: None,
+                    duration_minutes: None,
+                },
+                auth_token,
+            ).await?;
+
+            available_minutes += slots.iter().map(|slot| slot.duration_minutes as i64).sum::<i64>();
+        }
+
+        // Numerator: booked appointments in the same window
+        let range_start = from.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let range_end = (to + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let appointments_path = format!(
+            "/rest/v1/appointments?doctor_id=eq.{}&status=in.(pending,confirmed,in_progress,completed)&scheduled_start_time=gte.{}&scheduled_start_time=lt.{}&select=appointment_type,duration_minutes",
+            doctor_id,
+            range_start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            range_end.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        let appointments: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &appointments_path,
+            Some(auth_token),
+            None,
+        ).await?;
+
+        let mut by_appointment_type: HashMap<String, AppointmentTypeUtilization> = HashMap::new();
+        for appointment in &appointments {
+            let appointment_type = appointment["appointment_type"].as_str().unwrap_or("unknown").to_string();
+            let minutes = appointment["duration_minutes"].as_i64().unwrap_or(0);
+
+            let entry = by_appointment_type.entry(appointment_type).or_default();
+            entry.appointment_count += 1;
+            entry.booked_minutes += minutes;
+        }
+
+        let booked_minutes: i64 = by_appointment_type.values().map(|t| t.booked_minutes).sum();
+        let utilization_rate = if available_minutes > 0 {
+            booked_minutes as f64 / available_minutes as f64
+        } else {
+            0.0
+        };
+
+        Ok(DoctorUtilization {
+            doctor_id: doctor.id,
+            from,
+            to,
+            available_minutes,
+            booked_minutes,
+            utilization_rate,
+            appointment_count: appointments.len() as i32,
+            by_appointment_type,
+        })
+    }
+
     /// Verify doctor (admin only)
     pub async fn verify_doctor(
         &self,
@@ -480,6 +587,27 @@ impl DoctorService {
     }
 
     /// Helper function to validate timezone
+    /// Map free-text specialty input onto the canonical registry
+    pub fn normalize_specialty(specialty: &str) -> Option<Specialty> {
+        Specialty::parse(specialty)
+    }
+
+    /// PostgREST filter for a specialty search. Known specialties match the
+    /// canonical name or any alias (rows written before normalization may
+    /// still hold an alias); unknown text falls back to a substring match.
+    fn specialty_query_filter(specialty: &str) -> String {
+        match Self::normalize_specialty(specialty) {
+            Some(canonical) => {
+                let conditions: Vec<String> = std::iter::once(canonical.as_str())
+                    .chain(canonical.aliases().iter().copied())
+                    .map(|name| format!("specialty.ilike.\"{}\"", name))
+                    .collect();
+                format!("or=({})", conditions.join(","))
+            }
+            None => format!("specialty=ilike.%{}%", specialty),
+        }
+    }
+
     fn is_valid_timezone(&self, timezone: &str) -> bool {
         // Basic timezone validation - in production, use a proper timezone library
         let valid_timezones = [
@@ -512,7 +640,7 @@ impl DoctorService {
 
             // Apply filters
             if let Some(specialty) = filters.specialty {
-                query_parts.push(format!("specialty=ilike.%{}%", specialty));
+                query_parts.push(Self::specialty_query_filter(&specialty));
             }
             if let Some(min_rating) = filters.min_rating {
                 query_parts.push(format!("rating=gte.{}", min_rating));
@@ -543,7 +671,7 @@ impl DoctorService {
             })?;
 
             let doctors: Vec<Doctor> = result.into_iter()
-                .map(|doc| serde_json::from_value(doc))
+                .map(serde_json::from_value)
                 .collect::<Result<Vec<Doctor>, _>>()
                 .map_err(|e| {
                     error!("Failed to parse doctors: {}", e);
@@ -604,7 +732,7 @@ impl DoctorService {
             })?;
 
             let specialties: Vec<DoctorSpecialty> = result.into_iter()
-                .map(|spec| serde_json::from_value(spec))
+                .map(serde_json::from_value)
                 .collect::<Result<Vec<DoctorSpecialty>, _>>()
                 .map_err(|e| {
                     error!("Failed to parse specialties: {}", e);
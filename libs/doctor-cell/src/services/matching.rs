@@ -1,19 +1,27 @@
 // libs/doctor-cell/src/services/matching.rs
-use chrono::{NaiveDate, NaiveTime};
+use std::collections::BTreeSet;
+
+use chrono::{Duration, NaiveDate, NaiveTime};
 use reqwest::Method;
 use serde_json::{Value};
-use tracing::{debug, info, error};
+use tracing::{debug, info, error, warn};
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 
 use crate::models::{
     Doctor, DoctorMatch, DoctorMatchingRequest, AvailableSlot,
-    DoctorSearchFilters, AvailabilityQueryRequest, DoctorError
+    DoctorSearchFilters, AvailabilityQueryRequest, AvailabilityRangeRequest, DoctorError
 };
 use crate::services::doctor::DoctorService;
 use crate::services::availability::AvailabilityService;
 
+/// How far past the requested date `next_available_dates_for_specialty` looks
+pub const SPECIALTY_LOOKAHEAD_DAYS: i64 = 14;
+
+/// Doctors of the specialty whose schedules are read for the lookahead
+const SPECIALTY_LOOKAHEAD_DOCTORS: i32 = 20;
+
 pub struct DoctorMatchingService {
     supabase: SupabaseClient,
     doctor_service: DoctorService,
@@ -62,12 +70,7 @@ impl DoctorMatchingService {
         };
 
         // Search for potentially matching doctors
-        let candidate_doctors = self.doctor_service.search_doctors(
-            search_filters,
-            auth_token,
-            Some(50),
-            None,
-        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+        let candidate_doctors = self.search_verified_doctors(search_filters, auth_token, Some(50)).await?;
 
         if candidate_doctors.is_empty() {
             if let Some(ref specialty) = request.specialty_required {
@@ -95,8 +98,12 @@ impl DoctorMatchingService {
 
         // **CRITICAL: Check if we have any matches with required specialty**
         if let Some(ref required_specialty) = request.specialty_required {
+            let required = DoctorService::normalize_specialty(required_specialty);
             let specialty_matches = doctor_matches.iter()
-                .filter(|m| m.doctor.specialty.to_lowercase().contains(&required_specialty.to_lowercase()))
+                .filter(|m| match required {
+                    Some(required) => DoctorService::normalize_specialty(&m.doctor.specialty) == Some(required),
+                    None => m.doctor.specialty.to_lowercase().contains(&required_specialty.to_lowercase()),
+                })
                 .count();
             
             if specialty_matches == 0 {
@@ -136,6 +143,7 @@ impl DoctorMatchingService {
     }
 
     /// Find doctors with theoretical availability at a specific time
+    #[allow(clippy::too_many_arguments)]
     pub async fn find_theoretically_available_doctors(
         &self,
         date: NaiveDate,
@@ -168,12 +176,7 @@ impl DoctorMatchingService {
             is_verified_only: Some(true),
         };
 
-        let doctors = self.doctor_service.search_doctors(
-            search_filters,
-            auth_token,
-            None,
-            None,
-        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+        let doctors = self.search_verified_doctors(search_filters, auth_token, None).await?;
 
         if doctors.is_empty() && specialty_filter.is_some() {
             if let Some(ref specialty) = specialty_filter {
@@ -225,6 +228,63 @@ impl DoctorMatchingService {
         Ok(available_doctors)
     }
 
+    /// The first dates after `after`, up to `SPECIALTY_LOOKAHEAD_DAYS` ahead, on
+    /// which a verified doctor of the specialty has a theoretical slot, earliest
+    /// first. Bookings aren't checked, so a date is a lead for the patient to
+    /// try rather than a guarantee. Doctors whose schedule can't be read are
+    /// skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn next_available_dates_for_specialty(
+        &self,
+        specialty: &str,
+        after: NaiveDate,
+        appointment_type: &str,
+        duration_minutes: i32,
+        timezone: &str,
+        max_dates: usize,
+        auth_token: &str,
+    ) -> Result<Vec<NaiveDate>, DoctorError> {
+        let doctors = self.specialty_doctors(specialty, auth_token).await?;
+
+        let mut dates = BTreeSet::new();
+        for doctor in doctors {
+            let range = AvailabilityRangeRequest {
+                from: after + Duration::days(1),
+                to: after + Duration::days(SPECIALTY_LOOKAHEAD_DAYS),
+                timezone: Some(timezone.to_string()),
+                appointment_type: Some(appointment_type.to_string()),
+                duration_minutes: Some(duration_minutes),
+            };
+            match self.availability_service.get_available_slots_range(&doctor.id.to_string(), range, Some(auth_token)).await {
+                Ok(days) => dates.extend(days.into_iter()
+                    .filter(|day| !day.available_slots.is_empty())
+                    .map(|day| day.date)),
+                Err(e) => warn!("Leaving doctor {} out of the {} lookahead: {}", doctor.id, specialty, e),
+            }
+        }
+
+        Ok(dates.into_iter().take(max_dates).collect())
+    }
+
+    /// Verified doctors of `specialty` whose schedules are searched when
+    /// looking ahead for availability, at most `SPECIALTY_LOOKAHEAD_DOCTORS`
+    pub async fn specialty_doctors(
+        &self,
+        specialty: &str,
+        auth_token: &str,
+    ) -> Result<Vec<Doctor>, DoctorError> {
+        let specialty = DoctorService::normalize_specialty(specialty).ok_or_else(|| {
+            DoctorError::ValidationError(format!("Unknown specialty: {}", specialty))
+        })?;
+
+        let filters = DoctorSearchFilters {
+            specialty: Some(specialty.as_str().to_string()),
+            is_verified_only: Some(true),
+            ..Default::default()
+        };
+        self.search_verified_doctors(filters, auth_token, Some(SPECIALTY_LOOKAHEAD_DOCTORS)).await
+    }
+
     /// Get recommended doctors based on patient history and preferences
     pub async fn get_recommended_doctors(
         &self,
@@ -257,12 +317,7 @@ impl DoctorMatchingService {
             is_verified_only: Some(true),
         };
 
-        let candidate_doctors = self.doctor_service.search_doctors(
-            search_filters,
-            auth_token,
-            Some(20),
-            None,
-        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+        let candidate_doctors = self.search_verified_doctors(search_filters, auth_token, Some(20)).await?;
 
         if candidate_doctors.is_empty() && specialty.is_some() {
             if let Some(ref specialty_name) = specialty {
@@ -303,6 +358,27 @@ impl DoctorMatchingService {
     // PRIVATE HELPER METHODS
     // ==============================================================================
 
+    /// Candidate search for matching. Unverified doctors are never matched,
+    /// whatever the filters say.
+    async fn search_verified_doctors(
+        &self,
+        mut filters: DoctorSearchFilters,
+        auth_token: &str,
+        limit: Option<i32>,
+    ) -> Result<Vec<Doctor>, DoctorError> {
+        filters.is_verified_only = Some(true);
+
+        let mut doctors = self.doctor_service.search_doctors(
+            filters,
+            auth_token,
+            limit,
+            None,
+        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+
+        doctors.retain(|doctor| doctor.is_verified);
+        Ok(doctors)
+    }
+
     /// **NEW: Validate that doctors with the required specialty are available**
     async fn validate_specialty_availability(
         &self,
@@ -311,8 +387,12 @@ impl DoctorMatchingService {
     ) -> Result<(), DoctorError> {
         debug!("Validating specialty availability: {}", required_specialty);
 
+        let specialty = DoctorService::normalize_specialty(required_specialty).ok_or_else(|| {
+            DoctorError::ValidationError(format!("Unknown specialty: {}", required_specialty))
+        })?;
+
         let specialty_check_filters = DoctorSearchFilters {
-            specialty: Some(required_specialty.to_string()),
+            specialty: Some(specialty.as_str().to_string()),
             sub_specialty: None,
             min_experience: None,
             min_rating: None,
@@ -631,12 +711,10 @@ impl DoctorMatchingService {
             DoctorError::ValidationError(format!("Failed to retrieve patient info for {}: {}", patient_id, e))
         })?;
 
-        if result.is_empty() {
+        let patient_info = result.into_iter().next().ok_or_else(|| {
             error!("Patient not found with ID: {}", patient_id);
-            return Err(DoctorError::NotFound);
-        }
-
-        let patient_info = result.into_iter().next().unwrap();
+            DoctorError::NotFound
+        })?;
         debug!("Successfully retrieved patient info for: {}", patient_id);
         
         Ok(patient_info)
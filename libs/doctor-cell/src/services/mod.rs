@@ -1,3 +1,7 @@
 pub mod doctor;
 pub mod availability;
-pub mod matching;
\ No newline at end of file
+pub mod availability_cache;
+pub mod matching;
+pub mod review;
+pub mod calendar;
+pub mod verification;
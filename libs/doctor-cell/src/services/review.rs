@@ -0,0 +1,177 @@
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::{is_conflict, SupabaseClient};
+
+use crate::models::{DoctorError, DoctorReview};
+
+pub const MIN_REVIEW_RATING: i32 = 1;
+pub const MAX_REVIEW_RATING: i32 = 5;
+
+/// Patient reviews of completed appointments. Every accepted review
+/// recomputes the doctor's aggregate `rating` and `total_consultations` used
+/// by the matching score.
+pub struct ReviewService {
+    supabase: SupabaseClient,
+}
+
+impl ReviewService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+        }
+    }
+
+    /// Submit a review for a completed appointment the patient attended
+    pub async fn submit_review(
+        &self,
+        doctor_id: Uuid,
+        appointment_id: Uuid,
+        patient_id: Uuid,
+        stars: i32,
+        comment: Option<String>,
+        auth_token: &str,
+    ) -> Result<DoctorReview, DoctorError> {
+        debug!("Submitting review for appointment {} by patient {}", appointment_id, patient_id);
+
+        if !(MIN_REVIEW_RATING..=MAX_REVIEW_RATING).contains(&stars) {
+            return Err(DoctorError::ValidationError(format!(
+                "Rating must be between {} and {} stars", MIN_REVIEW_RATING, MAX_REVIEW_RATING
+            )));
+        }
+
+        let appointment = self.get_appointment(appointment_id, auth_token).await?;
+
+        if appointment["patient_id"].as_str() != Some(patient_id.to_string().as_str()) {
+            return Err(DoctorError::UnauthorizedAccess);
+        }
+
+        if appointment["doctor_id"].as_str() != Some(doctor_id.to_string().as_str()) {
+            return Err(DoctorError::ValidationError(
+                "Appointment does not belong to this doctor".to_string()
+            ));
+        }
+
+        // No-shows and cancellations never reach `completed`, so this also
+        // guarantees the patient attended
+        if appointment["status"].as_str() != Some("completed") {
+            return Err(DoctorError::ValidationError(
+                "Only completed appointments can be reviewed".to_string()
+            ));
+        }
+
+        let review_data = json!({
+            "doctor_id": doctor_id,
+            "appointment_id": appointment_id,
+            "patient_id": patient_id,
+            "rating": stars,
+            "comment": comment,
+            "created_at": Utc::now().to_rfc3339()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        // doctor_reviews is unique on appointment_id, so a second review of
+        // the same appointment is rejected even when two submissions race
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/doctor_reviews",
+            Some(auth_token),
+            Some(review_data),
+            Some(headers),
+        ).await.map_err(|e| {
+            if is_conflict(&e) {
+                return DoctorError::Conflict("This appointment has already been reviewed".to_string());
+            }
+            error!("Failed to store review for appointment {}: {}", appointment_id, e);
+            DoctorError::DatabaseError(e.to_string())
+        })?;
+
+        let review: DoctorReview = result.into_iter().next()
+            .ok_or_else(|| DoctorError::DatabaseError("Failed to store review".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| DoctorError::DatabaseError(format!("Failed to parse review: {}", e))))?;
+
+        // The review is stored either way; a missed refresh is corrected by the next one
+        if let Err(e) = self.refresh_doctor_rating(doctor_id, auth_token).await {
+            warn!("Failed to refresh aggregate rating for doctor {}: {}", doctor_id, e);
+        }
+
+        info!("Review {} stored for doctor {}", review.id, doctor_id);
+        Ok(review)
+    }
+
+    /// List reviews for a doctor, newest first
+    pub async fn get_doctor_reviews(
+        &self,
+        doctor_id: Uuid,
+        auth_token: Option<&str>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<DoctorReview>, DoctorError> {
+        debug!("Fetching reviews for doctor: {}", doctor_id);
+
+        let mut path = format!("/rest/v1/doctor_reviews?doctor_id=eq.{}&order=created_at.desc", doctor_id);
+        if let Some(limit) = limit {
+            path.push_str(&format!("&limit={}", limit));
+        }
+        if let Some(offset) = offset {
+            path.push_str(&format!("&offset={}", offset));
+        }
+
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            auth_token,
+            None,
+        ).await.map_err(|e| DoctorError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<DoctorReview>, _>>()
+            .map_err(|e| DoctorError::DatabaseError(format!("Failed to parse reviews: {}", e)))
+    }
+
+    // ==============================================================================
+    // PRIVATE HELPER METHODS
+    // ==============================================================================
+
+    async fn get_appointment(&self, appointment_id: Uuid, auth_token: &str) -> Result<Value, DoctorError> {
+        let path = format!(
+            "/rest/v1/appointments?id=eq.{}&select=id,patient_id,doctor_id,status",
+            appointment_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| DoctorError::DatabaseError(e.to_string()))?;
+
+        result.into_iter().next()
+            .ok_or(DoctorError::NotFound)
+    }
+
+    /// Have the database recompute `rating` from all of the doctor's reviews
+    /// and `total_consultations` from their completed appointments. It runs
+    /// server-side so patients never need to read other patients' reviews or
+    /// write to the doctors table. The function returns void, so PostgREST
+    /// answers with an empty 204.
+    pub async fn refresh_doctor_rating(&self, doctor_id: Uuid, auth_token: &str) -> Result<(), DoctorError> {
+        let _: Value = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/rpc/refresh_doctor_rating",
+            Some(auth_token),
+            Some(json!({ "p_doctor_id": doctor_id })),
+            None,
+        ).await.map_err(|e| DoctorError::DatabaseError(e.to_string()))?;
+
+        debug!("Doctor {} rating refreshed", doctor_id);
+        Ok(())
+    }
+}
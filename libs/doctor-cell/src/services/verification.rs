@@ -0,0 +1,281 @@
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use health_profile_cell::api::DocumentService;
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+use shared_models::auth::User;
+
+use crate::models::{
+    Doctor, DoctorError, DoctorVerification, ReviewVerificationRequest,
+    SubmitVerificationRequest, VerificationStatus,
+};
+use crate::services::doctor::DoctorService;
+
+/// Doctor onboarding: the doctor submits their license and supporting
+/// documents, an admin approves or rejects the submission, and the decision
+/// sets the doctor's `is_verified` flag. Every step lands in the audit log.
+pub struct VerificationService {
+    supabase: SupabaseClient,
+    doctor_service: DoctorService,
+    document_service: DocumentService,
+    audit_log: AuditLog,
+}
+
+impl VerificationService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            doctor_service: DoctorService::new(config),
+            document_service: DocumentService::new(config),
+            audit_log: AuditLog::new(config),
+        }
+    }
+
+    /// Submit credentials for review. A doctor can only have one submission
+    /// awaiting review at a time.
+    pub async fn submit_verification(
+        &self,
+        doctor_id: Uuid,
+        request: SubmitVerificationRequest,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<DoctorVerification, DoctorError> {
+        debug!("Doctor {} submitting credentials for verification", doctor_id);
+
+        let license_number = request.license_number.trim();
+        if license_number.is_empty() {
+            return Err(DoctorError::ValidationError("License number is required".to_string()));
+        }
+        if request.documents.is_empty() {
+            return Err(DoctorError::ValidationError(
+                "At least one credential document is required".to_string()
+            ));
+        }
+
+        self.doctor_service.get_doctor(&doctor_id.to_string(), auth_token).await
+            .map_err(|_| DoctorError::NotFound)?;
+
+        let pending_path = format!(
+            "/rest/v1/doctor_verifications?doctor_id=eq.{}&status=eq.pending&select=id",
+            doctor_id
+        );
+        let pending: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &pending_path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+
+        if !pending.is_empty() {
+            return Err(DoctorError::ValidationError(
+                "A verification request is already awaiting review".to_string()
+            ));
+        }
+
+        let mut document_ids = Vec::with_capacity(request.documents.len());
+        for document in request.documents {
+            let stored = self.document_service.upload_document(
+                &doctor_id.to_string(),
+                &document.title,
+                document.file_data,
+                &document.file_type,
+                auth_token,
+            ).await.map_err(|e| {
+                error!("Failed to store credential document for doctor {}: {}", doctor_id, e);
+                DoctorError::ValidationError(format!("Failed to store document '{}': {}", document.title, e))
+            })?;
+            document_ids.push(stored.id);
+        }
+
+        let verification = DoctorVerification {
+            id: Uuid::new_v4(),
+            doctor_id,
+            license_number: license_number.to_string(),
+            issuing_authority: request.issuing_authority,
+            document_ids,
+            status: VerificationStatus::Pending,
+            submitted_at: Utc::now(),
+            reviewed_by: None,
+            reviewed_at: None,
+            rejection_reason: None,
+        };
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/doctor_verifications",
+            Some(auth_token),
+            Some(json!(verification)),
+            Some(headers),
+        ).await.map_err(|e| {
+            error!("Failed to store verification request for doctor {}: {}", doctor_id, e);
+            DoctorError::ValidationError(e.to_string())
+        })?;
+
+        let verification = parse_verification(result.into_iter().next())?;
+
+        self.audit(user, "doctor.verification_submitted", &verification, auth_token).await;
+        info!("Doctor {} submitted verification request {}", doctor_id, verification.id);
+
+        Ok(verification)
+    }
+
+    /// The doctor's most recent submission, if they have made one
+    pub async fn latest_verification(
+        &self,
+        doctor_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Option<DoctorVerification>, DoctorError> {
+        let path = format!(
+            "/rest/v1/doctor_verifications?doctor_id=eq.{}&order=submitted_at.desc&limit=1",
+            doctor_id
+        );
+        let result = self.fetch_verifications(&path, auth_token).await?;
+        Ok(result.into_iter().next())
+    }
+
+    /// Submissions awaiting review, oldest first
+    pub async fn pending_verifications(
+        &self,
+        auth_token: &str,
+    ) -> Result<Vec<DoctorVerification>, DoctorError> {
+        let path = "/rest/v1/doctor_verifications?status=eq.pending&order=submitted_at.asc";
+        self.fetch_verifications(path, auth_token).await
+    }
+
+    /// Approve or reject a pending submission and update the doctor's
+    /// `is_verified` flag to match
+    pub async fn review_verification(
+        &self,
+        verification_id: Uuid,
+        request: ReviewVerificationRequest,
+        reviewer: &User,
+        auth_token: &str,
+    ) -> Result<(DoctorVerification, Doctor), DoctorError> {
+        let reason = request.reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+        if !request.approve && reason.is_none() {
+            return Err(DoctorError::ValidationError(
+                "A reason is required when rejecting a verification request".to_string()
+            ));
+        }
+
+        let reviewer_id = Uuid::parse_str(&reviewer.id)
+            .map_err(|_| DoctorError::ValidationError("Invalid reviewer ID".to_string()))?;
+
+        let path = format!("/rest/v1/doctor_verifications?id=eq.{}", verification_id);
+        let existing = self.fetch_verifications(&path, auth_token).await?
+            .into_iter()
+            .next()
+            .ok_or(DoctorError::NotFound)?;
+
+        if existing.status != VerificationStatus::Pending {
+            return Err(DoctorError::ValidationError(
+                "Verification request has already been reviewed".to_string()
+            ));
+        }
+
+        let status = if request.approve {
+            VerificationStatus::Approved
+        } else {
+            VerificationStatus::Rejected
+        };
+        let update_data = json!({
+            "status": status,
+            "reviewed_by": reviewer_id,
+            "reviewed_at": Utc::now().to_rfc3339(),
+            "rejection_reason": if request.approve { None } else { reason },
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        // Only a still-pending row is updated, so two admins deciding at once
+        // can't both win
+        let update_path = format!(
+            "/rest/v1/doctor_verifications?id=eq.{}&status=eq.pending",
+            verification_id
+        );
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::PATCH,
+            &update_path,
+            Some(auth_token),
+            Some(update_data),
+            Some(headers),
+        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+
+        if result.is_empty() {
+            return Err(DoctorError::ValidationError(
+                "Verification request has already been reviewed".to_string()
+            ));
+        }
+        let verification = parse_verification(result.into_iter().next())?;
+
+        let doctor = self.doctor_service.verify_doctor(
+            &verification.doctor_id.to_string(),
+            request.approve,
+            auth_token,
+        ).await.map_err(|e| {
+            error!("Failed to update verification flag of doctor {}: {}", verification.doctor_id, e);
+            DoctorError::ValidationError(e.to_string())
+        })?;
+
+        let action = if request.approve {
+            "doctor.verification_approved"
+        } else {
+            "doctor.verification_rejected"
+        };
+        self.audit(reviewer, action, &verification, auth_token).await;
+        info!(
+            "Verification request {} for doctor {} {:?} by {}",
+            verification.id, verification.doctor_id, verification.status, reviewer.id
+        );
+
+        Ok((verification, doctor))
+    }
+
+    async fn fetch_verifications(
+        &self,
+        path: &str,
+        auth_token: &str,
+    ) -> Result<Vec<DoctorVerification>, DoctorError> {
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| DoctorError::ValidationError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<DoctorVerification>, _>>()
+            .map_err(|e| DoctorError::ValidationError(format!("Failed to parse verification requests: {}", e)))
+    }
+
+    async fn audit(&self, user: &User, action: &str, verification: &DoctorVerification, auth_token: &str) {
+        let audit_entry = AuditEntry::new(user.id.clone(), action, "doctor", verification.doctor_id.to_string())
+            .with_details(json!({
+                "verification_id": verification.id,
+                "status": verification.status,
+                "rejection_reason": verification.rejection_reason,
+            }));
+
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit {} for doctor {}: {}", action, verification.doctor_id, e);
+        }
+    }
+}
+
+fn parse_verification(row: Option<Value>) -> Result<DoctorVerification, DoctorError> {
+    row.ok_or_else(|| DoctorError::ValidationError("Failed to store verification request".to_string()))
+        .and_then(|value| serde_json::from_value(value)
+            .map_err(|e| DoctorError::ValidationError(format!("Failed to parse verification request: {}", e))))
+}
@@ -10,7 +10,7 @@ use axum::{
 use tower::ServiceExt;
 use serde_json::json;
 use wiremock::{MockServer, Mock, ResponseTemplate};
-use wiremock::matchers::{method, path, query_param, query_param_contains};
+use wiremock::matchers::{method, path, header, query_param, query_param_contains};
 
 use doctor_cell::router::doctor_routes;
 use shared_config::AppConfig;
@@ -180,7 +180,7 @@ async fn test_get_doctor_public() {
 
     let request = Request::builder()
         .method("GET")
-        .uri(&format!("/{}", doctor_id))
+        .uri(format!("/{}", doctor_id))
         .body(Body::empty())
         .unwrap();
 
@@ -215,7 +215,7 @@ async fn test_get_doctor_availability_public() {
 
     let request = Request::builder()
         .method("GET")
-        .uri(&format!("/{}/availability?date=2024-01-01", doctor_id))
+        .uri(format!("/{}/availability?date=2024-01-01", doctor_id))
         .body(Body::empty())
         .unwrap();
 
@@ -231,7 +231,7 @@ async fn test_create_doctor_success() {
     config.supabase_url = mock_server.uri();
     
     let doctor_id = Uuid::new_v4().to_string();
-    let unique_email = format!("newdoc{}@example.com", Uuid::new_v4().to_string().replace("-", "")[..8].to_string());
+    let unique_email = format!("newdoc{}@example.com", &Uuid::new_v4().to_string().replace("-", "")[..8]);
     
     // Mock email check (no existing doctor with this email)
     Mock::given(method("GET"))
@@ -297,7 +297,7 @@ async fn test_update_doctor_success() {
 
     let request = Request::builder()
         .method("PUT")
-        .uri(&format!("/{}", doctor_id))
+        .uri(format!("/{}", doctor_id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(request_body.to_string()))
@@ -355,7 +355,7 @@ async fn test_create_availability_success() {
 
     let request = Request::builder()
         .method("POST")
-        .uri(&format!("/{}/availability", doctor_id))
+        .uri(format!("/{}/availability", doctor_id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(request_body.to_string()))
@@ -385,7 +385,7 @@ async fn test_verify_doctor_success() {
 
     let request = Request::builder()
         .method("PATCH")
-        .uri(&format!("/{}/verify", doctor_id))
+        .uri(format!("/{}/verify", doctor_id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(request_body.to_string()))
@@ -508,4 +508,377 @@ async fn test_public_endpoints_accessible() {
         let response = app.oneshot(request).await.unwrap();
         assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
     }
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_create_availability_with_malformed_existing_row() {
+    let mock_server = MockServer::start().await;
+    let test_config = TestConfig::default();
+    let mut config = test_config.to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_id = Uuid::new_v4().to_string();
+
+    // Existing availability row is missing its time columns
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_availabilities"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": Uuid::new_v4().to_string(), "start_time": null }
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let app = create_test_app(config.clone()).await;
+
+    let doctor_user = TestUser {
+        id: doctor_id.clone(),
+        email: "doctor@example.com".to_string(),
+        role: "doctor".to_string(),
+    };
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, None);
+
+    let request_body = json!({
+        "day_of_week": 1,
+        "start_time": "09:00:00",
+        "end_time": "17:00:00",
+        "duration_minutes": 30,
+        "timezone": "UTC",
+        "appointment_type": "consultation"
+    });
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/availability", doctor_id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(request_body.to_string()))
+        .unwrap();
+
+    // A bad row must surface as a handled error rather than a panic
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "internal");
+    assert!(json["detail"].as_str().unwrap().contains("missing start_time"));
+}
+
+#[tokio::test]
+async fn test_calendar_feed_requires_valid_token() {
+    let mock_server = MockServer::start().await;
+    let test_config = TestConfig::default();
+    let mut config = test_config.to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_user = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, None);
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_user.id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "patient_id": Uuid::new_v4(),
+            "appointment_type": "general_consultation",
+            "status": "confirmed",
+            "timezone": "UTC",
+            "scheduled_start_time": "2030-01-01T09:00:00Z",
+            "scheduled_end_time": "2030-01-01T09:30:00Z",
+            "video_conference_link": null,
+            "updated_at": "2029-12-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/doctor_calendar_feeds"))
+        .and(query_param("on_conflict", "doctor_id"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&mock_server)
+        .await;
+
+    // The doctor issues their subscription URL with a normal bearer token
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/calendar-feed", doctor_user.id))
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app(config.clone()).await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let feed_path = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["feed_path"]
+        .as_str().unwrap()
+        .trim_start_matches("/doctors")
+        .to_string();
+    let feed_token = feed_path.rsplit('=').next().unwrap().to_string();
+
+    // Only the hash is stored, never the token itself
+    let requests = mock_server.received_requests().await.unwrap();
+    let stored: serde_json::Value = requests.iter()
+        .find(|r| r.url.path() == "/rest/v1/doctor_calendar_feeds")
+        .unwrap()
+        .body_json()
+        .unwrap();
+    let token_hash = stored["token_hash"].as_str().unwrap().to_string();
+    assert_eq!(token_hash.len(), 64);
+    assert!(!stored.to_string().contains(&feed_token));
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctor_calendar_feeds"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_user.id)))
+        .and(query_param("token_hash", format!("eq.{}", token_hash)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "doctor_id": doctor_user.id }])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctor_calendar_feeds"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    // The calendar app subscribes with only the feed URL
+    let request = Request::builder()
+        .method("GET")
+        .uri(&feed_path)
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app(config.clone()).await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/calendar; charset=utf-8");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8(body.to_vec()).unwrap().contains("DTSTART:20300101T090000Z"));
+
+    // A token for another doctor is rejected
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/{}/calendar.ics?token={}", Uuid::new_v4(), feed_token))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app(config.clone()).await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // As is a token that was never issued, e.g. one replaced by a rotation
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/{}/calendar.ics?token=cal_stale", doctor_user.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app(config.clone()).await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Another doctor can't revoke this feed
+    let other_doctor = TestUser::doctor("other@example.com");
+    let other_token = JwtTestUtils::create_test_token(&other_doctor, &config.supabase_jwt_secret, None);
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/{}/calendar-feed", doctor_user.id))
+        .header("authorization", format!("Bearer {}", other_token))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app(config).await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_revoke_calendar_feed() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_user = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, None);
+
+    Mock::given(method("DELETE"))
+        .and(path("/rest/v1/doctor_calendar_feeds"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_user.id)))
+        .and(header("Prefer", "return=representation"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "doctor_id": doctor_user.id }])))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/rest/v1/doctor_calendar_feeds"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    let revoke = || Request::builder()
+        .method("DELETE")
+        .uri(format!("/{}/calendar-feed", doctor_user.id))
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = create_test_app(config.clone()).await.oneshot(revoke()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Once revoked there is no feed left to revoke
+    let response = create_test_app(config).await.oneshot(revoke()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_doctor_availability_for_date_range() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_id = Uuid::new_v4().to_string();
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctors"))
+        .and(query_param("id", format!("eq.{}", doctor_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            MockSupabaseResponses::doctor_response(&doctor_id, "doctor@example.com", "Dr. Test Doctor", "General Medicine")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let schedule = |day_of_week: i32, start: &str, end: &str, specific_date: Option<&str>| json!({
+        "id": Uuid::new_v4(),
+        "doctor_id": doctor_id,
+        "day_of_week": day_of_week,
+        "start_time": start,
+        "end_time": end,
+        "duration_minutes": 30,
+        "timezone": "UTC",
+        "appointment_type": "consultation",
+        "buffer_minutes": 0,
+        "max_concurrent_appointments": 1,
+        "is_recurring": specific_date.is_none(),
+        "specific_date": specific_date,
+        "is_available": true,
+        "created_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z"
+    });
+    // Mondays and Wednesdays every week, plus one extra Friday afternoon
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_availabilities"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            schedule(1, "09:00:00", "10:00:00", None),
+            schedule(3, "09:00:00", "10:00:00", None),
+            schedule(5, "14:00:00", "15:00:00", Some("2024-12-27")),
+        ])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctor_availability_overrides"))
+        .and(query_param("and", "(override_date.gte.2024-12-23,override_date.lte.2024-12-29)"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "doctor_id": doctor_id,
+            "override_date": "2024-12-25",
+            "is_available": false,
+            "reason": "Public holiday",
+            "created_at": "2024-01-01T00:00:00Z"
+        }])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let app = create_test_app(config).await;
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/{}/availability?from=2024-12-23&to=2024-12-29", doctor_id))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let days = json["days"].as_array().unwrap();
+    let slot_counts: Vec<usize> = days.iter()
+        .map(|day| day["available_slots"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(days[0]["date"], "2024-12-23");
+    assert_eq!(slot_counts, vec![2, 0, 0, 0, 2, 0, 0]);
+    assert_eq!(days[4]["available_slots"][0]["start_time"], "2024-12-27T14:00:00Z");
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/{}/availability?from=2024-01-01&to=2024-06-01", doctor_id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_next_available_dates_for_specialty() {
+    use doctor_cell::services::matching::DoctorMatchingService;
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let token = JwtTestUtils::create_test_token(
+        &TestUser::patient("patient@example.com"), &config.supabase_jwt_secret, Some(24),
+    );
+
+    let doctor_id = Uuid::new_v4().to_string();
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctors"))
+        .and(query_param("is_verified", "eq.true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            MockSupabaseResponses::doctor_response(&doctor_id, "doctor@example.com", "Dr. Heart", "Cardiology")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    // Mondays and Wednesdays, but not Monday 23 December
+    let schedule = |day_of_week: i32| json!({
+        "id": Uuid::new_v4(),
+        "doctor_id": doctor_id,
+        "day_of_week": day_of_week,
+        "start_time": "09:00:00",
+        "end_time": "10:00:00",
+        "duration_minutes": 30,
+        "timezone": "UTC",
+        "appointment_type": "consultation",
+        "buffer_minutes": 0,
+        "max_concurrent_appointments": 1,
+        "is_recurring": true,
+        "specific_date": null,
+        "is_available": true,
+        "created_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z"
+    });
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointment_availabilities"))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([schedule(1), schedule(3)])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/doctor_availability_overrides"))
+        .and(query_param("and", "(override_date.gte.2024-12-23,override_date.lte.2025-01-05)"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "doctor_id": doctor_id,
+            "override_date": "2024-12-23",
+            "is_available": false,
+            "reason": "Leave",
+            "created_at": "2024-01-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    let service = DoctorMatchingService::new(&config);
+    let sunday = chrono::NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    let dates = service.next_available_dates_for_specialty(
+        "cardiology", sunday, "consultation", 30, "UTC", 3, &token,
+    ).await.unwrap();
+
+    let dates: Vec<String> = dates.iter().map(|date| date.to_string()).collect();
+    assert_eq!(dates, vec!["2024-12-25", "2024-12-30", "2025-01-01"]);
+
+    let unknown = service.next_available_dates_for_specialty(
+        "astrology", sunday, "consultation", 30, "UTC", 3, &token,
+    ).await;
+    assert!(unknown.is_err());
+}
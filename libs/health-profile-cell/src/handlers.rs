@@ -9,12 +9,12 @@ use headers::{Authorization, authorization::Bearer};
 use serde_json::{json, Value};
 
 use shared_config::AppConfig;
-use shared_models::auth::User;
+use shared_models::auth::{Role, User};
 use shared_models::error::AppError;
 
 use crate::services::profile::HealthProfileService;
 use crate::services::avatar::AvatarService;
-use crate::services::document::DocumentService;
+use crate::services::document::{DocumentService, DocumentUploadError};
 use crate::services::ai::AiService;
 use crate::models::{
     UpdateHealthProfile, DocumentUpload, AvatarUpload, CarePlanRequest,
@@ -33,13 +33,15 @@ pub async fn get_health_profile(
     // Get token from TypedHeader
     let token = auth.token();
     
-    // Check authorization - only allow users to access their own profile
-    if id != user.id {
-        return Err(AppError::Auth("Not authorized to access this health profile".to_string()));
-    }
-    
     // Create profile service
     let profile_service = HealthProfileService::new(&state);
+
+    // Check authorization - the patient, an admin, or a doctor treating them
+    let allowed = profile_service.authorize_profile_access(&id, &user, token).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !allowed {
+        return Err(AppError::Forbidden("Not authorized to access this health profile".to_string()));
+    }
     
     // Get health profile
     match profile_service.get_profile(&id, token).await {
@@ -84,12 +86,46 @@ pub async fn update_health_profile(
         return Err(AppError::ValidationError("Female-specific fields can only be set for female patients".to_string()));
     }
 
-    let updated_profile = profile_service.update_profile(&current_profile.id.to_string(), update_data, token).await
+    let updated_profile = profile_service.update_profile(&current_profile, update_data, &user, token).await
         .map_err(|e| AppError::Internal(e.to_string()))?;
 
     Ok(Json(json!(updated_profile)))
 }
 
+/// Field-level edit history of a patient's health profile, for clinicians
+/// and admins reviewing who changed the record and when
+#[axum::debug_handler]
+pub async fn get_health_profile_history(
+    State(state): State<Arc<AppConfig>>,
+    Path(id): Path<String>,
+    Extension(user): Extension<User>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    if !user.is_admin() && !user.has_role(Role::Doctor) {
+        return Err(AppError::Forbidden("Only doctors and administrators can view profile history".to_string()));
+    }
+
+    let profile_service = HealthProfileService::new(&state);
+
+    // Doctors additionally need a care relationship with the patient
+    let allowed = profile_service.authorize_profile_access(&id, &user, token).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !allowed {
+        return Err(AppError::Forbidden("Not authorized to access this health profile".to_string()));
+    }
+
+    let changes = profile_service.change_history(&id, token).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(json!({
+        "patient_id": id,
+        "changes": changes,
+        "total": changes.len()
+    })))
+}
+
 #[axum::debug_handler]
 pub async fn create_health_profile(
     State(state): State<Arc<AppConfig>>,
@@ -254,10 +290,16 @@ pub async fn upload_document(
     let document = document_service.upload_document(
         &id, 
         &upload.title, 
-        &upload.file_data, 
+        upload.file_data, 
         &upload.file_type, 
         token
-    ).await.map_err(|e| AppError::Internal(e.to_string()))?;
+    ).await.map_err(|e| match e.downcast_ref::<DocumentUploadError>() {
+        Some(DocumentUploadError::TooLarge { .. }) => AppError::PayloadTooLarge(e.to_string()),
+        Some(DocumentUploadError::UnsupportedType { .. } | DocumentUploadError::TypeMismatch { .. }) => {
+            AppError::UnsupportedMediaType(e.to_string())
+        }
+        None => AppError::Internal(e.to_string()),
+    })?;
     
     Ok(Json(json!(document)))
 }
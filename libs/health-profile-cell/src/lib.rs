@@ -9,7 +9,9 @@ pub use models::{
     CreateHealthProfileRequest,
     UpdateHealthProfile,
     HealthProfile,
+    ProfileChangeLog,
     Document,
+    ImagingMetadata,
     DocumentUpload,
     AvatarUpload,
     NutritionPlanRequest,
@@ -26,7 +28,7 @@ pub use handlers::*;
 pub mod api {
     pub use crate::services::profile::HealthProfileService;
     pub use crate::services::avatar::AvatarService;
-    pub use crate::services::document::DocumentService;
+    pub use crate::services::document::{upload_body_limit, DocumentService, DocumentUploadError};
     pub use crate::services::ai::AiService;
 }
 
@@ -48,6 +50,9 @@ mod tests {
             is_pregnant: None,
             is_breastfeeding: None,
             reproductive_stage: None,
+            emergency_contact_name: None,
+            emergency_contact_phone: None,
+            notify_emergency_contact: None,
         };
     }
 }
\ No newline at end of file
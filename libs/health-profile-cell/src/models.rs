@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthProfile {
@@ -17,6 +17,14 @@ pub struct HealthProfile {
     pub is_pregnant: Option<bool>,
     pub is_breastfeeding: Option<bool>,
     pub reproductive_stage: Option<String>,
+    #[serde(default)]
+    pub emergency_contact_name: Option<String>,
+    #[serde(default)]
+    pub emergency_contact_phone: Option<String>,
+    /// Opt-in: alert the emergency contact about safeguarding events such as
+    /// a missed urgent appointment. Also needs emergency contact consent.
+    #[serde(default)]
+    pub notify_emergency_contact: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -32,6 +40,27 @@ pub struct UpdateHealthProfile {
     pub is_pregnant: Option<bool>,
     pub is_breastfeeding: Option<bool>,
     pub reproductive_stage: Option<String>,
+    pub emergency_contact_name: Option<String>,
+    pub emergency_contact_phone: Option<String>,
+    pub notify_emergency_contact: Option<bool>,
+}
+
+/// One field of a health profile changed by one edit. Entries are only ever
+/// inserted, so the history shows who changed what and when for
+/// medico-legal review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileChangeLog {
+    pub id: Uuid,
+    pub profile_id: Uuid,
+    pub patient_id: Uuid,
+    /// Shared by every field changed in the same edit
+    pub change_set_id: Uuid,
+    pub changed_by: String,
+    pub changed_by_role: Option<String>,
+    pub field: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+    pub changed_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +71,23 @@ pub struct Document {
     pub file_url: String,
     pub file_type: String,
     pub uploaded_at: DateTime<Utc>,
+    /// Set for DICOM imaging studies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ImagingMetadata>,
+    /// Set when the document was attached to an appointment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub appointment_id: Option<Uuid>,
+}
+
+/// Labels for an imaging study, read from its DICOM header. Patient
+/// identifiers in the header are never read.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImagingMetadata {
+    /// DICOM modality code such as "CT", "MR" or "US"
+    pub modality: Option<String>,
+    pub study_date: Option<NaiveDate>,
+    pub body_part: Option<String>,
+    pub series_description: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,7 +115,7 @@ pub struct CarePlanRequest {
 
 /// ✅ Canonical CreateHealthProfileRequest - single source of truth
 /// This is the type that should be used throughout the codebase
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateHealthProfileRequest {
     pub patient_id: String,
     pub is_pregnant: Option<bool>,
@@ -114,16 +160,6 @@ impl CreateHealthProfileRequest {
     }
 }
 
-impl Default for CreateHealthProfileRequest {
-    fn default() -> Self {
-        Self {
-            patient_id: String::new(),
-            is_pregnant: None,
-            is_breastfeeding: None,
-            reproductive_stage: None,
-        }
-    }
-}
 
 #[cfg(test)]
 mod tests {
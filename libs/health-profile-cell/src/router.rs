@@ -1,17 +1,24 @@
+use std::convert::Infallible;
 use std::sync::Arc;
 
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
     routing::{get, post, put, delete},
     middleware,
 };
+use tower_http::limit::RequestBodyLimitLayer;
 
 use shared_config::AppConfig;
 use shared_utils::extractor::auth_middleware;
 
 use crate::handlers;
+use crate::services::document::upload_body_limit;
 
 pub fn health_profile_routes(state: Arc<AppConfig>) -> Router {
+    // Oversize uploads are refused with 413 before the body is read
+    let upload_limit = upload_body_limit(state.max_document_upload_bytes);
+
     // Protected routes
     let protected_routes = Router::new()
         // Health profile endpoints
@@ -19,6 +26,7 @@ pub fn health_profile_routes(state: Arc<AppConfig>) -> Router {
         .route("/health-profiles/{id}", put(handlers::update_health_profile))
         .route("/health-profiles", post(handlers::create_health_profile))
         .route("/health-profiles/{id}", delete(handlers::delete_health_profile))
+        .route("/health-profiles/{id}/history", get(handlers::get_health_profile_history))
         
         // Avatar endpoints
         .route("/health-profiles/{id}/avatar", post(handlers::upload_avatar))
@@ -26,7 +34,9 @@ pub fn health_profile_routes(state: Arc<AppConfig>) -> Router {
         
         // Document endpoints
         .route("/health-profiles/{id}/documents", get(handlers::get_documents))
-        .route("/health-profiles/{id}/documents", post(handlers::upload_document))
+        .route("/health-profiles/{id}/documents", post(handlers::upload_document)
+            .layer::<_, Infallible>(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(upload_limit)))
         .route("/health-profiles/{id}/documents/{doc_id}", get(handlers::get_document))
         .route("/health-profiles/{id}/documents/{doc_id}", delete(handlers::delete_document))
         
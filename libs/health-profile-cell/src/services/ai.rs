@@ -1,13 +1,12 @@
 use anyhow::{Result, anyhow};
 use reqwest::{Client, header};
 use serde_json::{json, Value};
-use tracing::{debug, error};
+use tracing::debug;
 use std::env;
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 
-use crate::models::{HealthProfile, CarePlanRequest, NutritionPlanRequest};
 
 pub struct AiService {
     openai_api_key: String,
@@ -118,7 +117,7 @@ impl AiService {
         });
         
         // Call OpenAI API
-        let response = self.http_client.post(&format!("{}/v1/chat/completions", self.openai_base_url))
+        let response = self.http_client.post(format!("{}/v1/chat/completions", self.openai_base_url))
             .header(header::AUTHORIZATION, format!("Bearer {}", self.openai_api_key))
             .header(header::CONTENT_TYPE, "application/json")
             .json(&prompt)
@@ -189,7 +188,7 @@ impl AiService {
         });
         
         // Call OpenAI API
-        let response = self.http_client.post(&format!("{}/v1/chat/completions", self.openai_base_url))
+        let response = self.http_client.post(format!("{}/v1/chat/completions", self.openai_base_url))
             .header(header::AUTHORIZATION, format!("Bearer {}", self.openai_api_key))
             .header(header::CONTENT_TYPE, "application/json")
             .json(&prompt)
@@ -215,9 +214,9 @@ impl AiService {
         let plan_data = json!({
             "patient_id": patient_id,
             "condition": condition,
-            "diet_guidance": sections.get("diet_guidance").cloned().unwrap_or_else(|| String::new()),
-            "activity_targets": sections.get("activity_targets").cloned().unwrap_or_else(|| String::new()),
-            "monitoring_instructions": sections.get("monitoring_instructions").cloned().unwrap_or_else(|| String::new()),
+            "diet_guidance": sections.get("diet_guidance").cloned().unwrap_or_else(String::new),
+            "activity_targets": sections.get("activity_targets").cloned().unwrap_or_else(String::new),
+            "monitoring_instructions": sections.get("monitoring_instructions").cloned().unwrap_or_else(String::new),
             "ai_recommendations": care_plan_text,
             "last_updated": chrono::Utc::now().to_rfc3339()
         });
@@ -2,9 +2,8 @@ use anyhow::{Result, anyhow};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use reqwest::Method;
 use serde_json::{json, Value};
-use tracing::{debug, error};
+use tracing::debug;
 use uuid::Uuid;
-use std::str::FromStr;
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
@@ -44,13 +43,13 @@ impl AvatarService {
             "png" // Default to png
         };
         
-        let filename = format!("avatars/{}/{}", patient_id, Uuid::new_v4().to_string());
+        let filename = format!("avatars/{}/{}", patient_id, Uuid::new_v4());
         
         // Upload to Supabase storage
         let path = format!("/storage/v1/object/profiles/{}", filename);
         
         // Perform upload request directly
-        let upload_result: Value = self.supabase.request(
+        let _upload_result: Value = self.supabase.request(
             Method::POST,
             &path,
             Some(auth_token),
@@ -0,0 +1,266 @@
+use chrono::NaiveDate;
+
+use crate::models::ImagingMetadata;
+
+const MODALITY: u32 = 0x0008_0060;
+const STUDY_DATE: u32 = 0x0008_0020;
+const SERIES_DESCRIPTION: u32 = 0x0008_103E;
+const BODY_PART_EXAMINED: u32 = 0x0018_0015;
+const TRANSFER_SYNTAX_UID: u32 = 0x0002_0010;
+
+const ITEM: u32 = 0xFFFE_E000;
+const ITEM_DELIMITER: u32 = 0xFFFE_E00D;
+const SEQUENCE_DELIMITER: u32 = 0xFFFE_E0DD;
+
+const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+const DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1.99";
+
+/// Longest value kept for any label; LO values are at most 64 characters
+const MAX_VALUE_LEN: usize = 64;
+
+/// Modality, study date, body part and series description from the start
+/// of a DICOM Part 10 file. Only those four elements are decoded; everything
+/// else, including the patient module, is skipped over unread. Reading stops
+/// once past the body part, so a header-sized prefix of the file is enough.
+///
+/// Returns `None` for files that aren't DICOM, use a transfer syntax this
+/// reader doesn't handle (big endian or deflated), or carry none of the
+/// labels.
+pub fn parse_imaging_metadata(buf: &[u8]) -> Option<ImagingMetadata> {
+    if buf.get(128..132)? != b"DICM" {
+        return None;
+    }
+
+    // File meta information is always explicit VR little endian
+    let mut reader = Reader { buf, pos: 132, explicit: true };
+    let mut transfer_syntax = None;
+    while reader.peek_group() == Some(0x0002) {
+        let (tag, len) = reader.header()?;
+        if tag == TRANSFER_SYNTAX_UID {
+            transfer_syntax = reader.string(len?);
+        } else {
+            reader.skip_value(len)?;
+        }
+    }
+
+    match transfer_syntax.as_deref() {
+        Some(EXPLICIT_VR_BIG_ENDIAN | DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN) => return None,
+        Some(IMPLICIT_VR_LITTLE_ENDIAN) => reader.explicit = false,
+        _ => {}
+    }
+
+    let mut metadata = ImagingMetadata::default();
+    while let Some((tag, len)) = reader.header() {
+        // Past the labels, or past the end of what we were given
+        if tag > BODY_PART_EXAMINED || len.is_some_and(|len| reader.remaining() < len as usize) {
+            break;
+        }
+        match (tag, len) {
+            (MODALITY, Some(len)) => metadata.modality = reader.string(len),
+            (STUDY_DATE, Some(len)) => {
+                metadata.study_date = reader.string(len)
+                    .and_then(|date| NaiveDate::parse_from_str(&date, "%Y%m%d").ok());
+            }
+            (SERIES_DESCRIPTION, Some(len)) => metadata.series_description = reader.string(len),
+            (BODY_PART_EXAMINED, Some(len)) => metadata.body_part = reader.string(len),
+            _ => {
+                if reader.skip_value(len).is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    (metadata != ImagingMetadata::default()).then_some(metadata)
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    explicit: bool,
+}
+
+impl Reader<'_> {
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let bytes = self.buf.get(pos..pos + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let bytes = self.buf.get(pos..pos + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.pos)
+    }
+
+    fn peek_group(&self) -> Option<u16> {
+        self.u16_at(self.pos)
+    }
+
+    /// The next element's tag and value length, `None` for an undefined length
+    fn header(&mut self) -> Option<(u32, Option<u32>)> {
+        let group = self.u16_at(self.pos)?;
+        let element = self.u16_at(self.pos + 2)?;
+        let tag = (group as u32) << 16 | element as u32;
+
+        // Items and delimiters never carry a VR
+        let len = if group == 0xFFFE || !self.explicit {
+            let len = self.u32_at(self.pos + 4)?;
+            self.pos += 8;
+            len
+        } else {
+            let vr = self.buf.get(self.pos + 4..self.pos + 6)?;
+            if matches!(
+                vr,
+                b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV"
+            ) {
+                let len = self.u32_at(self.pos + 8)?;
+                self.pos += 12;
+                len
+            } else {
+                let len = self.u16_at(self.pos + 6)? as u32;
+                self.pos += 8;
+                len
+            }
+        };
+
+        Some((tag, (len != u32::MAX).then_some(len)))
+    }
+
+    fn skip(&mut self, len: u32) -> Option<()> {
+        let end = self.pos.checked_add(len as usize)?;
+        if end > self.buf.len() {
+            return None;
+        }
+        self.pos = end;
+        Some(())
+    }
+
+    fn skip_value(&mut self, len: Option<u32>) -> Option<()> {
+        match len {
+            Some(len) => self.skip(len),
+            None => self.skip_sequence(),
+        }
+    }
+
+    /// Skip the items of an undefined-length sequence (or encapsulated
+    /// pixel data) up to and including its delimiter
+    fn skip_sequence(&mut self) -> Option<()> {
+        loop {
+            match self.header()? {
+                (SEQUENCE_DELIMITER, _) => return Some(()),
+                (ITEM, Some(len)) => self.skip(len)?,
+                (ITEM, None) => loop {
+                    let (tag, len) = self.header()?;
+                    if tag == ITEM_DELIMITER {
+                        break;
+                    }
+                    self.skip_value(len)?;
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self, len: u32) -> Option<String> {
+        let bytes = self.buf.get(self.pos..self.pos.checked_add(len as usize)?)?;
+        self.pos += bytes.len();
+
+        let value = String::from_utf8_lossy(bytes);
+        let value = value.trim_matches(|c: char| c == ' ' || c == '\0');
+        (!value.is_empty()).then(|| value.chars().take(MAX_VALUE_LEN).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(group: u16, element: u16, vr: &[u8; 2], value: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(group.to_le_bytes());
+        bytes.extend(element.to_le_bytes());
+        bytes.extend(vr);
+        if vr == b"SQ" {
+            bytes.extend([0, 0]);
+            bytes.extend((value.len() as u32).to_le_bytes());
+        } else {
+            bytes.extend((value.len() as u16).to_le_bytes());
+        }
+        bytes.extend(value);
+        bytes
+    }
+
+    fn dicom_file(transfer_syntax: &str, dataset: &[u8]) -> Vec<u8> {
+        let mut uid = transfer_syntax.as_bytes().to_vec();
+        if uid.len() % 2 == 1 {
+            uid.push(0);
+        }
+        let mut file = vec![0u8; 128];
+        file.extend(b"DICM");
+        file.extend(element(0x0002, 0x0010, b"UI", &uid));
+        file.extend(dataset);
+        file
+    }
+
+    #[test]
+    fn test_reads_labels_and_skips_patient_data() {
+        // An undefined-length sequence between the labels has to be stepped over
+        let mut referenced = element(0x0008, 0x1110, b"SQ", &[]);
+        referenced.truncate(referenced.len() - 4);
+        referenced.extend(u32::MAX.to_le_bytes());
+        referenced.extend([0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        referenced.extend(element(0x0008, 0x1150, b"UI", b"1.2.3\0"));
+        referenced.extend([0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0]);
+        referenced.extend([0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+
+        let dataset = [
+            element(0x0008, 0x0020, b"DA", b"20240315"),
+            element(0x0008, 0x0060, b"CS", b"MR"),
+            element(0x0008, 0x103E, b"LO", b"T2 AX BRAIN "),
+            referenced,
+            element(0x0010, 0x0010, b"PN", b"Doe^Jane"),
+            element(0x0018, 0x0015, b"CS", b"HEAD"),
+            element(0x0020, 0x000D, b"UI", b"1.2.840.1\0"),
+        ].concat();
+
+        let metadata = parse_imaging_metadata(&dicom_file("1.2.840.10008.1.2.1", &dataset)).unwrap();
+        assert_eq!(metadata, ImagingMetadata {
+            modality: Some("MR".to_string()),
+            study_date: NaiveDate::from_ymd_opt(2024, 3, 15),
+            body_part: Some("HEAD".to_string()),
+            series_description: Some("T2 AX BRAIN".to_string()),
+        });
+    }
+
+    #[test]
+    fn test_implicit_vr_and_truncated_files() {
+        let mut dataset = Vec::new();
+        for (element, value) in [(0x0060u16, b"CT".as_slice()), (0x103E, b"CHEST W/O".as_slice())] {
+            dataset.extend(0x0008u16.to_le_bytes());
+            dataset.extend(element.to_le_bytes());
+            dataset.extend((value.len() as u32).to_le_bytes());
+            dataset.extend(value);
+        }
+        let file = dicom_file("1.2.840.10008.1.2", &dataset);
+
+        let metadata = parse_imaging_metadata(&file).unwrap();
+        assert_eq!(metadata.modality.as_deref(), Some("CT"));
+        assert_eq!(metadata.series_description.as_deref(), Some("CHEST W/O"));
+
+        // Cut off inside the second value: what was read is still returned
+        let metadata = parse_imaging_metadata(&file[..file.len() - 3]).unwrap();
+        assert_eq!(metadata.modality.as_deref(), Some("CT"));
+        assert_eq!(metadata.series_description, None);
+    }
+
+    #[test]
+    fn test_non_dicom_and_unsupported_files() {
+        assert_eq!(parse_imaging_metadata(b"%PDF-1.7"), None);
+        let dataset = element(0x0008, 0x0060, b"CS", b"MR");
+        assert_eq!(parse_imaging_metadata(&dicom_file("1.2.840.10008.1.2.2", &dataset)), None);
+    }
+}
@@ -1,24 +1,92 @@
+use std::fmt;
+
 use anyhow::{Result, anyhow};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-use reqwest::Method;
+use futures::stream::{self, Stream};
+use reqwest::{Body, Method};
 use serde_json::{json, Value};
-use tracing::{debug, error};
+use tracing::debug;
 use uuid::Uuid;
-use std::str::FromStr;
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 
-use crate::models::Document;
+use crate::models::{Document, ImagingMetadata};
+use crate::services::dicom::parse_imaging_metadata;
+
+/// Base64 characters decoded per chunk while checking and uploading a file
+const UPLOAD_CHUNK_CHARS: usize = 64 * 1024;
+
+/// Room in an upload request body for everything besides the file itself
+const UPLOAD_ENVELOPE_BYTES: usize = 64 * 1024;
+
+/// Request body limit for an upload of a file of up to `max_file_bytes`,
+/// sent base64 encoded inside JSON
+pub fn upload_body_limit(max_file_bytes: usize) -> usize {
+    max_file_bytes.div_ceil(3) * 4 + UPLOAD_ENVELOPE_BYTES
+}
+
+/// File types accepted for upload, as detected from the file's content
+pub const ALLOWED_DOCUMENT_TYPES: [&str; 7] = [
+    "application/pdf",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/heif",
+    "image/tiff",
+    "application/dicom",
+];
+
+/// An upload refused because of the file itself, as opposed to a storage
+/// or database failure
+#[derive(Debug)]
+pub enum DocumentUploadError {
+    TooLarge { size: usize, limit: usize },
+    /// The content isn't one of `ALLOWED_DOCUMENT_TYPES`
+    UnsupportedType { detected: Option<String> },
+    /// The content is an allowed type, but not the one the client declared
+    TypeMismatch { declared: String, detected: String },
+}
+
+/// What an upload turned out to be once its content was checked
+struct CheckedUpload {
+    size: usize,
+    file_type: &'static str,
+    extension: &'static str,
+    metadata: Option<ImagingMetadata>,
+}
+
+impl fmt::Display for DocumentUploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentUploadError::TooLarge { size, limit } => {
+                write!(f, "Document is {} bytes, the limit is {} bytes", size, limit)
+            }
+            DocumentUploadError::UnsupportedType { detected: Some(detected) } => {
+                write!(f, "Documents of type {} are not accepted", detected)
+            }
+            DocumentUploadError::UnsupportedType { detected: None } => {
+                write!(f, "Document type could not be recognised")
+            }
+            DocumentUploadError::TypeMismatch { declared, detected } => {
+                write!(f, "Document was declared as {} but its content is {}", declared, detected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DocumentUploadError {}
 
 pub struct DocumentService {
     supabase: SupabaseClient,
+    max_upload_bytes: usize,
 }
 
 impl DocumentService {
     pub fn new(config: &AppConfig) -> Self {
         Self {
             supabase: SupabaseClient::new(config),
+            max_upload_bytes: config.max_document_upload_bytes,
         }
     }
     
@@ -26,7 +94,33 @@ pub async fn upload_document(
     &self, 
     patient_id: &str, 
     title: &str,
-    base64_file: &str,
+    base64_file: String,
+    file_type: &str,
+    auth_token: &str
+) -> Result<Document> {
+    self.store_upload(patient_id, None, title, base64_file, file_type, auth_token).await
+}
+
+    /// Upload a document into the patient's documents, linked to one of
+    /// their appointments so the attending doctor can find it
+    pub async fn upload_appointment_attachment(
+        &self,
+        patient_id: &str,
+        appointment_id: Uuid,
+        title: &str,
+        base64_file: String,
+        file_type: &str,
+        auth_token: &str
+    ) -> Result<Document> {
+        self.store_upload(patient_id, Some(appointment_id), title, base64_file, file_type, auth_token).await
+    }
+
+async fn store_upload(
+    &self, 
+    patient_id: &str, 
+    appointment_id: Option<Uuid>,
+    title: &str,
+    base64_file: String,
     file_type: &str,
     auth_token: &str
 ) -> Result<Document> {
@@ -41,90 +135,161 @@ pub async fn upload_document(
         return Err(anyhow!("Document title cannot be empty"));
     }
     
-    // Extract base64 data more robustly
-    let base64_data = if base64_file.contains(";base64,") {
-        base64_file.split(";base64,").nth(1).unwrap_or(base64_file)
-    } else {
-        base64_file
-    };
-    
-    // Decode base64 data to bytes with better error handling
-    let file_data = match BASE64.decode(base64_data) {
-        Ok(data) => data,
-        Err(e) => return Err(anyhow!("Failed to decode base64 data: {}", e)),
-    };
+    // Skip a data URL prefix such as "data:application/pdf;base64,"
+    let data_start = base64_file.find(";base64,")
+        .map(|index| index + ";base64,".len())
+        .unwrap_or(0);
+    let checked = self.check_upload(&base64_file.as_bytes()[data_start..], file_type)?;
     
     // Generate a unique filename
     let file_id = Uuid::new_v4().to_string();
-    let file_ext = if file_type.contains('/') {
-        file_type.split('/').last().unwrap_or("bin")
-    } else {
-        file_type
-    };
-    
-    let filename = format!("patient-documents/{}/{}.{}", patient_id, file_id, file_ext);
+    let filename = format!("patient-documents/{}/{}.{}", patient_id, file_id, checked.extension);
     
-    // Upload to Supabase storage
+    // Upload to Supabase storage, decoding as the body is sent
     let path = format!("/storage/v1/object/patient-documents/{}", filename);
     debug!("Uploading to storage path: {}", path);
     
-    // Perform upload request
-    let upload_result: Value = self.supabase.request(
-        Method::POST,
-        &path,
-        Some(auth_token),
-        Some(json!({
-            "data": file_data,
-            "contentType": file_type
-        })),
-    ).await?;
-    
-    debug!("Upload result: {:?}", upload_result);
+    let body = Body::wrap_stream(decoded_chunks(base64_file, data_start));
+    self.supabase.upload_object(&path, Some(auth_token), checked.file_type, checked.size as u64, body).await?;
     
     let storage_path = format!("/storage/v1/object/public/patient-documents/{}", filename);
     // Get public URL
     let public_url = self.supabase.get_public_url(&storage_path);
     debug!("Generated public URL: {}", public_url);
     
-    // Create document record in database
-    let doc_path = "/rest/v1/documents";
-    
-    let doc_data = json!({
-        "patient_id": patient_id,
-        "title": title,
-        "file_url": public_url,
-        "file_type": file_type,
-        "uploaded_at": chrono::Utc::now().to_rfc3339()
-    });
-    
-    // Add Prefer header for the POST request to get back the created record
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(
-        "Prefer",
-        reqwest::header::HeaderValue::from_static("return=representation")
-    );
-    
-    let doc_result: Vec<Value> = self.supabase.request_with_headers(
-        Method::POST,
-        doc_path,
-        Some(auth_token),
-        Some(doc_data),
-        Some(headers),
-    ).await?;
-    
-    if doc_result.is_empty() {
-        return Err(anyhow!("Failed to create document record"));
-    }
-    
-    // Parse document with better error handling
-    let document: Document = match serde_json::from_value(doc_result[0].clone()) {
-        Ok(doc) => doc,
-        Err(e) => return Err(anyhow!("Failed to parse document record: {}", e)),
-    };
-    
-    Ok(document)
+    // Create document record in database, with the type found in the file
+    self.insert_document(patient_id, appointment_id, title, &public_url, checked.file_type, checked.metadata, auth_token).await
 }
-    
+
+    /// Size and type of the decoded file. The size is checked against the
+    /// upload limit before anything is decoded. The data is then decoded a
+    /// chunk at a time into a scratch buffer, so bad base64 is caught before
+    /// the upload starts without holding a decoded copy of the file; the
+    /// type is sniffed from the first chunk and must match `declared_type`.
+    fn check_upload(&self, base64_data: &[u8], declared_type: &str) -> Result<CheckedUpload> {
+        let max_size = base64_data.len().div_ceil(4) * 3;
+        if max_size > self.max_upload_bytes + 2 {
+            return Err(DocumentUploadError::TooLarge {
+                size: max_size,
+                limit: self.max_upload_bytes,
+            }.into());
+        }
+
+        let mut scratch = vec![0u8; UPLOAD_CHUNK_CHARS / 4 * 3];
+        let mut size = 0;
+        let mut detected = None;
+        let mut metadata = None;
+        for chunk in base64_data.chunks(UPLOAD_CHUNK_CHARS) {
+            let decoded = BASE64.decode_slice(chunk, &mut scratch)
+                .map_err(|e| anyhow!("Failed to decode base64 data: {}", e))?;
+            if size == 0 {
+                detected = infer::get(&scratch[..decoded]);
+                // The DICOM header sits at the start of the file
+                if detected.is_some_and(|kind| kind.mime_type() == "application/dicom") {
+                    metadata = parse_imaging_metadata(&scratch[..decoded]);
+                }
+            }
+            size += decoded;
+        }
+
+        if size > self.max_upload_bytes {
+            return Err(DocumentUploadError::TooLarge { size, limit: self.max_upload_bytes }.into());
+        }
+
+        let detected = detected
+            .filter(|kind| ALLOWED_DOCUMENT_TYPES.contains(&kind.mime_type()))
+            .ok_or_else(|| DocumentUploadError::UnsupportedType {
+                detected: detected.map(|kind| kind.mime_type().to_string()),
+            })?;
+
+        let declared = normalize_mime_type(declared_type);
+        if declared != detected.mime_type() {
+            return Err(DocumentUploadError::TypeMismatch {
+                declared,
+                detected: detected.mime_type().to_string(),
+            }.into());
+        }
+
+        Ok(CheckedUpload {
+            size,
+            file_type: detected.mime_type(),
+            extension: detected.extension(),
+            metadata,
+        })
+    }
+
+    /// Record a document whose file is already stored elsewhere, such as a
+    /// consultation recording kept by the video provider
+    pub async fn link_document(
+        &self,
+        patient_id: &str,
+        title: &str,
+        file_url: &str,
+        file_type: &str,
+        auth_token: &str
+    ) -> Result<Document> {
+        debug!("Linking document for patient: {}", patient_id);
+
+        if file_url.is_empty() {
+            return Err(anyhow!("Document URL cannot be empty"));
+        }
+
+        self.insert_document(patient_id, None, title, file_url, file_type, None, auth_token).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_document(
+        &self,
+        patient_id: &str,
+        appointment_id: Option<Uuid>,
+        title: &str,
+        file_url: &str,
+        file_type: &str,
+        metadata: Option<ImagingMetadata>,
+        auth_token: &str
+    ) -> Result<Document> {
+        let mut doc_data = json!({
+            "patient_id": patient_id,
+            "title": title,
+            "file_url": file_url,
+            "file_type": file_type,
+            "uploaded_at": chrono::Utc::now().to_rfc3339()
+        });
+        if let Some(metadata) = metadata {
+            doc_data["metadata"] = json!(metadata);
+        }
+        if let Some(appointment_id) = appointment_id {
+            doc_data["appointment_id"] = json!(appointment_id);
+        }
+
+        // Add Prefer header for the POST request to get back the created record
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "Prefer",
+            reqwest::header::HeaderValue::from_static("return=representation")
+        );
+
+        let doc_result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/documents",
+            Some(auth_token),
+            Some(doc_data),
+            Some(headers),
+        ).await?;
+
+        if doc_result.is_empty() {
+            return Err(anyhow!("Failed to create document record"));
+        }
+
+        // Parse document with better error handling
+        let document: Document = match serde_json::from_value(doc_result[0].clone()) {
+            Ok(doc) => doc,
+            Err(e) => return Err(anyhow!("Failed to parse document record: {}", e)),
+        };
+
+        Ok(document)
+    }
+
     pub async fn get_documents(
         &self, 
         patient_id: &str, 
@@ -142,12 +307,36 @@ pub async fn upload_document(
         ).await?;
         
         let documents: Vec<Document> = result.into_iter()
-            .map(|doc| serde_json::from_value(doc))
+            .map(serde_json::from_value)
             .collect::<std::result::Result<Vec<Document>, _>>()?;
         
         Ok(documents)
     }
     
+    /// Documents attached to an appointment, oldest first
+    pub async fn get_appointment_attachments(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str
+    ) -> Result<Vec<Document>> {
+        debug!("Fetching attachments for appointment: {}", appointment_id);
+
+        let path = format!("/rest/v1/documents?appointment_id=eq.{}&order=uploaded_at.asc", appointment_id);
+
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await?;
+
+        let documents: Vec<Document> = result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<Vec<Document>, _>>()?;
+
+        Ok(documents)
+    }
+    
     pub async fn get_document(
         &self, 
         document_id: &str, 
@@ -207,4 +396,63 @@ pub async fn upload_document(
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// `declared` without parameters, lowercased, with common aliases mapped to
+/// the names `infer` reports
+fn normalize_mime_type(declared: &str) -> String {
+    let mime = declared.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "image/jpg" | "image/pjpeg" => "image/jpeg".to_string(),
+        "image/heic" => "image/heif".to_string(),
+        _ => mime,
+    }
+}
+
+/// The file in `base64_file` from `start` on, decoded one chunk at a time
+fn decoded_chunks(
+    base64_file: String,
+    start: usize,
+) -> impl Stream<Item = std::result::Result<Vec<u8>, base64::DecodeError>> + Send + 'static {
+    stream::unfold((base64_file, start), |(data, offset)| async move {
+        if offset >= data.len() {
+            return None;
+        }
+        let end = (offset + UPLOAD_CHUNK_CHARS).min(data.len());
+        let chunk = BASE64.decode(&data.as_bytes()[offset..end]);
+        Some((chunk, (data, end)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_decoded_chunks_reassemble_the_file() {
+        let file: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let data_url = format!("data:application/pdf;base64,{}", BASE64.encode(&file));
+        let start = data_url.find(";base64,").unwrap() + ";base64,".len();
+
+        let chunks: Vec<Vec<u8>> = decoded_chunks(data_url, start)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), file);
+    }
+
+    #[test]
+    fn test_normalize_mime_type() {
+        assert_eq!(normalize_mime_type("Application/PDF; charset=binary"), "application/pdf");
+        assert_eq!(normalize_mime_type("image/jpg"), "image/jpeg");
+        assert_eq!(normalize_mime_type("image/png"), "image/png");
+    }
+
+    #[test]
+    fn test_upload_body_limit_covers_encoded_file() {
+        let limit = upload_body_limit(3 * 1024 * 1024);
+        assert_eq!(limit, 4 * 1024 * 1024 + UPLOAD_ENVELOPE_BYTES);
+    }
+}
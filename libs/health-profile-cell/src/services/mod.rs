@@ -1,4 +1,5 @@
 pub mod profile;
 pub mod avatar;
 pub mod document;
+pub mod dicom;
 pub mod ai;
\ No newline at end of file
@@ -1,24 +1,31 @@
 use anyhow::{Result, anyhow};
+use chrono::Utc;
 use reqwest::Method;
-use serde_json::{json, Value};
-use tracing::{debug, error};
+use serde_json::{json, Map, Value};
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 use headers::HeaderMap;
 use headers::HeaderValue;
 
 use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
 use shared_database::supabase::SupabaseClient;
+use shared_models::auth::{Role, User};
 
-use crate::models::{HealthProfile, UpdateHealthProfile};
+use crate::models::{HealthProfile, ProfileChangeLog, UpdateHealthProfile};
 
 pub struct HealthProfileService {
     supabase: SupabaseClient,
+    audit_log: AuditLog,
+    allow_any_doctor: bool,
 }
 
 impl HealthProfileService {
     pub fn new(config: &AppConfig) -> Self {
         Self {
             supabase: SupabaseClient::new(config),
+            audit_log: AuditLog::new(config),
+            allow_any_doctor: config.allow_any_doctor_profile_access,
         }
     }
 
@@ -26,6 +33,44 @@ impl HealthProfileService {
         &self.supabase
     }
     
+    /// Minimum-necessary access: patients see their own profile, admins see
+    /// any, and doctors only those of patients they have (or had) an
+    /// appointment with. Every decision is written to the audit log.
+    pub async fn authorize_profile_access(&self, patient_id: &str, viewer: &User, auth_token: &str) -> Result<bool> {
+        let allowed = if viewer.id == patient_id || viewer.is_admin() {
+            true
+        } else if viewer.has_role(Role::Doctor) {
+            self.allow_any_doctor || self.has_care_relationship(patient_id, &viewer.id, auth_token).await?
+        } else {
+            false
+        };
+
+        let action = if allowed { "health_profile.viewed" } else { "health_profile.access_denied" };
+        let entry = AuditEntry::new(viewer.id.clone(), action, "health_profile", patient_id)
+            .with_details(json!({ "patient_id": patient_id, "viewer_role": viewer.role }));
+        if let Err(e) = self.audit_log.record(entry, auth_token).await {
+            warn!("Failed to audit health profile access for patient {}: {}", patient_id, e);
+        }
+
+        Ok(allowed)
+    }
+
+    /// Whether the doctor has a current or past, non-cancelled appointment with the patient
+    async fn has_care_relationship(&self, patient_id: &str, doctor_id: &str, auth_token: &str) -> Result<bool> {
+        let path = format!(
+            "/rest/v1/appointments?patient_id=eq.{}&doctor_id=eq.{}&status=neq.cancelled&select=id&limit=1",
+            patient_id, doctor_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await?;
+
+        Ok(!result.is_empty())
+    }
+
     pub async fn get_profile(&self, patient_id: &str, auth_token: &str) -> Result<HealthProfile> {
         debug!("Fetching health profile for patient: {}", patient_id);
         
@@ -46,12 +91,16 @@ impl HealthProfileService {
         Ok(profile)
     }
     
+    /// Apply `update_data` to `current` and record each changed field in the
+    /// profile's change history
     pub async fn update_profile(
         &self, 
-        profile_id: &str, 
+        current: &HealthProfile, 
         update_data: UpdateHealthProfile, 
+        changed_by: &User,
         auth_token: &str
     ) -> Result<HealthProfile> {
+        let profile_id = current.id;
         debug!("Updating health profile: {}", profile_id);
 
         // Calculate BMI if both height and weight are provided
@@ -74,10 +123,14 @@ impl HealthProfileService {
         if let Some(v) = update_data.is_pregnant { update_json.insert("is_pregnant".to_string(), json!(v)); }
         if let Some(v) = update_data.is_breastfeeding { update_json.insert("is_breastfeeding".to_string(), json!(v)); }
         if let Some(ref v) = update_data.reproductive_stage { update_json.insert("reproductive_stage".to_string(), json!(v)); }
-        update_json.insert("updated_at".to_string(), json!(chrono::Utc::now().to_rfc3339()));
+        if let Some(ref v) = update_data.emergency_contact_name { update_json.insert("emergency_contact_name".to_string(), json!(v)); }
+        if let Some(ref v) = update_data.emergency_contact_phone { update_json.insert("emergency_contact_phone".to_string(), json!(v)); }
+        if let Some(v) = update_data.notify_emergency_contact { update_json.insert("notify_emergency_contact".to_string(), json!(v)); }
         if let Some(bmi_value) = bmi {
             update_json.insert("bmi".to_string(), json!(bmi_value));
         }
+        let changes = changed_fields(current, &update_json);
+        update_json.insert("updated_at".to_string(), json!(chrono::Utc::now().to_rfc3339()));
 
         let path = format!("/rest/v1/health_profiles?id=eq.{}", profile_id);
 
@@ -105,8 +158,73 @@ impl HealthProfileService {
             }
         };
 
+        if let Err(e) = self.record_changes(current, changes, changed_by, auth_token).await {
+            error!("Failed to record change history for health profile {}: {}", profile_id, e);
+        }
+
         Ok(updated_profile)
     }
+
+    async fn record_changes(
+        &self,
+        profile: &HealthProfile,
+        changes: Vec<(String, Value, Value)>,
+        changed_by: &User,
+        auth_token: &str,
+    ) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let change_set_id = Uuid::new_v4();
+        let changed_at = Utc::now();
+        let entries: Vec<ProfileChangeLog> = changes.into_iter()
+            .map(|(field, old_value, new_value)| ProfileChangeLog {
+                id: Uuid::new_v4(),
+                profile_id: profile.id,
+                patient_id: profile.patient_id,
+                change_set_id,
+                changed_by: changed_by.id.clone(),
+                changed_by_role: changed_by.role.clone(),
+                field,
+                old_value,
+                new_value,
+                changed_at,
+            })
+            .collect();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Prefer", HeaderValue::from_static("return=minimal"));
+
+        let _: Value = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/health_profile_changes",
+            Some(auth_token),
+            Some(json!(entries)),
+            Some(headers),
+        ).await?;
+
+        debug!("Recorded {} changed fields for health profile {}", entries.len(), profile.id);
+        Ok(())
+    }
+
+    /// Every recorded field change to the patient's profile, newest first
+    pub async fn change_history(&self, patient_id: &str, auth_token: &str) -> Result<Vec<ProfileChangeLog>> {
+        let path = format!(
+            "/rest/v1/health_profile_changes?patient_id=eq.{}&order=changed_at.desc,field.asc",
+            patient_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await?;
+
+        result.into_iter()
+            .map(|value| serde_json::from_value(value).map_err(|e| anyhow!("Failed to parse profile change: {}", e)))
+            .collect()
+    }
     
     pub async fn create_profile(
         &self, 
@@ -243,4 +361,54 @@ impl HealthProfileService {
         Ok(())
     }
     
-}
\ No newline at end of file
+}
+
+/// (field, old value, new value) for each field in `updates` that differs
+/// from the stored profile
+fn changed_fields(current: &HealthProfile, updates: &Map<String, Value>) -> Vec<(String, Value, Value)> {
+    let current = json!(current);
+    updates.iter()
+        .filter_map(|(field, new_value)| {
+            let old_value = current.get(field).cloned().unwrap_or(Value::Null);
+            (&old_value != new_value).then(|| (field.clone(), old_value, new_value.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_fields_skips_unchanged_values() {
+        let profile: HealthProfile = serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "patient_id": Uuid::new_v4(),
+            "blood_type": "O+",
+            "height_cm": 170,
+            "weight_kg": null,
+            "bmi": null,
+            "allergies": "Penicillin",
+            "chronic_conditions": null,
+            "medications": null,
+            "avatar_url": null,
+            "is_pregnant": null,
+            "is_breastfeeding": null,
+            "reproductive_stage": null,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z"
+        })).unwrap();
+
+        let updates = json!({
+            "blood_type": "O+",
+            "height_cm": 172,
+            "allergies": "Penicillin, nuts"
+        });
+        let changes = changed_fields(&profile, updates.as_object().unwrap());
+
+        assert_eq!(changes, vec![
+            ("allergies".to_string(), json!("Penicillin"), json!("Penicillin, nuts")),
+            ("height_cm".to_string(), json!(170), json!(172)),
+        ]);
+    }
+}
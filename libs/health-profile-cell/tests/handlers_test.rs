@@ -7,7 +7,7 @@ use axum_extra::TypedHeader;
 use headers::{Authorization, authorization::Bearer};
 use serde_json::json;
 use wiremock::{MockServer, Mock, ResponseTemplate};
-use wiremock::matchers::{method, path, header, query_param};
+use wiremock::matchers::{method, path, header, query_param, body_partial_json};
 use uuid::Uuid;
 
 use health_profile_cell::handlers::*;
@@ -15,11 +15,10 @@ use health_profile_cell::models::{
     CreateHealthProfileRequest,
     UpdateHealthProfile,
     DocumentUpload,
-    AvatarUpload,
-    CarePlanRequest,
 };
 use shared_config::AppConfig;
 use shared_models::auth::User;
+use shared_models::error::AppError;
 use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils};
 
 // Explicitly import to resolve ambiguity
@@ -190,6 +189,9 @@ async fn test_update_health_profile_success() {
         is_pregnant: Some(false),
         is_breastfeeding: Some(false),
         reproductive_stage: Some("reproductive".to_string()),
+        emergency_contact_name: None,
+        emergency_contact_phone: None,
+        notify_emergency_contact: None,
     };
 
     let profile_id = Uuid::new_v4();
@@ -261,6 +263,13 @@ async fn test_update_health_profile_success() {
         .mount(&mock_server)
         .await;
 
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/health_profile_changes"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
     let result = update_health_profile(
         State(Arc::new(config)),
         axum::extract::Path(patient_user.id.clone()),
@@ -274,6 +283,75 @@ async fn test_update_health_profile_success() {
     assert_eq!(response["patient_id"], patient_user.id);
     assert_eq!(response["blood_type"], "A+");
     assert_eq!(response["height_cm"], 180);
+
+    // Each changed field is logged with its old and new value
+    let requests = mock_server.received_requests().await.unwrap();
+    let history_request = requests.iter()
+        .find(|request| request.url.path() == "/rest/v1/health_profile_changes")
+        .expect("change history should be recorded");
+    let changes: Vec<serde_json::Value> = serde_json::from_slice(&history_request.body).unwrap();
+    let blood_type = changes.iter().find(|change| change["field"] == "blood_type").unwrap();
+    assert_eq!(blood_type["old_value"], "O+");
+    assert_eq!(blood_type["new_value"], "A+");
+    assert_eq!(blood_type["changed_by"], patient_user.id);
+    assert!(changes.iter().all(|change| change["field"] != "updated_at"));
+    assert!(changes.iter().all(|change| change["change_set_id"] == changes[0]["change_set_id"]));
+}
+
+#[tokio::test]
+async fn test_profile_history_is_for_clinicians_only() {
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let admin = TestUser::admin("admin@example.com");
+    let token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::new_v4();
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/health_profile_changes"))
+        .and(query_param("patient_id", format!("eq.{}", patient_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "profile_id": Uuid::new_v4(),
+            "patient_id": patient_id,
+            "change_set_id": Uuid::new_v4(),
+            "changed_by": patient_id.to_string(),
+            "changed_by_role": "patient",
+            "field": "allergies",
+            "old_value": "Penicillin",
+            "new_value": "Penicillin, nuts",
+            "changed_at": "2024-06-01T10:00:00Z"
+        }])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let response = get_health_profile_history(
+        State(Arc::new(config.clone())),
+        axum::extract::Path(patient_id.to_string()),
+        create_test_user_extension("admin", &admin.id),
+        create_auth_header(&token),
+    ).await.expect("admins can read profile history").0;
+
+    assert_eq!(response["total"], 1);
+    assert_eq!(response["changes"][0]["field"], "allergies");
+    assert_eq!(response["changes"][0]["old_value"], "Penicillin");
+
+    // Not even the patient themselves
+    let result = get_health_profile_history(
+        State(Arc::new(config)),
+        axum::extract::Path(patient_id.to_string()),
+        create_test_user_extension("patient", &patient_id.to_string()),
+        create_auth_header(&token),
+    ).await;
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
 }
 
 #[tokio::test]
@@ -283,10 +361,10 @@ async fn test_upload_document_success() {
     config.supabase_url = mock_server.uri();
     
     let patient_user = TestUser::patient("patient@example.com");
-    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let _token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
     let document_id = Uuid::new_v4();
     
-    let upload_request = DocumentUpload {
+    let _upload_request = DocumentUpload {
         title: "medical_report.pdf".to_string(),
         file_data: "base64encodedfiledata".to_string(),
         file_type: "application/pdf".to_string(),
@@ -334,7 +412,7 @@ async fn test_upload_document_success() {
 #[tokio::test] 
 async fn test_analyze_document_mock() {
     // Mock test for document analysis since the function doesn't exist
-    let patient_user = TestUser::patient("patient@example.com");
+    let _patient_user = TestUser::patient("patient@example.com");
     let document_id = Uuid::new_v4();
     
     let mock_analysis = json!({
@@ -375,7 +453,7 @@ async fn test_get_documents_list() {
     config.supabase_url = mock_server.uri();
     
     let patient_user = TestUser::patient("patient@example.com");
-    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let _token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
 
     // Mock get documents list
     Mock::given(method("GET"))
@@ -468,7 +546,7 @@ async fn test_doctor_access_to_patient_profile() {
     config.supabase_url = mock_server.uri();
     
     let doctor_user = TestUser::doctor("doctor@example.com");
-    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, Some(24));
+    let _token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, Some(24));
     let patient_id = Uuid::new_v4().to_string();
 
     // Mock doctor accessing patient profile (should be allowed with proper authorization)
@@ -497,4 +575,139 @@ async fn test_doctor_access_to_patient_profile() {
 
     assert_eq!(mock_result["user_id"], patient_id);
     assert_eq!(mock_result["gender"], "Male");
-}
\ No newline at end of file
+}
+
+fn health_profile_row(patient_id: &str) -> serde_json::Value {
+    json!({
+        "id": Uuid::new_v4(),
+        "patient_id": patient_id,
+        "blood_type": "A+",
+        "height_cm": null,
+        "weight_kg": null,
+        "bmi": null,
+        "allergies": null,
+        "chronic_conditions": null,
+        "medications": null,
+        "avatar_url": null,
+        "is_pregnant": null,
+        "is_breastfeeding": null,
+        "reproductive_stage": null,
+        "created_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z"
+    })
+}
+
+#[tokio::test]
+async fn test_treating_doctor_can_view_patient_profile() {
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_user = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::new_v4().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("patient_id", format!("eq.{}", patient_id)))
+        .and(query_param("doctor_id", format!("eq.{}", doctor_user.id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": Uuid::new_v4() }])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/health_profiles"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([health_profile_row(&patient_id)])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!({
+            "actor_id": doctor_user.id,
+            "action": "health_profile.viewed",
+            "resource_id": patient_id
+        })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let response = get_health_profile(
+        State(Arc::new(config)),
+        axum::extract::Path(patient_id.clone()),
+        create_test_user_extension("doctor", &doctor_user.id),
+        create_auth_header(&token)
+    ).await.expect("treating doctor should see the profile").0;
+
+    assert_eq!(response["patient_id"], patient_id);
+}
+
+#[tokio::test]
+async fn test_unrelated_doctor_cannot_view_patient_profile() {
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor_user = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::new_v4().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!({ "action": "health_profile.access_denied" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result = get_health_profile(
+        State(Arc::new(config)),
+        axum::extract::Path(patient_id),
+        create_test_user_extension("doctor", &doctor_user.id),
+        create_auth_header(&token)
+    ).await;
+
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
+}
+
+#[tokio::test]
+async fn test_relaxed_config_lets_any_doctor_view_profiles() {
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+    config.allow_any_doctor_profile_access = true;
+
+    let doctor_user = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor_user, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::new_v4().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/health_profiles"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([health_profile_row(&patient_id)])))
+        .mount(&mock_server)
+        .await;
+
+    let result = get_health_profile(
+        State(Arc::new(config)),
+        axum::extract::Path(patient_id),
+        create_test_user_extension("doctor", &doctor_user.id),
+        create_auth_header(&token)
+    ).await;
+
+    assert!(result.is_ok());
+}
@@ -4,16 +4,16 @@ use axum::{
     http::{Request, StatusCode},
     Router,
 };
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use tower::ServiceExt;
 use serde_json::json;
-use uuid;
 use wiremock::{MockServer, Mock, ResponseTemplate};
 use wiremock::matchers::{method, path, path_regex, header, query_param};
 
 use health_profile_cell::router::health_profile_routes;
 use health_profile_cell::models::CreateHealthProfileRequest;
 use shared_config::AppConfig;
-use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils, MockSupabaseResponses};
+use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils};
 
 async fn create_test_app(config: AppConfig) -> Router {
     health_profile_routes(Arc::new(config))
@@ -174,7 +174,7 @@ async fn test_get_health_profile_success() {
 
     let request = Request::builder()
         .method("GET")
-        .uri(&format!("/health-profiles/{}", user.id))
+        .uri(format!("/health-profiles/{}", user.id))
         .header("authorization", format!("Bearer {}", token))
         .body(Body::empty())
         .unwrap();
@@ -269,7 +269,7 @@ async fn test_update_health_profile_success() {
 
     let request = Request::builder()
         .method("PUT")
-        .uri(&format!("/health-profiles/{}", user.id))
+        .uri(format!("/health-profiles/{}", user.id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(update_body.to_string()))
@@ -321,7 +321,7 @@ async fn test_upload_avatar_success() {
 
     let request = Request::builder()
         .method("POST")
-        .uri(&format!("/health-profiles/{}/avatar", user.id))
+        .uri(format!("/health-profiles/{}/avatar", user.id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(avatar_data.to_string()))
@@ -339,13 +339,269 @@ async fn test_upload_avatar_success() {
     assert_eq!(status, StatusCode::OK);
 }
 
+#[tokio::test]
+async fn test_upload_document_streams_decoded_file_to_storage() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/storage/v1/object/patient-documents/.*\.pdf$"))
+        .and(header("content-type", "application/pdf"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "Key": "test-file" })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/documents"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{
+            "id": uuid::Uuid::new_v4(),
+            "patient_id": user.id,
+            "title": "Lab results",
+            "file_url": format!("{}/storage/v1/object/public/patient-documents/test-file.pdf", mock_server.uri()),
+            "file_type": "application/pdf",
+            "uploaded_at": "2024-01-01T12:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    let mut file = b"%PDF-1.7\n".to_vec();
+    file.extend((0..150_000u32).map(|i| (i % 251) as u8));
+    let upload = json!({
+        "title": "Lab results",
+        "file_data": format!("data:application/pdf;base64,{}", BASE64.encode(&file)),
+        "file_type": "Application/PDF"
+    });
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/health-profiles/{}/documents", user.id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(upload.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Storage gets the raw file, not a JSON wrapper around it
+    let requests = mock_server.received_requests().await.unwrap();
+    let stored = requests.iter()
+        .find(|r| r.url.path().starts_with("/storage/"))
+        .unwrap();
+    assert_eq!(stored.body, file);
+
+    // The record gets the detected type, not the client's spelling of it
+    let record = requests.iter()
+        .find(|r| r.url.path() == "/rest/v1/documents")
+        .unwrap();
+    let record: serde_json::Value = serde_json::from_slice(&record.body).unwrap();
+    assert_eq!(record["file_type"], "application/pdf");
+}
+
+#[tokio::test]
+async fn test_upload_document_rejects_disguised_and_unsupported_files() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+
+    let upload_request = |content: &[u8], file_type: &str| {
+        let upload = json!({
+            "title": "Scan",
+            "file_data": BASE64.encode(content),
+            "file_type": file_type
+        });
+        Request::builder()
+            .method("POST")
+            .uri(format!("/health-profiles/{}/documents", user.id))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(upload.to_string()))
+            .unwrap()
+    };
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    let mut executable = b"MZ\x90\0\x03\0\0\0".to_vec();
+    executable.resize(256, 0);
+
+    let cases = [
+        (png.as_slice(), "application/pdf", "declared as application/pdf but its content is image/png"),
+        (executable.as_slice(), "application/pdf", "not accepted"),
+        (b"just some text".as_slice(), "text/plain", "could not be recognised"),
+    ];
+    for (content, file_type, message) in cases {
+        let response = app.clone().oneshot(upload_request(content, file_type)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "unsupported_media_type");
+        assert!(problem["detail"].as_str().unwrap().contains(message), "unexpected detail: {}", problem["detail"]);
+    }
+
+    assert!(mock_server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_dicom_upload_records_imaging_metadata() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+
+    let document = json!({
+        "id": uuid::Uuid::new_v4(),
+        "patient_id": user.id,
+        "title": "Chest CT",
+        "file_url": format!("{}/storage/v1/object/public/patient-documents/scan.dcm", mock_server.uri()),
+        "file_type": "application/dicom",
+        "uploaded_at": "2024-01-01T12:00:00Z",
+        "metadata": {
+            "modality": "CT",
+            "study_date": "2024-03-15",
+            "body_part": "CHEST",
+            "series_description": null
+        }
+    });
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/storage/v1/object/patient-documents/.*\.dcm$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "Key": "scan.dcm" })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/documents"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([document.clone()])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/documents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([document])))
+        .mount(&mock_server)
+        .await;
+
+    // Explicit VR little endian: preamble, file meta, then the dataset
+    let element = |group: u16, element: u16, vr: &[u8], value: &[u8]| {
+        let mut bytes = [group.to_le_bytes(), element.to_le_bytes()].concat();
+        bytes.extend(vr);
+        bytes.extend((value.len() as u16).to_le_bytes());
+        bytes.extend(value);
+        bytes
+    };
+    let mut file = vec![0u8; 128];
+    file.extend(b"DICM");
+    file.extend(element(0x0002, 0x0010, b"UI", b"1.2.840.10008.1.2.1\0"));
+    file.extend(element(0x0008, 0x0020, b"DA", b"20240315"));
+    file.extend(element(0x0008, 0x0060, b"CS", b"CT"));
+    file.extend(element(0x0010, 0x0010, b"PN", b"Doe^Jane"));
+    file.extend(element(0x0018, 0x0015, b"CS", b"CHEST "));
+
+    let upload = json!({
+        "title": "Chest CT",
+        "file_data": BASE64.encode(&file),
+        "file_type": "application/dicom"
+    });
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/health-profiles/{}/documents", user.id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(Body::from(upload.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let record = requests.iter()
+        .find(|r| r.url.path() == "/rest/v1/documents")
+        .unwrap();
+    let record: serde_json::Value = serde_json::from_slice(&record.body).unwrap();
+    assert_eq!(record["metadata"], json!({
+        "modality": "CT",
+        "study_date": "2024-03-15",
+        "body_part": "CHEST",
+        "series_description": null
+    }));
+    assert!(!record.to_string().contains("Doe"));
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/health-profiles/{}/documents", user.id))
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let documents: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(documents[0]["metadata"]["modality"], "CT");
+}
+
+#[tokio::test]
+async fn test_upload_document_over_limit_is_rejected() {
+    let mock_server = MockServer::start().await;
+
+    let user = TestUser::patient("patient@example.com");
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    config.max_document_upload_bytes = 1024;
+
+    let app = create_test_app(config.clone()).await;
+    let token = JwtTestUtils::create_test_token(&user, &config.supabase_jwt_secret, Some(24));
+
+    let upload_request = |file_len: usize| {
+        let upload = json!({
+            "title": "Scan",
+            "file_data": BASE64.encode(vec![0u8; file_len]),
+            "file_type": "application/pdf"
+        });
+        Request::builder()
+            .method("POST")
+            .uri(format!("/health-profiles/{}/documents", user.id))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(upload.to_string()))
+            .unwrap()
+    };
+
+    // Just over the file limit: refused by the document service
+    let response = app.clone().oneshot(upload_request(1025)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(problem["code"], "payload_too_large");
+
+    // Far over it: refused before the body is read
+    let response = app.oneshot(upload_request(256 * 1024)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    assert!(mock_server.received_requests().await.unwrap().is_empty());
+}
+
 #[tokio::test]
 async fn test_generate_nutrition_plan_success() {
     let mock_server = MockServer::start().await;
     
     // Set environment variables for AI service
     std::env::set_var("OPENAI_API_KEY", "test-api-key");
-    std::env::set_var("OPENAI_BASE_URL", &mock_server.uri());
+    std::env::set_var("OPENAI_BASE_URL", mock_server.uri());
     
     let user = TestUser::patient("patient@example.com");
     let test_config = TestConfig::default();
@@ -431,7 +687,7 @@ async fn test_generate_nutrition_plan_success() {
 
     let request = Request::builder()
         .method("POST")
-        .uri(&format!("/health-profiles/{}/ai/nutrition-plan", user.id))
+        .uri(format!("/health-profiles/{}/ai/nutrition-plan", user.id))
         .header("authorization", format!("Bearer {}", token))
         .header("content-type", "application/json")
         .body(Body::from(nutrition_request.to_string()))
@@ -456,7 +712,7 @@ async fn test_generate_nutrition_plan_success() {
 #[tokio::test]
 async fn test_unauthorized_requests() {
     let config = TestConfig::default().to_app_config();
-    let app = create_test_app(config.clone()).await;
+    let _app = create_test_app(config.clone()).await;
     
     let test_cases = vec![
         ("GET", "/health-profiles/test-id"),
@@ -0,0 +1,279 @@
+// libs/patient-cell/src/handlers.rs
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State, Extension},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::TypedHeader;
+use headers::{Authorization, authorization::Bearer};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::audit::{AuditCursor, AuditLog, AuditQuery, MAX_AUDIT_PAGE_SIZE};
+use shared_models::auth::User;
+use shared_models::error::AppError;
+use shared_utils::extractor::{roles::Admin, RequireRole};
+
+use crate::models::{DataExportFormat, ErasePatientRequest, PatientError, RecordConsentRequest};
+use crate::services::consent::ConsentService;
+use crate::services::erasure::ErasureService;
+use crate::services::export::DataExportService;
+
+// ==============================================================================
+// QUERY PARAMETER STRUCTS
+// ==============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct DataExportQuery {
+    pub format: Option<DataExportFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub actor_id: Option<String>,
+    pub action: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// ==============================================================================
+// CONSENT HANDLERS
+// ==============================================================================
+
+#[axum::debug_handler]
+pub async fn get_patient_consents(
+    State(state): State<Arc<AppConfig>>,
+    Path(patient_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    require_self_or_admin(&user, patient_id)?;
+
+    let consent_service = ConsentService::new(&state);
+    let consents = consent_service.get_consents(patient_id, auth.token()).await
+        .map_err(map_patient_error)?;
+
+    Ok(Json(json!({
+        "consents": consents,
+        "total": consents.len()
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn record_patient_consent(
+    State(state): State<Arc<AppConfig>>,
+    Path(patient_id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<RecordConsentRequest>,
+) -> Result<Json<Value>, AppError> {
+    // Consent is the patient's own decision; admins can't give it for them
+    if user.id != patient_id.to_string() {
+        return Err(AppError::Forbidden("Patients can only record their own consent".to_string()));
+    }
+
+    let ip_address = client_ip(peer.ip(), &headers, &state.trusted_proxies).to_string();
+    let consent_service = ConsentService::new(&state);
+    let record = consent_service.record_consent(
+        patient_id,
+        request.consent_type,
+        &request.version,
+        request.granted,
+        Some(&ip_address),
+        auth.token(),
+    ).await.map_err(map_patient_error)?;
+
+    Ok(Json(json!({
+        "consent": record,
+        "message": if record.granted { "Consent recorded" } else { "Consent withdrawn" }
+    })))
+}
+
+// ==============================================================================
+// DATA EXPORT HANDLERS
+// ==============================================================================
+
+/// Subject access request: everything held about the patient
+#[axum::debug_handler]
+pub async fn export_patient_data(
+    State(state): State<Arc<AppConfig>>,
+    Path(patient_id): Path<Uuid>,
+    Query(params): Query<DataExportQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Response, AppError> {
+    require_self_or_admin(&user, patient_id)?;
+
+    let export_service = DataExportService::new(&state);
+    let export = export_service.export_patient_data(patient_id, &user.id, auth.token()).await
+        .map_err(map_patient_error)?;
+
+    match params.format.unwrap_or(DataExportFormat::Json) {
+        DataExportFormat::Json => Ok(Json(json!(export)).into_response()),
+        DataExportFormat::Zip => {
+            let archive = export_service.build_archive(&export).await
+                .map_err(map_patient_error)?;
+            let disposition = format!(
+                "attachment; filename=\"patient-{}-export-{}.zip\"",
+                patient_id,
+                export.generated_at.format("%Y%m%d%H%M%S")
+            );
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ],
+                archive,
+            ).into_response())
+        }
+    }
+}
+
+// ==============================================================================
+// ERASURE HANDLERS
+// ==============================================================================
+
+/// Right to erasure. The first call returns a confirmation token; calling
+/// again with that token anonymizes the patient. There is no undo.
+#[axum::debug_handler]
+pub async fn erase_patient(
+    State(state): State<Arc<AppConfig>>,
+    Path(patient_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    admin: RequireRole<Admin>,
+    Json(request): Json<ErasePatientRequest>,
+) -> Result<Json<Value>, AppError> {
+    let erasure_service = ErasureService::new(&state);
+
+    let Some(confirmation_token) = request.confirmation_token.as_deref() else {
+        return Ok(Json(json!({
+            "confirmation_required": true,
+            "confirmation_token": erasure_service.confirmation_token(patient_id, &admin.id),
+            "message": "Erasure is irreversible. Repeat the request with this confirmation token today to proceed."
+        })));
+    };
+    if !erasure_service.verify_confirmation_token(patient_id, &admin.id, confirmation_token) {
+        return Err(AppError::BadRequest("Invalid or expired confirmation token".to_string())
+            .with_code("patient.invalid_confirmation"));
+    }
+
+    let result = erasure_service.erase_patient(patient_id, &admin.id, &request.reason, auth.token()).await
+        .map_err(map_patient_error)?;
+
+    Ok(Json(json!({
+        "erasure": result,
+        "message": "Patient data erased"
+    })))
+}
+
+// ==============================================================================
+// AUDIT HANDLERS
+// ==============================================================================
+
+/// Audit trail search for compliance reviews, newest first. Follow
+/// `next_cursor` to page through results; it is absent on the last page.
+#[axum::debug_handler]
+pub async fn search_audit_log(
+    State(state): State<Arc<AppConfig>>,
+    Query(params): Query<AuditLogQuery>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    if params.limit.is_some_and(|limit| limit == 0 || limit > MAX_AUDIT_PAGE_SIZE) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {}", MAX_AUDIT_PAGE_SIZE))
+            .with_code("audit.invalid_query"));
+    }
+    let cursor = params.cursor.as_deref()
+        .map(AuditCursor::decode)
+        .transpose()
+        .map_err(|e| AppError::BadRequest(e.to_string()).with_code("audit.invalid_cursor"))?;
+
+    let query = AuditQuery {
+        actor_id: params.actor_id,
+        action: params.action,
+        resource_type: params.resource_type,
+        resource_id: params.resource_id,
+        from: params.from,
+        to: params.to,
+        cursor,
+        limit: params.limit,
+    };
+    query.validate()
+        .map_err(|e| AppError::BadRequest(e.to_string()).with_code("audit.invalid_query"))?;
+
+    let page = AuditLog::new(&state).query(&query, auth.token()).await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(json!({
+        "entries": page.entries,
+        "total": page.entries.len(),
+        "next_cursor": page.next_cursor
+    })))
+}
+
+// ==============================================================================
+// HELPERS
+// ==============================================================================
+
+fn require_self_or_admin(user: &User, patient_id: Uuid) -> Result<(), AppError> {
+    let is_self = user.id == patient_id.to_string();
+    let is_admin = user.role.as_deref() == Some("admin");
+
+    if !is_self && !is_admin {
+        return Err(AppError::Forbidden("Not authorized to access this patient's records".to_string()));
+    }
+    Ok(())
+}
+
+/// Address of the client behind any trusted proxies. `X-Forwarded-For` is
+/// only read when the connection comes from a trusted proxy, and then from
+/// the right: each trusted proxy appends the address it saw, so the first
+/// untrusted hop is the furthest address that wasn't supplied by the client.
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+    let forwarded = headers.get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for hop in forwarded.into_iter().rev() {
+        let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = hop;
+        if !trusted_proxies.contains(&hop) {
+            break;
+        }
+    }
+    client
+}
+
+fn map_patient_error(e: PatientError) -> AppError {
+    let code = e.code();
+    match e {
+        PatientError::NotFound => AppError::NotFound("Patient not found".to_string()),
+        PatientError::Unauthorized => AppError::Forbidden(e.to_string()),
+        PatientError::ConsentRequired(_) => AppError::Forbidden(e.to_string()),
+        PatientError::AlreadyErased => AppError::BadRequest(e.to_string()),
+        PatientError::ValidationError(msg) => AppError::BadRequest(msg),
+        PatientError::DatabaseError(msg) => AppError::Database(msg),
+    }.with_code(code)
+}
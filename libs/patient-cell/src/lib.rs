@@ -0,0 +1,4 @@
+pub mod handlers;
+pub mod router;
+pub mod models;
+pub mod services;
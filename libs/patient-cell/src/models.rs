@@ -0,0 +1,151 @@
+// libs/patient-cell/src/models.rs
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+// ==============================================================================
+// CONSENT MODELS
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsentType {
+    Telemedicine,
+    DataProcessing,
+    /// Audio/video recording of a consultation
+    Recording,
+    /// Sharing safeguarding alerts with the patient's emergency contact
+    EmergencyContact,
+}
+
+impl fmt::Display for ConsentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsentType::Telemedicine => write!(f, "telemedicine"),
+            ConsentType::DataProcessing => write!(f, "data_processing"),
+            ConsentType::Recording => write!(f, "recording"),
+            ConsentType::EmergencyContact => write!(f, "emergency_contact"),
+        }
+    }
+}
+
+/// One grant or withdrawal. Records are never updated; the most recent
+/// record for a consent type is the patient's current decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsentRecord {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub consent_type: ConsentType,
+    pub version: String,
+    pub granted: bool,
+    pub ip_address: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordConsentRequest {
+    pub consent_type: ConsentType,
+    pub version: String, // Version of the consent text the patient saw
+    pub granted: bool,
+}
+
+// ==============================================================================
+// DATA EXPORT MODELS
+// ==============================================================================
+
+/// Everything held about a patient, for subject access requests. Records
+/// are passed through as stored so nothing is lost to model mapping.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientDataExport {
+    pub patient_id: Uuid,
+    pub generated_at: DateTime<Utc>,
+    pub profile: serde_json::Value,
+    pub health_profile: Option<serde_json::Value>,
+    pub appointments: Vec<serde_json::Value>,
+    pub consultation_notes: Vec<serde_json::Value>,
+    pub intake_responses: Vec<serde_json::Value>,
+    pub prescriptions: Vec<serde_json::Value>,
+    pub documents: Vec<serde_json::Value>,
+    pub health_profile_changes: Vec<serde_json::Value>,
+    pub reviews: Vec<serde_json::Value>,
+    pub session_recordings: Vec<serde_json::Value>,
+    pub readiness_reports: Vec<serde_json::Value>,
+    pub consents: Vec<serde_json::Value>,
+    pub audit_entries: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataExportFormat {
+    Json,
+    /// The JSON export plus the patient's document files
+    Zip,
+}
+
+// ==============================================================================
+// ERASURE MODELS
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErasePatientRequest {
+    pub reason: String,
+    /// Issued by a first call without a token; proves the admin meant it
+    pub confirmation_token: Option<String>,
+}
+
+/// A record left in place because it is still under legal retention
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetainedRecord {
+    pub resource_type: String,
+    pub resource_id: Uuid,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErasureResult {
+    pub patient_id: Uuid,
+    pub erased_at: DateTime<Utc>,
+    pub anonymized_fields: Vec<String>,
+    pub deleted_records: usize,
+    pub retained_records: Vec<RetainedRecord>,
+}
+
+// ==============================================================================
+// ERROR TYPES
+// ==============================================================================
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatientError {
+    #[error("Patient not found")]
+    NotFound,
+
+    #[error("Unauthorized access to patient record")]
+    Unauthorized,
+
+    #[error("Patient has not given {0} consent")]
+    ConsentRequired(ConsentType),
+
+    #[error("Patient has already been erased")]
+    AlreadyErased,
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+impl PatientError {
+    /// Stable machine-readable code for API clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            PatientError::NotFound => "patient.not_found",
+            PatientError::Unauthorized => "patient.unauthorized",
+            PatientError::ConsentRequired(_) => "patient.consent_required",
+            PatientError::AlreadyErased => "patient.already_erased",
+            PatientError::ValidationError(_) => "patient.validation",
+            PatientError::DatabaseError(_) => "patient.database",
+        }
+    }
+}
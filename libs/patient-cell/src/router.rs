@@ -0,0 +1,35 @@
+// libs/patient-cell/src/router.rs
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+    middleware,
+};
+
+use shared_config::AppConfig;
+use shared_utils::extractor::auth_middleware;
+
+use crate::handlers;
+
+pub fn patient_routes(state: Arc<AppConfig>) -> Router {
+    let protected_routes = Router::new()
+        // Consent tracking
+        .route("/{patient_id}/consents", get(handlers::get_patient_consents))
+        .route("/{patient_id}/consents", post(handlers::record_patient_consent))
+
+        // GDPR subject access request
+        .route("/{patient_id}/export", get(handlers::export_patient_data))
+
+        // GDPR right to erasure (admin only)
+        .route("/{patient_id}/erase", post(handlers::erase_patient))
+
+        // Audit trail search (admin only)
+        .route("/audit", get(handlers::search_audit_log))
+
+        .layer(middleware::from_fn_with_state(state.clone(), auth_middleware));
+
+    Router::new()
+        .merge(protected_routes)
+        .with_state(state)
+}
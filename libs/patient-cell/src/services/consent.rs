@@ -0,0 +1,125 @@
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{ConsentRecord, ConsentType, PatientError};
+
+/// Append-only record of the consents a patient has granted or withdrawn
+pub struct ConsentService {
+    supabase: SupabaseClient,
+}
+
+impl ConsentService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+        }
+    }
+
+    pub async fn record_consent(
+        &self,
+        patient_id: Uuid,
+        consent_type: ConsentType,
+        version: &str,
+        granted: bool,
+        ip_address: Option<&str>,
+        auth_token: &str,
+    ) -> Result<ConsentRecord, PatientError> {
+        if version.trim().is_empty() {
+            return Err(PatientError::ValidationError("Consent version is required".to_string()));
+        }
+
+        let consent_data = json!({
+            "id": Uuid::new_v4(),
+            "patient_id": patient_id,
+            "consent_type": consent_type,
+            "version": version.trim(),
+            "granted": granted,
+            "ip_address": ip_address,
+            "recorded_at": Utc::now().to_rfc3339()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let result: Vec<Value> = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/patient_consents",
+            Some(auth_token),
+            Some(consent_data),
+            Some(headers),
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+
+        let record: ConsentRecord = result.into_iter().next()
+            .ok_or_else(|| PatientError::DatabaseError("Failed to store consent".to_string()))
+            .and_then(|value| serde_json::from_value(value)
+                .map_err(|e| PatientError::DatabaseError(format!("Failed to parse consent: {}", e))))?;
+
+        info!("Patient {} {} {} consent (version {})",
+              patient_id, if granted { "granted" } else { "withdrew" }, consent_type, record.version);
+        Ok(record)
+    }
+
+    /// Full consent history for a patient, newest first
+    pub async fn get_consents(&self, patient_id: Uuid, auth_token: &str) -> Result<Vec<ConsentRecord>, PatientError> {
+        let path = format!(
+            "/rest/v1/patient_consents?patient_id=eq.{}&order=recorded_at.desc",
+            patient_id
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+
+        result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<ConsentRecord>, _>>()
+            .map_err(|e| PatientError::DatabaseError(format!("Failed to parse consents: {}", e)))
+    }
+
+    /// Whether the patient's latest decision for `consent_type` is a grant
+    pub async fn has_valid_consent(
+        &self,
+        patient_id: Uuid,
+        consent_type: ConsentType,
+        auth_token: &str,
+    ) -> Result<bool, PatientError> {
+        debug!("Checking {} consent for patient {}", consent_type, patient_id);
+
+        let path = format!(
+            "/rest/v1/patient_consents?patient_id=eq.{}&consent_type=eq.{}&order=recorded_at.desc&limit=1",
+            patient_id, consent_type
+        );
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+
+        Ok(result.first()
+            .and_then(|record| record["granted"].as_bool())
+            .unwrap_or(false))
+    }
+
+    /// `Err(ConsentRequired)` unless the patient currently grants `consent_type`
+    pub async fn require_consent(
+        &self,
+        patient_id: Uuid,
+        consent_type: ConsentType,
+        auth_token: &str,
+    ) -> Result<(), PatientError> {
+        if self.has_valid_consent(patient_id, consent_type, auth_token).await? {
+            Ok(())
+        } else {
+            Err(PatientError::ConsentRequired(consent_type))
+        }
+    }
+}
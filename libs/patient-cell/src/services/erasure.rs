@@ -0,0 +1,372 @@
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{ErasureResult, PatientError, RetainedRecord};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where uploaded documents are served from; see `DocumentService::upload_document`
+const DOCUMENTS_PUBLIC_PATH: &str = "/storage/v1/object/public/patient-documents/";
+
+/// Right to erasure. The patient row is anonymized in place so appointment
+/// statistics keep their foreign keys; clinical records still inside the
+/// retention period are left alone and reported back. Audit entries only
+/// carry ids, so the audit trail is never rewritten.
+pub struct ErasureService {
+    supabase: SupabaseClient,
+    audit_log: AuditLog,
+    jwt_secret: String,
+    retention_years: u32,
+}
+
+impl ErasureService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            audit_log: AuditLog::new(config),
+            jwt_secret: config.supabase_jwt_secret.clone(),
+            retention_years: config.clinical_record_retention_years,
+        }
+    }
+
+    /// Token the admin must send back to go ahead. Bound to the admin, the
+    /// patient and the current UTC day.
+    pub fn confirmation_token(&self, patient_id: Uuid, admin_id: &str) -> String {
+        confirmation_token(&self.jwt_secret, patient_id, admin_id, Utc::now().date_naive())
+    }
+
+    pub fn verify_confirmation_token(&self, patient_id: Uuid, admin_id: &str, token: &str) -> bool {
+        let expected = self.confirmation_token(patient_id, admin_id);
+        // Constant-time comparison
+        expected.len() == token.len()
+            && expected.bytes().zip(token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+
+    pub async fn erase_patient(
+        &self,
+        patient_id: Uuid,
+        erased_by: &str,
+        reason: &str,
+        auth_token: &str,
+    ) -> Result<ErasureResult, PatientError> {
+        if reason.trim().is_empty() {
+            return Err(PatientError::ValidationError("A reason for the erasure is required".to_string()));
+        }
+
+        let patient = self.fetch(&format!("/rest/v1/patients?id=eq.{}", patient_id), auth_token).await?
+            .into_iter()
+            .next()
+            .ok_or(PatientError::NotFound)?;
+        if !patient["erased_at"].is_null() {
+            return Err(PatientError::AlreadyErased);
+        }
+
+        debug!("Erasing patient {}", patient_id);
+        let erased_at = Utc::now();
+        let mut retained_records = Vec::new();
+        let mut deleted_records = 0;
+
+        // Appointments stay for statistics; only their free text goes unless
+        // they hold clinical notes that are still retained
+        let appointments = self.fetch(
+            &format!("/rest/v1/appointments?patient_id=eq.{}", patient_id),
+            auth_token,
+        ).await?;
+        let mut cleared_appointments = Vec::new();
+        for appointment in &appointments {
+            let has_clinical_content = !appointment["doctor_notes"].is_null()
+                || appointment["prescription_issued"].as_bool().unwrap_or(false)
+                || appointment["medical_certificate_issued"].as_bool().unwrap_or(false);
+            match self.retained_until(appointment, "scheduled_start_time", erased_at) {
+                Some(until) if has_clinical_content => {
+                    retained_records.push(retained("appointment", appointment, until));
+                }
+                _ => cleared_appointments.push(appointment),
+            }
+        }
+        for appointment in cleared_appointments {
+            self.clear_appointment_notes(appointment, auth_token).await?;
+        }
+
+        // Every table holding the patient's data is listed here, with the
+        // date its retention runs from; readiness self-tests aren't clinical
+        // and always go. A table added here belongs in `DataExportService` too.
+        let appointment_ids: Vec<Uuid> = appointments.iter().filter_map(record_id).collect();
+        let sessions = self.fetch(
+            &format!("/rest/v1/video_sessions?patient_id=eq.{}&select=id", patient_id),
+            auth_token,
+        ).await?;
+        let session_ids: Vec<Uuid> = sessions.iter().filter_map(record_id).collect();
+        let by_patient = Some(format!("patient_id=eq.{}", patient_id));
+        let in_ids = |column: &str, ids: &[Uuid]| (!ids.is_empty()).then(|| format!("{}=in.({})", column, join_ids(ids)));
+
+        for (table, resource_type, filter, date_field) in [
+            ("prescriptions", "prescription", by_patient.clone(), Some("issued_at")),
+            ("documents", "document", by_patient.clone(), Some("uploaded_at")),
+            ("health_profiles", "health_profile", by_patient.clone(), Some("updated_at")),
+            ("health_profile_changes", "health_profile_change", by_patient.clone(), Some("changed_at")),
+            ("intake_responses", "intake_response", by_patient.clone(), Some("submitted_at")),
+            ("consultation_notes", "consultation_note", in_ids("appointment_id", &appointment_ids), Some("created_at")),
+            ("video_session_recordings", "video_session_recording", in_ids("session_id", &session_ids), Some("started_at")),
+            ("telemedicine_readiness_reports", "telemedicine_readiness_report", Some(format!("user_id=eq.{}", patient_id)), None),
+        ] {
+            let Some(filter) = filter else {
+                continue;
+            };
+            let records = self.fetch(&format!("/rest/v1/{}?{}", table, filter), auth_token).await?;
+            let mut expired = Vec::new();
+            for record in &records {
+                match date_field.and_then(|field| self.retained_until(record, field, erased_at)) {
+                    Some(until) => retained_records.push(retained(resource_type, record, until)),
+                    None => expired.extend(record_id(record).map(|id| (id, record))),
+                }
+            }
+            if !expired.is_empty() {
+                // A document row only points at the file, which stays
+                // readable through its public URL until it is removed too
+                if table == "documents" {
+                    for (_, document) in &expired {
+                        self.delete_stored_file(document, auth_token).await?;
+                    }
+                }
+                let ids: Vec<Uuid> = expired.iter().map(|(id, _)| *id).collect();
+                self.delete(&format!("/rest/v1/{}?id=in.({})", table, join_ids(&ids)), auth_token).await?;
+                deleted_records += ids.len();
+            }
+        }
+
+        // Ratings stay in the doctor's aggregate; the patient's own words go
+        self.update(
+            &format!("/rest/v1/doctor_reviews?patient_id=eq.{}", patient_id),
+            json!({ "comment": null }),
+            auth_token,
+        ).await?;
+
+        // Consent history is kept as proof of lawful processing, minus the IP
+        self.update(
+            &format!("/rest/v1/patient_consents?patient_id=eq.{}", patient_id),
+            json!({ "ip_address": null }),
+            auth_token,
+        ).await?;
+
+        // The patient row goes last: `erased_at` marks the erasure complete,
+        // so a failure above can be retried
+        let tombstone = tombstone(patient_id, &patient, erased_at);
+        let anonymized_fields = tombstone.as_object()
+            .map(|fields| fields.keys().filter(|key| *key != "erased_at").cloned().collect())
+            .unwrap_or_default();
+        self.update(&format!("/rest/v1/patients?id=eq.{}", patient_id), tombstone, auth_token).await?;
+
+        let erasure_record = json!({
+            "id": Uuid::new_v4(),
+            "patient_id": patient_id,
+            "erased_by": erased_by,
+            "reason": reason.trim(),
+            "erased_at": erased_at.to_rfc3339(),
+            "deleted_records": deleted_records,
+            "retained_records": retained_records
+        });
+        let _: Value = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/patient_erasures",
+            Some(auth_token),
+            Some(erasure_record),
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+
+        let audit_entry = AuditEntry::new(erased_by, "patient.erased", "patient", patient_id.to_string())
+            .with_details(json!({
+                "deleted_records": deleted_records,
+                "retained_records": retained_records.len()
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit erasure of patient {}: {}", patient_id, e);
+        }
+
+        info!("Patient {} erased by {} ({} records retained)", patient_id, erased_by, retained_records.len());
+        Ok(ErasureResult {
+            patient_id,
+            erased_at,
+            anonymized_fields,
+            deleted_records,
+            retained_records,
+        })
+    }
+
+    /// End of the retention period for a clinical record, if it hasn't passed
+    fn retained_until(&self, record: &Value, date_field: &str, now: DateTime<Utc>) -> Option<NaiveDate> {
+        // Undated records are kept: better to over-retain than to lose a chart
+        let Some(recorded) = record[date_field].as_str()
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        else {
+            return Some(now.date_naive());
+        };
+
+        recorded.with_timezone(&Utc)
+            .checked_add_months(Months::new(self.retention_years * 12))
+            .filter(|until| *until > now)
+            .map(|until| until.date_naive())
+    }
+
+    async fn fetch(&self, path: &str, auth_token: &str) -> Result<Vec<Value>, PatientError> {
+        self.supabase.request(
+            Method::GET,
+            path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))
+    }
+
+    async fn update(&self, path: &str, data: Value, auth_token: &str) -> Result<(), PatientError> {
+        let _: Vec<Value> = self.supabase.request_with_headers(
+            Method::PATCH,
+            path,
+            Some(auth_token),
+            Some(data),
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Clear an appointment's free text. Versioned like every other write to
+    /// an appointment, so an edit made from a copy read before the erasure
+    /// is turned away instead of putting the notes back.
+    async fn clear_appointment_notes(&self, appointment: &Value, auth_token: &str) -> Result<(), PatientError> {
+        let Some(id) = record_id(appointment) else {
+            return Ok(());
+        };
+        let version = appointment["version"].as_i64().unwrap_or(0);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+
+        let updated: Vec<Value> = self.supabase.request_with_headers(
+            Method::PATCH,
+            &format!("/rest/v1/appointments?id=eq.{}&version=eq.{}", id, version),
+            Some(auth_token),
+            Some(json!({ "patient_notes": null, "doctor_notes": null, "version": version + 1 })),
+            Some(headers),
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+
+        if updated.is_empty() {
+            return Err(PatientError::DatabaseError(format!(
+                "Appointment {} changed during the erasure; run it again", id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Remove an uploaded document's file from the `patient-documents`
+    /// bucket. Files linked from elsewhere aren't ours to delete, and one
+    /// that is already missing counts as deleted.
+    async fn delete_stored_file(&self, document: &Value, auth_token: &str) -> Result<(), PatientError> {
+        let Some(object) = document["file_url"].as_str()
+            .and_then(|url| url.split_once(DOCUMENTS_PUBLIC_PATH))
+            .map(|(_, object)| object)
+        else {
+            return Ok(());
+        };
+
+        let path = format!("/storage/v1/object/patient-documents/{}", object);
+        match self.supabase.request::<Value>(Method::DELETE, &path, Some(auth_token), None).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.to_string().starts_with("Resource not found") => {
+                warn!("Stored file for document {} was already gone", document["id"]);
+                Ok(())
+            }
+            Err(e) => Err(PatientError::DatabaseError(e.to_string())),
+        }
+    }
+
+    async fn delete(&self, path: &str, auth_token: &str) -> Result<(), PatientError> {
+        let _: Vec<Value> = self.supabase.request_with_headers(
+            Method::DELETE,
+            path,
+            Some(auth_token),
+            None,
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+fn confirmation_token(secret: &str, patient_id: Uuid, admin_id: &str, day: NaiveDate) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("erase:{}:{}:{}", patient_id, admin_id, day).as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Replacement values for the patient's identifying fields. Date of birth is
+/// cut down to the year and gender is kept so age/sex statistics still work.
+fn tombstone(patient_id: Uuid, patient: &Value, erased_at: DateTime<Utc>) -> Value {
+    let birth_year = patient["date_of_birth"].as_str()
+        .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        .map(|date| format!("{}-01-01", date.year()));
+
+    json!({
+        "full_name": "Erased patient",
+        "email": format!("erased-{}@erased.invalid", patient_id.simple()),
+        "phone_number": null,
+        "address": null,
+        "date_of_birth": birth_year,
+        "erased_at": erased_at.to_rfc3339()
+    })
+}
+
+fn retained(resource_type: &str, record: &Value, until: NaiveDate) -> RetainedRecord {
+    RetainedRecord {
+        resource_type: resource_type.to_string(),
+        resource_id: record_id(record).unwrap_or_default(),
+        reason: format!("Clinical record under legal retention until {}", until),
+    }
+}
+
+fn record_id(record: &Value) -> Option<Uuid> {
+    record["id"].as_str().and_then(|id| Uuid::parse_str(id).ok())
+}
+
+fn join_ids(ids: &[Uuid]) -> String {
+    ids.iter().map(Uuid::to_string).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmation_token_is_bound_to_patient_and_day() {
+        let patient_id = Uuid::new_v4();
+        let day = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let token = confirmation_token("secret", patient_id, "admin", day);
+
+        assert_eq!(token, confirmation_token("secret", patient_id, "admin", day));
+        assert_ne!(token, confirmation_token("secret", Uuid::new_v4(), "admin", day));
+        assert_ne!(token, confirmation_token("secret", patient_id, "admin", day.succ_opt().unwrap()));
+    }
+
+    #[test]
+    fn test_tombstone_keeps_birth_year_only() {
+        let patient_id = Uuid::new_v4();
+        let patient = json!({ "full_name": "Jane Doe", "date_of_birth": "1990-07-15" });
+        let tombstone = tombstone(patient_id, &patient, Utc::now());
+
+        assert_eq!(tombstone["full_name"], "Erased patient");
+        assert_eq!(tombstone["date_of_birth"], "1990-01-01");
+        assert!(tombstone["phone_number"].is_null());
+        assert!(tombstone["email"].as_str().unwrap().ends_with("@erased.invalid"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::io::{Cursor, Write};
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+
+use crate::models::{PatientDataExport, PatientError};
+
+/// How long one document download may take before it is listed as unavailable
+const DOCUMENT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Subject access requests: gathers every record held about a patient into
+/// one export and records that the export happened.
+pub struct DataExportService {
+    supabase: SupabaseClient,
+    audit_log: AuditLog,
+    max_document_bytes: usize,
+}
+
+impl DataExportService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+            audit_log: AuditLog::new(config),
+            max_document_bytes: config.max_document_upload_bytes,
+        }
+    }
+
+    pub async fn export_patient_data(
+        &self,
+        patient_id: Uuid,
+        requested_by: &str,
+        auth_token: &str,
+    ) -> Result<PatientDataExport, PatientError> {
+        debug!("Collecting data export for patient {}", patient_id);
+
+        let profile = self.fetch(&format!("/rest/v1/patients?id=eq.{}", patient_id), auth_token).await?
+            .into_iter()
+            .next()
+            .ok_or(PatientError::NotFound)?;
+
+        let health_profile = self.fetch(&format!("/rest/v1/health_profiles?patient_id=eq.{}", patient_id), auth_token).await?
+            .into_iter()
+            .next();
+
+        let appointments = self.fetch(
+            &format!("/rest/v1/appointments?patient_id=eq.{}&order=scheduled_start_time.asc", patient_id),
+            auth_token,
+        ).await?;
+        let appointment_ids: Vec<String> = appointments.iter()
+            .filter_map(|appointment| appointment["id"].as_str().map(str::to_string))
+            .collect();
+        let consultation_notes = self.fetch_for_ids("consultation_notes", "appointment_id", &appointment_ids, "created_at", auth_token).await?;
+        let intake_responses = self.fetch(
+            &format!("/rest/v1/intake_responses?patient_id=eq.{}&order=submitted_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        let prescriptions = self.fetch(
+            &format!("/rest/v1/prescriptions?patient_id=eq.{}&order=issued_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        let documents = self.fetch(
+            &format!("/rest/v1/documents?patient_id=eq.{}&order=uploaded_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        let health_profile_changes = self.fetch(
+            &format!("/rest/v1/health_profile_changes?patient_id=eq.{}&order=changed_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        let reviews = self.fetch(
+            &format!("/rest/v1/doctor_reviews?patient_id=eq.{}&order=created_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        let session_ids: Vec<String> = self.fetch(
+            &format!("/rest/v1/video_sessions?patient_id=eq.{}&select=id", patient_id),
+            auth_token,
+        ).await?
+            .iter()
+            .filter_map(|session| session["id"].as_str().map(str::to_string))
+            .collect();
+        let session_recordings = self.fetch_for_ids("video_session_recordings", "session_id", &session_ids, "started_at", auth_token).await?;
+        let readiness_reports = self.fetch(
+            &format!("/rest/v1/telemedicine_readiness_reports?user_id=eq.{}&order=created_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        let consents = self.fetch(
+            &format!("/rest/v1/patient_consents?patient_id=eq.{}&order=recorded_at.asc", patient_id),
+            auth_token,
+        ).await?;
+        // Entries made by the patient or about them
+        let audit_entries = self.fetch(
+            &format!(
+                "/rest/v1/audit_log?or=(actor_id.eq.{id},resource_id.eq.{id},details->>patient_id.eq.{id})&order=created_at.asc",
+                id = patient_id
+            ),
+            auth_token,
+        ).await?;
+
+        let export = PatientDataExport {
+            patient_id,
+            generated_at: Utc::now(),
+            profile,
+            health_profile,
+            appointments,
+            consultation_notes,
+            intake_responses,
+            prescriptions,
+            documents,
+            health_profile_changes,
+            reviews,
+            session_recordings,
+            readiness_reports,
+            consents,
+            audit_entries,
+        };
+
+        let audit_entry = AuditEntry::new(requested_by, "patient.data_exported", "patient", patient_id.to_string())
+            .with_details(json!({
+                "appointments": export.appointments.len(),
+                "documents": export.documents.len()
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit data export for patient {}: {}", patient_id, e);
+        }
+
+        info!("Data export generated for patient {} by {}", patient_id, requested_by);
+        Ok(export)
+    }
+
+    /// Zip archive of `export.json` plus each document file. Files that
+    /// can't be downloaded in time, or are over the upload limit, are listed
+    /// in `documents/unavailable.txt`.
+    pub async fn build_archive(&self, export: &PatientDataExport) -> Result<Vec<u8>, PatientError> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut unavailable = Vec::new();
+
+        let export_json = serde_json::to_vec_pretty(export)
+            .map_err(|e| PatientError::ValidationError(format!("Failed to serialize export: {}", e)))?;
+        write_entry(&mut archive, "export.json", &export_json, options)?;
+
+        for document in &export.documents {
+            let id = document["id"].as_str().unwrap_or("unknown");
+            let Some(url) = document["file_url"].as_str() else {
+                unavailable.push(format!("{}: no file URL", id));
+                continue;
+            };
+
+            match self.supabase.download_object(url, DOCUMENT_DOWNLOAD_TIMEOUT, self.max_document_bytes).await {
+                Ok(bytes) => {
+                    let name = format!("documents/{}.{}", id, file_extension(document["file_type"].as_str()));
+                    write_entry(&mut archive, &name, &bytes, options)?;
+                }
+                Err(e) => {
+                    warn!("Could not include document {} in export: {}", id, e);
+                    unavailable.push(format!("{}: {}", id, e));
+                }
+            }
+        }
+
+        if !unavailable.is_empty() {
+            write_entry(&mut archive, "documents/unavailable.txt", unavailable.join("\n").as_bytes(), options)?;
+        }
+
+        archive.finish()
+            .map(|cursor| cursor.into_inner())
+            .map_err(|e| PatientError::ValidationError(format!("Failed to build archive: {}", e)))
+    }
+
+    async fn fetch(&self, path: &str, auth_token: &str) -> Result<Vec<Value>, PatientError> {
+        self.supabase.request(
+            Method::GET,
+            path,
+            Some(auth_token),
+            None,
+        ).await.map_err(|e| PatientError::DatabaseError(e.to_string()))
+    }
+
+    /// Rows of `table` whose `column` is one of `ids`
+    async fn fetch_for_ids(
+        &self,
+        table: &str,
+        column: &str,
+        ids: &[String],
+        order_by: &str,
+        auth_token: &str,
+    ) -> Result<Vec<Value>, PatientError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.fetch(
+            &format!("/rest/v1/{}?{}=in.({})&order={}.asc", table, column, ids.join(","), order_by),
+            auth_token,
+        ).await
+    }
+}
+
+fn write_entry(
+    archive: &mut ZipWriter<Cursor<Vec<u8>>>,
+    name: &str,
+    data: &[u8],
+    options: SimpleFileOptions,
+) -> Result<(), PatientError> {
+    archive.start_file(name, options)
+        .and_then(|_| archive.write_all(data).map_err(Into::into))
+        .map_err(|e| PatientError::ValidationError(format!("Failed to write {} to archive: {}", name, e)))
+}
+
+/// Extension from a MIME type, the same way documents are named in storage
+fn file_extension(file_type: Option<&str>) -> &str {
+    match file_type {
+        Some(file_type) if file_type.contains('/') => file_type.rsplit('/').next().unwrap_or("bin"),
+        Some(file_type) if !file_type.is_empty() => file_type,
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_extension() {
+        assert_eq!(file_extension(Some("application/pdf")), "pdf");
+        assert_eq!(file_extension(Some("png")), "png");
+        assert_eq!(file_extension(None), "bin");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod consent;
+pub mod erasure;
+pub mod export;
@@ -0,0 +1,665 @@
+use std::sync::Arc;
+use axum::{
+    extract::{ConnectInfo, Extension, Path, Query, State},
+    http::{HeaderMap, HeaderValue},
+    Json,
+};
+use axum_extra::TypedHeader;
+use headers::{Authorization, authorization::Bearer};
+use serde_json::json;
+use uuid::Uuid;
+use wiremock::{MockServer, Mock, ResponseTemplate};
+use wiremock::matchers::{body_partial_json, method, path, query_param};
+
+use patient_cell::handlers::*;
+use patient_cell::models::*;
+use shared_models::auth::User;
+use shared_models::error::AppError;
+use shared_utils::extractor::{roles::Admin, RequireRole};
+use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils};
+
+fn create_test_user_extension(role: &str, id: &str) -> Extension<User> {
+    Extension(User {
+        id: id.to_string(),
+        email: Some(format!("{}@example.com", role)),
+        role: Some(role.to_string()),
+        metadata: None,
+        created_at: Some(chrono::Utc::now()),
+    })
+}
+
+fn require_admin(id: &str) -> RequireRole<Admin> {
+    RequireRole::check(create_test_user_extension("admin", id).0)
+        .expect("admin passes the admin guard")
+}
+
+fn create_auth_header(token: &str) -> TypedHeader<Authorization<Bearer>> {
+    TypedHeader(Authorization::bearer(token).unwrap())
+}
+
+#[tokio::test]
+async fn test_record_consent_stores_client_ip() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    config.trusted_proxies = vec!["10.0.0.1".parse().unwrap()];
+    let config = Arc::new(config);
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::parse_str(&patient.id).unwrap();
+
+    let consent = |ip_address: &str| json!([{
+        "id": Uuid::new_v4(),
+        "patient_id": patient_id,
+        "consent_type": "telemedicine",
+        "version": "2024-01",
+        "granted": true,
+        "ip_address": ip_address,
+        "recorded_at": "2024-06-01T00:00:00Z"
+    }]);
+    for ip_address in ["203.0.113.7", "198.51.100.20"] {
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/patient_consents"))
+            .and(body_partial_json(json!({
+                "consent_type": "telemedicine",
+                "granted": true,
+                "ip_address": ip_address
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(consent(ip_address)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    // The client made up the first hop; the load balancer appended the
+    // address it actually saw
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", HeaderValue::from_static("192.0.2.1, 203.0.113.7"));
+    let record = |peer: &str, headers: HeaderMap| record_patient_consent(
+        State(config.clone()),
+        Path(patient_id),
+        ConnectInfo(peer.parse().unwrap()),
+        headers,
+        create_auth_header(&token),
+        create_test_user_extension("patient", &patient.id),
+        Json(RecordConsentRequest {
+            consent_type: ConsentType::Telemedicine,
+            version: "2024-01".to_string(),
+            granted: true,
+        }),
+    );
+
+    let response = record("10.0.0.1:41000", headers.clone()).await
+        .expect("patient should be able to record consent").0;
+    assert_eq!(response["consent"]["granted"], true);
+    assert_eq!(response["message"], "Consent recorded");
+
+    // Straight from the internet the header is ignored
+    let response = record("198.51.100.20:41000", headers).await
+        .expect("patient should be able to record consent").0;
+    assert_eq!(response["consent"]["ip_address"], "198.51.100.20");
+}
+
+#[tokio::test]
+async fn test_other_users_cannot_read_consents() {
+    let config = TestConfig::default().to_app_config();
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+
+    let result = get_patient_consents(
+        State(Arc::new(config)),
+        Path(Uuid::new_v4()),
+        create_auth_header(&token),
+        create_test_user_extension("doctor", &doctor.id),
+    ).await;
+
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
+}
+
+#[tokio::test]
+async fn test_patient_can_export_own_data() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::parse_str(&patient.id).unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patients"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": patient_id,
+            "full_name": "Test Patient",
+            "email": "patient@example.com"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    let appointment_id = Uuid::new_v4();
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": appointment_id,
+            "patient_id": patient_id,
+            "status": "completed"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/consultation_notes"))
+        .and(query_param("appointment_id", format!("in.({})", appointment_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "appointment_id": appointment_id,
+            "assessment": "Tension headache"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    for table in [
+        "health_profiles", "prescriptions", "documents", "patient_consents", "intake_responses",
+        "health_profile_changes", "doctor_reviews", "video_sessions", "telemedicine_readiness_reports",
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/rest/v1/{}", table)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+    }
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/audit_log"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!({
+            "action": "patient.data_exported",
+            "resource_id": patient_id.to_string()
+        })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let response = export_patient_data(
+        State(Arc::new(config)),
+        Path(patient_id),
+        Query(DataExportQuery { format: None }),
+        create_auth_header(&token),
+        create_test_user_extension("patient", &patient.id),
+    ).await.expect("patient should be able to export their data");
+
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let export: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(export["profile"]["full_name"], "Test Patient");
+    assert_eq!(export["appointments"].as_array().unwrap().len(), 1);
+    assert_eq!(export["consultation_notes"][0]["assessment"], "Tension headache");
+    assert!(export["health_profile"].is_null());
+}
+
+#[tokio::test]
+async fn test_export_archive_lists_oversize_documents_as_unavailable() {
+    use patient_cell::services::export::DataExportService;
+
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    config.max_document_upload_bytes = 1024;
+
+    let small_id = Uuid::new_v4();
+    let large_id = Uuid::new_v4();
+    Mock::given(method("GET"))
+        .and(path("/files/small.pdf"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![b'a'; 512]))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/files/large.pdf"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![b'a'; 4096]))
+        .mount(&mock_server)
+        .await;
+
+    let export = PatientDataExport {
+        patient_id: Uuid::new_v4(),
+        generated_at: chrono::Utc::now(),
+        profile: json!({ "full_name": "Test Patient" }),
+        health_profile: None,
+        appointments: vec![],
+        consultation_notes: vec![],
+        intake_responses: vec![],
+        prescriptions: vec![],
+        documents: vec![
+            json!({ "id": small_id, "file_url": format!("{}/files/small.pdf", mock_server.uri()), "file_type": "application/pdf" }),
+            json!({ "id": large_id, "file_url": format!("{}/files/large.pdf", mock_server.uri()), "file_type": "application/pdf" }),
+        ],
+        health_profile_changes: vec![],
+        reviews: vec![],
+        session_recordings: vec![],
+        readiness_reports: vec![],
+        consents: vec![],
+        audit_entries: vec![],
+    };
+
+    let archive = DataExportService::new(&config).build_archive(&export).await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+    assert!(archive.by_name(&format!("documents/{}.pdf", small_id)).is_ok());
+    assert!(archive.by_name(&format!("documents/{}.pdf", large_id)).is_err());
+
+    let mut unavailable = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("documents/unavailable.txt").unwrap(), &mut unavailable).unwrap();
+    assert!(unavailable.starts_with(&large_id.to_string()), "{}", unavailable);
+    assert!(unavailable.contains("1024 byte limit"), "{}", unavailable);
+}
+
+#[tokio::test]
+async fn test_other_users_cannot_export_patient_data() {
+    let config = TestConfig::default().to_app_config();
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+
+    let result = export_patient_data(
+        State(Arc::new(config)),
+        Path(Uuid::new_v4()),
+        Query(DataExportQuery { format: Some(DataExportFormat::Zip) }),
+        create_auth_header(&token),
+        create_test_user_extension("doctor", &doctor.id),
+    ).await;
+
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
+}
+
+#[tokio::test]
+async fn test_erasure_requires_confirmation_then_anonymizes() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let config = Arc::new(config);
+
+    let admin = TestUser::admin("admin@example.com");
+    let token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::new_v4();
+    let retained_appointment = Uuid::new_v4();
+    let retained_note = Uuid::new_v4();
+    let expired_prescription = Uuid::new_v4();
+    let session_id = Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patients"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": patient_id,
+            "full_name": "Jane Doe",
+            "email": "jane@example.com",
+            "date_of_birth": "1990-07-15",
+            "erased_at": null
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": retained_appointment,
+            "patient_id": patient_id,
+            "scheduled_start_time": chrono::Utc::now().to_rfc3339(),
+            "doctor_notes": "S: headache"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/prescriptions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": expired_prescription,
+            "patient_id": patient_id,
+            "issued_at": "2005-01-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    // A note on the retained appointment is retained with it
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/consultation_notes"))
+        .and(query_param("appointment_id", format!("in.({})", retained_appointment)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": retained_note,
+            "appointment_id": retained_appointment,
+            "created_at": chrono::Utc::now().to_rfc3339()
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": session_id }])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_recordings"))
+        .and(query_param("session_id", format!("in.({})", session_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "session_id": session_id,
+            "started_at": "2005-01-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    // Self-tests aren't clinical, so even a recent one goes
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/telemedicine_readiness_reports"))
+        .and(query_param("user_id", format!("eq.{}", patient_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "user_id": patient_id,
+            "created_at": chrono::Utc::now().to_rfc3339()
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    for table in ["documents", "health_profiles", "health_profile_changes", "intake_responses"] {
+        Mock::given(method("GET"))
+            .and(path(format!("/rest/v1/{}", table)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+    }
+
+    for table in ["prescriptions", "video_session_recordings", "telemedicine_readiness_reports"] {
+        Mock::given(method("DELETE"))
+            .and(path(format!("/rest/v1/{}", table)))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/doctor_reviews"))
+        .and(query_param("patient_id", format!("eq.{}", patient_id)))
+        .and(body_partial_json(json!({ "comment": null })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // The retained appointment must not be touched
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/patient_consents"))
+        .and(body_partial_json(json!({ "ip_address": null })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/patients"))
+        .and(body_partial_json(json!({
+            "full_name": "Erased patient",
+            "phone_number": null,
+            "date_of_birth": "1990-01-01"
+        })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/patient_erasures"))
+        .and(body_partial_json(json!({ "patient_id": patient_id, "reason": "Patient request" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!({ "action": "patient.erased" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Without a token nothing is erased, a token is handed back instead
+    let response = erase_patient(
+        State(config.clone()),
+        Path(patient_id),
+        create_auth_header(&token),
+        require_admin(&admin.id),
+        Json(ErasePatientRequest {
+            reason: "Patient request".to_string(),
+            confirmation_token: None,
+        }),
+    ).await.expect("admin should get a confirmation token").0;
+
+    assert_eq!(response["confirmation_required"], true);
+    let confirmation_token = response["confirmation_token"].as_str().unwrap().to_string();
+
+    let response = erase_patient(
+        State(config),
+        Path(patient_id),
+        create_auth_header(&token),
+        require_admin(&admin.id),
+        Json(ErasePatientRequest {
+            reason: "Patient request".to_string(),
+            confirmation_token: Some(confirmation_token),
+        }),
+    ).await.expect("confirmed erasure should succeed").0;
+
+    let erasure = &response["erasure"];
+    assert_eq!(erasure["deleted_records"], 3);
+    assert_eq!(erasure["retained_records"][0]["resource_type"], "appointment");
+    assert_eq!(erasure["retained_records"][0]["resource_id"], retained_appointment.to_string());
+    assert_eq!(erasure["retained_records"][1]["resource_type"], "consultation_note");
+    assert_eq!(erasure["retained_records"][1]["resource_id"], retained_note.to_string());
+}
+
+#[tokio::test]
+async fn test_erasure_deletes_stored_files_of_expired_documents() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+    let config = Arc::new(config);
+
+    let admin = TestUser::admin("admin@example.com");
+    let token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let patient_id = Uuid::new_v4();
+    let object = format!("patient-documents/{}/scan.pdf", patient_id);
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patients"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": patient_id,
+            "full_name": "Jane Doe",
+            "erased_at": null
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/documents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "patient_id": patient_id,
+            "file_url": format!("{}/storage/v1/object/public/patient-documents/{}", mock_server.uri(), object),
+            "uploaded_at": "2005-01-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    for table in [
+        "appointments", "prescriptions", "health_profiles", "health_profile_changes", "intake_responses",
+        "video_sessions", "telemedicine_readiness_reports",
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/rest/v1/{}", table)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+    }
+
+    // The file behind the public URL goes along with its row
+    Mock::given(method("DELETE"))
+        .and(path(format!("/storage/v1/object/patient-documents/{}", object)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "message": "Successfully deleted" })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("DELETE"))
+        .and(path("/rest/v1/documents"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    for table in ["patient_consents", "patients", "doctor_reviews"] {
+        Mock::given(method("PATCH"))
+            .and(path(format!("/rest/v1/{}", table)))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&mock_server)
+            .await;
+    }
+    for table in ["patient_erasures", "audit_log"] {
+        Mock::given(method("POST"))
+            .and(path(format!("/rest/v1/{}", table)))
+            .respond_with(ResponseTemplate::new(201))
+            .mount(&mock_server)
+            .await;
+    }
+
+    let confirmation_token = patient_cell::services::erasure::ErasureService::new(&config)
+        .confirmation_token(patient_id, &admin.id);
+    let response = erase_patient(
+        State(config),
+        Path(patient_id),
+        create_auth_header(&token),
+        require_admin(&admin.id),
+        Json(ErasePatientRequest {
+            reason: "Patient request".to_string(),
+            confirmation_token: Some(confirmation_token),
+        }),
+    ).await.expect("confirmed erasure should succeed").0;
+
+    assert_eq!(response["erasure"]["deleted_records"], 1);
+}
+
+#[tokio::test]
+async fn test_erasure_rejects_wrong_confirmation_token() {
+    let config = TestConfig::default().to_app_config();
+    let admin = TestUser::admin("admin@example.com");
+    let token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+
+    let result = erase_patient(
+        State(Arc::new(config)),
+        Path(Uuid::new_v4()),
+        create_auth_header(&token),
+        require_admin(&admin.id),
+        Json(ErasePatientRequest {
+            reason: "Patient request".to_string(),
+            confirmation_token: Some("not-a-token".to_string()),
+        }),
+    ).await;
+
+    let err = result.expect_err("a forged token must be rejected");
+    assert_eq!(err.code(), "patient.invalid_confirmation");
+}
+
+#[tokio::test]
+async fn test_audit_search_pages_with_cursor() {
+    let mock_server = MockServer::start().await;
+    let mut config = TestConfig::default().to_app_config();
+    config.supabase_url = mock_server.uri();
+
+    let admin = TestUser::admin("admin@example.com");
+    let token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+    let entry = |id: Uuid, created_at: &str| json!({
+        "id": id,
+        "actor_id": admin.id,
+        "action": "patient.erased",
+        "resource_type": "patient",
+        "resource_id": "p-1",
+        "details": {},
+        "created_at": created_at
+    });
+
+    // limit=2 fetches one extra row to tell whether another page follows
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/audit_log"))
+        .and(query_param("action", "eq.patient.erased"))
+        .and(query_param("limit", "3"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            entry(ids[0], "2025-03-01T10:00:00Z"),
+            entry(ids[1], "2025-03-01T09:00:00Z"),
+            entry(ids[2], "2025-03-01T09:00:00Z"),
+        ])))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let query = |cursor: Option<String>| Query(AuditLogQuery {
+        actor_id: None,
+        action: Some("patient.erased".to_string()),
+        resource_type: None,
+        resource_id: None,
+        from: None,
+        to: None,
+        cursor,
+        limit: Some(2),
+    });
+
+    let first = search_audit_log(
+        State(Arc::new(config.clone())),
+        query(None),
+        create_auth_header(&token),
+        require_admin(&admin.id),
+    ).await.expect("admins can search the audit log").0;
+
+    assert_eq!(first["total"], 2);
+    assert_eq!(first["entries"][1]["id"], ids[1].to_string());
+    let cursor = first["next_cursor"].as_str().expect("a further page exists").to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/audit_log"))
+        .and(query_param(
+            "or",
+            format!("(created_at.lt.2025-03-01T09:00:00.000000Z,and(created_at.eq.2025-03-01T09:00:00.000000Z,id.lt.{}))", ids[1]),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            entry(ids[2], "2025-03-01T09:00:00Z"),
+        ])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let second = search_audit_log(
+        State(Arc::new(config.clone())),
+        query(Some(cursor)),
+        create_auth_header(&token),
+        require_admin(&admin.id),
+    ).await.unwrap().0;
+
+    assert_eq!(second["total"], 1);
+    assert!(second["next_cursor"].is_null());
+
+    // search_audit_log takes a RequireRole<Admin>, so a patient is turned
+    // away before the handler runs
+    let patient = TestUser::patient("patient@example.com");
+    let result = RequireRole::<Admin>::check(create_test_user_extension("patient", &patient.id).0);
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
+}
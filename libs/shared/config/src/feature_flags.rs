@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::Serialize;
+
+/// Smart booking (`POST /appointments/smart-book`): doctor matching based on
+/// consultation history
+pub const SMART_BOOKING: &str = "smart_booking";
+
+/// Flags that are on unless FEATURE_FLAGS says otherwise. Every other flag
+/// is off by default.
+const DEFAULT_ON: [&str; 1] = [SMART_BOOKING];
+
+/// Who a flag is being checked for
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlagContext<'a> {
+    pub user_id: Option<&'a str>,
+    pub role: Option<&'a str>,
+}
+
+impl<'a> FlagContext<'a> {
+    pub fn new(user_id: &'a str, role: Option<&'a str>) -> Self {
+        Self { user_id: Some(user_id), role }
+    }
+}
+
+/// Who a flag is on for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "rollout", content = "value", rename_all = "snake_case")]
+pub enum FlagRule {
+    On,
+    Off,
+    Roles(BTreeSet<String>),
+    Users(BTreeSet<String>),
+    /// A stable share of users, by a hash of the flag and the user id
+    Percent(u8),
+}
+
+/// Feature flags for gradual rollout, read from FEATURE_FLAGS at startup.
+/// The spec is `;`-separated `flag=rule` entries, where a rule is `on`,
+/// `off`, `roles:admin,doctor`, `users:<id>,<id>` or `percent:25`, e.g.
+/// `smart_booking=roles:admin;new_intake=percent:10`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeatureFlags {
+    rules: BTreeMap<String, FlagRule>,
+}
+
+impl FeatureFlags {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut rules = BTreeMap::new();
+        for entry in spec.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (flag, rule) = entry.split_once('=')
+                .ok_or_else(|| format!("'{}' is not of the form flag=rule", entry))?;
+            let flag = flag.trim();
+            if flag.is_empty() || !flag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid flag name '{}'", flag));
+            }
+            let rule = parse_rule(rule.trim()).map_err(|e| format!("{}: {}", flag, e))?;
+            if rules.insert(flag.to_string(), rule).is_some() {
+                return Err(format!("Flag {} is set more than once", flag));
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_enabled(&self, flag: &str, context: &FlagContext) -> bool {
+        match self.rule(flag) {
+            FlagRule::On => true,
+            FlagRule::Off => false,
+            FlagRule::Roles(roles) => context.role.is_some_and(|role| roles.contains(role)),
+            FlagRule::Users(users) => context.user_id.is_some_and(|user_id| users.contains(user_id)),
+            FlagRule::Percent(percent) => context.user_id
+                .is_some_and(|user_id| rollout_bucket(flag, user_id) < percent as u64),
+        }
+    }
+
+    /// The rule in force for `flag`, falling back to its default
+    pub fn rule(&self, flag: &str) -> FlagRule {
+        self.rules.get(flag).cloned().unwrap_or_else(|| {
+            if DEFAULT_ON.contains(&flag) {
+                FlagRule::On
+            } else {
+                FlagRule::Off
+            }
+        })
+    }
+
+    /// Every configured flag plus the default-on ones, with their rules
+    pub fn all(&self) -> BTreeMap<String, FlagRule> {
+        let mut all: BTreeMap<String, FlagRule> = DEFAULT_ON.iter()
+            .map(|flag| (flag.to_string(), FlagRule::On))
+            .collect();
+        all.extend(self.rules.clone());
+        all
+    }
+}
+
+fn parse_rule(rule: &str) -> Result<FlagRule, String> {
+    let list = |values: &str| -> Result<BTreeSet<String>, String> {
+        let values: BTreeSet<String> = values.split(',')
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect();
+        if values.is_empty() {
+            return Err("an empty list would never match".to_string());
+        }
+        Ok(values)
+    };
+
+    match rule.split_once(':') {
+        None if rule.eq_ignore_ascii_case("on") => Ok(FlagRule::On),
+        None if rule.eq_ignore_ascii_case("off") => Ok(FlagRule::Off),
+        Some(("roles", roles)) => list(roles).map(FlagRule::Roles),
+        Some(("users", users)) => list(users).map(FlagRule::Users),
+        Some(("percent", percent)) => match percent.trim().parse::<u8>() {
+            Ok(percent) if percent <= 100 => Ok(FlagRule::Percent(percent)),
+            _ => Err(format!("'{}' is not a percentage from 0 to 100", percent)),
+        },
+        _ => Err(format!("unknown rule '{}'", rule)),
+    }
+}
+
+/// 0-99, the same for a given flag and user on every instance and restart
+/// (FNV-1a, rather than std's hasher whose output may change between releases)
+fn rollout_bucket(flag: &str, user_id: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in flag.bytes().chain([b':']).chain(user_id.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash % 100
+}
+
+impl fmt::Display for FlagRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |values: &BTreeSet<String>| values.iter().cloned().collect::<Vec<_>>().join(",");
+        match self {
+            FlagRule::On => write!(f, "on"),
+            FlagRule::Off => write!(f, "off"),
+            FlagRule::Roles(roles) => write!(f, "roles:{}", join(roles)),
+            FlagRule::Users(users) => write!(f, "users:{}", join(users)),
+            FlagRule::Percent(percent) => write!(f, "percent:{}", percent),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_evaluate() {
+        let flags = FeatureFlags::parse(
+            "smart_booking=off; scheduler=roles:admin,doctor; beta=users:u1,u2; cohort=percent:100; dark=percent:0"
+        ).unwrap();
+
+        let admin = FlagContext::new("u9", Some("admin"));
+        let patient = FlagContext::new("u1", Some("patient"));
+
+        assert!(!flags.is_enabled(SMART_BOOKING, &admin));
+        assert!(flags.is_enabled("scheduler", &admin));
+        assert!(!flags.is_enabled("scheduler", &patient));
+        assert!(flags.is_enabled("beta", &patient));
+        assert!(!flags.is_enabled("beta", &admin));
+        assert!(flags.is_enabled("cohort", &patient));
+        assert!(!flags.is_enabled("dark", &patient));
+        assert!(!flags.is_enabled("cohort", &FlagContext::default()));
+        assert!(!flags.is_enabled("unknown", &admin));
+    }
+
+    #[test]
+    fn test_defaults_apply_when_unset() {
+        let flags = FeatureFlags::default();
+        assert!(flags.is_enabled(SMART_BOOKING, &FlagContext::default()));
+        assert_eq!(flags.all().get(SMART_BOOKING), Some(&FlagRule::On));
+    }
+
+    #[test]
+    fn test_percent_rollout_is_stable_and_roughly_proportional() {
+        let flags = FeatureFlags::parse("cohort=percent:30").unwrap();
+        let user_ids: Vec<String> = (0..1000).map(|i| format!("user-{}", i)).collect();
+        let enabled = user_ids.iter()
+            .filter(|id| flags.is_enabled("cohort", &FlagContext::new(id, None)))
+            .count();
+        assert!((250..350).contains(&enabled), "{} of 1000 enabled", enabled);
+
+        assert_eq!(rollout_bucket("cohort", "user-1"), rollout_bucket("cohort", "user-1"));
+    }
+
+    #[test]
+    fn test_rejects_malformed_specs() {
+        assert!(FeatureFlags::parse("smart_booking").is_err());
+        assert!(FeatureFlags::parse("a=percent:101").is_err());
+        assert!(FeatureFlags::parse("a=roles:").is_err());
+        assert!(FeatureFlags::parse("a=sometimes").is_err());
+        assert!(FeatureFlags::parse("a=on;a=off").is_err());
+        assert!(FeatureFlags::parse(" ; ").unwrap().all().len() == DEFAULT_ON.len());
+    }
+}
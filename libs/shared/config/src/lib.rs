@@ -1,24 +1,82 @@
 use std::env;
-use tracing::warn;
+use std::net::IpAddr;
+use tracing::{info, warn};
+use url::Url;
+
+pub mod feature_flags;
+
+pub use feature_flags::{FeatureFlags, FlagContext, FlagRule};
+
+/// Cloudflare Realtime's production API
+pub const DEFAULT_CLOUDFLARE_REALTIME_BASE_URL: &str = "https://rtc.live.cloudflare.com/v1";
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub supabase_url: String,
     pub supabase_anon_key: String,
     pub supabase_jwt_secret: String,
+    pub supabase_jwks_url: String,
+    pub supabase_slow_query_threshold_ms: u64,
+    pub jwks_cache_ttl_seconds: u64,
     pub cloudflare_realtime_app_id: String,
     pub cloudflare_realtime_api_token: String,
     pub cloudflare_realtime_base_url: String,
+    pub video_join_url_ttl_minutes: i64,
+    pub video_session_reconcile_interval_minutes: u64,
+    pub video_join_failure_alert_percent: u32,
+    /// Accept plain-http webhook URLs; for local development only
+    pub webhook_allow_insecure_urls: bool,
+    /// Key for the `webhook_worker` database role, which may only read
+    /// webhook subscriptions, work through the webhook outbox and write the
+    /// delivery log. Without it webhooks are neither queued nor delivered.
+    pub webhook_worker_key: Option<String>,
+    pub availability_cache_ttl_seconds: u64,
+    pub appointment_stats_cache_ttl_seconds: u64,
+    pub batch_conflict_check_concurrency: usize,
+    pub batch_conflict_check_timeout_seconds: u64,
+    pub batch_conflict_check_max_size: usize,
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allow_all: bool,
+    /// Load balancers and proxies in front of the API. `X-Forwarded-For` is
+    /// only believed when the connection comes from one of them.
+    pub trusted_proxies: Vec<IpAddr>,
+    pub graceful_shutdown_timeout_seconds: u64,
+    pub response_compression_enabled: bool,
+    pub clinic_name: String,
+    pub clinic_address: String,
+    pub clinic_contact: String,
+    /// Shown to patients who need help; unset shows none rather than a default
+    pub support_email: Option<String>,
+    /// Base URL of the patient help pages
+    pub help_url_base: Option<String>,
+    /// The clinic this deployment serves. When set, every table is read
+    /// and written for this clinic only.
+    pub clinic_id: Option<String>,
+    pub clinical_record_retention_years: u32,
+    pub max_document_upload_bytes: usize,
+    pub allow_any_doctor_profile_access: bool,
+    pub appointment_rules: AppointmentRuleOverrides,
+    pub feature_flags: FeatureFlags,
 }
 
 impl AppConfig {
     pub fn from_env() -> Self {
+        let supabase_url = env::var("SUPABASE_URL")
+            .unwrap_or_else(|_| {
+                warn!("SUPABASE_URL not set, using empty value");
+                String::new()
+            });
+
+        // Supabase Auth publishes its asymmetric signing keys here
+        let supabase_jwks_url = env::var("SUPABASE_JWKS_URL")
+            .unwrap_or_else(|_| if supabase_url.is_empty() {
+                String::new()
+            } else {
+                format!("{}/auth/v1/.well-known/jwks.json", supabase_url.trim_end_matches('/'))
+            });
+
         let config = Self {
-            supabase_url: env::var("SUPABASE_URL")
-                .unwrap_or_else(|_| {
-                    warn!("SUPABASE_URL not set, using empty value");
-                    String::new()
-                }),
+            supabase_url,
             supabase_anon_key: env::var("SUPABASE_ANON_PUBLIC_KEY")
                 .unwrap_or_else(|_| {
                     warn!("SUPABASE_ANON_PUBLIC_KEY not set, using empty value");
@@ -29,6 +87,16 @@ impl AppConfig {
                     warn!("SUPABASE_JWT_SECRET not set, using empty value");
                     String::new()
                 }),
+            supabase_jwks_url,
+            // Supabase calls slower than this are logged; 0 turns the check off
+            supabase_slow_query_threshold_ms: env::var("SUPABASE_SLOW_QUERY_THRESHOLD_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+            jwks_cache_ttl_seconds: env::var("JWKS_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
             cloudflare_realtime_app_id: env::var("CLOUDFLARE_REALTIME_APP_ID")
                 .unwrap_or_else(|_| {
                     warn!("CLOUDFLARE_REALTIME_APP_ID not set, using empty value");
@@ -42,13 +110,143 @@ impl AppConfig {
             cloudflare_realtime_base_url: env::var("CLOUDFLARE_REALTIME_BASE_URL")
                 .unwrap_or_else(|_| {
                     warn!("CLOUDFLARE_REALTIME_BASE_URL not set, using default");
-                    "https://rtc.live.cloudflare.com/v1".to_string()
+                    DEFAULT_CLOUDFLARE_REALTIME_BASE_URL.to_string()
+                }),
+            video_join_url_ttl_minutes: env::var("VIDEO_JOIN_URL_TTL_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(|| {
+                    warn!("VIDEO_JOIN_URL_TTL_MINUTES not set or invalid, using default");
+                    120
                 }),
+            // 0 turns off the background sweep for orphaned video sessions
+            video_session_reconcile_interval_minutes: env::var("VIDEO_SESSION_RECONCILE_INTERVAL_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15),
+            // Alert when this share of recent video joins fail; 0 turns the alert off
+            video_join_failure_alert_percent: env::var("VIDEO_JOIN_FAILURE_ALERT_PERCENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+            webhook_allow_insecure_urls: env::var("WEBHOOK_ALLOW_INSECURE_URLS")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            webhook_worker_key: env::var("WEBHOOK_WORKER_KEY")
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()),
+            // How long computed doctor slots are reused; 0 turns the cache off
+            availability_cache_ttl_seconds: env::var("AVAILABILITY_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            // How long rebuilt appointment stats are served; 0 turns the cache off
+            appointment_stats_cache_ttl_seconds: env::var("APPOINTMENT_STATS_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(900),
+            // Conflict checks in a batch run this many at a time, each given
+            // this long, and a batch can hold at most this many
+            batch_conflict_check_concurrency: env::var("BATCH_CONFLICT_CHECK_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            batch_conflict_check_timeout_seconds: env::var("BATCH_CONFLICT_CHECK_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            batch_conflict_check_max_size: env::var("BATCH_CONFLICT_CHECK_MAX_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            cors_allowed_origins: env::var("CORS_ALLOWED_ORIGINS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|origin| origin.trim().to_string())
+                        .filter(|origin| !origin.is_empty())
+                        .collect()
+                })
+                .unwrap_or_else(|_| {
+                    warn!("CORS_ALLOWED_ORIGINS not set, cross-origin requests will be rejected");
+                    Vec::new()
+                }),
+            cors_allow_all: env::var("CORS_ALLOW_ALL")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            graceful_shutdown_timeout_seconds: env::var("GRACEFUL_SHUTDOWN_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            response_compression_enabled: env::var("RESPONSE_COMPRESSION")
+                .map(|v| !v.eq_ignore_ascii_case("false"))
+                .unwrap_or(true),
+            clinic_name: env::var("CLINIC_NAME")
+                .unwrap_or_else(|_| "Amae Clinic".to_string()),
+            clinic_address: env::var("CLINIC_ADDRESS").unwrap_or_default(),
+            clinic_contact: env::var("CLINIC_CONTACT").unwrap_or_default(),
+            support_email: env::var("SUPPORT_EMAIL")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| validate_support_email(v.trim()).unwrap_or_else(|e| panic!("Invalid SUPPORT_EMAIL: {}", e))),
+            help_url_base: env::var("HELP_URL_BASE")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| match validate_https_url(&v) {
+                    Ok(_) => v.trim().trim_end_matches('/').to_string(),
+                    Err(e) => panic!("Invalid HELP_URL_BASE: {}", e),
+                }),
+            clinic_id: env::var("CLINIC_ID")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| validate_clinic_id(v.trim()).unwrap_or_else(|e| panic!("Invalid CLINIC_ID: {}", e))),
+            clinical_record_retention_years: env::var("CLINICAL_RECORD_RETENTION_YEARS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| proxy.parse().unwrap_or_else(|e| panic!("Invalid TRUSTED_PROXIES entry {}: {}", proxy, e)))
+                .collect(),
+            max_document_upload_bytes: env::var("MAX_DOCUMENT_UPLOAD_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10 * 1024 * 1024),
+            // Small single-clinic deployments can let every doctor see every profile
+            allow_any_doctor_profile_access: env::var("ALLOW_ANY_DOCTOR_PROFILE_ACCESS")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            appointment_rules: AppointmentRuleOverrides::from_env(),
+            feature_flags: FeatureFlags::parse(&env::var("FEATURE_FLAGS").unwrap_or_default())
+                .unwrap_or_else(|e| panic!("Invalid FEATURE_FLAGS: {}", e)),
         };
         
         if !config.is_configured() {
             warn!("Application not fully configured - missing environment variables");
         }
+
+        // A malformed override would otherwise only surface as failed video
+        // sessions, or worse, quietly talk to the wrong endpoint
+        match validate_cloudflare_base_url(&config.cloudflare_realtime_base_url) {
+            Ok(url) => info!(
+                "Cloudflare Realtime environment: {} ({})",
+                CloudflareEnvironment::detect(&url),
+                config.cloudflare_realtime_base_url
+            ),
+            Err(e) => panic!("Invalid CLOUDFLARE_REALTIME_BASE_URL: {}", e),
+        }
+
+        match &config.clinic_id {
+            Some(clinic_id) => info!("Serving clinic {}", clinic_id),
+            None => info!("No CLINIC_ID set, data is shared by every clinic"),
+        }
+
+        for (flag, rule) in config.feature_flags.all() {
+            info!("Feature flag {}: {}", flag, rule);
+        }
         
         config
     }
@@ -62,6 +260,176 @@ impl AppConfig {
     pub fn is_video_conferencing_configured(&self) -> bool {
         !self.cloudflare_realtime_app_id.is_empty()
             && !self.cloudflare_realtime_api_token.is_empty()
-            && !self.cloudflare_realtime_base_url.is_empty()
+            && validate_cloudflare_base_url(&self.cloudflare_realtime_base_url).is_ok()
+    }
+}
+
+/// Which Cloudflare Realtime deployment a base URL points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudflareEnvironment {
+    Production,
+    Staging,
+    Custom,
+}
+
+impl CloudflareEnvironment {
+    pub fn detect(url: &Url) -> Self {
+        let host = url.host_str().unwrap_or_default();
+        if host == "rtc.live.cloudflare.com" {
+            Self::Production
+        } else if host.contains("staging") {
+            Self::Staging
+        } else {
+            Self::Custom
+        }
+    }
+}
+
+impl std::fmt::Display for CloudflareEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Production => write!(f, "production"),
+            Self::Staging => write!(f, "staging"),
+            Self::Custom => write!(f, "custom"),
+        }
     }
-}
\ No newline at end of file
+}
+
+/// The Cloudflare base URL must be an absolute https URL. Plain http is only
+/// accepted for localhost, where local stubs and test servers run.
+pub fn validate_cloudflare_base_url(value: &str) -> Result<Url, String> {
+    validate_https_url(value)
+}
+
+/// An absolute https URL, or plain http on localhost
+pub fn validate_https_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value.trim()).map_err(|e| format!("'{}' is not an absolute URL: {}", value, e))?;
+
+    let host = url.host_str().unwrap_or_default();
+    if host.is_empty() {
+        return Err(format!("'{}' has no host", value));
+    }
+
+    let is_loopback = host == "localhost" || host == "127.0.0.1" || host == "[::1]";
+    match url.scheme() {
+        "https" => Ok(url),
+        "http" if is_loopback => Ok(url),
+        scheme => Err(format!("'{}' must use https, not {}", value, scheme)),
+    }
+}
+
+/// Just enough to catch typos and placeholder values: one `@`, a local
+/// part and a dotted domain, no whitespace
+pub fn validate_support_email(value: &str) -> Result<String, String> {
+    let valid = value.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && !domain.contains('@')
+            && domain.split('.').count() >= 2
+            && domain.split('.').all(|label| !label.is_empty())
+    }) && !value.chars().any(char::is_whitespace);
+
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("'{}' is not an email address", value))
+    }
+}
+
+/// The clinic id goes into every scoped query string, so only plain
+/// identifiers are accepted
+pub fn validate_clinic_id(value: &str) -> Result<String, String> {
+    if value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(value.to_string())
+    } else {
+        Err(format!("'{}' may only contain letters, digits, '-' and '_'", value))
+    }
+}
+
+/// Per-deployment overrides for the appointment booking rules. Unset values
+/// keep the built-in defaults; appointment-cell checks the combined rules.
+#[derive(Debug, Clone, Default)]
+pub struct AppointmentRuleOverrides {
+    pub min_advance_booking_hours: Option<i32>,
+    pub max_advance_booking_days: Option<i32>,
+    pub cancellation_notice_hours: Option<i32>,
+    pub reschedule_notice_hours: Option<i32>,
+    /// Active appointments a patient may have on one day; unset is unlimited
+    pub max_appointments_per_day: Option<i32>,
+    pub min_duration_minutes: Option<i32>,
+    pub max_duration_minutes: Option<i32>,
+    /// Hours routine appointments may be booked in, e.g.
+    /// `mon-fri 08:00-18:00, sat 09:00-13:00`. Unset allows any hour.
+    pub business_hours: Option<String>,
+    /// IANA timezone the business hours are in; UTC when unset
+    pub business_timezone: Option<String>,
+    /// Notice (hours) below which a patient cancellation incurs a fee, as
+    /// `key=hours` pairs keyed by appointment type, `doctor:<id>` or
+    /// `default`, e.g. `default=48, urgent=0`. Unset never flags a fee.
+    pub cancellation_fee_hours: Option<String>,
+}
+
+impl AppointmentRuleOverrides {
+    fn from_env() -> Self {
+        Self {
+            min_advance_booking_hours: env_i32("APPOINTMENT_MIN_ADVANCE_BOOKING_HOURS"),
+            max_advance_booking_days: env_i32("APPOINTMENT_MAX_ADVANCE_BOOKING_DAYS"),
+            cancellation_notice_hours: env_i32("APPOINTMENT_CANCELLATION_NOTICE_HOURS"),
+            reschedule_notice_hours: env_i32("APPOINTMENT_RESCHEDULE_NOTICE_HOURS"),
+            max_appointments_per_day: env_i32("APPOINTMENT_MAX_PER_DAY"),
+            min_duration_minutes: env_i32("APPOINTMENT_MIN_DURATION_MINUTES"),
+            max_duration_minutes: env_i32("APPOINTMENT_MAX_DURATION_MINUTES"),
+            business_hours: env::var("CLINIC_BUSINESS_HOURS").ok().filter(|v| !v.trim().is_empty()),
+            business_timezone: env::var("CLINIC_TIMEZONE").ok().filter(|v| !v.trim().is_empty()),
+            cancellation_fee_hours: env::var("APPOINTMENT_CANCELLATION_FEE_HOURS").ok().filter(|v| !v.trim().is_empty()),
+        }
+    }
+}
+
+fn env_i32(name: &str) -> Option<i32> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            warn!("{} is not a whole number, using default", name);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_cloudflare_base_url() {
+        assert!(validate_cloudflare_base_url(DEFAULT_CLOUDFLARE_REALTIME_BASE_URL).is_ok());
+        assert!(validate_cloudflare_base_url("http://127.0.0.1:8080").is_ok());
+
+        assert!(validate_cloudflare_base_url("").is_err());
+        assert!(validate_cloudflare_base_url("rtc.live.cloudflare.com/v1").is_err());
+        assert!(validate_cloudflare_base_url("http://rtc.live.cloudflare.com/v1").is_err());
+        assert!(validate_cloudflare_base_url("ftp://rtc.live.cloudflare.com/v1").is_err());
+    }
+
+    #[test]
+    fn test_validate_support_email() {
+        assert!(validate_support_email("support@clinic.example").is_ok());
+        assert!(validate_support_email("help.desk@mail.clinic.ie").is_ok());
+
+        assert!(validate_support_email("support").is_err());
+        assert!(validate_support_email("@clinic.example").is_err());
+        assert!(validate_support_email("support@localhost").is_err());
+        assert!(validate_support_email("support@clinic..ie").is_err());
+        assert!(validate_support_email("sup port@clinic.ie").is_err());
+        assert!(validate_support_email("a@b@clinic.ie").is_err());
+    }
+
+    #[test]
+    fn test_detect_cloudflare_environment() {
+        let detect = |value: &str| CloudflareEnvironment::detect(&Url::parse(value).unwrap());
+
+        assert_eq!(detect(DEFAULT_CLOUDFLARE_REALTIME_BASE_URL), CloudflareEnvironment::Production);
+        assert_eq!(detect("https://rtc.staging.cloudflare.com/v1"), CloudflareEnvironment::Staging);
+        assert_eq!(detect("http://localhost:9000"), CloudflareEnvironment::Custom);
+    }
+}
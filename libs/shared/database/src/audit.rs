@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::debug;
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+
+use crate::supabase::SupabaseClient;
+
+/// One entry in the `audit_log` table: who did what to which record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub actor_id: String,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: String,
+    #[serde(default)]
+    pub details: Value,
+}
+
+impl AuditEntry {
+    pub fn new(
+        actor_id: impl Into<String>,
+        action: impl Into<String>,
+        resource_type: impl Into<String>,
+        resource_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            actor_id: actor_id.into(),
+            action: action.into(),
+            resource_type: resource_type.into(),
+            resource_id: resource_id.into(),
+            details: Value::Null,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// An entry as stored, with the columns the database fills in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredAuditEntry {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    pub created_at: DateTime<Utc>,
+}
+
+pub const DEFAULT_AUDIT_PAGE_SIZE: usize = 100;
+pub const MAX_AUDIT_PAGE_SIZE: usize = 1000;
+
+/// Position after the last entry of a page. Entries are ordered newest
+/// first by (created_at, id), so the cursor stays valid while new entries
+/// are written and pages never skip or repeat rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl AuditCursor {
+    /// Opaque form handed to clients as `next_cursor`
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true), self.id);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let raw = URL_SAFE_NO_PAD.decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| anyhow!("Invalid audit cursor"))?;
+        let (created_at, id) = raw.split_once('|').ok_or_else(|| anyhow!("Invalid audit cursor"))?;
+
+        Ok(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at)
+                .map_err(|_| anyhow!("Invalid audit cursor"))?
+                .with_timezone(&Utc),
+            id: Uuid::parse_str(id).map_err(|_| anyhow!("Invalid audit cursor"))?,
+        })
+    }
+}
+
+/// Filters for reading the audit trail. Unset filters match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub actor_id: Option<String>,
+    pub action: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub cursor: Option<AuditCursor>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    /// Filter values go into the PostgREST query string, so only plain
+    /// identifiers are accepted
+    pub fn validate(&self) -> Result<()> {
+        for (column, value) in self.equality_filters() {
+            if let Some(value) = value {
+                if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:@".contains(c)) {
+                    return Err(anyhow!("Invalid {} filter", column));
+                }
+            }
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                return Err(anyhow!("from must be before to"));
+            }
+        }
+        Ok(())
+    }
+
+    fn equality_filters(&self) -> [(&'static str, &Option<String>); 4] {
+        [
+            ("actor_id", &self.actor_id),
+            ("action", &self.action),
+            ("resource_type", &self.resource_type),
+            ("resource_id", &self.resource_id),
+        ]
+    }
+
+    fn to_path(&self) -> Result<String> {
+        self.validate()?;
+
+        let mut filters = Vec::new();
+        for (column, value) in self.equality_filters() {
+            if let Some(value) = value {
+                filters.push(format!("{}=eq.{}", column, value));
+            }
+        }
+        if let Some(from) = self.from {
+            filters.push(format!("created_at=gte.{}", from.to_rfc3339_opts(SecondsFormat::Micros, true)));
+        }
+        if let Some(to) = self.to {
+            filters.push(format!("created_at=lt.{}", to.to_rfc3339_opts(SecondsFormat::Micros, true)));
+        }
+        if let Some(cursor) = self.cursor {
+            let created_at = cursor.created_at.to_rfc3339_opts(SecondsFormat::Micros, true);
+            filters.push(format!(
+                "or=(created_at.lt.{},and(created_at.eq.{},id.lt.{}))",
+                created_at, created_at, cursor.id
+            ));
+        }
+
+        // One extra row tells us whether there is another page
+        filters.push("order=created_at.desc,id.desc".to_string());
+        filters.push(format!("limit={}", self.page_size() + 1));
+
+        Ok(format!("/rest/v1/audit_log?{}", filters.join("&")))
+    }
+
+    fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_AUDIT_PAGE_SIZE).clamp(1, MAX_AUDIT_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditPage {
+    pub entries: Vec<StoredAuditEntry>,
+    /// Pass back as `cursor` for the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Append-only audit trail shared by all cells
+pub struct AuditLog {
+    supabase: SupabaseClient,
+}
+
+impl AuditLog {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            supabase: SupabaseClient::new(config),
+        }
+    }
+
+    pub async fn record(&self, entry: AuditEntry, auth_token: &str) -> Result<()> {
+        debug!("Audit: {} {} {}/{}", entry.actor_id, entry.action, entry.resource_type, entry.resource_id);
+
+        let entry_data = json!({
+            "actor_id": entry.actor_id,
+            "action": entry.action,
+            "resource_type": entry.resource_type,
+            "resource_id": entry.resource_id,
+            "details": entry.details,
+            "created_at": Utc::now().to_rfc3339()
+        });
+
+        let _: Value = self.supabase.request_with_headers(
+            Method::POST,
+            "/rest/v1/audit_log",
+            Some(auth_token),
+            Some(entry_data),
+            None,
+        ).await?;
+
+        Ok(())
+    }
+
+    /// One page of entries, newest first
+    pub async fn query(&self, query: &AuditQuery, auth_token: &str) -> Result<AuditPage> {
+        let path = query.to_path()?;
+        let result: Vec<Value> = self.supabase.request(
+            Method::GET,
+            &path,
+            Some(auth_token),
+            None,
+        ).await?;
+
+        let mut entries = result.into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<StoredAuditEntry>, _>>()?;
+
+        let page_size = query.page_size();
+        let next_cursor = if entries.len() > page_size {
+            entries.truncate(page_size);
+            entries.last().map(|last| AuditCursor { created_at: last.created_at, id: last.id }.encode())
+        } else {
+            None
+        };
+
+        Ok(AuditPage { entries, next_cursor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = AuditCursor {
+            created_at: DateTime::parse_from_rfc3339("2025-03-01T09:30:00.123456Z").unwrap().with_timezone(&Utc),
+            id: Uuid::new_v4(),
+        };
+
+        assert_eq!(AuditCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(AuditCursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_query_path_uses_keyset_after_cursor() {
+        let id = Uuid::new_v4();
+        let query = AuditQuery {
+            action: Some("doctor.verification_approved".to_string()),
+            cursor: Some(AuditCursor {
+                created_at: DateTime::parse_from_rfc3339("2025-03-01T09:30:00Z").unwrap().with_timezone(&Utc),
+                id,
+            }),
+            limit: Some(50),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            query.to_path().unwrap(),
+            format!(
+                "/rest/v1/audit_log?action=eq.doctor.verification_approved\
+                 &or=(created_at.lt.2025-03-01T09:30:00.000000Z,and(created_at.eq.2025-03-01T09:30:00.000000Z,id.lt.{}))\
+                 &order=created_at.desc,id.desc&limit=51",
+                id
+            )
+        );
+
+        let injected = AuditQuery { actor_id: Some("x&limit=1".to_string()), ..Default::default() };
+        assert!(injected.to_path().is_err());
+    }
+}
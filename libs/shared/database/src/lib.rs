@@ -1 +1,3 @@
+pub mod audit;
+pub mod metrics;
 pub mod supabase;
\ No newline at end of file
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+
+use crate::supabase::slow_query_count;
+
+/// Why a Supabase call failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupabaseErrorKind {
+    Timeout,
+    /// The request never got a response: DNS, connection refused, reset
+    Network,
+    ClientError,
+    ServerError,
+    /// A successful response whose body wasn't what the caller expected
+    Parse,
+}
+
+impl SupabaseErrorKind {
+    pub fn all() -> [Self; 5] {
+        [Self::Timeout, Self::Network, Self::ClientError, Self::ServerError, Self::Parse]
+    }
+
+    pub fn from_status(status: StatusCode) -> Self {
+        if status.is_server_error() {
+            Self::ServerError
+        } else {
+            Self::ClientError
+        }
+    }
+
+    pub fn from_transport(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout
+        } else if error.is_decode() {
+            Self::Parse
+        } else {
+            Self::Network
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Network => "network",
+            Self::ClientError => "4xx",
+            Self::ServerError => "5xx",
+            Self::Parse => "parse",
+        }
+    }
+}
+
+impl fmt::Display for SupabaseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Calls and failures for one method and normalized path
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EndpointStats {
+    pub calls: u64,
+    pub errors: BTreeMap<SupabaseErrorKind, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointMetrics {
+    pub method: String,
+    pub path: String,
+    #[serde(flatten)]
+    pub stats: EndpointStats,
+}
+
+type EndpointTable = BTreeMap<(String, String), EndpointStats>;
+
+/// Process-wide, like the slow query count. Paths are normalized before
+/// they're recorded so ids never become labels.
+fn endpoints() -> &'static Mutex<EndpointTable> {
+    static ENDPOINTS: OnceLock<Mutex<EndpointTable>> = OnceLock::new();
+    ENDPOINTS.get_or_init(Default::default)
+}
+
+pub(crate) fn record_call(method: &Method, path: &str) {
+    let mut endpoints = endpoints().lock().unwrap_or_else(|e| e.into_inner());
+    endpoints.entry((method.to_string(), normalize_path(path))).or_default().calls += 1;
+}
+
+pub(crate) fn record_error(method: &Method, path: &str, kind: SupabaseErrorKind) {
+    let mut endpoints = endpoints().lock().unwrap_or_else(|e| e.into_inner());
+    *endpoints.entry((method.to_string(), normalize_path(path))).or_default()
+        .errors.entry(kind).or_default() += 1;
+}
+
+/// Every endpoint called since startup, ordered by path then method
+pub fn supabase_call_metrics() -> Vec<EndpointMetrics> {
+    let endpoints = endpoints().lock().unwrap_or_else(|e| e.into_inner());
+    let mut metrics: Vec<EndpointMetrics> = endpoints.iter()
+        .map(|((method, path), stats)| EndpointMetrics {
+            method: method.clone(),
+            path: path.clone(),
+            stats: stats.clone(),
+        })
+        .collect();
+    metrics.sort_by(|a, b| (&a.path, &a.method).cmp(&(&b.path, &b.method)));
+    metrics
+}
+
+/// The endpoint a call went to, without its query string or any ids:
+/// `/rest/v1/<table>`, `/rest/v1/rpc/<function>`, and the first three
+/// segments of anything else (`/storage/v1/object`, `/auth/v1/user`)
+pub fn normalize_path(path: &str) -> String {
+    let path = path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let keep = match segments.as_slice() {
+        ["rest", "v1", "rpc", ..] => 4,
+        _ => 3,
+    };
+    format!("/{}", segments.iter().take(keep).copied().collect::<Vec<_>>().join("/"))
+}
+
+/// Supabase metrics in the Prometheus text exposition format
+pub fn prometheus_text() -> String {
+    let metrics = supabase_call_metrics();
+    let mut out = String::new();
+
+    // Writing to a String can't fail
+    let _ = writeln!(out, "# HELP supabase_requests_total Supabase calls by method and endpoint");
+    let _ = writeln!(out, "# TYPE supabase_requests_total counter");
+    for endpoint in &metrics {
+        let _ = writeln!(
+            out,
+            "supabase_requests_total{{method=\"{}\",path=\"{}\"}} {}",
+            endpoint.method, endpoint.path, endpoint.stats.calls
+        );
+    }
+
+    let _ = writeln!(out, "# HELP supabase_request_errors_total Failed Supabase calls by method, endpoint and kind");
+    let _ = writeln!(out, "# TYPE supabase_request_errors_total counter");
+    for endpoint in &metrics {
+        for kind in SupabaseErrorKind::all() {
+            let count = endpoint.stats.errors.get(&kind).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "supabase_request_errors_total{{method=\"{}\",path=\"{}\",kind=\"{}\"}} {}",
+                endpoint.method, endpoint.path, kind, count
+            );
+        }
+    }
+
+    let _ = writeln!(out, "# HELP supabase_slow_queries_total Supabase calls over the slow query threshold");
+    let _ = writeln!(out, "# TYPE supabase_slow_queries_total counter");
+    let _ = writeln!(out, "supabase_slow_queries_total {}", slow_query_count());
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/rest/v1/appointments?id=eq.42&select=*"), "/rest/v1/appointments");
+        assert_eq!(normalize_path("/rest/v1/rpc/book_slot"), "/rest/v1/rpc/book_slot");
+        assert_eq!(normalize_path("/storage/v1/object/documents/abc/file.pdf"), "/storage/v1/object");
+        assert_eq!(normalize_path("/auth/v1/user"), "/auth/v1/user");
+    }
+
+    #[test]
+    fn test_errors_are_counted_per_endpoint_and_kind() {
+        let path = "/rest/v1/metrics_test_table?id=eq.1";
+        record_call(&Method::GET, path);
+        record_call(&Method::GET, path);
+        record_error(&Method::GET, path, SupabaseErrorKind::ServerError);
+
+        let endpoint = supabase_call_metrics().into_iter()
+            .find(|endpoint| endpoint.path == "/rest/v1/metrics_test_table")
+            .unwrap();
+        assert_eq!(endpoint.stats.calls, 2);
+        assert_eq!(endpoint.stats.errors.get(&SupabaseErrorKind::ServerError), Some(&1));
+
+        let text = prometheus_text();
+        assert!(text.contains("supabase_requests_total{method=\"GET\",path=\"/rest/v1/metrics_test_table\"} 2"));
+        assert!(text.contains(
+            "supabase_request_errors_total{method=\"GET\",path=\"/rest/v1/metrics_test_table\",kind=\"5xx\"} 1"
+        ));
+    }
+}
@@ -1,19 +1,56 @@
 use anyhow::{Result, anyhow};
 use reqwest::{
+    Body,
     Client, 
-    header::{HeaderMap, HeaderValue, CONTENT_TYPE, AUTHORIZATION},
+    RequestBuilder,
+    Response,
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, AUTHORIZATION},
     Method,
 };
 use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
-use tracing::{debug, error};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, warn};
 
 use shared_config::AppConfig;
 
+use crate::metrics::{self, SupabaseErrorKind};
+
+/// Query parameters whose values never make it into logs
+const SENSITIVE_PARAMS: [&str; 5] = ["token", "key", "secret", "password", "signature"];
+
+static SLOW_QUERY_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Number of Supabase calls that exceeded the slow query threshold since startup
+pub fn slow_query_count() -> u64 {
+    SLOW_QUERY_COUNT.load(Ordering::Relaxed)
+}
+
+/// A write rejected by a unique or exclusion constraint (HTTP 409). Callers
+/// that rely on a constraint instead of a read-then-write check look for it
+/// with [`is_conflict`].
+#[derive(Debug)]
+pub struct ConflictError(pub String);
+
+impl std::fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Conflict: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+pub fn is_conflict(error: &anyhow::Error) -> bool {
+    error.is::<ConflictError>()
+}
+
 pub struct SupabaseClient {
     client: Client,
     base_url: String,
     anon_key: String,
+    slow_query_threshold: Option<Duration>,
+    clinic_id: Option<String>,
 }
 
 impl SupabaseClient {
@@ -22,9 +59,94 @@ impl SupabaseClient {
             client: Client::new(),
             base_url: config.supabase_url.clone(),
             anon_key: config.supabase_anon_key.clone(),
+            slow_query_threshold: (config.supabase_slow_query_threshold_ms > 0)
+                .then(|| Duration::from_millis(config.supabase_slow_query_threshold_ms)),
+            clinic_id: config.clinic_id.clone(),
+        }
+    }
+
+    /// `path` restricted to the configured clinic when it targets a
+    /// clinic-scoped table. Inserts are scoped through the body instead.
+    fn scope_path(&self, method: &Method, path: &str) -> String {
+        let Some(clinic_id) = &self.clinic_id else {
+            return path.to_string();
+        };
+        if *method == Method::POST || scoped_table(path).is_none() {
+            return path.to_string();
         }
+
+        let separator = if path.contains('?') { '&' } else { '?' };
+        format!("{}{}clinic_id=eq.{}", path, separator, clinic_id)
+    }
+
+    /// `body` with every row written to a clinic-scoped table stamped with
+    /// the configured clinic, overriding whatever the caller sent
+    fn scope_body(&self, method: &Method, path: &str, body: Option<Value>) -> Option<Value> {
+        let Some(clinic_id) = &self.clinic_id else {
+            return body;
+        };
+        if !matches!(*method, Method::POST | Method::PATCH | Method::PUT) || scoped_table(path).is_none() {
+            return body;
+        }
+        let mut body = body?;
+
+        let rows = match &mut body {
+            Value::Array(rows) => rows.iter_mut().collect(),
+            row => vec![row],
+        };
+        for row in rows {
+            if let Value::Object(fields) = row {
+                fields.insert("clinic_id".to_string(), json!(clinic_id));
+            }
+        }
+        Some(body)
+    }
+
+    /// Warn about a call that took longer than the configured threshold
+    fn record_timing(&self, method: &Method, path: &str, elapsed: Duration) {
+        let Some(threshold) = self.slow_query_threshold else {
+            return;
+        };
+        if elapsed < threshold {
+            return;
+        }
+
+        SLOW_QUERY_COUNT.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            method = %method,
+            path = %redact_path(path),
+            duration_ms = elapsed.as_millis() as u64,
+            "Slow Supabase query"
+        );
     }
     
+    /// Send `req` to the scoped `path`, recording the call and its timing,
+    /// and turn an error status into an error: `ConflictError` for a 409,
+    /// otherwise a message naming the kind of failure
+    async fn send(&self, method: &Method, path: &str, req: RequestBuilder) -> Result<Response> {
+        metrics::record_call(method, path);
+        let started = Instant::now();
+        let response = req.send().await
+            .inspect_err(|e| metrics::record_error(method, path, SupabaseErrorKind::from_transport(e)))?;
+        self.record_timing(method, path, started.elapsed());
+
+        let status = response.status();
+        if !status.is_success() {
+            metrics::record_error(method, path, SupabaseErrorKind::from_status(status));
+            let error_text = response.text().await?;
+            error!("API error ({}): {}", status, error_text);
+
+            return Err(match status.as_u16() {
+                401 | 403 => anyhow!("Authentication error: {}", error_text),
+                404 => anyhow!("Resource not found: {}", error_text),
+                409 => ConflictError(error_text).into(),
+                _ => anyhow!("API error ({}): {}", status, error_text),
+            });
+        }
+
+        Ok(response)
+    }
+
     fn get_headers(&self, auth_token: Option<&str>) -> HeaderMap {
         let mut headers = HeaderMap::new();
         
@@ -45,36 +167,53 @@ impl SupabaseClient {
                             auth_token: Option<&str>, body: Option<Value>) 
                             -> Result<T> 
     where T: DeserializeOwned {
+        let body = self.scope_body(&method, path, body);
+        let path = &self.scope_path(&method, path);
         let url = format!("{}{}", self.base_url, path);
         debug!("Making request to {}", url);
         
         let headers = self.get_headers(auth_token);
         
-        let mut req = self.client.request(method, &url)
+        let mut req = self.client.request(method.clone(), &url)
             .headers(headers);
             
         if let Some(body_data) = body {
             req = req.json(&body_data);
         }
         
-        let response = req.send().await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            error!("API error ({}): {}", status, error_text);
-            
-            return Err(match status.as_u16() {
-                401 | 403 => anyhow!("Authentication error: {}", error_text),
-                404 => anyhow!("Resource not found: {}", error_text),
-                _ => anyhow!("API error ({}): {}", status, error_text),
-            });
-        }
+        let response = self.send(&method, path, req).await?;
         
-        let data = response.json::<T>().await?;
+        let data = response.json::<T>().await
+            .inspect_err(|e| metrics::record_error(&method, path, SupabaseErrorKind::from_transport(e)))?;
         Ok(data)
     }
     
+    /// GET with `Prefer: count=exact`, returning the rows together with the
+    /// total number of matching rows reported in `Content-Range`. The total is
+    /// `None` when PostgREST didn't report one.
+    pub async fn request_with_count<T>(&self, path: &str, auth_token: Option<&str>)
+                                       -> Result<(T, Option<i64>)>
+    where T: DeserializeOwned {
+        let path = &self.scope_path(&Method::GET, path);
+        let url = format!("{}{}", self.base_url, path);
+        debug!("Making counted request to {}", url);
+
+        let mut headers = self.get_headers(auth_token);
+        headers.insert("Prefer", HeaderValue::from_static("count=exact"));
+
+        let req = self.client.request(Method::GET, &url).headers(headers);
+        let response = self.send(&Method::GET, path, req).await?;
+
+        let total = response.headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range_total);
+
+        let data = response.json::<T>().await
+            .inspect_err(|e| metrics::record_error(&Method::GET, path, SupabaseErrorKind::from_transport(e)))?;
+        Ok((data, total))
+    }
+
     pub async fn get_user_profile(&self, _user_id: &str, auth_token: &str) -> Result<Value> {
         // Use the Supabase Auth API to get user data
         let path = "/auth/v1/user";
@@ -108,6 +247,69 @@ impl SupabaseClient {
         Ok(result[0].clone())
     }
 
+    /// Store a file in Supabase Storage. The body goes out as-is, so a
+    /// streamed body is never buffered here.
+    pub async fn upload_object(&self, path: &str, auth_token: Option<&str>,
+                               content_type: &str, content_length: u64, body: Body)
+                               -> Result<()> {
+        let url = format!("{}{}", self.base_url, path);
+        debug!("Uploading {} bytes to {}", content_length, url);
+
+        let mut headers = self.get_headers(auth_token);
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)
+            .map_err(|_| anyhow!("Invalid content type: {}", content_type))?);
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+
+        metrics::record_call(&Method::POST, path);
+        let started = Instant::now();
+        let response = self.client.request(Method::POST, &url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await
+            .inspect_err(|e| metrics::record_error(&Method::POST, path, SupabaseErrorKind::from_transport(e)))?;
+        self.record_timing(&Method::POST, path, started.elapsed());
+
+        let status = response.status();
+        if !status.is_success() {
+            metrics::record_error(&Method::POST, path, SupabaseErrorKind::from_status(status));
+            let error_text = response.text().await?;
+            error!("Storage upload error ({}): {}", status, error_text);
+
+            return Err(match status.as_u16() {
+                401 | 403 => anyhow!("Authentication error: {}", error_text),
+                413 => anyhow!("File rejected by storage as too large: {}", error_text),
+                _ => anyhow!("Storage upload error ({}): {}", status, error_text),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Fetch a stored file by its URL, giving up after `timeout` or once it
+    /// grows past `max_bytes`. The body is read a chunk at a time, so an
+    /// oversize file is never held whole.
+    pub async fn download_object(&self, url: &str, timeout: Duration, max_bytes: usize) -> Result<Vec<u8>> {
+        let mut response = self.client.get(url).timeout(timeout).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!("HTTP {}", status));
+        }
+
+        let too_large = || anyhow!("File is larger than the {} byte limit", max_bytes);
+        if response.content_length().is_some_and(|length| length > max_bytes as u64) {
+            return Err(too_large());
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(too_large());
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
     pub fn get_base_url(&self) -> &str {
         &self.base_url
     }
@@ -136,6 +338,8 @@ impl SupabaseClient {
                                         additional_headers: Option<HeaderMap>) 
                                         -> Result<T> 
     where T: DeserializeOwned + Default {  // Add Default trait bound
+        let body = self.scope_body(&method, path, body);
+        let path = &self.scope_path(&method, path);
         let url = format!("{}{}", self.base_url, path);
         debug!("Making request to {}", url);
     
@@ -148,29 +352,18 @@ impl SupabaseClient {
         }
     }
     
-    let mut req = self.client.request(method, &url)
+    let mut req = self.client.request(method.clone(), &url)
         .headers(headers);
         
     if let Some(body_data) = body {
         req = req.json(&body_data);
     }
     
-    let response = req.send().await?;
-    
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await?;
-        error!("API error ({}): {}", status, error_text);
-        
-        return Err(match status.as_u16() {
-            401 | 403 => anyhow!("Authentication error: {}", error_text),
-            404 => anyhow!("Resource not found: {}", error_text),
-            _ => anyhow!("API error ({}): {}", status, error_text),
-        });
-    }
+    let response = self.send(&method, path, req).await?;
     
     // Using bytes() allows us to keep the body data for debugging
-    let bytes = response.bytes().await?;
+    let bytes = response.bytes().await
+        .inspect_err(|e| metrics::record_error(&method, path, SupabaseErrorKind::from_transport(e)))?;
     
     // If bytes are empty and T: Default, return default value (handles empty responses)
     if bytes.is_empty() {
@@ -185,6 +378,7 @@ impl SupabaseClient {
     let data = match serde_json::from_slice::<T>(&bytes) {
         Ok(parsed) => parsed,
         Err(e) => {
+            metrics::record_error(&method, path, SupabaseErrorKind::Parse);
             error!("Failed to parse response: {} - Raw body: {}", e, body_text);
             return Err(anyhow!("Failed to parse response: {}", e));
         }
@@ -193,4 +387,121 @@ impl SupabaseClient {
     Ok(data)
     }
 
-}
\ No newline at end of file
+}
+
+/// The table or view a PostgREST path targets, if it holds clinic data.
+/// Every one does: with a clinic configured, each read, update and delete is
+/// filtered to that clinic and each insert is stamped with it, so no service
+/// can reach another clinic's rows and a new table can't be left out. RPCs
+/// are not scoped and get no clinic id, so each one must only touch rows
+/// it is given ids for (`refresh_doctor_rating` takes just the doctor id).
+fn scoped_table(path: &str) -> Option<&str> {
+    let table = path.strip_prefix("/rest/v1/")?.split(['?', '/']).next()?;
+    (!table.is_empty() && table != "rpc").then_some(table)
+}
+
+/// Total from a PostgREST `Content-Range` header such as `0-24/3573` or `*/0`
+fn parse_content_range_total(content_range: &str) -> Option<i64> {
+    content_range.rsplit_once('/')
+        .and_then(|(_, total)| total.trim().parse().ok())
+}
+
+/// `path` with the values of credential-like query parameters masked
+fn redact_path(path: &str) -> String {
+    let Some((base, query)) = path.split_once('?') else {
+        return path.to_string();
+    };
+
+    let params: Vec<String> = query.split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) if is_sensitive_param(name) => format!("{}=REDACTED", name),
+            _ => param.to_string(),
+        })
+        .collect();
+
+    format!("{}?{}", base, params.join("&"))
+}
+
+fn is_sensitive_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SENSITIVE_PARAMS.iter().any(|sensitive| name.contains(sensitive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range_total() {
+        assert_eq!(parse_content_range_total("0-24/3573"), Some(3573));
+        assert_eq!(parse_content_range_total("*/0"), Some(0));
+        assert_eq!(parse_content_range_total("0-24/*"), None);
+        assert_eq!(parse_content_range_total("garbage"), None);
+    }
+
+    fn client(clinic_id: Option<&str>) -> SupabaseClient {
+        SupabaseClient {
+            client: Client::new(),
+            base_url: "http://localhost:54321".to_string(),
+            anon_key: String::new(),
+            slow_query_threshold: None,
+            clinic_id: clinic_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_clinic_scope() {
+        let scoped = client(Some("north"));
+        assert_eq!(
+            scoped.scope_path(&Method::GET, "/rest/v1/appointments?doctor_id=eq.1"),
+            "/rest/v1/appointments?doctor_id=eq.1&clinic_id=eq.north"
+        );
+        assert_eq!(scoped.scope_path(&Method::DELETE, "/rest/v1/doctors"), "/rest/v1/doctors?clinic_id=eq.north");
+        // Patient data outside the core tables is scoped too
+        for table in ["health_profiles", "documents", "prescriptions", "consultation_notes",
+                      "doctor_reviews", "video_sessions", "webhook_subscriptions"] {
+            assert_eq!(
+                scoped.scope_path(&Method::GET, &format!("/rest/v1/{}", table)),
+                format!("/rest/v1/{}?clinic_id=eq.north", table)
+            );
+        }
+        // RPCs, storage and inserts keep their path
+        assert_eq!(scoped.scope_path(&Method::GET, "/rest/v1/rpc/appointments"), "/rest/v1/rpc/appointments");
+        assert_eq!(scoped.scope_path(&Method::GET, "/storage/v1/object/documents/a.pdf"), "/storage/v1/object/documents/a.pdf");
+        assert_eq!(scoped.scope_path(&Method::POST, "/rest/v1/appointments"), "/rest/v1/appointments");
+
+        assert_eq!(
+            scoped.scope_body(&Method::POST, "/rest/v1/appointments", Some(json!([{ "id": 1 }, { "id": 2, "clinic_id": "south" }]))),
+            Some(json!([{ "id": 1, "clinic_id": "north" }, { "id": 2, "clinic_id": "north" }]))
+        );
+        assert_eq!(
+            scoped.scope_body(&Method::POST, "/rest/v1/health_profiles", Some(json!({ "id": 1 }))),
+            Some(json!({ "id": 1, "clinic_id": "north" }))
+        );
+        assert_eq!(
+            scoped.scope_body(&Method::POST, "/rest/v1/rpc/recompute", Some(json!({ "id": 1 }))),
+            Some(json!({ "id": 1 }))
+        );
+
+        let shared = client(None);
+        assert_eq!(shared.scope_path(&Method::GET, "/rest/v1/appointments"), "/rest/v1/appointments");
+        assert_eq!(shared.scope_body(&Method::POST, "/rest/v1/appointments", Some(json!({}))), Some(json!({})));
+    }
+
+    #[test]
+    fn test_redact_path() {
+        assert_eq!(
+            redact_path("/rest/v1/appointments?doctor_id=eq.1&status=eq.confirmed"),
+            "/rest/v1/appointments?doctor_id=eq.1&status=eq.confirmed"
+        );
+        assert_eq!(
+            redact_path("/auth/v1/verify?token=abc123&type=signup&apikey=xyz"),
+            "/auth/v1/verify?token=REDACTED&type=signup&apikey=REDACTED"
+        );
+        assert_eq!(
+            redact_path("/storage/v1/object/sign/docs?X-Amz-Signature=deadbeef"),
+            "/storage/v1/object/sign/docs?X-Amz-Signature=REDACTED"
+        );
+        assert_eq!(redact_path("/auth/v1/user"), "/auth/v1/user");
+    }
+}
@@ -1,10 +1,15 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JwtHeader {
     pub alg: String,
+    #[serde(default)]
     pub typ: String,
+    /// Which JWKS key signed the token (asymmetric tokens only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +33,51 @@ pub struct User {
     pub created_at: Option<DateTime<Utc>>,
 }
 
+impl User {
+    /// Application role from the token's `role` claim. `None` for roles the
+    /// app doesn't define, such as Supabase's default "authenticated".
+    pub fn app_role(&self) -> Option<Role> {
+        self.role.as_deref().and_then(Role::from_claim)
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.app_role() == Some(role)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.has_role(Role::Admin)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Patient,
+    Doctor,
+    Admin,
+}
+
+impl Role {
+    pub fn from_claim(claim: &str) -> Option<Self> {
+        match claim {
+            "patient" => Some(Role::Patient),
+            "doctor" => Some(Role::Doctor),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Patient => write!(f, "patient"),
+            Role::Doctor => write!(f, "doctor"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenResponse {
     pub valid: bool,
@@ -1,22 +1,30 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde_json::{json, Map, Value};
 use thiserror::Error;
 
+use crate::request::current_request_id;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Authentication error: {0}")]
     Auth(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Not Found: {0}")]
     NotFound(String),
 
     #[error("Bad Request: {0}")]
     BadRequest(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Internal Server Error: {0}")]
     Internal(String),
 
@@ -28,26 +36,207 @@ pub enum AppError {
 
     #[error("External service error: {0}")]
     ExternalService(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// Any of the above with a domain-specific machine-readable code (e.g. "appointment.conflict")
+    #[error("{inner}")]
+    Coded {
+        code: &'static str,
+        inner: Box<AppError>,
+    },
+
+    /// Any of the above with extra members for the problem body, such as
+    /// the free slots offered after a booking conflict
+    #[error("{inner}")]
+    Extended {
+        extensions: Map<String, Value>,
+        inner: Box<AppError>,
+    },
+}
+
+impl AppError {
+    /// Attach a stable, machine-readable code that clients can branch on
+    pub fn with_code(self, code: &'static str) -> Self {
+        match self {
+            AppError::Coded { inner, .. } => AppError::Coded { code, inner },
+            other => AppError::Coded {
+                code,
+                inner: Box::new(other),
+            },
+        }
+    }
+
+    /// Add a member to the problem body. Standard members can't be overridden.
+    pub fn with_extension(self, key: &str, value: Value) -> Self {
+        match self {
+            AppError::Extended { mut extensions, inner } => {
+                extensions.insert(key.to_string(), value);
+                AppError::Extended { extensions, inner }
+            }
+            other => {
+                let mut extensions = Map::new();
+                extensions.insert(key.to_string(), value);
+                AppError::Extended {
+                    extensions,
+                    inner: Box::new(other),
+                }
+            }
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Auth(_) => "auth.unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Conflict(_) => "conflict",
+            AppError::Internal(_) => "internal",
+            AppError::Database(_) => "database",
+            AppError::ValidationError(_) => "validation",
+            AppError::ExternalService(_) => "external_service",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::Coded { code, .. } => code,
+            AppError::Extended { inner, .. } => inner.code(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Auth(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            AppError::ExternalService(_) => StatusCode::BAD_GATEWAY,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Coded { inner, .. } => inner.status(),
+            AppError::Extended { inner, .. } => inner.status(),
+        }
+    }
+
+    /// Human-readable detail without the variant prefix
+    pub fn detail(&self) -> &str {
+        match self {
+            AppError::Auth(msg)
+            | AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::BadRequest(msg)
+            | AppError::Conflict(msg)
+            | AppError::Internal(msg)
+            | AppError::Database(msg)
+            | AppError::ValidationError(msg)
+            | AppError::ExternalService(msg)
+            | AppError::PayloadTooLarge(msg)
+            | AppError::UnsupportedMediaType(msg) => msg,
+            AppError::Coded { inner, .. } | AppError::Extended { inner, .. } => inner.detail(),
+        }
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        match self {
+            AppError::Coded { inner, .. } => inner.extensions(),
+            AppError::Extended { extensions, inner } => {
+                let mut merged = inner.extensions();
+                merged.extend(extensions.clone());
+                merged
+            }
+            _ => Map::new(),
+        }
+    }
 }
 
+/// Renders errors as RFC 7807 `application/problem+json`. The legacy `error`
+/// member is kept so existing clients keep working while they move to `code`.
+/// `request_id` matches the `X-Request-Id` header and the id in the server logs.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            AppError::Auth(msg) => (StatusCode::UNAUTHORIZED, msg),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::Database(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::ExternalService(msg) => (StatusCode::BAD_GATEWAY, msg),
-        };
-
-        tracing::error!("Error: {}: {}", status, message);
-
-        let body = Json(json!({
+        let status = self.status();
+        let code = self.code();
+        let message = self.detail();
+        let request_id = current_request_id();
+
+        tracing::error!("Error: {} [{}]: {}", status, code, message);
+
+        let mut body = json!({
+            "type": format!("/problems/{}", code),
+            "title": status.canonical_reason().unwrap_or("Error"),
+            "status": status.as_u16(),
+            "detail": message,
+            "code": code,
+            "request_id": request_id,
             "error": message
-        }));
+        });
+        if let Some(members) = body.as_object_mut() {
+            for (key, value) in self.extensions() {
+                members.entry(key).or_insert(value);
+            }
+        }
+        let body = Json(body);
+
+        let mut response = (status, body).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_codes_and_status() {
+        let err = AppError::NotFound("Appointment not found".to_string());
+        assert_eq!(err.code(), "not_found");
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_with_code_keeps_status_and_detail() {
+        let err = AppError::BadRequest("Slot taken".to_string()).with_code("appointment.conflict");
+        assert_eq!(err.code(), "appointment.conflict");
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.detail(), "Slot taken");
 
-        (status, body).into_response()
+        let recoded = err.with_code("appointment.slot_unavailable");
+        assert_eq!(recoded.code(), "appointment.slot_unavailable");
+        assert_eq!(recoded.detail(), "Slot taken");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_problem_json_content_type() {
+        let response = AppError::Auth("Missing token".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+    }
+
+    #[test]
+    fn test_extensions_survive_coding() {
+        let err = AppError::BadRequest("Slot taken".to_string())
+            .with_extension("suggested_alternatives", json!([{"start_time": "2030-01-01T10:00:00Z"}]))
+            .with_code("appointment.conflict");
+
+        assert_eq!(err.code(), "appointment.conflict");
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.detail(), "Slot taken");
+
+        let extensions = err.extensions();
+        assert_eq!(extensions["suggested_alternatives"][0]["start_time"], "2030-01-01T10:00:00Z");
+    }
+}
@@ -1,2 +1,3 @@
 pub mod auth;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod request;
@@ -0,0 +1,11 @@
+// Per-request context shared between the HTTP middleware and error rendering
+
+tokio::task_local! {
+    /// Correlation id for the request currently being handled
+    pub static REQUEST_ID: String;
+}
+
+/// Request id of the in-flight request, if running inside the request-id middleware
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
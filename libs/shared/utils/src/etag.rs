@@ -0,0 +1,108 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// Strong ETag for a record identified by `id` and last modified at
+/// `updated_at`. Any write that bumps `updated_at` yields a new tag.
+pub fn etag_for(id: impl std::fmt::Display, updated_at: DateTime<Utc>) -> String {
+    let digest = Sha256::digest(format!("{}:{}", id, updated_at.timestamp_micros()));
+    let hex: String = digest.iter().take(16).map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"{}\"", hex)
+}
+
+/// Whether the request's `If-None-Match` header already covers `etag`
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Response for a conditional GET: `304 Not Modified` when the client's copy
+/// is current, otherwise the body. Both carry the `ETag` header.
+pub enum Conditional<T> {
+    NotModified { etag: String },
+    Modified { etag: String, body: T },
+}
+
+impl<T> Conditional<T> {
+    pub fn new(headers: &HeaderMap, etag: String, body: T) -> Self {
+        if if_none_match(headers, &etag) {
+            Conditional::NotModified { etag }
+        } else {
+            Conditional::Modified { etag, body }
+        }
+    }
+
+    pub fn etag(&self) -> &str {
+        match self {
+            Conditional::NotModified { etag } | Conditional::Modified { etag, .. } => etag,
+        }
+    }
+
+    pub fn into_body(self) -> Option<T> {
+        match self {
+            Conditional::NotModified { .. } => None,
+            Conditional::Modified { body, .. } => Some(body),
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Conditional<T> {
+    fn into_response(self) -> Response {
+        let (mut response, etag) = match self {
+            Conditional::NotModified { etag } => (StatusCode::NOT_MODIFIED.into_response(), etag),
+            Conditional::Modified { etag, body } => (body.into_response(), etag),
+        };
+
+        if let Ok(value) = HeaderValue::from_str(&etag) {
+            response.headers_mut().insert(header::ETAG, value);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_etag_changes_with_updated_at() {
+        let now = Utc::now();
+        let first = etag_for("appt-1", now);
+        assert_eq!(first, etag_for("appt-1", now));
+        assert_ne!(first, etag_for("appt-1", now + Duration::milliseconds(1)));
+        assert_ne!(first, etag_for("appt-2", now));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = etag_for("appt-1", Utc::now());
+        let mut headers = HeaderMap::new();
+        assert!(!if_none_match(&headers, &etag));
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&format!("\"other\", W/{}", etag)).unwrap());
+        assert!(if_none_match(&headers, &etag));
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert!(!if_none_match(&headers, &etag));
+    }
+
+    #[test]
+    fn test_not_modified_response_has_no_body_and_keeps_etag() {
+        let etag = etag_for("appt-1", Utc::now());
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&etag).unwrap());
+
+        let response = Conditional::new(&headers, etag.clone(), "body").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), etag.as_str());
+    }
+}
@@ -1,18 +1,20 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
 use std::sync::Arc;
 
 use axum::{
-    extract::State,
-    http::Request,
+    extract::{FromRequestParts, State},
+    http::{request::Parts, Request},
     middleware::Next,
     response::Response,
     body::Body,
 };
 
-use shared_models::auth::User;
+use shared_models::auth::{Role, User};
 use shared_models::error::AppError;
 use shared_config::AppConfig;
 
-use crate::jwt::validate_token;
+use crate::jwks::authenticate_token;
 
 // Middleware for authentication - Fixed to use concrete body type
 pub async fn auth_middleware(
@@ -37,8 +39,8 @@ pub async fn auth_middleware(
     let token = &auth_value[7..];
     
     // Validate token
-    let user = validate_token(token, &config.supabase_jwt_secret)
-        .map_err(|e| AppError::Auth(e))?;
+    let user = authenticate_token(token, &config).await
+        .map_err(AppError::Auth)?;
     
     // Add user to request extensions
     request.extensions_mut().insert(user);
@@ -54,4 +56,110 @@ pub async fn extract_user<B>(request: &Request<B>) -> Result<User, AppError> {
         .get::<User>()
         .cloned()
         .ok_or_else(|| AppError::Auth("User not found in request extensions".to_string()))
-}
\ No newline at end of file
+}
+
+// ==============================================================================
+// ROLE GUARDS
+// ==============================================================================
+
+/// The roles a `RequireRole` guard lets through
+pub trait RoleRequirement: Send + Sync + 'static {
+    const ROLES: &'static [Role];
+}
+
+pub mod roles {
+    use super::RoleRequirement;
+    use shared_models::auth::Role;
+
+    pub struct Admin;
+    pub struct Doctor;
+    pub struct DoctorOrAdmin;
+
+    impl RoleRequirement for Admin {
+        const ROLES: &'static [Role] = &[Role::Admin];
+    }
+
+    impl RoleRequirement for Doctor {
+        const ROLES: &'static [Role] = &[Role::Doctor];
+    }
+
+    impl RoleRequirement for DoctorOrAdmin {
+        const ROLES: &'static [Role] = &[Role::Doctor, Role::Admin];
+    }
+}
+
+/// Extractor that only admits users holding one of `R::ROLES`, e.g.
+/// `admin: RequireRole<roles::Admin>`. Derefs to the authenticated `User`.
+/// Must run behind `auth_middleware`.
+pub struct RequireRole<R: RoleRequirement> {
+    pub user: User,
+    _requirement: PhantomData<fn() -> R>,
+}
+
+impl<R: RoleRequirement> RequireRole<R> {
+    pub fn check(user: User) -> Result<Self, AppError> {
+        match user.app_role() {
+            Some(role) if R::ROLES.contains(&role) => Ok(Self {
+                user,
+                _requirement: PhantomData,
+            }),
+            _ => Err(AppError::Forbidden(format!(
+                "Requires role: {}",
+                R::ROLES.iter().map(Role::to_string).collect::<Vec<_>>().join(" or ")
+            ))),
+        }
+    }
+}
+
+impl<R: RoleRequirement> Deref for RequireRole<R> {
+    type Target = User;
+
+    fn deref(&self) -> &User {
+        &self.user
+    }
+}
+
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    R: RoleRequirement,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = parts.extensions
+            .get::<User>()
+            .cloned()
+            .ok_or_else(|| AppError::Auth("User not found in request extensions".to_string()))?;
+
+        Self::check(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_with_role(role: Option<&str>) -> User {
+        User {
+            id: "user-1".to_string(),
+            email: None,
+            role: role.map(str::to_string),
+            metadata: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn test_require_role_admits_listed_roles_only() {
+        assert!(RequireRole::<roles::Admin>::check(user_with_role(Some("admin"))).is_ok());
+        assert!(RequireRole::<roles::DoctorOrAdmin>::check(user_with_role(Some("doctor"))).is_ok());
+
+        assert!(matches!(
+            RequireRole::<roles::Admin>::check(user_with_role(Some("doctor"))),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(RequireRole::<roles::Doctor>::check(user_with_role(Some("authenticated"))).is_err());
+        assert!(RequireRole::<roles::Doctor>::check(user_with_role(None)).is_err());
+    }
+}
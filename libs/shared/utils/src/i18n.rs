@@ -0,0 +1,201 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header::ACCEPT_LANGUAGE, request::Parts},
+};
+use serde::Serialize;
+
+/// Languages patient-facing messages are translated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    English,
+    Irish,
+    Polish,
+}
+
+impl Language {
+    /// ISO 639-1 code, as used in `Accept-Language` and `Content-Language`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Irish => "ga",
+            Language::Polish => "pl",
+        }
+    }
+
+    /// A language tag (`ga`, `pl-PL`) or name (`Irish`, `Gaeilge`, `polski`)
+    /// as patients enter it in their profile or booking
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let primary = value.split(['-', '_']).next().unwrap_or_default();
+        match primary {
+            "en" | "english" => Some(Language::English),
+            "ga" | "irish" | "gaeilge" => Some(Language::Irish),
+            "pl" | "polish" | "polski" => Some(Language::Polish),
+            _ => None,
+        }
+    }
+
+    /// The supported language the client prefers most, honouring q-values
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut ranked: Vec<(Language, f32)> = header.split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let language = Language::parse(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+                (quality > 0.0).then_some((language, quality))
+            })
+            .collect();
+        // Stable, so equally weighted languages keep the client's order
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.first().map(|(language, _)| *language)
+    }
+}
+
+/// Every translated patient-facing message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    AppointmentBooked,
+    AppointmentBookedWithPreferredDoctor,
+    AppointmentBookedWithBestAvailableDoctor,
+    AppointmentRescheduled,
+    AppointmentCancelled,
+    AttendanceConfirmed,
+    SlotHeld,
+    ReadinessAllowDevices,
+    ReadinessConnect,
+    ReadinessPublishTracks,
+    ReadinessSubmitResults,
+}
+
+impl Message {
+    /// The message in `language`, or in English where a translation is missing
+    pub fn text(self, language: Language) -> &'static str {
+        let translated = match language {
+            Language::English => None,
+            Language::Irish => irish(self),
+            Language::Polish => polish(self),
+        };
+        translated.unwrap_or_else(|| english(self))
+    }
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::AppointmentBooked => "Appointment booked successfully",
+        Message::AppointmentBookedWithPreferredDoctor => "Appointment booked with your preferred doctor based on consultation history",
+        Message::AppointmentBookedWithBestAvailableDoctor => "Appointment booked with best available doctor",
+        Message::AppointmentRescheduled => "Appointment rescheduled successfully",
+        Message::AppointmentCancelled => "Appointment cancelled successfully",
+        Message::AttendanceConfirmed => "Thanks for confirming you'll attend",
+        Message::SlotHeld => "Slot held. Confirm it before it expires to book the appointment.",
+        Message::ReadinessAllowDevices => "Allow camera and microphone access when prompted",
+        Message::ReadinessConnect => "Connect to the test session using the provided ICE servers",
+        Message::ReadinessPublishTracks => "Publish a short audio and video track and measure bandwidth and round-trip latency",
+        Message::ReadinessSubmitResults => "Submit the measured results to POST /video/test/results",
+    }
+}
+
+fn irish(message: Message) -> Option<&'static str> {
+    Some(match message {
+        Message::AppointmentBooked => "Cuireadh an coinne in áirithe",
+        Message::AppointmentBookedWithPreferredDoctor => "Cuireadh an coinne in áirithe le do rogha dochtúra, bunaithe ar do chomhairliúcháin roimhe seo",
+        Message::AppointmentBookedWithBestAvailableDoctor => "Cuireadh an coinne in áirithe leis an dochtúir is fearr atá ar fáil",
+        Message::AppointmentRescheduled => "Athraíodh am an choinne",
+        Message::AppointmentCancelled => "Cealaíodh an coinne",
+        Message::AttendanceConfirmed => "Go raibh maith agat as a dheimhniú go mbeidh tú i láthair",
+        Message::SlotHeld => "Tá an tráth curtha i leataobh duit. Deimhnigh é sula rachaidh sé in éag chun an coinne a chur in áirithe.",
+        Message::ReadinessAllowDevices => "Tabhair cead don cheamara agus don mhicreafón nuair a iarrtar ort",
+        Message::ReadinessConnect => "Ceangail leis an seisiún tástála leis na freastalaithe ICE a cuireadh ar fáil",
+        // Instructions for the client app rather than the patient
+        Message::ReadinessPublishTracks | Message::ReadinessSubmitResults => return None,
+    })
+}
+
+fn polish(message: Message) -> Option<&'static str> {
+    Some(match message {
+        Message::AppointmentBooked => "Wizyta została zarezerwowana",
+        Message::AppointmentBookedWithPreferredDoctor => "Wizyta została zarezerwowana u Twojego lekarza na podstawie historii konsultacji",
+        Message::AppointmentBookedWithBestAvailableDoctor => "Wizyta została zarezerwowana u najlepszego dostępnego lekarza",
+        Message::AppointmentRescheduled => "Termin wizyty został zmieniony",
+        Message::AppointmentCancelled => "Wizyta została odwołana",
+        Message::AttendanceConfirmed => "Dziękujemy za potwierdzenie obecności",
+        Message::SlotHeld => "Termin jest zarezerwowany. Potwierdź go przed upływem ważności, aby umówić wizytę.",
+        Message::ReadinessAllowDevices => "Zezwól na dostęp do kamery i mikrofonu, gdy pojawi się prośba",
+        Message::ReadinessConnect => "Połącz się z sesją testową za pomocą podanych serwerów ICE",
+        Message::ReadinessPublishTracks | Message::ReadinessSubmitResults => return None,
+    })
+}
+
+/// The language to answer in, from the `Accept-Language` header; English
+/// when the header is missing or names no supported language
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Locale(pub Language);
+
+impl Locale {
+    /// A language the patient chose explicitly (in their profile or on the
+    /// request) wins over what their client sends
+    pub fn preferring(self, preferred_language: Option<&str>) -> Language {
+        preferred_language.and_then(Language::parse).unwrap_or(self.0)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Locale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Locale(
+            parts.headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Language::from_accept_language)
+                .unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_language_negotiation() {
+        assert_eq!(Language::from_accept_language("ga-IE,ga;q=0.9,en;q=0.8"), Some(Language::Irish));
+        assert_eq!(Language::from_accept_language("fr-FR, en;q=0.5, pl;q=0.7"), Some(Language::Polish));
+        assert_eq!(Language::from_accept_language("pl;q=0, en"), Some(Language::English));
+        assert_eq!(Language::from_accept_language("de, fr"), None);
+    }
+
+    #[test]
+    fn test_messages_fall_back_to_english() {
+        assert_eq!(Message::AppointmentCancelled.text(Language::Polish), "Wizyta została odwołana");
+        assert_eq!(
+            Message::ReadinessSubmitResults.text(Language::Irish),
+            Message::ReadinessSubmitResults.text(Language::English)
+        );
+        assert_eq!(Locale(Language::Polish).preferring(Some("Gaeilge")), Language::Irish);
+        assert_eq!(Locale(Language::Polish).preferring(Some("Klingon")), Language::Polish);
+    }
+
+    async fn locale(header: Option<&str>) -> Locale {
+        let mut request = axum::http::Request::builder();
+        if let Some(header) = header {
+            request = request.header(ACCEPT_LANGUAGE, header);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        let Ok(locale) = Locale::from_request_parts(&mut parts, &()).await;
+        locale
+    }
+
+    #[tokio::test]
+    async fn test_locale_extractor_defaults_to_english() {
+        assert_eq!(locale(Some("pl-PL,pl;q=0.9")).await, Locale(Language::Polish));
+        assert_eq!(locale(Some("de")).await, Locale(Language::English));
+        assert_eq!(locale(None).await, Locale(Language::English));
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::Deserialize;
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::{debug, warn};
+
+use shared_config::AppConfig;
+use shared_models::auth::User;
+
+use crate::jwt::{decode_header, validate_rs256_token, validate_token};
+
+/// The JWKS endpoint is fetched at most this often, successful or not, so
+/// forged `kid`s or an outage can't be used to hammer it
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Token validation waits on the fetch, so a hung endpoint must not hang requests
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Validate a bearer token the way the auth middleware does: RS256 tokens
+/// against the Supabase JWKS, anything else with the HS256 shared secret.
+pub async fn authenticate_token(token: &str, config: &AppConfig) -> Result<User, String> {
+    let header = decode_header(token)?;
+
+    match header.alg.as_str() {
+        "RS256" => {
+            if config.supabase_jwks_url.is_empty() {
+                return Err("RS256 tokens are not accepted: no JWKS URL configured".to_string());
+            }
+            let kid = header.kid.ok_or_else(|| "Token has no key id".to_string())?;
+            let cache = JwksCache::shared(&config.supabase_jwks_url, Duration::from_secs(config.jwks_cache_ttl_seconds));
+            let key = cache.key(&kid).await?;
+            validate_rs256_token(token, &key)
+        }
+        // HS256 with the project secret, as used by local Supabase and our
+        // own service tokens
+        _ => validate_token(token, &config.supabase_jwt_secret),
+    }
+}
+
+/// RSA public key taken from a JWK
+#[derive(Debug, Clone)]
+pub struct RsaPublicKey {
+    n: Vec<u8>,
+    e: Vec<u8>,
+}
+
+impl RsaPublicKey {
+    pub fn from_components(n: Vec<u8>, e: Vec<u8>) -> Self {
+        Self { n, e }
+    }
+
+    /// Check an RS256 (PKCS#1 v1.5, SHA-256) signature
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), String> {
+        RsaPublicKeyComponents { n: &self.n, e: &self.e }
+            .verify(&RSA_PKCS1_2048_8192_SHA256, message, signature)
+            .map_err(|_| "Signature verification failed".to_string())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kid: Option<String>,
+    kty: String,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Default)]
+struct CachedKeys {
+    keys: HashMap<String, RsaPublicKey>,
+    fetched_at: Option<Instant>,
+    failed_at: Option<Instant>,
+}
+
+impl CachedKeys {
+    fn needs_refresh(&self, kid: &str, ttl: Duration) -> bool {
+        if self.failed_at.is_some_and(|failed_at| failed_at.elapsed() < MIN_REFRESH_INTERVAL) {
+            return false;
+        }
+        match (self.keys.contains_key(kid), self.fetched_at) {
+            (_, None) => true,
+            (true, Some(fetched_at)) => fetched_at.elapsed() >= ttl,
+            (false, Some(fetched_at)) => fetched_at.elapsed() >= MIN_REFRESH_INTERVAL,
+        }
+    }
+}
+
+/// Caches handed out by [`JwksCache::shared`], by URL and TTL
+type SharedCaches = HashMap<(String, Duration), Arc<JwksCache>>;
+
+/// Signing keys fetched from a JWKS endpoint, kept for `ttl`. A token signed
+/// with a key we don't have yet (rotation) triggers an early refetch.
+pub struct JwksCache {
+    http: reqwest::Client,
+    url: String,
+    ttl: Duration,
+    cached: RwLock<CachedKeys>,
+    /// Held while fetching so concurrent misses share one request
+    refreshing: AsyncMutex<()>,
+}
+
+impl JwksCache {
+    pub fn new(url: &str, ttl: Duration) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(JWKS_FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            url: url.to_string(),
+            ttl,
+            cached: RwLock::new(CachedKeys::default()),
+            refreshing: AsyncMutex::new(()),
+        }
+    }
+
+    /// Process-wide cache for `url` and `ttl`, so keys survive across requests
+    pub fn shared(url: &str, ttl: Duration) -> Arc<JwksCache> {
+        static CACHES: OnceLock<Mutex<SharedCaches>> = OnceLock::new();
+
+        let mut caches = CACHES.get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        caches.entry((url.to_string(), ttl))
+            .or_insert_with(|| Arc::new(JwksCache::new(url, ttl)))
+            .clone()
+    }
+
+    pub async fn key(&self, kid: &str) -> Result<RsaPublicKey, String> {
+        let (key, needs_refresh) = self.lookup(kid);
+        if !needs_refresh {
+            return key.ok_or_else(|| format!("Unknown signing key: {}", kid));
+        }
+
+        let refreshed = {
+            let _refreshing = self.refreshing.lock().await;
+            // Whoever held the lock before us may have just fetched
+            if self.lookup(kid).1 {
+                let refreshed = self.refresh().await;
+                if refreshed.is_err() {
+                    self.cached.write()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .failed_at = Some(Instant::now());
+                }
+                refreshed
+            } else {
+                Ok(())
+            }
+        };
+
+        if let Err(e) = refreshed {
+            // Keep serving a known key if the endpoint is briefly unavailable
+            return match key {
+                Some(key) => {
+                    warn!("JWKS refresh failed, using cached key {}: {}", kid, e);
+                    Ok(key)
+                }
+                None => Err(e),
+            };
+        }
+
+        self.cached.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| format!("Unknown signing key: {}", kid))
+    }
+
+    /// Fetch the key set now and report how many RSA keys it holds. Used by
+    /// startup and health checks to confirm the endpoint is reachable.
+    pub async fn check_reachable(&self) -> Result<usize, String> {
+        let _refreshing = self.refreshing.lock().await;
+        self.refresh().await?;
+        Ok(self.cached.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .keys
+            .len())
+    }
+
+    fn lookup(&self, kid: &str) -> (Option<RsaPublicKey>, bool) {
+        let cached = self.cached.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        (cached.keys.get(kid).cloned(), cached.needs_refresh(kid, self.ttl))
+    }
+
+    async fn refresh(&self) -> Result<(), String> {
+        debug!("Fetching JWKS from {}", self.url);
+
+        let response = self.http.get(&self.url).send().await
+            .map_err(|e| format!("Failed to fetch JWKS: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Failed to fetch JWKS: HTTP {}", response.status()));
+        }
+        let jwk_set: JwkSet = response.json().await
+            .map_err(|e| format!("Invalid JWKS response: {}", e))?;
+
+        let keys = parse_rsa_keys(jwk_set);
+        debug!("Loaded {} RSA signing keys from JWKS", keys.len());
+
+        let mut cached = self.cached.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cached.keys = keys;
+        cached.fetched_at = Some(Instant::now());
+        Ok(())
+    }
+}
+
+fn parse_rsa_keys(jwk_set: JwkSet) -> HashMap<String, RsaPublicKey> {
+    jwk_set.keys.into_iter()
+        .filter(|jwk| jwk.kty == "RSA")
+        .filter_map(|jwk| {
+            let n = URL_SAFE_NO_PAD.decode(jwk.n?).ok()?;
+            let e = URL_SAFE_NO_PAD.decode(jwk.e?).ok()?;
+            Some((jwk.kid?, RsaPublicKey::from_components(n, e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+    use serde_json::json;
+
+    fn test_key_pair() -> RsaKeyPair {
+        RsaKeyPair::from_pkcs8(include_bytes!("testdata/rsa_test_key.der")).unwrap()
+    }
+
+    fn sign_rs256(key_pair: &RsaKeyPair, claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "RS256", "typ": "JWT", "kid": "key-1" }).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signing_input = format!("{}.{}", header, claims);
+
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair.sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), signing_input.as_bytes(), &mut signature).unwrap();
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn public_key(key_pair: &RsaKeyPair) -> RsaPublicKey {
+        let components: RsaPublicKeyComponents<Vec<u8>> = key_pair.public().into();
+        RsaPublicKey::from_components(components.n, components.e)
+    }
+
+    #[test]
+    fn test_validate_rs256_token() {
+        let key_pair = test_key_pair();
+        let exp = chrono::Utc::now().timestamp() + 3600;
+        let token = sign_rs256(&key_pair, json!({ "sub": "user-1", "role": "authenticated", "exp": exp }));
+
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.alg, "RS256");
+        assert_eq!(header.kid.as_deref(), Some("key-1"));
+
+        let user = validate_rs256_token(&token, &public_key(&key_pair)).unwrap();
+        assert_eq!(user.id, "user-1");
+    }
+
+    #[test]
+    fn test_rs256_token_with_tampered_claims_is_rejected() {
+        let key_pair = test_key_pair();
+        let token = sign_rs256(&key_pair, json!({ "sub": "user-1", "role": "authenticated" }));
+
+        let mut parts: Vec<String> = token.split('.').map(str::to_string).collect();
+        parts[1] = URL_SAFE_NO_PAD.encode(json!({ "sub": "user-1", "role": "admin" }).to_string());
+
+        assert!(validate_rs256_token(&parts.join("."), &public_key(&key_pair)).is_err());
+    }
+
+    #[test]
+    fn test_parse_rsa_keys_skips_other_key_types() {
+        let jwk_set: JwkSet = serde_json::from_value(json!({
+            "keys": [
+                { "kid": "rsa", "kty": "RSA", "n": "AQAB", "e": "AQAB" },
+                { "kid": "ec", "kty": "EC", "crv": "P-256", "x": "AA", "y": "AA" },
+                { "kty": "RSA", "n": "AQAB", "e": "AQAB" }
+            ]
+        })).unwrap();
+
+        let keys = parse_rsa_keys(jwk_set);
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains_key("rsa"));
+    }
+
+    fn jwks_body(key_pair: &RsaKeyPair) -> serde_json::Value {
+        let components: RsaPublicKeyComponents<Vec<u8>> = key_pair.public().into();
+        json!({ "keys": [{
+            "kid": "key-1",
+            "kty": "RSA",
+            "n": URL_SAFE_NO_PAD.encode(components.n),
+            "e": URL_SAFE_NO_PAD.encode(components.e)
+        }] })
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_fetch() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_json(jwks_body(&test_key_pair()))
+                .set_delay(Duration::from_millis(100)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache = Arc::new(JwksCache::new(&server.uri(), Duration::from_secs(300)));
+        let lookups: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                tokio::spawn(async move { cache.key("key-1").await })
+            })
+            .collect();
+        for lookup in lookups {
+            assert!(lookup.await.unwrap().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_is_not_retried_immediately() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache = JwksCache::new(&server.uri(), Duration::from_secs(300));
+        assert!(cache.key("key-1").await.is_err());
+        assert!(cache.key("key-2").await.is_err());
+    }
+
+    #[test]
+    fn test_shared_cache_is_per_ttl() {
+        let url = "https://example.supabase.co/auth/v1/.well-known/jwks.json";
+        let short = JwksCache::shared(url, Duration::from_secs(60));
+
+        assert!(Arc::ptr_eq(&short, &JwksCache::shared(url, Duration::from_secs(60))));
+        let long = JwksCache::shared(url, Duration::from_secs(600));
+        assert!(!Arc::ptr_eq(&short, &long));
+        assert_eq!(long.ttl, Duration::from_secs(600));
+    }
+}
@@ -1,13 +1,23 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
-use chrono::{ Utc, TimeZone};
+use chrono::{DateTime, Utc, TimeZone};
 use tracing::debug;
-use shared_models::auth::{JwtClaims, User};
+use shared_models::auth::{JwtClaims, JwtHeader, User};
+
+use crate::jwks::RsaPublicKey;
 
 type HmacSha256 = Hmac<Sha256>;
 
 pub fn validate_token(token: &str, jwt_secret: &str) -> Result<User, String> {
+    verify_hs256_signature(token, jwt_secret)?;
+
+    let claims_b64 = token.split('.').nth(1).unwrap_or_default();
+    user_from_claims(claims_b64)
+}
+
+/// Check an HS256 token's signature without looking at its claims
+fn verify_hs256_signature(token: &str, jwt_secret: &str) -> Result<(), String> {
     if jwt_secret.is_empty() {
         return Err("JWT secret is not set".to_string());
     }
@@ -43,11 +53,51 @@ pub fn validate_token(token: &str, jwt_secret: &str) -> Result<User, String> {
     mac.update(signature_string.as_bytes());
     
     // Verify signature
-    if let Err(_) = mac.verify_slice(&signature) {
+    if mac.verify_slice(&signature).is_err() {
         debug!("Token signature verification failed");
         return Err("Invalid token signature".to_string());
     }
 
+    Ok(())
+}
+
+/// Validate an RS256 token against a public key from the Supabase JWKS
+pub fn validate_rs256_token(token: &str, key: &RsaPublicKey) -> Result<User, String> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err("Invalid token format".to_string());
+    }
+
+    let signature = URL_SAFE_NO_PAD.decode(parts[2])
+        .map_err(|_| "Invalid signature encoding".to_string())?;
+    let signing_input = format!("{}.{}", parts[0], parts[1]);
+
+    if key.verify(signing_input.as_bytes(), &signature).is_err() {
+        debug!("RS256 token signature verification failed");
+        return Err("Invalid token signature".to_string());
+    }
+
+    user_from_claims(parts[1])
+}
+
+/// Decode a token's header without verifying anything
+pub fn decode_header(token: &str) -> Result<JwtHeader, String> {
+    let header_b64 = token.split('.').next().unwrap_or_default();
+    let header_json = URL_SAFE_NO_PAD.decode(header_b64)
+        .map_err(|_| "Invalid header encoding".to_string())?;
+    serde_json::from_slice(&header_json)
+        .map_err(|_| "Invalid header format".to_string())
+}
+
+/// When a token expires, read without verifying anything. For tokens that
+/// have already been validated, such as one held by a long-lived stream.
+pub fn token_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let claims_json = URL_SAFE_NO_PAD.decode(token.split('.').nth(1)?).ok()?;
+    let claims: JwtClaims = serde_json::from_slice(&claims_json).ok()?;
+    Utc.timestamp_opt(claims.exp? as i64, 0).single()
+}
+
+fn user_from_claims(claims_b64: &str) -> Result<User, String> {
     // Decode claims
     let claims_json = match URL_SAFE_NO_PAD.decode(claims_b64) {
         Ok(bytes) => match String::from_utf8(bytes) {
@@ -90,4 +140,127 @@ pub fn validate_token(token: &str, jwt_secret: &str) -> Result<User, String> {
     
     debug!("Token validated successfully for user: {}", user.id);
     Ok(user)
-}
\ No newline at end of file
+}
+
+/// Sign `claims` as an HS256 JWT that `validate_token` (and PostgREST) accept
+pub fn create_token(claims: &JwtClaims, jwt_secret: &str) -> Result<String, String> {
+    if jwt_secret.is_empty() {
+        return Err("JWT secret is not set".to_string());
+    }
+
+    let header = JwtHeader {
+        alg: "HS256".to_string(),
+        typ: "JWT".to_string(),
+        kid: None,
+    };
+    let header_json = serde_json::to_vec(&header).map_err(|e| e.to_string())?;
+    let claims_json = serde_json::to_vec(claims).map_err(|e| e.to_string())?;
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header_json),
+        URL_SAFE_NO_PAD.encode(claims_json)
+    );
+
+    let mut mac = HmacSha256::new_from_slice(jwt_secret.as_bytes())
+        .map_err(|_| "Failed to create HMAC".to_string())?;
+    mac.update(signing_input.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+    Ok(format!("{}.{}", signing_input, signature))
+}
+
+/// Supabase generates 40-character secrets and requires at least 32
+pub const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// What the JWT secret self-check could confirm
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct JwtSecretCheck {
+    /// A throwaway token signed with the secret validated again
+    pub round_trip: bool,
+    /// The anon key, itself an HS256 JWT signed with the project secret,
+    /// validated too. False for non-JWT (publishable) keys, which can't be
+    /// checked this way.
+    pub anon_key_verified: bool,
+}
+
+/// Check the configured secret can actually validate tokens. A round trip
+/// alone passes with any non-empty secret, so the secret's length and, where
+/// possible, the project's own anon key are checked as well: a truncated or
+/// wrong secret fails those.
+pub fn check_jwt_secret(jwt_secret: &str, anon_key: &str) -> Result<JwtSecretCheck, String> {
+    if jwt_secret.len() < MIN_JWT_SECRET_LEN {
+        return Err(format!(
+            "JWT secret is {} characters, expected at least {}; it may be truncated",
+            jwt_secret.len(), MIN_JWT_SECRET_LEN
+        ));
+    }
+
+    let claims = JwtClaims {
+        sub: "jwt-self-check".to_string(),
+        exp: Some(Utc::now().timestamp() as u64 + 60),
+        email: None,
+        role: None,
+        app_metadata: None,
+        user_metadata: None,
+        aud: None,
+        iat: Some(Utc::now().timestamp() as u64),
+    };
+    let token = create_token(&claims, jwt_secret)?;
+    let user = validate_token(&token, jwt_secret)
+        .map_err(|e| format!("A token signed with the JWT secret did not validate: {}", e))?;
+    if user.id != claims.sub {
+        return Err("A token signed with the JWT secret came back with different claims".to_string());
+    }
+
+    let anon_key_is_hs256 = decode_header(anon_key).is_ok_and(|header| header.alg == "HS256");
+    if anon_key_is_hs256 {
+        // Anon keys carry no subject, so only the signature is checked
+        verify_hs256_signature(anon_key, jwt_secret).map_err(|e| format!(
+            "The anon key was not signed with the configured JWT secret ({}); the secret may be truncated or from another project",
+            e
+        ))?;
+    }
+
+    Ok(JwtSecretCheck {
+        round_trip: true,
+        anon_key_verified: anon_key_is_hs256,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "super-secret-jwt-token-with-at-least-32-characters-long";
+
+    /// Shaped like a Supabase anon key: no subject, just a role
+    fn anon_key(secret: &str) -> String {
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#),
+            URL_SAFE_NO_PAD.encode(br#"{"iss":"supabase","role":"anon","exp":4102444800}"#)
+        );
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(signing_input.as_bytes());
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_check_jwt_secret() {
+        assert_eq!(
+            check_jwt_secret(SECRET, &anon_key(SECRET)),
+            Ok(JwtSecretCheck { round_trip: true, anon_key_verified: true })
+        );
+        // Publishable keys aren't JWTs and are skipped
+        assert_eq!(
+            check_jwt_secret(SECRET, "sb_publishable_abc123"),
+            Ok(JwtSecretCheck { round_trip: true, anon_key_verified: false })
+        );
+
+        // Truncated secrets fail on length, or on the anon key once long enough
+        assert!(check_jwt_secret(&SECRET[..20], "sb_publishable_abc123").is_err());
+        assert!(check_jwt_secret(&SECRET[..40], &anon_key(SECRET)).is_err());
+        assert!(check_jwt_secret("", "").is_err());
+    }
+}
@@ -1,3 +1,7 @@
 pub mod jwt;
+pub mod jwks;
 pub mod extractor;
-pub mod test_utils;
\ No newline at end of file
+pub mod etag;
+pub mod request_id;
+pub mod i18n;
+pub mod test_utils;
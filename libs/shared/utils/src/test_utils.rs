@@ -34,6 +34,35 @@ impl TestConfig {
             cloudflare_realtime_app_id: "test-app-id".to_string(),
             cloudflare_realtime_api_token: "test-token".to_string(),
             cloudflare_realtime_base_url: "https://test.cloudflare.com/v1".to_string(),
+            video_join_url_ttl_minutes: 120,
+            cors_allowed_origins: Vec::new(),
+            trusted_proxies: Vec::new(),
+            cors_allow_all: false,
+            graceful_shutdown_timeout_seconds: 30,
+            clinic_name: "Amae Clinic".to_string(),
+            clinic_address: String::new(),
+            clinic_contact: String::new(),
+            clinical_record_retention_years: 10,
+            supabase_jwks_url: String::new(),
+            jwks_cache_ttl_seconds: 600,
+            allow_any_doctor_profile_access: false,
+            video_session_reconcile_interval_minutes: 15,
+            appointment_rules: Default::default(),
+            supabase_slow_query_threshold_ms: 1000,
+            video_join_failure_alert_percent: 20,
+            response_compression_enabled: true,
+            max_document_upload_bytes: 10 * 1024 * 1024,
+            feature_flags: Default::default(),
+            availability_cache_ttl_seconds: 0,
+            clinic_id: None,
+            appointment_stats_cache_ttl_seconds: 0,
+            support_email: None,
+            help_url_base: None,
+            batch_conflict_check_concurrency: 5,
+            batch_conflict_check_timeout_seconds: 10,
+            batch_conflict_check_max_size: 100,
+            webhook_allow_insecure_urls: false,
+            webhook_worker_key: None,
         }
     }
     
@@ -127,7 +156,7 @@ impl JwtTestUtils {
         let signature = mac.finalize().into_bytes();
         
         // Encode signature
-        let signature_encoded = URL_SAFE_NO_PAD.encode(&signature);
+        let signature_encoded = URL_SAFE_NO_PAD.encode(signature);
         
         // Return complete JWT token
         format!("{}.{}.{}", header_encoded, payload_encoded, signature_encoded)
@@ -9,17 +9,26 @@ use axum_extra::TypedHeader;
 use headers::{Authorization, authorization::Bearer};
 use serde_json::{json, Value};
 use serde::Deserialize;
+use tracing::warn;
 use uuid::Uuid;
 
 use shared_config::AppConfig;
 use shared_models::auth::User;
 use shared_models::error::AppError;
+use shared_utils::extractor::{roles::Admin, RequireRole};
+use shared_utils::i18n::Locale;
 
 use crate::models::{
-    AddTracksRequest, CreateVideoSessionRequest, JoinSessionRequest, 
-    VideoConferencingError, VideoSessionType
+    AddParticipantRequest, AddTracksRequest, CreateInviteRequest, CreateVideoSessionRequest,
+    JoinSessionRequest, ReadinessTestResults, SessionNoticeRequest, ToggleRecordingRequest,
+    VideoConferencingError,
+    VideoSessionType
 };
-use crate::services::{VideoSessionService, VideoConferencingIntegrationService};
+use crate::services::{
+    SessionRecordingService, TelemedicineReadinessService, VideoInviteService,
+    VideoSessionService, VideoConferencingIntegrationService,
+};
+use crate::services::metrics::video_metrics;
 
 // ==============================================================================
 // QUERY PARAMETER STRUCTS
@@ -67,6 +76,9 @@ pub async fn create_video_session(
             VideoConferencingError::Unauthorized => {
                 AppError::Auth("Not authorized for this appointment".to_string())
             }
+            VideoConferencingError::ConsentRequired { .. } => {
+                AppError::Forbidden(e.to_string()).with_code("video.consent_required")
+            }
             VideoConferencingError::ValidationError { message } => {
                 AppError::BadRequest(message)
             }
@@ -108,12 +120,28 @@ pub async fn join_video_session(
             VideoConferencingError::Unauthorized => {
                 AppError::Auth("Not authorized for this session".to_string())
             }
+            VideoConferencingError::ConsentRequired { .. } => {
+                AppError::Forbidden(e.to_string()).with_code("video.consent_required")
+            }
             VideoConferencingError::InvalidSessionState { status } => {
                 AppError::BadRequest(format!("Session not available: {}", status))
             }
+            VideoConferencingError::SessionCapacityExceeded => {
+                AppError::BadRequest(e.to_string()).with_code("video.session_full")
+            }
+            VideoConferencingError::JoinLinksExpired => {
+                AppError::Forbidden(e.to_string()).with_code("video.join_links_expired")
+            }
+            VideoConferencingError::RecordingConsentRequired { .. } => {
+                AppError::Forbidden("This session is being recorded and you have not consented to recording".to_string())
+                    .with_code("video.recording_consent_required")
+            }
             VideoConferencingError::WebRTCError { message } => {
                 AppError::BadRequest(format!("WebRTC error: {}", message))
             }
+            VideoConferencingError::ProviderUnavailable { .. } => {
+                AppError::ExternalService(e.to_string()).with_code("video.provider_unavailable")
+            }
             _ => AppError::Internal(e.to_string()),
         })?;
     
@@ -153,18 +181,62 @@ pub async fn add_session_tracks(
             VideoConferencingError::SessionNotFound => {
                 AppError::NotFound("Video session not found".to_string())
             }
+            VideoConferencingError::Unauthorized => {
+                AppError::Auth("Not an active participant in this session".to_string())
+            }
             VideoConferencingError::InvalidSessionState { status } => {
                 AppError::BadRequest(format!("Cannot add tracks: {}", status))
             }
             VideoConferencingError::CloudflareApiError { message } => {
                 AppError::Internal(format!("Cloudflare error: {}", message))
             }
+            VideoConferencingError::ProviderUnavailable { .. } => {
+                AppError::ExternalService(e.to_string()).with_code("video.provider_unavailable")
+            }
             _ => AppError::Internal(e.to_string()),
         })?;
     
     Ok(Json(response))
 }
 
+/// Subscribe to the tracks every other active participant is publishing
+#[axum::debug_handler]
+pub async fn subscribe_session_tracks(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let session_service = VideoSessionService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let response = session_service
+        .subscribe_to_participants(session_id, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Auth("Not an active participant in this session".to_string())
+            }
+            VideoConferencingError::InvalidSessionState { status } => {
+                AppError::BadRequest(format!("Cannot subscribe to tracks: {}", status))
+            }
+            VideoConferencingError::CloudflareApiError { message } => {
+                AppError::Internal(format!("Cloudflare error: {}", message))
+            }
+            VideoConferencingError::ProviderUnavailable { .. } => {
+                AppError::ExternalService(e.to_string()).with_code("video.provider_unavailable")
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(response))
+}
+
 /// Handle WebRTC renegotiation
 #[axum::debug_handler]
 pub async fn renegotiate_session(
@@ -186,9 +258,15 @@ pub async fn renegotiate_session(
             VideoConferencingError::SessionNotFound => {
                 AppError::NotFound("Video session not found".to_string())
             }
+            VideoConferencingError::Unauthorized => {
+                AppError::Auth("Not an active participant in this session".to_string())
+            }
             VideoConferencingError::InvalidSessionState { status } => {
                 AppError::BadRequest(format!("Cannot renegotiate: {}", status))
             }
+            VideoConferencingError::ProviderUnavailable { .. } => {
+                AppError::ExternalService(e.to_string()).with_code("video.provider_unavailable")
+            }
             _ => AppError::Internal(e.to_string()),
         })?;
     
@@ -223,6 +301,17 @@ pub async fn end_video_session(
             }
             _ => AppError::Internal(e.to_string()),
         })?;
+
+    // A recording still running when the call ends is stopped and filed
+    if session.recording_enabled {
+        let stopped = match SessionRecordingService::new(&state) {
+            Ok(recording_service) => recording_service.stop_if_recording(session_id, &user, token).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stopped {
+            warn!("Failed to stop recording of ended session {}: {}", session_id, e);
+        }
+    }
     
     Ok(Json(json!({
         "success": true,
@@ -231,6 +320,205 @@ pub async fn end_video_session(
     })))
 }
 
+/// Leave a video session without ending it for the other participants
+#[axum::debug_handler]
+pub async fn leave_video_session(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let session_service = VideoSessionService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    session_service
+        .leave_session(session_id, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::Unauthorized => {
+                AppError::Auth("Not an active participant in this session".to_string())
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Left video session"
+    })))
+}
+
+/// List everyone on a session's roster
+#[axum::debug_handler]
+pub async fn get_session_participants(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let session_service = VideoSessionService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let participants = session_service
+        .get_participants(session_id, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Auth("Not authorized to view this session".to_string())
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    let active = participants.iter().filter(|p| p.is_active()).count();
+
+    Ok(Json(json!({
+        "session_id": session_id,
+        "participants": participants,
+        "total": participants.len(),
+        "active": active
+    })))
+}
+
+/// Add an interpreter, observer or group member to a session's roster
+#[axum::debug_handler]
+pub async fn add_session_participant(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<AddParticipantRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let session_service = VideoSessionService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let participant = session_service
+        .add_participant(session_id, request, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Forbidden("Only the session's doctor or an admin can add participants".to_string())
+            }
+            VideoConferencingError::ValidationError { message } => {
+                AppError::BadRequest(message)
+            }
+            VideoConferencingError::SessionCapacityExceeded => {
+                AppError::BadRequest(e.to_string()).with_code("video.session_full")
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "participant": participant,
+        "message": "Participant added to video session"
+    })))
+}
+
+/// Start or stop recording a session (doctor-initiated)
+#[axum::debug_handler]
+pub async fn toggle_session_recording(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<ToggleRecordingRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let recording_service = SessionRecordingService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let (session, recording) = recording_service
+        .set_recording(session_id, request.enabled, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Forbidden("Only the session's doctor can start a recording".to_string())
+            }
+            VideoConferencingError::RecordingConsentRequired { missing } => {
+                let missing: Vec<String> = missing.iter().map(Uuid::to_string).collect();
+                AppError::Forbidden(format!("Recording consent missing for: {}", missing.join(", ")))
+                    .with_code("video.recording_consent_required")
+            }
+            VideoConferencingError::InvalidSessionState { status } => {
+                AppError::BadRequest(format!("Cannot change recording: {}", status))
+            }
+            VideoConferencingError::ValidationError { message } => {
+                AppError::BadRequest(message)
+            }
+            VideoConferencingError::CloudflareApiError { message } => {
+                AppError::ExternalService(format!("Cloudflare error: {}", message))
+            }
+            VideoConferencingError::ProviderUnavailable { .. } => {
+                AppError::ExternalService(e.to_string()).with_code("video.provider_unavailable")
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "recording_enabled": session.recording_enabled,
+        "recording": recording,
+        "message": if session.recording_enabled { "Recording started" } else { "Recording stopped" }
+    })))
+}
+
+/// Let patients waiting for the doctor know they're held up, e.g.
+/// "running ~10 min late". Only the session's doctor can send one.
+#[axum::debug_handler]
+pub async fn notify_waiting_participants(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<SessionNoticeRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let session_service = VideoSessionService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let (session, recipients) = session_service
+        .notify_waiting_participants(session_id, request, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Forbidden("Only the session's doctor can notify waiting participants".to_string())
+            }
+            VideoConferencingError::InvalidSessionState { status } => {
+                AppError::BadRequest(format!("Cannot notify participants of a session that is {}", status))
+            }
+            VideoConferencingError::ValidationError { message } => {
+                AppError::BadRequest(message)
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "notice": session.last_notice,
+        "recipients": recipients,
+        "message": "Waiting participants notified"
+    })))
+}
+
 /// Get video session details
 #[axum::debug_handler]
 pub async fn get_video_session(
@@ -266,6 +554,119 @@ pub async fn get_video_session(
     }
 }
 
+// ==============================================================================
+// INVITE HANDLERS
+// ==============================================================================
+
+/// Issue a single-use join link for an interpreter or observer
+#[axum::debug_handler]
+pub async fn create_session_invite(
+    State(state): State<Arc<AppConfig>>,
+    Path(session_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateInviteRequest>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let invite_service = VideoInviteService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let response = invite_service
+        .create_invite(session_id, request, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Forbidden("Not allowed to invite participants to this session".to_string())
+            }
+            VideoConferencingError::InvalidSessionState { status } => {
+                AppError::BadRequest(format!("Video session is no longer active: {}", status))
+            }
+            VideoConferencingError::ValidationError { message } => {
+                AppError::BadRequest(message)
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "invite": response.invite,
+        "join_url": response.join_url,
+        "message": "Invite link created"
+    })))
+}
+
+/// Revoke an invite link, removing the guest if they already used it
+#[axum::debug_handler]
+pub async fn revoke_session_invite(
+    State(state): State<Arc<AppConfig>>,
+    Path((session_id, invite_id)): Path<(Uuid, Uuid)>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let invite_service = VideoInviteService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let invite = invite_service
+        .revoke_invite(session_id, invite_id, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound | VideoConferencingError::InvalidInvite { .. } => {
+                AppError::NotFound("Invite not found".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Forbidden("Not allowed to revoke this invite".to_string())
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "invite": invite,
+        "message": "Invite revoked"
+    })))
+}
+
+/// Redeem an invite link for a guest token (no sign-in required)
+#[axum::debug_handler]
+pub async fn redeem_session_invite(
+    State(state): State<Arc<AppConfig>>,
+    Path(link_token): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let invite_service = VideoInviteService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let redeemed = invite_service
+        .redeem_invite(&link_token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::InvalidInvite { .. } => {
+                AppError::Forbidden(e.to_string()).with_code("video.invite_invalid")
+            }
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("Video session not found".to_string())
+            }
+            VideoConferencingError::InvalidSessionState { status } => {
+                AppError::BadRequest(format!("Video session is no longer active: {}", status))
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "session_id": redeemed.session_id,
+        "participant_type": redeemed.participant_type,
+        "access_token": redeemed.access_token,
+        "expires_at": redeemed.expires_at,
+        "join_path": format!("/video/sessions/{}/join", redeemed.session_id)
+    })))
+}
+
 // ==============================================================================
 // APPOINTMENT INTEGRATION HANDLERS
 // ==============================================================================
@@ -291,6 +692,9 @@ pub async fn create_session_for_appointment(
             VideoConferencingError::InvalidAppointment => {
                 AppError::NotFound("Appointment not found".to_string())
             }
+            VideoConferencingError::ConsentRequired { .. } => {
+                AppError::Forbidden(e.to_string()).with_code("video.consent_required")
+            }
             VideoConferencingError::ValidationError { message } => {
                 AppError::BadRequest(message)
             }
@@ -360,6 +764,43 @@ pub async fn check_video_availability(
     })))
 }
 
+/// Regenerate patient/doctor join URLs for an appointment with a fresh expiry
+#[axum::debug_handler]
+pub async fn refresh_join_urls(
+    State(state): State<Arc<AppConfig>>,
+    Path(appointment_id): Path<Uuid>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let integration_service = VideoConferencingIntegrationService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let refreshed = integration_service
+        .refresh_join_urls_for_appointment(appointment_id, &user, token)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::SessionNotFound => {
+                AppError::NotFound("No video session found for this appointment".to_string())
+            }
+            VideoConferencingError::Unauthorized => {
+                AppError::Auth("Not authorized for this appointment".to_string())
+            }
+            VideoConferencingError::InvalidSessionState { status } => {
+                AppError::BadRequest(format!("Video session is no longer active: {}", status))
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "session_id": refreshed.session_id,
+        "join_urls": refreshed.join_urls,
+        "expires_at": refreshed.expires_at,
+    })))
+}
+
 /// Get video stats for an appointment
 #[axum::debug_handler]
 pub async fn get_appointment_video_stats(
@@ -389,6 +830,70 @@ pub async fn get_appointment_video_stats(
     Ok(Json(stats))
 }
 
+// ==============================================================================
+// READINESS SELF-TEST HANDLERS
+// ==============================================================================
+
+/// Start a pre-call camera/microphone/bandwidth self-test
+#[axum::debug_handler]
+pub async fn start_readiness_test(
+    State(state): State<Arc<AppConfig>>,
+    Extension(user): Extension<User>,
+    locale: Locale,
+) -> Result<Json<Value>, AppError> {
+    let readiness_service = TelemedicineReadinessService::new(&state)
+        .map_err(|e| match e {
+            VideoConferencingError::NotConfigured => {
+                AppError::Internal("Video conferencing not configured".to_string())
+            }
+            VideoConferencingError::ProviderUnavailable { .. } => {
+                AppError::ExternalService(e.to_string()).with_code("video.provider_unavailable")
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    let test = readiness_service
+        .start_self_test(&user, locale.0)
+        .await
+        .map_err(|e| match e {
+            VideoConferencingError::CloudflareApiError { message } => {
+                AppError::ExternalService(message)
+            }
+            _ => AppError::Internal(e.to_string()),
+        })?;
+
+    Ok(Json(json!(test)))
+}
+
+/// Submit client-measured self-test results and store a readiness report
+#[axum::debug_handler]
+pub async fn submit_readiness_results(
+    State(state): State<Arc<AppConfig>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Extension(user): Extension<User>,
+    Json(results): Json<ReadinessTestResults>,
+) -> Result<Json<Value>, AppError> {
+    let token = auth.token();
+
+    let readiness_service = TelemedicineReadinessService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let report = readiness_service
+        .record_results(results, &user, token)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(json!({
+        "success": true,
+        "report": report,
+        "message": if report.is_ready {
+            "Your device is ready for video consultations"
+        } else {
+            "Some checks failed; review the reported issues before your appointment"
+        }
+    })))
+}
+
 // ==============================================================================
 // SYSTEM ADMINISTRATION HANDLERS
 // ==============================================================================
@@ -413,11 +918,13 @@ pub async fn video_health_check(
         .health_check()
         .await
         .unwrap_or(false);
+    let circuit_breaker = cloudflare_client.circuit_breaker().snapshot();
     
     Ok(Json(json!({
         "status": if cloudflare_healthy { "healthy" } else { "unhealthy" },
         "video_configured": true,
         "cloudflare_status": if cloudflare_healthy { "connected" } else { "error" },
+        "circuit_breaker": circuit_breaker,
         "message": if cloudflare_healthy {
             "Video conferencing system is operational"
         } else {
@@ -426,7 +933,15 @@ pub async fn video_health_check(
     })))
 }
 
-/// Admin: Cleanup expired sessions
+/// Admin: Session creation, join success/failure and join latency counters
+#[axum::debug_handler]
+pub async fn get_video_metrics(
+    _admin: RequireRole<Admin>,
+) -> Result<Json<Value>, AppError> {
+    Ok(Json(json!(video_metrics().snapshot())))
+}
+
+/// Admin: Cleanup expired sessions and reclaim orphaned ones
 #[axum::debug_handler]
 pub async fn cleanup_expired_sessions(
     State(state): State<Arc<AppConfig>>,
@@ -447,10 +962,22 @@ pub async fn cleanup_expired_sessions(
         .cleanup_expired_sessions(token)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let session_service = VideoSessionService::new(&state)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let reclaimed_count = session_service
+        .reconcile_orphaned_sessions(token)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
     
     Ok(Json(json!({
         "success": true,
         "cleaned_sessions": cleaned_count,
-        "message": format!("Cleaned up {} expired sessions", cleaned_count)
+        "reclaimed_sessions": reclaimed_count,
+        "message": format!(
+            "Cleaned up {} expired sessions and reclaimed {} orphaned sessions",
+            cleaned_count, reclaimed_count
+        )
     })))
 }
\ No newline at end of file
@@ -6,7 +6,8 @@
 //! 
 //! ## Features
 //! 
-//! - **WebRTC Video Sessions**: Direct patient-doctor video calls
+//! - **WebRTC Video Sessions**: Patient-doctor video calls and group sessions
+//! - **Participant Roster**: Interpreters, observers and group members beyond the two primary parties
 //! - **Cloudflare Realtime Integration**: Serverless SFU for reliable connections  
 //! - **Appointment Integration**: Automatic session creation tied to appointments
 //! - **Session Management**: Join, leave, track management, renegotiation
@@ -26,8 +27,13 @@
 //! |  models.rs      |  Data structures & DTOs           |
 //! |  services/      |  Business logic layer             |
 //! |    cloudflare.rs|  Cloudflare Realtime API client   |
+//! |    circuit_breaker.rs| Cloudflare outage protection |
 //! |    session.rs   |  Video session management         |
 //! |    integration.rs| Appointment system integration   |
+//! |    invites.rs   |  Single-use guest invite links    |
+//! |    readiness.rs |  Pre-call readiness self-test     |
+//! |    recording.rs |  Consent-gated session recording  |
+//! |    metrics.rs   |  Session and join success rates   |
 //! +-----------------------------------------------------+
 //! ```
 //! 
@@ -38,20 +44,32 @@
 //! - `GET /video/sessions/{id}` - Get session details
 //! - `POST /video/sessions/{id}/join` - Join session
 //! - `POST /video/sessions/{id}/tracks` - Add audio/video tracks
+//! - `POST /video/sessions/{id}/tracks/subscribe` - Subscribe to other participants' tracks
 //! - `PUT /video/sessions/{id}/renegotiate` - Handle WebRTC renegotiation
+//! - `POST /video/sessions/{id}/leave` - Leave without ending the session
+//! - `POST /video/sessions/{id}/recording` - Start/stop recording (doctor, consent required)
 //! - `DELETE /video/sessions/{id}/end` - End session
+//! - `GET /video/sessions/{id}/participants` - Participant roster
+//! - `POST /video/sessions/{id}/participants` - Add an interpreter, observer or group member
+//! - `POST /video/sessions/{id}/invite` - Issue a single-use interpreter/observer join link
+//! - `DELETE /video/sessions/{id}/invites/{invite_id}` - Revoke an invite link
+//! - `POST /video/invites/{token}` - Redeem an invite link for a guest token (public)
 //! 
 //! ### Appointment Integration
 //! - `POST /video/appointments/{id}/session` - Create session for appointment
 //! - `GET /video/appointments/{id}/availability` - Check video availability
 //! - `GET /video/appointments/{id}/stats` - Get video statistics
+//! - `POST /video/appointments/{id}/join-urls/refresh` - Regenerate expired join URLs
 //! 
 //! ### User Management
 //! - `GET /video/upcoming` - Get upcoming sessions
+//! - `GET /video/test` - Start a camera/microphone/bandwidth self-test
+//! - `POST /video/test/results` - Submit self-test results
 //! 
 //! ### System Administration
 //! - `GET /video/health` - Health check
-//! - `POST /video/admin/cleanup` - Cleanup expired sessions
+//! - `POST /video/admin/cleanup` - Cleanup expired sessions and reclaim orphaned ones
+//! - `GET /video/admin/metrics` - Session and join success/failure counters
 //! 
 //! ## Usage Example
 //! 
@@ -69,7 +87,10 @@
 //! Required environment variables:
 //! - `CLOUDFLARE_REALTIME_APP_ID` - Cloudflare app identifier
 //! - `CLOUDFLARE_REALTIME_API_TOKEN` - API authentication token
-//! - `CLOUDFLARE_REALTIME_BASE_URL` - API base URL (optional, defaults to production)
+//! - `CLOUDFLARE_REALTIME_BASE_URL` - API base URL (optional, defaults to production; must be https, startup fails otherwise)
+//! - `VIDEO_JOIN_URL_TTL_MINUTES` - Join URL lifetime (optional, defaults to 120)
+//! - `VIDEO_SESSION_RECONCILE_INTERVAL_MINUTES` - How often orphaned sessions are reclaimed (optional, defaults to 15, 0 disables)
+//! - `VIDEO_JOIN_FAILURE_ALERT_PERCENT` - Recent join failure rate that raises an alert (optional, defaults to 20, 0 disables)
 //! 
 //! ## Integration with Appointment Cell
 //! 
@@ -86,7 +107,8 @@
 //! let appointment_id = Uuid::new_v4();
 //! let token = "token";
 //! 
-//! // Automatically create video session when appointment is confirmed
+//! // Automatically create video session when appointment is confirmed;
+//! // a repeated event returns the session that already exists
 //! let integration = VideoConferencingIntegrationService::new(&config)?;
 //! integration.handle_appointment_status_change(appointment_id, "confirmed", token).await?;
 //! # Ok(())
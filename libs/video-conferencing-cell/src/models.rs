@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+use shared_config::AppConfig;
+
 // ==============================================================================
 // VIDEO CONFERENCING DOMAIN MODELS
 // ==============================================================================
@@ -23,6 +25,13 @@ pub struct VideoSession {
     pub session_duration_minutes: Option<i32>,
     pub quality_rating: Option<i32>, // 1-5 stars
     pub connection_issues: Vec<String>,
+    #[serde(default)]
+    pub join_urls_expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub recording_enabled: bool,
+    /// The doctor's most recent message to the waiting room
+    #[serde(default)]
+    pub last_notice: Option<SessionNotice>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -51,6 +60,19 @@ pub enum VideoSessionType {
     FollowUp,
     #[serde(rename = "emergency")]
     Emergency,
+    #[serde(rename = "group_session")]
+    GroupSession,    // Several patients with one clinician, e.g. group therapy
+}
+
+impl VideoSessionType {
+    /// Most participants connected at once. One-to-one sessions leave room
+    /// for an interpreter and an observer alongside the patient and doctor.
+    pub fn max_participants(&self) -> usize {
+        match self {
+            VideoSessionType::GroupSession => 12,
+            _ => 4,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,14 +85,31 @@ pub struct SessionParticipant {
     pub connection_quality: Option<ConnectionQuality>,
     pub audio_enabled: bool,
     pub video_enabled: bool,
+    /// Each participant has their own Cloudflare session (one peer connection)
+    #[serde(default)]
+    pub cloudflare_session_id: Option<String>,
+    /// Names of the local tracks this participant has published
+    #[serde(default)]
+    pub published_tracks: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl SessionParticipant {
+    /// Joined and hasn't left. Rostered participants who never joined aren't active.
+    pub fn is_active(&self) -> bool {
+        self.joined_at.is_some() && self.left_at.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ParticipantType {
     #[serde(rename = "patient")]
     Patient,
     #[serde(rename = "doctor")]
     Doctor,
+    #[serde(rename = "interpreter")]
+    Interpreter,
+    #[serde(rename = "observer")]
+    Observer,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -156,6 +195,27 @@ pub struct TrackResult {
     pub error_description: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudflareRecordingRequest {
+    #[serde(rename = "sessionIds")]
+    pub session_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudflareRecordingResponse {
+    #[serde(rename = "recordingId")]
+    pub recording_id: String,
+    /// Set once a stopped recording has been written out
+    #[serde(rename = "downloadUrl", skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(rename = "errorCode", skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(rename = "errorDescription", skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloudflareRenegotiateRequest {
     #[serde(rename = "sessionDescription")]
@@ -178,9 +238,17 @@ pub struct CreateVideoSessionResponse {
     pub success: bool,
     pub session: VideoSession,
     pub join_urls: HashMap<String, String>, // participant_type -> join_url
+    pub join_urls_expires_at: DateTime<Utc>,
     pub message: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct JoinUrlsResponse {
+    pub session_id: Uuid,
+    pub join_urls: HashMap<String, String>,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct JoinSessionRequest {
     pub user_type: ParticipantType,
@@ -207,6 +275,13 @@ pub struct IceServer {
     pub credential: Option<String>,
 }
 
+/// Put someone on a session's roster ahead of time
+#[derive(Debug, Deserialize)]
+pub struct AddParticipantRequest {
+    pub user_id: Uuid,
+    pub user_type: ParticipantType,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AddTracksRequest {
     pub tracks: Vec<TrackObject>,
@@ -232,6 +307,23 @@ pub struct UpdateSessionRequest {
     pub connection_issues: Option<Vec<String>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SessionNoticeRequest {
+    /// Shown as is; when left out, a message is built from `delay_minutes`
+    pub message: Option<String>,
+    pub delay_minutes: Option<i32>,
+}
+
+/// A message from the doctor to participants waiting for them to join,
+/// e.g. that they're running late
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionNotice {
+    pub message: String,
+    pub delay_minutes: Option<i32>,
+    pub sent_by: Uuid,
+    pub sent_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct VideoSessionStatsResponse {
     pub session: VideoSession,
@@ -240,6 +332,137 @@ pub struct VideoSessionStatsResponse {
     pub connection_quality_summary: HashMap<String, i32>,
 }
 
+// ==============================================================================
+// RECORDING MODELS
+// ==============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ToggleRecordingRequest {
+    pub enabled: bool,
+}
+
+/// One start/stop span of a session recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecording {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub cloudflare_recording_id: String,
+    pub started_by: Uuid,
+    pub started_at: DateTime<Utc>,
+    /// Everyone whose recording consent was checked when it started
+    pub consented_participants: Vec<Uuid>,
+    pub stopped_by: Option<Uuid>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    /// The patient document holding the recording, once it is available
+    pub document_id: Option<Uuid>,
+}
+
+// ==============================================================================
+// INVITE MODELS
+// ==============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInviteRequest {
+    /// Defaults to an interpreter; invites can't be issued for patients or doctors
+    pub participant_type: Option<ParticipantType>,
+    pub expires_in_minutes: Option<i64>,
+    /// Who the link is for, e.g. "BSL interpreter", shown to the clinician only
+    pub label: Option<String>,
+}
+
+/// A single-use join link. Only a hash of the link token is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoSessionInvite {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub participant_type: ParticipantType,
+    pub label: Option<String>,
+    pub created_by: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InviteLinkResponse {
+    pub invite: VideoSessionInvite,
+    pub join_url: String,
+}
+
+/// What an invitee gets for redeeming a link: a guest token scoped to the
+/// session's roster, never the patient's or doctor's own credentials
+#[derive(Debug, Serialize)]
+pub struct RedeemInviteResponse {
+    pub session_id: Uuid,
+    pub participant_type: ParticipantType,
+    pub access_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// ==============================================================================
+// TELEMEDICINE READINESS MODELS
+// ==============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessTestResponse {
+    pub test_session_id: String,
+    pub ice_servers: Vec<IceServer>,
+    pub rtc_configuration: serde_json::Value,
+    pub instructions: Vec<String>,
+    pub min_bandwidth_kbps: i32,
+    pub max_latency_ms: i32,
+    pub support: SupportContact,
+}
+
+/// Where patients can get help, from this deployment's branding config.
+/// Unconfigured contacts are left out rather than defaulted, so staging and
+/// white-label deployments never point patients at another clinic.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SupportContact {
+    pub clinic_name: String,
+    pub email: Option<String>,
+    pub help_url: Option<String>,
+}
+
+impl SupportContact {
+    /// Contact details with `help_url` pointing at `topic` under the help pages
+    pub fn from_config(config: &AppConfig, topic: &str) -> Self {
+        Self {
+            clinic_name: config.clinic_name.clone(),
+            email: config.support_email.clone(),
+            help_url: config.help_url_base.as_ref()
+                .map(|base| format!("{}/{}", base.trim_end_matches('/'), topic.trim_start_matches('/'))),
+        }
+    }
+}
+
+/// Client-measured results of the pre-call self-test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessTestResults {
+    pub test_session_id: Option<String>,
+    pub camera_available: bool,
+    pub microphone_available: bool,
+    pub bandwidth_kbps: Option<i32>,
+    pub latency_ms: Option<i32>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemedicineReadinessReport {
+    pub id: Uuid,
+    pub user_id: String,
+    pub test_session_id: Option<String>,
+    pub camera_available: bool,
+    pub microphone_available: bool,
+    pub bandwidth_kbps: Option<i32>,
+    pub latency_ms: Option<i32>,
+    pub user_agent: Option<String>,
+    pub is_ready: bool,
+    pub issues: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
 // ==============================================================================
 // ERROR HANDLING
 // ==============================================================================
@@ -261,12 +484,27 @@ pub enum VideoConferencingError {
     #[error("Cloudflare API error: {message}")]
     CloudflareApiError { message: String },
     
+    #[error("Video provider is unavailable: {message}")]
+    ProviderUnavailable { message: String },
+    
     #[error("WebRTC configuration error: {message}")]
     WebRTCError { message: String },
     
     #[error("Session capacity exceeded")]
     SessionCapacityExceeded,
     
+    #[error("Recording consent has not been given by every participant")]
+    RecordingConsentRequired { missing: Vec<Uuid> },
+    
+    #[error("Join links for this session have expired")]
+    JoinLinksExpired,
+
+    #[error("Invite link is not valid: {reason}")]
+    InvalidInvite { reason: String },
+    
+    #[error("Patient has not given {consent_type} consent")]
+    ConsentRequired { consent_type: String },
+    
     #[error("Video conferencing not configured")]
     NotConfigured,
     
@@ -17,7 +17,9 @@ use crate::handlers::*;
 pub fn video_conferencing_routes(state: Arc<AppConfig>) -> Router {
     // Public routes (no authentication required)
     let public_routes = Router::new()
-        .route("/health", get(video_health_check));
+        .route("/health", get(video_health_check))
+        // Invite links carry their own credential
+        .route("/invites/{link_token}", post(redeem_session_invite));
 
     // Protected routes (authentication required)
     let protected_routes = Router::new()
@@ -26,19 +28,37 @@ pub fn video_conferencing_routes(state: Arc<AppConfig>) -> Router {
         .route("/sessions/{session_id}", get(get_video_session))
         .route("/sessions/{session_id}/join", post(join_video_session))
         .route("/sessions/{session_id}/tracks", post(add_session_tracks))
+        .route("/sessions/{session_id}/tracks/subscribe", post(subscribe_session_tracks))
         .route("/sessions/{session_id}/renegotiate", put(renegotiate_session))
+        .route("/sessions/{session_id}/leave", post(leave_video_session))
+        .route("/sessions/{session_id}/recording", post(toggle_session_recording))
+        .route("/sessions/{session_id}/notify", post(notify_waiting_participants))
         .route("/sessions/{session_id}/end", delete(end_video_session))
+
+        // Participant roster
+        .route("/sessions/{session_id}/participants", get(get_session_participants))
+        .route("/sessions/{session_id}/participants", post(add_session_participant))
+
+        // Interpreter/observer invite links
+        .route("/sessions/{session_id}/invite", post(create_session_invite))
+        .route("/sessions/{session_id}/invites/{invite_id}", delete(revoke_session_invite))
         
         // Appointment integration
         .route("/appointments/{appointment_id}/session", post(create_session_for_appointment))
         .route("/appointments/{appointment_id}/availability", get(check_video_availability))
         .route("/appointments/{appointment_id}/stats", get(get_appointment_video_stats))
+        .route("/appointments/{appointment_id}/join-urls/refresh", post(refresh_join_urls))
         
         // User session management
         .route("/upcoming", get(get_upcoming_sessions))
+
+        // Pre-call readiness self-test
+        .route("/test", get(start_readiness_test))
+        .route("/test/results", post(submit_readiness_results))
         
         // Admin endpoints
         .route("/admin/cleanup", post(cleanup_expired_sessions))
+        .route("/admin/metrics", get(get_video_metrics))
         
         // Apply authentication middleware to all protected routes
         .layer(middleware::from_fn_with_state(state.clone(), auth_middleware));
@@ -0,0 +1,158 @@
+// libs/video-conferencing-cell/src/services/circuit_breaker.rs
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stops calling a provider that keeps failing. After `failure_threshold`
+/// consecutive failures the breaker opens and calls are refused outright;
+/// once `open_duration` has passed a single trial call is let through, and
+/// its outcome closes the breaker again or keeps it open for another round.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitBreakerSnapshot {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Seconds until a trial call is allowed, while open
+    pub retry_after_seconds: Option<u64>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Whether a call may go ahead. Taking the half-open trial re-arms the
+    /// open timer, so only one caller probes the provider at a time and a
+    /// trial that never reports back doesn't wedge the breaker.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.opened_at {
+            None => true,
+            Some(opened_at) if now.duration_since(opened_at) >= self.open_duration => {
+                state.opened_at = Some(now);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+    }
+
+    /// Count a failed call. Returns true if this failure opened the breaker.
+    pub fn record_failure(&self) -> bool {
+        self.record_failure_at(Instant::now())
+    }
+
+    fn record_failure_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.consecutive_failures < self.failure_threshold {
+            return false;
+        }
+
+        let was_closed = state.opened_at.is_none();
+        state.opened_at = Some(now);
+        was_closed
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.snapshot().state
+    }
+
+    pub fn snapshot(&self) -> CircuitBreakerSnapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> CircuitBreakerSnapshot {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let remaining = state.opened_at
+            .map(|opened_at| self.open_duration.saturating_sub(now.duration_since(opened_at)));
+
+        CircuitBreakerSnapshot {
+            state: match remaining {
+                None => CircuitState::Closed,
+                Some(remaining) if remaining.is_zero() => CircuitState::HalfOpen,
+                Some(_) => CircuitState::Open,
+            },
+            consecutive_failures: state.consecutive_failures,
+            retry_after_seconds: remaining
+                .filter(|remaining| !remaining.is_zero())
+                .map(|remaining| remaining.as_secs().max(1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN_FOR: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, OPEN_FOR);
+        let now = Instant::now();
+
+        assert!(!breaker.record_failure_at(now));
+        breaker.record_success();
+        assert!(!breaker.record_failure_at(now));
+        assert!(!breaker.record_failure_at(now));
+        assert_eq!(breaker.snapshot_at(now).state, CircuitState::Closed);
+
+        assert!(breaker.record_failure_at(now));
+        assert_eq!(breaker.snapshot_at(now).state, CircuitState::Open);
+        assert_eq!(breaker.snapshot_at(now).retry_after_seconds, Some(30));
+        assert!(!breaker.try_acquire_at(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_half_open_allows_a_single_trial() {
+        let breaker = CircuitBreaker::new(1, OPEN_FOR);
+        let start = Instant::now();
+        breaker.record_failure_at(start);
+
+        let later = start + OPEN_FOR;
+        assert_eq!(breaker.snapshot_at(later).state, CircuitState::HalfOpen);
+        assert!(breaker.try_acquire_at(later));
+        assert!(!breaker.try_acquire_at(later + Duration::from_secs(1)));
+
+        // A failed trial keeps it open for another round
+        assert!(!breaker.record_failure_at(later + Duration::from_secs(1)));
+        assert_eq!(breaker.snapshot_at(later + Duration::from_secs(2)).state, CircuitState::Open);
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire());
+    }
+}
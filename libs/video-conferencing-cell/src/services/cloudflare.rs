@@ -1,15 +1,45 @@
 // libs/video-conferencing-cell/src/services/cloudflare.rs
 use anyhow::Result;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
 use shared_config::AppConfig;
 
 use crate::models::{
-    CloudflareRenegotiateRequest, CloudflareSessionRequest, CloudflareSessionResponse,
+    CloudflareRecordingRequest, CloudflareRecordingResponse, CloudflareRenegotiateRequest, CloudflareSessionRequest, CloudflareSessionResponse,
     CloudflareTrackRequest, CloudflareTrackResponse, IceServer, SessionDescription,
     TrackObject, VideoConferencingError,
 };
+use crate::services::circuit_breaker::CircuitBreaker;
+
+/// Attempts per Cloudflare call before the call counts as failed
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled for each one after
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// Failed calls in a row that open the breaker
+const BREAKER_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open breaker refuses calls before letting a trial through
+const BREAKER_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// Clients are built per request, so breakers live here, one per base URL
+fn circuit_breaker_for(base_url: &str) -> Arc<CircuitBreaker> {
+    static BREAKERS: OnceLock<Mutex<HashMap<String, Arc<CircuitBreaker>>>> = OnceLock::new();
+    let mut breakers = BREAKERS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    breakers
+        .entry(base_url.to_string())
+        .or_insert_with(|| Arc::new(CircuitBreaker::new(BREAKER_FAILURE_THRESHOLD, BREAKER_OPEN_DURATION)))
+        .clone()
+}
 
 /// Cloudflare Realtime API client for managing WebRTC sessions and tracks
 /// Based on: https://developers.cloudflare.com/realtime/
@@ -18,6 +48,7 @@ pub struct CloudflareRealtimeClient {
     app_id: String,
     api_token: String,
     base_url: String,
+    breaker: Arc<CircuitBreaker>,
 }
 
 impl CloudflareRealtimeClient {
@@ -33,6 +64,7 @@ impl CloudflareRealtimeClient {
             app_id: config.cloudflare_realtime_app_id.clone(),
             api_token: config.cloudflare_realtime_api_token.clone(),
             base_url: config.cloudflare_realtime_base_url.clone(),
+            breaker: circuit_breaker_for(&config.cloudflare_realtime_base_url),
         })
     }
 
@@ -55,18 +87,16 @@ impl CloudflareRealtimeClient {
 
         debug!("Sending session creation request to: {}", url);
 
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
+        let (status, response_text) = self
+            .send(|| {
+                self.client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", self.api_token))
+                    .header("Content-Type", "application/json")
+                    .json(&request_body)
+            })
             .await?;
 
-        let status = response.status();
-        let response_text = response.text().await?;
-
         debug!("Cloudflare session creation response: {} - {}", status, response_text);
 
         if !status.is_success() {
@@ -87,6 +117,43 @@ impl CloudflareRealtimeClient {
         Ok(session_response)
     }
 
+    /// Create a session without an initial offer, used for connectivity self-tests
+    /// POST /v1/apps/{appId}/sessions/new
+    pub async fn create_test_session(&self) -> Result<String, VideoConferencingError> {
+        info!("Creating Cloudflare Realtime test session");
+
+        let url = format!("{}/apps/{}/sessions/new", self.base_url, self.app_id);
+
+        let (status, response_text) = self
+            .send(|| {
+                self.client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", self.api_token))
+                    .header("Content-Type", "application/json")
+                    .json(&serde_json::json!({}))
+            })
+            .await?;
+
+        if !status.is_success() {
+            error!("Cloudflare test session creation failed: {} - {}", status, response_text);
+            return Err(VideoConferencingError::CloudflareApiError {
+                message: format!("HTTP {}: {}", status, response_text),
+            });
+        }
+
+        let body: serde_json::Value = serde_json::from_str(&response_text)
+            .map_err(|e| VideoConferencingError::CloudflareApiError {
+                message: format!("Failed to parse session response: {}", e),
+            })?;
+
+        body["sessionId"]
+            .as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| VideoConferencingError::CloudflareApiError {
+                message: "Session response missing sessionId".to_string(),
+            })
+    }
+
     /// Add new tracks to an existing session (publish local tracks or request remote tracks)
     /// POST /v1/apps/{appId}/sessions/{sessionId}/tracks/new
     pub async fn add_tracks(
@@ -116,18 +183,16 @@ impl CloudflareRealtimeClient {
 
         debug!("Sending add tracks request to: {}", url);
 
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
+        let (status, response_text) = self
+            .send(|| {
+                self.client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", self.api_token))
+                    .header("Content-Type", "application/json")
+                    .json(&request_body)
+            })
             .await?;
 
-        let status = response.status();
-        let response_text = response.text().await?;
-
         debug!("Cloudflare add tracks response: {} - {}", status, response_text);
 
         if !status.is_success() {
@@ -171,19 +236,17 @@ impl CloudflareRealtimeClient {
 
         debug!("Sending renegotiation request to: {}", url);
 
-        let response = self
-            .client
-            .put(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
+        let (status, response_text) = self
+            .send(|| {
+                self.client
+                    .put(&url)
+                    .header("Authorization", format!("Bearer {}", self.api_token))
+                    .header("Content-Type", "application/json")
+                    .json(&request_body)
+            })
             .await?;
 
-        let status = response.status();
-
         if !status.is_success() {
-            let response_text = response.text().await?;
             error!("Cloudflare renegotiation failed: {} - {}", status, response_text);
             return Err(VideoConferencingError::CloudflareApiError {
                 message: format!("HTTP {}: {}", status, response_text),
@@ -221,6 +284,137 @@ impl CloudflareRealtimeClient {
         Ok(())
     }
 
+    /// Start recording the given sessions' tracks
+    /// POST /v1/apps/{appId}/recordings
+    pub async fn start_recording(
+        &self,
+        session_ids: Vec<String>,
+    ) -> Result<CloudflareRecordingResponse, VideoConferencingError> {
+        info!("Starting recording of {} Cloudflare sessions", session_ids.len());
+
+        let url = format!("{}/apps/{}/recordings", self.base_url, self.app_id);
+        let request_body = CloudflareRecordingRequest { session_ids };
+
+        self.send_recording_request(&url, serde_json::json!(request_body)).await
+    }
+
+    /// Stop a recording; the response carries the download URL once written
+    /// POST /v1/apps/{appId}/recordings/{recordingId}/stop
+    pub async fn stop_recording(
+        &self,
+        recording_id: &str,
+    ) -> Result<CloudflareRecordingResponse, VideoConferencingError> {
+        info!("Stopping recording: {}", recording_id);
+
+        let url = format!(
+            "{}/apps/{}/recordings/{}/stop",
+            self.base_url, self.app_id, recording_id
+        );
+
+        self.send_recording_request(&url, serde_json::json!({})).await
+    }
+
+    async fn send_recording_request(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<CloudflareRecordingResponse, VideoConferencingError> {
+        debug!("Sending recording request to: {}", url);
+
+        let (status, response_text) = self
+            .send(|| {
+                self.client
+                    .post(url)
+                    .header("Authorization", format!("Bearer {}", self.api_token))
+                    .header("Content-Type", "application/json")
+                    .json(&body)
+            })
+            .await?;
+
+        if !status.is_success() {
+            error!("Cloudflare recording request failed: {} - {}", status, response_text);
+            return Err(VideoConferencingError::CloudflareApiError {
+                message: format!("HTTP {}: {}", status, response_text),
+            });
+        }
+
+        let recording_response: CloudflareRecordingResponse = serde_json::from_str(&response_text)
+            .map_err(|e| VideoConferencingError::CloudflareApiError {
+                message: format!("Failed to parse recording response: {}", e),
+            })?;
+
+        if let Some(error_code) = &recording_response.error_code {
+            let message = recording_response
+                .error_description
+                .as_deref()
+                .unwrap_or("Unknown error");
+            error!("Cloudflare recording error: {} - {}", error_code, message);
+            return Err(VideoConferencingError::CloudflareApiError {
+                message: format!("{}: {}", error_code, message),
+            });
+        }
+
+        Ok(recording_response)
+    }
+
+    /// Send a request, retrying transport errors and 5xx/429 responses with
+    /// backoff. A call that still fails counts against the circuit breaker;
+    /// while the breaker is open calls fail fast with `ProviderUnavailable`.
+    async fn send(
+        &self,
+        build_request: impl Fn() -> RequestBuilder,
+    ) -> Result<(StatusCode, String), VideoConferencingError> {
+        if !self.breaker.try_acquire() {
+            warn!("Cloudflare circuit breaker is open, not calling {}", self.base_url);
+            return Err(VideoConferencingError::ProviderUnavailable {
+                message: "Cloudflare Realtime is temporarily unavailable".to_string(),
+            });
+        }
+
+        let mut attempt = 1;
+        loop {
+            let failure = match build_request().send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    match response.text().await {
+                        Ok(text) if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+                            format!("HTTP {}: {}", status, text)
+                        }
+                        Ok(text) => {
+                            self.breaker.record_success();
+                            return Ok((status, text));
+                        }
+                        Err(e) => e.to_string(),
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= MAX_ATTEMPTS {
+                if self.breaker.record_failure() {
+                    error!(
+                        "Cloudflare circuit breaker opened after {} failed calls: {}",
+                        BREAKER_FAILURE_THRESHOLD, failure
+                    );
+                }
+                return Err(VideoConferencingError::ProviderUnavailable { message: failure });
+            }
+
+            let delay = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            warn!(
+                "Cloudflare call failed (attempt {}/{}), retrying in {:?}: {}",
+                attempt, MAX_ATTEMPTS, delay, failure
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// The breaker guarding this client's base URL
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
     /// Check for errors in session response
     fn check_session_errors(
         &self,
@@ -311,6 +505,35 @@ mod tests {
             cloudflare_realtime_app_id: "test-app-id".to_string(),
             cloudflare_realtime_api_token: "test-token".to_string(),
             cloudflare_realtime_base_url: "https://test.cloudflare.com/v1".to_string(),
+            video_join_url_ttl_minutes: 120,
+            cors_allowed_origins: Vec::new(),
+            trusted_proxies: Vec::new(),
+            cors_allow_all: false,
+            graceful_shutdown_timeout_seconds: 30,
+            clinic_name: "Amae Clinic".to_string(),
+            clinic_address: String::new(),
+            clinic_contact: String::new(),
+            clinical_record_retention_years: 10,
+            supabase_jwks_url: String::new(),
+            jwks_cache_ttl_seconds: 600,
+            allow_any_doctor_profile_access: false,
+            video_session_reconcile_interval_minutes: 15,
+            appointment_rules: Default::default(),
+            supabase_slow_query_threshold_ms: 1000,
+            video_join_failure_alert_percent: 20,
+            response_compression_enabled: true,
+            max_document_upload_bytes: 10 * 1024 * 1024,
+            feature_flags: Default::default(),
+            availability_cache_ttl_seconds: 0,
+            clinic_id: None,
+            appointment_stats_cache_ttl_seconds: 0,
+            support_email: None,
+            help_url_base: None,
+            batch_conflict_check_concurrency: 5,
+            batch_conflict_check_timeout_seconds: 10,
+            batch_conflict_check_max_size: 100,
+            webhook_allow_insecure_urls: false,
+            webhook_worker_key: None,
         }
     }
 
@@ -349,4 +572,52 @@ mod tests {
         assert!(rtc_config["iceServers"].is_array());
         assert_eq!(rtc_config["bundlePolicy"], "max-bundle");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_transient_server_error_is_retried() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apps/test-app-id/sessions/new"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/apps/test-app-id/sessions/new"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "sessionId": "cf-1" })))
+            .mount(&mock_server)
+            .await;
+
+        let mut config = create_test_config();
+        config.cloudflare_realtime_base_url = mock_server.uri();
+        let client = CloudflareRealtimeClient::new(&config).unwrap();
+
+        assert_eq!(client.create_test_session().await.unwrap(), "cf-1");
+        assert_eq!(client.circuit_breaker().snapshot().consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_report_provider_unavailable() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apps/test-app-id/sessions/new"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(MAX_ATTEMPTS as u64)
+            .mount(&mock_server)
+            .await;
+
+        let mut config = create_test_config();
+        config.cloudflare_realtime_base_url = mock_server.uri();
+        let client = CloudflareRealtimeClient::new(&config).unwrap();
+
+        let result = client.create_test_session().await;
+        assert!(matches!(result, Err(VideoConferencingError::ProviderUnavailable { .. })));
+        assert_eq!(client.circuit_breaker().snapshot().consecutive_failures, 1);
+    }
+}
@@ -4,16 +4,18 @@ use chrono::{DateTime, Duration, Utc};
 use reqwest::Method;
 use serde_json::{json, Value};
 use std::sync::Arc;
-use tracing::{debug, info, warn};
+use tracing::{debug, info};
 use uuid::Uuid;
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 use shared_models::auth::User;
+use appointment_cell::models::{AppointmentStatus, AppointmentType};
+use appointment_cell::services::versioning;
 
 use crate::models::{
-    CreateVideoSessionRequest, VideoConferencingError, VideoSession, VideoSessionStatus,
-    VideoSessionType,
+    CreateVideoSessionRequest, JoinUrlsResponse, VideoConferencingError, VideoSession,
+    VideoSessionStatus, VideoSessionType,
 };
 use crate::services::session::VideoSessionService;
 
@@ -37,8 +39,8 @@ impl VideoConferencingIntegrationService {
         })
     }
 
-    /// Automatically create video session when appointment is confirmed
-    /// This can be called by appointment-cell when appointment status changes
+    /// Create the video session for an appointment, or return the one it
+    /// already has. This can be called by appointment-cell when appointment status changes
     pub async fn create_session_for_appointment(
         &self,
         appointment_id: Uuid,
@@ -55,21 +57,7 @@ impl VideoConferencingIntegrationService {
         let appointment = self.get_appointment(appointment_id, auth_token).await?;
 
         // Extract appointment time for session scheduling
-        let appointment_date = appointment["appointment_date"]
-            .as_str()
-            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
-            .map(|dt| dt.with_timezone(&Utc))
-            .ok_or_else(|| VideoConferencingError::ValidationError {
-                message: "Invalid appointment date format".to_string(),
-            })?;
-
-        // Check if video session already exists for this appointment
-        if let Ok(_existing_session) = self.get_session_by_appointment(appointment_id, auth_token).await {
-            warn!("Video session already exists for appointment: {}", appointment_id);
-            return Err(VideoConferencingError::ValidationError {
-                message: "Video session already exists for this appointment".to_string(),
-            });
-        }
+        let appointment_date = appointment_start_time(&appointment)?;
 
         // Create session request
         let session_request = CreateVideoSessionRequest {
@@ -78,7 +66,7 @@ impl VideoConferencingIntegrationService {
             scheduled_start_time: appointment_date,
         };
 
-        // Create the session
+        // Create the session (or get the existing one)
         let session_response = self
             .session_service
             .create_session(session_request, requesting_user, auth_token)
@@ -93,7 +81,7 @@ impl VideoConferencingIntegrationService {
         .await?;
 
         info!(
-            "Successfully created video session {} for appointment: {}",
+            "Video session {} ready for appointment: {}",
             session_response.session.id, appointment_id
         );
 
@@ -101,76 +89,123 @@ impl VideoConferencingIntegrationService {
     }
 
     /// Handle appointment status changes
-    /// Called when appointment status changes to update video session accordingly
+    /// Called when appointment status changes to update video session accordingly.
+    /// Idempotent per appointment: retries and duplicate events see the session
+    /// the first call created, which is returned.
     pub async fn handle_appointment_status_change(
         &self,
         appointment_id: Uuid,
         new_status: &str,
         auth_token: &str,
-    ) -> Result<(), VideoConferencingError> {
+    ) -> Result<Option<VideoSession>, VideoConferencingError> {
         info!(
             "Handling appointment status change for {}: {}",
             appointment_id, new_status
         );
 
-        // Get associated video session if it exists
-        let session_result = self.get_session_by_appointment(appointment_id, auth_token).await;
+        // Get associated video session if it exists. A failed lookup must not
+        // be mistaken for "no session", or a retry would create a second one.
+        let existing = self
+            .session_service
+            .find_session_by_appointment(appointment_id, auth_token)
+            .await?;
 
-        match new_status {
-            "confirmed" => {
-                // If no video session exists, optionally create one
-                if session_result.is_err() {
-                    debug!("Appointment confirmed but no video session - manual creation may be needed");
-                }
+        let session = match (new_status, existing) {
+            ("confirmed", Some(session)) => {
+                debug!("Appointment {} already has video session {}", appointment_id, session.id);
+                Some(session)
             }
-            "in_progress" => {
+            ("confirmed", None) => self.create_session_on_confirmation(appointment_id, auth_token).await?,
+            ("in_progress", Some(mut session)) => {
                 // Appointment started - ensure video session is ready
-                if let Ok(session) = session_result {
-                    if session.status == VideoSessionStatus::Scheduled {
-                        self.update_session_status(
-                            session.id,
-                            VideoSessionStatus::Ready,
-                            auth_token,
-                        )
+                if session.status == VideoSessionStatus::Scheduled {
+                    self.update_session_status(session.id, VideoSessionStatus::Ready, auth_token)
                         .await?;
-                    }
+                    session.status = VideoSessionStatus::Ready;
                 }
+                Some(session)
             }
-            "completed" => {
+            ("completed", Some(mut session)) => {
                 // Appointment completed - end video session if still active
-                if let Ok(session) = session_result {
-                    if matches!(
-                        session.status,
-                        VideoSessionStatus::Ready | VideoSessionStatus::InProgress
-                    ) {
-                        self.update_session_status(
-                            session.id,
-                            VideoSessionStatus::Completed,
-                            auth_token,
-                        )
+                if matches!(
+                    session.status,
+                    VideoSessionStatus::Ready | VideoSessionStatus::InProgress
+                ) {
+                    self.update_session_status(session.id, VideoSessionStatus::Completed, auth_token)
                         .await?;
-                    }
+                    session.status = VideoSessionStatus::Completed;
                 }
+                Some(session)
             }
-            "cancelled" => {
+            ("cancelled", Some(mut session)) => {
                 // Appointment cancelled - cancel video session
-                if let Ok(session) = session_result {
-                    if !matches!(session.status, VideoSessionStatus::Completed) {
-                        self.update_session_status(
-                            session.id,
-                            VideoSessionStatus::Cancelled,
-                            auth_token,
-                        )
+                if !matches!(
+                    session.status,
+                    VideoSessionStatus::Completed | VideoSessionStatus::Cancelled
+                ) {
+                    self.update_session_status(session.id, VideoSessionStatus::Cancelled, auth_token)
                         .await?;
-                    }
+                    session.status = VideoSessionStatus::Cancelled;
                 }
+                Some(session)
             }
-            _ => {
+            (_, session) => {
                 debug!("No video session action needed for status: {}", new_status);
+                session
             }
-        }
+        };
 
-        Ok(())
+        Ok(session)
+    }
+
+    /// Confirmed telemedicine appointments get their session up front; other
+    /// appointment types, and patients without telemedicine consent, don't
+    async fn create_session_on_confirmation(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Option<VideoSession>, VideoConferencingError> {
+        let appointment = self.get_appointment(appointment_id, auth_token).await?;
+
+        let appointment_type = match serde_json::from_value::<AppointmentType>(
+            appointment["appointment_type"].clone(),
+        ) {
+            Ok(appointment_type) if appointment_type.is_telemedicine_capable() => appointment_type,
+            _ => {
+                debug!("Appointment {} is not a telemedicine appointment - no video session", appointment_id);
+                return Ok(None);
+            }
+        };
+
+        let session_type = match appointment_type {
+            AppointmentType::FollowUp => VideoSessionType::FollowUp,
+            AppointmentType::Urgent => VideoSessionType::Emergency,
+            _ => VideoSessionType::Consultation,
+        };
+
+        let created = self
+            .session_service
+            .create_session_for_appointment(
+                &appointment,
+                session_type,
+                appointment_start_time(&appointment)?,
+                auth_token,
+            )
+            .await;
+
+        let (session, _) = match created {
+            Ok(created) => created,
+            Err(VideoConferencingError::ConsentRequired { .. }) => {
+                info!("Patient has no telemedicine consent - video session for {} not created", appointment_id);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.update_appointment_with_video_link(appointment_id, &session.id, auth_token)
+            .await?;
+
+        Ok(Some(session))
     }
 
     /// Get upcoming video sessions for a user (patient or doctor)
@@ -209,7 +244,7 @@ impl VideoConferencingIntegrationService {
 
         let sessions: Result<Vec<VideoSession>, _> = result
             .into_iter()
-            .map(|v| serde_json::from_value(v))
+            .map(serde_json::from_value)
             .collect();
 
         sessions.map_err(|e| VideoConferencingError::DatabaseError {
@@ -249,6 +284,63 @@ impl VideoConferencingIntegrationService {
         }))
     }
 
+    /// Mint fresh join URLs for an appointment's video session with a new expiry
+    pub async fn refresh_join_urls_for_appointment(
+        &self,
+        appointment_id: Uuid,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<JoinUrlsResponse, VideoConferencingError> {
+        let session = self.get_session_by_appointment(appointment_id, auth_token).await?;
+
+        // Only the involved patient/doctor or an admin may refresh
+        let has_access = session.patient_id.to_string() == user.id
+            || session.doctor_id.to_string() == user.id
+            || user.role.as_deref() == Some("admin");
+
+        if !has_access {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        if matches!(
+            session.status,
+            VideoSessionStatus::Completed | VideoSessionStatus::Cancelled | VideoSessionStatus::Failed
+        ) {
+            return Err(VideoConferencingError::InvalidSessionState {
+                status: format!("{:?}", session.status),
+            });
+        }
+
+        let (join_urls, expires_at) = self.session_service.generate_join_urls(session.id);
+
+        let path = format!("/rest/v1/video_sessions?id=eq.{}", session.id);
+        let body = json!({
+            "join_urls_expires_at": expires_at,
+            "updated_at": Utc::now(),
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+        let _: Vec<Value> = self
+            .supabase
+            .request_with_headers(Method::PATCH, &path, Some(auth_token), Some(body), Some(headers))
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        info!(
+            "Refreshed join URLs for session {} (appointment {}) until {}",
+            session.id, appointment_id, expires_at
+        );
+
+        Ok(JoinUrlsResponse {
+            session_id: session.id,
+            join_urls,
+            expires_at,
+        })
+    }
+
     /// Check if video conferencing is available for an appointment
     pub async fn is_video_available_for_appointment(
         &self,
@@ -264,21 +356,17 @@ impl VideoConferencingIntegrationService {
         let appointment = self.get_appointment(appointment_id, auth_token).await?;
 
         // Check appointment type and status
-        let appointment_type = appointment["appointment_type"]
-            .as_str()
-            .unwrap_or("")
-            .to_lowercase();
-
-        let status = appointment["status"].as_str().unwrap_or("").to_lowercase();
+        let telemedicine_capable = serde_json::from_value::<AppointmentType>(
+            appointment["appointment_type"].clone(),
+        )
+        .map(|appointment_type| appointment_type.is_telemedicine_capable())
+        .unwrap_or(false);
 
-        // Video conferencing is available for certain appointment types and statuses
-        let video_compatible_types = vec!["consultation", "follow_up", "general_consultation"];
-        let video_compatible_statuses = vec!["confirmed", "in_progress"];
+        let status = serde_json::from_value::<AppointmentStatus>(appointment["status"].clone()).ok();
 
-        let is_compatible = video_compatible_types
-            .iter()
-            .any(|&t| appointment_type.contains(t))
-            && video_compatible_statuses.contains(&status.as_str());
+        // Video conferencing is available for telemedicine-capable types in active statuses
+        let is_compatible = telemedicine_capable
+            && matches!(status, Some(AppointmentStatus::Confirmed | AppointmentStatus::InProgress));
 
         Ok(is_compatible)
     }
@@ -362,23 +450,10 @@ impl VideoConferencingIntegrationService {
         appointment_id: Uuid,
         auth_token: &str,
     ) -> Result<VideoSession, VideoConferencingError> {
-        let path = format!("/rest/v1/video_sessions?appointment_id=eq.{}", appointment_id);
-        let result: Vec<Value> = self
-            .supabase
-            .request(Method::GET, &path, Some(auth_token), None)
-            .await
-            .map_err(|e| VideoConferencingError::DatabaseError {
-                message: e.to_string(),
-            })?;
-
-        let session_data = result
-            .into_iter()
-            .next()
-            .ok_or(VideoConferencingError::SessionNotFound)?;
-
-        serde_json::from_value(session_data).map_err(|e| VideoConferencingError::DatabaseError {
-            message: format!("Failed to parse session: {}", e),
-        })
+        self.session_service
+            .find_session_by_appointment(appointment_id, auth_token)
+            .await?
+            .ok_or(VideoConferencingError::SessionNotFound)
     }
 
     async fn update_appointment_with_video_link(
@@ -387,15 +462,12 @@ impl VideoConferencingIntegrationService {
         session_id: &Uuid,
         auth_token: &str,
     ) -> Result<(), VideoConferencingError> {
-        let path = format!("/rest/v1/appointments?id=eq.{}", appointment_id);
         let body = json!({
             "video_conference_link": format!("/video/sessions/{}", session_id),
             "updated_at": Utc::now(),
         });
 
-        let _: Vec<Value> = self
-            .supabase
-            .request(Method::PATCH, &path, Some(auth_token), Some(body))
+        versioning::set_appointment_fields(&self.supabase, appointment_id, body, auth_token)
             .await
             .map_err(|e| VideoConferencingError::DatabaseError {
                 message: e.to_string(),
@@ -428,6 +500,18 @@ impl VideoConferencingIntegrationService {
     }
 }
 
+/// Start time of an appointment row; older rows call it `appointment_date`
+fn appointment_start_time(appointment: &Value) -> Result<DateTime<Utc>, VideoConferencingError> {
+    appointment["scheduled_start_time"]
+        .as_str()
+        .or_else(|| appointment["appointment_date"].as_str())
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| VideoConferencingError::ValidationError {
+            message: "Invalid appointment date format".to_string(),
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -440,6 +524,35 @@ mod tests {
             cloudflare_realtime_app_id: "test-app-id".to_string(),
             cloudflare_realtime_api_token: "test-token".to_string(),
             cloudflare_realtime_base_url: "https://test.cloudflare.com/v1".to_string(),
+            video_join_url_ttl_minutes: 120,
+            cors_allowed_origins: Vec::new(),
+            trusted_proxies: Vec::new(),
+            cors_allow_all: false,
+            graceful_shutdown_timeout_seconds: 30,
+            clinic_name: "Amae Clinic".to_string(),
+            clinic_address: String::new(),
+            clinic_contact: String::new(),
+            clinical_record_retention_years: 10,
+            supabase_jwks_url: String::new(),
+            jwks_cache_ttl_seconds: 600,
+            allow_any_doctor_profile_access: false,
+            video_session_reconcile_interval_minutes: 15,
+            appointment_rules: Default::default(),
+            supabase_slow_query_threshold_ms: 1000,
+            video_join_failure_alert_percent: 20,
+            response_compression_enabled: true,
+            max_document_upload_bytes: 10 * 1024 * 1024,
+            feature_flags: Default::default(),
+            availability_cache_ttl_seconds: 0,
+            clinic_id: None,
+            appointment_stats_cache_ttl_seconds: 0,
+            support_email: None,
+            help_url_base: None,
+            batch_conflict_check_concurrency: 5,
+            batch_conflict_check_timeout_seconds: 10,
+            batch_conflict_check_max_size: 100,
+            webhook_allow_insecure_urls: false,
+            webhook_worker_key: None,
         }
     }
 
@@ -0,0 +1,325 @@
+// libs/video-conferencing-cell/src/services/invites.rs
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+use shared_models::auth::{JwtClaims, User};
+use shared_utils::jwt::create_token;
+
+use crate::models::{
+    CreateInviteRequest, InviteLinkResponse, ParticipantType, RedeemInviteResponse,
+    SessionParticipant, VideoConferencingError, VideoSession, VideoSessionInvite,
+    VideoSessionStatus,
+};
+use crate::services::service_token;
+use crate::services::session::{user_uuid, VideoSessionService};
+
+/// Longest an unused invite link stays valid
+const MAX_INVITE_TTL_MINUTES: i64 = 7 * 24 * 60;
+
+/// Single-use join links for people without a stake in the appointment, such
+/// as interpreters. Redeeming a link puts the invitee on the roster under a
+/// guest identity (the invite id) and hands them a token for that identity.
+pub struct VideoInviteService {
+    supabase: Arc<SupabaseClient>,
+    session_service: VideoSessionService,
+    audit_log: AuditLog,
+    jwt_secret: String,
+    guest_token_ttl: Duration,
+}
+
+impl VideoInviteService {
+    pub fn new(config: &AppConfig) -> Result<Self, VideoConferencingError> {
+        Ok(Self {
+            supabase: Arc::new(SupabaseClient::new(config)),
+            session_service: VideoSessionService::new(config)?,
+            audit_log: AuditLog::new(config),
+            jwt_secret: config.supabase_jwt_secret.clone(),
+            guest_token_ttl: Duration::minutes(config.video_join_url_ttl_minutes),
+        })
+    }
+
+    /// Issue an invite link. Interpreter links can come from the patient as
+    /// well as the doctor; observer links only from the doctor or an admin.
+    pub async fn create_invite(
+        &self,
+        session_id: Uuid,
+        request: CreateInviteRequest,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<InviteLinkResponse, VideoConferencingError> {
+        let session = self.session_service.get_session(session_id, auth_token).await?;
+        ensure_open(&session)?;
+
+        let participant_type = request.participant_type.unwrap_or(ParticipantType::Interpreter);
+        let is_doctor = session.doctor_id.to_string() == user.id || user.is_admin();
+        let allowed = match participant_type {
+            ParticipantType::Interpreter => is_doctor || session.patient_id.to_string() == user.id,
+            ParticipantType::Observer => is_doctor,
+            ParticipantType::Patient | ParticipantType::Doctor => {
+                return Err(VideoConferencingError::ValidationError {
+                    message: "Invites are only for interpreters and observers".to_string(),
+                });
+            }
+        };
+        if !allowed {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        let ttl_minutes = request.expires_in_minutes.unwrap_or(self.guest_token_ttl.num_minutes());
+        if !(1..=MAX_INVITE_TTL_MINUTES).contains(&ttl_minutes) {
+            return Err(VideoConferencingError::ValidationError {
+                message: format!("Invite expiry must be between 1 and {} minutes", MAX_INVITE_TTL_MINUTES),
+            });
+        }
+
+        let created_by = user_uuid(user)?;
+        let link_token = format!("inv_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let now = Utc::now();
+        let invite = VideoSessionInvite {
+            id: Uuid::new_v4(),
+            session_id,
+            participant_type,
+            label: request.label.map(|label| label.trim().to_string()).filter(|label| !label.is_empty()),
+            created_by,
+            expires_at: now + Duration::minutes(ttl_minutes),
+            used_at: None,
+            revoked_at: None,
+            created_at: now,
+        };
+
+        let mut record = json!(invite);
+        record["token_hash"] = json!(hash_link_token(&link_token));
+        let _: Value = self
+            .supabase
+            .request_with_headers(Method::POST, "/rest/v1/video_session_invites", Some(auth_token), Some(record), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        self.audit(user, "video.invite_created", &invite, auth_token).await;
+        info!(
+            "User {} issued {:?} invite {} for session {}",
+            user.id, participant_type, invite.id, session_id
+        );
+
+        Ok(InviteLinkResponse {
+            join_url: format!("/video/invites/{}", link_token),
+            invite,
+        })
+    }
+
+    /// Revoke an invite. If it was already redeemed the guest is removed from
+    /// the roster, which cuts off their access to the session.
+    pub async fn revoke_invite(
+        &self,
+        session_id: Uuid,
+        invite_id: Uuid,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<VideoSessionInvite, VideoConferencingError> {
+        let session = self.session_service.get_session(session_id, auth_token).await?;
+
+        let path = format!("/rest/v1/video_session_invites?id=eq.{}&session_id=eq.{}", invite_id, session_id);
+        let mut invite = self.fetch_invite(&path, auth_token).await?;
+
+        let may_revoke = invite.created_by.to_string() == user.id
+            || session.doctor_id.to_string() == user.id
+            || user.is_admin();
+        if !may_revoke {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        if invite.revoked_at.is_some() {
+            return Ok(invite);
+        }
+
+        let revoked_at = Utc::now();
+        let _: Value = self
+            .supabase
+            .request_with_headers(Method::PATCH, &path, Some(auth_token), Some(json!({ "revoked_at": revoked_at })), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+        invite.revoked_at = Some(revoked_at);
+
+        if invite.used_at.is_some() {
+            self.session_service
+                .update_participant(session_id, invite.id, json!({ "left_at": revoked_at }), auth_token)
+                .await?;
+        }
+
+        self.audit(user, "video.invite_revoked", &invite, auth_token).await;
+        info!("User {} revoked invite {} for session {}", user.id, invite_id, session_id);
+
+        Ok(invite)
+    }
+
+    /// Redeem a link. Works without a signed-in user; the link token is the credential.
+    pub async fn redeem_invite(&self, link_token: &str) -> Result<RedeemInviteResponse, VideoConferencingError> {
+        // Links are looked up before anyone is signed in
+        let service_token = service_token(&self.jwt_secret, "video-invites")?;
+
+        let path = format!("/rest/v1/video_session_invites?token_hash=eq.{}", hash_link_token(link_token));
+        let invite = self.fetch_invite(&path, &service_token).await?;
+
+        if invite.revoked_at.is_some() {
+            return Err(invalid_invite("it has been revoked"));
+        }
+        if invite.used_at.is_some() {
+            return Err(invalid_invite("it has already been used"));
+        }
+        let now = Utc::now();
+        if invite.expires_at <= now {
+            return Err(invalid_invite("it has expired"));
+        }
+
+        let session = self.session_service.get_session(invite.session_id, &service_token).await?;
+        ensure_open(&session)?;
+
+        // Claim the invite; the filter makes a concurrent second redemption match nothing
+        let claim_path = format!(
+            "/rest/v1/video_session_invites?id=eq.{}&used_at=is.null&revoked_at=is.null",
+            invite.id
+        );
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+        let claimed: Vec<Value> = self
+            .supabase
+            .request_with_headers(Method::PATCH, &claim_path, Some(&service_token), Some(json!({ "used_at": now })), Some(headers))
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+        if claimed.is_empty() {
+            return Err(invalid_invite("it has already been used"));
+        }
+
+        let participant = SessionParticipant {
+            session_id: invite.session_id,
+            user_id: invite.id,
+            user_type: invite.participant_type,
+            joined_at: None,
+            left_at: None,
+            connection_quality: None,
+            audio_enabled: true,
+            video_enabled: true,
+            cloudflare_session_id: None,
+            published_tracks: Vec::new(),
+        };
+        self.session_service.upsert_participant(json!(participant), &service_token).await?;
+
+        let expires_at = now + self.guest_token_ttl;
+        let access_token = self.guest_token(&invite, now, expires_at)?;
+
+        let audit_entry = AuditEntry::new(invite.id.to_string(), "video.invite_redeemed", "video_session", invite.session_id.to_string())
+            .with_details(json!({ "invite_id": invite.id, "participant_type": invite.participant_type }));
+        if let Err(e) = self.audit_log.record(audit_entry, &service_token).await {
+            warn!("Failed to audit redemption of invite {}: {}", invite.id, e);
+        }
+
+        info!("Invite {} redeemed for session {}", invite.id, invite.session_id);
+        Ok(RedeemInviteResponse {
+            session_id: invite.session_id,
+            participant_type: invite.participant_type,
+            access_token,
+            expires_at,
+        })
+    }
+
+    async fn fetch_invite(&self, path: &str, auth_token: &str) -> Result<VideoSessionInvite, VideoConferencingError> {
+        let result: Vec<Value> = self
+            .supabase
+            .request(Method::GET, path, Some(auth_token), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        let invite = result.into_iter().next().ok_or_else(|| invalid_invite("it does not exist"))?;
+        serde_json::from_value(invite).map_err(|e| VideoConferencingError::DatabaseError {
+            message: format!("Failed to parse invite: {}", e),
+        })
+    }
+
+    /// Token for the guest identity. Only valid for what the roster grants it.
+    fn guest_token(
+        &self,
+        invite: &VideoSessionInvite,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, VideoConferencingError> {
+        let claims = JwtClaims {
+            sub: invite.id.to_string(),
+            exp: Some(expires_at.timestamp() as u64),
+            email: None,
+            role: Some("authenticated".to_string()),
+            app_metadata: None,
+            user_metadata: Some(json!({
+                "video_guest": true,
+                "video_session_id": invite.session_id,
+                "participant_type": invite.participant_type,
+            })),
+            aud: Some("authenticated".to_string()),
+            iat: Some(now.timestamp() as u64),
+        };
+        create_token(&claims, &self.jwt_secret).map_err(|message| VideoConferencingError::Internal { message })
+    }
+
+    async fn audit(&self, user: &User, action: &str, invite: &VideoSessionInvite, auth_token: &str) {
+        let audit_entry = AuditEntry::new(user.id.clone(), action, "video_session", invite.session_id.to_string())
+            .with_details(json!({ "invite_id": invite.id, "participant_type": invite.participant_type }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit {} for invite {}: {}", action, invite.id, e);
+        }
+    }
+}
+
+fn ensure_open(session: &VideoSession) -> Result<(), VideoConferencingError> {
+    if matches!(
+        session.status,
+        VideoSessionStatus::Completed | VideoSessionStatus::Cancelled | VideoSessionStatus::Failed
+    ) {
+        return Err(VideoConferencingError::InvalidSessionState {
+            status: format!("{:?}", session.status),
+        });
+    }
+    Ok(())
+}
+
+fn invalid_invite(reason: &str) -> VideoConferencingError {
+    VideoConferencingError::InvalidInvite {
+        reason: reason.to_string(),
+    }
+}
+
+/// Hex SHA-256 of a link token, which is what gets stored
+fn hash_link_token(link_token: &str) -> String {
+    Sha256::digest(link_token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_tokens_are_stored_hashed() {
+        let hash = hash_link_token("inv_abc");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_link_token("inv_abc"));
+        assert_ne!(hash, hash_link_token("inv_abd"));
+        assert!(!hash.contains("inv_abc"));
+    }
+}
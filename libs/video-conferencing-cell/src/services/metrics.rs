@@ -0,0 +1,203 @@
+// libs/video-conferencing-cell/src/services/metrics.rs
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+use crate::models::VideoConferencingError;
+
+/// How far back the join failure rate is measured
+pub const JOIN_FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Fewer attempts than this in the window never raise an alert
+pub const MIN_JOINS_FOR_ALERT: usize = 10;
+
+/// Process-wide video session counters. Join outcomes are also kept for the
+/// last `JOIN_FAILURE_WINDOW` so a spike in failures (a Cloudflare or TURN
+/// outage) raises an alert instead of waiting for patient complaints.
+#[derive(Default)]
+pub struct VideoMetrics {
+    sessions_created: AtomicU64,
+    sessions_failed: AtomicU64,
+    joins_succeeded: AtomicU64,
+    joins_failed: AtomicU64,
+    join_latency_ms_total: AtomicU64,
+    recent_joins: Mutex<JoinWindow>,
+}
+
+#[derive(Default)]
+struct JoinWindow {
+    outcomes: VecDeque<(Instant, bool)>,
+    last_alert: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoMetricsSnapshot {
+    pub sessions_created: u64,
+    pub sessions_failed: u64,
+    pub joins_succeeded: u64,
+    pub joins_failed: u64,
+    /// Over successful joins only
+    pub average_join_latency_ms: Option<u64>,
+    pub window_minutes: u64,
+    pub window_join_attempts: usize,
+    pub window_failure_rate: Option<f64>,
+}
+
+/// The metrics shared by every request in this process
+pub fn video_metrics() -> &'static VideoMetrics {
+    static METRICS: OnceLock<VideoMetrics> = OnceLock::new();
+    METRICS.get_or_init(VideoMetrics::default)
+}
+
+impl VideoMetrics {
+    pub fn record_session_created(&self) {
+        self.sessions_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_session_failed(&self) {
+        self.sessions_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a join attempt. Returns true if it pushed the failure rate over
+    /// `alert_percent` and an alert was logged; alerts repeat at most once per
+    /// window, and an `alert_percent` of 0 disables them.
+    pub fn record_join(&self, succeeded: bool, latency: Duration, alert_percent: u32) -> bool {
+        self.record_join_at(Instant::now(), succeeded, latency, alert_percent)
+    }
+
+    fn record_join_at(&self, now: Instant, succeeded: bool, latency: Duration, alert_percent: u32) -> bool {
+        if succeeded {
+            self.joins_succeeded.fetch_add(1, Ordering::Relaxed);
+            self.join_latency_ms_total.fetch_add(latency.as_millis() as u64, Ordering::Relaxed);
+        } else {
+            self.joins_failed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut window = self.recent_joins.lock().unwrap_or_else(|e| e.into_inner());
+        window.outcomes.push_back((now, succeeded));
+        window.prune(now);
+
+        if succeeded || alert_percent == 0 || window.outcomes.len() < MIN_JOINS_FOR_ALERT {
+            return false;
+        }
+        if window.last_alert.is_some_and(|last| now.duration_since(last) < JOIN_FAILURE_WINDOW) {
+            return false;
+        }
+
+        let failure_rate = window.failure_rate().unwrap_or(0.0);
+        if failure_rate * 100.0 < alert_percent as f64 {
+            return false;
+        }
+
+        window.last_alert = Some(now);
+        error!(
+            failure_rate = failure_rate,
+            attempts = window.outcomes.len(),
+            "ALERT: {:.0}% of video session joins failed in the last {} minutes",
+            failure_rate * 100.0,
+            JOIN_FAILURE_WINDOW.as_secs() / 60
+        );
+        true
+    }
+
+    pub fn snapshot(&self) -> VideoMetricsSnapshot {
+        let joins_succeeded = self.joins_succeeded.load(Ordering::Relaxed);
+        let latency_total = self.join_latency_ms_total.load(Ordering::Relaxed);
+
+        let mut window = self.recent_joins.lock().unwrap_or_else(|e| e.into_inner());
+        window.prune(Instant::now());
+
+        VideoMetricsSnapshot {
+            sessions_created: self.sessions_created.load(Ordering::Relaxed),
+            sessions_failed: self.sessions_failed.load(Ordering::Relaxed),
+            joins_succeeded,
+            joins_failed: self.joins_failed.load(Ordering::Relaxed),
+            average_join_latency_ms: (joins_succeeded > 0).then(|| latency_total / joins_succeeded),
+            window_minutes: JOIN_FAILURE_WINDOW.as_secs() / 60,
+            window_join_attempts: window.outcomes.len(),
+            window_failure_rate: window.failure_rate(),
+        }
+    }
+}
+
+impl JoinWindow {
+    fn prune(&mut self, now: Instant) {
+        while self.outcomes.front().is_some_and(|(at, _)| now.duration_since(*at) > JOIN_FAILURE_WINDOW) {
+            self.outcomes.pop_front();
+        }
+    }
+
+    fn failure_rate(&self) -> Option<f64> {
+        if self.outcomes.is_empty() {
+            return None;
+        }
+        let failed = self.outcomes.iter().filter(|(_, succeeded)| !succeeded).count();
+        Some(failed as f64 / self.outcomes.len() as f64)
+    }
+}
+
+/// Whether a failed join points at the video infrastructure rather than at
+/// the caller (no access, no consent, session full or over)
+pub(crate) fn is_connection_failure(error: &VideoConferencingError) -> bool {
+    matches!(
+        error,
+        VideoConferencingError::CloudflareApiError { .. }
+            | VideoConferencingError::ProviderUnavailable { .. }
+            | VideoConferencingError::WebRTCError { .. }
+            | VideoConferencingError::DatabaseError { .. }
+            | VideoConferencingError::Internal { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_averages_successful_join_latency() {
+        let metrics = VideoMetrics::default();
+        metrics.record_session_created();
+        metrics.record_join(true, Duration::from_millis(200), 20);
+        metrics.record_join(true, Duration::from_millis(400), 20);
+        metrics.record_join(false, Duration::from_millis(5000), 20);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.sessions_created, 1);
+        assert_eq!(snapshot.joins_succeeded, 2);
+        assert_eq!(snapshot.joins_failed, 1);
+        assert_eq!(snapshot.average_join_latency_ms, Some(300));
+        assert_eq!(snapshot.window_join_attempts, 3);
+    }
+
+    #[test]
+    fn test_alert_fires_once_per_window_above_threshold() {
+        let metrics = VideoMetrics::default();
+        let start = Instant::now();
+        let latency = Duration::from_millis(100);
+
+        for i in 0..7 {
+            assert!(!metrics.record_join_at(start, i % 2 == 0, latency, 30));
+        }
+        // 10 attempts, 4 failed: 40% is over the 30% threshold
+        assert!(!metrics.record_join_at(start, true, latency, 30));
+        assert!(!metrics.record_join_at(start, true, latency, 30));
+        assert!(metrics.record_join_at(start, false, latency, 30));
+        assert!(!metrics.record_join_at(start, false, latency, 30));
+
+        // Once the window has rolled over, old failures no longer count
+        let later = start + JOIN_FAILURE_WINDOW + Duration::from_secs(1);
+        assert!(!metrics.record_join_at(later, false, latency, 30));
+    }
+
+    #[test]
+    fn test_only_infrastructure_errors_count_as_failures() {
+        assert!(is_connection_failure(&VideoConferencingError::CloudflareApiError {
+            message: "timeout".to_string()
+        }));
+        assert!(!is_connection_failure(&VideoConferencingError::Unauthorized));
+        assert!(!is_connection_failure(&VideoConferencingError::SessionCapacityExceeded));
+    }
+}
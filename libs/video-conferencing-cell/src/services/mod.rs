@@ -1,9 +1,42 @@
 // libs/video-conferencing-cell/src/services/mod.rs
 
+pub mod circuit_breaker;
 pub mod cloudflare;
 pub mod integration;
+pub mod invites;
+pub mod metrics;
+pub mod readiness;
+pub mod recording;
 pub mod session;
 
 pub use cloudflare::CloudflareRealtimeClient;
 pub use integration::VideoConferencingIntegrationService;
-pub use session::VideoSessionService;
\ No newline at end of file
+pub use invites::VideoInviteService;
+pub use readiness::TelemedicineReadinessService;
+pub use recording::SessionRecordingService;
+pub use session::VideoSessionService;
+use chrono::{Duration, Utc};
+use shared_models::auth::JwtClaims;
+use shared_utils::jwt::create_token;
+
+use crate::models::VideoConferencingError;
+
+/// Lifetime of service tokens minted for work that happens outside a user's request
+const SERVICE_TOKEN_TTL_SECONDS: i64 = 300;
+
+/// Short-lived service-role token, for lookups before anyone is signed in and
+/// for background jobs
+pub(crate) fn service_token(jwt_secret: &str, subject: &str) -> Result<String, VideoConferencingError> {
+    let now = Utc::now();
+    let claims = JwtClaims {
+        sub: subject.to_string(),
+        exp: Some((now + Duration::seconds(SERVICE_TOKEN_TTL_SECONDS)).timestamp() as u64),
+        email: None,
+        role: Some("service_role".to_string()),
+        app_metadata: None,
+        user_metadata: None,
+        aud: None,
+        iat: Some(now.timestamp() as u64),
+    };
+    create_token(&claims, jwt_secret).map_err(|message| VideoConferencingError::Internal { message })
+}
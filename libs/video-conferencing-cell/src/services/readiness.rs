@@ -0,0 +1,204 @@
+// libs/video-conferencing-cell/src/services/readiness.rs
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use shared_config::AppConfig;
+use shared_database::supabase::SupabaseClient;
+use shared_models::auth::User;
+use shared_utils::i18n::{Language, Message};
+
+use crate::models::{
+    ReadinessTestResponse, ReadinessTestResults, SupportContact, TelemedicineReadinessReport,
+    VideoConferencingError,
+};
+use crate::services::cloudflare::CloudflareRealtimeClient;
+
+/// Minimum measured bandwidth for a stable two-way video call
+pub const MIN_BANDWIDTH_KBPS: i32 = 1000;
+/// Maximum round-trip latency before audio/video lag becomes noticeable
+pub const MAX_LATENCY_MS: i32 = 300;
+/// Help page for patients whose self-test fails
+const READINESS_HELP_TOPIC: &str = "telemedicine/readiness";
+
+/// Pre-call self-test so patients can verify camera, microphone and network
+/// before joining an appointment
+pub struct TelemedicineReadinessService {
+    supabase: Arc<SupabaseClient>,
+    cloudflare: CloudflareRealtimeClient,
+    support: SupportContact,
+}
+
+impl TelemedicineReadinessService {
+    pub fn new(config: &AppConfig) -> Result<Self, VideoConferencingError> {
+        let supabase = Arc::new(SupabaseClient::new(config));
+        let cloudflare = CloudflareRealtimeClient::new(config)?;
+
+        Ok(Self {
+            supabase,
+            cloudflare,
+            support: SupportContact::from_config(config, READINESS_HELP_TOPIC),
+        })
+    }
+
+    /// Issue a throwaway Cloudflare session and the instructions for the
+    /// client-side test, in the patient's language
+    pub async fn start_self_test(&self, user: &User, language: Language) -> Result<ReadinessTestResponse, VideoConferencingError> {
+        info!("Starting telemedicine readiness test for user {}", user.id);
+
+        let test_session_id = self.cloudflare.create_test_session().await?;
+
+        Ok(ReadinessTestResponse {
+            test_session_id,
+            ice_servers: self.cloudflare.get_ice_servers(),
+            rtc_configuration: self.cloudflare.get_rtc_configuration(),
+            instructions: [
+                Message::ReadinessAllowDevices,
+                Message::ReadinessConnect,
+                Message::ReadinessPublishTracks,
+                Message::ReadinessSubmitResults,
+            ].into_iter().map(|message| message.text(language).to_string()).collect(),
+            min_bandwidth_kbps: MIN_BANDWIDTH_KBPS,
+            max_latency_ms: MAX_LATENCY_MS,
+            support: self.support.clone(),
+        })
+    }
+
+    /// Evaluate client-measured results, returning readiness and any detected issues
+    pub fn check_telemedicine_readiness(results: &ReadinessTestResults) -> (bool, Vec<String>) {
+        let mut issues = Vec::new();
+
+        if !results.camera_available {
+            issues.push("Camera not available".to_string());
+        }
+        if !results.microphone_available {
+            issues.push("Microphone not available".to_string());
+        }
+
+        match results.bandwidth_kbps {
+            Some(bandwidth) if bandwidth < MIN_BANDWIDTH_KBPS => issues.push(format!(
+                "Bandwidth of {} kbps is below the recommended {} kbps",
+                bandwidth, MIN_BANDWIDTH_KBPS
+            )),
+            None => issues.push("Bandwidth could not be measured".to_string()),
+            _ => {}
+        }
+
+        if let Some(latency) = results.latency_ms {
+            if latency > MAX_LATENCY_MS {
+                issues.push(format!(
+                    "Latency of {} ms exceeds the recommended {} ms",
+                    latency, MAX_LATENCY_MS
+                ));
+            }
+        }
+
+        (issues.is_empty(), issues)
+    }
+
+    /// Store the results of a self-test as a readiness report
+    pub async fn record_results(
+        &self,
+        results: ReadinessTestResults,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<TelemedicineReadinessReport, VideoConferencingError> {
+        let (is_ready, issues) = Self::check_telemedicine_readiness(&results);
+
+        let report = TelemedicineReadinessReport {
+            id: Uuid::new_v4(),
+            user_id: user.id.clone(),
+            test_session_id: results.test_session_id,
+            camera_available: results.camera_available,
+            microphone_available: results.microphone_available,
+            bandwidth_kbps: results.bandwidth_kbps,
+            latency_ms: results.latency_ms,
+            user_agent: results.user_agent,
+            is_ready,
+            issues,
+            created_at: Utc::now(),
+        };
+
+        let body = serde_json::to_value(&report).map_err(|e| VideoConferencingError::Internal {
+            message: e.to_string(),
+        })?;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("return=representation"));
+        let _: Vec<Value> = self
+            .supabase
+            .request_with_headers(
+                Method::POST,
+                "/rest/v1/telemedicine_readiness_reports",
+                Some(auth_token),
+                Some(body),
+                Some(headers),
+            )
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        info!(
+            "Stored readiness report {} for user {} (ready: {})",
+            report.id, user.id, report.is_ready
+        );
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(camera: bool, bandwidth: Option<i32>, latency: Option<i32>) -> ReadinessTestResults {
+        ReadinessTestResults {
+            test_session_id: Some("test-session".to_string()),
+            camera_available: camera,
+            microphone_available: true,
+            bandwidth_kbps: bandwidth,
+            latency_ms: latency,
+            user_agent: None,
+        }
+    }
+
+    #[test]
+    fn test_ready_when_all_checks_pass() {
+        let (ready, issues) = TelemedicineReadinessService::check_telemedicine_readiness(
+            &results(true, Some(2500), Some(80)),
+        );
+        assert!(ready);
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn test_not_ready_reports_each_issue() {
+        let (ready, issues) = TelemedicineReadinessService::check_telemedicine_readiness(
+            &results(false, Some(400), Some(900)),
+        );
+        assert!(!ready);
+        assert_eq!(issues.len(), 3);
+    }
+
+    #[test]
+    fn test_support_contact_comes_from_branding_config() {
+        let mut config = shared_utils::test_utils::TestConfig::default().to_app_config();
+        assert_eq!(SupportContact::from_config(&config, READINESS_HELP_TOPIC), SupportContact {
+            clinic_name: config.clinic_name.clone(),
+            email: None,
+            help_url: None,
+        });
+
+        config.clinic_name = "Staging Clinic".to_string();
+        config.support_email = Some("support@staging.example".to_string());
+        config.help_url_base = Some("https://help.staging.example/".to_string());
+        let support = SupportContact::from_config(&config, READINESS_HELP_TOPIC);
+        assert_eq!(support.clinic_name, "Staging Clinic");
+        assert_eq!(support.email.as_deref(), Some("support@staging.example"));
+        assert_eq!(support.help_url.as_deref(), Some("https://help.staging.example/telemedicine/readiness"));
+    }
+}
@@ -0,0 +1,258 @@
+// libs/video-conferencing-cell/src/services/recording.rs
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use health_profile_cell::api::DocumentService;
+use shared_config::AppConfig;
+use shared_database::audit::{AuditEntry, AuditLog};
+use shared_database::supabase::SupabaseClient;
+use shared_models::auth::User;
+
+use crate::models::{SessionRecording, VideoConferencingError, VideoSession, VideoSessionStatus};
+use crate::services::cloudflare::CloudflareRealtimeClient;
+use crate::services::session::{user_uuid, VideoSessionService};
+
+/// Used when the provider doesn't say what it wrote
+const DEFAULT_RECORDING_MIME_TYPE: &str = "video/webm";
+
+/// Consultation recording. Recording only starts when everyone in the call
+/// (and the session's patient, connected or not) currently grants recording
+/// consent; the finished file is filed as one of the patient's documents.
+pub struct SessionRecordingService {
+    supabase: Arc<SupabaseClient>,
+    session_service: VideoSessionService,
+    cloudflare: CloudflareRealtimeClient,
+    document_service: DocumentService,
+    audit_log: AuditLog,
+}
+
+impl SessionRecordingService {
+    pub fn new(config: &AppConfig) -> Result<Self, VideoConferencingError> {
+        Ok(Self {
+            supabase: Arc::new(SupabaseClient::new(config)),
+            session_service: VideoSessionService::new(config)?,
+            cloudflare: CloudflareRealtimeClient::new(config)?,
+            document_service: DocumentService::new(config),
+            audit_log: AuditLog::new(config),
+        })
+    }
+
+    /// Turn recording on or off. Only the session's doctor can start a
+    /// recording; the doctor or an admin can stop one.
+    pub async fn set_recording(
+        &self,
+        session_id: Uuid,
+        enabled: bool,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<(VideoSession, SessionRecording), VideoConferencingError> {
+        let session = self.session_service.get_session(session_id, auth_token).await?;
+
+        let is_doctor = session.doctor_id.to_string() == user.id;
+        if !(is_doctor || (!enabled && user.is_admin())) {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        if enabled {
+            self.start_recording(session, user, auth_token).await
+        } else {
+            self.stop_recording(session, user, auth_token).await
+        }
+    }
+
+    /// Stop the recording if one is running, e.g. because the session is ending
+    pub async fn stop_if_recording(
+        &self,
+        session_id: Uuid,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<Option<SessionRecording>, VideoConferencingError> {
+        let session = self.session_service.get_session(session_id, auth_token).await?;
+        if !session.recording_enabled {
+            return Ok(None);
+        }
+
+        let (_, recording) = self.stop_recording(session, user, auth_token).await?;
+        Ok(Some(recording))
+    }
+
+    async fn start_recording(
+        &self,
+        mut session: VideoSession,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<(VideoSession, SessionRecording), VideoConferencingError> {
+        if session.status != VideoSessionStatus::InProgress {
+            return Err(VideoConferencingError::InvalidSessionState {
+                status: format!("{:?}", session.status),
+            });
+        }
+        if session.recording_enabled {
+            return Err(VideoConferencingError::ValidationError {
+                message: "The session is already being recorded".to_string(),
+            });
+        }
+
+        let participants = self.session_service.get_session_participants(session.id, auth_token).await?;
+        let active: Vec<_> = participants.iter().filter(|p| p.is_active()).collect();
+
+        let mut subjects = vec![session.patient_id];
+        for participant in &active {
+            if !subjects.contains(&participant.user_id) {
+                subjects.push(participant.user_id);
+            }
+        }
+
+        let missing = self.session_service.missing_recording_consent(&subjects, auth_token).await?;
+        if !missing.is_empty() {
+            warn!(
+                "Recording of session {} blocked: {} participants without recording consent",
+                session.id,
+                missing.len()
+            );
+            return Err(VideoConferencingError::RecordingConsentRequired { missing });
+        }
+
+        let cf_session_ids: Vec<String> = active
+            .iter()
+            .filter_map(|p| p.cloudflare_session_id.clone())
+            .collect();
+        if cf_session_ids.is_empty() {
+            return Err(VideoConferencingError::InvalidSessionState {
+                status: "Nobody is connected to record".to_string(),
+            });
+        }
+
+        let cf_recording = self.cloudflare.start_recording(cf_session_ids).await?;
+
+        let recording = SessionRecording {
+            id: Uuid::new_v4(),
+            session_id: session.id,
+            cloudflare_recording_id: cf_recording.recording_id,
+            started_by: user_uuid(user)?,
+            started_at: Utc::now(),
+            consented_participants: subjects,
+            stopped_by: None,
+            stopped_at: None,
+            document_id: None,
+        };
+
+        let _: Value = self
+            .supabase
+            .request_with_headers(Method::POST, "/rest/v1/video_session_recordings", Some(auth_token), Some(json!(recording)), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        session.recording_enabled = true;
+        session.updated_at = Utc::now();
+        self.session_service.update_session_record(&session, auth_token).await?;
+
+        self.audit(user, "video.recording_started", &recording, auth_token).await;
+        info!("User {} started recording {} of session {}", user.id, recording.id, session.id);
+
+        Ok((session, recording))
+    }
+
+    async fn stop_recording(
+        &self,
+        mut session: VideoSession,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<(VideoSession, SessionRecording), VideoConferencingError> {
+        if !session.recording_enabled {
+            return Err(VideoConferencingError::ValidationError {
+                message: "The session is not being recorded".to_string(),
+            });
+        }
+
+        let path = format!(
+            "/rest/v1/video_session_recordings?session_id=eq.{}&stopped_at=is.null&order=started_at.desc&limit=1",
+            session.id
+        );
+        let result: Vec<Value> = self
+            .supabase
+            .request(Method::GET, &path, Some(auth_token), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+        let mut recording: SessionRecording = result
+            .into_iter()
+            .next()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: format!("Failed to parse recording: {}", e),
+            })?
+            .ok_or_else(|| VideoConferencingError::InvalidSessionState {
+                status: "No running recording found".to_string(),
+            })?;
+
+        let cf_recording = self.cloudflare.stop_recording(&recording.cloudflare_recording_id).await?;
+
+        // The file belongs in the patient's record; a failure here must not
+        // leave the session stuck in "recording"
+        if let Some(download_url) = &cf_recording.download_url {
+            let title = format!("Video consultation recording {}", recording.started_at.format("%Y-%m-%d %H:%M"));
+            let mime_type = cf_recording.mime_type.as_deref().unwrap_or(DEFAULT_RECORDING_MIME_TYPE);
+            match self
+                .document_service
+                .link_document(&session.patient_id.to_string(), &title, download_url, mime_type, auth_token)
+                .await
+            {
+                Ok(document) => recording.document_id = Some(document.id),
+                Err(e) => warn!("Failed to file recording {} as a document: {}", recording.id, e),
+            }
+        }
+
+        recording.stopped_by = Some(user_uuid(user)?);
+        recording.stopped_at = Some(Utc::now());
+
+        let path = format!("/rest/v1/video_session_recordings?id=eq.{}", recording.id);
+        let _: Value = self
+            .supabase
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                Some(auth_token),
+                Some(json!({
+                    "stopped_by": recording.stopped_by,
+                    "stopped_at": recording.stopped_at,
+                    "document_id": recording.document_id,
+                })),
+                None,
+            )
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        session.recording_enabled = false;
+        session.updated_at = Utc::now();
+        self.session_service.update_session_record(&session, auth_token).await?;
+
+        self.audit(user, "video.recording_stopped", &recording, auth_token).await;
+        info!("User {} stopped recording {} of session {}", user.id, recording.id, session.id);
+
+        Ok((session, recording))
+    }
+
+    async fn audit(&self, user: &User, action: &str, recording: &SessionRecording, auth_token: &str) {
+        let audit_entry = AuditEntry::new(user.id.clone(), action, "video_session", recording.session_id.to_string())
+            .with_details(json!({
+                "recording_id": recording.id,
+                "cloudflare_recording_id": recording.cloudflare_recording_id,
+                "consented_participants": recording.consented_participants,
+                "document_id": recording.document_id,
+            }));
+        if let Err(e) = self.audit_log.record(audit_entry, auth_token).await {
+            warn!("Failed to audit {} for session {}: {}", action, recording.session_id, e);
+        }
+    }
+}
@@ -1,30 +1,40 @@
 // libs/video-conferencing-cell/src/services/session.rs
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 use reqwest::Method;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::info;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use shared_config::AppConfig;
 use shared_database::supabase::SupabaseClient;
 use shared_models::auth::User;
+use appointment_cell::models::AppointmentStatus;
+use patient_cell::models::{ConsentType, PatientError};
+use patient_cell::services::consent::ConsentService;
 
 use crate::models::{
-    CreateVideoSessionRequest, CreateVideoSessionResponse, JoinSessionRequest,
-    JoinSessionResponse, ParticipantType, SessionParticipant, TrackObject,
+    AddParticipantRequest, CreateVideoSessionRequest, CreateVideoSessionResponse,
+    JoinSessionRequest, JoinSessionResponse, ParticipantType, SessionNotice, SessionNoticeRequest,
+    SessionParticipant, TrackObject,
     VideoConferencingError, VideoSession, VideoSessionStatsResponse, VideoSessionStatus,
+    VideoSessionType,
 };
 use crate::services::cloudflare::CloudflareRealtimeClient;
+use crate::services::metrics::{is_connection_failure, video_metrics};
+use crate::services::service_token;
 
 /// Video session management service
 /// Handles session lifecycle, participant management, and Cloudflare integration
 pub struct VideoSessionService {
     supabase: Arc<SupabaseClient>,
     cloudflare: CloudflareRealtimeClient,
-    config: Arc<AppConfig>,
+    consent_service: ConsentService,
+    join_url_ttl: Duration,
+    join_failure_alert_percent: u32,
 }
 
 impl VideoSessionService {
@@ -35,11 +45,14 @@ impl VideoSessionService {
         Ok(Self {
             supabase,
             cloudflare,
-            config: Arc::new(config.clone()),
+            consent_service: ConsentService::new(config),
+            join_url_ttl: Duration::minutes(config.video_join_url_ttl_minutes),
+            join_failure_alert_percent: config.video_join_failure_alert_percent,
         })
     }
 
-    /// Create a new video session tied to an appointment
+    /// Create a new video session tied to an appointment. An appointment has
+    /// at most one session; asking again returns the one that exists.
     pub async fn create_session(
         &self,
         request: CreateVideoSessionRequest,
@@ -59,6 +72,48 @@ impl VideoSessionService {
         // Verify user authorization (patient, doctor, or admin)
         self.verify_appointment_access(&appointment, user)?;
 
+        let (session, created) = self
+            .create_session_for_appointment(
+                &appointment,
+                request.session_type,
+                request.scheduled_start_time,
+                auth_token,
+            )
+            .await?;
+
+        let join_urls_expires_at = session
+            .join_urls_expires_at
+            .unwrap_or_else(|| Utc::now() + self.join_url_ttl);
+        let join_urls = self.join_urls(session.id);
+
+        Ok(CreateVideoSessionResponse {
+            success: true,
+            session,
+            join_urls,
+            join_urls_expires_at,
+            message: if created {
+                "Video session created successfully".to_string()
+            } else {
+                "Video session already exists for this appointment".to_string()
+            },
+        })
+    }
+
+    /// Get-or-create the session for an appointment, keyed by appointment id.
+    /// Safe to repeat: the insert is ignored if another request got there first,
+    /// and whichever row was stored is returned. `true` if this call created it.
+    pub(crate) async fn create_session_for_appointment(
+        &self,
+        appointment: &Value,
+        session_type: VideoSessionType,
+        scheduled_start_time: DateTime<Utc>,
+        auth_token: &str,
+    ) -> Result<(VideoSession, bool), VideoConferencingError> {
+        let appointment_id = appointment["id"]
+            .as_str()
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .ok_or(VideoConferencingError::InvalidAppointment)?;
+
         // Extract appointment details
         let patient_id = appointment["patient_id"]
             .as_str()
@@ -74,22 +129,32 @@ impl VideoSessionService {
                 message: "Invalid doctor ID in appointment".to_string(),
             })?;
 
+        self.require_telemedicine_consent(patient_id, auth_token).await?;
+
+        if let Some(existing) = self.find_session_by_appointment(appointment_id, auth_token).await? {
+            info!("Reusing video session {} for appointment {}", existing.id, appointment_id);
+            return Ok((existing, false));
+        }
+
         // Create video session record
         let session_id = Uuid::new_v4();
         let video_session = VideoSession {
             id: session_id,
-            appointment_id: request.appointment_id,
+            appointment_id,
             patient_id,
             doctor_id,
             cloudflare_session_id: None, // Will be set when first participant joins
             status: VideoSessionStatus::Scheduled,
-            session_type: request.session_type,
-            scheduled_start_time: request.scheduled_start_time,
+            session_type,
+            scheduled_start_time,
             actual_start_time: None,
             actual_end_time: None,
             session_duration_minutes: None,
             quality_rating: None,
             connection_issues: Vec::new(),
+            join_urls_expires_at: Some(Utc::now() + self.join_url_ttl),
+            recording_enabled: false,
+            last_notice: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -97,34 +162,106 @@ impl VideoSessionService {
         // Store session in database
         self.store_session(&video_session, auth_token).await?;
 
-        // Generate join URLs (for frontend integration)
-        let mut join_urls = HashMap::new();
-        join_urls.insert(
-            "patient".to_string(),
-            format!("/video/sessions/{}/join?type=patient", session_id),
-        );
-        join_urls.insert(
-            "doctor".to_string(),
-            format!("/video/sessions/{}/join?type=doctor", session_id),
+        // A concurrent request may have stored its session first
+        let stored = self
+            .find_session_by_appointment(appointment_id, auth_token)
+            .await?
+            .unwrap_or(video_session);
+        let created = stored.id == session_id;
+
+        if created {
+            video_metrics().record_session_created();
+            info!("Successfully created video session: {}", session_id);
+        } else {
+            info!("Video session {} was created concurrently for appointment {}", stored.id, appointment_id);
+        }
+
+        Ok((stored, created))
+    }
+
+    /// The session for an appointment, if there is one. Should duplicates
+    /// exist from before sessions were unique per appointment, the oldest wins.
+    pub(crate) async fn find_session_by_appointment(
+        &self,
+        appointment_id: Uuid,
+        auth_token: &str,
+    ) -> Result<Option<VideoSession>, VideoConferencingError> {
+        let path = format!(
+            "/rest/v1/video_sessions?appointment_id=eq.{}&order=created_at.asc&limit=1",
+            appointment_id
         );
+        let result: Vec<Value> = self
+            .supabase
+            .request(Method::GET, &path, Some(auth_token), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
 
-        info!("Successfully created video session: {}", session_id);
+        result
+            .into_iter()
+            .next()
+            .map(|session_data| {
+                serde_json::from_value(session_data).map_err(|e| VideoConferencingError::DatabaseError {
+                    message: format!("Failed to parse session: {}", e),
+                })
+            })
+            .transpose()
+    }
 
-        Ok(CreateVideoSessionResponse {
-            success: true,
-            session: video_session,
-            join_urls,
-            message: "Video session created successfully".to_string(),
-        })
+    /// Generate patient/doctor join URLs (for frontend integration) valid for the configured TTL
+    pub fn generate_join_urls(&self, session_id: Uuid) -> (HashMap<String, String>, DateTime<Utc>) {
+        let expires_at = Utc::now() + self.join_url_ttl;
+        (self.join_urls(session_id), expires_at)
     }
 
-    /// Join a video session (patient or doctor)
+    /// The expiry isn't part of the URL, where a client could edit it; joins
+    /// are checked against the session's stored `join_urls_expires_at`.
+    fn join_urls(&self, session_id: Uuid) -> HashMap<String, String> {
+        let mut join_urls = HashMap::new();
+        for participant in ["patient", "doctor"] {
+            join_urls.insert(
+                participant.to_string(),
+                format!("/video/sessions/{}/join?type={}", session_id, participant),
+            );
+        }
+
+        join_urls
+    }
+
+    /// Join a video session. The patient and doctor are taken from the session;
+    /// anyone else (interpreters, observers, group members) must be on the roster.
     pub async fn join_session(
         &self,
         session_id: Uuid,
         request: JoinSessionRequest,
         user: &User,
         auth_token: &str,
+    ) -> Result<JoinSessionResponse, VideoConferencingError> {
+        let started = std::time::Instant::now();
+        let result = self.connect_participant(session_id, request, user, auth_token).await;
+
+        // Refused joins say nothing about connectivity, so they aren't counted
+        match &result {
+            Ok(_) => {
+                video_metrics().record_join(true, started.elapsed(), self.join_failure_alert_percent);
+            }
+            Err(e) if is_connection_failure(e) => {
+                warn!("Join of video session {} by user {} failed: {}", session_id, user.id, e);
+                video_metrics().record_join(false, started.elapsed(), self.join_failure_alert_percent);
+            }
+            Err(_) => {}
+        }
+
+        result
+    }
+
+    async fn connect_participant(
+        &self,
+        session_id: Uuid,
+        request: JoinSessionRequest,
+        user: &User,
+        auth_token: &str,
     ) -> Result<JoinSessionResponse, VideoConferencingError> {
         info!(
             "User {} joining video session {} as {:?}",
@@ -133,9 +270,21 @@ impl VideoSessionService {
 
         // Get session from database
         let mut session = self.get_session(session_id, auth_token).await?;
+        let participants = self.get_session_participants(session_id, auth_token).await?;
 
         // Verify user authorization for this session
-        self.verify_session_access(&session, user, &request.user_type)?;
+        self.verify_session_access(&session, user, &request.user_type, &participants)?;
+
+        // Consent can be withdrawn after the session was scheduled
+        self.require_telemedicine_consent(session.patient_id, auth_token).await?;
+
+        // Nobody joins a call that is being recorded without agreeing to it
+        if session.recording_enabled {
+            let missing = self.missing_recording_consent(&[user_uuid(user)?], auth_token).await?;
+            if !missing.is_empty() {
+                return Err(VideoConferencingError::RecordingConsentRequired { missing });
+            }
+        }
 
         // Check session state
         if !matches!(
@@ -147,34 +296,53 @@ impl VideoSessionService {
             });
         }
 
-        // Create or get Cloudflare session
-        let cloudflare_session_id = if let Some(cf_session_id) = &session.cloudflare_session_id {
-            cf_session_id.clone()
-        } else {
-            // First participant - create Cloudflare session
-            if let Some(session_desc) = &request.session_description {
-                let cf_response = self
-                    .cloudflare
-                    .create_session(session_desc.sdp.clone())
-                    .await?;
-
-                // Update session with Cloudflare session ID
+        // Expired links have to be refreshed before anyone can join with them
+        if session.join_urls_expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(VideoConferencingError::JoinLinksExpired);
+        }
+
+        // Rejoining doesn't take an extra place
+        let others_active = participants
+            .iter()
+            .filter(|p| p.is_active() && p.user_id.to_string() != user.id)
+            .count();
+        if others_active >= session.session_type.max_participants() {
+            return Err(VideoConferencingError::SessionCapacityExceeded);
+        }
+
+        // Every participant gets their own Cloudflare session; a client that
+        // reconnects without a new offer keeps the one it had
+        let cloudflare_session_id = if let Some(session_desc) = &request.session_description {
+            let cf_response = self
+                .cloudflare
+                .create_session(session_desc.sdp.clone())
+                .await?;
+
+            // The first participant's session is kept on the video session too
+            if session.cloudflare_session_id.is_none() {
                 session.cloudflare_session_id = Some(cf_response.session_id.clone());
                 session.status = VideoSessionStatus::Ready;
                 session.updated_at = Utc::now();
 
                 self.update_session_record(&session, auth_token).await?;
-
-                cf_response.session_id
-            } else {
-                return Err(VideoConferencingError::WebRTCError {
-                    message: "Session description required for first participant".to_string(),
-                });
             }
+
+            cf_response.session_id
+        } else if let Some(cf_session_id) = participants
+            .iter()
+            .find(|p| p.user_id.to_string() == user.id)
+            .and_then(|p| p.cloudflare_session_id.clone())
+            .or_else(|| session.cloudflare_session_id.clone())
+        {
+            cf_session_id
+        } else {
+            return Err(VideoConferencingError::WebRTCError {
+                message: "Session description required for first participant".to_string(),
+            });
         };
 
         // Record participant joining
-        self.record_participant_join(&session, user, &request.user_type, auth_token)
+        self.record_participant_join(&session, user, &request.user_type, &cloudflare_session_id, auth_token)
             .await?;
 
         // Update session status if this is first active participant
@@ -199,7 +367,7 @@ impl VideoSessionService {
         })
     }
 
-    /// Add tracks to a session (publish local audio/video or request remote tracks)
+    /// Add tracks to the caller's connection (publish local audio/video or request remote tracks)
     pub async fn add_tracks(
         &self,
         session_id: Uuid,
@@ -216,14 +384,15 @@ impl VideoSessionService {
         );
 
         let session = self.get_session(session_id, auth_token).await?;
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+        let participant = active_participant(&participants, user)?;
+        let cloudflare_session_id = participant_cloudflare_session(&session, participant)?;
 
-        // Verify session has Cloudflare session ID
-        let cloudflare_session_id = session
-            .cloudflare_session_id
-            .as_ref()
-            .ok_or_else(|| VideoConferencingError::InvalidSessionState {
-                status: "No Cloudflare session initialized".to_string(),
-            })?;
+        let published: Vec<String> = tracks
+            .iter()
+            .filter(|track| track.location == "local")
+            .filter_map(|track| track.track_name.clone())
+            .collect();
 
         // Add tracks via Cloudflare API
         let track_response = self
@@ -231,6 +400,18 @@ impl VideoSessionService {
             .add_tracks(cloudflare_session_id, tracks, offer_sdp)
             .await?;
 
+        // Remember what was published so later joiners can subscribe to it
+        if !published.is_empty() {
+            let mut published_tracks = participant.published_tracks.clone();
+            for track_name in published {
+                if !published_tracks.contains(&track_name) {
+                    published_tracks.push(track_name);
+                }
+            }
+            self.update_participant(session_id, participant.user_id, json!({ "published_tracks": published_tracks }), auth_token)
+                .await?;
+        }
+
         info!("Successfully added tracks to session: {}", session_id);
 
         Ok(json!({
@@ -242,6 +423,211 @@ impl VideoSessionService {
         }))
     }
 
+    /// Pull every track the other active participants have published into the
+    /// caller's connection. The returned offer is answered via renegotiation.
+    pub async fn subscribe_to_participants(
+        &self,
+        session_id: Uuid,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<Value, VideoConferencingError> {
+        let session = self.get_session(session_id, auth_token).await?;
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+        let participant = active_participant(&participants, user)?;
+        let cloudflare_session_id = participant_cloudflare_session(&session, participant)?;
+
+        let remote_tracks = remote_tracks_for(&participants, participant.user_id);
+        if remote_tracks.is_empty() {
+            return Ok(json!({
+                "success": true,
+                "tracks": [],
+                "message": "No other participant is publishing yet"
+            }));
+        }
+
+        info!(
+            "User {} subscribing to {} remote tracks in session {}",
+            user.id,
+            remote_tracks.len(),
+            session_id
+        );
+
+        let track_response = self
+            .cloudflare
+            .add_tracks(cloudflare_session_id, remote_tracks, None)
+            .await?;
+
+        Ok(json!({
+            "success": true,
+            "tracks": track_response.tracks,
+            "sessionDescription": track_response.session_description,
+            "requiresImmediateRenegotiation": track_response.requires_immediate_renegotiation,
+            "message": "Subscribed to participant tracks"
+        }))
+    }
+
+    /// Everyone on the session's roster, including people invited who haven't joined
+    pub async fn get_participants(
+        &self,
+        session_id: Uuid,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<Vec<SessionParticipant>, VideoConferencingError> {
+        let session = self.get_session(session_id, auth_token).await?;
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+
+        let has_access = session.patient_id.to_string() == user.id
+            || session.doctor_id.to_string() == user.id
+            || user.is_admin()
+            || participants.iter().any(|p| p.user_id.to_string() == user.id);
+
+        if !has_access {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        Ok(participants)
+    }
+
+    /// Put an interpreter, observer or (for group sessions) another patient
+    /// on the roster so they can join. Only the session's doctor or an admin may.
+    pub async fn add_participant(
+        &self,
+        session_id: Uuid,
+        request: AddParticipantRequest,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<SessionParticipant, VideoConferencingError> {
+        let session = self.get_session(session_id, auth_token).await?;
+
+        if session.doctor_id.to_string() != user.id && !user.is_admin() {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        match request.user_type {
+            ParticipantType::Doctor => {
+                return Err(VideoConferencingError::ValidationError {
+                    message: "The session's doctor is set by its appointment".to_string(),
+                });
+            }
+            ParticipantType::Patient if session.session_type != VideoSessionType::GroupSession => {
+                return Err(VideoConferencingError::ValidationError {
+                    message: "Only group sessions can have additional patients".to_string(),
+                });
+            }
+            _ => {}
+        }
+
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+        if participants.iter().any(|p| p.user_id == request.user_id && p.left_at.is_none()) {
+            return Err(VideoConferencingError::ValidationError {
+                message: "User is already a participant in this session".to_string(),
+            });
+        }
+
+        // The patient and doctor always have a place
+        let rostered = participants
+            .iter()
+            .filter(|p| p.left_at.is_none() && p.user_id != session.patient_id && p.user_id != session.doctor_id)
+            .count();
+        if rostered + 2 >= session.session_type.max_participants() {
+            return Err(VideoConferencingError::SessionCapacityExceeded);
+        }
+
+        let participant = SessionParticipant {
+            session_id,
+            user_id: request.user_id,
+            user_type: request.user_type,
+            joined_at: None,
+            left_at: None,
+            connection_quality: None,
+            audio_enabled: true,
+            video_enabled: true,
+            cloudflare_session_id: None,
+            published_tracks: Vec::new(),
+        };
+        self.upsert_participant(json!(participant), auth_token).await?;
+
+        info!(
+            "User {} added {} as {:?} to session {}",
+            user.id, request.user_id, request.user_type, session_id
+        );
+        Ok(participant)
+    }
+
+    /// Tell everyone waiting in the session (connected participants other
+    /// than the doctor) that the doctor is held up. The notice is kept on the
+    /// session, replacing any earlier one, where waiting clients pick it up
+    /// when they poll the session. Returns the participants it was meant for.
+    pub async fn notify_waiting_participants(
+        &self,
+        session_id: Uuid,
+        request: SessionNoticeRequest,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<(VideoSession, Vec<Uuid>), VideoConferencingError> {
+        let mut session = self.get_session(session_id, auth_token).await?;
+
+        if session.doctor_id.to_string() != user.id {
+            return Err(VideoConferencingError::Unauthorized);
+        }
+
+        if matches!(
+            session.status,
+            VideoSessionStatus::Completed | VideoSessionStatus::Cancelled | VideoSessionStatus::Failed
+        ) {
+            return Err(VideoConferencingError::InvalidSessionState {
+                status: format!("{:?}", session.status),
+            });
+        }
+
+        let notice = SessionNotice {
+            message: notice_message(&request)?,
+            delay_minutes: request.delay_minutes,
+            sent_by: session.doctor_id,
+            sent_at: Utc::now(),
+        };
+
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+        let recipients: Vec<Uuid> = participants
+            .iter()
+            .filter(|p| p.is_active() && p.user_id != session.doctor_id)
+            .map(|p| p.user_id)
+            .collect();
+
+        session.last_notice = Some(notice);
+        session.updated_at = Utc::now();
+        self.update_session_record(&session, auth_token).await?;
+
+        info!(
+            "Doctor {} sent a notice to {} waiting participants of session {}",
+            user.id,
+            recipients.len(),
+            session_id
+        );
+        Ok((session, recipients))
+    }
+
+    /// Leave a session without ending it for everyone else
+    pub async fn leave_session(
+        &self,
+        session_id: Uuid,
+        user: &User,
+        auth_token: &str,
+    ) -> Result<(), VideoConferencingError> {
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+        let participant = active_participant(&participants, user)?;
+
+        self.update_participant(session_id, participant.user_id, json!({ "left_at": Utc::now() }), auth_token)
+            .await?;
+
+        if let Some(cf_session_id) = &participant.cloudflare_session_id {
+            let _ = self.cloudflare.cleanup_session(cf_session_id).await;
+        }
+
+        info!("User {} left video session {}", user.id, session_id);
+        Ok(())
+    }
+
     /// Handle WebRTC renegotiation
     pub async fn renegotiate_session(
         &self,
@@ -253,13 +639,9 @@ impl VideoSessionService {
         info!("Renegotiating session {} for user {}", session_id, user.id);
 
         let session = self.get_session(session_id, auth_token).await?;
-
-        let cloudflare_session_id = session
-            .cloudflare_session_id
-            .as_ref()
-            .ok_or_else(|| VideoConferencingError::InvalidSessionState {
-                status: "No Cloudflare session initialized".to_string(),
-            })?;
+        let participants = self.get_session_participants(session_id, auth_token).await?;
+        let participant = active_participant(&participants, user)?;
+        let cloudflare_session_id = participant_cloudflare_session(&session, participant)?;
 
         self.cloudflare
             .renegotiate_session(cloudflare_session_id, answer_sdp)
@@ -289,32 +671,89 @@ impl VideoSessionService {
             return Err(VideoConferencingError::Unauthorized);
         }
 
-        // Update session status
-        session.status = VideoSessionStatus::Completed;
-        session.actual_end_time = Some(Utc::now());
-
-        // Calculate duration if session was started
-        if let Some(start_time) = session.actual_start_time {
-            let duration = Utc::now().signed_duration_since(start_time);
-            session.session_duration_minutes = Some(duration.num_minutes() as i32);
-        }
+        self.close_session(&mut session, VideoSessionStatus::Completed, auth_token)
+            .await?;
 
-        session.updated_at = Utc::now();
+        info!("Successfully ended video session: {}", session_id);
+        Ok(session)
+    }
 
-        // Update in database
-        self.update_session_record(&session, auth_token).await?;
+    /// Reclaim sessions left open after their appointment was cancelled or
+    /// completed, e.g. cancelled before anyone joined. Each one is closed to
+    /// match its appointment and its Cloudflare sessions are ended. Returns
+    /// how many sessions were reclaimed.
+    pub async fn reconcile_orphaned_sessions(&self, auth_token: &str) -> Result<usize, VideoConferencingError> {
+        let path = "/rest/v1/video_sessions?status=in.(scheduled,ready,in_progress)";
+        let result: Vec<Value> = self
+            .supabase
+            .request(Method::GET, path, Some(auth_token), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+        let open_sessions: Vec<VideoSession> = result
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: format!("Failed to parse sessions: {}", e),
+            })?;
 
-        // Record participant leaving
-        self.record_participant_leave(&session, user, auth_token)
-            .await?;
+        if open_sessions.is_empty() {
+            return Ok(0);
+        }
 
-        // Cleanup Cloudflare session
-        if let Some(cf_session_id) = &session.cloudflare_session_id {
-            let _ = self.cloudflare.cleanup_session(cf_session_id).await;
+        let appointment_ids = open_sessions
+            .iter()
+            .map(|session| session.appointment_id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = format!(
+            "/rest/v1/appointments?id=in.({})&status=in.({},{})&select=id,status",
+            appointment_ids,
+            AppointmentStatus::Cancelled,
+            AppointmentStatus::Completed
+        );
+        let finished_appointments: Vec<Value> = self
+            .supabase
+            .request(Method::GET, &path, Some(auth_token), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+        let appointment_status: HashMap<String, AppointmentStatus> = finished_appointments
+            .into_iter()
+            .filter_map(|appointment| {
+                Some((
+                    appointment["id"].as_str()?.to_string(),
+                    serde_json::from_value(appointment["status"].clone()).ok()?,
+                ))
+            })
+            .collect();
+
+        let mut reclaimed = 0;
+        for mut session in open_sessions {
+            let status = match appointment_status.get(&session.appointment_id.to_string()) {
+                Some(AppointmentStatus::Cancelled) => VideoSessionStatus::Cancelled,
+                Some(_) => VideoSessionStatus::Completed,
+                None => continue,
+            };
+
+            // One bad row shouldn't stop the rest from being reclaimed
+            match self.close_session(&mut session, status, auth_token).await {
+                Ok(()) => {
+                    info!(
+                        "Reclaimed video session {} for finished appointment {}",
+                        session.id, session.appointment_id
+                    );
+                    reclaimed += 1;
+                }
+                Err(e) => warn!("Failed to reclaim video session {}: {}", session.id, e),
+            }
         }
 
-        info!("Successfully ended video session: {}", session_id);
-        Ok(session)
+        info!("Reclaimed {} orphaned video sessions", reclaimed);
+        Ok(reclaimed)
     }
 
     /// Get session statistics
@@ -398,23 +837,72 @@ impl VideoSessionService {
         Ok(())
     }
 
+    /// Video consultations need the patient's current telemedicine consent
+    async fn require_telemedicine_consent(
+        &self,
+        patient_id: Uuid,
+        auth_token: &str,
+    ) -> Result<(), VideoConferencingError> {
+        self.consent_service
+            .require_consent(patient_id, ConsentType::Telemedicine, auth_token)
+            .await
+            .map_err(|e| match e {
+                PatientError::ConsentRequired(consent_type) => VideoConferencingError::ConsentRequired {
+                    consent_type: consent_type.to_string(),
+                },
+                other => VideoConferencingError::DatabaseError {
+                    message: other.to_string(),
+                },
+            })
+    }
+
+    /// Which of `user_ids` don't currently grant recording consent
+    pub(crate) async fn missing_recording_consent(
+        &self,
+        user_ids: &[Uuid],
+        auth_token: &str,
+    ) -> Result<Vec<Uuid>, VideoConferencingError> {
+        let mut missing = Vec::new();
+        for user_id in user_ids {
+            let granted = self
+                .consent_service
+                .has_valid_consent(*user_id, ConsentType::Recording, auth_token)
+                .await
+                .map_err(|e| VideoConferencingError::DatabaseError {
+                    message: e.to_string(),
+                })?;
+            if !granted {
+                missing.push(*user_id);
+            }
+        }
+        Ok(missing)
+    }
+
     fn verify_session_access(
         &self,
         session: &VideoSession,
         user: &User,
         user_type: &ParticipantType,
+        participants: &[SessionParticipant],
     ) -> Result<(), VideoConferencingError> {
-        match user_type {
+        let rostered_as = |participant_type: ParticipantType| {
+            participants.iter().any(|p| {
+                p.user_id.to_string() == user.id && p.user_type == participant_type && p.left_at.is_none()
+            })
+        };
+
+        let allowed = match user_type {
             ParticipantType::Patient => {
-                if session.patient_id.to_string() != user.id {
-                    return Err(VideoConferencingError::Unauthorized);
-                }
-            }
-            ParticipantType::Doctor => {
-                if session.doctor_id.to_string() != user.id {
-                    return Err(VideoConferencingError::Unauthorized);
-                }
+                session.patient_id.to_string() == user.id
+                    || (session.session_type == VideoSessionType::GroupSession
+                        && rostered_as(ParticipantType::Patient))
             }
+            ParticipantType::Doctor => session.doctor_id.to_string() == user.id,
+            ParticipantType::Interpreter | ParticipantType::Observer => rostered_as(*user_type),
+        };
+
+        if !allowed {
+            return Err(VideoConferencingError::Unauthorized);
         }
 
         Ok(())
@@ -425,7 +913,8 @@ impl VideoSessionService {
         session: &VideoSession,
         auth_token: &str,
     ) -> Result<(), VideoConferencingError> {
-        let path = "/rest/v1/video_sessions";
+        // One session per appointment; a duplicate insert is dropped
+        let path = "/rest/v1/video_sessions?on_conflict=appointment_id";
         let body = json!({
             "id": session.id,
             "appointment_id": session.appointment_id,
@@ -440,13 +929,18 @@ impl VideoSessionService {
             "session_duration_minutes": session.session_duration_minutes,
             "quality_rating": session.quality_rating,
             "connection_issues": session.connection_issues,
+            "join_urls_expires_at": session.join_urls_expires_at,
+            "recording_enabled": session.recording_enabled,
             "created_at": session.created_at,
             "updated_at": session.updated_at,
         });
 
-        let _: Vec<Value> = self
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("resolution=ignore-duplicates"));
+
+        let _: Value = self
             .supabase
-            .request(Method::POST, path, Some(auth_token), Some(body))
+            .request_with_headers(Method::POST, path, Some(auth_token), Some(body), Some(headers))
             .await
             .map_err(|e| VideoConferencingError::DatabaseError {
                 message: e.to_string(),
@@ -455,7 +949,7 @@ impl VideoSessionService {
         Ok(())
     }
 
-    async fn get_session(
+    pub(crate) async fn get_session(
         &self,
         session_id: Uuid,
         auth_token: &str,
@@ -479,7 +973,7 @@ impl VideoSessionService {
         })
     }
 
-    async fn update_session_record(
+    pub(crate) async fn update_session_record(
         &self,
         session: &VideoSession,
         auth_token: &str,
@@ -493,6 +987,8 @@ impl VideoSessionService {
             "session_duration_minutes": session.session_duration_minutes,
             "quality_rating": session.quality_rating,
             "connection_issues": session.connection_issues,
+            "recording_enabled": session.recording_enabled,
+            "last_notice": session.last_notice,
             "updated_at": session.updated_at,
         });
 
@@ -512,21 +1008,38 @@ impl VideoSessionService {
         session: &VideoSession,
         user: &User,
         user_type: &ParticipantType,
+        cloudflare_session_id: &str,
         auth_token: &str,
     ) -> Result<(), VideoConferencingError> {
-        let path = "/rest/v1/video_session_participants";
+        // A new connection starts with nothing published
         let body = json!({
             "session_id": session.id,
             "user_id": user.id,
             "user_type": user_type,
             "joined_at": Utc::now(),
+            "left_at": null,
             "audio_enabled": true,
             "video_enabled": true,
+            "cloudflare_session_id": cloudflare_session_id,
+            "published_tracks": [],
         });
 
-        let _: Vec<Value> = self
+        self.upsert_participant(body, auth_token).await
+    }
+
+    /// Insert a participant row, or update it if the user is already on the roster
+    pub(crate) async fn upsert_participant(
+        &self,
+        body: Value,
+        auth_token: &str,
+    ) -> Result<(), VideoConferencingError> {
+        let path = "/rest/v1/video_session_participants?on_conflict=session_id,user_id";
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Prefer", reqwest::header::HeaderValue::from_static("resolution=merge-duplicates"));
+
+        let _: Value = self
             .supabase
-            .request(Method::POST, path, Some(auth_token), Some(body))
+            .request_with_headers(Method::POST, path, Some(auth_token), Some(body), Some(headers))
             .await
             .map_err(|e| VideoConferencingError::DatabaseError {
                 message: e.to_string(),
@@ -535,23 +1048,88 @@ impl VideoSessionService {
         Ok(())
     }
 
-    async fn record_participant_leave(
+    pub(crate) async fn update_participant(
         &self,
-        session: &VideoSession,
-        user: &User,
+        session_id: Uuid,
+        user_id: Uuid,
+        body: Value,
         auth_token: &str,
     ) -> Result<(), VideoConferencingError> {
         let path = format!(
             "/rest/v1/video_session_participants?session_id=eq.{}&user_id=eq.{}",
-            session.id, user.id
+            session_id, user_id
+        );
+
+        let _: Value = self
+            .supabase
+            .request_with_headers(Method::PATCH, &path, Some(auth_token), Some(body), None)
+            .await
+            .map_err(|e| VideoConferencingError::DatabaseError {
+                message: e.to_string(),
+            })?;
+
+        Ok(())
+    }
+
+    /// Move a session to a final status, disconnect everyone still in it and
+    /// end its Cloudflare sessions
+    async fn close_session(
+        &self,
+        session: &mut VideoSession,
+        status: VideoSessionStatus,
+        auth_token: &str,
+    ) -> Result<(), VideoConferencingError> {
+        let now = Utc::now();
+        if status == VideoSessionStatus::Failed {
+            video_metrics().record_session_failed();
+        }
+        session.status = status;
+        session.actual_end_time = Some(now);
+
+        // Calculate duration if session was started
+        if let Some(start_time) = session.actual_start_time {
+            let duration = now.signed_duration_since(start_time);
+            session.session_duration_minutes = Some(duration.num_minutes() as i32);
+        }
+
+        session.updated_at = now;
+
+        // Update in database
+        self.update_session_record(session, auth_token).await?;
+
+        let participants = self.get_session_participants(session.id, auth_token).await?;
+        self.record_participants_leave(session, auth_token).await?;
+
+        // Cleanup Cloudflare sessions
+        let cf_session_ids = participants
+            .iter()
+            .filter_map(|p| p.cloudflare_session_id.as_ref())
+            .chain(session.cloudflare_session_id.as_ref());
+        for cf_session_id in cf_session_ids {
+            if let Err(e) = self.cloudflare.cleanup_session(cf_session_id).await {
+                warn!("Failed to clean up Cloudflare session {}: {}", cf_session_id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn record_participants_leave(
+        &self,
+        session: &VideoSession,
+        auth_token: &str,
+    ) -> Result<(), VideoConferencingError> {
+        let path = format!(
+            "/rest/v1/video_session_participants?session_id=eq.{}&left_at=is.null",
+            session.id
         );
         let body = json!({
             "left_at": Utc::now(),
         });
 
-        let _: Vec<Value> = self
+        let _: Value = self
             .supabase
-            .request(Method::PATCH, &path, Some(auth_token), Some(body))
+            .request_with_headers(Method::PATCH, &path, Some(auth_token), Some(body), None)
             .await
             .map_err(|e| VideoConferencingError::DatabaseError {
                 message: e.to_string(),
@@ -560,7 +1138,7 @@ impl VideoSessionService {
         Ok(())
     }
 
-    async fn get_session_participants(
+    pub(crate) async fn get_session_participants(
         &self,
         session_id: Uuid,
         auth_token: &str,
@@ -583,4 +1161,125 @@ impl VideoSessionService {
             })
             .collect()
     }
-}
\ No newline at end of file
+}
+/// Start the background task that periodically reclaims orphaned video
+/// sessions (see `VideoSessionService::reconcile_orphaned_sessions`). Call once
+/// at startup. Does nothing when video conferencing isn't configured or
+/// `VIDEO_SESSION_RECONCILE_INTERVAL_MINUTES` is 0.
+pub fn spawn_reconciliation_worker(config: &AppConfig) -> Option<JoinHandle<()>> {
+    if config.video_session_reconcile_interval_minutes == 0 {
+        info!("Video session reconciliation is disabled");
+        return None;
+    }
+
+    let service = match VideoSessionService::new(config) {
+        Ok(service) => service,
+        Err(e) => {
+            warn!("Video session reconciliation not started: {}", e);
+            return None;
+        }
+    };
+    let jwt_secret = config.supabase_jwt_secret.clone();
+    let period = std::time::Duration::from_secs(config.video_session_reconcile_interval_minutes * 60);
+
+    Some(tokio::spawn(async move {
+        info!("Video session reconciliation worker started");
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let token = match service_token(&jwt_secret, "video-reconciliation") {
+                Ok(token) => token,
+                Err(e) => {
+                    error!("Failed to mint token for video session reconciliation: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = service.reconcile_orphaned_sessions(&token).await {
+                error!("Video session reconciliation failed: {}", e);
+            }
+        }
+    }))
+}
+
+pub(crate) fn user_uuid(user: &User) -> Result<Uuid, VideoConferencingError> {
+    Uuid::parse_str(&user.id).map_err(|_| VideoConferencingError::Unauthorized)
+}
+
+/// The caller's own participant entry; only people currently in the call can
+/// manage tracks
+fn active_participant<'a>(
+    participants: &'a [SessionParticipant],
+    user: &User,
+) -> Result<&'a SessionParticipant, VideoConferencingError> {
+    participants
+        .iter()
+        .find(|p| p.user_id.to_string() == user.id && p.is_active())
+        .ok_or(VideoConferencingError::Unauthorized)
+}
+
+/// Longest delay a waiting-room notice can announce
+const MAX_NOTICE_DELAY_MINUTES: i32 = 240;
+const MAX_NOTICE_LENGTH: usize = 500;
+
+/// The text of a waiting-room notice: the doctor's own message, or one
+/// built from the delay
+fn notice_message(request: &SessionNoticeRequest) -> Result<String, VideoConferencingError> {
+    if let Some(delay) = request.delay_minutes {
+        if !(1..=MAX_NOTICE_DELAY_MINUTES).contains(&delay) {
+            return Err(VideoConferencingError::ValidationError {
+                message: format!("delay_minutes must be between 1 and {}", MAX_NOTICE_DELAY_MINUTES),
+            });
+        }
+    }
+
+    match (request.message.as_deref().map(str::trim), request.delay_minutes) {
+        (Some(message), _) if !message.is_empty() => {
+            if message.chars().count() > MAX_NOTICE_LENGTH {
+                return Err(VideoConferencingError::ValidationError {
+                    message: format!("Notice must be at most {} characters", MAX_NOTICE_LENGTH),
+                });
+            }
+            Ok(message.to_string())
+        }
+        (_, Some(delay)) => Ok(format!(
+            "Your doctor is running about {} minutes late. Please stay in the waiting room.",
+            delay
+        )),
+        _ => Err(VideoConferencingError::ValidationError {
+            message: "A notice needs a message or delay_minutes".to_string(),
+        }),
+    }
+}
+
+/// Participants recorded before per-participant sessions share the session's one
+fn participant_cloudflare_session<'a>(
+    session: &'a VideoSession,
+    participant: &'a SessionParticipant,
+) -> Result<&'a str, VideoConferencingError> {
+    participant
+        .cloudflare_session_id
+        .as_deref()
+        .or(session.cloudflare_session_id.as_deref())
+        .ok_or_else(|| VideoConferencingError::InvalidSessionState {
+            status: "No Cloudflare session initialized".to_string(),
+        })
+}
+
+/// Remote track requests for everything the other active participants publish
+fn remote_tracks_for(participants: &[SessionParticipant], subscriber: Uuid) -> Vec<TrackObject> {
+    participants
+        .iter()
+        .filter(|p| p.user_id != subscriber && p.is_active())
+        .filter_map(|p| p.cloudflare_session_id.as_ref().map(|cf_session_id| (p, cf_session_id)))
+        .flat_map(|(p, cf_session_id)| {
+            p.published_tracks.iter().map(move |track_name| TrackObject {
+                location: "remote".to_string(),
+                mid: None,
+                track_name: Some(track_name.clone()),
+                session_id: Some(cf_session_id.clone()),
+            })
+        })
+        .collect()
+}
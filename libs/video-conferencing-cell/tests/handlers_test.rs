@@ -109,4 +109,1088 @@ async fn test_get_upcoming_sessions_unauthorized() {
         .unwrap();
     
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_create_video_session_requires_telemedicine_consent() {
+    use shared_utils::test_utils::{JwtTestUtils, MockSupabaseResponses, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path, query_param}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let appointment = MockSupabaseResponses::appointment_response(&patient.id, &uuid::Uuid::new_v4().to_string());
+    let appointment_id = appointment["id"].clone();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([appointment])))
+        .mount(&mock_server)
+        .await;
+
+    // Latest telemedicine decision is a withdrawal
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patient_consents"))
+        .and(query_param("consent_type", "eq.telemedicine"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": uuid::Uuid::new_v4(),
+            "patient_id": patient.id,
+            "consent_type": "telemedicine",
+            "version": "2024-01",
+            "granted": false,
+            "ip_address": null,
+            "recorded_at": "2024-06-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions")
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({
+                    "appointment_id": appointment_id,
+                    "session_type": "consultation",
+                    "scheduled_start_time": "2024-12-25T10:00:00Z"
+                }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "video.consent_required");
+}
+
+fn video_session_json(session_id: uuid::Uuid, patient_id: &str, doctor_id: &str, session_type: &str) -> serde_json::Value {
+    json!({
+        "id": session_id,
+        "appointment_id": uuid::Uuid::new_v4(),
+        "patient_id": patient_id,
+        "doctor_id": doctor_id,
+        "cloudflare_session_id": "cf-first",
+        "status": "in_progress",
+        "session_type": session_type,
+        "scheduled_start_time": "2024-12-25T10:00:00Z",
+        "actual_start_time": "2024-12-25T10:00:00Z",
+        "actual_end_time": null,
+        "session_duration_minutes": null,
+        "quality_rating": null,
+        "connection_issues": [],
+        "created_at": "2024-12-01T00:00:00Z",
+        "updated_at": "2024-12-01T00:00:00Z"
+    })
+}
+
+fn participant_json(session_id: uuid::Uuid, user_id: &str, user_type: &str, joined: bool, cf_session_id: Option<&str>, tracks: &[&str]) -> serde_json::Value {
+    json!({
+        "session_id": session_id,
+        "user_id": user_id,
+        "user_type": user_type,
+        "joined_at": if joined { Some("2024-12-25T10:01:00Z") } else { None },
+        "left_at": null,
+        "connection_quality": null,
+        "audio_enabled": true,
+        "video_enabled": true,
+        "cloudflare_session_id": cf_session_id,
+        "published_tracks": tracks
+    })
+}
+
+#[tokio::test]
+async fn test_session_participant_roster() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let patient_id = uuid::Uuid::new_v4().to_string();
+    let interpreter_id = uuid::Uuid::new_v4().to_string();
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient_id, &doctor.id, "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            participant_json(session_id, &patient_id, "patient", true, Some("cf-patient"), &["patient-video"]),
+            participant_json(session_id, &interpreter_id, "interpreter", false, None, &[])
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/sessions/{}/participants", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 2);
+    assert_eq!(json["active"], 1);
+    assert_eq!(json["participants"][1]["user_type"], "interpreter");
+}
+
+#[tokio::test]
+async fn test_interpreter_must_be_on_roster_to_join() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let interpreter = TestUser::patient("interpreter@example.com");
+    let token = JwtTestUtils::create_test_token(&interpreter, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([video_session_json(
+            session_id,
+            &uuid::Uuid::new_v4().to_string(),
+            &uuid::Uuid::new_v4().to_string(),
+            "consultation"
+        )])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/join", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "user_type": "interpreter" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_join_rejected_when_session_is_full() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+    let doctor_id = uuid::Uuid::new_v4().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient.id, &doctor_id, "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    // Four other people already connected to a one-to-one consultation
+    let mut others = vec![participant_json(session_id, &doctor_id, "doctor", true, Some("cf-doctor"), &[])];
+    for user_type in ["interpreter", "observer", "observer"] {
+        others.push(participant_json(session_id, &uuid::Uuid::new_v4().to_string(), user_type, true, None, &[]));
+    }
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(others)))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patient_consents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": uuid::Uuid::new_v4(),
+            "patient_id": patient.id,
+            "consent_type": "telemedicine",
+            "version": "2024-01",
+            "granted": true,
+            "ip_address": null,
+            "recorded_at": "2024-06-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/join", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "user_type": "patient" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "video.session_full");
+}
+
+#[tokio::test]
+async fn test_join_rejected_after_join_links_expire() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+    let doctor_id = uuid::Uuid::new_v4().to_string();
+
+    let mut session = video_session_json(session_id, &patient.id, &doctor_id, "consultation");
+    session["join_urls_expires_at"] = json!("2024-12-25T09:00:00Z");
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([session])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patient_consents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": uuid::Uuid::new_v4(),
+            "patient_id": patient.id,
+            "consent_type": "telemedicine",
+            "version": "2024-01",
+            "granted": true,
+            "ip_address": null,
+            "recorded_at": "2024-06-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    // An `expires` parameter edited into the link changes nothing
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/join?type=patient&expires=4102444800", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "user_type": "patient" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "video.join_links_expired");
+}
+
+#[tokio::test]
+async fn test_subscribe_fans_out_to_all_active_participants() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+    config.cloudflare_realtime_base_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+    let doctor_id = uuid::Uuid::new_v4().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient.id, &doctor_id, "group_session")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            participant_json(session_id, &patient.id, "patient", true, Some("cf-patient"), &["patient-video"]),
+            participant_json(session_id, &doctor_id, "doctor", true, Some("cf-doctor"), &["doctor-audio", "doctor-video"]),
+            participant_json(session_id, &uuid::Uuid::new_v4().to_string(), "interpreter", true, Some("cf-interpreter"), &["interpreter-audio"]),
+            // Invited but not connected: nothing to subscribe to
+            participant_json(session_id, &uuid::Uuid::new_v4().to_string(), "observer", false, None, &[])
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/apps/test-app-id/sessions/cf-patient/tracks/new"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sessionDescription": { "type": "offer", "sdp": "v=0" },
+            "requiresImmediateRenegotiation": true,
+            "tracks": [
+                { "mid": "0", "trackName": "doctor-audio" },
+                { "mid": "1", "trackName": "doctor-video" },
+                { "mid": "2", "trackName": "interpreter-audio" }
+            ]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/tracks/subscribe", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let cloudflare_request = requests
+        .iter()
+        .find(|request| request.url.path().ends_with("/tracks/new"))
+        .unwrap();
+    let sent: serde_json::Value = serde_json::from_slice(&cloudflare_request.body).unwrap();
+    let tracks = sent["tracks"].as_array().unwrap();
+    assert_eq!(tracks.len(), 3);
+    assert!(tracks.iter().all(|track| track["location"] == "remote"));
+    assert!(tracks.iter().any(|track| track["sessionId"] == "cf-doctor" && track["trackName"] == "doctor-video"));
+    assert!(tracks.iter().any(|track| track["sessionId"] == "cf-interpreter"));
+}
+
+fn invite_json(session_id: uuid::Uuid, used: bool) -> serde_json::Value {
+    json!({
+        "id": uuid::Uuid::new_v4(),
+        "session_id": session_id,
+        "participant_type": "interpreter",
+        "label": "BSL interpreter",
+        "created_by": uuid::Uuid::new_v4(),
+        "expires_at": (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339(),
+        "used_at": if used { Some("2024-12-25T09:55:00Z") } else { None },
+        "revoked_at": null,
+        "created_at": "2024-12-25T09:00:00Z"
+    })
+}
+
+#[tokio::test]
+async fn test_doctor_issues_interpreter_invite() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &uuid::Uuid::new_v4().to_string(), &doctor.id, "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_session_invites"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/invite", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "label": "BSL interpreter", "expires_in_minutes": 60 }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["invite"]["participant_type"], "interpreter");
+    let join_url = json["join_url"].as_str().unwrap();
+    let link_token = join_url.trim_start_matches("/video/invites/");
+
+    // Only a hash of the link token is stored
+    let requests = mock_server.received_requests().await.unwrap();
+    let stored = requests
+        .iter()
+        .find(|request| request.url.path() == "/rest/v1/video_session_invites")
+        .unwrap();
+    let stored: serde_json::Value = serde_json::from_slice(&stored.body).unwrap();
+    assert_eq!(stored["token_hash"].as_str().unwrap().len(), 64);
+    assert!(!stored.to_string().contains(link_token));
+}
+
+#[tokio::test]
+async fn test_patient_cannot_issue_observer_invite() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient.id, &uuid::Uuid::new_v4().to_string(), "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_session_invites"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/invite", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "participant_type": "observer" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_redeem_invite_issues_guest_token() {
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path, body_partial_json}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let session_id = uuid::Uuid::new_v4();
+    let invite = invite_json(session_id, false);
+    let invite_id = invite["id"].as_str().unwrap().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_invites"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([invite.clone()])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([video_session_json(
+            session_id,
+            &uuid::Uuid::new_v4().to_string(),
+            &uuid::Uuid::new_v4().to_string(),
+            "consultation"
+        )])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_session_invites"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([invite])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_session_participants"))
+        .and(body_partial_json(json!({ "user_id": invite_id, "user_type": "interpreter" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&mock_server)
+        .await;
+
+    let jwt_secret = config.supabase_jwt_secret.clone();
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/invites/inv_example")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["participant_type"], "interpreter");
+
+    // The guest acts as the invite, not as the patient or doctor
+    let guest = shared_utils::jwt::validate_token(json["access_token"].as_str().unwrap(), &jwt_secret).unwrap();
+    assert_eq!(guest.id, invite_id);
+}
+
+#[tokio::test]
+async fn test_used_invite_cannot_be_redeemed_again() {
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_invites"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([invite_json(uuid::Uuid::new_v4(), true)])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let app = video_conferencing_routes(Arc::new(config));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/invites/inv_example")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "video.invite_invalid");
+}
+
+fn consent_json(user_id: &str, granted: bool) -> serde_json::Value {
+    json!([{
+        "id": uuid::Uuid::new_v4(),
+        "patient_id": user_id,
+        "consent_type": "recording",
+        "version": "2024-01",
+        "granted": granted,
+        "ip_address": null,
+        "recorded_at": "2024-06-01T00:00:00Z"
+    }])
+}
+
+async fn toggle_recording(config: shared_config::AppConfig, session_id: uuid::Uuid, token: &str, enabled: bool) -> axum::response::Response {
+    video_conferencing_routes(Arc::new(config))
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/recording", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!({ "enabled": enabled }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_recording_blocked_without_everyones_consent() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path, query_param}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+    config.cloudflare_realtime_base_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let patient_id = uuid::Uuid::new_v4().to_string();
+    let interpreter_id = uuid::Uuid::new_v4().to_string();
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient_id, &doctor.id, "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            participant_json(session_id, &patient_id, "patient", true, Some("cf-patient"), &[]),
+            participant_json(session_id, &doctor.id, "doctor", true, Some("cf-doctor"), &[]),
+            participant_json(session_id, &interpreter_id, "interpreter", true, Some("cf-interpreter"), &[])
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    // Patient and doctor agreed; the interpreter didn't
+    for user_id in [&patient_id, &doctor.id] {
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/patient_consents"))
+            .and(query_param("patient_id", format!("eq.{}", user_id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(consent_json(user_id, true)))
+            .mount(&mock_server)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patient_consents"))
+        .and(query_param("patient_id", format!("eq.{}", interpreter_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/apps/test-app-id/recordings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "recordingId": "rec-1" })))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let response = toggle_recording(config, session_id, &token, true).await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "video.recording_consent_required");
+    assert!(json["detail"].as_str().unwrap().contains(&interpreter_id));
+}
+
+#[tokio::test]
+async fn test_recording_starts_when_everyone_consented() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path, body_partial_json}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+    config.cloudflare_realtime_base_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let patient_id = uuid::Uuid::new_v4().to_string();
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient_id, &doctor.id, "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            participant_json(session_id, &patient_id, "patient", true, Some("cf-patient"), &[]),
+            participant_json(session_id, &doctor.id, "doctor", true, Some("cf-doctor"), &[])
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patient_consents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(consent_json(&patient_id, true)))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/apps/test-app-id/recordings"))
+        .and(body_partial_json(json!({ "sessionIds": ["cf-patient", "cf-doctor"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "recordingId": "rec-1" })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_session_recordings"))
+        .and(body_partial_json(json!({ "cloudflare_recording_id": "rec-1" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(body_partial_json(json!({ "recording_enabled": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!({ "action": "video.recording_started" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let response = toggle_recording(config, session_id, &token, true).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["recording_enabled"], true);
+}
+
+#[tokio::test]
+async fn test_stopped_recording_is_filed_as_patient_document() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path, body_partial_json}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+    config.cloudflare_realtime_base_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let patient_id = uuid::Uuid::new_v4().to_string();
+    let session_id = uuid::Uuid::new_v4();
+    let document_id = uuid::Uuid::new_v4();
+
+    let mut session = video_session_json(session_id, &patient_id, &doctor.id, "consultation");
+    session["recording_enabled"] = json!(true);
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([session])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_recordings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": uuid::Uuid::new_v4(),
+            "session_id": session_id,
+            "cloudflare_recording_id": "rec-1",
+            "started_by": doctor.id,
+            "started_at": "2024-12-25T10:05:00Z",
+            "consented_participants": [patient_id, doctor.id],
+            "stopped_by": null,
+            "stopped_at": null,
+            "document_id": null
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/apps/test-app-id/recordings/rec-1/stop"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recordingId": "rec-1",
+            "downloadUrl": "https://recordings.example.com/rec-1.webm",
+            "mimeType": "video/webm"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/documents"))
+        .and(body_partial_json(json!({
+            "patient_id": patient_id,
+            "file_url": "https://recordings.example.com/rec-1.webm"
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{
+            "id": document_id,
+            "patient_id": patient_id,
+            "title": "Video consultation recording 2024-12-25 10:05",
+            "file_url": "https://recordings.example.com/rec-1.webm",
+            "file_type": "video/webm",
+            "uploaded_at": "2024-12-25T10:30:00Z"
+        }])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_session_recordings"))
+        .and(body_partial_json(json!({ "document_id": document_id })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(body_partial_json(json!({ "recording_enabled": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!({ "action": "video.recording_stopped" })))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let response = toggle_recording(config, session_id, &token, false).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["recording"]["document_id"], document_id.to_string());
+}
+
+#[tokio::test]
+async fn test_patient_cannot_start_recording() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient.id, &uuid::Uuid::new_v4().to_string(), "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let response = toggle_recording(config, session_id, &token, true).await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+async fn notify_waiting(config: shared_config::AppConfig, session_id: uuid::Uuid, token: &str, body: serde_json::Value) -> axum::response::Response {
+    video_conferencing_routes(Arc::new(config))
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/{}/notify", session_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_doctor_notifies_waiting_patients_of_delay() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{body_partial_json, method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let doctor = TestUser::doctor("doctor@example.com");
+    let patient_id = uuid::Uuid::new_v4().to_string();
+    let observer_id = uuid::Uuid::new_v4().to_string();
+    let token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    let mut session = video_session_json(session_id, &patient_id, &doctor.id, "consultation");
+    session["status"] = json!("ready");
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([session])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            participant_json(session_id, &patient_id, "patient", true, Some("cf-patient"), &[]),
+            participant_json(session_id, &observer_id, "observer", false, None, &[])
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(body_partial_json(json!({ "last_notice": { "delay_minutes": 10 } })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let response = notify_waiting(config, session_id, &token, json!({ "delay_minutes": 10 })).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    // Only the connected patient is waiting; the observer never joined
+    assert_eq!(json["recipients"], json!([patient_id]));
+    assert_eq!(json["notice"]["delay_minutes"], 10);
+    assert!(json["notice"]["message"].as_str().unwrap().contains("10 minutes late"));
+}
+
+#[tokio::test]
+async fn test_only_the_doctor_can_notify_waiting_participants() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+    use wiremock::{Mock, ResponseTemplate, matchers::{method, path}};
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient = TestUser::patient("patient@example.com");
+    let doctor = TestUser::doctor("doctor@example.com");
+    let patient_token = JwtTestUtils::create_test_token(&patient, &config.supabase_jwt_secret, Some(24));
+    let doctor_token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let session_id = uuid::Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            video_session_json(session_id, &patient.id, &doctor.id, "consultation")
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let response = notify_waiting(config.clone(), session_id, &patient_token, json!({ "delay_minutes": 10 })).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = notify_waiting(config.clone(), session_id, &doctor_token, json!({ "delay_minutes": 0 })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = notify_waiting(config, session_id, &doctor_token, json!({ "message": "  " })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_video_metrics_require_admin() {
+    use shared_utils::test_utils::{JwtTestUtils, TestUser};
+
+    let config = create_test_config();
+    let doctor = TestUser::doctor("doctor@example.com");
+    let admin = TestUser::admin("admin@example.com");
+    let doctor_token = JwtTestUtils::create_test_token(&doctor, &config.supabase_jwt_secret, Some(24));
+    let admin_token = JwtTestUtils::create_test_token(&admin, &config.supabase_jwt_secret, Some(24));
+    let app = video_conferencing_routes(Arc::new(config));
+
+    let response = app.clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/metrics")
+                .header("Authorization", format!("Bearer {}", doctor_token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/metrics")
+                .header("Authorization", format!("Bearer {}", admin_token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let metrics: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(metrics["joins_failed"].is_u64());
+    assert_eq!(metrics["window_minutes"], 15);
+}
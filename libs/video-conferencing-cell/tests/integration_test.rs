@@ -3,10 +3,10 @@ use uuid::Uuid;
 use wiremock::{MockServer, Mock, ResponseTemplate};
 use wiremock::matchers::{method, path};
 
-use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils};
+use shared_utils::test_utils::{TestConfig, TestUser, JwtTestUtils, MockSupabaseResponses};
 use video_conferencing_cell::{
     models::{VideoSessionType, CreateVideoSessionRequest},
-    services::{VideoConferencingIntegrationService, CloudflareRealtimeClient},
+    services::{VideoConferencingIntegrationService, VideoSessionService, CloudflareRealtimeClient},
 };
 
 fn create_test_config() -> shared_config::AppConfig {
@@ -57,7 +57,7 @@ async fn test_video_availability_check() {
     
     // Mock appointment response
     Mock::given(method("GET"))
-        .and(path(format!("/rest/v1/appointments")))
+        .and(path("/rest/v1/appointments"))
         .respond_with(ResponseTemplate::new(200).set_body_json(vec![json!({
             "id": appointment_id,
             "patient_id": patient_id,
@@ -97,6 +97,7 @@ async fn test_video_session_type_serialization() {
     assert_eq!(consultation_json, "\"consultation\"");
     assert_eq!(follow_up_json, "\"follow_up\"");
     assert_eq!(emergency_json, "\"emergency\"");
+    assert_eq!(serde_json::to_string(&VideoSessionType::GroupSession).unwrap(), "\"group_session\"");
 }
 
 #[tokio::test]
@@ -116,4 +117,331 @@ async fn test_create_session_request_validation() {
     
     assert_eq!(request.appointment_id, deserialized.appointment_id);
     assert_eq!(request.session_type, deserialized.session_type);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_refresh_join_urls_for_appointment() {
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+    config.video_join_url_ttl_minutes = 30;
+
+    let patient_user = TestUser::patient("patient@example.com");
+    let session_id = Uuid::new_v4();
+    let appointment_id = Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(vec![json!({
+            "id": session_id,
+            "appointment_id": appointment_id,
+            "patient_id": patient_user.id,
+            "doctor_id": Uuid::new_v4(),
+            "cloudflare_session_id": null,
+            "status": "scheduled",
+            "session_type": "consultation",
+            "scheduled_start_time": "2024-12-25T10:00:00Z",
+            "actual_start_time": null,
+            "actual_end_time": null,
+            "session_duration_minutes": null,
+            "quality_rating": null,
+            "connection_issues": [],
+            "join_urls_expires_at": "2024-12-25T09:00:00Z",
+            "created_at": "2024-12-24T10:00:00Z",
+            "updated_at": "2024-12-24T10:00:00Z"
+        })]))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(wiremock::matchers::header("Prefer", "return=representation"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let user = shared_models::auth::User {
+        id: patient_user.id.clone(),
+        email: Some(patient_user.email.clone()),
+        role: Some("patient".to_string()),
+        metadata: None,
+        created_at: None,
+    };
+
+    let service = VideoConferencingIntegrationService::new(&config).unwrap();
+    let refreshed = service
+        .refresh_join_urls_for_appointment(appointment_id, &user, &token)
+        .await
+        .unwrap();
+
+    assert_eq!(refreshed.session_id, session_id);
+    assert!(refreshed.join_urls["patient"].contains(&session_id.to_string()));
+    let ttl = refreshed.expires_at - chrono::Utc::now();
+    assert!(ttl > chrono::Duration::minutes(29) && ttl <= chrono::Duration::minutes(30));
+
+    // Someone not on the appointment cannot refresh
+    let outsider = shared_models::auth::User {
+        id: Uuid::new_v4().to_string(),
+        role: Some("patient".to_string()),
+        ..user
+    };
+    let denied = service
+        .refresh_join_urls_for_appointment(appointment_id, &outsider, &token)
+        .await;
+    assert!(matches!(denied, Err(video_conferencing_cell::VideoConferencingError::Unauthorized)));
+}
+
+fn existing_session_json(session_id: Uuid, appointment_id: Uuid, patient_id: &str) -> serde_json::Value {
+    json!({
+        "id": session_id,
+        "appointment_id": appointment_id,
+        "patient_id": patient_id,
+        "doctor_id": Uuid::new_v4(),
+        "cloudflare_session_id": null,
+        "status": "scheduled",
+        "session_type": "consultation",
+        "scheduled_start_time": "2024-12-25T10:00:00Z",
+        "actual_start_time": null,
+        "actual_end_time": null,
+        "session_duration_minutes": null,
+        "quality_rating": null,
+        "connection_issues": [],
+        "join_urls_expires_at": "2024-12-25T09:00:00Z",
+        "created_at": "2024-12-24T10:00:00Z",
+        "updated_at": "2024-12-24T10:00:00Z"
+    })
+}
+
+#[tokio::test]
+async fn test_repeated_confirmation_reuses_existing_session() {
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient_user = TestUser::patient("patient@example.com");
+    let session_id = Uuid::new_v4();
+    let appointment_id = Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(vec![existing_session_json(
+            session_id,
+            appointment_id,
+            &patient_user.id,
+        )]))
+        .mount(&mock_server)
+        .await;
+
+    // A retried "confirmed" must not create anything
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let service = VideoConferencingIntegrationService::new(&config).unwrap();
+
+    for _ in 0..2 {
+        let session = service
+            .handle_appointment_status_change(appointment_id, "confirmed", &token)
+            .await
+            .unwrap()
+            .expect("existing session is returned");
+        assert_eq!(session.id, session_id);
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_confirmation_returns_the_stored_session() {
+    use wiremock::matchers::query_param;
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient_user = TestUser::patient("patient@example.com");
+    let winner_id = Uuid::new_v4();
+    let mut appointment = MockSupabaseResponses::appointment_response(&patient_user.id, &Uuid::new_v4().to_string());
+    appointment["status"] = json!("confirmed");
+    let appointment_id: Uuid = appointment["id"].as_str().unwrap().parse().unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([appointment])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/patient_consents"))
+        .and(query_param("consent_type", "eq.telemedicine"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": Uuid::new_v4(),
+            "patient_id": patient_user.id,
+            "consent_type": "telemedicine",
+            "version": "2024-01",
+            "granted": true,
+            "ip_address": null,
+            "recorded_at": "2024-06-01T00:00:00Z"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    // No session on either lookup, but another delivery of the event stores
+    // one before our insert lands
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .up_to_n_times(2)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(vec![existing_session_json(
+            winner_id,
+            appointment_id,
+            &patient_user.id,
+        )]))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(query_param("on_conflict", "appointment_id"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/appointments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([appointment])))
+        .mount(&mock_server)
+        .await;
+
+    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let service = VideoConferencingIntegrationService::new(&config).unwrap();
+
+    let session = service
+        .handle_appointment_status_change(appointment_id, "confirmed", &token)
+        .await
+        .unwrap()
+        .expect("session is created on confirmation");
+    assert_eq!(session.id, winner_id);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let prefer = requests
+        .iter()
+        .find(|r| r.method.as_str() == "POST")
+        .and_then(|r| r.headers.get("Prefer"))
+        .map(|v| v.to_str().unwrap().to_string());
+    assert_eq!(prefer.as_deref(), Some("resolution=ignore-duplicates"));
+}
+
+#[tokio::test]
+async fn test_reconcile_reclaims_sessions_of_finished_appointments() {
+    use wiremock::matchers::query_param;
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    let patient_user = TestUser::patient("patient@example.com");
+    let orphan_id = Uuid::new_v4();
+    let cancelled_appointment_id = Uuid::new_v4();
+    let live_appointment_id = Uuid::new_v4();
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(query_param("status", "in.(scheduled,ready,in_progress)"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(vec![
+            existing_session_json(orphan_id, cancelled_appointment_id, &patient_user.id),
+            existing_session_json(Uuid::new_v4(), live_appointment_id, &patient_user.id),
+        ]))
+        .mount(&mock_server)
+        .await;
+
+    // Only the cancelled appointment comes back from the finished-status filter
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/appointments"))
+        .and(query_param("status", "in.(cancelled,completed)"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": cancelled_appointment_id,
+            "status": "cancelled"
+        }])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_sessions"))
+        .and(query_param("id", format!("eq.{}", orphan_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/video_session_participants"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&mock_server)
+        .await;
+
+    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let service = VideoSessionService::new(&config).unwrap();
+
+    let reclaimed = service.reconcile_orphaned_sessions(&token).await.unwrap();
+    assert_eq!(reclaimed, 1);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let update = requests
+        .iter()
+        .find(|r| r.method.as_str() == "PATCH" && r.url.path() == "/rest/v1/video_sessions")
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&update.body).unwrap();
+    assert_eq!(body["status"], "cancelled");
+}
+
+#[tokio::test]
+async fn test_readiness_report_is_stored_when_insert_returns_no_body() {
+    use video_conferencing_cell::models::ReadinessTestResults;
+    use video_conferencing_cell::services::TelemedicineReadinessService;
+
+    let mock_server = MockServer::start().await;
+    let mut config = create_test_config();
+    config.supabase_url = mock_server.uri();
+
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/telemedicine_readiness_reports"))
+        .and(wiremock::matchers::header("Prefer", "return=representation"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let patient_user = TestUser::patient("patient@example.com");
+    let token = JwtTestUtils::create_test_token(&patient_user, &config.supabase_jwt_secret, Some(24));
+    let results = ReadinessTestResults {
+        test_session_id: Some("self-test".to_string()),
+        camera_available: true,
+        microphone_available: true,
+        bandwidth_kbps: Some(2500),
+        latency_ms: Some(80),
+        user_agent: None,
+    };
+
+    let report = TelemedicineReadinessService::new(&config)
+        .unwrap()
+        .record_results(results, &patient_user.to_user(), &token)
+        .await
+        .expect("an empty 201 still stores the report");
+    assert!(report.is_ready);
+}